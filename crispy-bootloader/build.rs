@@ -11,7 +11,11 @@ fn main() {
 
     let linker_script = fs::read_to_string(linker_dir.join("bootloader_rp2040.x"))
         .expect("Failed to read bootloader_rp2040.x");
-    fs::write(out_dir.join("memory.x"), linker_script).expect("Failed to write memory.x");
+    fs::write(
+        out_dir.join("memory.x"),
+        sized_linker_script(&linker_script),
+    )
+    .expect("Failed to write memory.x");
     println!("cargo:rustc-link-search={}", out_dir.display());
     println!("cargo:rustc-link-arg=-Tlink.x");
     println!("cargo:rustc-link-arg=-Tdefmt.x");
@@ -30,6 +34,483 @@ fn main() {
         .expect("Failed to read VERSION file")
         .trim()
         .to_string();
+    if let Err(e) = crispy_common::protocol::try_parse_semver(&version) {
+        panic!(
+            "Invalid VERSION file {}: {:?} ({})",
+            version_file.display(),
+            version,
+            e
+        );
+    }
     println!("cargo:rustc-env=CRISPY_VERSION={}", version);
     println!("cargo:rerun-if-changed={}", version_file.display());
+
+    write_build_info();
+    write_min_stack_headroom();
+    write_tx_poll_budget();
+    write_trial_watchdog_ms();
+    write_warm_boot();
+    write_boot2(&out_dir);
+    write_board_config(&out_dir);
+}
+
+/// Export `CRISPY_MIN_STACK_HEADROOM`: bytes a RAM image's initial SP must
+/// lie above the copied image (default 1024, `0` only rejects SPs inside it).
+fn write_min_stack_headroom() {
+    println!("cargo:rerun-if-env-changed=CRISPY_MIN_STACK_HEADROOM");
+    let headroom = env::var("CRISPY_MIN_STACK_HEADROOM").unwrap_or_else(|_| "1024".to_string());
+    let headroom: u32 = headroom.trim().parse().unwrap_or_else(|_| {
+        panic!("CRISPY_MIN_STACK_HEADROOM must be a byte count, got {headroom:?}")
+    });
+    println!("cargo:rustc-env=CRISPY_MIN_STACK_HEADROOM={}", headroom);
+}
+
+/// Export `CRISPY_TX_POLL_BUDGET`: USB polls a response may wait for TX
+/// space without progress before it is abandoned (default 10000).
+fn write_tx_poll_budget() {
+    println!("cargo:rerun-if-env-changed=CRISPY_TX_POLL_BUDGET");
+    let budget = env::var("CRISPY_TX_POLL_BUDGET").unwrap_or_else(|_| "10000".to_string());
+    let budget: u32 = budget
+        .trim()
+        .parse()
+        .ok()
+        .filter(|&polls| polls > 0)
+        .unwrap_or_else(|| {
+            panic!("CRISPY_TX_POLL_BUDGET must be a non-zero poll count, got {budget:?}")
+        });
+    println!("cargo:rustc-env=CRISPY_TX_POLL_BUDGET={}", budget);
+}
+
+/// Export `CRISPY_TRIAL_WATCHDOG_MS`: watchdog timeout armed before jumping
+/// to an unconfirmed image (default 8000, at most 8388; `0` disables).
+fn write_trial_watchdog_ms() {
+    println!("cargo:rerun-if-env-changed=CRISPY_TRIAL_WATCHDOG_MS");
+    let timeout = env::var("CRISPY_TRIAL_WATCHDOG_MS").unwrap_or_else(|_| "8000".to_string());
+    let timeout: u32 = timeout
+        .trim()
+        .parse()
+        .ok()
+        .filter(|&ms| ms <= 8388)
+        .unwrap_or_else(|| {
+            panic!("CRISPY_TRIAL_WATCHDOG_MS must be 0-8388 milliseconds, got {timeout:?}")
+        });
+    println!("cargo:rustc-env=CRISPY_TRIAL_WATCHDOG_MS={}", timeout);
+}
+
+/// Export `CRISPY_WARM_BOOT`: skip the RAM copy when a reset kept the image
+/// loaded by the previous boot (default off).
+fn write_warm_boot() {
+    println!("cargo:rerun-if-env-changed=CRISPY_WARM_BOOT");
+    let warm_boot = match env::var("CRISPY_WARM_BOOT").as_deref() {
+        Err(_) | Ok("0") | Ok("false") => false,
+        Ok("1") | Ok("true") => true,
+        Ok(other) => panic!("CRISPY_WARM_BOOT must be 0/1 or false/true, got {other:?}"),
+    };
+    println!("cargo:rustc-env=CRISPY_WARM_BOOT={}", u8::from(warm_boot));
+}
+
+/// `flash-*` features and the flash size, in MB, each one selects.
+const FLASH_SIZE_VARIANTS: [(&str, u32); 3] = [("4M", 4), ("8M", 8), ("16M", 16)];
+
+/// The linker script with `__flash_size` and `__fw_bank_size` set for the
+/// selected `flash-*` feature, from `crispy_common::flash_layout`. Without one
+/// the script is used as is: it is written for 2 MB.
+fn sized_linker_script(script: &str) -> String {
+    let selected: Vec<_> = FLASH_SIZE_VARIANTS
+        .iter()
+        .filter(|(size, _)| env::var_os(format!("CARGO_FEATURE_FLASH_{size}")).is_some())
+        .collect();
+    let megabytes = match selected.as_slice() {
+        [] => return script.to_string(),
+        [(_, megabytes)] => *megabytes,
+        _ => panic!("Only one flash-* layout feature may be enabled"),
+    };
+    let layout = crispy_common::flash_layout::FlashLayout::for_flash_size(megabytes << 20)
+        .expect("flash-* feature without a layout");
+
+    let mut sized = String::with_capacity(script.len());
+    for line in script.lines() {
+        let assignment = |symbol: &str| {
+            line.strip_prefix(symbol)
+                .is_some_and(|rest| rest.trim_start().starts_with('='))
+        };
+        if assignment("__flash_size") {
+            sized.push_str(&format!("__flash_size       = {megabytes}M;"));
+        } else if assignment("__fw_bank_size") {
+            sized.push_str(&format!(
+                "__fw_bank_size     = 0x{:X}; /* flash-{megabytes}m layout */",
+                layout.bank_size
+            ));
+        } else {
+            sized.push_str(line);
+        }
+        sized.push('\n');
+    }
+    sized
+}
+
+/// `boot2-*` features and the `rp2040_boot2` blob each one selects.
+const BOOT2_VARIANTS: [(&str, &str); 5] = [
+    ("W25Q080", "BOOT_LOADER_W25Q080"),
+    ("W25X10CL", "BOOT_LOADER_W25X10CL"),
+    ("AT25SF128A", "BOOT_LOADER_AT25SF128A"),
+    ("GD25Q64CS", "BOOT_LOADER_GD25Q64CS"),
+    ("IS25LP080", "BOOT_LOADER_IS25LP080"),
+];
+
+/// Generate `boot2.rs` (included by `src/main.rs`) holding the `.boot2`
+/// section for the selected flash chip.
+///
+/// The `rp2040_boot2` blobs already carry the CRC the boot ROM checks, so they
+/// are placed verbatim. Without a chip-specific feature the generic 03h blob
+/// is used.
+fn write_boot2(out_dir: &std::path::Path) {
+    let selected: Vec<_> = BOOT2_VARIANTS
+        .iter()
+        .filter(|(chip, _)| env::var_os(format!("CARGO_FEATURE_BOOT2_{chip}")).is_some())
+        .collect();
+
+    let blob = match selected.as_slice() {
+        [] => "BOOT_LOADER_GENERIC_03H",
+        [(_, blob)] => blob,
+        _ => panic!(
+            "Only one chip-specific boot2 feature may be enabled, got: {}",
+            selected
+                .iter()
+                .map(|(chip, _)| format!("boot2-{}", chip.to_lowercase()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+
+    let source = format!(
+        "/// Second-stage bootloader for the board's flash chip (`boot2-*` features).\n\
+         #[unsafe(link_section = \".boot2\")]\n\
+         #[used]\n\
+         pub static BOOT2: [u8; 256] = rp2040_boot2::{blob};\n"
+    );
+    fs::write(out_dir.join("boot2.rs"), source).expect("Failed to write boot2.rs");
+}
+
+/// Export `CRISPY_GIT_HASH` and `CRISPY_BUILD_EPOCH` for `Response::BuildInfo`.
+///
+/// The hash falls back to `unknown` outside a git checkout. The epoch honours
+/// `SOURCE_DATE_EPOCH` so reproducible builds stay reproducible.
+fn write_build_info() {
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    let repo_root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap())
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    let git_dir = repo_root.join(".git");
+    if let Ok(head) = fs::read_to_string(git_dir.join("HEAD")) {
+        println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+        if let Some(branch_ref) = head.trim().strip_prefix("ref: ") {
+            println!(
+                "cargo:rerun-if-changed={}",
+                git_dir.join(branch_ref).display()
+            );
+        }
+    }
+
+    let git_hash = std::process::Command::new("git")
+        .args(["rev-parse", "--short=8", "HEAD"])
+        .current_dir(&repo_root)
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| String::from_utf8(out.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .filter(|hash| !hash.is_empty() && hash.len() <= 8)
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let build_epoch: u32 = match env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch
+            .trim()
+            .parse()
+            .unwrap_or_else(|_| panic!("SOURCE_DATE_EPOCH must be a u32, got {epoch:?}")),
+        Err(_) => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("system clock before 1970")
+            .as_secs()
+            .try_into()
+            .expect("build time does not fit in u32"),
+    };
+
+    println!("cargo:rustc-env=CRISPY_GIT_HASH={}", git_hash);
+    println!("cargo:rustc-env=CRISPY_BUILD_EPOCH={}", build_epoch);
+}
+
+/// GPIO used by the status LED; not available for other roles.
+const STATUS_LED_PIN: u8 = 25;
+
+/// Generate `board_config.rs` (included by `src/peripherals.rs`) from the
+/// `CRISPY_TRIGGER_*` and `CRISPY_HEALTH_LED_*` environment variables.
+fn write_board_config(out_dir: &std::path::Path) {
+    let trigger = trigger_config();
+    let health_led = health_led_config(trigger.map(|(pin, ..)| pin));
+    let vbus_sense =
+        vbus_sense_pin(&[trigger.map(|(pin, ..)| pin), health_led.map(|(pin, _)| pin)]);
+    let vsys_sense = vsys_sense_config(&[
+        trigger.map(|(pin, ..)| pin),
+        health_led.map(|(pin, _)| pin),
+        vbus_sense,
+    ]);
+    let skip_on_bad_power = power_guard_skips();
+    let uart = uart_config(&[
+        trigger.map(|(pin, ..)| pin),
+        health_led.map(|(pin, _)| pin),
+        vbus_sense,
+        vsys_sense.map(|(pin, _)| pin),
+    ]);
+
+    let trigger = match trigger {
+        Some((pin, active_high, pull, hold_ms, window_ms, fast_boot)) => format!(
+            "Some(TriggerConfig {{ pin: {pin}, active_high: {active_high}, pull: hal::gpio::DynPullType::{pull}, hold_ms: {hold_ms}, window_ms: {window_ms}, fast_boot: {fast_boot} }})"
+        ),
+        None => "None".to_string(),
+    };
+    let health_led = match health_led {
+        Some((pin, active_low)) => {
+            format!("Some(HealthLedConfig {{ pin: {pin}, active_low: {active_low} }})")
+        }
+        None => "None".to_string(),
+    };
+    let vbus_sense = match vbus_sense {
+        Some(pin) => format!("Some({pin})"),
+        None => "None".to_string(),
+    };
+    let vsys_sense = match vsys_sense {
+        Some((pin, divider)) => {
+            format!("Some(VsysSenseConfig {{ pin: {pin}, divider: {divider} }})")
+        }
+        None => "None".to_string(),
+    };
+
+    let uart = match uart {
+        Some((instance, tx, rx, baud)) => format!(
+            "/// UART carrying the update protocol (`uart-transport`, `CRISPY_UART_*`).\n\
+             pub type UartDevice = hal::pac::UART{instance};\n\
+             /// Pins and baud rate of the update UART.\n\
+             pub const UART: UpdateUartConfig = UpdateUartConfig {{ tx: {tx}, rx: {rx}, baud: {baud} }};\n"
+        ),
+        None => String::new(),
+    };
+
+    let config = format!(
+        "/// Update-mode trigger pin (`CRISPY_TRIGGER_*`).\n\
+         pub const TRIGGER: Option<TriggerConfig> = {trigger};\n\
+         /// Optional health LED (`CRISPY_HEALTH_LED_*`).\n\
+         pub const HEALTH_LED: Option<HealthLedConfig> = {health_led};\n\
+         /// GPIO reading high while VBUS is present (`CRISPY_VBUS_SENSE_PIN`).\n\
+         pub const VBUS_SENSE_PIN: Option<u8> = {vbus_sense};\n\
+         /// ADC input sensing VSYS through a divider (`CRISPY_VSYS_SENSE_*`).\n\
+         pub const VSYS_SENSE: Option<VsysSenseConfig> = {vsys_sense};\n\
+         /// Skip boot-path flash writes while the supply looks marginal (`CRISPY_POWER_GUARD`).\n\
+         pub const SKIP_WRITES_ON_BAD_POWER: bool = {skip_on_bad_power};\n\
+         {uart}"
+    );
+    fs::write(out_dir.join("board_config.rs"), config).expect("Failed to write board_config.rs");
+}
+
+/// Parse a GPIO number, rejecting pins the bootloader already drives.
+fn parse_gpio(var: &str, value: &str) -> u8 {
+    let pin: u8 = match value.trim().parse() {
+        Ok(pin) if pin <= 29 => pin,
+        _ => panic!("{var} must be a GPIO number 0-29, got {value:?}"),
+    };
+    if pin == STATUS_LED_PIN {
+        panic!("{var}={pin} is already used by the status LED");
+    }
+    pin
+}
+
+/// Update-mode trigger pin: `(gpio, active_high, pull, hold_ms, window_ms,
+/// fast_boot)`, or `None` when disabled.
+///
+/// - `CRISPY_TRIGGER_PIN`: GPIO number, or `none` to disable (default: `2`)
+/// - `CRISPY_TRIGGER_ACTIVE`: `low` or `high` (default: `low`)
+/// - `CRISPY_TRIGGER_PULL`: `up`, `down` or `none` (default: away from the
+///   active level, i.e. `up` for active-low)
+/// - `CRISPY_TRIGGER_HOLD_MS`: how long the pin must stay asserted, `0` for a
+///   single sample (default: `50`)
+/// - `CRISPY_TRIGGER_WINDOW_MS`: how long after reset the pin is watched
+///   before booting, `0` to decide at reset (default: `0`)
+/// - `CRISPY_FAST_BOOT`: `1`/`true` to skip the window when the active image
+///   is confirmed (default: `0`)
+fn trigger_config() -> Option<(u8, bool, &'static str, u32, u32, bool)> {
+    println!("cargo:rerun-if-env-changed=CRISPY_TRIGGER_PIN");
+    println!("cargo:rerun-if-env-changed=CRISPY_TRIGGER_ACTIVE");
+    println!("cargo:rerun-if-env-changed=CRISPY_TRIGGER_PULL");
+    println!("cargo:rerun-if-env-changed=CRISPY_TRIGGER_HOLD_MS");
+    println!("cargo:rerun-if-env-changed=CRISPY_TRIGGER_WINDOW_MS");
+    println!("cargo:rerun-if-env-changed=CRISPY_FAST_BOOT");
+
+    let pin = env::var("CRISPY_TRIGGER_PIN").unwrap_or_else(|_| "2".to_string());
+    if pin.trim().eq_ignore_ascii_case("none") {
+        return None;
+    }
+    let pin = parse_gpio("CRISPY_TRIGGER_PIN", &pin);
+
+    let active_high = match env::var("CRISPY_TRIGGER_ACTIVE").as_deref() {
+        Err(_) | Ok("low") => false,
+        Ok("high") => true,
+        Ok(other) => panic!("CRISPY_TRIGGER_ACTIVE must be low or high, got {other:?}"),
+    };
+
+    let pull = match env::var("CRISPY_TRIGGER_PULL").as_deref() {
+        Err(_) if active_high => "Down",
+        Err(_) => "Up",
+        Ok("up") => "Up",
+        Ok("down") => "Down",
+        Ok("none") => "None",
+        Ok(other) => panic!("CRISPY_TRIGGER_PULL must be up, down or none, got {other:?}"),
+    };
+
+    let hold_ms = env::var("CRISPY_TRIGGER_HOLD_MS").unwrap_or_else(|_| "50".to_string());
+    let hold_ms: u32 = match hold_ms.trim().parse() {
+        Ok(ms) if ms <= 10_000 => ms,
+        _ => panic!("CRISPY_TRIGGER_HOLD_MS must be 0-10000 milliseconds, got {hold_ms:?}"),
+    };
+
+    let window_ms = env::var("CRISPY_TRIGGER_WINDOW_MS").unwrap_or_else(|_| "0".to_string());
+    let window_ms: u32 = match window_ms.trim().parse() {
+        Ok(ms) if ms <= 10_000 => ms,
+        _ => panic!("CRISPY_TRIGGER_WINDOW_MS must be 0-10000 milliseconds, got {window_ms:?}"),
+    };
+
+    let fast_boot = match env::var("CRISPY_FAST_BOOT").as_deref() {
+        Err(_) | Ok("0") | Ok("false") => false,
+        Ok("1") | Ok("true") => true,
+        Ok(other) => panic!("CRISPY_FAST_BOOT must be 0/1 or false/true, got {other:?}"),
+    };
+
+    Some((pin, active_high, pull, hold_ms, window_ms, fast_boot))
+}
+
+/// Optional "firmware healthy" LED: `(gpio, active_low)`.
+///
+/// `CRISPY_HEALTH_LED_PIN` selects the GPIO (unset = no health LED) and
+/// `CRISPY_HEALTH_LED_ACTIVE_LOW=1` inverts its polarity.
+fn health_led_config(trigger_pin: Option<u8>) -> Option<(u8, bool)> {
+    println!("cargo:rerun-if-env-changed=CRISPY_HEALTH_LED_PIN");
+    println!("cargo:rerun-if-env-changed=CRISPY_HEALTH_LED_ACTIVE_LOW");
+
+    let pin = env::var("CRISPY_HEALTH_LED_PIN").ok()?;
+    let pin = parse_gpio("CRISPY_HEALTH_LED_PIN", &pin);
+    if Some(pin) == trigger_pin {
+        panic!("CRISPY_HEALTH_LED_PIN={pin} is already used by the update-mode trigger");
+    }
+
+    let active_low = match env::var("CRISPY_HEALTH_LED_ACTIVE_LOW").as_deref() {
+        Err(_) | Ok("0") | Ok("false") => false,
+        Ok("1") | Ok("true") => true,
+        Ok(other) => {
+            panic!("CRISPY_HEALTH_LED_ACTIVE_LOW must be 0/1 or false/true, got {other:?}")
+        }
+    };
+
+    Some((pin, active_low))
+}
+
+/// Optional VBUS sense input, e.g. GPIO24 on the Pico (`CRISPY_VBUS_SENSE_PIN`,
+/// unset = not checked). Only set it on boards powered from USB.
+fn vbus_sense_pin(used: &[Option<u8>]) -> Option<u8> {
+    println!("cargo:rerun-if-env-changed=CRISPY_VBUS_SENSE_PIN");
+
+    let pin = env::var("CRISPY_VBUS_SENSE_PIN").ok()?;
+    let pin = parse_gpio("CRISPY_VBUS_SENSE_PIN", &pin);
+    if used.contains(&Some(pin)) {
+        panic!("CRISPY_VBUS_SENSE_PIN={pin} is already used by the trigger or health LED");
+    }
+    Some(pin)
+}
+
+/// Optional VSYS sense input for `GetTelemetry`: `(gpio, divider)`.
+///
+/// `CRISPY_VSYS_SENSE_PIN` selects an ADC-capable GPIO (`26`-`29`, unset = VSYS
+/// not reported) and `CRISPY_VSYS_SENSE_DIVIDER` the ratio of the resistor
+/// divider in front of it (default: `3`, as on the Pico's GPIO29).
+fn vsys_sense_config(used: &[Option<u8>]) -> Option<(u8, u32)> {
+    println!("cargo:rerun-if-env-changed=CRISPY_VSYS_SENSE_PIN");
+    println!("cargo:rerun-if-env-changed=CRISPY_VSYS_SENSE_DIVIDER");
+
+    let pin = env::var("CRISPY_VSYS_SENSE_PIN").ok()?;
+    let pin = parse_gpio("CRISPY_VSYS_SENSE_PIN", &pin);
+    if !(26..=29).contains(&pin) {
+        panic!("CRISPY_VSYS_SENSE_PIN must be an ADC input (26-29), got {pin}");
+    }
+    if used.contains(&Some(pin)) {
+        panic!("CRISPY_VSYS_SENSE_PIN={pin} is already used by another board pin");
+    }
+
+    let divider = env::var("CRISPY_VSYS_SENSE_DIVIDER").unwrap_or_else(|_| "3".to_string());
+    let divider: u32 = match divider.trim().parse() {
+        Ok(ratio) if (1..=100).contains(&ratio) => ratio,
+        _ => panic!("CRISPY_VSYS_SENSE_DIVIDER must be 1-100, got {divider:?}"),
+    };
+
+    Some((pin, divider))
+}
+
+/// TX and RX GPIOs of UART0 and UART1, as `(instance, tx, rx)`.
+const UART_PINS: [(u8, [u8; 4], [u8; 4]); 2] = [
+    (0, [0, 12, 16, 28], [1, 13, 17, 29]),
+    (1, [4, 8, 20, 24], [5, 9, 21, 25]),
+];
+
+/// Update UART for the `uart-transport` feature: `(instance, tx, rx, baud)`,
+/// or `None` when the feature is off.
+///
+/// - `CRISPY_UART_TX_PIN` / `CRISPY_UART_RX_PIN`: GPIOs of the same UART
+///   instance (default: `0` and `1`, UART0)
+/// - `CRISPY_UART_BAUD`: baud rate, 8N1 (default: `115200`)
+fn uart_config(used: &[Option<u8>]) -> Option<(u8, u8, u8, u32)> {
+    println!("cargo:rerun-if-env-changed=CRISPY_UART_TX_PIN");
+    println!("cargo:rerun-if-env-changed=CRISPY_UART_RX_PIN");
+    println!("cargo:rerun-if-env-changed=CRISPY_UART_BAUD");
+
+    env::var_os("CARGO_FEATURE_UART_TRANSPORT")?;
+
+    let tx = env::var("CRISPY_UART_TX_PIN").unwrap_or_else(|_| "0".to_string());
+    let tx = parse_gpio("CRISPY_UART_TX_PIN", &tx);
+    let rx = env::var("CRISPY_UART_RX_PIN").unwrap_or_else(|_| "1".to_string());
+    let rx = parse_gpio("CRISPY_UART_RX_PIN", &rx);
+    for (var, pin) in [("CRISPY_UART_TX_PIN", tx), ("CRISPY_UART_RX_PIN", rx)] {
+        if used.contains(&Some(pin)) {
+            panic!("{var}={pin} is already used by another board pin");
+        }
+    }
+
+    let instance = UART_PINS
+        .iter()
+        .find(|(_, txs, rxs)| txs.contains(&tx) && rxs.contains(&rx))
+        .map(|(instance, ..)| *instance)
+        .unwrap_or_else(|| {
+            panic!(
+                "CRISPY_UART_TX_PIN={tx} and CRISPY_UART_RX_PIN={rx} are not TX and RX of \
+                 the same UART (UART0: TX 0/12/16/28, RX 1/13/17/29; \
+                 UART1: TX 4/8/20/24, RX 5/9/21/25)"
+            )
+        });
+
+    let baud = env::var("CRISPY_UART_BAUD").unwrap_or_else(|_| "115200".to_string());
+    let baud: u32 = match baud.trim().parse() {
+        Ok(rate) if (1200..=921_600).contains(&rate) => rate,
+        _ => panic!("CRISPY_UART_BAUD must be 1200-921600, got {baud:?}"),
+    };
+
+    Some((instance, tx, rx, baud))
+}
+
+/// `CRISPY_POWER_GUARD`: `skip` (default) leaves boot-path flash writes out
+/// while the supply looks marginal, `proceed` only logs it.
+fn power_guard_skips() -> bool {
+    println!("cargo:rerun-if-env-changed=CRISPY_POWER_GUARD");
+
+    match env::var("CRISPY_POWER_GUARD").as_deref() {
+        Err(_) | Ok("skip") => true,
+        Ok("proceed") => false,
+        Ok(other) => panic!("CRISPY_POWER_GUARD must be skip or proceed, got {other:?}"),
+    }
 }
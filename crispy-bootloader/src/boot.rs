@@ -3,19 +3,50 @@
 
 //! Boot management: memory layout, firmware validation, bank selection, and jump.
 
-use crate::flash;
-use crispy_common::protocol::{BootData, RAM_UPDATE_FLAG_ADDR, RAM_UPDATE_MAGIC};
-
-const MAX_BOOT_ATTEMPTS: u8 = 3;
+use crate::{error_log, flash};
+use core::cell::UnsafeCell;
+use crispy_common::boot_selection::{select_boot_action, BootAction, UpdateReason};
+use crispy_common::crc32::crc32;
+use crispy_common::image_header::{self, IMAGE_HEADER_SIZE};
+#[cfg(feature = "golden-bank")]
+use crispy_common::protocol::GOLDEN_BANK;
+use crispy_common::protocol::{
+    parse_build_semver, BootData, BootInfo, BootTimings, ErrorCode, BOOT_DATA_ADDR, BOOT_INFO_ADDR,
+    DATA_ADDR, DATA_SIZE, FLASH_SIZE, FW_A_ADDR, FW_BANK_SIZE, FW_B_ADDR, FW_GOLD_ADDR,
+    FW_GOLD_SIZE, RAM_UPDATE_FLAG_ADDR, RAM_UPDATE_MAGIC, WARM_BOOT_MARKER_ADDR,
+    WATCHDOG_SCRATCH0_ADDR, WATCHDOG_UPDATE_MAGIC,
+};
+use crispy_common::reset_reason::ResetReason;
+use crispy_common::update::ImageLimits;
+use crispy_common::vector_table::{ExecMode, ImageRegions, VectorTable, VectorTableError};
+use crispy_common::warm_boot::{self, WarmBootMarker};
+
+/// Packed bootloader version; a malformed `VERSION` file fails the build.
+pub const BOOTLOADER_VERSION: u32 = parse_build_semver(env!("CRISPY_VERSION"));
 
 unsafe extern "C" {
+    static __flash_base: u32;
+    static __flash_size: u32;
+    static __boot2_size: u32;
+    static __bootloader_size: u32;
+    static __boot_data_size: u32;
     static __fw_a_entry: u32;
     static __fw_b_entry: u32;
+    static __fw_bank_size: u32;
     static __fw_ram_base: u32;
     static __fw_copy_size: u32;
     static __boot_data_addr: u32;
-    static __fw_ram_start: u32;
     static __fw_ram_end: u32;
+    static __fw_gold_addr: u32;
+    static __fw_gold_size: u32;
+    static __data_addr: u32;
+    static __data_size: u32;
+    static __bootloader_ram: u32;
+    static __bootloader_ram_size: u32;
+    // cortex-m-rt: `.data` and its initializers, the last section in flash.
+    static __sdata: u32;
+    static __edata: u32;
+    static __sidata: u32;
 }
 
 macro_rules! linker_addr {
@@ -24,154 +55,381 @@ macro_rules! linker_addr {
     };
 }
 
+/// Firmware memory geometry, taken from the linker script symbols.
+///
+/// This is the single source of truth for bank addresses and the firmware
+/// RAM region (also used as the upload buffer).
 pub struct MemoryLayout {
+    pub flash_base: u32,
+    pub flash_size: u32,
+    /// boot2 and the bootloader share the region starting at `flash_base`.
+    pub boot2_size: u32,
+    pub bootloader_size: u32,
+    /// Size of the BootData sector, and of the wear stats sector after it.
+    pub boot_data_size: u32,
     pub fw_a: u32,
     pub fw_b: u32,
+    pub bank_size: u32,
     pub ram_base: u32,
     pub copy_size: u32,
+    pub ram_end: u32,
+    pub boot_data: u32,
+    /// Golden recovery bank; only used with the `golden-bank` feature.
+    pub fw_gold: u32,
+    pub gold_size: u32,
+    /// Data partition, in the golden bank's range above its largest image.
+    pub data: u32,
+    pub data_size: u32,
+    /// The bootloader's own `.data`, `.bss` and stack.
+    pub bootloader_ram: u32,
+    pub bootloader_ram_size: u32,
 }
 
 impl MemoryLayout {
     pub fn from_linker() -> Self {
         Self {
+            flash_base: linker_addr!(__flash_base),
+            flash_size: linker_addr!(__flash_size),
+            boot2_size: linker_addr!(__boot2_size),
+            bootloader_size: linker_addr!(__bootloader_size),
+            boot_data_size: linker_addr!(__boot_data_size),
             fw_a: linker_addr!(__fw_a_entry),
             fw_b: linker_addr!(__fw_b_entry),
+            bank_size: linker_addr!(__fw_bank_size),
             ram_base: linker_addr!(__fw_ram_base),
             copy_size: linker_addr!(__fw_copy_size),
+            ram_end: linker_addr!(__fw_ram_end),
+            boot_data: linker_addr!(__boot_data_addr),
+            fw_gold: linker_addr!(__fw_gold_addr),
+            gold_size: linker_addr!(__fw_gold_size),
+            data: linker_addr!(__data_addr),
+            data_size: linker_addr!(__data_size),
+            bootloader_ram: linker_addr!(__bootloader_ram),
+            bootloader_ram_size: linker_addr!(__bootloader_ram_size),
         }
     }
-}
 
-struct VectorTable {
-    initial_sp: u32,
-    reset_vector: u32,
-}
+    /// Check the linker script against the `protocol` constants that the
+    /// update code, the firmware and the host tools use.
+    ///
+    /// Logs every mismatching value; the two are maintained by hand and a
+    /// drift would write or boot images at the wrong address. The flash size
+    /// is also checked against the part's detected capacity, so a layout
+    /// built for a larger part does not boot from a smaller one; a layout for
+    /// a smaller part only logs a warning.
+    pub fn matches_protocol(&self) -> bool {
+        let pairs = [
+            ("__flash_size", self.flash_size, FLASH_SIZE),
+            ("__fw_a_entry", self.fw_a, FW_A_ADDR),
+            ("__fw_b_entry", self.fw_b, FW_B_ADDR),
+            ("__fw_bank_size", self.bank_size, FW_BANK_SIZE),
+            ("__boot_data_addr", self.boot_data, BOOT_DATA_ADDR),
+            ("__fw_gold_addr", self.fw_gold, FW_GOLD_ADDR),
+            ("__fw_gold_size", self.gold_size, FW_GOLD_SIZE),
+            ("__data_addr", self.data, DATA_ADDR),
+            ("__data_size", self.data_size, DATA_SIZE),
+        ];
+        let mut ok = true;
+        for (symbol, linker, protocol) in pairs {
+            if linker != protocol {
+                defmt::error!(
+                    "Layout mismatch: {} is 0x{:08x}, protocol expects 0x{:08x}",
+                    symbol,
+                    linker,
+                    protocol
+                );
+                ok = false;
+            }
+        }
+        let capacity = flash::capacity();
+        if self.flash_size > capacity {
+            error_log::record(ErrorCode::FlashTooSmall);
+            defmt::error!(
+                "Layout mismatch: __flash_size is {} bytes, the flash part has {}",
+                self.flash_size,
+                capacity
+            );
+            ok = false;
+        } else if self.flash_size < capacity {
+            // Boots fine, but the rest of the part is never used.
+            defmt::warn!(
+                "Layout is for {} bytes of flash, the flash part has {}: build with its flash-* feature",
+                self.flash_size,
+                capacity
+            );
+        }
+        ok
+    }
 
-impl VectorTable {
-    unsafe fn read_from(addr: u32) -> Self {
-        Self {
-            initial_sp: (addr as *const u32).read_volatile(),
-            reset_vector: (addr as *const u32).offset(1).read_volatile(),
+    /// End address of the bootloader image in flash (after the `.data`
+    /// initializers).
+    pub fn bootloader_image_end(&self) -> u32 {
+        linker_addr!(__sidata) + (linker_addr!(__edata) - linker_addr!(__sdata))
+    }
+
+    /// Size of the bank starting at `bank_addr`.
+    pub fn bank_size_at(&self, bank_addr: u32) -> u32 {
+        if bank_addr == self.fw_gold {
+            self.gold_size
+        } else {
+            self.bank_size
         }
     }
 
-    fn is_valid_for_ram_execution(&self) -> bool {
-        is_in_ram(self.initial_sp) && is_in_ram(self.reset_vector)
+    /// Regions the vector table of the bank at `bank_addr` is checked against.
+    pub fn image_regions(&self, bank_addr: u32, config: &BootConfig) -> ImageRegions {
+        self.image_limits(config)
+            .regions(bank_addr, self.bank_size_at(bank_addr))
+    }
+
+    /// What uploads are checked against before their bank is overwritten:
+    /// the regions of `image_regions` for any bank, and this bootloader's
+    /// version.
+    pub fn image_limits(&self, config: &BootConfig) -> ImageLimits {
+        ImageLimits {
+            bootloader_version: BOOTLOADER_VERSION,
+            ram_base: self.ram_base,
+            copy_size: self.copy_size,
+            ram_end: self.ram_end,
+            min_stack_headroom: config.min_stack_headroom,
+        }
     }
 }
 
-fn is_in_ram(addr: u32) -> bool {
-    let start = linker_addr!(__fw_ram_start);
-    let end = linker_addr!(__fw_ram_end);
-    (start..=end).contains(&addr)
+/// Read the initial SP and reset vector at `addr`.
+///
+/// # Safety
+/// `addr` must be a readable, word-aligned address.
+unsafe fn read_vector_table(addr: u32) -> VectorTable {
+    VectorTable {
+        initial_sp: (addr as *const u32).read_volatile(),
+        reset_vector: (addr as *const u32).offset(1).read_volatile(),
+    }
 }
 
-/// Check if update mode is requested via GP2 pin (LOW) or RAM magic flag.
-pub fn check_update_trigger(gp2_is_low: bool) -> bool {
-    let ram_flag = unsafe { (RAM_UPDATE_FLAG_ADDR as *const u32).read_volatile() };
+/// Check if update mode is requested via the trigger pin, RAM magic flag or
+/// watchdog scratch register.
+///
+/// Both flags are cleared before returning, so a crash in update mode does
+/// not bring the device back into update mode forever. After a power-on reset
+/// RAM holds garbage, so the RAM flag is ignored.
+pub fn check_update_trigger(pin_active: bool, reset_reason: ResetReason) -> bool {
+    let ram_flag = match reset_reason {
+        ResetReason::PowerOn => 0,
+        _ => unsafe { (RAM_UPDATE_FLAG_ADDR as *const u32).read_volatile() },
+    };
+    let scratch_flag = unsafe { (WATCHDOG_SCRATCH0_ADDR as *const u32).read_volatile() };
     unsafe {
         (RAM_UPDATE_FLAG_ADDR as *mut u32).write_volatile(0);
+        if scratch_flag == WATCHDOG_UPDATE_MAGIC {
+            (WATCHDOG_SCRATCH0_ADDR as *mut u32).write_volatile(0);
+        }
     }
-    gp2_is_low || ram_flag == RAM_UPDATE_MAGIC
-}
 
-/// Validate a firmware bank with full CRC check.
-/// Returns false if size == 0 (no firmware metadata).
-pub fn validate_bank_with_crc(addr: u32, crc: u32, size: u32) -> bool {
-    if size == 0 {
-        return false;
+    if ram_flag == RAM_UPDATE_MAGIC {
+        defmt::println!("Update mode requested via RAM flag");
     }
-
-    let vt = unsafe { VectorTable::read_from(addr) };
-    if !vt.is_valid_for_ram_execution() {
-        return false;
+    if scratch_flag == WATCHDOG_UPDATE_MAGIC {
+        defmt::println!("Update mode requested via watchdog scratch register");
     }
 
-    let actual_crc = flash::compute_crc32(addr, size);
-    if actual_crc != crc {
-        defmt::println!(
-            "CRC mismatch at 0x{:08x}: expected 0x{:08x}, got 0x{:08x}",
-            addr,
-            crc,
-            actual_crc
-        );
-        return false;
-    }
+    pin_active || ram_flag == RAM_UPDATE_MAGIC || scratch_flag == WATCHDOG_UPDATE_MAGIC
+}
 
-    true
+/// Boot latency milestones, handed to firmware in `BootInfo::timings`.
+#[derive(Clone, Copy)]
+pub enum Milestone {
+    PeripheralsReady,
+    TriggerDecided,
+    ImageChecked,
+    Jump,
 }
 
-/// Simple vector table validation without CRC (fallback mode).
-pub fn validate_bank(flash_addr: u32) -> Option<(u32, u32)> {
-    let vt = unsafe { VectorTable::read_from(flash_addr) };
-    if vt.is_valid_for_ram_execution() {
-        Some((vt.initial_sp, vt.reset_vector))
-    } else {
-        None
+/// Wrapper to hold the milestones in a static without `static mut`.
+///
+/// SAFETY: single-threaded bootloader; no interrupt handler records milestones.
+struct SyncTimings(UnsafeCell<BootTimings>);
+unsafe impl Sync for SyncTimings {}
+
+static BOOT_TIMINGS: SyncTimings = SyncTimings(UnsafeCell::new(BootTimings {
+    peripherals_ready: 0,
+    trigger_decided: 0,
+    image_checked: 0,
+    jump: 0,
+}));
+
+/// Low 32 bits of the microsecond timer (one register read).
+fn timer_now() -> u32 {
+    // SAFETY: TIMERAWL is a read-only counter register
+    unsafe { (*rp2040_hal::pac::TIMER::ptr()).timerawl().read().bits() }
+}
+
+/// Record `milestone` at the current timer value.
+pub fn record_milestone(milestone: Milestone) {
+    let now = timer_now();
+    // SAFETY: see SyncTimings
+    let timings = unsafe { &mut *BOOT_TIMINGS.0.get() };
+    match milestone {
+        Milestone::PeripheralsReady => timings.peripherals_ready = now,
+        Milestone::TriggerDecided => timings.trigger_decided = now,
+        Milestone::ImageChecked => timings.image_checked = now,
+        Milestone::Jump => timings.jump = now,
     }
 }
 
-/// Select which bank to boot from, with automatic rollback on failure.
-pub fn select_boot_bank(bd: &BootData, layout: &MemoryLayout) -> (u32, BootData) {
-    let mut bd = *bd;
+/// Build-time boot policy.
+pub struct BootConfig {
+    /// CRC the whole bank before jumping when BootData records its size/CRC.
+    /// Costs tens of milliseconds; disable with the `skip-boot-crc` feature.
+    pub verify_crc: bool,
+    /// Bytes a RAM image's initial SP must lie above the copied image
+    /// (`CRISPY_MIN_STACK_HEADROOM`). Lower it for apps with a deliberately
+    /// low stack.
+    pub min_stack_headroom: u32,
+    /// Watchdog timeout armed before jumping to an unconfirmed image
+    /// (`CRISPY_TRIAL_WATCHDOG_MS`); `0` leaves the watchdog off.
+    pub trial_watchdog_ms: u32,
+    /// Boot a RAM image already loaded by the previous boot without copying
+    /// it again (`CRISPY_WARM_BOOT`).
+    pub warm_boot: bool,
+}
 
-    if bd.boot_attempts >= MAX_BOOT_ATTEMPTS && bd.confirmed == 0 {
-        defmt::println!(
-            "Boot attempts exhausted ({}), rolling back",
-            bd.boot_attempts
-        );
-        bd.active_bank = toggle_bank(bd.active_bank);
-        bd.boot_attempts = 0;
-        bd.confirmed = 0;
+impl BootConfig {
+    pub const fn from_features() -> Self {
+        Self {
+            verify_crc: !cfg!(feature = "skip-boot-crc"),
+            min_stack_headroom: match u32::from_str_radix(env!("CRISPY_MIN_STACK_HEADROOM"), 10) {
+                Ok(headroom) => headroom,
+                Err(_) => panic!("CRISPY_MIN_STACK_HEADROOM is not a u32"),
+            },
+            trial_watchdog_ms: match u32::from_str_radix(env!("CRISPY_TRIAL_WATCHDOG_MS"), 10) {
+                Ok(timeout) => timeout,
+                Err(_) => panic!("CRISPY_TRIAL_WATCHDOG_MS is not a u32"),
+            },
+            warm_boot: matches!(env!("CRISPY_WARM_BOOT").as_bytes(), b"1"),
+        }
     }
+}
 
-    let (primary_addr, fallback_addr) = bank_addresses(&bd, layout);
-    let (primary_crc, primary_size) = bank_metadata(&bd, bd.active_bank);
-    let (fallback_crc, fallback_size) = bank_metadata(&bd, toggle_bank(bd.active_bank));
+/// A bootable image located in a bank.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct BootImage {
+    pub mode: ExecMode,
+    /// Flash address of the vector table: the bank start, or past the image
+    /// header when there is one.
+    pub vector_table: u32,
+}
 
-    if validate_bank_with_crc(primary_addr, primary_crc, primary_size) {
-        bd.boot_attempts += 1;
-        return (primary_addr, bd);
+impl BootImage {
+    /// Bytes from the vector table to the end of an image of `size` bytes
+    /// at `bank_addr`; 0 when `size` is unknown (0).
+    pub fn len_from_vector_table(&self, bank_addr: u32, size: u32) -> u32 {
+        size.saturating_sub(self.vector_table - bank_addr)
     }
+}
 
-    defmt::println!("Primary bank invalid, trying fallback");
+/// Image header (including its bootloader version requirement) and vector
+/// table validation without CRC.
+///
+/// `size` is the recorded image size, 0 if unknown; a RAM image larger than
+/// the copy window is rejected. Returns where the image's vector table is and
+/// how it must be executed, or which check rejected it.
+pub fn validate_bank(
+    flash_addr: u32,
+    size: u32,
+    layout: &MemoryLayout,
+    config: &BootConfig,
+) -> Result<BootImage, VectorTableError> {
+    let first_bytes = unsafe { (flash_addr as *const [u8; IMAGE_HEADER_SIZE]).read_volatile() };
+    let header = image_header::check_header(
+        &first_bytes,
+        layout.bank_size_at(flash_addr),
+        BOOTLOADER_VERSION,
+    )?;
+    let vector_table = flash_addr + header.map_or(0, |header| header.vector_table_offset);
+
+    let vt = unsafe { read_vector_table(vector_table) };
+    let regions = layout.image_regions(flash_addr, config);
+    let mode = vt.validate(&regions)?;
+    let image = BootImage { mode, vector_table };
+    regions.check_image_len(mode, image.len_from_vector_table(flash_addr, size))?;
+    Ok(image)
+}
 
-    if validate_bank_with_crc(fallback_addr, fallback_crc, fallback_size) {
-        bd.active_bank = toggle_bank(bd.active_bank);
-        bd.boot_attempts = 1;
-        bd.confirmed = 0;
-        return (fallback_addr, bd);
+/// Check that a bank can be booted.
+///
+/// The vector table must describe a RAM or XIP image. When BootData records a size for the
+/// bank (and `config.verify_crc` is set), the CRC over that size must match too;
+/// banks without metadata only get the vector table check.
+fn bank_is_bootable(bd: &BootData, bank: u8, layout: &MemoryLayout, config: &BootConfig) -> bool {
+    let addr = if bank == 0 { layout.fw_a } else { layout.fw_b };
+    let (crc, size) = bank_metadata(bd, bank);
+
+    if let Err(reason) = validate_bank(addr, size, layout, config) {
+        let first_bytes = unsafe { (addr as *const [u8; IMAGE_HEADER_SIZE]).read_volatile() };
+        let offset = image_header::vector_table_offset(&first_bytes, layout.bank_size).unwrap_or(0);
+        let vt = unsafe { read_vector_table(addr + offset) };
+        defmt::error!(
+            "Bank {}: image check failed: {} (SP 0x{:08x}, reset 0x{:08x})",
+            bank,
+            reason,
+            vt.initial_sp,
+            vt.reset_vector
+        );
+        return false;
     }
 
-    if validate_bank(primary_addr).is_some() {
-        bd.boot_attempts += 1;
-        return (primary_addr, bd);
+    if size == 0 || !config.verify_crc {
+        return true;
     }
 
-    if validate_bank(fallback_addr).is_some() {
-        bd.active_bank = toggle_bank(bd.active_bank);
-        bd.boot_attempts = 1;
-        return (fallback_addr, bd);
+    let actual_crc = flash::compute_crc32(addr, size);
+    if actual_crc != crc {
+        defmt::println!(
+            "Bank {}: CRC check failed at 0x{:08x}: expected 0x{:08x}, got 0x{:08x}",
+            bank,
+            addr,
+            crc,
+            actual_crc
+        );
+        return false;
     }
 
-    bd.boot_attempts += 1;
-    (primary_addr, bd)
+    true
 }
 
-fn toggle_bank(bank: u8) -> u8 {
-    if bank == 0 {
-        1
-    } else {
-        0
+/// Last resort when neither A nor B can be booted: the golden image, if one
+/// was provisioned and it passes the same checks as a regular bank.
+///
+/// The golden image is never counted or rolled back, and BootData is left
+/// as is so a later upload can repair A or B.
+#[cfg(feature = "golden-bank")]
+fn select_golden_bank(bd: &BootData, layout: &MemoryLayout, config: &BootConfig) -> Option<u32> {
+    if !bd.golden_populated() {
+        return None;
     }
-}
+    let Some(info) = flash::read_golden_info() else {
+        defmt::error!("Golden bank flagged as populated but has no valid GoldenInfo");
+        return None;
+    };
 
-fn bank_addresses(bd: &BootData, layout: &MemoryLayout) -> (u32, u32) {
-    if bd.active_bank == 0 {
-        (layout.fw_a, layout.fw_b)
-    } else {
-        (layout.fw_b, layout.fw_a)
+    if let Err(reason) = validate_bank(layout.fw_gold, info.size, layout, config) {
+        defmt::error!("Golden bank: image check failed: {}", reason);
+        return None;
+    }
+    if config.verify_crc {
+        let actual_crc = flash::compute_crc32(layout.fw_gold, info.size);
+        if actual_crc != info.crc32 {
+            defmt::error!(
+                "Golden bank: CRC check failed: expected 0x{:08x}, got 0x{:08x}",
+                info.crc32,
+                actual_crc
+            );
+            return None;
+        }
     }
+
+    Some(layout.fw_gold)
 }
 
 fn bank_metadata(bd: &BootData, bank: u8) -> (u32, u32) {
@@ -182,23 +440,106 @@ fn bank_metadata(bd: &BootData, bank: u8) -> (u32, u32) {
     }
 }
 
+/// Load the image (unless it runs in place), publish `boot_info` with the
+/// recorded boot milestones in the RAM mailbox and jump to it.
+///
+/// `image_len` is the image size from its vector table on
+/// (`BootImage::len_from_vector_table`), 0 if unknown. With a `warm_key`
+/// (`warm_boot::image_key`), a RAM image the previous boot left in place is
+/// not copied again, and a copied one is recorded for the next boot.
+///
 /// # Safety
-/// Caller must ensure `flash_addr` and `layout` are valid.
-pub unsafe fn load_and_jump(flash_addr: u32, layout: &MemoryLayout) -> ! {
-    copy_firmware_to_ram(flash_addr, layout);
+/// Caller must ensure `layout` is valid and that `image` is what
+/// `validate_bank` returned for the bank.
+pub unsafe fn load_and_jump(
+    image: BootImage,
+    image_len: u32,
+    warm_key: Option<u32>,
+    layout: &MemoryLayout,
+    boot_info: &BootInfo,
+) -> ! {
+    // Before the copy: a running core 1 could be executing from that RAM.
+    cortex_m::interrupt::disable();
+    if crate::peripherals::park_core1() {
+        defmt::trace!("Core 1 parked in the boot ROM");
+    } else {
+        defmt::warn!("Core 1 did not report in after its reset");
+    }
+
+    // A header, if any, is not copied: RAM images start at their vector table.
+    let (vector_table, copied) = match image.mode {
+        ExecMode::Xip => (image.vector_table, 0),
+        ExecMode::Ram => {
+            let start = timer_now();
+            let copied = match warm_key.and_then(|key| warm_image_len(key, image_len, layout)) {
+                Some(loaded) => {
+                    defmt::println!(
+                        "Image already in RAM ({} bytes), copy skipped in {} us",
+                        loaded,
+                        timer_now().wrapping_sub(start)
+                    );
+                    loaded
+                }
+                None => {
+                    let copied = copy_firmware_to_ram(image.vector_table, image_len, layout);
+                    if let Some(key) = warm_key {
+                        write_warm_marker(WarmBootMarker::new(key, ram_crc(copied, layout)));
+                    }
+                    defmt::println!(
+                        "Copied {} bytes to RAM in {} us",
+                        copied,
+                        timer_now().wrapping_sub(start)
+                    );
+                    copied
+                }
+            };
+            (layout.ram_base, copied)
+        }
+    };
+    if warm_key.is_none() || image.mode == ExecMode::Xip {
+        write_warm_marker(WarmBootMarker::cleared());
+    }
+
+    // The copy window doubles as the upload buffer: whatever was uploaded
+    // this session must not outlive the jump.
+    let start = timer_now();
+    let scrubbed = scrub_upload_buffer(copied, layout);
+    defmt::println!(
+        "Scrubbed {} bytes of upload buffer in {} us",
+        scrubbed,
+        timer_now().wrapping_sub(start)
+    );
+
+    record_milestone(Milestone::Jump);
+    let timings = *BOOT_TIMINGS.0.get();
+    defmt::println!(
+        "Boot timings (us): peripherals {}, trigger {}, image checked {}, jump {}",
+        timings.peripherals_ready,
+        timings.trigger_decided,
+        timings.image_checked,
+        timings.jump
+    );
+
+    // The mailbox lies above the copied region and outside the firmware's
+    // RAM region, so neither the copy nor the firmware's startup touches it.
+    BootInfo {
+        timings,
+        ..*boot_info
+    }
+    .write_to(BOOT_INFO_ADDR);
 
     // Reset peripherals before jumping so firmware SDK can reinitialize cleanly
     prepare_for_firmware_handoff();
 
-    relocate_vector_table(layout.ram_base);
+    relocate_vector_table(vector_table);
 
-    let vt = VectorTable::read_from(layout.ram_base);
-    jump_to_firmware(vt.initial_sp, vt.reset_vector);
+    let vt = read_vector_table(vector_table);
+    jump_to_firmware(vt.initial_sp, vt.reset_vector, layout);
 }
 
-/// Prepare the system for firmware handoff.
-/// Clocks are left configured - SDK's runtime_init_clocks handles this
-/// by switching away from PLLs before reconfiguring them.
+/// Prepare the system for firmware handoff: mask and clear interrupts, then
+/// return peripherals and clocks to their reset state
+/// (`peripherals::deinit_for_boot`).
 unsafe fn prepare_for_firmware_handoff() {
     use cortex_m::peripheral::NVIC;
 
@@ -208,53 +549,143 @@ unsafe fn prepare_for_firmware_handoff() {
     // SAFETY: We're in bootloader context and need to reset NVIC state before handoff
     let nvic = &*NVIC::PTR;
 
-    // Clear all pending interrupts in NVIC (32 interrupt lines on Cortex-M0+)
+    // Clear all pending interrupts in NVIC (32 interrupt lines on Cortex-M0+),
+    // including SIO_IRQ_PROC0 from core 1's FIFO push in `park_core1`
     nvic.icpr[0].write(0xFFFF_FFFF);
 
     // Disable all NVIC interrupts
     nvic.icer[0].write(0xFFFF_FFFF);
 
-    // NOTE: Clocks are NOT reset - SDK handles this by switching
-    // clk_sys to clk_ref before touching PLLs
+    // Applications bring up clocks and USB from their reset state; a PLL
+    // still driving clk_sys or a USB controller left attached breaks that.
+    crate::peripherals::deinit_for_boot();
+
+    // Interrupts raised before the peripherals went into reset
+    nvic.icpr[0].write(0xFFFF_FFFF);
 }
 
-// NOTE: Clock reset is not performed during firmware handoff.
-// The SDK's runtime_init_clocks handles clock reconfiguration by switching
-// away from PLLs before modifying them. If future requirements change,
-// reference implementation for resetting clocks is available in git history.
+/// Copy a RAM image to `layout.ram_base` and return the bytes copied.
+///
+/// Copies `len` bytes rounded up to a word, or the whole copy window when the
+/// size is unknown (0, e.g. an image flashed by a debugger).
+unsafe fn copy_firmware_to_ram(flash_addr: u32, len: u32, layout: &MemoryLayout) -> u32 {
+    let copy_len = if len == 0 {
+        layout.copy_size
+    } else {
+        len.next_multiple_of(4).min(layout.copy_size)
+    };
 
-unsafe fn copy_firmware_to_ram(flash_addr: u32, layout: &MemoryLayout) {
     core::ptr::copy_nonoverlapping(
         flash_addr as *const u32,
         layout.ram_base as *mut u32,
-        layout.copy_size as usize / 4,
+        copy_len as usize / 4,
     );
+    copy_len
+}
+
+/// Bytes of the image `key` identifies that the previous boot left loaded
+/// and intact in the copy window, or `None` if it must be copied.
+unsafe fn warm_image_len(key: u32, len: u32, layout: &MemoryLayout) -> Option<u32> {
+    let marker = (WARM_BOOT_MARKER_ADDR as *const WarmBootMarker).read_volatile();
+    let loaded = len.next_multiple_of(4).min(layout.copy_size);
+    (marker.matches(key) && ram_crc(loaded, layout) == marker.ram_crc).then_some(loaded)
+}
+
+/// CRC-32 of the first `len` bytes of the copy window.
+unsafe fn ram_crc(len: u32, layout: &MemoryLayout) -> u32 {
+    crc32(core::slice::from_raw_parts(
+        layout.ram_base as *const u8,
+        len as usize,
+    ))
+}
+
+unsafe fn write_warm_marker(marker: WarmBootMarker) {
+    (WARM_BOOT_MARKER_ADDR as *mut WarmBootMarker).write_volatile(marker);
 }
 
-unsafe fn relocate_vector_table(ram_base: u32) {
+/// Zero the copy window above the first `keep` bytes and return the bytes
+/// zeroed.
+///
+/// `keep` is the RAM image just copied, or 0 for an XIP image, which leaves
+/// the whole window to zero. Stale RAM can neither pass for firmware state
+/// nor leak an earlier upload.
+unsafe fn scrub_upload_buffer(keep: u32, layout: &MemoryLayout) -> u32 {
+    let len = layout.copy_size - keep;
+    core::ptr::write_bytes((layout.ram_base + keep) as *mut u32, 0, len as usize / 4);
+    len
+}
+
+unsafe fn relocate_vector_table(table_addr: u32) {
     use cortex_m::peripheral::SCB;
 
     cortex_m::interrupt::disable();
 
-    // SAFETY: We're setting VTOR to point to the firmware's vector table in RAM
+    // SAFETY: We're setting VTOR to point to the firmware's vector table (RAM or XIP flash)
     let scb = &*SCB::PTR;
-    scb.vtor.write(ram_base);
+    scb.vtor.write(table_addr);
 
     cortex_m::asm::dsb();
     cortex_m::asm::isb();
 }
 
-unsafe fn jump_to_firmware(initial_sp: u32, reset_vector: u32) -> ! {
+/// Zero the bootloader's RAM, then enter the firmware.
+///
+/// `.data`, `.bss` and the stack (`__bootloader_ram`) hold the USB transport,
+/// the command queue and stack copies of uploaded `DataBlock`s. They are
+/// zeroed in the same asm block as the jump, since no Rust code may run once
+/// its statics and stack are gone; nothing is logged after it. The loop
+/// stores two words per iteration and runs on the ring oscillator, as
+/// `deinit_for_boot` left it: about 2 ms for 16 KB.
+unsafe fn jump_to_firmware(initial_sp: u32, reset_vector: u32, layout: &MemoryLayout) -> ! {
+    let ram_start = layout.bootloader_ram;
+    let ram_end = layout.bootloader_ram + layout.bootloader_ram_size;
+    // The linker script asserts the region is non-empty and 8-byte aligned.
     core::arch::asm!(
-        "msr msp, {sp}",
+        "2:",
+        "stm r0!, {{r4, r5}}",
+        "cmp r0, r1",
+        "blo 2b",
+        "msr msp, r2",
         "cpsie i",  // Re-enable interrupts before jumping (SDK expects PRIMASK=0)
-        "bx {reset}",
-        sp = in(reg) initial_sp,
-        reset = in(reg) reset_vector,
+        "bx r3",
+        in("r0") ram_start,
+        in("r1") ram_end,
+        in("r2") initial_sp,
+        in("r3") reset_vector,
+        in("r4") 0u32,
+        in("r5") 0u32,
         options(noreturn)
     );
 }
 
+/// Supply checks, 10 ms apart, before a boot-path BootData write is given up.
+const POWER_CHECKS: u32 = 10;
+
+/// Whether the boot path may rewrite BootData now.
+///
+/// A brown-out during the sector erase would lose BootData, so the supply is
+/// checked first (`flash::power_ok`). A marginal supply gets up to
+/// `POWER_CHECKS` tries to settle; if it does not, the write is skipped and
+/// this boot is not counted, unless built with `CRISPY_POWER_GUARD=proceed`.
+fn boot_write_allowed(p: &mut crate::peripherals::Peripherals) -> bool {
+    use embedded_hal::delay::DelayNs;
+
+    for _ in 0..POWER_CHECKS {
+        if crate::flash::power_ok() {
+            return true;
+        }
+        p.timer.delay_ms(10u32);
+    }
+
+    if crate::peripherals::SKIP_WRITES_ON_BAD_POWER {
+        defmt::warn!("Supply looks marginal, not saving BOOT_DATA this boot");
+        false
+    } else {
+        defmt::warn!("Supply looks marginal, saving BOOT_DATA anyway");
+        true
+    }
+}
+
 /// Run the normal boot sequence.
 /// If no valid firmware is found, returns to let services handle it.
 pub fn run_normal_boot(p: &mut crate::peripherals::Peripherals) {
@@ -266,43 +697,163 @@ pub fn run_normal_boot(p: &mut crate::peripherals::Peripherals) {
     let bd = crate::flash::read_boot_data();
 
     defmt::println!(
-        "BOOT_DATA: bank={}, confirmed={}, attempts={}, size_a={}, size_b={}, valid={}",
+        "BOOT_DATA: bank={}, confirmed={}, attempts={}, size_a={}, size_b={}",
         bd.active_bank,
         bd.confirmed,
         bd.boot_attempts,
         bd.size_a,
-        bd.size_b,
-        bd.is_valid()
+        bd.size_b
     );
 
-    // If BootData is valid but no firmware uploaded (both sizes 0), return to main loop
-    if bd.is_valid() && bd.size_a == 0 && bd.size_b == 0 {
-        defmt::println!("No firmware uploaded, staying in bootloader");
-        return;
+    let config = BootConfig::from_features();
+    if !config.verify_crc {
+        defmt::println!("Boot CRC verification disabled");
     }
 
-    let (flash_addr, updated_bd) = select_boot_bank(&bd, &layout);
-    defmt::println!("Selected bank at 0x{:08x}", flash_addr);
+    let max_attempts = bd.boot_attempt_limit();
+    let action = select_boot_action(&bd, p.reset_reason, max_attempts, |bd, bank| {
+        bank_is_bootable(bd, bank, &layout, &config)
+    });
+    record_milestone(Milestone::ImageChecked);
+    let updated_bd = match action {
+        BootAction::Boot { boot_data, .. } => {
+            if p.reset_reason == ResetReason::Debugger && boot_data.confirmed == 0 {
+                defmt::println!("Debugger restart, boot attempt not counted");
+            }
+            boot_data
+        }
+        BootAction::Rollback { bank, boot_data } => {
+            defmt::println!(
+                "Boot attempts exhausted ({}), rolling back to bank {}",
+                bd.boot_attempts,
+                bank
+            );
+            boot_data
+        }
+        BootAction::Fallback { bank, boot_data } => {
+            defmt::warn!("Primary bank invalid, booting bank {} as fallback", bank);
+            boot_data
+        }
+        BootAction::UpdateMode(UpdateReason::NoFirmware) => {
+            defmt::println!("No firmware uploaded, staying in bootloader");
+            return;
+        }
+        BootAction::UpdateMode(reason) => {
+            defmt::println!("No bank to boot: {}", reason);
+            #[cfg(feature = "golden-bank")]
+            if let Some(gold_addr) = select_golden_bank(&bd, &layout, &config) {
+                record_milestone(Milestone::ImageChecked);
+                boot_golden(p, gold_addr, &bd, &layout, &config);
+            }
+            defmt::println!("No bootable firmware in any bank, staying in bootloader");
+            return;
+        }
+    };
+    let flash_addr = if updated_bd.active_bank == 0 {
+        layout.fw_a
+    } else {
+        layout.fw_b
+    };
+    defmt::println!(
+        "Selected bank at 0x{:08x} (attempt {}/{})",
+        flash_addr,
+        updated_bd.boot_attempts,
+        max_attempts
+    );
 
-    unsafe {
-        crate::flash::write_boot_data(&updated_bd);
+    // Persist the attempt counter before jumping (and before load_and_jump
+    // masks interrupts and resets the NVIC), so a crashing image is still
+    // counted. Skip the write when nothing changed (confirmed image) to
+    // avoid wearing the BootData sector on every normal boot.
+    if updated_bd.as_bytes() != bd.as_bytes() && boot_write_allowed(p) {
+        // A record that fails to program is not read back, so the boot is
+        // not counted; there is nothing better to do before jumping.
+        match unsafe { crate::flash::write_boot_data(&updated_bd) } {
+            Ok(()) => defmt::println!(
+                "BOOT_DATA saved: bank={}, boot_attempts={}",
+                updated_bd.active_bank,
+                updated_bd.boot_attempts
+            ),
+            Err(err) => defmt::error!("BOOT_DATA write failed: {}", err),
+        }
     }
 
     let bank_label = if flash_addr == layout.fw_a { "A" } else { "B" };
-    if validate_bank(flash_addr).is_none() {
-        defmt::println!("No valid firmware in any bank, staying in bootloader");
+
+    let bank = updated_bd.active_bank;
+    let (crc, size) = bank_metadata(&updated_bd, bank);
+    // Selection already validated the bank; this only locates the image.
+    let Ok(image) = validate_bank(flash_addr, size, &layout, &config) else {
         return;
+    };
+    if image.mode == ExecMode::Xip {
+        defmt::println!(
+            "Executing bank {} in place from 0x{:08x}",
+            bank_label,
+            image.vector_table
+        );
+    } else {
+        defmt::println!(
+            "Loading bank {} from 0x{:08x} to 0x{:08x} ({}KB)",
+            bank_label,
+            image.vector_table,
+            layout.ram_base,
+            layout.copy_size / 1024
+        );
     }
+    crate::services::led::signal_boot_bank(p, updated_bd.active_bank);
+
+    defmt::println!("Jumping to firmware...");
+    p.timer.delay_ms(10u32);
+
+    let image_len = image.len_from_vector_table(flash_addr, size);
+    let version = if bank == 0 {
+        updated_bd.version_a
+    } else {
+        updated_bd.version_b
+    };
+    // Without a recorded size the copy covers the whole window, which the
+    // key could not vouch for.
+    let warm_key = (config.warm_boot && image_len != 0)
+        .then(|| warm_boot::image_key(bank, version, crc, size));
+    let boot_info = BootInfo::new(&updated_bd, BOOTLOADER_VERSION);
+    // A trial image that hangs before confirming is reset and its attempt
+    // counted; confirmed images boot with the watchdog off.
+    if updated_bd.confirmed == 0 && config.trial_watchdog_ms != 0 {
+        defmt::println!(
+            "Trial boot, watchdog armed for {} ms",
+            config.trial_watchdog_ms
+        );
+        unsafe { crate::peripherals::arm_trial_watchdog(config.trial_watchdog_ms) };
+    }
+    unsafe { load_and_jump(image, image_len, warm_key, &layout, &boot_info) }
+}
+
+/// Jump to the golden image. Returns only if it cannot be located.
+#[cfg(feature = "golden-bank")]
+fn boot_golden(
+    p: &mut crate::peripherals::Peripherals,
+    gold_addr: u32,
+    bd: &BootData,
+    layout: &MemoryLayout,
+    config: &BootConfig,
+) {
+    use embedded_hal::delay::DelayNs;
+
+    let size = flash::read_golden_info().map_or(0, |info| info.size);
+    let Ok(image) = validate_bank(gold_addr, size, layout, config) else {
+        return;
+    };
+    defmt::warn!("Banks A and B unbootable, booting golden image");
+    crate::services::led::signal_boot_bank(p, GOLDEN_BANK);
 
-    defmt::println!(
-        "Loading bank {} from 0x{:08x} to 0x{:08x} ({}KB)",
-        bank_label,
-        flash_addr,
-        layout.ram_base,
-        layout.copy_size / 1024
-    );
     defmt::println!("Jumping to firmware...");
     p.timer.delay_ms(10u32);
 
-    unsafe { load_and_jump(flash_addr, &layout) }
+    let mut boot_info = BootInfo::new(bd, BOOTLOADER_VERSION);
+    boot_info.active_bank = GOLDEN_BANK;
+    boot_info.confirmed = 1;
+    boot_info.boot_attempts = 0;
+    let image_len = image.len_from_vector_table(gold_addr, size);
+    unsafe { load_and_jump(image, image_len, None, layout, &boot_info) }
 }
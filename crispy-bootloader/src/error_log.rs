@@ -0,0 +1,51 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Structured error reporting.
+//!
+//! Failure sites call `record` instead of logging free text: the code is
+//! logged over defmt as `err=<code>` and kept in a RAM ring buffer of the
+//! last `ERROR_LOG_LEN` codes, which the host reads with `GetErrorLog`, so
+//! errors can be diagnosed without a debug probe. The log starts empty at
+//! every reset.
+
+use core::cell::UnsafeCell;
+use crispy_common::protocol::{ErrorCode, ERROR_LOG_LEN};
+
+struct ErrorLog {
+    codes: [ErrorCode; ERROR_LOG_LEN],
+    /// Codes recorded so far; the next one goes to `total % ERROR_LOG_LEN`.
+    total: u32,
+}
+
+/// Wrapper to hold the error log in a static without `static mut`.
+///
+/// SAFETY: single-threaded bootloader; no interrupt handler records errors.
+struct SyncErrorLog(UnsafeCell<ErrorLog>);
+unsafe impl Sync for SyncErrorLog {}
+
+static ERROR_LOG: SyncErrorLog = SyncErrorLog(UnsafeCell::new(ErrorLog {
+    codes: [ErrorCode::BadOffset; ERROR_LOG_LEN],
+    total: 0,
+}));
+
+/// Log `code` and append it to the ring buffer.
+pub fn record(code: ErrorCode) {
+    defmt::error!("err={}", code);
+    // SAFETY: see SyncErrorLog
+    let log = unsafe { &mut *ERROR_LOG.0.get() };
+    log.codes[log.total as usize % ERROR_LOG_LEN] = code;
+    log.total = log.total.wrapping_add(1);
+}
+
+/// Number of codes recorded since reset, and the retained ones oldest first.
+pub fn snapshot() -> (u32, heapless::Vec<ErrorCode, ERROR_LOG_LEN>) {
+    // SAFETY: see SyncErrorLog
+    let log = unsafe { &*ERROR_LOG.0.get() };
+    let kept = (log.total as usize).min(ERROR_LOG_LEN);
+    let start = log.total as usize - kept;
+    let recent = (start..log.total as usize)
+        .map(|i| log.codes[i % ERROR_LOG_LEN])
+        .collect();
+    (log.total, recent)
+}
@@ -13,15 +13,44 @@
 //!
 //! All code executing during steps 1-5 must run from RAM, not flash.
 //! We use `#[link_section = ".data"]` to place critical functions in RAM,
-//! and pre-resolve all ROM function pointers at init time.
+//! and pre-resolve all ROM function pointers at init time. Each routine
+//! checks the pointers it uses before step 1 and fails with
+//! `FlashError::NotInitialized` if `init()` has not resolved them, rather
+//! than jumping to address 0 with interrupts masked.
+//!
+//! Raw commands to the flash (its JEDEC ID at `init()`, and its unique ID)
+//! go through the same sequence, with the SSI driven directly in step 3
+//! (`flash_do_cmd`). The JEDEC ID gives the part's capacity, so erases and
+//! programs past it are refused (see `check_range`).
+//!
+//! Once startup is done, `protect_bootloader()` sets the part's volatile
+//! block-protect bits over boot2 and the bootloader, so the flash itself
+//! ignores erases and programs there, including those of firmware that writes
+//! flash by mistake. This is best effort, not security: any code can clear
+//! the bits again, a power cycle clears them, and parts other than the W25Q
+//! family may ignore them. Only the bootloader self-update writes that region,
+//! and lifts the protection first (`unprotect_temporarily()`).
+//!
+//! Reads go through XIP, hashed or copied in place (`xip_bytes`): step 4
+//! invalidates the XIP cache after every write, so it never serves bytes from
+//! before one.
 
-use core::sync::atomic::{AtomicUsize, Ordering};
-use crc::{Crc, CRC_32_ISO_HDLC};
+use crate::boot::MemoryLayout;
+use crate::error_log;
+use core::sync::atomic::{compiler_fence, AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use crispy_common::boot_journal;
+use crispy_common::crc32::CrcAlgorithm;
+use crispy_common::flash_bounds::{self, FlashBounds, RangeError};
+use crispy_common::flash_ops::{self, FlashOps};
 use crispy_common::protocol::{
-    BootData, BOOT_DATA_ADDR, FLASH_BASE, FLASH_PAGE_SIZE, FLASH_SECTOR_SIZE,
+    BootData, DataInfo, ErrorCode, BOOT_DATA_ADDR, BOOT_DATA_MAGIC, BOOT_DATA_MIRROR_ADDR,
+    CONFIG_ADDR, CONFIG_SLOTS, DATA_INFO_ADDR, FLASH_BASE, FLASH_PAGE_SIZE, FLASH_SECTOR_SIZE,
+    WARM_BOOT_MARKER_ADDR,
 };
+#[cfg(feature = "golden-bank")]
+use crispy_common::protocol::{GoldenInfo, GOLDEN_INFO_ADDR};
 
-const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+pub use crispy_common::flash_ops::FlashError;
 
 // RP2040 ROM table addresses (defined in RP2040 datasheet section 2.8.3)
 /// Pointer to the ROM function table (16-bit pointer stored at 0x14)
@@ -33,6 +62,11 @@ const ROM_TABLE_LOOKUP_PTR: *const u16 = 0x0000_0018 as *const u16;
 type RomFnVoid = unsafe extern "C" fn();
 type RomFnErase = unsafe extern "C" fn(u32, usize, u32, u8);
 type RomFnProgram = unsafe extern "C" fn(u32, *const u8, usize);
+type RomFnResetUsbBoot = unsafe extern "C" fn(u32, u32) -> !;
+
+/// Addresses below this lie in the 16 KB boot ROM, as every function the ROM
+/// table resolves does.
+const ROM_END: usize = 0x4000;
 
 /// ROM function pointers, resolved once at init from the ROM table.
 /// Using AtomicUsize for thread-safe initialization without static mut.
@@ -42,6 +76,87 @@ static ROM_FLASH_RANGE_ERASE: AtomicUsize = AtomicUsize::new(0);
 static ROM_FLASH_RANGE_PROGRAM: AtomicUsize = AtomicUsize::new(0);
 static ROM_FLASH_FLUSH_CACHE: AtomicUsize = AtomicUsize::new(0);
 static ROM_FLASH_ENTER_CMD_XIP: AtomicUsize = AtomicUsize::new(0);
+static ROM_RESET_USB_BOOT: AtomicUsize = AtomicUsize::new(0);
+
+/// The ROM function resolved into `slot`, or `NotInitialized` if it holds no
+/// boot ROM address: `init()` has not run.
+#[inline(always)]
+fn rom_fn(slot: &AtomicUsize) -> Result<usize, FlashError> {
+    let addr = slot.load(Ordering::Acquire);
+    if addr != 0 && addr < ROM_END {
+        Ok(addr)
+    } else {
+        Err(FlashError::NotInitialized)
+    }
+}
+
+/// The ROM routines around a flash erase, program or raw command, checked
+/// before any of them runs.
+pub(crate) struct RomFlashFns {
+    pub(crate) connect: RomFnVoid,
+    pub(crate) exit_xip: RomFnVoid,
+    pub(crate) erase: RomFnErase,
+    pub(crate) program: RomFnProgram,
+    pub(crate) flush: RomFnVoid,
+    pub(crate) enter_xip: RomFnVoid,
+}
+
+impl RomFlashFns {
+    /// # Safety
+    /// The pointers must have been stored by `resolve_rom_functions`, which
+    /// `rom_fn` can only check for being in the boot ROM.
+    #[inline(always)]
+    pub(crate) unsafe fn load() -> Result<Self, FlashError> {
+        Ok(Self {
+            connect: core::mem::transmute::<usize, RomFnVoid>(rom_fn(&ROM_CONNECT_INTERNAL_FLASH)?),
+            exit_xip: core::mem::transmute::<usize, RomFnVoid>(rom_fn(&ROM_FLASH_EXIT_XIP)?),
+            erase: core::mem::transmute::<usize, RomFnErase>(rom_fn(&ROM_FLASH_RANGE_ERASE)?),
+            program: core::mem::transmute::<usize, RomFnProgram>(rom_fn(&ROM_FLASH_RANGE_PROGRAM)?),
+            flush: core::mem::transmute::<usize, RomFnVoid>(rom_fn(&ROM_FLASH_FLUSH_CACHE)?),
+            enter_xip: core::mem::transmute::<usize, RomFnVoid>(rom_fn(&ROM_FLASH_ENTER_CMD_XIP)?),
+        })
+    }
+}
+
+/// Flash capacity in bytes detected at `init()`, 0 if the part did not report
+/// a plausible one.
+static FLASH_CAPACITY: AtomicU32 = AtomicU32::new(0);
+
+// SSI status/data registers and the QSPI chip-select control, for sending a
+// raw command to the flash while XIP is off (RP2040 datasheet 4.10, 2.19).
+const SSI_SR: *const u32 = 0x1800_0028 as *const u32;
+const SSI_DR0: *mut u32 = 0x1800_0060 as *mut u32;
+const SSI_SR_RFNE: u32 = 1 << 3;
+const IO_QSPI_SS_CTRL: *mut u32 = 0x4001_800C as *mut u32;
+const SS_OUTOVER_MASK: u32 = 0x3 << 8;
+const SS_OUTOVER_LOW: u32 = 0x2 << 8;
+const SS_OUTOVER_HIGH: u32 = 0x3 << 8;
+/// JEDEC "Read Identification": answers manufacturer, memory type, capacity.
+const CMD_READ_JEDEC_ID: u8 = 0x9F;
+/// "Read Unique ID": four dummy bytes, then the 64-bit ID.
+const CMD_READ_UNIQUE_ID: u8 = 0x4B;
+const UNIQUE_ID_DUMMY_BYTES: usize = 4;
+/// "Read Status Register-1" and "-2".
+const CMD_READ_STATUS_1: u8 = 0x05;
+const CMD_READ_STATUS_2: u8 = 0x35;
+/// "Write Enable for Volatile Status Register": the next status write only
+/// changes the volatile copy, which needs no erase cycle and is lost at
+/// power-off.
+const CMD_VOLATILE_SR_WRITE_ENABLE: u8 = 0x50;
+/// "Write Status Register": status register 1, then 2.
+const CMD_WRITE_STATUS: u8 = 0x01;
+/// Status register 1 bits that are read-only (BUSY, WEL).
+const SR1_READ_ONLY: u8 = 0x03;
+const SR1_BUSY: u8 = 0x01;
+/// Status reads before a part still busy after a status write is given up
+/// on: far beyond the few milliseconds of a non-volatile write, in case a
+/// part takes the write as one.
+const BUSY_POLL_LIMIT: u32 = 100_000;
+
+/// The flash unique ID, split in two words, once `UNIQUE_ID_READ` is set.
+static UNIQUE_ID_HIGH: AtomicU32 = AtomicU32::new(0);
+static UNIQUE_ID_LOW: AtomicU32 = AtomicU32::new(0);
+static UNIQUE_ID_READ: AtomicBool = AtomicBool::new(false);
 
 /// Look up a ROM function by its two-character tag.
 /// Uses RP2040 ROM table as documented in datasheet section 2.8.3.
@@ -59,16 +174,247 @@ unsafe fn rom_func_lookup(tag: &[u8; 2]) -> usize {
     lookup(fn_table, code)
 }
 
-/// Initialize ROM flash function pointers. Must be called once before any flash operations.
-/// This performs ROM table lookups which require XIP to be active.
+/// Initialize ROM flash function pointers and detect the flash capacity.
+/// Must be called once before any flash operations.
 pub fn init() {
+    resolve_rom_functions();
+
+    let detected = detect_capacity();
+    match detected {
+        Some(bytes) => defmt::println!("Flash: {} KB", bytes / 1024),
+        None => defmt::warn!("Flash: no capacity in the JEDEC ID, trusting the linker script"),
+    }
+    FLASH_CAPACITY.store(detected.unwrap_or(0), Ordering::Release);
+    defmt::trace!("Flash unique ID: {=[u8]:02x}", read_unique_id());
+}
+
+/// Resolve the ROM flash function pointers, unless already done.
+/// This performs ROM table lookups which require XIP to be active.
+fn resolve_rom_functions() {
+    if ROM_FLASH_EXIT_XIP.load(Ordering::Acquire) != 0 {
+        return;
+    }
     unsafe {
         ROM_CONNECT_INTERNAL_FLASH.store(rom_func_lookup(b"IF"), Ordering::Release);
-        ROM_FLASH_EXIT_XIP.store(rom_func_lookup(b"EX"), Ordering::Release);
         ROM_FLASH_RANGE_ERASE.store(rom_func_lookup(b"RE"), Ordering::Release);
         ROM_FLASH_RANGE_PROGRAM.store(rom_func_lookup(b"RP"), Ordering::Release);
         ROM_FLASH_FLUSH_CACHE.store(rom_func_lookup(b"FC"), Ordering::Release);
         ROM_FLASH_ENTER_CMD_XIP.store(rom_func_lookup(b"CX"), Ordering::Release);
+        ROM_RESET_USB_BOOT.store(rom_func_lookup(b"UB"), Ordering::Release);
+        // Last: marks the table as resolved.
+        ROM_FLASH_EXIT_XIP.store(rom_func_lookup(b"EX"), Ordering::Release);
+    }
+}
+
+/// Read the flash capacity from the part's JEDEC ID; `None` if it is not
+/// plausible (see `flash_bounds::capacity_from_jedec`) or could not be read.
+pub fn detect_capacity() -> Option<u32> {
+    read_jedec_id()
+        .ok()
+        .and_then(flash_bounds::capacity_from_jedec)
+}
+
+/// Flash capacity in bytes: as detected at `init()`, or the linker script's
+/// flash size if the part did not report one.
+pub fn capacity() -> u32 {
+    match FLASH_CAPACITY.load(Ordering::Acquire) {
+        0 => MemoryLayout::from_linker().flash_size,
+        bytes => bytes,
+    }
+}
+
+/// Send `buf` to the flash as one command and overwrite it with the bytes
+/// clocked back, like the pico-sdk's `flash_do_cmd`: the RP2040 ROM has no
+/// generic command routine, so this drives the SSI directly between the ROM's
+/// XIP exit and re-entry. Runs entirely from RAM; the ROM's cache flush
+/// releases the chip select again.
+///
+/// # Safety
+/// `len` must not exceed the 16-entry SSI FIFOs.
+#[link_section = ".data"]
+#[inline(never)]
+unsafe fn flash_do_cmd(buf: *mut u8, len: usize) -> Result<(), FlashError> {
+    let rom = RomFlashFns::load()?;
+
+    cortex_m::interrupt::disable();
+    (rom.connect)();
+    (rom.exit_xip)();
+    ssi_transfer(buf, len);
+    (rom.flush)();
+    (rom.enter_xip)();
+    cortex_m::interrupt::enable();
+    Ok(())
+}
+
+/// One command on the SSI with XIP off: chip select low, `len` bytes of `buf`
+/// out and the answer back into `buf`, chip select high.
+///
+/// # Safety
+/// XIP must be off and `len` within the SSI FIFOs; inlined into RAM code.
+#[inline(always)]
+unsafe fn ssi_transfer(buf: *mut u8, len: usize) {
+    let ss_ctrl = IO_QSPI_SS_CTRL.read_volatile() & !SS_OUTOVER_MASK;
+    IO_QSPI_SS_CTRL.write_volatile(ss_ctrl | SS_OUTOVER_LOW);
+    let mut sent = 0;
+    while sent < len {
+        SSI_DR0.write_volatile(buf.add(sent).read() as u32);
+        sent += 1;
+    }
+    let mut received = 0;
+    while received < len {
+        if SSI_SR.read_volatile() & SSI_SR_RFNE != 0 {
+            buf.add(received).write(SSI_DR0.read_volatile() as u8);
+            received += 1;
+        }
+    }
+    IO_QSPI_SS_CTRL.write_volatile(ss_ctrl | SS_OUTOVER_HIGH);
+}
+
+/// Write both status registers' volatile copies: the volatile write enable
+/// and the write itself back to back, with no XIP fetch in between. XIP is
+/// only re-entered once the part no longer reports a write in progress, or
+/// `BUSY_POLL_LIMIT` reads later with `Busy`.
+#[link_section = ".data"]
+#[inline(never)]
+unsafe fn flash_write_status(sr1: u8, sr2: u8) -> Result<(), FlashError> {
+    let rom = RomFlashFns::load()?;
+    let mut enable = [CMD_VOLATILE_SR_WRITE_ENABLE];
+    let mut write = [CMD_WRITE_STATUS, sr1, sr2];
+
+    cortex_m::interrupt::disable();
+    (rom.connect)();
+    (rom.exit_xip)();
+    ssi_transfer(enable.as_mut_ptr(), enable.len());
+    ssi_transfer(write.as_mut_ptr(), write.len());
+    let mut polls = 0;
+    let mut status = [CMD_READ_STATUS_1, SR1_BUSY];
+    while status[1] & SR1_BUSY != 0 && polls < BUSY_POLL_LIMIT {
+        status = [CMD_READ_STATUS_1, 0];
+        ssi_transfer(status.as_mut_ptr(), status.len());
+        polls += 1;
+    }
+    (rom.flush)();
+    (rom.enter_xip)();
+    cortex_m::interrupt::enable();
+
+    if status[1] & SR1_BUSY != 0 {
+        Err(FlashError::Busy)
+    } else {
+        Ok(())
+    }
+}
+
+/// Status register `cmd` (`CMD_READ_STATUS_1` or `_2`).
+fn read_status(cmd: u8) -> Result<u8, FlashError> {
+    let mut buf = [cmd, 0];
+    // SAFETY: 2 bytes fit the FIFOs.
+    unsafe { flash_do_cmd(buf.as_mut_ptr(), buf.len())? };
+    Ok(buf[1])
+}
+
+/// Replace the protected range with `sr1_bits` (within
+/// `flash_bounds::SR1_PROTECT_MASK`, 0 for none), keeping every other status
+/// bit, such as QE which quad XIP needs, and clearing CMP. Returns whether the
+/// part took the bits.
+fn set_protection(sr1_bits: u8) -> Result<bool, FlashError> {
+    let sr1 = read_status(CMD_READ_STATUS_1)?;
+    let sr2 = read_status(CMD_READ_STATUS_2)?;
+    let sr1 = sr1 & !(flash_bounds::SR1_PROTECT_MASK | SR1_READ_ONLY) | sr1_bits;
+    // SAFETY: both values are what the part reported, but for the protection.
+    unsafe { flash_write_status(sr1, sr2 & !flash_bounds::SR2_CMP)? };
+    Ok(read_status(CMD_READ_STATUS_1)? & flash_bounds::SR1_PROTECT_MASK == sr1_bits)
+}
+
+/// Write-protect boot2 and the bootloader through the flash's block-protect
+/// bits, chosen from the capacity detected at `init()`. Best effort: a
+/// failure is only logged (see the module docs).
+pub fn protect_bootloader() {
+    let capacity = FLASH_CAPACITY.load(Ordering::Acquire);
+    let bootloader_end = MemoryLayout::from_linker().bootloader_size;
+    let Some(protection) = flash_bounds::bootloader_protection(capacity, bootloader_end) else {
+        defmt::warn!("Flash: no block protection fits this part, bootloader unprotected");
+        return;
+    };
+    match set_protection(protection.sr1_bits) {
+        Ok(true) => defmt::println!("Flash: lowest {} KB write-protected", protection.len / 1024),
+        Ok(false) => defmt::warn!("Flash: block-protect bits not taken, bootloader unprotected"),
+        Err(err) => defmt::warn!("Flash: bootloader write protection failed: {}", err),
+    }
+}
+
+/// Protection lifted by [`unprotect_temporarily`], set again on drop.
+#[must_use]
+pub struct Unprotected(());
+
+impl Drop for Unprotected {
+    fn drop(&mut self) {
+        protect_bootloader();
+    }
+}
+
+/// Clear the block-protect bits until the returned guard is dropped, for
+/// the bootloader's own writes below `bootloader_end`.
+pub fn unprotect_temporarily() -> Unprotected {
+    if !matches!(set_protection(0), Ok(true)) {
+        defmt::warn!("Flash: block protection may still be set");
+    }
+    Unprotected(())
+}
+
+/// The three bytes the flash answers to "Read JEDEC ID".
+fn read_jedec_id() -> Result<[u8; 3], FlashError> {
+    let mut buf = [0u8; 4];
+    buf[0] = CMD_READ_JEDEC_ID;
+    // SAFETY: 4 bytes fit the FIFOs.
+    unsafe { flash_do_cmd(buf.as_mut_ptr(), buf.len())? };
+    Ok([buf[1], buf[2], buf[3]])
+}
+
+/// The flash's 64-bit unique ID ("Read Unique ID", W25Q and compatibles),
+/// read once and cached. Resolves the ROM functions itself if `init()` has
+/// not run yet, so it can be called before USB is up.
+pub fn read_unique_id() -> [u8; 8] {
+    if !UNIQUE_ID_READ.load(Ordering::Acquire) {
+        resolve_rom_functions();
+        let mut buf = [0u8; 1 + UNIQUE_ID_DUMMY_BYTES + 8];
+        buf[0] = CMD_READ_UNIQUE_ID;
+        // SAFETY: 13 bytes fit the FIFOs.
+        if unsafe { flash_do_cmd(buf.as_mut_ptr(), buf.len()) }.is_err() {
+            defmt::error!("Flash: ROM routines unresolved, no unique ID");
+            return [0; 8];
+        }
+        let id = &buf[1 + UNIQUE_ID_DUMMY_BYTES..];
+        UNIQUE_ID_HIGH.store(
+            u32::from_be_bytes([id[0], id[1], id[2], id[3]]),
+            Ordering::Relaxed,
+        );
+        UNIQUE_ID_LOW.store(
+            u32::from_be_bytes([id[4], id[5], id[6], id[7]]),
+            Ordering::Relaxed,
+        );
+        UNIQUE_ID_READ.store(true, Ordering::Release);
+    }
+    let high = UNIQUE_ID_HIGH.load(Ordering::Relaxed).to_be_bytes();
+    let low = UNIQUE_ID_LOW.load(Ordering::Relaxed).to_be_bytes();
+    [
+        high[0], high[1], high[2], high[3], low[0], low[1], low[2], low[3],
+    ]
+}
+
+/// Reset into the ROM USB bootloader (BOOTSEL mode, UF2 mass storage + PICOBOOT).
+///
+/// `activity_gpio_mask` selects GPIOs the ROM toggles on USB activity (0 = none).
+///
+/// Panics if `init()` has not resolved the ROM routine.
+pub fn reset_to_usb_boot(activity_gpio_mask: u32) -> ! {
+    let Ok(addr) = rom_fn(&ROM_RESET_USB_BOOT) else {
+        defmt::panic!("reset_to_usb_boot: ROM routines unresolved, flash::init() not called");
+    };
+    // SAFETY: a boot ROM address stored by `resolve_rom_functions`; 0 keeps
+    // both the mass storage and PICOBOOT interfaces enabled.
+    unsafe {
+        let reset_usb_boot = core::mem::transmute::<usize, RomFnResetUsbBoot>(addr);
+        reset_usb_boot(activity_gpio_mask, 0)
     }
 }
 
@@ -81,102 +427,528 @@ pub fn addr_to_offset(abs_addr: u32) -> u32 {
 /// Runs entirely from RAM with proper XIP teardown/setup.
 ///
 /// # Safety
-/// The `init()` function must have been called first.
+/// The `init()` function must have been called first; otherwise this fails
+/// with `NotInitialized` before touching flash.
 #[link_section = ".data"]
 #[inline(never)]
-pub unsafe fn flash_erase(offset: u32, size: u32) {
-    let connect: RomFnVoid =
-        core::mem::transmute(ROM_CONNECT_INTERNAL_FLASH.load(Ordering::Acquire));
-    let exit_xip: RomFnVoid = core::mem::transmute(ROM_FLASH_EXIT_XIP.load(Ordering::Acquire));
-    let erase: RomFnErase = core::mem::transmute(ROM_FLASH_RANGE_ERASE.load(Ordering::Acquire));
-    let flush: RomFnVoid = core::mem::transmute(ROM_FLASH_FLUSH_CACHE.load(Ordering::Acquire));
-    let enter_xip: RomFnVoid =
-        core::mem::transmute(ROM_FLASH_ENTER_CMD_XIP.load(Ordering::Acquire));
+pub unsafe fn flash_erase(offset: u32, size: u32) -> Result<(), FlashError> {
+    let rom = RomFlashFns::load()?;
+    forget_warm_image(offset);
 
     cortex_m::interrupt::disable();
-    connect();
-    exit_xip();
-    erase(offset, size as usize, FLASH_SECTOR_SIZE, 0x20);
-    flush();
-    enter_xip();
+    (rom.connect)();
+    (rom.exit_xip)();
+    (rom.erase)(offset, size as usize, FLASH_SECTOR_SIZE, 0x20);
+    (rom.flush)();
+    (rom.enter_xip)();
     cortex_m::interrupt::enable();
+    Ok(())
+}
+
+/// Before erasing at flash-relative `offset`: banks lie below BootData, so a
+/// RAM copy of what they held is stale.
+#[inline(always)]
+pub(crate) fn forget_warm_image(offset: u32) {
+    if offset < BOOT_DATA_ADDR - FLASH_BASE {
+        // SAFETY: the marker is a reserved word of RAM
+        unsafe { (WARM_BOOT_MARKER_ADDR as *mut u32).write_volatile(0) };
+    }
 }
 
 /// Program flash at the given flash-relative offset.
 /// Runs entirely from RAM with proper XIP teardown/setup.
 ///
 /// # Safety
-/// The `init()` function must have been called first.
+/// As for [`flash_erase`]; `data` must point to `len` readable bytes.
 #[link_section = ".data"]
 #[inline(never)]
-pub unsafe fn flash_program(offset: u32, data: *const u8, len: usize) {
-    let connect: RomFnVoid =
-        core::mem::transmute(ROM_CONNECT_INTERNAL_FLASH.load(Ordering::Acquire));
-    let exit_xip: RomFnVoid = core::mem::transmute(ROM_FLASH_EXIT_XIP.load(Ordering::Acquire));
-    let program: RomFnProgram =
-        core::mem::transmute(ROM_FLASH_RANGE_PROGRAM.load(Ordering::Acquire));
-    let flush: RomFnVoid = core::mem::transmute(ROM_FLASH_FLUSH_CACHE.load(Ordering::Acquire));
-    let enter_xip: RomFnVoid =
-        core::mem::transmute(ROM_FLASH_ENTER_CMD_XIP.load(Ordering::Acquire));
+pub unsafe fn flash_program(offset: u32, data: *const u8, len: usize) -> Result<(), FlashError> {
+    let rom = RomFlashFns::load()?;
 
     cortex_m::interrupt::disable();
-    connect();
-    exit_xip();
-    program(offset, data, len);
-    flush();
-    enter_xip();
+    (rom.connect)();
+    (rom.exit_xip)();
+    (rom.program)(offset, data, len);
+    (rom.flush)();
+    (rom.enter_xip)();
     cortex_m::interrupt::enable();
+    Ok(())
 }
 
-/// Read bytes from an absolute XIP flash address via volatile reads.
-pub fn flash_read(abs_addr: u32, buf: &mut [u8]) {
-    for (i, byte) in buf.iter_mut().enumerate() {
-        *byte = unsafe { ((abs_addr + i as u32) as *const u8).read_volatile() };
+/// Refuse a flash-relative range that overlaps boot2 or the bootloader, or
+/// runs past the end of flash: the linker script's or the detected
+/// [`capacity`], whichever is smaller (see `crispy_common::flash_bounds`).
+pub fn check_range(offset: u32, len: u32) -> Result<(), RangeError> {
+    let layout = MemoryLayout::from_linker();
+    let bounds = FlashBounds {
+        boot2_end: layout.boot2_size,
+        bootloader_end: layout.bootloader_size,
+        flash_size: layout.flash_size.min(capacity()),
+    };
+    bounds.check(offset, len).inspect_err(|err| {
+        error_log::record(ErrorCode::FlashOutOfRange);
+        defmt::error!("flash: refused 0x{:08x}+{}: {}", offset, len, err);
+    })
+}
+
+/// Erase flash like [`flash_erase`] once the range passed the bounds check.
+///
+/// # Safety
+/// The `init()` function must have been called first.
+pub unsafe fn checked_erase(offset: u32, size: u32) -> Result<(), FlashError> {
+    check_range(offset, size)?;
+    flash_erase(offset, size)
+}
+
+/// Program `data` at the given flash-relative offset like [`flash_program`],
+/// once the range passed the bounds check, then read each page back and
+/// compare. A page that differs is programmed once more; programming only
+/// clears bits, so this fixes bits that did not take the first time.
+///
+/// `0xFF` bytes in `data` are not compared: programming leaves them as they
+/// were, and pages written around existing records hold `0xFF` there.
+///
+/// # Safety
+/// Same as [`flash_program`]; `data.len()` must be a multiple of the page size.
+pub unsafe fn flash_program_verified(offset: u32, data: &[u8]) -> Result<(), FlashError> {
+    check_range(offset, data.len() as u32)?;
+    flash_program(offset, data.as_ptr(), data.len())?;
+    for (index, page) in data.chunks(FLASH_PAGE_SIZE as usize).enumerate() {
+        let page_offset = offset + index as u32 * FLASH_PAGE_SIZE;
+        if programmed_as(page_offset, page) {
+            continue;
+        }
+        error_log::record(ErrorCode::ProgramRetried);
+        flash_program(page_offset, page.as_ptr(), page.len())?;
+        if !programmed_as(page_offset, page) {
+            error_log::record(ErrorCode::ProgramVerifyFailed);
+            return Err(FlashError::VerifyFailed {
+                offset: page_offset,
+            });
+        }
     }
+    Ok(())
+}
+
+/// Whether flash at `offset` reads back as `data`, `0xFF` bytes excepted.
+///
+/// Byte by byte with volatile reads rather than through [`xip_bytes`]: this
+/// runs right after programming, and each byte must come from flash rather
+/// than a value the compiler kept from before the write.
+pub(crate) fn programmed_as(offset: u32, data: &[u8]) -> bool {
+    let base = (FLASH_BASE + offset) as *const u8;
+    data.iter().enumerate().all(|(i, &expected)| {
+        // SAFETY: XIP flash is mapped and readable
+        expected == 0xFF || unsafe { base.add(i).read_volatile() } == expected
+    })
+}
+
+/// The RP2040's flash through the ROM routines, for code written against
+/// [`FlashOps`]: erases and programs are bounds-checked ([`check_range`])
+/// and programs verified ([`flash_program_verified`]).
+///
+/// An erase of bank A's or B's first sector is counted in the wear stats:
+/// every rewrite of a bank (an upload, paged or not, a `CopyBank` or a
+/// staged bootloader) starts there, and erases the rest of the bank at most
+/// once.
+pub struct RomFlash(());
+
+impl RomFlash {
+    /// # Safety
+    /// The `init()` function must have been called first.
+    pub unsafe fn new() -> Self {
+        Self(())
+    }
+}
+
+impl FlashOps for RomFlash {
+    fn check_range(&self, offset: u32, len: u32) -> Result<(), RangeError> {
+        check_range(offset, len)
+    }
+
+    fn erase(&mut self, offset: u32, len: u32) -> Result<(), FlashError> {
+        // SAFETY: `RomFlash::new` requires `init()` to have run.
+        unsafe { checked_erase(offset, len)? };
+        for bank in [0, 1] {
+            if flash_ops::bank_range(bank).is_some_and(|(addr, _)| addr_to_offset(addr) == offset) {
+                // SAFETY: as for `checked_erase`.
+                unsafe { crate::wear::record_erase(crate::wear::WearRegion::for_bank(bank)) };
+            }
+        }
+        Ok(())
+    }
+
+    fn program(&mut self, offset: u32, data: &[u8]) -> Result<(), FlashError> {
+        // SAFETY: as for `erase`.
+        unsafe { flash_program_verified(offset, data) }
+    }
+
+    fn read(&self, offset: u32, buf: &mut [u8]) {
+        flash_read(FLASH_BASE + offset, buf);
+    }
+
+    fn read_boot_data(&self) -> BootData {
+        read_boot_data()
+    }
+
+    fn write_boot_data(&mut self, bd: &BootData) -> Result<(), FlashError> {
+        // SAFETY: as for `erase`.
+        unsafe { write_boot_data(bd) }
+    }
+
+    fn restore_boot_data(&mut self) -> Result<bool, FlashError> {
+        // SAFETY: as for `erase`.
+        unsafe { restore_boot_data() }
+    }
+
+    fn compute_crc(
+        &self,
+        algo: CrcAlgorithm,
+        offset: u32,
+        len: u32,
+        on_progress: impl FnMut(u32),
+    ) -> u32 {
+        compute_crc_with_progress(algo, FLASH_BASE + offset, len, on_progress)
+    }
+}
+
+/// Program-and-verify passes before `flash_bootloader_and_reset` gives up.
+const BOOTLOADER_COPY_ATTEMPTS: u32 = 3;
+/// Cortex-M `AIRCR` and the value requesting a system reset (`VECTKEY | SYSRESETREQ`).
+const AIRCR_ADDR: u32 = 0xE000_ED0C;
+const AIRCR_SYSRESETREQ: u32 = 0x05FA_0004;
+
+/// Replace the bootloader region with `len` bytes at `src`, then reset.
+///
+/// Runs entirely from RAM with interrupts masked: once the region is erased,
+/// no code in flash may run, so this only calls ROM functions and uses
+/// volatile loops (no `memcmp`, no CRC tables). Sector 0 (boot2 and the
+/// vector table) is erased first and programmed last, so an interrupted copy
+/// leaves no valid boot2 and the ROM starts in BOOTSEL mode instead of
+/// running a half-written bootloader.
+///
+/// The result is compared with `src` through XIP. Only on a match is
+/// `boot_data_page` written to the BootData sector, clearing the staging
+/// flag; after `BOOTLOADER_COPY_ATTEMPTS` mismatches the device resets with
+/// the flag still set.
+///
+/// Lifts the bootloader write protection first. Panics, before touching
+/// flash, if `init()` has not resolved the ROM routines.
+///
+/// # Safety
+/// `src` must be in RAM and hold `len` bytes
+/// padded with 0xFF to a whole number of pages, and `boot_data_page` one page.
+#[link_section = ".data"]
+#[inline(never)]
+pub unsafe fn flash_bootloader_and_reset(src: *const u8, len: u32, boot_data_page: *const u8) -> ! {
+    let Ok(rom) = RomFlashFns::load() else {
+        defmt::panic!("Bootloader copy: ROM routines unresolved, flash::init() not called");
+    };
+    let RomFlashFns {
+        connect,
+        exit_xip,
+        erase,
+        program,
+        flush,
+        enter_xip,
+    } = rom;
+    // Never dropped: the next boot protects the new bootloader again.
+    let _unprotected = unprotect_temporarily();
+
+    let erase_len = (len + FLASH_SECTOR_SIZE - 1) & !(FLASH_SECTOR_SIZE - 1);
+    let program_len = (len + FLASH_PAGE_SIZE - 1) & !(FLASH_PAGE_SIZE - 1);
+    let first_sector_len = if program_len < FLASH_SECTOR_SIZE {
+        program_len
+    } else {
+        FLASH_SECTOR_SIZE
+    };
+
+    cortex_m::interrupt::disable();
+
+    let mut attempt = 0;
+    while attempt < BOOTLOADER_COPY_ATTEMPTS {
+        connect();
+        exit_xip();
+        erase(0, erase_len as usize, FLASH_SECTOR_SIZE, 0x20);
+        if program_len > FLASH_SECTOR_SIZE {
+            program(
+                FLASH_SECTOR_SIZE,
+                src.add(FLASH_SECTOR_SIZE as usize),
+                (program_len - FLASH_SECTOR_SIZE) as usize,
+            );
+        }
+        program(0, src, first_sector_len as usize);
+        flush();
+        enter_xip();
+
+        let mut i = 0;
+        while i < len
+            && ((FLASH_BASE + i) as *const u8).read_volatile()
+                == src.add(i as usize).read_volatile()
+        {
+            i += 1;
+        }
+
+        if i == len {
+            connect();
+            exit_xip();
+            erase(
+                BOOT_DATA_ADDR - FLASH_BASE,
+                FLASH_SECTOR_SIZE as usize,
+                FLASH_SECTOR_SIZE,
+                0x20,
+            );
+            program(
+                BOOT_DATA_ADDR - FLASH_BASE,
+                boot_data_page,
+                FLASH_PAGE_SIZE as usize,
+            );
+            flush();
+            enter_xip();
+            break;
+        }
+        attempt += 1;
+    }
+
+    (AIRCR_ADDR as *mut u32).write_volatile(AIRCR_SYSRESETREQ);
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// Bytes hashed per `on_progress` call of [`compute_crc_with_progress`].
+const CRC_CHUNK_SIZE: u32 = 256;
+
+/// `len` bytes of flash at the absolute XIP address `abs_addr`, read in place.
+///
+/// Reads go through the XIP cache like any other load. That is safe while XIP
+/// is on: every erase, program and raw command here ends with the ROM's
+/// `flash_flush_cache` (step 4 above), which invalidates the cache, so no line
+/// cached before a write survives it. The fence keeps the compiler from
+/// moving reads of the slice before a preceding write.
+///
+/// # Safety
+/// The range must be mapped flash, and must not be erased or programmed while
+/// the slice is alive.
+unsafe fn xip_bytes(abs_addr: u32, len: u32) -> &'static [u8] {
+    compiler_fence(Ordering::SeqCst);
+    core::slice::from_raw_parts(abs_addr as *const u8, len as usize)
+}
+
+/// Read bytes from an absolute XIP flash address.
+pub fn flash_read(abs_addr: u32, buf: &mut [u8]) {
+    // SAFETY: nothing writes flash while the slice is copied.
+    buf.copy_from_slice(unsafe { xip_bytes(abs_addr, buf.len() as u32) });
 }
 
 /// Compute CRC-32 (ISO HDLC) over flash data at the given absolute address.
 pub fn compute_crc32(abs_addr: u32, size: u32) -> u32 {
-    let mut digest = CRC32.digest();
-    let mut remaining = size as usize;
-    let mut addr = abs_addr;
-    let mut chunk = [0u8; 256];
+    compute_crc32_with_progress(abs_addr, size, |_| {})
+}
+
+/// Compute CRC-32 like [`compute_crc32`], calling `on_progress(bytes_done)` after each chunk.
+pub fn compute_crc32_with_progress(abs_addr: u32, size: u32, on_progress: impl FnMut(u32)) -> u32 {
+    compute_crc_with_progress(CrcAlgorithm::IsoHdlc, abs_addr, size, on_progress)
+}
 
-    while remaining > 0 {
-        let n = remaining.min(chunk.len());
-        flash_read(addr, &mut chunk[..n]);
-        digest.update(&chunk[..n]);
-        addr += n as u32;
-        remaining -= n;
+/// Compute the `algo` CRC of flash data, calling `on_progress(bytes_done)`
+/// after each chunk. The chunks are hashed in place, without a copy.
+pub fn compute_crc_with_progress(
+    algo: CrcAlgorithm,
+    abs_addr: u32,
+    size: u32,
+    mut on_progress: impl FnMut(u32),
+) -> u32 {
+    let mut crc = algo.empty();
+    let mut done = 0;
+
+    while done < size {
+        let n = (size - done).min(CRC_CHUNK_SIZE);
+        // SAFETY: `on_progress` may service the link, but not write flash.
+        crc = algo.extend(crc, unsafe { xip_bytes(abs_addr + done, n) });
+        done += n;
+        on_progress(done);
     }
 
-    digest.finalize()
+    crc
 }
 
-/// Read BootData from flash. Returns default if magic is invalid.
+/// Whether the supply looks stable enough to erase and program flash.
+///
+/// Checks that the core regulator reports it is in regulation and, on boards
+/// with a VBUS sense pin (`CRISPY_VBUS_SENSE_PIN`), that VBUS is present. The
+/// brown-out detector resets the chip instead of reporting a level, so there
+/// is no BOD status to poll.
+pub fn power_ok() -> bool {
+    // SAFETY: read-only accesses to status registers
+    let (vreg, sio) = unsafe {
+        (
+            &*rp2040_hal::pac::VREG_AND_CHIP_RESET::ptr(),
+            &*rp2040_hal::pac::SIO::ptr(),
+        )
+    };
+    if vreg.vreg().read().rok().bit_is_clear() {
+        return false;
+    }
+    match crate::peripherals::VBUS_SENSE_PIN {
+        Some(pin) => sio.gpio_in().read().bits() & (1 << pin) != 0,
+        None => true,
+    }
+}
+
+/// The BootData sector, read through XIP.
+pub fn boot_data_sector() -> &'static boot_journal::Sector {
+    journal_sector(BOOT_DATA_ADDR)
+}
+
+/// The BootData mirror sector, read through XIP.
+pub fn boot_data_mirror_sector() -> &'static boot_journal::Sector {
+    journal_sector(BOOT_DATA_MIRROR_ADDR)
+}
+
+fn journal_sector(addr: u32) -> &'static boot_journal::Sector {
+    unsafe { &*(addr as *const boot_journal::Sector) }
+}
+
+/// Read BootData from flash: the newest journal record, from the mirror if
+/// the BootData sector has none (`boot_journal::select`). Returns default if
+/// neither has one that passes `BootData::is_valid`.
 pub fn read_boot_data() -> BootData {
-    let bd = unsafe { BootData::read_from(BOOT_DATA_ADDR) };
-    if bd.is_valid() {
-        bd
-    } else {
-        BootData::default_new()
+    match boot_journal::select(boot_data_sector(), boot_data_mirror_sector()) {
+        Some((bd, _)) => bd,
+        None => {
+            if boot_journal::read(boot_data_sector()).magic == BOOT_DATA_MAGIC {
+                defmt::warn!("BOOT_DATA inconsistent, treating it as empty");
+            }
+            BootData::default_new()
+        }
+    }
+}
+
+/// Append BootData to the journal, then to its mirror, erasing each sector
+/// first only when it is full (see `boot_journal`), and verify the records.
+/// A mirror without a valid record is written first instead
+/// (`boot_journal::mirror_first`), so a power loss never leaves both
+/// sectors without one.
+///
+/// On failure the record does not pass its CRC, so the previous one stays in
+/// effect; the second sector is not written after a failed first write.
+///
+/// # Safety
+/// The `init()` function must have been called first.
+pub unsafe fn write_boot_data(bd: &BootData) -> Result<(), FlashError> {
+    let mut order = [BOOT_DATA_ADDR, BOOT_DATA_MIRROR_ADDR];
+    if boot_journal::mirror_first(boot_data_mirror_sector()) {
+        order.reverse();
+    }
+    append_boot_data(order[0], bd)?;
+    append_boot_data(order[1], bd)
+}
+
+/// Restore the BootData sector from its mirror when only the mirror holds a
+/// valid record, recording `BootDataRestored`. Returns whether it did.
+///
+/// # Safety
+/// The `init()` function must have been called first.
+pub unsafe fn restore_boot_data() -> Result<bool, FlashError> {
+    match boot_journal::select(boot_data_sector(), boot_data_mirror_sector()) {
+        Some((bd, boot_journal::Source::Mirror)) => {
+            defmt::warn!("BOOT_DATA sector has no valid record, restoring it from the mirror");
+            append_boot_data(BOOT_DATA_ADDR, &bd)?;
+            crate::error_log::record(ErrorCode::BootDataRestored);
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Append `bd` to the journal sector at `addr`. Only erases of the BootData
+/// sector are counted in the wear stats: the mirror's follow the same
+/// cadence.
+unsafe fn append_boot_data(addr: u32, bd: &BootData) -> Result<(), FlashError> {
+    let offset = addr_to_offset(addr);
+    let plan = boot_journal::plan_write(journal_sector(addr));
+
+    if plan.erase {
+        checked_erase(offset, FLASH_SECTOR_SIZE)?;
+        if addr == BOOT_DATA_ADDR {
+            crate::wear::record_erase(crate::wear::WearRegion::BootData);
+        }
     }
+
+    let (page_offset, page) = plan.page(bd);
+    flash_program_verified(offset + page_offset, &page)
+}
+
+/// Config slot `key`, or `None` if `key` is not below `CONFIG_SLOTS`.
+pub fn read_config(key: u8) -> Option<u32> {
+    let slots = CONFIG_ADDR as *const u32;
+    (key < CONFIG_SLOTS).then(|| unsafe { slots.add(key as usize).read_volatile() })
+}
+
+/// Store `value` in config slot `key`, which must be below `CONFIG_SLOTS`:
+/// read all slots, erase the sector and program them back with the one
+/// changed.
+///
+/// # Safety
+/// The `init()` function must have been called first.
+pub unsafe fn write_config(key: u8, value: u32) -> Result<(), FlashError> {
+    let mut page = [0xFFu8; FLASH_PAGE_SIZE as usize];
+    flash_read(CONFIG_ADDR, &mut page[..CONFIG_SLOTS as usize * 4]);
+    let slot = key as usize * 4;
+    page[slot..slot + 4].copy_from_slice(&value.to_le_bytes());
+
+    let offset = addr_to_offset(CONFIG_ADDR);
+    checked_erase(offset, FLASH_SECTOR_SIZE)?;
+    flash_program_verified(offset, &page)
+}
+
+/// Read the golden image metadata. Returns `None` while the golden bank is empty.
+#[cfg(feature = "golden-bank")]
+pub fn read_golden_info() -> Option<GoldenInfo> {
+    let info = unsafe { GoldenInfo::read_from(GOLDEN_INFO_ADDR) };
+    info.is_valid().then_some(info)
 }
 
-/// Write BootData to flash (erase sector, then program padded to 256B page).
+/// Write the golden image metadata to its own sector.
 ///
 /// # Safety
 /// The `init()` function must have been called first.
-pub unsafe fn write_boot_data(bd: &BootData) {
-    let offset = addr_to_offset(BOOT_DATA_ADDR);
+#[cfg(feature = "golden-bank")]
+pub unsafe fn write_golden_info(info: &GoldenInfo) -> Result<(), FlashError> {
+    write_info_sector(GOLDEN_INFO_ADDR, info.as_bytes())
+}
+
+/// Read the data partition metadata. Returns `None` while no complete data
+/// upload is recorded.
+pub fn read_data_info() -> Option<DataInfo> {
+    let info = unsafe { DataInfo::read_from(DATA_INFO_ADDR) };
+    info.is_valid().then_some(info)
+}
+
+/// Write the data partition metadata to its own sector.
+///
+/// # Safety
+/// The `init()` function must have been called first.
+pub unsafe fn write_data_info(info: &DataInfo) -> Result<(), FlashError> {
+    write_info_sector(DATA_INFO_ADDR, info.as_bytes())
+}
+
+/// Erase the data partition metadata, before the data it describes changes.
+///
+/// # Safety
+/// The `init()` function must have been called first.
+pub unsafe fn erase_data_info() -> Result<(), FlashError> {
+    checked_erase(addr_to_offset(DATA_INFO_ADDR), FLASH_SECTOR_SIZE)
+}
 
-    // Erase the 4KB sector containing boot data
-    flash_erase(offset, FLASH_SECTOR_SIZE);
+/// Erase the sector at `addr` and program `record` at its start.
+unsafe fn write_info_sector(addr: u32, record: &[u8]) -> Result<(), FlashError> {
+    let offset = addr_to_offset(addr);
+    checked_erase(offset, FLASH_SECTOR_SIZE)?;
 
-    // Pad to a full 256-byte page
     let mut page = [0xFFu8; FLASH_PAGE_SIZE as usize];
-    let src = bd.as_bytes();
-    page[..src.len()].copy_from_slice(src);
+    page[..record.len()].copy_from_slice(record);
 
-    flash_program(offset, page.as_ptr(), page.len());
+    flash_program_verified(offset, &page)
 }
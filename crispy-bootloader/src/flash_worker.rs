@@ -0,0 +1,403 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Flash writes on core 1 (`dual-core-flash`).
+//!
+//! `FinishUpdate` hands its erase/program sequence to a worker on core 1 and
+//! returns to the main loop, so core 0 keeps polling the host link, sends
+//! `Progress` and answers `GetStatus` while the image is written. Core 1 is
+//! launched the first time a job starts and then waits for the next one in a
+//! RAM loop; `boot::load_and_jump` parks it before anything overwrites RAM.
+//!
+//! XIP is shared: while core 1 has it off for an erase or a page batch, core
+//! 0 must not fetch from flash either. Each such window is a handshake over
+//! the SIO FIFOs:
+//!
+//!   1. core 1 pushes `TOKEN_LOCKOUT` and waits;
+//!   2. core 0 sees it in `poll()`, enters `park()` (in RAM, interrupts
+//!      masked) and pushes `TOKEN_PARKED`;
+//!   3. core 1 runs the ROM sequence of `flash::flash_erase` or
+//!      `flash::flash_program` from RAM, then pushes `TOKEN_RELEASE`;
+//!   4. core 0 returns from `park()` to flash code.
+//!
+//! Between windows both cores run from flash; core 1 reads the pages back
+//! through XIP there. Core 0 must not touch flash itself while a job runs:
+//! commands other than `GetStatus` and `Ping` are answered `Busy`.
+//!
+//! Ranges are checked on core 0 before the job starts, as `RomFlash` would;
+//! a failed check, or a core 1 that does not start, leaves the write to the
+//! single-core path.
+
+use crate::error_log;
+use crate::flash::{self, RomFlashFns};
+use core::cell::UnsafeCell;
+use core::sync::atomic::{compiler_fence, AtomicBool, AtomicU32, Ordering};
+use crispy_common::flash_ops::{ErasePlan, FlashError, FlashOps};
+use crispy_common::protocol::{ErrorCode, ProgressPhase, FLASH_PAGE_SIZE, FLASH_SECTOR_SIZE};
+use rp2040_hal as hal;
+
+/// Segments a job can hold: the tail and the first sector of a paged upload.
+pub const MAX_SEGMENTS: usize = 2;
+
+/// Bytes programmed per lockout window, as `flash_ops::write_image` does.
+const PROGRAM_BATCH_SIZE: u32 = FLASH_SECTOR_SIZE;
+
+/// Core 1 stack, in words. The worker only needs room for the ROM routines.
+const CORE1_STACK_WORDS: usize = 256;
+
+// SIO inter-core FIFO, as seen by the core accessing it (RP2040 datasheet
+// 2.3.1.5). Plain register accesses, so the RAM routines call no flash code.
+const SIO_FIFO_ST: *const u32 = 0xD000_0050 as *const u32;
+const SIO_FIFO_WR: *mut u32 = 0xD000_0054 as *mut u32;
+const SIO_FIFO_RD: *const u32 = 0xD000_0058 as *const u32;
+const FIFO_ST_VLD: u32 = 1 << 0;
+const FIFO_ST_RDY: u32 = 1 << 1;
+
+// Tokens exchanged over the FIFOs; none of them is a word the boot ROM's
+// launch sequence uses.
+const TOKEN_START: u32 = 0xC0DE_0001;
+const TOKEN_LOCKOUT: u32 = 0xC0DE_0002;
+const TOKEN_PARKED: u32 = 0xC0DE_0003;
+const TOKEN_RELEASE: u32 = 0xC0DE_0004;
+const TOKEN_FINISHED: u32 = 0xC0DE_0005;
+
+/// `FAILURE` while the job has not failed.
+const NO_FAILURE: u32 = u32::MAX;
+/// `FAILURE` when core 1 found the ROM routines unresolved.
+const FAILURE_NOT_INITIALIZED: u32 = u32::MAX - 1;
+
+/// `PROGRESS` layout: segment index, phase, then bytes done in that phase.
+const PROGRESS_SEGMENT_SHIFT: u32 = 31;
+const PROGRESS_PROGRAM: u32 = 1 << 30;
+const PROGRESS_DONE_MASK: u32 = PROGRESS_PROGRAM - 1;
+
+/// One range of a job: `len` bytes of RAM at `data` written to `offset`.
+#[derive(Clone, Copy)]
+pub struct Segment {
+    /// Flash-relative, sector aligned.
+    pub offset: u32,
+    /// In RAM, `len` bytes, a whole number of pages.
+    pub data: *const u8,
+    pub len: u32,
+    /// Erase the sectors first; otherwise the range must already be erased.
+    pub erase: bool,
+    /// Report this segment's progress to the host.
+    pub report: bool,
+}
+
+impl Segment {
+    const EMPTY: Self = Self {
+        offset: 0,
+        data: core::ptr::null(),
+        len: 0,
+        erase: false,
+        report: false,
+    };
+
+    /// Bytes of work: the erase (whole sectors) and the program.
+    fn units(&self) -> u32 {
+        let erased = if self.erase {
+            self.len.next_multiple_of(FLASH_SECTOR_SIZE)
+        } else {
+            0
+        };
+        erased + self.len
+    }
+}
+
+/// The job's segments. Core 0 writes them before pushing `TOKEN_START` and
+/// core 1 reads them until it pushes `TOKEN_FINISHED`; neither writes them
+/// in between.
+struct JobSlot(UnsafeCell<[Segment; MAX_SEGMENTS]>);
+
+// SAFETY: ownership passes between the cores with the FIFO tokens above.
+unsafe impl Sync for JobSlot {}
+
+static JOB: JobSlot = JobSlot(UnsafeCell::new([Segment::EMPTY; MAX_SEGMENTS]));
+static JOB_LEN: AtomicU32 = AtomicU32::new(0);
+/// Where core 1 is, packed as described at `PROGRESS_SEGMENT_SHIFT`.
+static PROGRESS: AtomicU32 = AtomicU32::new(0);
+static UNITS_DONE: AtomicU32 = AtomicU32::new(0);
+static UNITS_TOTAL: AtomicU32 = AtomicU32::new(0);
+/// `NO_FAILURE`, `FAILURE_NOT_INITIALIZED`, or the offset of the page that
+/// stayed wrong.
+static FAILURE: AtomicU32 = AtomicU32::new(NO_FAILURE);
+/// A page needed a second program.
+static RETRIED: AtomicBool = AtomicBool::new(false);
+static LAUNCHED: AtomicBool = AtomicBool::new(false);
+
+static CORE1_STACK: hal::multicore::Stack<CORE1_STACK_WORDS> = hal::multicore::Stack::new();
+
+/// What `poll()` found.
+pub enum Poll {
+    /// The job runs; the progress of a segment that reports it, as
+    /// `(phase, done, total)` in bytes.
+    Running(Option<(ProgressPhase, u32, u32)>),
+    /// The job ended.
+    Done(Result<(), FlashError>),
+}
+
+#[inline(always)]
+unsafe fn fifo_push(token: u32) {
+    while SIO_FIFO_ST.read_volatile() & FIFO_ST_RDY == 0 {}
+    SIO_FIFO_WR.write_volatile(token);
+}
+
+#[inline(always)]
+unsafe fn fifo_pop() -> u32 {
+    while SIO_FIFO_ST.read_volatile() & FIFO_ST_VLD == 0 {}
+    SIO_FIFO_RD.read_volatile()
+}
+
+/// Start writing `segments` on core 1, launching it the first time.
+///
+/// Returns `Ok(false)`, having written nothing, if core 1 did not start; the
+/// caller then writes on core 0. A range `ErasePlan` or `check_range` refuses
+/// fails here, before anything is erased.
+pub fn start(flash: &impl FlashOps, segments: &[Segment]) -> Result<bool, FlashError> {
+    let mut units = 0;
+    for segment in segments {
+        if segment.erase {
+            ErasePlan::new(flash, segment.offset, segment.len)?;
+        } else {
+            flash.check_range(segment.offset, segment.len)?;
+        }
+        units += segment.units();
+    }
+    // SAFETY: only checks that `flash::init()` resolved the routines.
+    unsafe { RomFlashFns::load()? };
+    if segments.len() > MAX_SEGMENTS || !launch() {
+        return Ok(false);
+    }
+
+    for segment in segments.iter().filter(|segment| segment.erase) {
+        flash::forget_warm_image(segment.offset);
+    }
+    // SAFETY: core 1 is idle until `TOKEN_START`, so nothing reads the slot.
+    unsafe { (&mut *JOB.0.get())[..segments.len()].copy_from_slice(segments) };
+    JOB_LEN.store(segments.len() as u32, Ordering::Relaxed);
+    PROGRESS.store(0, Ordering::Relaxed);
+    UNITS_DONE.store(0, Ordering::Relaxed);
+    UNITS_TOTAL.store(units, Ordering::Relaxed);
+    FAILURE.store(NO_FAILURE, Ordering::Relaxed);
+    RETRIED.store(false, Ordering::Relaxed);
+    compiler_fence(Ordering::Release);
+    // SAFETY: core 1 waits for this token in `wait_for_start`.
+    unsafe { fifo_push(TOKEN_START) };
+    Ok(true)
+}
+
+/// Launch the worker on core 1 unless it runs already. Returns whether it
+/// runs.
+fn launch() -> bool {
+    if LAUNCHED.load(Ordering::Relaxed) {
+        return true;
+    }
+    let Some(stack) = CORE1_STACK.take() else {
+        return false;
+    };
+    // SAFETY: core 1 is in the boot ROM, and nothing else uses the PSM or the
+    // SIO FIFO
+    let mut pac = unsafe { hal::pac::Peripherals::steal() };
+    let mut sio = hal::Sio::new(pac.SIO);
+    let mut multicore = hal::multicore::Multicore::new(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
+    match multicore.cores()[1].spawn(stack, || worker()) {
+        Ok(()) => {
+            defmt::println!("Flash worker started on core 1");
+            LAUNCHED.store(true, Ordering::Relaxed);
+            true
+        }
+        Err(_) => {
+            defmt::warn!("Core 1 did not start, writing flash on core 0");
+            false
+        }
+    }
+}
+
+/// Serve core 1's lockout requests and report how the job stands. Called
+/// from the main loop while a job runs.
+pub fn poll() -> Poll {
+    // SAFETY: core 1 only pushes the tokens handled here.
+    while unsafe { SIO_FIFO_ST.read_volatile() } & FIFO_ST_VLD != 0 {
+        match unsafe { SIO_FIFO_RD.read_volatile() } {
+            TOKEN_LOCKOUT => unsafe { park() },
+            TOKEN_FINISHED => return Poll::Done(outcome()),
+            _ => {}
+        }
+    }
+    Poll::Running(progress())
+}
+
+/// Overall progress of the job, in percent.
+pub fn percent() -> u8 {
+    let total = UNITS_TOTAL.load(Ordering::Relaxed);
+    let done = UNITS_DONE.load(Ordering::Relaxed);
+    if total == 0 {
+        100
+    } else {
+        (u64::from(done) * 100 / u64::from(total)).min(100) as u8
+    }
+}
+
+fn progress() -> Option<(ProgressPhase, u32, u32)> {
+    let packed = PROGRESS.load(Ordering::Relaxed);
+    let index = (packed >> PROGRESS_SEGMENT_SHIFT) as usize;
+    // SAFETY: both cores only read the slot while the job runs.
+    let segment = unsafe { (*JOB.0.get())[index] };
+    let done = packed & PROGRESS_DONE_MASK;
+    match (segment.report, packed & PROGRESS_PROGRAM != 0) {
+        (false, _) => None,
+        (true, true) => Some((ProgressPhase::Program, done, segment.len)),
+        (true, false) => Some((
+            ProgressPhase::Erase,
+            done,
+            segment.len.next_multiple_of(FLASH_SECTOR_SIZE),
+        )),
+    }
+}
+
+/// The job's result, recording a retried or failed page as
+/// `flash_program_verified` does.
+fn outcome() -> Result<(), FlashError> {
+    compiler_fence(Ordering::Acquire);
+    if RETRIED.load(Ordering::Relaxed) {
+        error_log::record(ErrorCode::ProgramRetried);
+    }
+    match FAILURE.load(Ordering::Relaxed) {
+        NO_FAILURE => Ok(()),
+        FAILURE_NOT_INITIALIZED => Err(FlashError::NotInitialized),
+        offset => {
+            error_log::record(ErrorCode::ProgramVerifyFailed);
+            Err(FlashError::VerifyFailed { offset })
+        }
+    }
+}
+
+/// Core 0's side of a lockout window: confirm, then wait in RAM until core 1
+/// has XIP back on.
+#[link_section = ".data"]
+#[inline(never)]
+unsafe fn park() {
+    cortex_m::interrupt::disable();
+    fifo_push(TOKEN_PARKED);
+    while fifo_pop() != TOKEN_RELEASE {}
+    cortex_m::interrupt::enable();
+}
+
+/// Core 1's entry point.
+fn worker() -> ! {
+    // SAFETY: runs on core 1 only.
+    unsafe { wait_for_start(false) };
+    loop {
+        run_job();
+        unsafe { wait_for_start(true) };
+    }
+}
+
+/// Push `TOKEN_FINISHED` if `finished`, then wait for the next job. In RAM,
+/// since core 0 writes flash itself between jobs.
+#[link_section = ".data"]
+#[inline(never)]
+unsafe fn wait_for_start(finished: bool) {
+    if finished {
+        fifo_push(TOKEN_FINISHED);
+    }
+    while fifo_pop() != TOKEN_START {}
+}
+
+/// Erase and program every segment of the job, stopping at the first page
+/// that stays wrong.
+fn run_job() {
+    compiler_fence(Ordering::Acquire);
+    // SAFETY: `flash::init()` resolved the pointers; `start` checked them.
+    let Ok(rom) = (unsafe { RomFlashFns::load() }) else {
+        FAILURE.store(FAILURE_NOT_INITIALIZED, Ordering::Relaxed);
+        return;
+    };
+    let len = JOB_LEN.load(Ordering::Relaxed) as usize;
+    for index in 0..len {
+        // SAFETY: core 0 leaves the slot alone until `TOKEN_FINISHED`.
+        let segment = unsafe { (*JOB.0.get())[index] };
+        let tag = (index as u32) << PROGRESS_SEGMENT_SHIFT;
+        PROGRESS.store(tag, Ordering::Relaxed);
+
+        if segment.erase {
+            let total = segment.len.next_multiple_of(FLASH_SECTOR_SIZE);
+            let mut done = 0;
+            while done < total {
+                // SAFETY: core 0 checked the range and parks during the erase.
+                unsafe { locked_flash_op(&rom, segment.offset + done, core::ptr::null(), 0) };
+                done += FLASH_SECTOR_SIZE;
+                advance(tag, done, FLASH_SECTOR_SIZE);
+            }
+        }
+
+        let mut done = 0;
+        while done < segment.len {
+            let batch = (segment.len - done).min(PROGRAM_BATCH_SIZE);
+            // SAFETY: `data` holds `len` bytes.
+            let data = unsafe {
+                core::slice::from_raw_parts(segment.data.add(done as usize), batch as usize)
+            };
+            if let Err(offset) = program_verified(&rom, segment.offset + done, data) {
+                FAILURE.store(offset, Ordering::Relaxed);
+                return;
+            }
+            done += batch;
+            advance(tag | PROGRESS_PROGRAM, done, batch);
+        }
+    }
+}
+
+fn advance(tag: u32, done: u32, step: u32) {
+    PROGRESS.store(tag | done, Ordering::Relaxed);
+    let units = UNITS_DONE.load(Ordering::Relaxed);
+    UNITS_DONE.store(units + step, Ordering::Relaxed);
+}
+
+/// Program `data` at `offset`, read each page back and program a page that
+/// differs once more, as `flash::flash_program_verified` does. Returns the
+/// offset of a page that stays wrong.
+fn program_verified(rom: &RomFlashFns, offset: u32, data: &[u8]) -> Result<(), u32> {
+    // SAFETY: core 0 checked the range and parks during the program.
+    unsafe { locked_flash_op(rom, offset, data.as_ptr(), data.len()) };
+    for (index, page) in data.chunks(FLASH_PAGE_SIZE as usize).enumerate() {
+        let page_offset = offset + index as u32 * FLASH_PAGE_SIZE;
+        if flash::programmed_as(page_offset, page) {
+            continue;
+        }
+        RETRIED.store(true, Ordering::Relaxed);
+        unsafe { locked_flash_op(rom, page_offset, page.as_ptr(), page.len()) };
+        if !flash::programmed_as(page_offset, page) {
+            return Err(page_offset);
+        }
+    }
+    Ok(())
+}
+
+/// Erase the sector at `offset` (`data` null) or program `len` bytes of
+/// `data` there, inside a lockout window. Runs entirely from RAM.
+///
+/// # Safety
+/// Core 1 only, with `rom` from `RomFlashFns::load`; core 0 must be serving
+/// `poll()`.
+#[link_section = ".data"]
+#[inline(never)]
+unsafe fn locked_flash_op(rom: &RomFlashFns, offset: u32, data: *const u8, len: usize) {
+    fifo_push(TOKEN_LOCKOUT);
+    while fifo_pop() != TOKEN_PARKED {}
+
+    cortex_m::interrupt::disable();
+    (rom.connect)();
+    (rom.exit_xip)();
+    if data.is_null() {
+        (rom.erase)(offset, FLASH_SECTOR_SIZE as usize, FLASH_SECTOR_SIZE, 0x20);
+    } else {
+        (rom.program)(offset, data, len);
+    }
+    (rom.flush)();
+    (rom.enter_xip)();
+    cortex_m::interrupt::enable();
+
+    fifo_push(TOKEN_RELEASE);
+}
@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! defmt logger for `log-stream` builds.
+//!
+//! Encoded defmt frames go to a RAM buffer instead of RTT. While the host has
+//! streaming on (`StreamLogs`), the transport service forwards the buffer as
+//! `Response::Log` frames between command responses. Output logged before
+//! streaming starts is kept until the buffer fills, so a host that connects
+//! after boot still sees the boot log.
+
+use crate::transport::Transport;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+use crispy_common::log_buffer::LogBuffer;
+use crispy_common::protocol::{Response, LOG_CHUNK_SIZE};
+
+/// Log bytes held while no host is reading.
+#[cfg(not(feature = "dual-core-flash"))]
+const LOG_BUFFER_SIZE: usize = 2048;
+/// With `dual-core-flash`, the size of the RTT buffer this replaces: core 1's
+/// stack takes the rest of the bootloader's 16 KB of RAM.
+#[cfg(feature = "dual-core-flash")]
+const LOG_BUFFER_SIZE: usize = 1024;
+
+struct LogState {
+    buffer: UnsafeCell<LogBuffer<LOG_BUFFER_SIZE>>,
+    encoder: UnsafeCell<defmt::Encoder>,
+    cs_restore: UnsafeCell<critical_section::RestoreState>,
+}
+
+/// SAFETY: every access is made inside a critical section.
+unsafe impl Sync for LogState {}
+
+static LOG: LogState = LogState {
+    buffer: UnsafeCell::new(LogBuffer::new()),
+    encoder: UnsafeCell::new(defmt::Encoder::new()),
+    cs_restore: UnsafeCell::new(critical_section::RestoreState::invalid()),
+};
+
+static TAKEN: AtomicBool = AtomicBool::new(false);
+static STREAMING: AtomicBool = AtomicBool::new(false);
+
+fn buffer_bytes(bytes: &[u8]) {
+    // SAFETY: only called by the logger, inside its critical section
+    unsafe { (*LOG.buffer.get()).push(bytes) }
+}
+
+#[defmt::global_logger]
+struct Logger;
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {
+        // SAFETY: paired with the release in `release`
+        let restore = unsafe { critical_section::acquire() };
+        if TAKEN.load(Ordering::Relaxed) {
+            panic!("defmt logger taken reentrantly")
+        }
+        TAKEN.store(true, Ordering::Relaxed);
+        // SAFETY: inside the critical section just acquired
+        unsafe {
+            *LOG.cs_restore.get() = restore;
+            (*LOG.encoder.get()).start_frame(buffer_bytes);
+        }
+    }
+
+    unsafe fn flush() {}
+
+    unsafe fn release() {
+        // SAFETY: defmt calls this after `acquire`, still in its critical section
+        unsafe {
+            (*LOG.encoder.get()).end_frame(buffer_bytes);
+            TAKEN.store(false, Ordering::Relaxed);
+            critical_section::release(*LOG.cs_restore.get());
+        }
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        // SAFETY: defmt calls this between `acquire` and `release`
+        unsafe { (*LOG.encoder.get()).write(bytes, buffer_bytes) }
+    }
+}
+
+/// Start or stop forwarding the log to the host.
+pub fn set_streaming(enable: bool) {
+    STREAMING.store(enable, Ordering::Relaxed);
+}
+
+/// Send the next chunk of the log while streaming is on.
+///
+/// A failed send stops streaming: the host is no longer reading, and the
+/// rest of the log is better kept for the next one.
+pub fn forward(transport: &mut dyn Transport) {
+    if !STREAMING.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut chunk = [0u8; LOG_CHUNK_SIZE];
+    let (len, dropped) = critical_section::with(|_| {
+        // SAFETY: inside a critical section, so the logger is not running
+        let buffer = unsafe { &mut *LOG.buffer.get() };
+        (buffer.drain(&mut chunk), buffer.take_dropped())
+    });
+    if len == 0 && dropped == 0 {
+        return;
+    }
+    let mut bytes = heapless::Vec::new();
+    bytes.extend_from_slice(&chunk[..len]).ok();
+    if transport.send(&Response::Log { dropped, bytes }).is_err() {
+        set_streaming(false);
+    }
+}
@@ -7,18 +7,29 @@
 #![no_main]
 
 mod boot;
+mod error_log;
 mod flash;
+#[cfg(feature = "dual-core-flash")]
+mod flash_worker;
+#[cfg(feature = "log-stream")]
+mod log_stream;
 mod peripherals;
 mod services;
+mod transport;
+#[cfg(feature = "uart-transport")]
+mod uart_transport;
 mod update;
+#[cfg(not(feature = "uart-transport"))]
 mod usb_transport;
+mod wear;
 
+#[cfg(not(feature = "log-stream"))]
 use defmt_rtt as _;
 use panic_probe as _;
 
 use crispy_common::service::{Event, EventBus, Service, ServiceContext};
 use peripherals::Peripherals;
-use services::{LedBlinkService, TriggerCheckService, UpdateService, UsbTransportService};
+use services::{LedBlinkService, TransportService, TriggerCheckService, UpdateService};
 
 defmt::timestamp!("{=u64:us}", { 0 });
 
@@ -26,13 +37,11 @@ use cortex_m_rt::entry;
 
 const BOOTLOADER_VERSION: &str = env!("CRISPY_VERSION");
 
-#[unsafe(link_section = ".boot2")]
-#[used]
-pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+include!(concat!(env!("OUT_DIR"), "/boot2.rs"));
 
 /// Enum containing all possible services
 enum ServiceType {
-    UsbTransport(UsbTransportService),
+    Transport(TransportService),
     Trigger(TriggerCheckService),
     Update(UpdateService),
     Led(LedBlinkService),
@@ -42,7 +51,7 @@ impl ServiceType {
     /// Process this service
     fn process(&self, ctx: &mut ServiceContext<Peripherals>) {
         match self {
-            ServiceType::UsbTransport(s) => s.process(ctx),
+            ServiceType::Transport(s) => s.process(ctx),
             ServiceType::Trigger(s) => s.process(ctx),
             ServiceType::Update(s) => s.process(ctx),
             ServiceType::Led(s) => s.process(ctx),
@@ -55,14 +64,15 @@ fn main() -> ! {
     defmt::println!("Bootloader starting v{}", BOOTLOADER_VERSION);
 
     let mut p = init_hardware();
+    let layout_ok = boot::MemoryLayout::from_linker().matches_protocol();
 
     // Initialize command queue for USB<->Update communication
-    services::usb::init_command_queue();
+    services::transport::init_command_queue();
 
     let event_bus = EventBus::new();
 
     let services = [
-        ServiceType::UsbTransport(UsbTransportService::new()),
+        ServiceType::Transport(TransportService::new()),
         ServiceType::Trigger(TriggerCheckService::new()),
         ServiceType::Update(UpdateService::new()),
         ServiceType::Led(LedBlinkService::new()),
@@ -82,18 +92,22 @@ fn main() -> ! {
 
         if event_bus.has_event(|e| matches!(e, Event::RequestBoot)) {
             event_bus.consume(|e| matches!(e, Event::RequestBoot));
-            boot::run_normal_boot(&mut p);
-
-            // run_normal_boot only returns when no valid firmware is found
-            // → fall back to update mode so the device enumerates on USB
-            defmt::println!("No bootable firmware, entering update mode");
+            if layout_ok {
+                boot::run_normal_boot(&mut p);
+
+                // run_normal_boot only returns when no valid firmware is found
+                // → fall back to update mode so the device enumerates on USB
+                defmt::println!("No bootable firmware, entering update mode");
+            } else {
+                defmt::error!("Linker script disagrees with protocol constants, refusing to boot");
+            }
             event_bus.publish(Event::RequestUpdate);
         }
     }
 }
 
 fn init_hardware() -> peripherals::Peripherals {
-    let mut p = match peripherals::init() {
+    let p = match peripherals::init() {
         Ok(p) => p,
         Err(e) => {
             defmt::error!("Failed to initialize peripherals: {:?}", e);
@@ -103,8 +117,16 @@ fn init_hardware() -> peripherals::Peripherals {
         }
     };
 
-    crispy_common::blink(&mut p.led_pin, &mut p.timer, 3, 200);
     flash::init();
+    // Before anything reads BootData. A failed restore is retried next
+    // reset; reads fall back to the mirror meanwhile.
+    if unsafe { flash::restore_boot_data() }.is_err() {
+        defmt::warn!("Restoring BOOT_DATA from its mirror failed");
+    }
+    update::resume_staged_update();
+    // After the only writer of the bootloader region has had its turn.
+    flash::protect_bootloader();
+    boot::record_milestone(boot::Milestone::PeripheralsReady);
 
     p
 }
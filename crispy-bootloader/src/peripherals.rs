@@ -3,8 +3,15 @@
 
 //! Peripheral initialization for the bootloader.
 
+use crispy_common::reset_reason::ResetReason;
+use crispy_common::telemetry;
+use embedded_hal::digital::{InputPin, OutputPin};
 use rp2040_hal as hal;
+#[cfg(not(feature = "uart-transport"))]
 use rp2040_hal::usb::UsbBus;
+#[cfg(feature = "uart-transport")]
+use rp2040_hal::Clock;
+#[cfg(not(feature = "uart-transport"))]
 use usb_device::class_prelude::UsbBusAllocator;
 
 #[derive(Debug, defmt::Format)]
@@ -14,16 +21,136 @@ pub enum InitError {
 
 pub type LedPin =
     hal::gpio::Pin<hal::gpio::bank0::Gpio25, hal::gpio::FunctionSioOutput, hal::gpio::PullDown>;
-pub type Gp2Pin =
-    hal::gpio::Pin<hal::gpio::bank0::Gpio2, hal::gpio::FunctionSioInput, hal::gpio::PullUp>;
+pub type TriggerPin =
+    hal::gpio::Pin<hal::gpio::DynPinId, hal::gpio::FunctionSioInput, hal::gpio::DynPullType>;
+pub type HealthLedPin =
+    hal::gpio::Pin<hal::gpio::DynPinId, hal::gpio::FunctionSioOutput, hal::gpio::PullDown>;
+pub type VsysPin = hal::adc::AdcPin<
+    hal::gpio::Pin<hal::gpio::DynPinId, hal::gpio::FunctionNull, hal::gpio::PullNone>,
+>;
+#[cfg(feature = "uart-transport")]
+pub type UartPin = hal::gpio::Pin<hal::gpio::DynPinId, hal::gpio::FunctionUart, hal::gpio::PullUp>;
+#[cfg(feature = "uart-transport")]
+pub type UartPins = (
+    hal::uart::ValidatedPinTx<UartPin, UartDevice>,
+    hal::uart::ValidatedPinRx<UartPin, UartDevice>,
+);
+
+/// Build-time configuration of the update-mode trigger pin.
+pub struct TriggerConfig {
+    pub pin: u8,
+    pub active_high: bool,
+    pub pull: hal::gpio::DynPullType,
+    /// How long the pin must stay asserted; `0` decides on a single sample.
+    pub hold_ms: u32,
+    /// How long after reset a released pin is still watched; `0` boots at once.
+    pub window_ms: u32,
+    /// Skip the watch window when the active image is confirmed.
+    pub fast_boot: bool,
+}
+
+/// Build-time configuration of the optional health LED.
+pub struct HealthLedConfig {
+    pub pin: u8,
+    pub active_low: bool,
+}
+
+/// Build-time configuration of the optional VSYS sense input.
+pub struct VsysSenseConfig {
+    pub pin: u8,
+    /// Ratio of the resistor divider between VSYS and the pin.
+    pub divider: u32,
+}
+
+/// Build-time configuration of the update UART (`uart-transport` feature).
+#[cfg(feature = "uart-transport")]
+pub struct UpdateUartConfig {
+    pub tx: u8,
+    pub rx: u8,
+    pub baud: u32,
+}
+
+// Generated by build.rs: `TRIGGER`, `HEALTH_LED`, `VBUS_SENSE_PIN`,
+// `VSYS_SENSE`, `SKIP_WRITES_ON_BAD_POWER`, and `UartDevice` and `UART` with
+// the `uart-transport` feature.
+include!(concat!(env!("OUT_DIR"), "/board_config.rs"));
+
+/// Input that forces update mode when held at its active level during reset.
+pub struct Trigger {
+    pin: TriggerPin,
+    active_high: bool,
+    pub hold_ms: u32,
+    pub window_ms: u32,
+    pub fast_boot: bool,
+}
+
+impl Trigger {
+    pub fn is_active(&mut self) -> bool {
+        let level = if self.active_high {
+            self.pin.is_high()
+        } else {
+            self.pin.is_low()
+        };
+        level.unwrap_or(false)
+    }
+}
+
+/// Second status LED that the firmware drives once it is healthy.
+///
+/// The bootloader uses it to show which bank is booting and when it sits in
+/// update mode.
+pub struct HealthLed {
+    pin: HealthLedPin,
+    active_low: bool,
+}
+
+impl HealthLed {
+    pub fn set(&mut self, on: bool) {
+        if on != self.active_low {
+            self.pin.set_high().ok();
+        } else {
+            self.pin.set_low().ok();
+        }
+    }
+}
+
+/// ADC readings reported by `GetTelemetry`.
+pub struct Sensors {
+    adc: hal::Adc,
+    temp: hal::adc::TempSense,
+    vsys: Option<(VsysPin, u32)>,
+}
+
+impl Sensors {
+    /// Temperature in milli-degrees Celsius and VSYS in millivolts (zero
+    /// when the board has no VSYS sense pin).
+    pub fn read(&mut self) -> (i32, u32) {
+        let temp_c_milli = self
+            .adc
+            .read(&mut self.temp)
+            .map(telemetry::temp_c_milli)
+            .unwrap_or(0);
+        let vsys_mv = match &mut self.vsys {
+            Some((pin, divider)) => self
+                .adc
+                .read(pin)
+                .map(|raw| telemetry::vsys_mv(raw, *divider))
+                .unwrap_or(0),
+            None => 0,
+        };
+        (temp_c_milli, vsys_mv)
+    }
+}
 
 /// Static storage for UsbBusAllocator (required by usb-device for 'static lifetime).
+#[cfg(not(feature = "uart-transport"))]
 static mut USB_BUS: Option<UsbBusAllocator<UsbBus>> = None;
 
 /// Get reference to the USB bus allocator.
 ///
 /// # Panics
 /// Panics if called before `store_usb_bus()`.
+#[cfg(not(feature = "uart-transport"))]
 pub fn usb_bus_ref() -> &'static UsbBusAllocator<UsbBus> {
     unsafe {
         (*core::ptr::addr_of!(USB_BUS))
@@ -32,6 +159,13 @@ pub fn usb_bus_ref() -> &'static UsbBusAllocator<UsbBus> {
     }
 }
 
+/// Whether `store_usb_bus()` has been called.
+#[cfg(not(feature = "uart-transport"))]
+pub fn usb_bus_stored() -> bool {
+    unsafe { (*core::ptr::addr_of!(USB_BUS)).is_some() }
+}
+
+#[cfg(not(feature = "uart-transport"))]
 pub fn store_usb_bus(bus: UsbBusAllocator<UsbBus>) {
     unsafe {
         USB_BUS = Some(bus);
@@ -40,11 +174,19 @@ pub fn store_usb_bus(bus: UsbBusAllocator<UsbBus>) {
 
 pub struct Peripherals {
     pub led_pin: LedPin,
-    pub gp2: Gp2Pin,
+    pub health_led: Option<HealthLed>,
+    pub trigger: Option<Trigger>,
     pub timer: hal::Timer,
+    #[cfg(not(feature = "uart-transport"))]
     pub usb: Option<UsbPeripherals>,
+    #[cfg(feature = "uart-transport")]
+    pub uart: Option<UartPeripherals>,
+    pub sensors: Sensors,
+    /// Why the chip last reset, read before anything else touches the watchdog.
+    pub reset_reason: ResetReason,
 }
 
+#[cfg(not(feature = "uart-transport"))]
 pub struct UsbPeripherals {
     pub regs: hal::pac::USBCTRL_REGS,
     pub dpram: hal::pac::USBCTRL_DPRAM,
@@ -52,6 +194,15 @@ pub struct UsbPeripherals {
     pub resets: hal::pac::RESETS,
 }
 
+#[cfg(feature = "uart-transport")]
+pub struct UartPeripherals {
+    pub device: UartDevice,
+    pub pins: UartPins,
+    pub resets: hal::pac::RESETS,
+    /// `clk_peri`, which drives the UART's baud rate generator.
+    pub clock: hal::fugit::HertzU32,
+}
+
 /// Initialize all peripherals for the bootloader.
 ///
 /// # Safety
@@ -62,6 +213,15 @@ pub fn init() -> Result<Peripherals, InitError> {
     // SAFETY: In bootloader context, we're the first code running with exclusive hardware access
     let mut pac = unsafe { hal::pac::Peripherals::steal() };
 
+    let reset_reason = ResetReason::decode(
+        pac.WATCHDOG.reason().read().bits(),
+        pac.VREG_AND_CHIP_RESET.chip_reset().read().bits(),
+    );
+    defmt::println!("Reset reason: {}", reset_reason);
+    // A trial watchdog from the last boot, or one the application started
+    // before resetting into update mode, must not reset us mid-update.
+    pac.WATCHDOG.ctrl().modify(|_, w| w.enable().clear_bit());
+
     let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
     let clocks = hal::clocks::init_clocks_and_plls(
         12_000_000u32,
@@ -75,6 +235,7 @@ pub fn init() -> Result<Peripherals, InitError> {
     .map_err(|_| InitError::ClockInitFailed)?;
 
     let timer = hal::Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+    let mut adc = hal::Adc::new(pac.ADC, &mut pac.RESETS);
     let sio = hal::Sio::new(pac.SIO);
     let pins = hal::gpio::Pins::new(
         pac.IO_BANK0,
@@ -83,15 +244,307 @@ pub fn init() -> Result<Peripherals, InitError> {
         &mut pac.RESETS,
     );
 
+    let led_pin = pins.gpio25.into_push_pull_output();
+    let trigger = TRIGGER.as_ref().and_then(init_trigger);
+    let health_led = HEALTH_LED.as_ref().and_then(init_health_led);
+    let sensors = Sensors {
+        temp: adc
+            .take_temp_sensor()
+            .expect("temperature sensor taken twice"),
+        vsys: VSYS_SENSE.as_ref().and_then(init_vsys_sense),
+        adc,
+    };
+
     Ok(Peripherals {
-        led_pin: pins.gpio25.into_push_pull_output(),
-        gp2: pins.gpio2.into_pull_up_input(),
+        led_pin,
+        health_led,
+        trigger,
         timer,
+        #[cfg(not(feature = "uart-transport"))]
         usb: Some(UsbPeripherals {
             regs: pac.USBCTRL_REGS,
             dpram: pac.USBCTRL_DPRAM,
             clock: clocks.usb_clock,
             resets: pac.RESETS,
         }),
+        #[cfg(feature = "uart-transport")]
+        uart: init_uart(pac.RESETS, clocks.peripheral_clock.freq()),
+        sensors,
+        reset_reason,
+    })
+}
+
+/// Cycles to hold the USB pull-up off so the host sees a detach before the
+/// application connects again (10 ms at the 125 MHz system clock).
+const USB_DETACH_CYCLES: u32 = 1_250_000;
+
+/// Return the hardware `init()` configured to its reset state before the jump
+/// to the application.
+///
+/// The USB controller is detached from the host and disabled, timer alarms
+/// are disarmed, and `clk_ref`/`clk_sys` are moved back to the ring
+/// oscillator as after the boot ROM, with the PLL-derived clocks stopped.
+/// USBCTRL, TIMER, the ADC, the UARTs, the GPIO bank and both PLLs are then
+/// held in reset and released again, so the application's HAL finds them in
+/// their reset state. QSPI, XOSC and the watchdog are left alone: XIP keeps running.
+///
+/// # Safety
+/// Must be called with interrupts disabled, right before the jump, after the
+/// last use of `Peripherals`.
+pub unsafe fn deinit_for_boot() {
+    // SAFETY: nothing uses the HAL handles past this point
+    let pac = unsafe { hal::pac::Peripherals::steal() };
+
+    let usb_running = pac.RESETS.reset().read().usbctrl().bit_is_clear()
+        && pac.RESETS.reset_done().read().usbctrl().bit_is_set();
+    if usb_running {
+        pac.USBCTRL_REGS
+            .sie_ctrl()
+            .modify(|_, w| w.pullup_en().clear_bit());
+        pac.USBCTRL_REGS
+            .main_ctrl()
+            .modify(|_, w| w.controller_en().clear_bit());
+        cortex_m::asm::delay(USB_DETACH_CYCLES);
+    }
+
+    pac.TIMER.inte().write(|w| unsafe { w.bits(0) });
+    pac.TIMER.armed().write(|w| unsafe { w.bits(0xF) });
+    pac.TIMER.intr().write(|w| unsafe { w.bits(0xF) });
+
+    // Glitchless muxes: clk_sys <- clk_ref <- ROSC, as the boot ROM leaves them
+    pac.CLOCKS.clk_sys_ctrl().modify(|_, w| w.src().clk_ref());
+    while pac.CLOCKS.clk_sys_selected().read().bits() != 1 {}
+    pac.CLOCKS
+        .clk_ref_ctrl()
+        .modify(|_, w| w.src().rosc_clksrc_ph());
+    while pac.CLOCKS.clk_ref_selected().read().bits() != 1 {}
+    pac.CLOCKS
+        .clk_ref_div()
+        .write(|w| unsafe { w.int().bits(1) });
+    pac.CLOCKS
+        .clk_peri_ctrl()
+        .modify(|_, w| w.enable().clear_bit());
+    pac.CLOCKS
+        .clk_usb_ctrl()
+        .modify(|_, w| w.enable().clear_bit());
+    pac.CLOCKS
+        .clk_adc_ctrl()
+        .modify(|_, w| w.enable().clear_bit());
+    pac.CLOCKS
+        .clk_rtc_ctrl()
+        .modify(|_, w| w.enable().clear_bit());
+
+    pac.RESETS.reset().modify(|_, w| {
+        w.usbctrl()
+            .set_bit()
+            .timer()
+            .set_bit()
+            .adc()
+            .set_bit()
+            .uart0()
+            .set_bit()
+            .uart1()
+            .set_bit()
+            .io_bank0()
+            .set_bit()
+            .pads_bank0()
+            .set_bit()
+            .pll_sys()
+            .set_bit()
+            .pll_usb()
+            .set_bit()
+    });
+    pac.RESETS.reset().modify(|_, w| {
+        w.usbctrl()
+            .clear_bit()
+            .timer()
+            .clear_bit()
+            .adc()
+            .clear_bit()
+            .uart0()
+            .clear_bit()
+            .uart1()
+            .clear_bit()
+            .io_bank0()
+            .clear_bit()
+            .pads_bank0()
+            .clear_bit()
+            .pll_sys()
+            .clear_bit()
+            .pll_usb()
+            .clear_bit()
+    });
+}
+
+/// Polls of the SIO FIFO while core 1 restarts in the boot ROM.
+const CORE1_PARK_POLLS: u32 = 100_000;
+
+/// Reset core 1 into the boot ROM, where it waits for a launch sequence.
+///
+/// Only `dual-core-flash` builds launch core 1 (`flash_worker`), but a
+/// debugger script might too; left running, it would execute code the application is
+/// about to overwrite. Core 1 is forced off through the PSM and released
+/// again, as the SDK's `multicore_reset_core1()` does. The boot ROM then
+/// drains core 1's FIFO and pushes a `0` to ours; that `0` is the check that
+/// core 1 is parked. The FIFO is left empty with its sticky flags cleared,
+/// so the application's `multicore_launch_core1()` starts from a clean
+/// handshake. Launching core 1 is up to the application.
+///
+/// Returns whether core 1 reported in.
+///
+/// # Safety
+/// Must be called with interrupts disabled, before the image is copied or
+/// jumped to.
+pub unsafe fn park_core1() -> bool {
+    // SAFETY: core 1 is not running bootloader code and nothing else uses
+    // the PSM or the SIO FIFO
+    let pac = unsafe { hal::pac::Peripherals::steal() };
+
+    pac.PSM.frce_off().modify(|_, w| w.proc1().set_bit());
+    // The read-back also fences the write through the APB bridge
+    while pac.PSM.frce_off().read().proc1().bit_is_clear() {}
+    pac.PSM.frce_off().modify(|_, w| w.proc1().clear_bit());
+
+    let mut parked = false;
+    for _ in 0..CORE1_PARK_POLLS {
+        if pac.SIO.fifo_st().read().vld().bit_is_set() {
+            // Anything queued before the reset is dropped with it
+            parked = pac.SIO.fifo_rd().read().bits() == 0;
+            if parked {
+                break;
+            }
+        }
+    }
+    while pac.SIO.fifo_st().read().vld().bit_is_set() {
+        pac.SIO.fifo_rd().read();
+    }
+    pac.SIO
+        .fifo_st()
+        .write(|w| w.wof().clear_bit_by_one().roe().clear_bit_by_one());
+
+    parked
+}
+
+/// Longest watchdog timeout: the 24-bit counter runs at 2 counts per µs
+/// (RP2040-E1).
+pub const WATCHDOG_MAX_TIMEOUT_MS: u32 = 0x00FF_FFFF / 2 / 1000;
+
+/// Value of `WATCHDOG.SCRATCH4` that makes the boot ROM jump to the PC in
+/// `SCRATCH6` after a watchdog reset instead of booting from flash.
+const ROM_WATCHDOG_BOOT_MAGIC: u32 = 0xB007_C0D3;
+
+/// Arm the watchdog for a trial boot: the chip resets unless the
+/// application disables or feeds it within `timeout_ms`.
+///
+/// The reset covers everything but the oscillators (as `hal::Watchdog`
+/// configures it), so the next boot goes through the bootloader and counts
+/// the attempt. The watchdog pauses while a debugger halts either core.
+/// SCRATCH0-3 (the update request) are left alone; SCRATCH4 is cleared so a
+/// stale boot ROM magic cannot divert the reset.
+///
+/// # Safety
+/// Must be called right before the jump, after the last use of `Peripherals`.
+pub unsafe fn arm_trial_watchdog(timeout_ms: u32) {
+    // SAFETY: nothing else uses the watchdog or the PSM from here on
+    let pac = unsafe { hal::pac::Peripherals::steal() };
+    let load = timeout_ms.min(WATCHDOG_MAX_TIMEOUT_MS) * 1000 * 2;
+
+    pac.WATCHDOG.ctrl().modify(|_, w| w.enable().clear_bit());
+    if pac.WATCHDOG.scratch4().read().bits() == ROM_WATCHDOG_BOOT_MAGIC {
+        pac.WATCHDOG.scratch4().write(|w| unsafe { w.bits(0) });
+    }
+    pac.PSM.wdsel().write(|w| unsafe {
+        w.bits(0x0001_FFFF);
+        w.xosc().clear_bit();
+        w.rosc().clear_bit();
+        w
+    });
+    pac.WATCHDOG.load().write(|w| unsafe { w.bits(load) });
+    pac.WATCHDOG.ctrl().write(|w| {
+        w.pause_dbg0()
+            .set_bit()
+            .pause_dbg1()
+            .set_bit()
+            .pause_jtag()
+            .set_bit()
+            .enable()
+            .set_bit()
+    });
+}
+
+/// Take a configured GPIO by number.
+///
+/// # Safety
+/// build.rs rejects the status LED pin and overlapping roles, and the typed
+/// `Pins` other than GP25 are dropped, so this is the only handle to `num`.
+unsafe fn take_configured_pin(
+    num: u8,
+) -> hal::gpio::Pin<hal::gpio::DynPinId, hal::gpio::DynFunction, hal::gpio::DynPullType> {
+    hal::gpio::new_pin(hal::gpio::DynPinId {
+        bank: hal::gpio::DynBankId::Bank0,
+        num,
+    })
+}
+
+fn init_trigger(config: &TriggerConfig) -> Option<Trigger> {
+    let mut pin = unsafe { take_configured_pin(config.pin) }
+        .try_into_function::<hal::gpio::FunctionSioInput>()
+        .ok()?;
+    pin.set_pull_type(config.pull);
+
+    Some(Trigger {
+        pin,
+        active_high: config.active_high,
+        hold_ms: config.hold_ms,
+        window_ms: config.window_ms,
+        fast_boot: config.fast_boot,
+    })
+}
+
+fn init_health_led(config: &HealthLedConfig) -> Option<HealthLed> {
+    let pin = unsafe { take_configured_pin(config.pin) }
+        .try_into_function::<hal::gpio::FunctionSioOutput>()
+        .ok()?
+        .into_pull_type::<hal::gpio::PullDown>();
+
+    let mut led = HealthLed {
+        pin,
+        active_low: config.active_low,
+    };
+    led.set(false);
+    Some(led)
+}
+
+fn init_vsys_sense(config: &VsysSenseConfig) -> Option<(VsysPin, u32)> {
+    let pin = unsafe { take_configured_pin(config.pin) }
+        .try_into_function::<hal::gpio::FunctionNull>()
+        .ok()?
+        .into_pull_type::<hal::gpio::PullNone>();
+    let pin = hal::adc::AdcPin::new(pin).ok()?;
+    Some((pin, config.divider))
+}
+
+#[cfg(feature = "uart-transport")]
+fn init_uart(resets: hal::pac::RESETS, clock: hal::fugit::HertzU32) -> Option<UartPeripherals> {
+    // SAFETY: the UART instance build.rs selected is not used anywhere else
+    let device = unsafe { UartDevice::steal() };
+    let tx = unsafe { take_configured_pin(UART.tx) }
+        .try_into_function::<hal::gpio::FunctionUart>()
+        .ok()?
+        .into_pull_type::<hal::gpio::PullUp>();
+    let rx = unsafe { take_configured_pin(UART.rx) }
+        .try_into_function::<hal::gpio::FunctionUart>()
+        .ok()?
+        .into_pull_type::<hal::gpio::PullUp>();
+    let pins = (
+        hal::uart::ValidatedPinTx::validate(tx, &device).ok()?,
+        hal::uart::ValidatedPinRx::validate(rx, &device).ok()?,
+    );
+
+    Some(UartPeripherals {
+        device,
+        pins,
+        resets,
+        clock,
     })
 }
@@ -2,10 +2,23 @@
 // Copyright (c) 2026 ADNT Sarl <info@adnt.io>
 
 //! LED service for status indication.
+//!
+//! The status LED (GP25) blinks slowly whenever the bootloader runs. The
+//! optional health LED (see `peripherals::HealthLed`) shows:
+//! - bank A booting: one long pulse before the jump
+//! - bank B booting: two long pulses before the jump
+//! - update mode: fast continuous blink
+//!
+//! While the trigger pin is watched after reset (`CRISPY_TRIGGER_WINDOW_MS`)
+//! both LEDs stay on, prompting the user to press the button now.
+//!
+//! In recovery (USB could not be initialized) both LEDs repeat SOS in Morse
+//! code instead.
 
 use crate::peripherals::Peripherals;
 use core::cell::Cell;
-use crispy_common::service::{Service, ServiceContext};
+use crispy_common::service::{Event, Service, ServiceContext};
+use embedded_hal::delay::DelayNs;
 use embedded_hal::digital::OutputPin;
 
 /// LED state machine
@@ -18,23 +31,109 @@ enum LedState {
 /// Service that blinks the LED periodically based on time
 pub struct LedBlinkService {
     state: Cell<LedState>,
+    update_mode: Cell<bool>,
+    recovery: Cell<bool>,
+    /// End of the trigger watch window (timer µs), 0 if none was opened.
+    window_until: Cell<u64>,
 }
 
 const LED_PERIOD_US: u64 = 500_000; // 500ms
+const HEALTH_UPDATE_PERIOD_US: u64 = 100_000; // 100ms
+const HEALTH_BOOT_PULSE_MS: u32 = 300;
+
+/// Morse unit of the recovery pattern.
+const SOS_UNIT_US: u64 = 200_000;
+/// SOS, one character per unit ('1' = LED on), with the letter and word gaps.
+const SOS_PATTERN: &[u8] = b"1010100011101110111000101010000000";
 
 impl LedBlinkService {
     pub fn new() -> Self {
         Self {
             state: Cell::new(LedState::Off { since_us: 0 }),
+            update_mode: Cell::new(false),
+            recovery: Cell::new(false),
+            window_until: Cell::new(0),
+        }
+    }
+
+    fn track_mode(&self, ctx: &mut ServiceContext<Peripherals>) {
+        ctx.events.consume(|event| match event {
+            Event::UpdateModeEntered => {
+                self.update_mode.set(true);
+                self.recovery.set(false);
+                true
+            }
+            Event::RecoveryEntered => {
+                self.recovery.set(true);
+                true
+            }
+            Event::TriggerWindowOpened { until_us } => {
+                self.window_until.set(*until_us);
+                true
+            }
+            _ => false,
+        });
+    }
+
+    /// Drive both LEDs with the SOS pattern.
+    fn drive_recovery(&self, ctx: &mut ServiceContext<Peripherals>, now: u64) {
+        let unit = (now / SOS_UNIT_US) as usize % SOS_PATTERN.len();
+        let on = SOS_PATTERN[unit] == b'1';
+        if on {
+            ctx.peripherals.led_pin.set_high().ok();
+        } else {
+            ctx.peripherals.led_pin.set_low().ok();
+        }
+        if let Some(health) = ctx.peripherals.health_led.as_mut() {
+            health.set(on);
+        }
+    }
+
+    fn drive_health_led(&self, ctx: &mut ServiceContext<Peripherals>, now: u64) {
+        if !self.update_mode.get() {
+            return;
+        }
+        if let Some(health) = ctx.peripherals.health_led.as_mut() {
+            health.set((now / HEALTH_UPDATE_PERIOD_US).is_multiple_of(2));
         }
     }
 }
 
+/// Pulse the health LED once for bank A, twice for bank B, three times for
+/// the golden bank, then leave it off for the firmware to drive. Does nothing
+/// without a health LED.
+pub fn signal_boot_bank(p: &mut Peripherals, bank: u8) {
+    let Some(health) = p.health_led.as_mut() else {
+        return;
+    };
+
+    for _ in 0..=bank.min(2) {
+        health.set(true);
+        p.timer.delay_ms(HEALTH_BOOT_PULSE_MS);
+        health.set(false);
+        p.timer.delay_ms(HEALTH_BOOT_PULSE_MS);
+    }
+}
+
 impl Service<Peripherals> for LedBlinkService {
     fn process(&self, ctx: &mut ServiceContext<Peripherals>) {
         let now = ctx.peripherals.timer.get_counter().ticks();
         let state = self.state.get();
 
+        self.track_mode(ctx);
+        if self.recovery.get() {
+            self.drive_recovery(ctx, now);
+            return;
+        }
+        if now < self.window_until.get() && !self.update_mode.get() {
+            ctx.peripherals.led_pin.set_high().ok();
+            if let Some(health) = ctx.peripherals.health_led.as_mut() {
+                health.set(true);
+            }
+            return;
+        }
+        self.drive_health_led(ctx, now);
+
         match state {
             LedState::On { since_us } => {
                 if now - since_us >= LED_PERIOD_US {
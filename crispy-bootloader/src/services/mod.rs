@@ -4,11 +4,11 @@
 //! Service implementations for the bootloader.
 
 pub mod led;
+pub mod transport;
 pub mod trigger;
 pub mod update;
-pub mod usb;
 
 pub use led::LedBlinkService;
+pub use transport::TransportService;
 pub use trigger::TriggerCheckService;
 pub use update::UpdateService;
-pub use usb::UsbTransportService;
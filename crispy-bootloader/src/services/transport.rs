@@ -1,10 +1,13 @@
 // SPDX-License-Identifier: MIT
 // Copyright (c) 2026 ADNT Sarl <info@adnt.io>
 
-//! USB transport service for polling and receiving commands.
+//! Transport service for polling the host link and receiving commands.
 
-use crate::{peripherals::Peripherals, usb_transport::UsbTransport};
-use core::cell::UnsafeCell;
+use crate::{
+    peripherals::Peripherals,
+    transport::{ActiveTransport, Transport},
+};
+use core::{cell::UnsafeCell, mem::MaybeUninit};
 use crispy_common::{
     protocol::Command,
     service::{Service, ServiceContext},
@@ -14,7 +17,7 @@ use heapless::spsc::Queue;
 /// Wrapper to hold a Queue in a static without `static mut`.
 ///
 /// SAFETY: This is only safe in a single-threaded (bare-metal, no OS) environment.
-/// Only UsbTransportService (producer) calls enqueue, only UpdateService (consumer) calls dequeue.
+/// Only TransportService (producer) calls enqueue, only UpdateService (consumer) calls dequeue.
 struct SyncQueue(UnsafeCell<Queue<Command, 8>>);
 unsafe impl Sync for SyncQueue {}
 
@@ -25,7 +28,7 @@ pub fn init_command_queue() {
     // spsc::Queue is already initialized statically
 }
 
-/// Push a command to the queue (called by USB service)
+/// Push a command to the queue (called by the transport service)
 #[allow(clippy::result_large_err)]
 pub fn push_command(cmd: Command) -> Result<(), Command> {
     // SAFETY: Single-threaded bare-metal environment, no concurrent access
@@ -38,58 +41,79 @@ pub fn pop_command() -> Option<Command> {
     unsafe { (*COMMAND_QUEUE.0.get()).dequeue() }
 }
 
-/// Wrapper to hold an Option<UsbTransport> in a static without `static mut`.
+/// Wrapper to hold the transport in a static without `static mut`.
+///
+/// The transport is kept in `MaybeUninit` behind a flag rather than in an
+/// `Option`: a `None` initializer is not all zeros, so it would be placed in
+/// `.data` and cost 4KB of flash for its initial image.
 ///
 /// SAFETY: Same single-threaded guarantee as above.
-struct SyncTransport(UnsafeCell<Option<UsbTransport>>);
+struct SyncTransport {
+    transport: UnsafeCell<MaybeUninit<ActiveTransport>>,
+    stored: UnsafeCell<bool>,
+}
 unsafe impl Sync for SyncTransport {}
 
-static USB_TRANSPORT: SyncTransport = SyncTransport(UnsafeCell::new(None));
+static TRANSPORT: SyncTransport = SyncTransport {
+    transport: UnsafeCell::new(MaybeUninit::uninit()),
+    stored: UnsafeCell::new(false),
+};
 
-/// Store the USB transport (call once after initialization)
-pub fn store_transport(transport: UsbTransport) {
+/// Store the transport (call once after initialization)
+pub fn store_transport(transport: ActiveTransport) {
     // SAFETY: Called only once during initialization, single-threaded
     unsafe {
-        *USB_TRANSPORT.0.get() = Some(transport);
+        (*TRANSPORT.transport.get()).write(transport);
+        *TRANSPORT.stored.get() = true;
     }
 }
 
-/// Get a reference to the USB transport for sending responses
+/// Get a reference to the transport for sending responses
 pub fn with_transport<F, R>(f: F) -> Option<R>
 where
-    F: FnOnce(&mut UsbTransport) -> R,
+    F: FnOnce(&mut ActiveTransport) -> R,
 {
-    // SAFETY: Single-threaded environment, no concurrent access
-    unsafe { (*USB_TRANSPORT.0.get()).as_mut().map(f) }
+    // SAFETY: Single-threaded environment, no concurrent access; the
+    // transport is only read once `store_transport` has initialized it.
+    unsafe {
+        if *TRANSPORT.stored.get() {
+            Some(f((*TRANSPORT.transport.get()).assume_init_mut()))
+        } else {
+            None
+        }
+    }
 }
 
-/// Service that polls USB and queues received commands
-pub struct UsbTransportService;
+/// Service that polls the transport and queues received commands
+pub struct TransportService;
 
-impl UsbTransportService {
+impl TransportService {
     pub fn new() -> Self {
         Self
     }
 }
 
-impl Service<Peripherals> for UsbTransportService {
+impl Service<Peripherals> for TransportService {
     fn process(&self, _ctx: &mut ServiceContext<Peripherals>) {
         with_transport(|transport| {
-            // Poll USB device
+            // Poll the link
             transport.poll();
 
             // Try to receive a command and queue it
             if let Some(cmd) = transport.try_receive() {
-                defmt::println!("USB: Received command");
+                defmt::println!("Transport: Received command");
                 match push_command(cmd) {
                     Ok(()) => {
-                        defmt::println!("USB: Command queued successfully");
+                        defmt::println!("Transport: Command queued successfully");
                     }
                     Err(_) => {
                         defmt::warn!("Command queue full, dropping command");
                     }
                 }
             }
+
+            #[cfg(feature = "log-stream")]
+            crate::log_stream::forward(transport);
         });
     }
 }
@@ -3,34 +3,144 @@
 
 //! Trigger checking service for boot mode selection.
 
-use crate::{boot, peripherals::Peripherals};
+use crate::{boot, flash, peripherals::Peripherals};
 use core::cell::Cell;
 use crispy_common::service::{Event, Service, ServiceContext};
-use embedded_hal::digital::InputPin;
 
-/// Service for checking mode triggers at startup
+/// Trigger pin samples are taken this far apart while its hold time or the
+/// watch window runs.
+const SAMPLE_INTERVAL_US: u64 = 5_000;
+
+#[derive(Clone, Copy)]
+enum TriggerState {
+    /// The pin has not been sampled yet.
+    Start,
+    /// The pin has been released at every sample so far and is watched until
+    /// the window ends; the last sample was taken at `sampled`.
+    Watching { sampled: u64 },
+    /// The pin has been asserted at every sample since `since` (timer µs);
+    /// the last sample was taken at `sampled`.
+    Holding { since: u64, sampled: u64 },
+    /// Boot or update mode has been published.
+    Decided,
+}
+
+/// Service for checking mode triggers at startup.
+///
+/// A released trigger pin is decided on its first sample, so normal boots
+/// are not delayed, unless a watch window is configured
+/// (`CRISPY_TRIGGER_WINDOW_MS`): the pin is then sampled until the window
+/// ends, with the LEDs lit, and update mode is entered as soon as it asserts.
+/// With `CRISPY_FAST_BOOT` a confirmed image skips the window.
+///
+/// An asserted pin must stay asserted for the configured hold time
+/// (`CRISPY_TRIGGER_HOLD_MS`), sampled once per tick at most every
+/// `SAMPLE_INTERVAL_US`, so a glitch at power-up does not enter update mode.
+/// A glitch inside the window resumes watching.
 pub struct TriggerCheckService {
-    checked: Cell<bool>,
+    state: Cell<TriggerState>,
+    /// End of the watch window (timer µs); the reset time when there is none.
+    window_end: Cell<u64>,
 }
 
 impl TriggerCheckService {
     pub fn new() -> Self {
         Self {
-            checked: Cell::new(false),
+            state: Cell::new(TriggerState::Start),
+            window_end: Cell::new(0),
         }
     }
 }
 
+/// Whether the watch window can be skipped: there is no firmware to boot, or
+/// the active image is confirmed and fast boot is on.
+fn skip_window(fast_boot: bool) -> bool {
+    let bd = flash::read_boot_data();
+    (bd.size_a == 0 && bd.size_b == 0) || (fast_boot && bd.confirmed != 0)
+}
+
 impl Service<Peripherals> for TriggerCheckService {
     fn process(&self, ctx: &mut ServiceContext<Peripherals>) {
-        if self.checked.get() {
-            return;
-        }
+        let now = ctx.peripherals.timer.get_counter().ticks();
+        let (asserted, hold_us, window_us, fast_boot) = match ctx.peripherals.trigger.as_mut() {
+            Some(trigger) => (
+                trigger.is_active(),
+                u64::from(trigger.hold_ms) * 1000,
+                u64::from(trigger.window_ms) * 1000,
+                trigger.fast_boot,
+            ),
+            None => (false, 0, 0, false),
+        };
+        let in_window = now < self.window_end.get();
 
-        self.checked.set(true);
-        let gp2_low = ctx.peripherals.gp2.is_low().unwrap_or(false);
+        let pin_active = match self.state.get() {
+            TriggerState::Decided => return,
+            TriggerState::Start => {
+                let window_us = if window_us > 0 && !skip_window(fast_boot) {
+                    window_us
+                } else {
+                    0
+                };
+                self.window_end.set(now + window_us);
+                if asserted && hold_us > 0 {
+                    self.state.set(TriggerState::Holding {
+                        since: now,
+                        sampled: now,
+                    });
+                    return;
+                }
+                if asserted || window_us == 0 {
+                    asserted
+                } else if boot::check_update_trigger(false, ctx.peripherals.reset_reason) {
+                    // A software request (RAM flag, watchdog scratch) needs no window.
+                    true
+                } else {
+                    defmt::println!("Watching trigger pin for {} ms", window_us / 1000);
+                    self.state.set(TriggerState::Watching { sampled: now });
+                    ctx.events.publish(Event::TriggerWindowOpened {
+                        until_us: now + window_us,
+                    });
+                    return;
+                }
+            }
+            TriggerState::Watching { sampled } if now - sampled < SAMPLE_INTERVAL_US => return,
+            TriggerState::Watching { .. } if asserted && hold_us > 0 => {
+                self.state.set(TriggerState::Holding {
+                    since: now,
+                    sampled: now,
+                });
+                return;
+            }
+            TriggerState::Watching { .. } if !asserted && in_window => {
+                self.state.set(TriggerState::Watching { sampled: now });
+                return;
+            }
+            TriggerState::Watching { .. } => asserted,
+            TriggerState::Holding { sampled, .. } if now - sampled < SAMPLE_INTERVAL_US => {
+                return;
+            }
+            TriggerState::Holding { since, .. } if !asserted => {
+                defmt::println!("Trigger pin released after {} us, ignored", now - since);
+                if in_window {
+                    self.state.set(TriggerState::Watching { sampled: now });
+                    return;
+                }
+                false
+            }
+            TriggerState::Holding { since, .. } if now - since < hold_us => {
+                self.state.set(TriggerState::Holding {
+                    since,
+                    sampled: now,
+                });
+                return;
+            }
+            TriggerState::Holding { .. } => true,
+        };
 
-        if boot::check_update_trigger(gp2_low) {
+        self.state.set(TriggerState::Decided);
+        let update = boot::check_update_trigger(pin_active, ctx.peripherals.reset_reason);
+        boot::record_milestone(boot::Milestone::TriggerDecided);
+        if update {
             defmt::println!("Update mode triggered");
             ctx.events.publish(Event::RequestUpdate);
         } else {
@@ -1,15 +1,35 @@
 // SPDX-License-Identifier: MIT
 // Copyright (c) 2026 ADNT Sarl <info@adnt.io>
 
-//! Update service for firmware updates via USB.
+//! Update service for firmware updates over the host link (USB CDC, or a
+//! UART with the `uart-transport` feature).
+//!
+//! If the transport cannot be brought up, the service enters a last-resort
+//! recovery state instead of dropping the update request: transport
+//! initialization is retried with exponential backoff, the LED service shows
+//! an SOS pattern, and the banks are checked again periodically (e.g. after a
+//! debugger has rewritten flash), booting the first valid image.
 
-use crate::{peripherals, peripherals::Peripherals, services::usb, update};
+use crate::{
+    flash,
+    peripherals::Peripherals,
+    services::transport as transport_service,
+    transport::{self, Transport},
+    update,
+};
 use core::cell::Cell;
 use crispy_common::service::{Event, Service, ServiceContext};
 use embedded_hal::digital::OutputPin;
 use update::UpdateState;
 
-/// Service for handling firmware updates via USB
+/// Delay before the first transport retry; doubled after each failure.
+const TRANSPORT_RETRY_BASE_US: u64 = 1_000_000;
+/// Upper bound on the transport retry delay.
+const TRANSPORT_RETRY_MAX_US: u64 = 30_000_000;
+/// Interval between bank checks while in recovery.
+const BANK_RECHECK_INTERVAL_US: u64 = 10_000_000;
+
+/// Service for handling firmware updates
 pub struct UpdateService {
     state: Cell<UpdateState>,
 }
@@ -19,14 +39,20 @@ pub struct UpdateService {
 enum FsmEvent {
     Tick,
     UpdateRequested,
+    TransportRetryDue,
+    BankRecheckDue,
 }
 
 /// Side effect to execute after a state transition.
 #[derive(Clone, Copy)]
 enum FsmAction {
     None,
-    InitializeUsb,
+    InitializeTransport,
+    RecheckBanks,
     PumpCommandQueue,
+    /// Serve core 1's flash write, then pump the command queue.
+    #[cfg(feature = "dual-core-flash")]
+    PollPersist,
 }
 
 /// Result of one pure FSM transition step.
@@ -53,29 +79,69 @@ impl UpdateService {
         requested
     }
 
-    fn initialize_usb(ctx: &mut ServiceContext<Peripherals>) -> UpdateState {
-        let Some(mut usb) = ctx.peripherals.usb.take() else {
-            defmt::warn!("Update: USB peripheral unavailable during initialization");
-            return UpdateState::Standby;
-        };
-
-        let usb_bus = usb_device::class_prelude::UsbBusAllocator::new(
-            rp2040_hal::usb::UsbBus::new(usb.regs, usb.dpram, usb.clock, true, &mut usb.resets),
-        );
-
-        peripherals::store_usb_bus(usb_bus);
-
-        match crate::usb_transport::UsbTransport::new(peripherals::usb_bus_ref()) {
-            Ok(transport) => {
-                defmt::println!("USB CDC initialized");
+    fn initialize_transport(ctx: &mut ServiceContext<Peripherals>, failures: u8) -> UpdateState {
+        match transport::open(ctx.peripherals) {
+            Some(mut link) => {
+                // Startup blink, only here: it would cost 1.2 s on the boot
+                // path. The link is polled meanwhile, and the first host
+                // activity ends the blink so the host is not kept waiting.
+                if failures == 0 {
+                    crispy_common::blink_until(
+                        &mut ctx.peripherals.led_pin,
+                        &mut ctx.peripherals.timer,
+                        3,
+                        200,
+                        || link.poll(),
+                    );
+                }
                 ctx.peripherals.led_pin.set_high().ok();
-                usb::store_transport(transport);
+                ctx.events.publish(Event::UpdateModeEntered);
+                transport_service::store_transport(link);
                 UpdateState::Ready
             }
-            Err(e) => {
-                defmt::error!("Failed to initialize USB transport: {:?}", e);
-                UpdateState::Standby
-            }
+            None => Self::enter_recovery(ctx, failures),
+        }
+    }
+
+    /// Schedule the next transport attempt after `failures` earlier failed ones.
+    fn enter_recovery(ctx: &mut ServiceContext<Peripherals>, failures: u8) -> UpdateState {
+        let now = ctx.peripherals.timer.get_counter().ticks();
+        let delay_us = TRANSPORT_RETRY_BASE_US
+            .saturating_mul(1 << failures.min(15))
+            .min(TRANSPORT_RETRY_MAX_US);
+        if failures == 0 {
+            defmt::error!("Update: transport unavailable, entering recovery mode");
+            ctx.events.publish(Event::RecoveryEntered);
+        }
+        defmt::warn!(
+            "Update: transport attempt {} failed, retrying in {} ms",
+            u32::from(failures) + 1,
+            delay_us / 1000
+        );
+        UpdateState::Recovery {
+            failures: failures.saturating_add(1),
+            retry_at_us: now + delay_us,
+            recheck_at_us: now + BANK_RECHECK_INTERVAL_US,
+        }
+    }
+
+    /// Ask the main loop to try a normal boot again; it publishes a new
+    /// update request, dropped in recovery, when nothing is bootable.
+    fn recheck_banks(ctx: &mut ServiceContext<Peripherals>, state: UpdateState) -> UpdateState {
+        let UpdateState::Recovery {
+            failures,
+            retry_at_us,
+            ..
+        } = state
+        else {
+            return state;
+        };
+        defmt::println!("Recovery: checking the banks again");
+        ctx.events.publish(Event::RequestBoot);
+        UpdateState::Recovery {
+            failures,
+            retry_at_us,
+            recheck_at_us: ctx.peripherals.timer.get_counter().ticks() + BANK_RECHECK_INTERVAL_US,
         }
     }
 
@@ -83,16 +149,20 @@ impl UpdateService {
         ctx: &mut ServiceContext<Peripherals>,
         state: UpdateState,
     ) -> UpdateState {
-        let Some(cmd) = usb::pop_command() else {
+        let Some(cmd) = transport_service::pop_command() else {
             return state;
         };
 
         defmt::println!("Update: Dequeued command from queue");
         let t_start = ctx.peripherals.timer.get_counter().ticks();
 
-        let Some(new_state) = usb::with_transport(|transport| {
+        let sensors = &mut ctx.peripherals.sensors;
+        let reset_reason = ctx.peripherals.reset_reason;
+        let Some(new_state) = transport_service::with_transport(|transport| {
             defmt::println!("Update: Dispatching command");
-            update::dispatch_command(transport, state, cmd)
+            // SAFETY: `flash::init()` runs before any service.
+            let mut rom_flash = unsafe { flash::RomFlash::new() };
+            update::dispatch_command(transport, sensors, reset_reason, &mut rom_flash, state, cmd)
         }) else {
             defmt::error!("Update: with_transport returned None!");
             return state;
@@ -110,27 +180,61 @@ impl UpdateService {
     fn transition(state: UpdateState, event: FsmEvent) -> FsmStep {
         match (state, event) {
             (UpdateState::Standby, FsmEvent::UpdateRequested) => FsmStep {
-                next_state: UpdateState::InitializingUsb,
+                next_state: UpdateState::InitializingTransport,
                 action: FsmAction::None,
             },
-            (UpdateState::Standby, FsmEvent::Tick) => FsmStep {
+            (UpdateState::Standby, _) => FsmStep {
                 next_state: UpdateState::Standby,
                 action: FsmAction::None,
             },
-            (UpdateState::InitializingUsb, _) => FsmStep {
-                next_state: UpdateState::InitializingUsb,
-                action: FsmAction::InitializeUsb,
+            (UpdateState::InitializingTransport, _) => FsmStep {
+                next_state: UpdateState::InitializingTransport,
+                action: FsmAction::InitializeTransport,
+            },
+            (UpdateState::Recovery { .. }, FsmEvent::TransportRetryDue) => FsmStep {
+                next_state: state,
+                action: FsmAction::InitializeTransport,
+            },
+            (UpdateState::Recovery { .. }, FsmEvent::BankRecheckDue) => FsmStep {
+                next_state: state,
+                action: FsmAction::RecheckBanks,
+            },
+            (UpdateState::Recovery { .. }, _) => FsmStep {
+                next_state: state,
+                action: FsmAction::None,
             },
-            (UpdateState::Ready | UpdateState::ReceivingData { .. }, _) => FsmStep {
+            (UpdateState::Ready | UpdateState::ReceivingData(_), _) => FsmStep {
                 next_state: state,
                 action: FsmAction::PumpCommandQueue,
             },
+            #[cfg(feature = "dual-core-flash")]
+            (UpdateState::Persisting { .. }, _) => FsmStep {
+                next_state: state,
+                action: FsmAction::PollPersist,
+            },
         }
     }
 
     fn detect_event(ctx: &mut ServiceContext<Peripherals>, state: UpdateState) -> FsmEvent {
         match state {
             UpdateState::Standby if Self::consume_update_request(ctx) => FsmEvent::UpdateRequested,
+            UpdateState::Recovery {
+                retry_at_us,
+                recheck_at_us,
+                ..
+            } => {
+                // Already in update mode: a request from a failed bank check
+                // changes nothing.
+                Self::consume_update_request(ctx);
+                let now = ctx.peripherals.timer.get_counter().ticks();
+                if now >= retry_at_us {
+                    FsmEvent::TransportRetryDue
+                } else if now >= recheck_at_us {
+                    FsmEvent::BankRecheckDue
+                } else {
+                    FsmEvent::Tick
+                }
+            }
             _ => FsmEvent::Tick,
         }
     }
@@ -142,8 +246,25 @@ impl UpdateService {
     ) -> UpdateState {
         match action {
             FsmAction::None => state,
-            FsmAction::InitializeUsb => Self::initialize_usb(ctx),
+            FsmAction::InitializeTransport => {
+                let failures = match state {
+                    UpdateState::Recovery { failures, .. } => failures,
+                    _ => 0,
+                };
+                Self::initialize_transport(ctx, failures)
+            }
+            FsmAction::RecheckBanks => Self::recheck_banks(ctx, state),
             FsmAction::PumpCommandQueue => Self::process_pending_command(ctx, state),
+            #[cfg(feature = "dual-core-flash")]
+            FsmAction::PollPersist => {
+                let state = transport_service::with_transport(|transport| {
+                    // SAFETY: `flash::init()` runs before any service.
+                    let mut rom_flash = unsafe { flash::RomFlash::new() };
+                    update::poll_persist(transport, &mut rom_flash, state)
+                })
+                .unwrap_or(state);
+                Self::process_pending_command(ctx, state)
+            }
         }
     }
 
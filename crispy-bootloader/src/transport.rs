@@ -0,0 +1,174 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Link-independent side of the update protocol: the `Transport` trait and
+//! the COBS-framed postcard encoding shared by the USB CDC and UART links.
+//!
+//! One link is built in: USB CDC by default, a UART with the
+//! `uart-transport` feature. `ActiveTransport` and `open` name it.
+
+use crate::error_log;
+use crispy_common::protocol::{
+    Command, ErrorCode, LegacyCommand, Response, MAX_COMMAND_FRAME_SIZE, MAX_RESPONSE_FRAME_SIZE,
+};
+
+#[cfg(feature = "uart-transport")]
+pub use crate::uart_transport::{open, UartTransport as ActiveTransport};
+#[cfg(not(feature = "uart-transport"))]
+pub use crate::usb_transport::{open, UsbTransport as ActiveTransport};
+
+/// Holds one COBS frame without its delimiter; longer frames cannot be a
+/// valid command and are discarded as overflows.
+pub const RX_BUF_SIZE: usize = MAX_COMMAND_FRAME_SIZE;
+/// One encoded response including its `0x00` delimiter.
+pub const TX_BUF_SIZE: usize = MAX_RESPONSE_FRAME_SIZE + 1;
+
+// A full DataBlock must never be truncated by the RX buffer.
+const _: () = assert!(RX_BUF_SIZE >= MAX_COMMAND_FRAME_SIZE);
+
+/// Why a response was not fully sent.
+///
+/// A UART send always completes, so only `Encode` occurs with
+/// `uart-transport`.
+#[cfg_attr(feature = "uart-transport", allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum SendError {
+    /// The response does not fit in the TX buffer.
+    Encode,
+    /// The host stopped reading: no TX space within the poll budget. `sent`
+    /// bytes of the frame were queued; the next `send` terminates it so the
+    /// host drops it instead of merging it with the next frame.
+    Stalled { sent: usize, total: usize },
+    /// The USB stack reported an error.
+    Usb,
+}
+
+/// Error counters reported by `GetTransportStats`. They wrap on overflow.
+#[derive(Debug, Default, Clone, Copy, defmt::Format)]
+pub struct TransportStats {
+    pub rx_overflows: u32,
+    pub tx_drops: u32,
+    pub decode_errors: u32,
+    pub frames_received: u32,
+}
+
+/// A link to the host carrying COBS-framed postcard messages.
+pub trait Transport {
+    /// Service the link. Must be called frequently; returns whether there
+    /// was activity.
+    fn poll(&mut self) -> bool;
+
+    /// Return the next complete command, if one has arrived.
+    fn try_receive(&mut self) -> Option<Command>;
+
+    /// Send a response as one frame.
+    ///
+    /// On `SendError::Stalled` the caller may send the same response again:
+    /// the truncated copy is terminated first and fails to decode on the host.
+    fn send(&mut self, resp: &Response) -> Result<(), SendError>;
+
+    /// Counters since the transport was created.
+    fn stats(&self) -> TransportStats;
+}
+
+/// Accumulates received bytes into COBS frames and decodes them.
+pub struct FrameBuffer {
+    buf: [u8; RX_BUF_SIZE],
+    pos: usize,
+}
+
+impl FrameBuffer {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0u8; RX_BUF_SIZE],
+            pos: 0,
+        }
+    }
+
+    /// Bytes that can still be appended to the current frame.
+    #[cfg_attr(feature = "uart-transport", allow(dead_code))]
+    pub fn free(&self) -> usize {
+        RX_BUF_SIZE - self.pos
+    }
+
+    /// Process one received byte. Returns `Some(Command)` when it completes
+    /// a frame that decodes.
+    pub fn push(&mut self, byte: u8, stats: &mut TransportStats) -> Option<Command> {
+        match byte {
+            // COBS frame delimiter
+            0x00 => self.decode(stats),
+            _ if self.pos < RX_BUF_SIZE => {
+                self.buf[self.pos] = byte;
+                self.pos += 1;
+                None
+            }
+            _ => {
+                self.discard(stats);
+                None
+            }
+        }
+    }
+
+    /// Drop the frame in progress after bytes of it were lost, counting it
+    /// as an RX overflow.
+    pub fn discard(&mut self, stats: &mut TransportStats) {
+        error_log::record(ErrorCode::RxOverflow);
+        stats.rx_overflows = stats.rx_overflows.wrapping_add(1);
+        self.pos = 0;
+    }
+
+    /// Try to decode the accumulated frame as a Command.
+    fn decode(&mut self, stats: &mut TransportStats) -> Option<Command> {
+        if self.pos == 0 {
+            return None;
+        }
+
+        let result = cobs::decode_in_place(&mut self.buf[..self.pos])
+            .map_err(|_| postcard::Error::DeserializeBadEncoding)
+            .and_then(|len| decode_command(&self.buf[..len]));
+        self.pos = 0;
+        match result {
+            Ok(cmd) => {
+                stats.frames_received = stats.frames_received.wrapping_add(1);
+                Some(cmd)
+            }
+            Err(_) => {
+                error_log::record(ErrorCode::DecodeFailed);
+                stats.decode_errors = stats.decode_errors.wrapping_add(1);
+                None
+            }
+        }
+    }
+}
+
+/// Decode a Command, falling back to the layout of hosts that predate
+/// fields appended to it (such as `StartUpdate::crc_algo`).
+fn decode_command(bytes: &[u8]) -> postcard::Result<Command> {
+    match postcard::from_bytes::<Command>(bytes) {
+        Err(postcard::Error::DeserializeUnexpectedEnd) => {
+            postcard::from_bytes::<LegacyCommand>(bytes).map(LegacyCommand::upgrade)
+        }
+        result => result,
+    }
+}
+
+/// Encode a response as a COBS frame, delimiter included, into `buf`.
+pub fn encode<'a>(resp: &Response, buf: &'a mut [u8; TX_BUF_SIZE]) -> Result<&'a [u8], SendError> {
+    // Forwarded log chunks are not logged, or each would queue another.
+    let quiet = cfg!(feature = "log-stream") && matches!(resp, Response::Log { .. });
+    if !quiet {
+        defmt::println!("Transport: Sending response");
+    }
+    match postcard::to_slice_cobs(resp, buf) {
+        Ok(data) => {
+            if !quiet {
+                defmt::println!("Transport: Encoded {} bytes", data.len());
+            }
+            Ok(data)
+        }
+        Err(_) => {
+            error_log::record(ErrorCode::EncodeFailed);
+            Err(SendError::Encode)
+        }
+    }
+}
@@ -0,0 +1,100 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! UART transport with COBS-framed postcard serialization, for boards
+//! without USB (`uart-transport` feature).
+//!
+//! The line runs 8N1 without flow control. The 32-byte RX FIFO only holds a
+//! few milliseconds of data, which is enough because the host waits for each
+//! response before sending the next command: nothing arrives while a command
+//! is being executed.
+
+use crate::peripherals::{Peripherals, UartDevice, UartPins, UART};
+use crate::transport::{self, FrameBuffer, SendError, Transport, TransportStats, TX_BUF_SIZE};
+use crispy_common::protocol::{Command, Response};
+use rp2040_hal::fugit::RateExtU32;
+use rp2040_hal::uart::{DataBits, Enabled, StopBits, UartConfig, UartPeripheral};
+
+pub struct UartTransport {
+    uart: UartPeripheral<Enabled, UartDevice, UartPins>,
+    rx: FrameBuffer,
+    stats: TransportStats,
+}
+
+impl UartTransport {
+    pub fn new(uart: UartPeripheral<Enabled, UartDevice, UartPins>) -> Self {
+        Self {
+            uart,
+            rx: FrameBuffer::new(),
+            stats: TransportStats::default(),
+        }
+    }
+}
+
+impl Transport for UartTransport {
+    /// Report whether received bytes are waiting in the FIFO.
+    fn poll(&mut self) -> bool {
+        self.uart.uart_is_readable()
+    }
+
+    /// Drain the RX FIFO into the frame buffer until a command completes.
+    ///
+    /// Bytes are taken one at a time so those after a completed frame stay
+    /// in the FIFO for the next call.
+    fn try_receive(&mut self) -> Option<Command> {
+        let mut byte = [0u8; 1];
+        while self.uart.uart_is_readable() {
+            match self.uart.read_raw(&mut byte) {
+                Ok(_) => {
+                    if let Some(cmd) = self.rx.push(byte[0], &mut self.stats) {
+                        return Some(cmd);
+                    }
+                }
+                // Break, framing, parity or FIFO overrun: the frame in
+                // progress lost or garbled a byte.
+                Err(_) => self.rx.discard(&mut self.stats),
+            }
+        }
+        None
+    }
+
+    /// Send a response, blocking until it is in the TX FIFO.
+    ///
+    /// Without flow control the UART always drains at the line rate, so a
+    /// send cannot stall.
+    fn send(&mut self, resp: &Response) -> Result<(), SendError> {
+        let mut buf = [0u8; TX_BUF_SIZE];
+        let encoded = transport::encode(resp, &mut buf)?;
+        self.uart.write_full_blocking(encoded);
+        Ok(())
+    }
+
+    fn stats(&self) -> TransportStats {
+        self.stats
+    }
+}
+
+/// Enable the update UART on the pins and baud rate from `CRISPY_UART_*`.
+pub fn open(peripherals: &mut Peripherals) -> Option<UartTransport> {
+    let Some(mut uart) = peripherals.uart.take() else {
+        defmt::warn!("Update: UART unavailable during initialization");
+        return None;
+    };
+
+    let config = UartConfig::new(UART.baud.Hz(), DataBits::Eight, None, StopBits::One);
+    match UartPeripheral::new(uart.device, uart.pins, &mut uart.resets).enable(config, uart.clock) {
+        Ok(enabled) => {
+            defmt::println!(
+                "UART initialized: {} baud, TX GPIO{}, RX GPIO{}",
+                UART.baud,
+                UART.tx,
+                UART.rx
+            );
+            Some(UartTransport::new(enabled))
+        }
+        Err(_) => {
+            defmt::error!("UART cannot run at {} baud", UART.baud);
+            None
+        }
+    }
+}
@@ -8,11 +8,21 @@
 //! - `GetStatus`: Query current bootloader state
 //! - `StartUpdate`: Begin firmware upload to a bank
 //! - `DataBlock`: Send firmware data chunks (accumulated in RAM)
-//! - `FinishUpdate`: Persist to flash, verify CRC and commit the update
+//! - `FinishUpdate`: Persist to flash, verify CRC and commit the update (on
+//!   core 1 with `dual-core-flash`, answering `GetStatus` meanwhile)
 //! - `Reboot`: Restart the device
+//! - `StartBootloaderUpdate`: Stage a new bootloader and copy it over the running one
+//! - `GetTelemetry`: Read the chip temperature and VSYS
+//! - `Ping`: Echo a nonce for liveness checks
+//! - `GetFlashMap`: Report the flash layout and how much of it is used
+//! - `GetResetReason`: Report why the chip last reset
 mod commands;
+mod self_update;
 mod state;
 mod storage;
 
 pub use commands::dispatch_command;
+#[cfg(feature = "dual-core-flash")]
+pub use commands::poll_persist;
+pub use self_update::resume_staged_update;
 pub use state::UpdateState;
@@ -1,21 +1,83 @@
 // SPDX-License-Identifier: MIT
 // Copyright (c) 2026 ADNT Sarl <info@adnt.io>
 
-use super::{state::UpdateState, storage};
-use crate::flash;
-use crate::usb_transport::UsbTransport;
+use super::{self_update, state::UpdateState, storage};
+use crate::peripherals::Sensors;
+use crate::transport::{SendError, Transport};
+use crate::wear;
+use crate::{boot, error_log, flash};
+use crispy_common::boot_journal;
+use crispy_common::bootloader_image::validate_bootloader_image;
+use crispy_common::crc32::CrcAlgorithm;
+use crispy_common::flash_map::with_free_gaps;
+use crispy_common::flash_ops::{self, FlashError, FlashOps};
+use crispy_common::image_header::{self, IMAGE_HEADER_SIZE};
+use crispy_common::protocol::FLASH_SECTOR_SIZE;
 use crispy_common::protocol::{
-    parse_semver, AckStatus, BootData, Command, Response, FW_A_ADDR, FW_BANK_SIZE, FW_B_ADDR,
+    AckStatus, BootData, Command, DataInfo, ErrorCode, FirmwareMetadata, FlashRegion,
+    FlashRegionKind, ProgressPhase, Response, BOOTLOADER_REGION_SIZE, BOOT_DATA_MIRROR_ADDR,
+    BOOT_FLAG_ANTI_ROLLBACK, BUILD_FEATURE_DUAL_CORE_FLASH, BUILD_FEATURE_GOLDEN_BANK,
+    BUILD_FEATURE_LOGGING, BUILD_FEATURE_LOG_STREAM, BUILD_FEATURE_SKIP_BOOT_CRC, CONFIG_ADDR,
+    CONFIG_SLOTS, DATA_ADDR, DATA_BANK, DATA_INFO_ADDR, DATA_MAX_IMAGE_SIZE, FW_A_ADDR,
+    FW_BANK_SIZE, FW_B_ADDR, MAX_BOOT_ATTEMPTS_UNSET, MAX_DATA_BLOCK_SIZE, MAX_FLASH_REGIONS,
+    SELFTEST_PASSED, SELFTEST_SCRATCH_ADDR,
 };
+#[cfg(feature = "golden-bank")]
+use crispy_common::protocol::{
+    GoldenInfo, BOOT_FLAG_GOLDEN, FW_GOLD_ADDR, GOLDEN_BANK, GOLDEN_INFO_ADDR,
+    GOLDEN_MAX_IMAGE_SIZE,
+};
+use crispy_common::reset_reason::ResetReason;
+use crispy_common::update::{self, Handled, ImageLimits, PendingImage, UpdateError, Upload};
 
-const BOOTLOADER_VERSION: &str = env!("CRISPY_VERSION");
+/// `UpdateState::ReceivingData::bank` while receiving a bootloader image.
+const BOOTLOADER_STAGING: u8 = 0xFF;
 
-fn bank_addr(bank: u8) -> Option<u32> {
-    match bank {
-        0 => Some(FW_A_ADDR),
-        1 => Some(FW_B_ADDR),
-        _ => None,
+/// Activity LED (GP25) toggled by the ROM USB bootloader.
+const BOOTROM_ACTIVITY_LED_MASK: u32 = 1 << 25;
+
+/// Git short hash from `build.rs`, NUL-padded to 8 bytes.
+const GIT_HASH: [u8; 8] = {
+    let src = env!("CRISPY_GIT_HASH").as_bytes();
+    let mut hash = [0u8; 8];
+    let mut i = 0;
+    while i < src.len() && i < hash.len() {
+        hash[i] = src[i];
+        i += 1;
     }
+    hash
+};
+
+const BUILD_EPOCH: u32 = match u32::from_str_radix(env!("CRISPY_BUILD_EPOCH"), 10) {
+    Ok(epoch) => epoch,
+    Err(_) => panic!("CRISPY_BUILD_EPOCH is not a u32"),
+};
+
+/// Capabilities compiled into this build (`BUILD_FEATURE_*`).
+const BUILD_FEATURES: u32 = BUILD_FEATURE_LOGGING
+    | if cfg!(feature = "skip-boot-crc") {
+        BUILD_FEATURE_SKIP_BOOT_CRC
+    } else {
+        0
+    }
+    | if cfg!(feature = "golden-bank") {
+        BUILD_FEATURE_GOLDEN_BANK
+    } else {
+        0
+    }
+    | if cfg!(feature = "log-stream") {
+        BUILD_FEATURE_LOG_STREAM
+    } else {
+        0
+    }
+    | if cfg!(feature = "dual-core-flash") {
+        BUILD_FEATURE_DUAL_CORE_FLASH
+    } else {
+        0
+    };
+
+fn bank_addr(bank: u8) -> Option<u32> {
+    flash_ops::bank_range(bank).map(|(addr, _)| addr)
 }
 
 fn bank_firmware_info(bd: &BootData, bank: u8) -> Option<(u32, u32)> {
@@ -26,57 +88,577 @@ fn bank_firmware_info(bd: &BootData, bank: u8) -> Option<(u32, u32)> {
     }
 }
 
-fn send_ack(transport: &mut UsbTransport, status: AckStatus) {
-    let _ = transport.send(&Response::Ack(status));
+fn bank_version(bd: &BootData, bank: u8) -> u32 {
+    if bank == 0 {
+        bd.version_a
+    } else {
+        bd.version_b
+    }
 }
 
-fn reject_with(transport: &mut UsbTransport, status: AckStatus, state: UpdateState) -> UpdateState {
+/// Send a final response, retrying once if the host stalled mid-frame.
+///
+/// The host waits for exactly one final response per command, so losing it
+/// hangs the host until its read timeout. A stalled copy is terminated by the
+/// retry and fails to decode on the host, which then reads the retried copy.
+fn respond(transport: &mut dyn Transport, resp: &Response) {
+    if let Err(SendError::Stalled { sent, total }) = transport.send(resp) {
+        defmt::debug!("Response stalled after {}/{} bytes, retrying", sent, total);
+        // A failed retry is recorded by the transport as well.
+        let _ = transport.send(resp);
+    }
+}
+
+/// Metadata from the image header at `addr`, if the image has a valid one.
+fn image_metadata(addr: u32, bank_size: u32) -> Option<FirmwareMetadata> {
+    let first_bytes = unsafe { (addr as *const [u8; IMAGE_HEADER_SIZE]).read_volatile() };
+    image_header::parse_header(&first_bytes, bank_size)
+        .ok()
+        .flatten()
+        .map(|header| header.metadata())
+}
+
+fn send_ack(transport: &mut dyn Transport, status: AckStatus) {
+    respond(transport, &Response::Ack(status));
+}
+
+/// Streams `Response::Progress` frames, skipping updates that don't change the
+/// percentage. Called between flash operations, it also services the link.
+struct ProgressReporter<'a> {
+    transport: &'a mut dyn Transport,
+    last: Option<(ProgressPhase, u8)>,
+}
+
+impl<'a> ProgressReporter<'a> {
+    fn new(transport: &'a mut dyn Transport) -> Self {
+        Self {
+            transport,
+            last: None,
+        }
+    }
+
+    /// Carry on from an earlier reporter whose last frame was `last`.
+    #[cfg(feature = "dual-core-flash")]
+    fn resume(transport: &'a mut dyn Transport, last: Option<(ProgressPhase, u8)>) -> Self {
+        Self { transport, last }
+    }
+
+    fn report(&mut self, phase: ProgressPhase, done: u32, total: u32) {
+        // Interrupts are enabled between sector erases and page batches, so
+        // USB can answer the host's control requests here.
+        self.transport.poll();
+        let percent = if total == 0 {
+            100
+        } else {
+            (u64::from(done) * 100 / u64::from(total)).min(100) as u8
+        };
+
+        if self.last == Some((phase, percent)) {
+            return;
+        }
+        self.last = Some((phase, percent));
+        let _ = self.transport.send(&Response::Progress { phase, percent });
+    }
+}
+
+/// Append `bd` to the BootData journal: `Ok`, or `FlashError` if the record
+/// did not read back, in which case the previous one stays in effect.
+fn store_boot_data(flash_ops: &mut impl FlashOps, bd: &BootData) -> AckStatus {
+    match flash_ops.write_boot_data(bd) {
+        Ok(()) => AckStatus::Ok,
+        Err(err) => err.into(),
+    }
+}
+
+fn reject_with(
+    transport: &mut dyn Transport,
+    status: AckStatus,
+    state: UpdateState,
+) -> UpdateState {
     send_ack(transport, status);
     state
 }
 
+/// Answer an update command `err` refused: log it and `Ack` its status, then
+/// carry on with `state` if the upload in progress survives the error, or
+/// from `Ready`.
+fn reject_update(
+    transport: &mut dyn Transport,
+    err: UpdateError,
+    state: UpdateState,
+) -> UpdateState {
+    if let Some(code) = err.error_code() {
+        error_log::record(code);
+    }
+    defmt::debug!("Update command refused: {}", err);
+    send_ack(transport, err.into());
+    if err.keeps_upload() {
+        state
+    } else {
+        UpdateState::Ready
+    }
+}
+
+/// What uploads are checked against, from the linker script and the build.
+fn image_limits() -> ImageLimits {
+    boot::MemoryLayout::from_linker().image_limits(&boot::BootConfig::from_features())
+}
+
 /// Dispatch a command to its handler.
 pub fn dispatch_command(
-    transport: &mut UsbTransport,
+    transport: &mut dyn Transport,
+    sensors: &mut Sensors,
+    reset_reason: ResetReason,
+    flash_ops: &mut impl FlashOps,
     state: UpdateState,
     cmd: Command,
 ) -> UpdateState {
+    // Core 1 owns flash until the image is written.
+    #[cfg(feature = "dual-core-flash")]
+    if matches!(state, UpdateState::Persisting { .. })
+        && !matches!(cmd, Command::GetStatus | Command::Ping { .. })
+    {
+        return reject_with(transport, AckStatus::Busy, state);
+    }
+
     match cmd {
         Command::GetStatus => handle_get_status(transport, state),
-        Command::StartUpdate {
-            bank,
+        Command::StartUpdate { .. }
+        | Command::DataBlock { .. }
+        | Command::FinishUpdate
+        | Command::FinishUpdateNoActivate
+        | Command::AbortUpdate
+        | Command::SetActiveBank { .. }
+        | Command::SetBankLock { .. }
+        | Command::WipeAll => handle_update_command(transport, flash_ops, state, &cmd),
+        Command::Reboot => handle_reboot(transport),
+        Command::EnterBootrom => handle_enter_bootrom(transport),
+        Command::GetWearStats => handle_get_wear_stats(transport, state),
+        Command::GetBankInfo { bank } => handle_get_bank_info(transport, state, bank),
+        Command::CopyBank { from, to } => handle_copy_bank(transport, flash_ops, state, from, to),
+        Command::GetBuildInfo => handle_get_build_info(transport, state),
+        Command::ConfirmBoot => handle_confirm_boot(transport, flash_ops, state),
+        Command::GetTransportStats => handle_get_transport_stats(transport, state),
+        Command::WriteGolden {
             size,
             crc32,
             version,
-        } => handle_start_update(transport, state, bank, size, crc32, version),
-        Command::DataBlock { offset, data } => {
-            handle_data_block(transport, state, offset, data.as_slice())
+        } => handle_write_golden(transport, state, size, crc32, version),
+        Command::StartBootloaderUpdate { size, crc32 } => {
+            handle_start_bootloader_update(transport, state, size, crc32)
         }
-        Command::FinishUpdate => handle_finish_update(transport, state),
-        Command::Reboot => handle_reboot(transport),
-        Command::SetActiveBank { bank } => handle_set_active_bank(transport, state, bank),
-        Command::WipeAll => handle_wipe_all(transport, state),
+        Command::EnableAntiRollback => handle_enable_anti_rollback(transport, flash_ops, state),
+        Command::GetTelemetry => handle_get_telemetry(transport, sensors, state),
+        Command::Ping { nonce } => {
+            respond(transport, &Response::Pong { nonce });
+            state
+        }
+        Command::GetFlashMap => handle_get_flash_map(transport, state),
+        Command::GetErrorLog => {
+            let (total, recent) = error_log::snapshot();
+            respond(transport, &Response::ErrorLog { total, recent });
+            state
+        }
+        Command::VerifyBank { bank, expected_crc } => {
+            handle_verify_bank(transport, flash_ops, state, bank, expected_crc)
+        }
+        Command::SetBootPolicy { max_attempts } => {
+            handle_set_boot_policy(transport, flash_ops, state, max_attempts)
+        }
+        Command::StreamLogs { enable } => handle_stream_logs(transport, state, enable),
+        Command::ConfigGet { key } => {
+            match flash::read_config(key) {
+                Some(value) => respond(transport, &Response::ConfigValue { key, value }),
+                None => send_ack(transport, AckStatus::BadCommand),
+            }
+            state
+        }
+        Command::ConfigSet { key, value } => handle_config_set(transport, state, key, value),
+        Command::CompareBanks => handle_compare_banks(transport, flash_ops, state),
+        Command::GetResetReason => {
+            respond(
+                transport,
+                &Response::ResetReason {
+                    reason: reset_reason.id(),
+                },
+            );
+            state
+        }
+        Command::ReadBank { bank, offset, len } => {
+            handle_read_bank(transport, flash_ops, state, bank, offset, len)
+        }
+        Command::FlashSelfTest => handle_flash_self_test(transport, flash_ops, state),
     }
 }
 
 /// Handle `GetStatus` command: return current bootloader status.
-fn handle_get_status(transport: &mut UsbTransport, state: UpdateState) -> UpdateState {
+fn handle_get_status(transport: &mut dyn Transport, state: UpdateState) -> UpdateState {
     let bd = flash::read_boot_data();
-    let _ = transport.send(&Response::Status {
-        active_bank: bd.active_bank,
-        version_a: bd.version_a,
-        version_b: bd.version_b,
-        state: state.as_boot_state(),
-        bootloader_version: parse_semver(BOOTLOADER_VERSION),
-    });
+    respond(
+        transport,
+        &Response::Status {
+            active_bank: bd.active_bank,
+            version_a: bd.version_a,
+            version_b: bd.version_b,
+            state: state.as_boot_state(),
+            bootloader_version: Some(boot::BOOTLOADER_VERSION),
+            max_data_block_size: Some(MAX_DATA_BLOCK_SIZE as u32),
+            confirmed: Some(bd.confirmed != 0),
+            boot_attempts: Some(bd.boot_attempts),
+            max_boot_attempts: Some(bd.boot_attempt_limit()),
+            fell_back: Some(bd.fell_back()),
+            min_version: bd.rollback_floor(),
+            persist_percent: state.persist_percent(),
+        },
+    );
+    state
+}
+
+/// Handle `GetWearStats` command: return flash erase counters.
+fn handle_get_wear_stats(transport: &mut dyn Transport, state: UpdateState) -> UpdateState {
+    let stats = wear::read();
+    respond(
+        transport,
+        &Response::WearStats {
+            bank_a_erases: stats.bank_a_erases,
+            bank_b_erases: stats.bank_b_erases,
+            bootdata_erases: stats.bootdata_erases,
+        },
+    );
+    state
+}
+
+/// Handle `GetBuildInfo` command: identify the exact bootloader build.
+fn handle_get_build_info(transport: &mut dyn Transport, state: UpdateState) -> UpdateState {
+    respond(
+        transport,
+        &Response::BuildInfo {
+            git_hash: GIT_HASH,
+            build_epoch: BUILD_EPOCH,
+            features: BUILD_FEATURES,
+        },
+    );
+    state
+}
+
+/// Handle `GetTransportStats` command: return transport counters.
+fn handle_get_transport_stats(transport: &mut dyn Transport, state: UpdateState) -> UpdateState {
+    let stats = transport.stats();
+    respond(
+        transport,
+        &Response::TransportStats {
+            rx_overflows: stats.rx_overflows,
+            tx_drops: stats.tx_drops,
+            decode_errors: stats.decode_errors,
+            frames_received: stats.frames_received,
+        },
+    );
+    state
+}
+
+/// Handle `GetTelemetry` command: sample the temperature sensor and VSYS.
+fn handle_get_telemetry(
+    transport: &mut dyn Transport,
+    sensors: &mut Sensors,
+    state: UpdateState,
+) -> UpdateState {
+    let (temp_c_milli, vsys_mv) = sensors.read();
+    respond(
+        transport,
+        &Response::Telemetry {
+            temp_c_milli,
+            vsys_mv,
+        },
+    );
+    state
+}
+
+/// Handle `GetFlashMap` command: list the linker script regions with their
+/// usage, and the unassigned flash between them.
+fn handle_get_flash_map(transport: &mut dyn Transport, state: UpdateState) -> UpdateState {
+    let layout = boot::MemoryLayout::from_linker();
+    let bd = flash::read_boot_data();
+    let region = |start, len, used, kind| FlashRegion {
+        start,
+        len,
+        used,
+        kind,
+    };
+    let bootloader_start = layout.flash_base + layout.boot2_size;
+    let wear_stats = layout.boot_data + layout.boot_data_size;
+
+    let mut assigned: heapless::Vec<FlashRegion, MAX_FLASH_REGIONS> = heapless::Vec::new();
+    let _ = assigned.extend_from_slice(&[
+        region(
+            layout.flash_base,
+            layout.boot2_size,
+            layout.boot2_size,
+            FlashRegionKind::Boot2,
+        ),
+        region(
+            bootloader_start,
+            layout.bootloader_size - layout.boot2_size,
+            layout.bootloader_image_end() - bootloader_start,
+            FlashRegionKind::Bootloader,
+        ),
+        region(
+            layout.fw_a,
+            layout.bank_size,
+            bd.size_a,
+            FlashRegionKind::BankA,
+        ),
+        region(
+            layout.fw_b,
+            layout.bank_size,
+            bd.size_b,
+            FlashRegionKind::BankB,
+        ),
+        region(
+            layout.boot_data,
+            layout.boot_data_size,
+            boot_journal::used_bytes(flash::boot_data_sector()),
+            FlashRegionKind::BootData,
+        ),
+        region(
+            wear_stats,
+            layout.boot_data_size,
+            wear::log_bytes(),
+            FlashRegionKind::WearStats,
+        ),
+    ]);
+    // The data partition, BootData mirror and config sector split the golden
+    // bank: its images end below them, and the golden info record takes the last sector.
+    // The self-test scratch sector sits in the data partition, below its info sector.
+    #[cfg(feature = "golden-bank")]
+    let golden_info = flash::read_golden_info();
+    #[cfg(feature = "golden-bank")]
+    let _ = assigned.push(region(
+        layout.fw_gold,
+        GOLDEN_MAX_IMAGE_SIZE,
+        golden_info.map_or(0, |info| info.size),
+        FlashRegionKind::Golden,
+    ));
+    let data_info = flash::read_data_info();
+    let _ = assigned.push(region(
+        DATA_ADDR,
+        DATA_MAX_IMAGE_SIZE,
+        data_info.map_or(0, |info| info.size),
+        FlashRegionKind::Data,
+    ));
+    let _ = assigned.push(region(
+        SELFTEST_SCRATCH_ADDR,
+        FLASH_SECTOR_SIZE,
+        0,
+        FlashRegionKind::SelfTest,
+    ));
+    let _ = assigned.push(region(
+        DATA_INFO_ADDR,
+        FLASH_SECTOR_SIZE,
+        data_info.map_or(0, |_| FLASH_SECTOR_SIZE),
+        FlashRegionKind::Data,
+    ));
+    let _ = assigned.push(region(
+        BOOT_DATA_MIRROR_ADDR,
+        FLASH_SECTOR_SIZE,
+        boot_journal::used_bytes(flash::boot_data_mirror_sector()),
+        FlashRegionKind::BootData,
+    ));
+    let _ = assigned.push(region(
+        CONFIG_ADDR,
+        FLASH_SECTOR_SIZE,
+        u32::from(CONFIG_SLOTS) * 4,
+        FlashRegionKind::Config,
+    ));
+    #[cfg(feature = "golden-bank")]
+    let _ = assigned.push(region(
+        GOLDEN_INFO_ADDR,
+        FLASH_SECTOR_SIZE,
+        golden_info.map_or(0, |_| FLASH_SECTOR_SIZE),
+        FlashRegionKind::Golden,
+    ));
+
+    let regions = with_free_gaps(
+        &assigned,
+        layout.flash_base,
+        layout.flash_base + layout.flash_size,
+    );
+    respond(transport, &Response::FlashMap { regions });
     state
 }
 
-/// Handle `StartUpdate` command: validate parameters, erase bank, begin receiving.
-fn handle_start_update(
-    transport: &mut UsbTransport,
+/// Handle `GetBankInfo` command: return the stored metadata of one bank.
+fn handle_get_bank_info(
+    transport: &mut dyn Transport,
     state: UpdateState,
     bank: u8,
+) -> UpdateState {
+    #[cfg(feature = "golden-bank")]
+    if bank == GOLDEN_BANK {
+        let info = flash::read_golden_info().unwrap_or(GoldenInfo::new(0, 0, 0));
+        respond(
+            transport,
+            &Response::BankInfo {
+                bank,
+                size: info.size,
+                crc32: info.crc32,
+                version: info.version,
+                active: false,
+                metadata: (info.size != 0)
+                    .then(|| image_metadata(FW_GOLD_ADDR, GOLDEN_MAX_IMAGE_SIZE))
+                    .flatten(),
+                locked: None,
+            },
+        );
+        return state;
+    }
+
+    // The data partition holds no firmware, so has no image metadata.
+    if bank == DATA_BANK {
+        let info = flash::read_data_info().unwrap_or(DataInfo::new(0, 0, 0));
+        respond(
+            transport,
+            &Response::BankInfo {
+                bank,
+                size: info.size,
+                crc32: info.crc32,
+                version: info.version,
+                active: false,
+                metadata: None,
+                locked: None,
+            },
+        );
+        return state;
+    }
+
+    let bd = flash::read_boot_data();
+    let (Some((size, crc32)), Some(addr)) = (bank_firmware_info(&bd, bank), bank_addr(bank)) else {
+        return reject_with(transport, AckStatus::BankInvalid, state);
+    };
+
+    respond(
+        transport,
+        &Response::BankInfo {
+            bank,
+            size,
+            crc32,
+            version: if bank == 0 {
+                bd.version_a
+            } else {
+                bd.version_b
+            },
+            active: bd.active_bank == bank,
+            metadata: (size != 0)
+                .then(|| image_metadata(addr, FW_BANK_SIZE))
+                .flatten(),
+            locked: Some(bd.bank_locked(bank)),
+        },
+    );
+    state
+}
+
+/// Handle the commands `update::dispatch` runs: uploads to bank A, B or the
+/// data partition (and the data of golden and bootloader uploads), and
+/// switching between the banks.
+fn handle_update_command(
+    transport: &mut dyn Transport,
+    flash_ops: &mut impl FlashOps,
+    state: UpdateState,
+    cmd: &Command,
+) -> UpdateState {
+    let mut upload = match state {
+        UpdateState::Ready => None,
+        UpdateState::ReceivingData(upload) => Some(upload),
+        _ => return reject_with(transport, AckStatus::BadState, state),
+    };
+
+    match (cmd, upload) {
+        (Command::DataBlock { offset, data }, _) => {
+            defmt::trace!("DataBlock: offset={}, data_len={}", offset, data.len());
+        }
+        (Command::FinishUpdate | Command::FinishUpdateNoActivate, Some(staged)) => {
+            defmt::println!("FinishUpdate: Verifying CRC of RAM buffer");
+            // A bootloader image is not booted from a bank: it has checks
+            // and a write of its own.
+            if staged.bank == BOOTLOADER_STAGING {
+                let ram = storage::ram_buffer(storage::fw_ram_buffer_size());
+                if let Err(err) = staged.verify_ram(ram) {
+                    return reject_update(transport, err, state);
+                }
+                return finish_bootloader_update(transport, flash_ops, &staged);
+            }
+        }
+        _ => {}
+    }
+
+    let ram = storage::ram_buffer_mut();
+    let mut progress = ProgressReporter::new(transport);
+    let result = update::dispatch(
+        flash_ops,
+        ram,
+        &image_limits(),
+        &mut upload,
+        cmd,
+        |phase, done, total| progress.report(phase, done, total),
+    );
+    let state = upload.map_or(UpdateState::Ready, UpdateState::ReceivingData);
+    let handled = match result {
+        Ok(handled) => handled,
+        Err(err) => return reject_update(transport, err, state),
+    };
+
+    match (handled, upload) {
+        (Handled::Started, Some(upload)) => {
+            // Stale data must not stay described as valid while it is
+            // overwritten.
+            if upload.bank == DATA_BANK {
+                if let Err(err) = unsafe { flash::erase_data_info() } {
+                    return reject_with(transport, err.into(), UpdateState::Ready);
+                }
+            }
+            defmt::println!(
+                "StartUpdate: bank={}, size={}, paged into flash: {}",
+                upload.bank,
+                upload.expected_size,
+                upload.expected_size > storage::fw_ram_buffer_size()
+            );
+        }
+        (Handled::Received { paging_started }, _) => {
+            if paging_started {
+                defmt::println!("DataBlock: image exceeds RAM buffer, paging into flash");
+            }
+        }
+        (Handled::Finish(image), _) => return persist_image(transport, flash_ops, ram, image),
+        (Handled::Aborted(dropped), _) => {
+            defmt::println!(
+                "AbortUpdate: bank {} upload dropped after {} bytes",
+                dropped.bank,
+                dropped.bytes_received
+            );
+        }
+        (Handled::Done, _) => match *cmd {
+            Command::SetActiveBank { bank } => {
+                defmt::println!("SetActiveBank: switched to bank {}", bank)
+            }
+            Command::SetBankLock { bank, locked } => {
+                defmt::println!("SetBankLock: bank {} locked={}", bank, locked)
+            }
+            Command::WipeAll => defmt::println!("Resetting boot data"),
+            _ => {}
+        },
+        (Handled::Started | Handled::Other, _) => {
+            return reject_with(transport, AckStatus::BadCommand, state);
+        }
+    }
+    send_ack(transport, AckStatus::Ok);
+    state
+}
+
+/// Handle `WriteGolden` command: like `StartUpdate` for the golden bank,
+/// accepted only while the golden bank is empty.
+#[cfg(feature = "golden-bank")]
+fn handle_write_golden(
+    transport: &mut dyn Transport,
+    state: UpdateState,
     size: u32,
     crc32: u32,
     version: u32,
@@ -85,231 +667,715 @@ fn handle_start_update(
         return reject_with(transport, AckStatus::BadState, state);
     }
 
-    let max_buffer_size = storage::fw_ram_buffer_size();
-    let Some(bank_addr) = bank_addr(bank) else {
+    let bd = flash::read_boot_data();
+    if bd.golden_populated() || flash::read_golden_info().is_some() {
+        error_log::record(ErrorCode::GoldenProvisioned);
         return reject_with(transport, AckStatus::BankInvalid, state);
-    };
+    }
 
-    if size == 0 || size > max_buffer_size {
-        defmt::warn!(
-            "Firmware size {} exceeds RAM buffer {}",
-            size,
-            max_buffer_size
-        );
+    if size == 0 || size > storage::fw_ram_buffer_size() || size > GOLDEN_MAX_IMAGE_SIZE {
         return reject_with(transport, AckStatus::BankInvalid, state);
     }
 
-    if size > FW_BANK_SIZE {
-        return reject_with(transport, AckStatus::BankInvalid, state);
+    if flash::check_range(flash::addr_to_offset(FW_GOLD_ADDR), size).is_err() {
+        return reject_with(transport, AckStatus::FlashError, state);
     }
 
-    defmt::println!(
-        "StartUpdate: bank={}, size={}, will buffer in RAM",
-        bank,
-        size
-    );
+    defmt::println!("WriteGolden: size={}, will buffer in RAM", size);
     send_ack(transport, AckStatus::Ok);
 
-    UpdateState::ReceivingData {
-        bank,
-        bank_addr,
-        expected_size: size,
-        expected_crc: crc32,
+    UpdateState::ReceivingData(Upload::new(
+        GOLDEN_BANK,
+        FW_GOLD_ADDR,
+        size,
+        crc32,
+        CrcAlgorithm::IsoHdlc,
         version,
-        bytes_received: 0,
-    }
+    ))
 }
 
-/// Handle `DataBlock` command: validate offset and append data to the RAM buffer.
-fn handle_data_block(
-    transport: &mut UsbTransport,
-    mut state: UpdateState,
-    offset: u32,
-    data: &[u8],
+/// Without the `golden-bank` feature there is no golden bank to write.
+#[cfg(not(feature = "golden-bank"))]
+fn handle_write_golden(
+    transport: &mut dyn Transport,
+    state: UpdateState,
+    _size: u32,
+    _crc32: u32,
+    _version: u32,
 ) -> UpdateState {
-    defmt::trace!("DataBlock: offset={}, data_len={}", offset, data.len());
+    reject_with(transport, AckStatus::BankInvalid, state)
+}
 
-    let UpdateState::ReceivingData {
-        ref mut bytes_received,
-        expected_size,
-        ..
-    } = state
-    else {
-        defmt::warn!("handle_data_block: BadState");
+/// Handle `StartBootloaderUpdate` command: receive a bootloader image to be
+/// staged in the inactive bank.
+fn handle_start_bootloader_update(
+    transport: &mut dyn Transport,
+    state: UpdateState,
+    size: u32,
+    crc32: u32,
+) -> UpdateState {
+    if !matches!(state, UpdateState::Ready) {
         return reject_with(transport, AckStatus::BadState, state);
-    };
-
-    if offset != *bytes_received {
-        defmt::warn!(
-            "handle_data_block: BadOffset {} != {}",
-            offset,
-            *bytes_received
-        );
-        return reject_with(transport, AckStatus::BadCommand, state);
     }
 
-    let data_len = u32::try_from(data.len())
-        .unwrap_or_else(|_| unreachable!("data block length always fits in u32"));
-    if *bytes_received + data_len > expected_size {
-        defmt::warn!("handle_data_block: Size overflow");
+    if size == 0 || size > BOOTLOADER_REGION_SIZE {
         return reject_with(transport, AckStatus::BadCommand, state);
     }
 
-    storage::copy_to_ram_buffer(*bytes_received as usize, data);
-    *bytes_received += data_len;
+    let bd = flash::read_boot_data();
+    let staging_bank = self_update::staging_bank(&bd);
+    if bd.bank_locked(staging_bank) {
+        error_log::record(ErrorCode::BankLocked);
+        return reject_with(transport, AckStatus::BankLocked, state);
+    }
 
+    defmt::println!(
+        "StartBootloaderUpdate: size={}, staging in bank {}",
+        size,
+        staging_bank
+    );
     send_ack(transport, AckStatus::Ok);
-    state
+
+    UpdateState::ReceivingData(Upload::new(
+        BOOTLOADER_STAGING,
+        self_update::staging_bank_addr(&bd),
+        size,
+        crc32,
+        CrcAlgorithm::IsoHdlc,
+        0,
+    ))
 }
 
-/// Handle `FinishUpdate` command: persist RAM buffer to flash, verify CRC, update `BootData`.
-fn handle_finish_update(transport: &mut UsbTransport, state: UpdateState) -> UpdateState {
-    let UpdateState::ReceivingData {
-        bank,
+/// Finish a bootloader update: stage the image received in RAM, acknowledge,
+/// then replace the bootloader and reset. Returns only if staging fails.
+fn finish_bootloader_update(
+    transport: &mut dyn Transport,
+    flash_ops: &mut impl FlashOps,
+    upload: &Upload,
+) -> UpdateState {
+    let Upload {
         bank_addr,
-        expected_size,
-        expected_crc,
-        version,
-        bytes_received,
-    } = state
-    else {
-        return reject_with(transport, AckStatus::BadState, state);
-    };
-
-    if bytes_received != expected_size {
-        defmt::warn!(
-            "FinishUpdate: Incomplete data {} != {}",
-            bytes_received,
-            expected_size
-        );
+        expected_size: size,
+        expected_crc: crc,
+        ..
+    } = *upload;
+    if let Err(reason) = validate_bootloader_image(storage::ram_buffer(size)) {
+        error_log::record(ErrorCode::ImageRejected);
+        defmt::debug!("Not a bootloader image: {}", reason);
         send_ack(transport, AckStatus::BadCommand);
-        return UpdateState::ReceivingData {
-            bank,
-            bank_addr,
-            expected_size,
-            expected_crc,
-            version,
-            bytes_received,
-        };
+        return UpdateState::Ready;
     }
 
-    defmt::println!("FinishUpdate: Verifying CRC of RAM buffer");
-    let ram_crc = storage::compute_ram_crc32(expected_size);
+    let mut bd = flash_ops.read_boot_data();
+    let mut progress = ProgressReporter::new(transport);
+    let written =
+        storage::persist_ram_to_flash(flash_ops, bank_addr, size, |phase, done, total| {
+            progress.report(phase, done, total)
+        });
+    if let Err(err) = written {
+        send_ack(transport, err.into());
+        return UpdateState::Ready;
+    }
 
-    if ram_crc != expected_crc {
-        defmt::warn!(
-            "FinishUpdate: CRC mismatch in RAM: expected 0x{:08x}, got 0x{:08x}",
-            expected_crc,
-            ram_crc
-        );
+    let flash_crc = flash_ops.compute_crc(
+        CrcAlgorithm::IsoHdlc,
+        flash::addr_to_offset(bank_addr),
+        size,
+        |done| progress.report(ProgressPhase::Verify, done, size),
+    );
+    if flash_crc != crc {
+        error_log::record(ErrorCode::CrcMismatchFlash);
         send_ack(transport, AckStatus::CrcError);
         return UpdateState::Ready;
     }
 
+    self_update::mark_staged(&mut bd, size, crc);
+    if let Err(err) = flash_ops.write_boot_data(&bd) {
+        send_ack(transport, err.into());
+        return UpdateState::Ready;
+    }
+
+    send_ack(transport, AckStatus::Ok);
+    // Let the Ack reach the host before USB goes silent.
+    cortex_m::asm::delay(12_000_000);
+    unsafe { self_update::apply(size, &bd) }
+}
+
+/// Write an image whose `FinishUpdate` checks passed, verify it and record
+/// it (switching `active_bank` if `image.activate`).
+fn persist_image(
+    transport: &mut dyn Transport,
+    flash_ops: &mut impl FlashOps,
+    ram: &[u8],
+    image: PendingImage,
+) -> UpdateState {
     defmt::println!("FinishUpdate: CRC OK, persisting to flash...");
-    unsafe { storage::persist_ram_to_flash(bank_addr, expected_size) };
+    #[cfg(feature = "dual-core-flash")]
+    if let Some(next) = start_persist_on_core1(transport, flash_ops, image) {
+        return next;
+    }
 
-    defmt::println!("FinishUpdate: Flash write complete, verifying...");
+    let mut progress = ProgressReporter::new(transport);
+    let written = image.write(flash_ops, ram, |phase, done, total| {
+        progress.report(phase, done, total)
+    });
+    complete_finish_update(&mut progress, flash_ops, image, written)
+}
 
-    let flash_crc = flash::compute_crc32(bank_addr, expected_size);
-    if flash_crc != expected_crc {
-        defmt::error!(
-            "FinishUpdate: Flash CRC mismatch: expected 0x{:08x}, got 0x{:08x}",
-            expected_crc,
-            flash_crc
-        );
-        send_ack(transport, AckStatus::CrcError);
-        return UpdateState::Ready;
+/// Hand the write of `image` to core 1 (`dual-core-flash`), returning the
+/// `Persisting` state, or the state after the `Ack` if the worker refused the
+/// ranges. `None` if core 1 is unavailable: the caller writes on core 0.
+///
+/// The segments are those of the single-core path, in the same order.
+#[cfg(feature = "dual-core-flash")]
+fn start_persist_on_core1(
+    transport: &mut dyn Transport,
+    flash_ops: &mut impl FlashOps,
+    image: PendingImage,
+) -> Option<UpdateState> {
+    use crate::flash_worker::{self, Segment};
+    use crispy_common::protocol::FLASH_PAGE_SIZE;
+    use crispy_common::update::PAGED_HEAD_SIZE;
+
+    let paged_out = image.paged_out;
+    let offset = flash::addr_to_offset(image.bank_addr);
+    let ram = storage::pad_ram_buffer_to_page(image.size - paged_out);
+    let buffered = (image.size - paged_out).next_multiple_of(FLASH_PAGE_SIZE);
+    let whole = [Segment {
+        offset,
+        data: ram,
+        len: buffered,
+        erase: true,
+        report: true,
+    }];
+    let paged = [
+        Segment {
+            offset: offset + PAGED_HEAD_SIZE + paged_out,
+            // SAFETY: the head sector precedes the tail in the buffer.
+            data: unsafe { ram.add(PAGED_HEAD_SIZE as usize) },
+            len: buffered - PAGED_HEAD_SIZE,
+            erase: true,
+            report: true,
+        },
+        Segment {
+            offset,
+            data: ram,
+            len: PAGED_HEAD_SIZE,
+            erase: false,
+            report: false,
+        },
+    ];
+    let segments: &[Segment] = if paged_out == 0 { &whole } else { &paged };
+
+    match flash_worker::start(flash_ops, segments) {
+        Ok(true) => {
+            // Core 1 erases without `RomFlash`, which would count this.
+            if paged_out == 0 && flash_ops::bank_range(image.bank).is_some() {
+                unsafe { wear::record_erase(wear::WearRegion::for_bank(image.bank)) };
+            }
+            defmt::println!("FinishUpdate: writing on core 1");
+            Some(UpdateState::Persisting {
+                image,
+                last_progress: None,
+            })
+        }
+        Ok(false) => None,
+        Err(err) => {
+            defmt::debug!("flash write refused: {}", err);
+            send_ack(transport, err.into());
+            Some(UpdateState::Ready)
+        }
     }
+}
 
-    let mut bd = flash::read_boot_data();
-    bd.active_bank = bank;
-    bd.confirmed = 0;
-    bd.boot_attempts = 0;
+/// Serve core 1 while it writes an image (`dual-core-flash`): forward its
+/// progress, and once it is done verify and record the image as
+/// `FinishUpdate` does on core 0.
+#[cfg(feature = "dual-core-flash")]
+pub fn poll_persist(
+    transport: &mut dyn Transport,
+    flash_ops: &mut impl FlashOps,
+    state: UpdateState,
+) -> UpdateState {
+    use crate::flash_worker::{self, Poll};
 
-    if bank == 0 {
-        bd.version_a = version;
-        bd.crc_a = expected_crc;
-        bd.size_a = expected_size;
-    } else {
-        bd.version_b = version;
-        bd.crc_b = expected_crc;
-        bd.size_b = expected_size;
+    let UpdateState::Persisting {
+        image,
+        last_progress,
+    } = state
+    else {
+        return state;
+    };
+    let mut progress = ProgressReporter::resume(transport, last_progress);
+    match flash_worker::poll() {
+        Poll::Running(reported) => {
+            if let Some((phase, done, total)) = reported {
+                progress.report(phase, done, total);
+            }
+            UpdateState::Persisting {
+                image,
+                last_progress: progress.last,
+            }
+        }
+        Poll::Done(written) => complete_finish_update(&mut progress, flash_ops, image, written),
+    }
+}
+
+/// The end of `FinishUpdate` once the image is `written`: verify it in flash,
+/// then update `BootData` (switching `active_bank` if `image.activate`), or
+/// the data partition or golden bank records.
+fn complete_finish_update(
+    progress: &mut ProgressReporter,
+    flash_ops: &mut impl FlashOps,
+    image: PendingImage,
+    written: Result<(), FlashError>,
+) -> UpdateState {
+    defmt::println!("FinishUpdate: Flash write complete, verifying...");
+    let stored = image.complete(flash_ops, written, |phase, done, total| {
+        progress.report(phase, done, total)
+    });
+    let transport = &mut *progress.transport;
+    let crc32 = match stored {
+        Ok(crc32) => crc32,
+        Err(err) => return reject_update(transport, err, UpdateState::Ready),
+    };
+
+    if image.bank == DATA_BANK {
+        let info = DataInfo::new(image.size, crc32, image.version);
+        let status = match unsafe { flash::write_data_info(&info) } {
+            Ok(()) => AckStatus::Ok,
+            Err(err) => err.into(),
+        };
+        defmt::println!("FinishUpdate: data partition stored");
+        send_ack(transport, status);
+        return UpdateState::Ready;
     }
 
-    unsafe {
-        flash::write_boot_data(&bd);
+    // The golden bank is never activated: it is only booted when A and B fail.
+    #[cfg(feature = "golden-bank")]
+    if image.bank == GOLDEN_BANK {
+        let info = GoldenInfo::new(image.size, crc32, image.version);
+        if let Err(err) = unsafe { flash::write_golden_info(&info) } {
+            send_ack(transport, err.into());
+            return UpdateState::Ready;
+        }
+        let mut bd = flash_ops.read_boot_data();
+        bd.flags |= BOOT_FLAG_GOLDEN;
+        defmt::println!("FinishUpdate: golden bank provisioned");
+        send_ack(transport, store_boot_data(flash_ops, &bd));
+        return UpdateState::Ready;
     }
 
+    if !image.activate {
+        defmt::println!(
+            "FinishUpdate: bank {} stored, active bank unchanged",
+            image.bank
+        );
+    }
     send_ack(transport, AckStatus::Ok);
     UpdateState::Ready
 }
 
+/// Handle `CopyBank` command: copy a validated image to the inactive bank.
+///
+/// The source image is staged in the RAM firmware buffer (unused while
+/// `Ready`) and written with the same erase/program path as an upload, so the
+/// host gets the same progress stream and never re-sends the image.
+fn handle_copy_bank(
+    transport: &mut dyn Transport,
+    flash_ops: &mut impl FlashOps,
+    state: UpdateState,
+    from: u8,
+    to: u8,
+) -> UpdateState {
+    if !matches!(state, UpdateState::Ready) {
+        return reject_with(transport, AckStatus::BadState, state);
+    }
+
+    let (Some(from_addr), Some(to_addr)) = (bank_addr(from), bank_addr(to)) else {
+        return reject_with(transport, AckStatus::BankInvalid, state);
+    };
+    if from == to {
+        return reject_with(transport, AckStatus::BadCommand, state);
+    }
+
+    let mut bd = flash_ops.read_boot_data();
+    if to == bd.active_bank {
+        defmt::println!("CopyBank: refusing to overwrite the active bank {}", to);
+        return reject_with(transport, AckStatus::BankInvalid, state);
+    }
+    if bd.bank_locked(to) {
+        defmt::println!("CopyBank: bank {} is locked", to);
+        return reject_with(transport, AckStatus::BankLocked, state);
+    }
+
+    let Some((size, crc)) = bank_firmware_info(&bd, from) else {
+        return reject_with(transport, AckStatus::BankInvalid, state);
+    };
+    if size == 0 {
+        defmt::println!("CopyBank: bank {} has no firmware to copy", from);
+        return reject_with(transport, AckStatus::BankInvalid, state);
+    }
+    let to_offset = flash::addr_to_offset(to_addr);
+    if flash_ops.check_range(to_offset, size).is_err() {
+        return reject_with(transport, AckStatus::FlashError, state);
+    }
+
+    let source_crc = flash_ops.compute_crc32(flash::addr_to_offset(from_addr), size);
+    if source_crc != crc {
+        error_log::record(ErrorCode::CrcMismatchFlash);
+        defmt::debug!("CopyBank source bank {} is corrupt", from);
+        return reject_with(transport, AckStatus::CrcError, state);
+    }
+
+    defmt::println!(
+        "CopyBank: copying {} bytes from bank {} to bank {}",
+        size,
+        from,
+        to
+    );
+    let mut progress = ProgressReporter::new(transport);
+    let written =
+        storage::copy_flash_via_ram(flash_ops, from_addr, to_addr, size, |phase, done, total| {
+            progress.report(phase, done, total)
+        });
+    if let Err(err) = written {
+        return reject_with(transport, err.into(), state);
+    }
+
+    let copy_crc = flash_ops.compute_crc(CrcAlgorithm::IsoHdlc, to_offset, size, |done| {
+        progress.report(ProgressPhase::Verify, done, size)
+    });
+    if copy_crc != crc {
+        error_log::record(ErrorCode::CrcMismatchFlash);
+        return reject_with(transport, AckStatus::CrcError, state);
+    }
+
+    if to == 0 {
+        bd.version_a = bd.version_b;
+        bd.crc_a = crc;
+        bd.size_a = size;
+    } else {
+        bd.version_b = bd.version_a;
+        bd.crc_b = crc;
+        bd.size_b = size;
+    }
+
+    defmt::println!("CopyBank: done");
+    send_ack(transport, store_boot_data(flash_ops, &bd));
+    state
+}
+
 /// Handle `Reboot` command: send ACK and reset the system.
-fn handle_reboot(transport: &mut UsbTransport) -> ! {
+fn handle_reboot(transport: &mut dyn Transport) -> ! {
     send_ack(transport, AckStatus::Ok);
     cortex_m::asm::delay(12_000_000);
     cortex_m::peripheral::SCB::sys_reset();
 }
 
-/// Handle `SetActiveBank` command: change the active bank for next boot.
-fn handle_set_active_bank(
-    transport: &mut UsbTransport,
+/// Handle `EnterBootrom` command: send ACK and reset into the RP2040 ROM USB bootloader.
+fn handle_enter_bootrom(transport: &mut dyn Transport) -> ! {
+    send_ack(transport, AckStatus::Ok);
+    defmt::println!("Entering ROM USB bootloader");
+    cortex_m::asm::delay(12_000_000);
+    flash::reset_to_usb_boot(BOOTROM_ACTIVITY_LED_MASK)
+}
+
+/// Handle `VerifyBank` command: check a bank's flash CRC against the host's.
+fn handle_verify_bank(
+    transport: &mut dyn Transport,
+    flash_ops: &mut impl FlashOps,
     state: UpdateState,
     bank: u8,
+    expected_crc: u32,
 ) -> UpdateState {
-    if !matches!(state, UpdateState::Ready) {
-        return reject_with(transport, AckStatus::BadState, state);
+    match state {
+        UpdateState::Ready => {}
+        UpdateState::ReceivingData(_) => {
+            return reject_with(transport, AckStatus::Busy, state);
+        }
+        _ => return reject_with(transport, AckStatus::BadState, state),
     }
 
-    let Some(bank_addr) = bank_addr(bank) else {
-        return reject_with(transport, AckStatus::BankInvalid, state);
-    };
-
-    let mut bd = flash::read_boot_data();
-    let Some((size, crc)) = bank_firmware_info(&bd, bank) else {
+    let bd = flash_ops.read_boot_data();
+    let (Some(bank_addr), Some((size, _))) = (bank_addr(bank), bank_firmware_info(&bd, bank))
+    else {
         return reject_with(transport, AckStatus::BankInvalid, state);
     };
-
     if size == 0 {
-        defmt::println!("SetActiveBank: bank {} has no firmware", bank);
+        defmt::println!("VerifyBank: bank {} has no firmware", bank);
         return reject_with(transport, AckStatus::BankInvalid, state);
     }
 
-    let actual_crc = flash::compute_crc32(bank_addr, size);
-    if actual_crc != crc {
+    let mut progress = ProgressReporter::new(transport);
+    let actual_crc = flash_ops.compute_crc(
+        CrcAlgorithm::IsoHdlc,
+        flash::addr_to_offset(bank_addr),
+        size,
+        |done| progress.report(ProgressPhase::Verify, done, size),
+    );
+    if actual_crc != expected_crc {
         defmt::println!(
-            "SetActiveBank: bank {} CRC mismatch (expected 0x{:08x}, got 0x{:08x})",
+            "VerifyBank: bank {} CRC mismatch (expected 0x{:08x}, got 0x{:08x})",
             bank,
-            crc,
+            expected_crc,
             actual_crc
         );
         return reject_with(transport, AckStatus::CrcError, state);
     }
 
-    bd.active_bank = bank;
-    bd.confirmed = 0;
-    bd.boot_attempts = 0;
+    send_ack(transport, AckStatus::Ok);
+    state
+}
+
+/// Handle `CompareBanks` command: CRC both banks over their stored sizes, so
+/// the host can tell whether they hold the same image without reading them.
+fn handle_compare_banks(
+    transport: &mut dyn Transport,
+    flash_ops: &mut impl FlashOps,
+    state: UpdateState,
+) -> UpdateState {
+    match state {
+        UpdateState::Ready => {}
+        UpdateState::ReceivingData(_) => {
+            return reject_with(transport, AckStatus::Busy, state);
+        }
+        _ => return reject_with(transport, AckStatus::BadState, state),
+    }
+
+    let bd = flash_ops.read_boot_data();
+    let total = bd.size_a + bd.size_b;
+    let mut progress = ProgressReporter::new(transport);
+    let a_crc = flash_ops.compute_crc(
+        CrcAlgorithm::IsoHdlc,
+        flash::addr_to_offset(FW_A_ADDR),
+        bd.size_a,
+        |done| progress.report(ProgressPhase::Verify, done, total),
+    );
+    let b_crc = flash_ops.compute_crc(
+        CrcAlgorithm::IsoHdlc,
+        flash::addr_to_offset(FW_B_ADDR),
+        bd.size_b,
+        |done| progress.report(ProgressPhase::Verify, bd.size_a + done, total),
+    );
+
+    // Two empty banks hold no firmware, so nothing to call equal.
+    let equal = bd.size_a != 0 && bd.size_a == bd.size_b && a_crc == b_crc;
+    respond(
+        transport,
+        &Response::BankCompare {
+            a_crc,
+            b_crc,
+            equal,
+        },
+    );
+    state
+}
+
+/// Handle `ReadBank` command: send back part of a bank's image, trimmed at
+/// `MAX_DATA_BLOCK_SIZE` and at the stored size.
+fn handle_read_bank(
+    transport: &mut dyn Transport,
+    flash_ops: &mut impl FlashOps,
+    state: UpdateState,
+    bank: u8,
+    offset: u32,
+    len: u32,
+) -> UpdateState {
+    match state {
+        UpdateState::Ready => {}
+        UpdateState::ReceivingData(_) => {
+            return reject_with(transport, AckStatus::Busy, state);
+        }
+        _ => return reject_with(transport, AckStatus::BadState, state),
+    }
+
+    let bd = flash_ops.read_boot_data();
+    let (Some(bank_addr), Some((size, _))) = (bank_addr(bank), bank_firmware_info(&bd, bank))
+    else {
+        return reject_with(transport, AckStatus::BankInvalid, state);
+    };
+
+    let len = len
+        .min(size.saturating_sub(offset))
+        .min(MAX_DATA_BLOCK_SIZE as u32);
+    let mut data = heapless::Vec::new();
+    // Cannot fail: `len` is at most the capacity.
+    let _ = data.resize(len as usize, 0);
+    if len != 0 {
+        flash_ops.read(flash::addr_to_offset(bank_addr) + offset, &mut data);
+    }
+    respond(transport, &Response::BankData { bank, offset, data });
+    state
+}
+
+/// Handle `FlashSelfTest` command: erase, program and read back the scratch
+/// sector, keeping its contents in the RAM firmware buffer meanwhile.
+fn handle_flash_self_test(
+    transport: &mut dyn Transport,
+    flash_ops: &mut impl FlashOps,
+    state: UpdateState,
+) -> UpdateState {
+    match state {
+        UpdateState::Ready => {}
+        UpdateState::ReceivingData(_) => {
+            return reject_with(transport, AckStatus::Busy, state);
+        }
+        _ => return reject_with(transport, AckStatus::BadState, state),
+    }
+
+    unsafe { storage::load_flash_to_ram(SELFTEST_SCRATCH_ADDR, FLASH_SECTOR_SIZE) };
+    let detail = flash_ops::self_test(
+        flash_ops,
+        flash::addr_to_offset(SELFTEST_SCRATCH_ADDR),
+        storage::ram_buffer(FLASH_SECTOR_SIZE),
+    );
+    if detail != SELFTEST_PASSED {
+        defmt::println!("FlashSelfTest: failed with detail {}", detail);
+    }
+    respond(
+        transport,
+        &Response::SelfTest {
+            passed: detail == SELFTEST_PASSED,
+            detail,
+        },
+    );
+    state
+}
+
+/// Handle `ConfirmBoot` command: confirm the active image from the host.
+fn handle_confirm_boot(
+    transport: &mut dyn Transport,
+    flash_ops: &mut impl FlashOps,
+    state: UpdateState,
+) -> UpdateState {
+    if !matches!(state, UpdateState::Ready) {
+        return reject_with(transport, AckStatus::BadState, state);
+    }
+
+    let mut bd = flash_ops.read_boot_data();
+    let bank = bd.active_bank;
+    let (Some(bank_addr), Some((size, crc))) = (bank_addr(bank), bank_firmware_info(&bd, bank))
+    else {
+        return reject_with(transport, AckStatus::BankInvalid, state);
+    };
+
+    if size == 0 {
+        defmt::println!("ConfirmBoot: active bank {} has no firmware", bank);
+        return reject_with(transport, AckStatus::BankInvalid, state);
+    }
+
+    if flash_ops.compute_crc32(flash::addr_to_offset(bank_addr), size) != crc {
+        defmt::println!("ConfirmBoot: active bank {} CRC mismatch", bank);
+        return reject_with(transport, AckStatus::CrcError, state);
+    }
+
+    let mut status = AckStatus::Ok;
+    if bd.confirmed != 1 || bd.boot_attempts != 0 {
+        bd.confirmed = 1;
+        bd.boot_attempts = 0;
+        status = store_boot_data(flash_ops, &bd);
+    }
+
+    defmt::println!("ConfirmBoot: bank {} confirmed", bank);
+    send_ack(transport, status);
+    state
+}
+
+/// Handle `EnableAntiRollback` command: refuse older images from now on.
+fn handle_enable_anti_rollback(
+    transport: &mut dyn Transport,
+    flash_ops: &mut impl FlashOps,
+    state: UpdateState,
+) -> UpdateState {
+    if !matches!(state, UpdateState::Ready) {
+        return reject_with(transport, AckStatus::BadState, state);
+    }
 
-    unsafe {
-        flash::write_boot_data(&bd);
+    let mut bd = flash_ops.read_boot_data();
+    let mut status = AckStatus::Ok;
+    if !bd.anti_rollback() {
+        bd.flags |= BOOT_FLAG_ANTI_ROLLBACK;
+        bd.min_version = bank_version(&bd, bd.active_bank);
+        status = store_boot_data(flash_ops, &bd);
+        defmt::println!("Anti-rollback enabled, floor 0x{:08x}", bd.min_version);
     }
 
-    defmt::println!("SetActiveBank: switched to bank {}", bank);
-    send_ack(transport, AckStatus::Ok);
+    send_ack(transport, status);
     state
 }
 
-fn handle_wipe_all(transport: &mut UsbTransport, state: UpdateState) -> UpdateState {
+/// Handle `SetBootPolicy` command: store the rollback threshold.
+fn handle_set_boot_policy(
+    transport: &mut dyn Transport,
+    flash_ops: &mut impl FlashOps,
+    state: UpdateState,
+    max_attempts: u8,
+) -> UpdateState {
     if !matches!(state, UpdateState::Ready) {
         return reject_with(transport, AckStatus::BadState, state);
     }
 
-    defmt::println!("Resetting boot data");
-    unsafe {
-        flash::write_boot_data(&BootData::default_new());
+    if max_attempts == 0 || max_attempts == MAX_BOOT_ATTEMPTS_UNSET {
+        return reject_with(transport, AckStatus::BadCommand, state);
+    }
+
+    let mut bd = flash_ops.read_boot_data();
+    let mut status = AckStatus::Ok;
+    if bd.max_boot_attempts != max_attempts {
+        bd.max_boot_attempts = max_attempts;
+        status = store_boot_data(flash_ops, &bd);
+    }
+
+    defmt::println!("SetBootPolicy: max boot attempts {}", max_attempts);
+    send_ack(transport, status);
+    state
+}
+
+/// Handle `ConfigSet` command: rewrite the config sector with slot `key`
+/// set to `value`. A slot that already holds `value` is not rewritten.
+fn handle_config_set(
+    transport: &mut dyn Transport,
+    state: UpdateState,
+    key: u8,
+    value: u32,
+) -> UpdateState {
+    if !matches!(state, UpdateState::Ready) {
+        return reject_with(transport, AckStatus::BadState, state);
     }
 
+    let status = match flash::read_config(key) {
+        None => AckStatus::BadCommand,
+        Some(old) if old == value => AckStatus::Ok,
+        Some(_) => match unsafe { flash::write_config(key, value) } {
+            Ok(()) => AckStatus::Ok,
+            Err(err) => err.into(),
+        },
+    };
+    send_ack(transport, status);
+    state
+}
+
+/// Handle `StreamLogs` command: start or stop forwarding defmt output.
+///
+/// The Ack goes out before the first `Log` frame, and after the last one.
+#[cfg(feature = "log-stream")]
+fn handle_stream_logs(
+    transport: &mut dyn Transport,
+    state: UpdateState,
+    enable: bool,
+) -> UpdateState {
+    crate::log_stream::set_streaming(enable);
     send_ack(transport, AckStatus::Ok);
     state
 }
+
+/// Without the `log-stream` feature defmt output only goes to RTT.
+#[cfg(not(feature = "log-stream"))]
+fn handle_stream_logs(
+    transport: &mut dyn Transport,
+    state: UpdateState,
+    _enable: bool,
+) -> UpdateState {
+    reject_with(transport, AckStatus::BadCommand, state)
+}
@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Bootloader self-update.
+//!
+//! A new bootloader image is uploaded like firmware, but with
+//! `StartBootloaderUpdate`: it is stored and CRC-checked in the inactive bank,
+//! and BootData records it with `BOOT_FLAG_BOOTLOADER_STAGED` and that bank's
+//! size/CRC fields. `flash::flash_bootloader_and_reset` then copies it over the
+//! bootloader region from RAM, verifies it and clears the flag.
+//!
+//! If the device resets after staging but before the copy (e.g. power loss
+//! while the host is being acknowledged), `resume_staged_update` finishes the
+//! job at the next start. Once the copier has erased the region the old
+//! bootloader is gone; an interrupted copy can only be recovered over BOOTSEL.
+
+use super::storage;
+use crate::flash;
+use crispy_common::boot_journal::{self, WritePlan};
+use crispy_common::protocol::{
+    BootData, BOOTLOADER_REGION_SIZE, BOOT_FLAG_BOOTLOADER_STAGED, FLASH_BASE, FW_A_ADDR, FW_B_ADDR,
+};
+
+/// The bank a bootloader image is staged in: the inactive one.
+pub(super) fn staging_bank(bd: &BootData) -> u8 {
+    if bd.active_bank == 0 {
+        1
+    } else {
+        0
+    }
+}
+
+pub(super) fn staging_bank_addr(bd: &BootData) -> u32 {
+    if staging_bank(bd) == 0 {
+        FW_A_ADDR
+    } else {
+        FW_B_ADDR
+    }
+}
+
+/// Size and CRC of the staged image, from the staging bank's BootData fields.
+fn staged_image(bd: &BootData) -> (u32, u32) {
+    if staging_bank(bd) == 0 {
+        (bd.size_a, bd.crc_a)
+    } else {
+        (bd.size_b, bd.crc_b)
+    }
+}
+
+/// Record a verified bootloader image of `size` bytes in the staging bank.
+pub(super) fn mark_staged(bd: &mut BootData, size: u32, crc: u32) {
+    set_staging_bank(bd, size, crc);
+    bd.flags |= BOOT_FLAG_BOOTLOADER_STAGED;
+}
+
+/// Drop the staging flag. The staging bank no longer holds firmware.
+fn clear_staged(bd: &mut BootData) {
+    set_staging_bank(bd, 0, 0);
+    bd.flags &= !BOOT_FLAG_BOOTLOADER_STAGED;
+}
+
+fn set_staging_bank(bd: &mut BootData, size: u32, crc: u32) {
+    if staging_bank(bd) == 0 {
+        bd.size_a = size;
+        bd.crc_a = crc;
+        bd.version_a = 0;
+    } else {
+        bd.size_b = size;
+        bd.crc_b = crc;
+        bd.version_b = 0;
+    }
+}
+
+/// Copy the image in the RAM buffer over the bootloader region and reset.
+///
+/// # Safety
+/// The RAM buffer must hold the verified, staged image of `size` bytes, and
+/// `bd` must be the BootData recording it.
+pub(super) unsafe fn apply(size: u32, bd: &BootData) -> ! {
+    let mut done = *bd;
+    clear_staged(&mut done);
+    // `flash_bootloader_and_reset` erases the BootData sector and programs
+    // this page at its start, so the record goes in the first slot.
+    let plan = WritePlan {
+        erase: true,
+        slot: 0,
+        ..boot_journal::plan_write(flash::boot_data_sector())
+    };
+    let (_, page) = plan.page(&done);
+
+    defmt::warn!("Replacing bootloader ({} bytes), do not remove power", size);
+    let src = storage::pad_ram_buffer_to_page(size);
+    flash::flash_bootloader_and_reset(src, size, page.as_ptr())
+}
+
+/// Finish a bootloader update left staged by a reset, or discard it.
+pub fn resume_staged_update() {
+    let mut bd = flash::read_boot_data();
+    if bd.flags & BOOT_FLAG_BOOTLOADER_STAGED == 0 {
+        return;
+    }
+
+    let (size, crc) = staged_image(&bd);
+    let plausible = size != 0 && size <= BOOTLOADER_REGION_SIZE;
+
+    if plausible && flash::compute_crc32(FLASH_BASE, size) == crc {
+        defmt::println!("Bootloader update already applied, clearing staging flag");
+    } else if plausible && flash::compute_crc32(staging_bank_addr(&bd), size) == crc {
+        defmt::warn!("Resuming staged bootloader update");
+        unsafe {
+            storage::load_flash_to_ram(staging_bank_addr(&bd), size);
+            apply(size, &bd);
+        }
+    } else {
+        defmt::error!("Staged bootloader image is invalid, discarding it");
+    }
+
+    // If this write fails the flag stays set and the check runs again on
+    // the next boot.
+    clear_staged(&mut bd);
+    unsafe {
+        flash::write_boot_data(&bd).ok();
+    }
+}
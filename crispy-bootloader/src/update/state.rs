@@ -2,32 +2,58 @@
 // Copyright (c) 2026 ADNT Sarl <info@adnt.io>
 
 use crispy_common::protocol::BootState;
+#[cfg(feature = "dual-core-flash")]
+use crispy_common::protocol::ProgressPhase;
+#[cfg(feature = "dual-core-flash")]
+use crispy_common::update::PendingImage;
+use crispy_common::update::Upload;
 
 /// Update state machine states.
 #[derive(Clone, Copy, defmt::Format)]
 pub enum UpdateState {
     /// Waiting for an explicit update-mode request.
     Standby,
-    /// Initializing USB transport for update mode.
-    InitializingUsb,
+    /// Initializing the transport for update mode.
+    InitializingTransport,
+    /// Transport initialization failed: the transport is retried at `retry_at_us`
+    /// and the banks are checked again at `recheck_at_us` (timer µs).
+    Recovery {
+        failures: u8,
+        retry_at_us: u64,
+        recheck_at_us: u64,
+    },
     /// Update mode is active and ready for commands.
     Ready,
-    /// Actively receiving firmware data (accumulating in RAM).
-    ReceivingData {
-        bank: u8,
-        bank_addr: u32,
-        expected_size: u32,
-        expected_crc: u32,
-        version: u32,
-        bytes_received: u32,
+    /// Actively receiving firmware data (accumulating in RAM, paged into
+    /// flash past the buffer size).
+    ReceivingData(Upload),
+    /// Core 1 is writing `image` to flash (`flash_worker`); `last_progress`
+    /// is the last `Progress` frame sent for it.
+    #[cfg(feature = "dual-core-flash")]
+    Persisting {
+        image: PendingImage,
+        last_progress: Option<(ProgressPhase, u8)>,
     },
 }
 
 impl UpdateState {
     pub(super) fn as_boot_state(self) -> BootState {
         match self {
-            Self::Standby | Self::InitializingUsb | Self::Ready => BootState::UpdateMode,
-            Self::ReceivingData { .. } => BootState::Receiving,
+            Self::Standby | Self::InitializingTransport | Self::Recovery { .. } | Self::Ready => {
+                BootState::UpdateMode
+            }
+            Self::ReceivingData(_) => BootState::Receiving,
+            #[cfg(feature = "dual-core-flash")]
+            Self::Persisting { .. } => BootState::Persisting,
+        }
+    }
+
+    /// How much of the flash write is done, in percent, while `Persisting`.
+    pub(super) fn persist_percent(self) -> Option<u8> {
+        match self {
+            #[cfg(feature = "dual-core-flash")]
+            Self::Persisting { .. } => Some(crate::flash_worker::percent()),
+            _ => None,
         }
     }
 }
@@ -1,38 +1,58 @@
 // SPDX-License-Identifier: MIT
 // Copyright (c) 2026 ADNT Sarl <info@adnt.io>
 
-use crate::flash;
-use crc::{Crc, CRC_32_ISO_HDLC};
-use crispy_common::protocol::{FLASH_PAGE_SIZE, FLASH_SECTOR_SIZE};
+use crate::boot::MemoryLayout;
+use crispy_common::flash_ops::{self, FlashError, FlashOps};
+use crispy_common::protocol::{ProgressPhase, FLASH_BASE, FLASH_PAGE_SIZE, FLASH_SECTOR_SIZE};
 
-const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
-const FLASH_PROGRAM_BATCH_SIZE: u32 = FLASH_SECTOR_SIZE;
+// The upload buffer is the firmware's own RAM image region: images are staged
+// exactly where `boot::load_and_jump` later copies them. Its geometry comes
+// only from the linker script, via `MemoryLayout`.
 
-unsafe extern "C" {
-    static __fw_ram_base: u8;
-    static __fw_copy_size: u32;
-}
-
-/// Base pointer for firmware RAM region exported by linker script (`__fw_ram_base`).
+/// Base pointer of the firmware RAM region (`__fw_ram_base`).
 #[inline]
 fn fw_ram_buffer_ptr() -> *mut u8 {
-    core::ptr::addr_of!(__fw_ram_base).cast_mut()
+    MemoryLayout::from_linker().ram_base as *mut u8
 }
 
-/// Buffer size exported by linker script (`__fw_copy_size`).
-///
-/// `__fw_copy_size` is an absolute symbol, so its address is the value.
+/// Size of the firmware RAM region (`__fw_copy_size`).
 #[inline]
 pub(super) fn fw_ram_buffer_size() -> u32 {
-    core::ptr::addr_of!(__fw_copy_size) as usize as u32
+    MemoryLayout::from_linker().copy_size
+}
+
+/// The first `size` bytes of the RAM firmware buffer.
+pub(super) fn ram_buffer(size: u32) -> &'static [u8] {
+    ram_buffer_range(0, size)
+}
+
+/// The whole RAM firmware buffer, for the upload to fill.
+///
+/// Each command handler takes it at most once, and drops it before the
+/// next handler runs.
+pub(super) fn ram_buffer_mut() -> &'static mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(fw_ram_buffer_ptr(), fw_ram_buffer_size() as usize) }
+}
+
+/// `len` bytes of the RAM firmware buffer starting at `offset`.
+fn ram_buffer_range(offset: u32, len: u32) -> &'static [u8] {
+    unsafe {
+        core::slice::from_raw_parts(
+            fw_ram_buffer_ptr().add(offset as usize).cast_const(),
+            len as usize,
+        )
+    }
 }
 
-pub(super) fn compute_ram_crc32(size: u32) -> u32 {
-    let mut digest = CRC32.digest();
+/// Fill the RAM buffer from `size` up to the next page boundary with 0xFF,
+/// so the image can be programmed as whole pages. Returns the buffer start.
+pub(super) fn pad_ram_buffer_to_page(size: u32) -> *const u8 {
     let ram_base = fw_ram_buffer_ptr();
-    let ram_slice = unsafe { core::slice::from_raw_parts(ram_base.cast_const(), size as usize) };
-    digest.update(ram_slice);
-    digest.finalize()
+    let padded = size.div_ceil(FLASH_PAGE_SIZE) * FLASH_PAGE_SIZE;
+    unsafe {
+        core::ptr::write_bytes(ram_base.add(size as usize), 0xFF, (padded - size) as usize);
+    }
+    ram_base.cast_const()
 }
 
 pub(super) fn copy_to_ram_buffer(offset: usize, data: &[u8]) {
@@ -42,42 +62,77 @@ pub(super) fn copy_to_ram_buffer(offset: usize, data: &[u8]) {
     }
 }
 
-/// Persist RAM firmware buffer into flash.
+/// Copy `size` bytes of flash starting at `flash_addr` into the RAM firmware buffer.
 ///
 /// # Safety
-/// `bank_addr` must point to a valid writable firmware bank and `size` must be validated.
-pub(super) unsafe fn persist_ram_to_flash(bank_addr: u32, size: u32) {
-    let flash_offset = flash::addr_to_offset(bank_addr);
-    let ram_base = fw_ram_buffer_ptr();
-    let erase_size = size.div_ceil(FLASH_SECTOR_SIZE) * FLASH_SECTOR_SIZE;
-    flash::flash_erase(flash_offset, erase_size);
+/// `size` must not exceed `fw_ram_buffer_size()`.
+pub(super) unsafe fn load_flash_to_ram(flash_addr: u32, size: u32) {
+    let src = core::slice::from_raw_parts(flash_addr as *const u8, size as usize);
+    copy_to_ram_buffer(0, src);
+}
 
-    // Program full pages in larger batches to reduce XIP enter/exit overhead.
-    let full_page_bytes = (size / FLASH_PAGE_SIZE) * FLASH_PAGE_SIZE;
-    let mut offset = 0u32;
-    while offset < full_page_bytes {
-        let chunk = (full_page_bytes - offset).min(FLASH_PROGRAM_BATCH_SIZE);
-        flash::flash_program(
-            flash_offset + offset,
-            ram_base.add(offset as usize).cast_const(),
-            chunk as usize,
-        );
-        offset += chunk;
+/// Copy `size` bytes of flash from `from_addr` to sector aligned `to_addr`
+/// through the RAM firmware buffer, a buffer's worth of whole sectors at a
+/// time, so the copy is not limited to the buffer size.
+///
+/// Each chunk is erased and written like `persist_ram_range`;
+/// `on_progress(phase, done, total)` counts over the whole copy.
+pub(super) fn copy_flash_via_ram(
+    flash: &mut impl FlashOps,
+    from_addr: u32,
+    to_addr: u32,
+    size: u32,
+    mut on_progress: impl FnMut(ProgressPhase, u32, u32),
+) -> Result<(), FlashError> {
+    let chunk = fw_ram_buffer_size() / FLASH_SECTOR_SIZE * FLASH_SECTOR_SIZE;
+    let mut copied = 0;
+    while copied < size {
+        let len = (size - copied).min(chunk);
+        unsafe { load_flash_to_ram(from_addr + copied, len) };
+        persist_ram_range(flash, to_addr + copied, 0, len, true, |phase, done, _| {
+            on_progress(phase, copied + done, size)
+        })?;
+        copied += len;
     }
+    Ok(())
+}
+
+/// Persist RAM firmware buffer into flash.
+///
+/// The bank is erased one sector at a time so that `on_progress(phase, done, total)`
+/// runs between flash operations (interrupts are masked during each one); the
+/// callers' progress reporter polls the host link there.
+pub(super) fn persist_ram_to_flash(
+    flash: &mut impl FlashOps,
+    bank_addr: u32,
+    size: u32,
+    on_progress: impl FnMut(ProgressPhase, u32, u32),
+) -> Result<(), FlashError> {
+    persist_ram_range(flash, bank_addr, 0, size, true, on_progress)
+}
 
-    // Program trailing partial page padded with 0xFF to avoid writing stale RAM bytes.
-    let trailing_bytes = size - full_page_bytes;
-    if trailing_bytes > 0 {
-        let mut last_page = [0xFFu8; FLASH_PAGE_SIZE as usize];
-        core::ptr::copy_nonoverlapping(
-            ram_base.add(full_page_bytes as usize),
-            last_page.as_mut_ptr(),
-            trailing_bytes as usize,
-        );
-        flash::flash_program(
-            flash_offset + full_page_bytes,
-            last_page.as_ptr(),
-            last_page.len(),
-        );
+/// Write `len` bytes of the RAM buffer starting at `ram_offset` to sector
+/// aligned `flash_addr` with `flash_ops::write_image`, erasing the sectors
+/// first with `flash_ops::erase_region` when `erase` is set; without it the
+/// range must already be erased.
+pub(super) fn persist_ram_range(
+    flash: &mut impl FlashOps,
+    flash_addr: u32,
+    ram_offset: u32,
+    len: u32,
+    erase: bool,
+    mut on_progress: impl FnMut(ProgressPhase, u32, u32),
+) -> Result<(), FlashError> {
+    if erase {
+        flash_ops::erase_region(flash, flash_addr, len, |done, total| {
+            on_progress(ProgressPhase::Erase, done, total)
+        })?;
     }
+    flash_ops::write_image(
+        flash,
+        flash_addr - FLASH_BASE,
+        ram_buffer_range(ram_offset, len),
+        false,
+        on_progress,
+    )
 }
@@ -3,14 +3,24 @@
 
 //! USB CDC transport with COBS-framed postcard serialization.
 
-use crispy_common::protocol::{Command, Response};
+use crate::error_log;
+use crate::peripherals::{self, Peripherals};
+use crate::transport::{self, FrameBuffer, SendError, Transport, TransportStats, TX_BUF_SIZE};
+use crispy_common::protocol::{Command, ErrorCode, Response};
 use rp2040_hal::usb::UsbBus;
 use usb_device::class_prelude::UsbBusAllocator;
 use usb_device::prelude::*;
 use usbd_serial::SerialPort;
 
-const RX_BUF_SIZE: usize = 2048;
-const TX_BUF_SIZE: usize = 2048;
+/// Bytes taken from the CDC class per read (one full-speed bulk packet).
+const USB_READ_BUF_SIZE: usize = 64;
+
+/// USB polls `write_all` waits for TX space without progress before giving
+/// up on a response (`CRISPY_TX_POLL_BUDGET`).
+const TX_POLL_BUDGET: u32 = match u32::from_str_radix(env!("CRISPY_TX_POLL_BUDGET"), 10) {
+    Ok(budget) => budget,
+    Err(_) => panic!("CRISPY_TX_POLL_BUDGET is not a u32"),
+};
 
 #[derive(Debug, defmt::Format)]
 pub enum TransportError {
@@ -20,168 +30,93 @@ pub enum TransportError {
 pub struct UsbTransport {
     serial: SerialPort<'static, UsbBus>,
     usb_dev: UsbDevice<'static, UsbBus>,
-    rx_buf: [u8; RX_BUF_SIZE],
-    rx_pos: usize,
+    rx: FrameBuffer,
     /// Command decoded during drain_rx_to_buffer, delivered on next try_receive().
     pending_cmd: Option<Command>,
+    /// A partial frame was left on the wire by a stalled `write_all`.
+    tx_frame_open: bool,
+    stats: TransportStats,
 }
 
 impl UsbTransport {
     pub fn new(usb_bus: &'static UsbBusAllocator<UsbBus>) -> Result<Self, TransportError> {
-        let serial = SerialPort::new(usb_bus);
-        let usb_dev = UsbDeviceBuilder::new(usb_bus, UsbVidPid(0x2E8A, 0x000A))
+        // Fallible steps first: an error leaves no endpoints allocated, so
+        // the caller can retry with the same bus.
+        let builder = UsbDeviceBuilder::new(usb_bus, UsbVidPid(0x2E8A, 0x000A))
             .strings(&[StringDescriptors::default()
                 .manufacturer("ADNT")
                 .product("Crispy Bootloader")
                 .serial_number("0001")])
             .map_err(|_| TransportError::StringTooLong)?
-            .device_class(usbd_serial::USB_CLASS_CDC)
-            .build();
+            .device_class(usbd_serial::USB_CLASS_CDC);
+        let serial = SerialPort::new(usb_bus);
+        let usb_dev = builder.build();
 
         Ok(Self {
             serial,
             usb_dev,
-            rx_buf: [0u8; RX_BUF_SIZE],
-            rx_pos: 0,
+            rx: FrameBuffer::new(),
             pending_cmd: None,
+            tx_frame_open: false,
+            stats: TransportStats::default(),
         })
     }
 
-    /// Poll USB device. Must be called frequently.
-    pub fn poll(&mut self) -> bool {
-        self.usb_dev.poll(&mut [&mut self.serial])
-    }
-
-    /// Try to receive a complete COBS-framed command.
-    /// Returns `Some(Command)` when a full frame has been decoded.
-    /// Delivers commands buffered during TX drain before reading new data.
-    pub fn try_receive(&mut self) -> Option<Command> {
-        // Deliver command that was decoded during drain_rx_to_buffer first
-        if let Some(cmd) = self.pending_cmd.take() {
-            return Some(cmd);
-        }
-
-        const USB_READ_BUF_SIZE: usize = 64;
-        let mut tmp = [0u8; USB_READ_BUF_SIZE];
-
-        let count = self.serial.read(&mut tmp).ok()?;
-        if count == 0 {
-            return None;
+    /// Terminate a frame a previous `write_all` left unfinished.
+    fn close_open_frame(&mut self) -> Result<(), SendError> {
+        if !self.tx_frame_open {
+            return Ok(());
         }
-
-        for &byte in &tmp[..count] {
-            if let Some(cmd) = self.process_byte(byte) {
-                return Some(cmd);
-            }
-        }
-        None
-    }
-
-    /// Process a single received byte, handling COBS framing.
-    /// Returns `Some(Command)` when a complete frame is decoded.
-    fn process_byte(&mut self, byte: u8) -> Option<Command> {
-        match byte {
-            // COBS frame delimiter
-            0x00 => self.try_decode_frame(),
-            // Regular data byte
-            _ => {
-                self.append_byte(byte);
-                None
-            }
-        }
-    }
-
-    /// Append a byte to the receive buffer, handling overflow.
-    fn append_byte(&mut self, byte: u8) {
-        if self.rx_pos < RX_BUF_SIZE {
-            self.rx_buf[self.rx_pos] = byte;
-            self.rx_pos += 1;
-        } else {
-            // Buffer overflow - discard current frame
-            self.rx_pos = 0;
-        }
-    }
-
-    /// Try to decode the accumulated frame buffer as a Command.
-    fn try_decode_frame(&mut self) -> Option<Command> {
-        if self.rx_pos == 0 {
-            return None;
-        }
-
-        let result = postcard::from_bytes_cobs::<Command>(&mut self.rx_buf[..self.rx_pos]);
-        self.rx_pos = 0;
-        result.ok()
-    }
-
-    /// Send a response as a COBS-framed postcard message.
-    ///
-    /// Returns true if the response was fully sent.
-    pub fn send(&mut self, resp: &Response) -> bool {
-        defmt::println!("Transport: Sending response");
-        let mut buf = [0u8; TX_BUF_SIZE];
-        let encoded = match postcard::to_slice_cobs(resp, &mut buf) {
-            Ok(data) => {
-                defmt::println!("Transport: Encoded {} bytes", data.len());
-                data
-            }
-            Err(_) => {
-                defmt::error!("Failed to encode response");
-                return false;
-            }
-        };
-
-        let success = self.write_all(encoded);
-        defmt::println!("Transport: write_all returned {}", success);
-        success
+        self.write_all(&[0x00])
     }
 
     /// Write all bytes to USB serial, handling WouldBlock by polling.
     ///
-    /// Returns true if all data was sent, false if some data was dropped.
-    fn write_all(&mut self, data: &[u8]) -> bool {
+    /// Gives up after `TX_POLL_BUDGET` polls without progress. While waiting,
+    /// incoming bytes are drained so the host is not blocked on its own
+    /// writes, but only after a poll that saw USB activity and while the
+    /// pending command slot is free, so no decoded command is overwritten.
+    fn write_all(&mut self, data: &[u8]) -> Result<(), SendError> {
         let mut offset = 0;
-        let mut poll_count = 0;
-        const MAX_POLLS: usize = 100; // Prevent infinite blocking
+        let mut polls = 0;
 
         while offset < data.len() {
             match self.serial.write(&data[offset..]) {
                 Ok(n) => {
                     offset += n;
-                    poll_count = 0; // Reset on progress
+                    polls = 0; // Reset on progress
+                    self.tx_frame_open = offset < data.len();
                 }
                 Err(UsbError::WouldBlock) => {
-                    poll_count += 1;
-                    if poll_count > MAX_POLLS {
-                        defmt::warn!(
-                            "TX buffer full after {} polls, dropping {} bytes",
-                            MAX_POLLS,
-                            data.len() - offset
-                        );
-                        return false;
+                    polls += 1;
+                    if polls > TX_POLL_BUDGET {
+                        return Err(SendError::Stalled {
+                            sent: offset,
+                            total: data.len(),
+                        });
                     }
 
-                    // Poll device AND read RX to prevent buffer overflow
-                    self.poll();
-                    self.drain_rx_to_buffer();
+                    if self.poll() && self.pending_cmd.is_none() {
+                        self.drain_rx_to_buffer();
+                    }
                 }
                 Err(_) => {
-                    defmt::error!("USB write error");
-                    return false;
+                    error_log::record(ErrorCode::UsbWriteFailed);
+                    return Err(SendError::Usb);
                 }
             }
         }
-        true
+        Ok(())
     }
 
     /// Drain RX buffer without blocking, accumulating data for next try_receive()
     fn drain_rx_to_buffer(&mut self) {
-        // Don't drain if RX buffer is already >75% full to prevent corruption
-        if self.rx_pos > (RX_BUF_SIZE * 3 / 4) {
-            defmt::warn!("RX buffer nearly full ({}), skipping drain", self.rx_pos);
+        // Only drain when a whole read fits: bytes read here cannot be put back
+        if self.rx.free() < USB_READ_BUF_SIZE {
+            defmt::warn!("RX buffer nearly full, skipping drain");
             return;
         }
 
-        const USB_READ_BUF_SIZE: usize = 64;
         let mut tmp = [0u8; USB_READ_BUF_SIZE];
 
         // Read whatever is available (non-blocking)
@@ -190,26 +125,95 @@ impl UsbTransport {
                 defmt::trace!("Drained {} RX bytes during TX", count);
                 // Process bytes into our RX buffer
                 for &byte in &tmp[..count] {
-                    // Stop draining if buffer is getting full
-                    if self.rx_pos >= (RX_BUF_SIZE * 3 / 4) {
-                        defmt::warn!("RX buffer filling up during drain, stopping");
-                        break;
-                    }
-
-                    // Accumulate data - will be processed on next try_receive()
-                    if byte == 0x00 {
-                        // Frame delimiter - decode and buffer the command
-                        if let Some(cmd) = self.try_decode_frame() {
-                            if self.pending_cmd.is_some() {
-                                defmt::warn!("Pending command slot full, dropping command");
-                            }
-                            self.pending_cmd = Some(cmd);
+                    // Accumulate data; a completed command is delivered on
+                    // the next try_receive()
+                    if let Some(cmd) = self.rx.push(byte, &mut self.stats) {
+                        if self.pending_cmd.is_some() {
+                            error_log::record(ErrorCode::CommandDropped);
                         }
-                    } else {
-                        self.append_byte(byte);
+                        self.pending_cmd = Some(cmd);
                     }
                 }
             }
         }
     }
 }
+
+impl Transport for UsbTransport {
+    /// Poll USB device. Must be called frequently.
+    fn poll(&mut self) -> bool {
+        self.usb_dev.poll(&mut [&mut self.serial])
+    }
+
+    /// Try to receive a complete COBS-framed command.
+    /// Delivers commands buffered during TX drain before reading new data.
+    fn try_receive(&mut self) -> Option<Command> {
+        // Deliver command that was decoded during drain_rx_to_buffer first
+        if let Some(cmd) = self.pending_cmd.take() {
+            return Some(cmd);
+        }
+
+        let mut tmp = [0u8; USB_READ_BUF_SIZE];
+
+        let count = self.serial.read(&mut tmp).ok()?;
+        if count == 0 {
+            return None;
+        }
+
+        for &byte in &tmp[..count] {
+            if let Some(cmd) = self.rx.push(byte, &mut self.stats) {
+                return Some(cmd);
+            }
+        }
+        None
+    }
+
+    fn send(&mut self, resp: &Response) -> Result<(), SendError> {
+        let mut buf = [0u8; TX_BUF_SIZE];
+        let encoded = transport::encode(resp, &mut buf)?;
+
+        let result = self
+            .close_open_frame()
+            .and_then(|()| self.write_all(encoded));
+        if let Err(e) = result {
+            error_log::record(ErrorCode::TxDropped);
+            defmt::debug!("Response not sent: {:?}", e);
+            self.stats.tx_drops = self.stats.tx_drops.wrapping_add(1);
+        }
+        result
+    }
+
+    fn stats(&self) -> TransportStats {
+        self.stats
+    }
+}
+
+/// Bring up USB CDC on the board's USB controller.
+///
+/// The bus is created once; a retry after a failure reuses it.
+pub fn open(peripherals: &mut Peripherals) -> Option<UsbTransport> {
+    if let Some(mut usb) = peripherals.usb.take() {
+        let usb_bus = UsbBusAllocator::new(UsbBus::new(
+            usb.regs,
+            usb.dpram,
+            usb.clock,
+            true,
+            &mut usb.resets,
+        ));
+        peripherals::store_usb_bus(usb_bus);
+    } else if !peripherals::usb_bus_stored() {
+        defmt::warn!("Update: USB peripheral unavailable during initialization");
+        return None;
+    }
+
+    match UsbTransport::new(peripherals::usb_bus_ref()) {
+        Ok(transport) => {
+            defmt::println!("USB CDC initialized");
+            Some(transport)
+        }
+        Err(e) => {
+            defmt::error!("Failed to initialize USB transport: {:?}", e);
+            None
+        }
+    }
+}
@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Flash erase counters, kept as an append-only log in a dedicated sector.
+//!
+//! Every erase of a firmware bank or of the BootData sector appends a new
+//! 16-byte record holding all three counters to the sector at
+//! `WEAR_STATS_ADDR`. Appending only clears bits, so it needs a page program
+//! but no erase; the log sector itself is erased once every
+//! `RECORDS_PER_SECTOR` (256) events. This costs one extra page program per
+//! counted erase, and keeps the wear of the log sector at 1/256 of the
+//! BootData sector it is tracking. Counters survive resets and power loss
+//! (a torn record is ignored and the previous one is used).
+
+use crate::flash;
+use crispy_common::protocol::{FLASH_PAGE_SIZE, FLASH_SECTOR_SIZE, WEAR_STATS_ADDR};
+
+const RECORD_SIZE: u32 = 16;
+const RECORDS_PER_SECTOR: u32 = FLASH_SECTOR_SIZE / RECORD_SIZE;
+const RECORD_MAGIC: u32 = 0x5745_4152; // "WEAR"
+
+/// Flash regions whose erases are counted.
+#[derive(Clone, Copy)]
+pub enum WearRegion {
+    BankA,
+    BankB,
+    BootData,
+}
+
+impl WearRegion {
+    pub fn for_bank(bank: u8) -> Self {
+        if bank == 0 {
+            Self::BankA
+        } else {
+            Self::BankB
+        }
+    }
+}
+
+/// Erase counters per region.
+#[derive(Clone, Copy, Default)]
+pub struct WearStats {
+    pub bank_a_erases: u32,
+    pub bank_b_erases: u32,
+    pub bootdata_erases: u32,
+}
+
+impl WearStats {
+    fn check(&self) -> u32 {
+        RECORD_MAGIC ^ self.bank_a_erases ^ self.bank_b_erases ^ self.bootdata_erases
+    }
+
+    fn to_bytes(self) -> [u8; RECORD_SIZE as usize] {
+        let mut bytes = [0u8; RECORD_SIZE as usize];
+        bytes[0..4].copy_from_slice(&self.bank_a_erases.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.bank_b_erases.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.bootdata_erases.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.check().to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; RECORD_SIZE as usize]) -> Option<Self> {
+        let word =
+            |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let stats = Self {
+            bank_a_erases: word(0),
+            bank_b_erases: word(4),
+            bootdata_erases: word(8),
+        };
+        (stats.check() == word(12)).then_some(stats)
+    }
+}
+
+/// Latest counters and the slot index where the next record goes.
+fn scan() -> (WearStats, u32) {
+    let mut latest = WearStats::default();
+    let mut next_slot = 0;
+
+    for slot in 0..RECORDS_PER_SECTOR {
+        let mut bytes = [0u8; RECORD_SIZE as usize];
+        flash::flash_read(WEAR_STATS_ADDR + slot * RECORD_SIZE, &mut bytes);
+        if bytes.iter().all(|&b| b == 0xFF) {
+            continue;
+        }
+        if let Some(stats) = WearStats::from_bytes(&bytes) {
+            latest = stats;
+        }
+        next_slot = slot + 1;
+    }
+
+    (latest, next_slot)
+}
+
+/// Read the current erase counters.
+pub fn read() -> WearStats {
+    scan().0
+}
+
+/// Bytes of the log sector holding records.
+pub fn log_bytes() -> u32 {
+    scan().1 * RECORD_SIZE
+}
+
+/// Count one erase of `region` and append the updated counters to the log.
+/// The counters are best effort: a failed erase or program only loses this
+/// count.
+///
+/// # Safety
+/// The `flash::init()` function must have been called first.
+pub unsafe fn record_erase(region: WearRegion) {
+    let (mut stats, mut slot) = scan();
+    match region {
+        WearRegion::BankA => stats.bank_a_erases = stats.bank_a_erases.saturating_add(1),
+        WearRegion::BankB => stats.bank_b_erases = stats.bank_b_erases.saturating_add(1),
+        WearRegion::BootData => stats.bootdata_erases = stats.bootdata_erases.saturating_add(1),
+    }
+
+    let sector_offset = flash::addr_to_offset(WEAR_STATS_ADDR);
+    if slot >= RECORDS_PER_SECTOR {
+        if flash::flash_erase(sector_offset, FLASH_SECTOR_SIZE).is_err() {
+            return;
+        }
+        slot = 0;
+    }
+
+    // Program the whole page with 0xFF around the new record: erased bytes
+    // stay erased and records already in this page are left untouched.
+    let byte_offset = slot * RECORD_SIZE;
+    let page_start = byte_offset - byte_offset % FLASH_PAGE_SIZE;
+    let mut page = [0xFFu8; FLASH_PAGE_SIZE as usize];
+    let in_page = (byte_offset - page_start) as usize;
+    page[in_page..in_page + RECORD_SIZE as usize].copy_from_slice(&stats.to_bytes());
+
+    flash::flash_program(sector_offset + page_start, page.as_ptr(), page.len()).ok();
+}
@@ -38,10 +38,13 @@ from .protocol import (
     BootState,
     StatusResponse,
     AckResponse,
+    ProgressPhase,
+    ProgressResponse,
     encode_get_status,
     encode_start_update,
     encode_data_block,
     encode_finish_update,
+    encode_finish_update_no_activate,
     encode_reboot,
     decode_response,
 )
@@ -73,11 +76,14 @@ __all__ = [
     "BootState",
     "StatusResponse",
     "AckResponse",
+    "ProgressPhase",
+    "ProgressResponse",
     # Protocol encoding
     "encode_get_status",
     "encode_start_update",
     "encode_data_block",
     "encode_finish_update",
+    "encode_finish_update_no_activate",
     "encode_reboot",
     "decode_response",
     # Transport
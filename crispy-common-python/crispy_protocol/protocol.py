@@ -17,6 +17,8 @@ class CommandType(IntEnum):
     REBOOT = 4
     SET_ACTIVE_BANK = 5
     WIPE_ALL = 6
+    # 7-11 (EnterBootrom ... GetBuildInfo) are not mirrored here yet
+    FINISH_UPDATE_NO_ACTIVATE = 12
 
 
 class Command:
@@ -36,6 +38,10 @@ class Command:
     def finish_update() -> bytes:
         return encode_finish_update()
 
+    @staticmethod
+    def finish_update_no_activate() -> bytes:
+        return encode_finish_update_no_activate()
+
     @staticmethod
     def reboot() -> bytes:
         return encode_reboot()
@@ -56,6 +62,9 @@ class AckStatus(IntEnum):
     BAD_COMMAND = 3
     BAD_STATE = 4
     BANK_INVALID = 5
+    VERSION_TOO_OLD = 6
+    BANK_LOCKED = 7
+    BUSY = 8
 
     def __str__(self) -> str:
         return self.name
@@ -70,9 +79,19 @@ class BootState(IntEnum):
         return self.name
 
 
+class ProgressPhase(IntEnum):
+    ERASE = 0
+    PROGRAM = 1
+    VERIFY = 2
+
+    def __str__(self) -> str:
+        return self.name
+
+
 class Response:
     TYPE_ACK = 0
     TYPE_STATUS = 1
+    TYPE_PROGRESS = 2
 
 
 @dataclass
@@ -99,7 +118,14 @@ class StatusResponse:
         return "A" if self.active_bank == 0 else "B"
 
 
-ResponseType = Union[AckResponse, StatusResponse]
+@dataclass
+class ProgressResponse:
+    phase: ProgressPhase
+    percent: int
+    type: int = Response.TYPE_PROGRESS
+
+
+ResponseType = Union[AckResponse, StatusResponse, ProgressResponse]
 
 
 def _frame(data: bytes) -> bytes:
@@ -138,6 +164,10 @@ def encode_finish_update() -> bytes:
     return _simple_command(CommandType.FINISH_UPDATE)
 
 
+def encode_finish_update_no_activate() -> bytes:
+    return _simple_command(CommandType.FINISH_UPDATE_NO_ACTIVATE)
+
+
 def encode_reboot() -> bytes:
     return _simple_command(CommandType.REBOOT)
 
@@ -192,5 +222,10 @@ def decode_response(data: bytes) -> ResponseType:
             bootloader_version=bootloader_version,
         )
 
+    elif resp_type == Response.TYPE_PROGRESS:
+        if len(decoded) < 3:
+            raise ValueError("Truncated Progress response")
+        return ProgressResponse(phase=ProgressPhase(decoded[1]), percent=decoded[2])
+
     else:
         raise ValueError(f"Unknown response type: {resp_type}")
@@ -12,12 +12,14 @@ from .protocol import (
     ResponseType,
     AckResponse,
     StatusResponse,
+    ProgressResponse,
     AckStatus,
     decode_response,
     encode_get_status,
     encode_start_update,
     encode_data_block,
     encode_finish_update,
+    encode_finish_update_no_activate,
     encode_reboot,
 )
 
@@ -80,9 +82,20 @@ class Transport:
                 break
         return bytes(result)
 
+    def _receive_final(
+        self, on_progress: Optional[Callable[[ProgressResponse], None]] = None
+    ) -> ResponseType:
+        """Receive the next response, skipping intermediate Progress frames."""
+        while True:
+            resp = decode_response(self._receive())
+            if not isinstance(resp, ProgressResponse):
+                return resp
+            if on_progress:
+                on_progress(resp)
+
     def _send_recv(self, data: bytes) -> ResponseType:
         self._send(data)
-        return decode_response(self._receive())
+        return self._receive_final()
 
     def _expect(self, data: bytes, expected_type: type):
         resp = self._send_recv(data)
@@ -95,8 +108,10 @@ class Transport:
     def send(self, data: bytes) -> None:
         self._send(data)
 
-    def receive(self) -> ResponseType:
-        return decode_response(self._receive())
+    def receive(
+        self, on_progress: Optional[Callable[[ProgressResponse], None]] = None
+    ) -> ResponseType:
+        return self._receive_final(on_progress)
 
     def get_status(self) -> StatusResponse:
         return self._expect(encode_get_status(), StatusResponse)
@@ -107,8 +122,10 @@ class Transport:
     def send_data_block(self, offset: int, data: bytes) -> AckResponse:
         return self._expect(encode_data_block(offset, data), AckResponse)
 
-    def finish_update(self) -> AckResponse:
-        return self._expect(encode_finish_update(), AckResponse)
+    def finish_update(self, activate: bool = True) -> AckResponse:
+        """Store the uploaded image; with ``activate=False`` the active bank is kept."""
+        frame = encode_finish_update() if activate else encode_finish_update_no_activate()
+        return self._expect(frame, AckResponse)
 
     def reboot(self) -> AckResponse:
         return self._expect(encode_reboot(), AckResponse)
@@ -120,6 +137,7 @@ class Transport:
         version: int,
         chunk_size: int = 1024,
         progress_callback: Optional[Callable[[int, int], None]] = None,
+        activate: bool = True,
     ) -> None:
         size = len(firmware)
         checksum = crc32(firmware)
@@ -141,7 +159,7 @@ class Transport:
             if progress_callback:
                 progress_callback(offset, size)
 
-        resp = self.finish_update()
+        resp = self.finish_update(activate)
         if not resp.is_ok:
             if resp.status == AckStatus.CRC_ERROR:
                 raise UploadError("CRC verification failed")
@@ -154,8 +172,11 @@ class Transport:
         version: int,
         chunk_size: int = 1024,
         progress_callback: Optional[Callable[[int, int], None]] = None,
+        activate: bool = True,
     ) -> int:
         """Upload firmware from file. Returns CRC-32 of uploaded data."""
         firmware = Path(path).read_bytes()
-        self.upload_firmware(firmware, bank, version, chunk_size, progress_callback)
+        self.upload_firmware(
+            firmware, bank, version, chunk_size, progress_callback, activate
+        )
         return crc32(firmware)
@@ -10,10 +10,13 @@ from crispy_protocol.protocol import (
     BootState,
     AckResponse,
     StatusResponse,
+    ProgressPhase,
+    ProgressResponse,
     encode_get_status,
     encode_start_update,
     encode_data_block,
     encode_finish_update,
+    encode_finish_update_no_activate,
     encode_reboot,
     encode_set_active_bank,
     encode_wipe_all,
@@ -35,10 +38,11 @@ class TestCommandEnum:
         assert CommandType.REBOOT == 4
         assert CommandType.SET_ACTIVE_BANK == 5
         assert CommandType.WIPE_ALL == 6
+        assert CommandType.FINISH_UPDATE_NO_ACTIVATE == 12
 
     def test_all_members(self):
         """All expected commands exist."""
-        assert len(CommandType) == 7
+        assert len(CommandType) == 8
 
 
 class TestAckStatusEnum:
@@ -52,6 +56,9 @@ class TestAckStatusEnum:
         assert AckStatus.BAD_COMMAND == 3
         assert AckStatus.BAD_STATE == 4
         assert AckStatus.BANK_INVALID == 5
+        assert AckStatus.VERSION_TOO_OLD == 6
+        assert AckStatus.BANK_LOCKED == 7
+        assert AckStatus.BUSY == 8
 
     def test_str(self):
         """AckStatus __str__ returns name."""
@@ -212,6 +219,18 @@ class TestEncodeFinishUpdate:
         assert decoded == bytes([CommandType.FINISH_UPDATE])
 
 
+class TestEncodeFinishUpdateNoActivate:
+    """Tests for encode_finish_update_no_activate."""
+
+    def test_encodes_correctly(self):
+        """FinishUpdateNoActivate command encodes correctly."""
+        encoded = encode_finish_update_no_activate()
+        assert encoded[-1] == 0
+
+        decoded = cobs_decode(encoded[:-1])
+        assert decoded == bytes([CommandType.FINISH_UPDATE_NO_ACTIVATE])
+
+
 class TestEncodeReboot:
     """Tests for encode_reboot."""
 
@@ -342,6 +361,25 @@ class TestDecodeResponse:
         assert resp.version_a == 10
         assert resp.version_b == 20
 
+    def test_decode_progress_response(self):
+        """Decode Progress response."""
+        from crispy_protocol.cobs import cobs_encode
+        raw = bytes([2, ProgressPhase.PROGRAM, 42])  # Type 2 = Progress
+        framed = cobs_encode(raw) + b"\x00"
+
+        resp = decode_response(framed)
+        assert isinstance(resp, ProgressResponse)
+        assert resp.phase == ProgressPhase.PROGRAM
+        assert resp.percent == 42
+
+    def test_decode_truncated_progress_raises(self):
+        """Truncated Progress response raises ValueError."""
+        from crispy_protocol.cobs import cobs_encode
+        framed = cobs_encode(bytes([2, 0])) + b"\x00"
+
+        with pytest.raises(ValueError, match="Truncated Progress"):
+            decode_response(framed)
+
     def test_decode_without_delimiter(self):
         """Decode response without trailing delimiter."""
         from crispy_protocol.cobs import cobs_encode
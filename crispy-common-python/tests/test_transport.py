@@ -19,6 +19,7 @@ from crispy_protocol.protocol import (
     BootState,
     AckResponse,
     StatusResponse,
+    ProgressPhase,
 )
 from crispy_protocol.cobs import cobs_encode
 from crispy_protocol.varint import encode_varint
@@ -31,6 +32,12 @@ def make_ack_response(status: AckStatus) -> bytes:
     return cobs_encode(raw) + b"\x00"
 
 
+def make_progress_response(phase: ProgressPhase, percent: int) -> bytes:
+    """Create a framed Progress response."""
+    raw = bytes([2, phase, percent])  # Type 2 = Progress
+    return cobs_encode(raw) + b"\x00"
+
+
 def make_status_response(
     active_bank: int,
     version_a: int,
@@ -319,6 +326,23 @@ class TestTransportFinishUpdate:
         assert resp.is_ok is False
         assert resp.status == AckStatus.CRC_ERROR
 
+    @patch('crispy_protocol.transport.serial.Serial')
+    @patch('crispy_protocol.transport.time.sleep')
+    def test_finish_update_skips_progress(self, mock_sleep, mock_serial_class):
+        """finish_update ignores Progress frames before the final Ack."""
+        mock_serial = MockSerial([
+            make_progress_response(ProgressPhase.ERASE, 50),
+            make_progress_response(ProgressPhase.VERIFY, 100),
+            make_ack_response(AckStatus.OK),
+        ])
+        mock_serial_class.return_value = mock_serial
+
+        t = Transport("/dev/ttyACM0")
+        resp = t.finish_update()
+
+        assert isinstance(resp, AckResponse)
+        assert resp.is_ok is True
+
 
 class TestTransportReboot:
     """Tests for reboot method."""
@@ -0,0 +1,195 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! BootData journal: the BootData sector as an append-only log of records.
+//!
+//! Every BootData write appends a 64-byte record (the BootData, a sequence
+//! number and a CRC) to the first erased slot of the sector at
+//! `BOOT_DATA_ADDR`. Appending only clears bits, so it needs a page program
+//! but no erase; the sector is erased once every `RECORDS_PER_SECTOR` (64)
+//! writes. Readers take the valid record with the highest sequence number,
+//! so a write torn by a power loss leaves the previous record in effect.
+//!
+//! Every write then appends the same BootData to a second journal in the
+//! sector at `BOOT_DATA_MIRROR_ADDR`. When the BootData sector holds no valid
+//! record (a power loss during its erase, a failing flash cell), [`select`]
+//! falls back to the mirror, and the bootloader restores the BootData sector
+//! from it at startup. The mirror is written second, so it is never newer,
+//! unless it holds no valid record: then it is written first
+//! ([`mirror_first`]), so that erasing a full BootData sector never destroys
+//! the only valid record. Either way a power loss after any erase or program
+//! leaves the previous or the new BootData in effect.
+//!
+//! The CRC covers every BootData field, so a flipped bit in a size or CRC is
+//! caught rather than only a bad magic. A bare BootData at the start of the
+//! sector, as written before the journal existed, is still read while the
+//! sector holds no journal record. The bootloader and firmware share this
+//! module; it only works on sector contents so it can be tested on the host.
+
+use crate::crc32::crc32;
+use crate::protocol::{BootData, BOOT_DATA_SIZE, FLASH_PAGE_SIZE, FLASH_SECTOR_SIZE};
+
+pub const RECORD_SIZE: u32 = 64;
+pub const RECORDS_PER_SECTOR: u32 = FLASH_SECTOR_SIZE / RECORD_SIZE;
+
+const SEQ_OFFSET: usize = BOOT_DATA_SIZE;
+const CRC_OFFSET: usize = SEQ_OFFSET + 4;
+
+// The rest of the record stays erased.
+const _: () = assert!(CRC_OFFSET + 4 <= RECORD_SIZE as usize);
+
+/// Contents of the BootData sector.
+pub type Sector = [u8; FLASH_SECTOR_SIZE as usize];
+
+/// A valid journal record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Record {
+    pub slot: u32,
+    pub seq: u32,
+    pub boot_data: BootData,
+}
+
+/// Encode `bd` as the record with sequence number `seq`.
+pub fn encode(bd: &BootData, seq: u32) -> [u8; RECORD_SIZE as usize] {
+    let mut record = [0xFFu8; RECORD_SIZE as usize];
+    record[..BOOT_DATA_SIZE].copy_from_slice(bd.as_bytes());
+    record[SEQ_OFFSET..CRC_OFFSET].copy_from_slice(&seq.to_le_bytes());
+    let crc = crc32(&record[..CRC_OFFSET]);
+    record[CRC_OFFSET..CRC_OFFSET + 4].copy_from_slice(&crc.to_le_bytes());
+    record
+}
+
+/// Sequence number and BootData of a record; `None` if it is erased, torn
+/// or corrupt.
+pub fn decode(record: &[u8; RECORD_SIZE as usize]) -> Option<(u32, BootData)> {
+    let word =
+        |i: usize| u32::from_le_bytes([record[i], record[i + 1], record[i + 2], record[i + 3]]);
+    if crc32(&record[..CRC_OFFSET]) != word(CRC_OFFSET) {
+        return None;
+    }
+    let mut bd = [0u8; BOOT_DATA_SIZE];
+    bd.copy_from_slice(&record[..BOOT_DATA_SIZE]);
+    Some((word(SEQ_OFFSET), BootData::from_bytes(&bd)))
+}
+
+fn slot(sector: &Sector, index: u32) -> &[u8; RECORD_SIZE as usize] {
+    let start = (index * RECORD_SIZE) as usize;
+    sector[start..start + RECORD_SIZE as usize]
+        .try_into()
+        .unwrap()
+}
+
+/// The valid record with the highest sequence number, if any.
+pub fn newest(sector: &Sector) -> Option<Record> {
+    (0..RECORDS_PER_SECTOR)
+        .filter_map(|index| {
+            decode(slot(sector, index)).map(|(seq, boot_data)| Record {
+                slot: index,
+                seq,
+                boot_data,
+            })
+        })
+        .max_by_key(|record| record.seq)
+}
+
+/// The current BootData: the newest record, or else a pre-journal record at
+/// the start of the sector. Callers check `BootData::is_valid` as for any
+/// record.
+///
+/// A corrupt journal record in the first slot is not read as a pre-journal
+/// one: its sequence number and CRC are not erased. It reads as the erased
+/// record instead, which fails `is_valid`.
+pub fn read(sector: &Sector) -> BootData {
+    if let Some(record) = newest(sector) {
+        return record.boot_data;
+    }
+    let mut bd = [0xFFu8; BOOT_DATA_SIZE];
+    if sector[SEQ_OFFSET..CRC_OFFSET + 4]
+        .iter()
+        .all(|&b| b == 0xFF)
+    {
+        bd.copy_from_slice(&sector[..BOOT_DATA_SIZE]);
+    }
+    BootData::from_bytes(&bd)
+}
+
+/// Bytes of the sector in use: up to the last slot that is not erased.
+pub fn used_bytes(sector: &Sector) -> u32 {
+    next_slot(sector) * RECORD_SIZE
+}
+
+/// Slot after the last one that is not erased; torn records and a
+/// pre-journal record count as used.
+fn next_slot(sector: &Sector) -> u32 {
+    (0..RECORDS_PER_SECTOR)
+        .rev()
+        .find(|&index| slot(sector, index).iter().any(|&b| b != 0xFF))
+        .map_or(0, |index| index + 1)
+}
+
+/// Where the next record goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WritePlan {
+    /// The sector is full and must be erased first.
+    pub erase: bool,
+    pub slot: u32,
+    pub seq: u32,
+}
+
+/// Plan the write of the next record into `sector`.
+pub fn plan_write(sector: &Sector) -> WritePlan {
+    let seq = newest(sector).map_or(1, |record| record.seq.wrapping_add(1));
+    match next_slot(sector) {
+        RECORDS_PER_SECTOR => WritePlan {
+            erase: true,
+            slot: 0,
+            seq,
+        },
+        slot => WritePlan {
+            erase: false,
+            slot,
+            seq,
+        },
+    }
+}
+
+impl WritePlan {
+    /// Sector offset of the page to program and its contents: `bd`'s record
+    /// in its slot and `0xFF` around it, so records already in the page are
+    /// left untouched.
+    pub fn page(&self, bd: &BootData) -> (u32, [u8; FLASH_PAGE_SIZE as usize]) {
+        let offset = self.slot * RECORD_SIZE;
+        let page_start = offset - offset % FLASH_PAGE_SIZE;
+        let in_page = (offset - page_start) as usize;
+        let mut page = [0xFFu8; FLASH_PAGE_SIZE as usize];
+        page[in_page..in_page + RECORD_SIZE as usize].copy_from_slice(&encode(bd, self.seq));
+        (page_start, page)
+    }
+}
+
+/// Whether a write must append to the mirror before the BootData sector: the
+/// mirror holds no valid record, so the BootData sector may hold the only
+/// one, and erasing it first (when its journal is full) would leave none
+/// until its new record is programmed.
+pub fn mirror_first(mirror: &Sector) -> bool {
+    !read(mirror).is_valid()
+}
+
+/// Which sector [`select`] took the BootData from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Primary,
+    Mirror,
+}
+
+/// The BootData in effect given the BootData sector and its mirror: the
+/// primary's if it passes `BootData::is_valid`, else the mirror's if that
+/// does, or `None` if neither does.
+pub fn select(primary: &Sector, mirror: &Sector) -> Option<(BootData, Source)> {
+    let bd = read(primary);
+    if bd.is_valid() {
+        return Some((bd, Source::Primary));
+    }
+    let bd = read(mirror);
+    bd.is_valid().then_some((bd, Source::Mirror))
+}
@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Boot bank selection: which bank to boot, or why to stay in update mode.
+//!
+//! The decision is pure so it can be tested on the host. The bootloader
+//! supplies BootData, the reset reason and a bank check (vector table and
+//! CRC); it then logs the action, persists the updated BootData and jumps.
+
+use crate::protocol::{BootData, BOOT_FLAG_FALLBACK};
+use crate::reset_reason::{boot_decision, BootDecision, ResetReason};
+
+/// Why no bank A/B image can be booted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UpdateReason {
+    /// BootData records no image in either bank (or is not valid).
+    NoFirmware,
+    /// The active image used up its attempts and the other bank fails its
+    /// checks, so it is not booted again.
+    RollbackTargetInvalid,
+    /// Neither bank passes its checks.
+    NothingBootable,
+}
+
+/// Outcome of boot bank selection.
+///
+/// `boot_data` is the record to persist before the jump; it equals the input
+/// when nothing changed (a confirmed image).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootAction {
+    /// Boot the active bank.
+    Boot { bank: u8, boot_data: BootData },
+    /// The active image used up its attempts: boot the other bank, now active.
+    Rollback { bank: u8, boot_data: BootData },
+    /// The active bank fails its checks: boot the other bank, now active,
+    /// with `BOOT_FLAG_FALLBACK` set.
+    Fallback { bank: u8, boot_data: BootData },
+    /// Boot nothing from A or B.
+    UpdateMode(UpdateReason),
+}
+
+fn other_bank(bank: u8) -> u8 {
+    if bank == 0 {
+        1
+    } else {
+        0
+    }
+}
+
+/// Select what to boot.
+///
+/// `bank_ok(bd, bank)` reports whether `bank` passes its image checks; it is
+/// only called for banks that are candidates, so a confirmed active image
+/// costs one check. An unconfirmed image is counted per `boot_decision` and
+/// rolled back after `max_attempts` boots. A bank chosen by rollback or
+/// fallback starts a fresh trial on its first attempt.
+pub fn select_boot_action(
+    bd: &BootData,
+    reset_reason: ResetReason,
+    max_attempts: u8,
+    mut bank_ok: impl FnMut(&BootData, u8) -> bool,
+) -> BootAction {
+    let mut bd = if bd.is_valid() {
+        *bd
+    } else {
+        BootData::default_new()
+    };
+    if bd.size_a == 0 && bd.size_b == 0 {
+        return BootAction::UpdateMode(UpdateReason::NoFirmware);
+    }
+
+    let decision = boot_decision(
+        reset_reason,
+        bd.confirmed != 0,
+        bd.boot_attempts,
+        max_attempts,
+    );
+    let other = other_bank(bd.active_bank);
+
+    if decision == BootDecision::Rollback {
+        if !bank_ok(&bd, other) {
+            return BootAction::UpdateMode(UpdateReason::RollbackTargetInvalid);
+        }
+        bd.active_bank = other;
+        bd.confirmed = 0;
+        bd.boot_attempts = 1;
+        return BootAction::Rollback {
+            bank: other,
+            boot_data: bd,
+        };
+    }
+
+    if bank_ok(&bd, bd.active_bank) {
+        if decision == BootDecision::Count {
+            bd.boot_attempts = bd.boot_attempts.saturating_add(1);
+        }
+        return BootAction::Boot {
+            bank: bd.active_bank,
+            boot_data: bd,
+        };
+    }
+
+    // e.g. bank A erased by an interrupted update while bank B still holds
+    // the previous image: boot B rather than dropping to update mode.
+    if bank_ok(&bd, other) {
+        bd.active_bank = other;
+        bd.confirmed = 0;
+        bd.boot_attempts = 1;
+        bd.flags |= BOOT_FLAG_FALLBACK;
+        return BootAction::Fallback {
+            bank: other,
+            boot_data: bd,
+        };
+    }
+
+    BootAction::UpdateMode(UpdateReason::NothingBootable)
+}
@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Sanity checks for a bootloader image before it replaces the running one.
+//!
+//! A bootloader image is the raw binary of the whole bootloader region,
+//! starting at `FLASH_BASE`: the 256-byte boot2 stage, then the bootloader's
+//! vector table. Writing anything else there leaves a device that only
+//! starts in BOOTSEL mode, so both the host and the bootloader check the
+//! image before it is flashed.
+
+use crate::crc32::CrcAlgorithm;
+use crate::protocol::{BOOTLOADER_REGION_SIZE, FLASH_BASE};
+
+/// Size of the second-stage bootloader, including its trailing CRC.
+pub const BOOT2_SIZE: usize = 256;
+
+/// The boot ROM checks boot2 with CRC-32/MPEG-2 over its first 252 bytes.
+const BOOT2_CRC: CrcAlgorithm = CrcAlgorithm::Mpeg2;
+
+/// SRAM, including the two 4KB scratch banks.
+const SRAM_START: u32 = 0x2000_0000;
+const SRAM_END: u32 = 0x2004_2000;
+
+/// Why an image was rejected as a bootloader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BootloaderImageError {
+    /// Shorter than boot2 plus the first two vector table words.
+    TooSmall,
+    /// Larger than the bootloader region.
+    TooLarge,
+    /// boot2 CRC mismatch: not a bootloader binary starting at `FLASH_BASE`
+    /// (for example a firmware image, or a UF2 file).
+    Boot2Crc,
+    /// Initial SP outside SRAM or not 8-byte aligned.
+    StackOutOfRange,
+    /// Reset vector not a Thumb address inside the bootloader region.
+    ResetOutOfRange,
+}
+
+/// Check that `image` is a bootloader binary for the bootloader region.
+pub fn validate_bootloader_image(image: &[u8]) -> Result<(), BootloaderImageError> {
+    if image.len() < BOOT2_SIZE + 8 {
+        return Err(BootloaderImageError::TooSmall);
+    }
+    if image.len() > BOOTLOADER_REGION_SIZE as usize {
+        return Err(BootloaderImageError::TooLarge);
+    }
+
+    let word = |i: usize| u32::from_le_bytes([image[i], image[i + 1], image[i + 2], image[i + 3]]);
+
+    if BOOT2_CRC.checksum(&image[..BOOT2_SIZE - 4]) != word(BOOT2_SIZE - 4) {
+        return Err(BootloaderImageError::Boot2Crc);
+    }
+
+    let initial_sp = word(BOOT2_SIZE);
+    if !(SRAM_START..=SRAM_END).contains(&initial_sp) || !initial_sp.is_multiple_of(8) {
+        return Err(BootloaderImageError::StackOutOfRange);
+    }
+
+    let reset_vector = word(BOOT2_SIZE + 4);
+    let code = (FLASH_BASE + BOOT2_SIZE as u32)..(FLASH_BASE + BOOTLOADER_REGION_SIZE);
+    if reset_vector & 1 == 0 || !code.contains(&(reset_vector & !1)) {
+        return Err(BootloaderImageError::ResetOutOfRange);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Image CRC-32 (ISO-HDLC, as used by `StartUpdate` and `BootData`) computed
+//! in pieces.
+//!
+//! The bootloader checksums an upload as it moves through the RAM buffer, so
+//! it has to extend a finished CRC with more bytes rather than hash the whole
+//! image at once. A host may give the `StartUpdate` CRC in another variant
+//! (`CrcAlgorithm`); `BootData` always holds ISO-HDLC.
+
+use crc::{Crc, NoTable, CRC_32_ISO_HDLC, CRC_32_MPEG_2};
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+/// MSB-first variants; BZIP2 is MPEG-2 with the result inverted. Computed
+/// bitwise: another 1KB lookup table does not fit the bootloader.
+const CRC32_MPEG2: Crc<u32, NoTable> = Crc::<u32, NoTable>::new(&CRC_32_MPEG_2);
+
+/// CRC-32 of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    CRC32.checksum(data)
+}
+
+/// Extend `crc`, the CRC-32 of some bytes, with `data`: the result is the
+/// CRC-32 of those bytes followed by `data`. The CRC of no bytes is 0.
+pub fn crc32_extend(crc: u32, data: &[u8]) -> u32 {
+    // Undo the final XOR; the reflected algorithm keeps its register
+    // bit-reversed relative to the `init` parameter.
+    let mut digest = CRC32.digest_with_initial((crc ^ 0xFFFF_FFFF).reverse_bits());
+    digest.update(data);
+    digest.finalize()
+}
+
+/// CRC-32 variant of `StartUpdate.crc32`, sent as `StartUpdate.crc_algo`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CrcAlgorithm {
+    /// CRC-32/ISO-HDLC (zlib, PNG); the default.
+    IsoHdlc,
+    /// CRC-32/MPEG-2.
+    Mpeg2,
+    /// CRC-32/BZIP2.
+    Bzip2,
+}
+
+impl CrcAlgorithm {
+    /// The algorithm with wire id `id`, if it is supported.
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::IsoHdlc),
+            1 => Some(Self::Mpeg2),
+            2 => Some(Self::Bzip2),
+            _ => None,
+        }
+    }
+
+    /// Wire id, for `StartUpdate.crc_algo`.
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    /// CRC of no bytes, to start [`Self::extend`] from.
+    pub fn empty(self) -> u32 {
+        match self {
+            Self::IsoHdlc | Self::Bzip2 => 0,
+            Self::Mpeg2 => 0xFFFF_FFFF,
+        }
+    }
+
+    /// CRC of `data`.
+    pub fn checksum(self, data: &[u8]) -> u32 {
+        self.extend(self.empty(), data)
+    }
+
+    /// Extend `crc`, the CRC of some bytes, with `data`, as [`crc32_extend`]
+    /// does for ISO-HDLC.
+    pub fn extend(self, crc: u32, data: &[u8]) -> u32 {
+        match self {
+            Self::IsoHdlc => crc32_extend(crc, data),
+            // No final XOR and no reflection: the CRC is the register.
+            Self::Mpeg2 => {
+                let mut digest = CRC32_MPEG2.digest_with_initial(crc);
+                digest.update(data);
+                digest.finalize()
+            }
+            Self::Bzip2 => {
+                let mut digest = CRC32_MPEG2.digest_with_initial(!crc);
+                digest.update(data);
+                !digest.finalize()
+            }
+        }
+    }
+}
@@ -5,39 +5,90 @@
 //!
 //! This module provides flash operations that can be used by firmware to:
 //! - Confirm boot (write confirmed=1 to BootData)
+//! - Stop the trial-boot watchdog once confirmed
 //! - Write firmware to banks (self-update capability)
 //! - Manage boot configuration
 
+use crate::boot_journal;
 use crate::protocol::{
-    BootData, BOOT_DATA_ADDR, FLASH_BASE, FLASH_PAGE_SIZE, FLASH_SECTOR_SIZE, FW_A_ADDR,
-    FW_BANK_SIZE, FW_B_ADDR, RAM_UPDATE_FLAG_ADDR, RAM_UPDATE_MAGIC,
+    BootData, BootInfo, BOOT_DATA_ADDR, BOOT_DATA_MIRROR_ADDR, BOOT_FLAG_FALLBACK, BOOT_INFO_ADDR,
+    CONFIG_ADDR, CONFIG_SLOTS, FLASH_BASE, FLASH_SECTOR_SIZE, FW_A_ADDR, FW_BANK_SIZE, FW_B_ADDR,
+    RAM_UPDATE_FLAG_ADDR, RAM_UPDATE_MAGIC, WATCHDOG_SCRATCH0_ADDR, WATCHDOG_UPDATE_MAGIC,
 };
 
-/// Read BootData from flash.
+/// Read the boot context left by the bootloader in the RAM mailbox.
+///
+/// Returns `None` if the mailbox does not hold a valid `BootInfo` (e.g. the
+/// firmware was started by a debugger or an older bootloader).
+pub fn boot_info() -> Option<BootInfo> {
+    let info = unsafe { BootInfo::read_from(BOOT_INFO_ADDR) };
+    info.is_valid().then_some(info)
+}
+
+/// Read BootData from flash: the newest record of the BootData journal, or
+/// of its mirror if the journal has no valid one.
 pub fn read_boot_data() -> BootData {
-    unsafe { BootData::read_from(BOOT_DATA_ADDR) }
+    match boot_journal::select(
+        journal_sector(BOOT_DATA_ADDR),
+        journal_sector(BOOT_DATA_MIRROR_ADDR),
+    ) {
+        Some((bd, _)) => bd,
+        None => boot_journal::read(journal_sector(BOOT_DATA_ADDR)),
+    }
 }
 
-/// Write BootData to flash.
+/// Append BootData to the BootData journal, then to its mirror, erasing each
+/// sector first only when it is full. A mirror without a valid record is
+/// written first instead (`boot_journal::mirror_first`).
 ///
 /// # Safety
 /// Caller must ensure no code is executing from flash during this operation.
 pub unsafe fn write_boot_data(bd: &BootData) {
-    let offset = BOOT_DATA_ADDR - FLASH_BASE;
+    let mut order = [BOOT_DATA_ADDR, BOOT_DATA_MIRROR_ADDR];
+    if boot_journal::mirror_first(journal_sector(BOOT_DATA_MIRROR_ADDR)) {
+        order.reverse();
+    }
+    append_boot_data(order[0], bd);
+    append_boot_data(order[1], bd);
+}
+
+unsafe fn append_boot_data(addr: u32, bd: &BootData) {
+    let offset = addr - FLASH_BASE;
+    let plan = boot_journal::plan_write(journal_sector(addr));
+    let (page_offset, page) = plan.page(bd);
+
+    cortex_m::interrupt::disable();
+    if plan.erase {
+        flash_erase_sector(offset);
+    }
+    flash_program(offset + page_offset, &page);
+    cortex_m::interrupt::enable();
+}
 
-    // Pad to page size
-    let mut page = [0xFFu8; FLASH_PAGE_SIZE as usize];
-    let src = bd.as_bytes();
-    page[..src.len()].copy_from_slice(src);
+fn journal_sector(addr: u32) -> &'static boot_journal::Sector {
+    unsafe { &*(addr as *const boot_journal::Sector) }
+}
 
-    flash_erase_and_program(offset, &page);
+/// Read user config slot `key`, as set with `crispy-upload config set`.
+///
+/// Returns `None` if `key` is not below `CONFIG_SLOTS`; a slot never set
+/// reads `CONFIG_UNSET`.
+pub fn read_config(key: u8) -> Option<u32> {
+    let slots = CONFIG_ADDR as *const u32;
+    (key < CONFIG_SLOTS).then(|| unsafe { slots.add(key as usize).read_volatile() })
 }
 
 /// Confirm the current boot to the bootloader.
 /// Sets confirmed=1 and boot_attempts=0 in BootData.
 ///
-/// Returns true if confirmation was successful, false if BootData is invalid.
+/// Returns true if confirmation was successful, false if BootData is invalid
+/// or the firmware was booted from the golden bank (which is never on trial,
+/// and must not confirm the failed A/B image).
 pub fn confirm_boot() -> bool {
+    if boot_info().is_some_and(|info| info.is_golden()) {
+        return false;
+    }
+
     let mut bd = read_boot_data();
 
     if !bd.is_valid() {
@@ -58,6 +109,23 @@ pub fn confirm_boot() -> bool {
     true
 }
 
+/// Stop the watchdog the bootloader arms before jumping to an unconfirmed
+/// image (`CRISPY_TRIAL_WATCHDOG_MS`, 8 s by default).
+///
+/// Call it right after `confirm_boot()`: until then a hang resets the chip
+/// and counts as a failed attempt. Firmware that wants a watchdog of its own
+/// can restart it with `hal::Watchdog::start` instead. Does nothing when the
+/// watchdog is not running (a confirmed boot, or an older bootloader).
+pub fn disarm_trial_watchdog() {
+    // SAFETY: a single read-modify-write of WATCHDOG.CTRL; the scratch
+    // registers, including the update request in SCRATCH0, are untouched
+    unsafe {
+        (*rp2040_hal::pac::WATCHDOG::ptr())
+            .ctrl()
+            .modify(|_, w| w.enable().clear_bit());
+    }
+}
+
 /// Set the active bank for next boot.
 ///
 /// # Arguments
@@ -77,6 +145,7 @@ pub fn set_active_bank(bank: u8) -> bool {
     bd.active_bank = bank;
     bd.confirmed = 0;
     bd.boot_attempts = 0;
+    bd.flags &= !BOOT_FLAG_FALLBACK;
 
     unsafe {
         write_boot_data(&bd);
@@ -224,6 +293,21 @@ pub fn reboot_to_bootloader() -> ! {
     cortex_m::peripheral::SCB::sys_reset();
 }
 
+/// Request bootloader update mode via the watchdog scratch register.
+///
+/// Writes `WATCHDOG_UPDATE_MAGIC` to `WATCHDOG.SCRATCH0` and triggers a
+/// system reset. The bootloader clears the register before entering update
+/// mode, so the request is consumed exactly once.
+pub fn request_bootloader() -> ! {
+    unsafe {
+        (WATCHDOG_SCRATCH0_ADDR as *mut u32).write_volatile(WATCHDOG_UPDATE_MAGIC);
+    }
+
+    cortex_m::asm::dsb();
+
+    cortex_m::peripheral::SCB::sys_reset();
+}
+
 /// Reboot normally.
 pub fn reboot() -> ! {
     cortex_m::peripheral::SCB::sys_reset();
@@ -231,9 +315,8 @@ pub fn reboot() -> ! {
 
 // --- Internal helpers ---
 
-unsafe fn flash_erase_and_program(offset: u32, data: &[u8]) {
-    cortex_m::interrupt::disable();
-
+/// Erase the sector at `offset`. Interrupts must be disabled.
+unsafe fn flash_erase_sector(offset: u32) {
     rp2040_hal::rom_data::connect_internal_flash();
     rp2040_hal::rom_data::flash_exit_xip();
     rp2040_hal::rom_data::flash_range_erase(
@@ -244,12 +327,13 @@ unsafe fn flash_erase_and_program(offset: u32, data: &[u8]) {
     );
     rp2040_hal::rom_data::flash_flush_cache();
     rp2040_hal::rom_data::flash_enter_cmd_xip();
+}
 
+/// Program `data` at `offset`. Interrupts must be disabled.
+unsafe fn flash_program(offset: u32, data: &[u8]) {
     rp2040_hal::rom_data::connect_internal_flash();
     rp2040_hal::rom_data::flash_exit_xip();
     rp2040_hal::rom_data::flash_range_program(offset, data.as_ptr(), data.len());
     rp2040_hal::rom_data::flash_flush_cache();
     rp2040_hal::rom_data::flash_enter_cmd_xip();
-
-    cortex_m::interrupt::enable();
 }
@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Bounds checks for the bootloader's flash erases and programs.
+//!
+//! boot2 and the bootloader sit at the start of flash; erasing a sector of
+//! either leaves a board that only boots to BOOTSEL. The bootloader checks
+//! every erase and program range of an update or BootData write against
+//! them and the end of flash before touching it, so a bad offset computation
+//! fails the command instead. Only the bootloader self-update writes that
+//! region, with its own copy routine.
+//!
+//! The end of flash is the smaller of the linker script's flash size and the
+//! capacity the part reports in its JEDEC ID, so a layout built for a larger
+//! part than the one fitted fails its writes instead of wrapping around.
+//!
+//! [`bootloader_protection`] picks the W25Q block-protect bits that keep the
+//! flash itself from erasing or programming the bottom of the part, boot2 and
+//! the bootloader, so that firmware writing flash by mistake cannot destroy
+//! them either.
+
+/// Why a range was refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RangeError {
+    /// The range overlaps boot2.
+    Boot2,
+    /// The range overlaps the bootloader code.
+    Bootloader,
+    /// The range runs past the end of flash.
+    PastEnd,
+}
+
+/// Flash-relative layout ranges are checked against: boot2 from offset 0,
+/// then the bootloader, then writable flash up to `flash_size`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FlashBounds {
+    pub boot2_end: u32,
+    pub bootloader_end: u32,
+    pub flash_size: u32,
+}
+
+impl FlashBounds {
+    /// Check the `len` bytes at flash-relative `offset`. Overlapping boot2
+    /// takes precedence over overlapping the bootloader, which takes
+    /// precedence over running past the end.
+    pub fn check(&self, offset: u32, len: u32) -> Result<(), RangeError> {
+        if offset < self.boot2_end {
+            Err(RangeError::Boot2)
+        } else if offset < self.bootloader_end {
+            Err(RangeError::Bootloader)
+        } else if offset
+            .checked_add(len)
+            .is_none_or(|end| end > self.flash_size)
+        {
+            Err(RangeError::PastEnd)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Smallest and largest capacity codes taken from a JEDEC ID: 64 KB, and the
+/// 16 MB the RP2040 can map through XIP.
+const CAPACITY_CODES: core::ops::RangeInclusive<u8> = 16..=24;
+
+/// Flash capacity in bytes from a JEDEC ID (manufacturer, memory type,
+/// capacity code), where the capacity code is log2 of the size in bytes.
+/// `None` for a code outside 64 KB..=16 MB, such as the `0x00` or `0xFF` of
+/// a part that did not answer.
+pub fn capacity_from_jedec(id: [u8; 3]) -> Option<u32> {
+    let code = id[2];
+    CAPACITY_CODES.contains(&code).then(|| 1 << code)
+}
+
+/// W25Q status register 1 block-protect bits BP0-BP2, TB (count from the
+/// bottom) and SEC (4 KB sectors rather than 64 KB blocks).
+pub const SR1_BP0: u8 = 1 << 2;
+pub const SR1_BP1: u8 = 1 << 3;
+pub const SR1_BP2: u8 = 1 << 4;
+pub const SR1_TB: u8 = 1 << 5;
+pub const SR1_SEC: u8 = 1 << 6;
+/// Every status register 1 bit that selects the protected range.
+pub const SR1_PROTECT_MASK: u8 = SR1_BP0 | SR1_BP1 | SR1_BP2 | SR1_TB | SR1_SEC;
+/// W25Q status register 2 bit that inverts the protected range; kept clear.
+pub const SR2_CMP: u8 = 1 << 6;
+
+/// A protected range at the bottom of flash: status register 1 bits within
+/// [`SR1_PROTECT_MASK`], and the bytes from offset 0 they cover.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockProtection {
+    pub sr1_bits: u8,
+    pub len: u32,
+}
+
+/// The largest bottom range of a W25Q part of `capacity` bytes that stays
+/// within `bootloader_end`, or `None` if none does.
+///
+/// Block protection (`BP = 001`, bottom) covers the lowest 64 KB up to 4 MB
+/// parts but 1/64 of larger ones, which would reach into bank A; those fall
+/// back to sector protection, at most 32 KB. `capacity` 0 (not detected)
+/// gets `None`: the table depends on it.
+pub fn bootloader_protection(capacity: u32, bootloader_end: u32) -> Option<BlockProtection> {
+    if capacity == 0 {
+        return None;
+    }
+    let block = BlockProtection {
+        sr1_bits: SR1_TB | SR1_BP0,
+        len: (capacity / 64).max(64 * 1024),
+    };
+    let sectors = [
+        (SR1_BP2, 32 * 1024),
+        (SR1_BP1 | SR1_BP0, 16 * 1024),
+        (SR1_BP1, 8 * 1024),
+        (SR1_BP0, 4 * 1024),
+    ]
+    .map(|(bits, len)| BlockProtection {
+        sr1_bits: SR1_SEC | SR1_TB | bits,
+        len,
+    });
+    core::iter::once(block)
+        .chain(sectors)
+        .find(|protection| protection.len <= bootloader_end && protection.len <= capacity)
+}
@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Flash layouts for the supported part sizes, chosen at build time.
+//!
+//! The `flash-4m`, `flash-8m` and `flash-16m` features select a 4, 8 or
+//! 16 MB layout; without one the layout is for the Pico's 2 MB. Only the
+//! bank size depends on it: boot2 and the bootloader, the BootData and wear
+//! stats sectors and the golden bank's range keep their sizes, and banks A
+//! and B split the rest. The bootloader, the firmware and the host tools
+//! must be built with the same feature; the bootloader's `build.rs` writes
+//! the matching sizes into its linker script.
+
+use crate::protocol::{BOOTLOADER_REGION_SIZE, FLASH_SECTOR_SIZE, FW_GOLD_SIZE};
+
+#[cfg(any(
+    all(feature = "flash-4m", feature = "flash-8m"),
+    all(feature = "flash-4m", feature = "flash-16m"),
+    all(feature = "flash-8m", feature = "flash-16m"),
+))]
+compile_error!("enable at most one of the flash-4m, flash-8m and flash-16m features");
+
+const MIB: u32 = 1024 * 1024;
+
+/// Part sizes with a layout, smallest first.
+pub const SUPPORTED_FLASH_SIZES: [u32; 4] = [2 * MIB, 4 * MIB, 8 * MIB, 16 * MIB];
+
+/// Flash size this build is laid out for.
+pub const FLASH_SIZE: u32 = if cfg!(feature = "flash-16m") {
+    16 * MIB
+} else if cfg!(feature = "flash-8m") {
+    8 * MIB
+} else if cfg!(feature = "flash-4m") {
+    4 * MIB
+} else {
+    2 * MIB
+};
+
+/// The layout of this build.
+pub const SELECTED: FlashLayout = match FlashLayout::for_flash_size(FLASH_SIZE) {
+    Some(layout) => layout,
+    None => panic!("FLASH_SIZE has no layout"),
+};
+
+/// The sizes that vary with the flash part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlashLayout {
+    /// Bytes of flash the layout spans, from `FLASH_BASE`.
+    pub flash_size: u32,
+    /// Size of bank A and of bank B.
+    pub bank_size: u32,
+}
+
+impl FlashLayout {
+    /// The layout for a `flash_size` byte part, if it is one of
+    /// [`SUPPORTED_FLASH_SIZES`].
+    pub const fn for_flash_size(flash_size: u32) -> Option<Self> {
+        let mut i = 0;
+        while i < SUPPORTED_FLASH_SIZES.len() {
+            if SUPPORTED_FLASH_SIZES[i] == flash_size {
+                // BootData and wear stats sectors, then the golden bank.
+                let fixed = BOOTLOADER_REGION_SIZE + 2 * FLASH_SECTOR_SIZE + FW_GOLD_SIZE;
+                let bank_size = (flash_size - fixed) / 2 / FLASH_SECTOR_SIZE * FLASH_SECTOR_SIZE;
+                return Some(Self {
+                    flash_size,
+                    bank_size,
+                });
+            }
+            i += 1;
+        }
+        None
+    }
+}
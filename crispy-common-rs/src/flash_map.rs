@@ -0,0 +1,44 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Assembly of the `Response::FlashMap` region list.
+//!
+//! The bootloader lists the regions its linker script assigns; this module
+//! fills in the unassigned gaps so the map covers the whole flash.
+
+use crate::protocol::{FlashRegion, FlashRegionKind, MAX_FLASH_REGIONS};
+
+/// The regions of `assigned` (in address order) with every gap between
+/// `flash_start`, them and `flash_end` listed as `Free`.
+///
+/// Regions that overlap or are out of order are kept as given, without a gap
+/// before them. Entries beyond `MAX_FLASH_REGIONS` are dropped.
+pub fn with_free_gaps(
+    assigned: &[FlashRegion],
+    flash_start: u32,
+    flash_end: u32,
+) -> heapless::Vec<FlashRegion, MAX_FLASH_REGIONS> {
+    let mut map = heapless::Vec::new();
+    let mut cursor = flash_start;
+
+    for region in assigned {
+        if region.start > cursor {
+            let _ = map.push(free(cursor, region.start));
+        }
+        let _ = map.push(*region);
+        cursor = cursor.max(region.start.saturating_add(region.len));
+    }
+    if flash_end > cursor {
+        let _ = map.push(free(cursor, flash_end));
+    }
+    map
+}
+
+fn free(start: u32, end: u32) -> FlashRegion {
+    FlashRegion {
+        start,
+        len: end - start,
+        used: 0,
+        kind: FlashRegionKind::Free,
+    }
+}
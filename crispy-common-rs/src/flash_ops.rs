@@ -0,0 +1,471 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Flash access behind a trait, so the update sequence runs the same against
+//! the bootloader's ROM-backed flash and, on the host, an in-memory flash.
+//!
+//! Offsets are flash-relative, like those of the ROM erase and program
+//! routines. Implementations refuse ranges their `check_range` refuses (see
+//! `flash_bounds`) before touching flash. [`write_image`] is the erase and
+//! program pass of `FinishUpdate` and `CopyBank`, [`erase_region`] and
+//! [`erase_bank`] the sector rounding every other erase shares, and
+//! [`self_test`] the `FlashSelfTest` sequence; [`MemFlash`]
+//! (`std` feature) emulates NOR flash for host tests of them.
+
+use crate::crc32::CrcAlgorithm;
+use crate::flash_bounds::RangeError;
+use crate::protocol::{
+    AckStatus, BootData, ProgressPhase, BOOT_DATA_ADDR, BOOT_DATA_MIRROR_ADDR, FLASH_BASE,
+    FLASH_PAGE_SIZE, FLASH_SECTOR_SIZE, FW_A_ADDR, FW_BANK_SIZE, FW_B_ADDR, SELFTEST_ERASE_FAILED,
+    SELFTEST_PASSED, SELFTEST_PROGRAM_FAILED, SELFTEST_RESTORE_FAILED, SELFTEST_VERIFY_FAILED,
+};
+
+/// Flash-relative offset of the BootData sector.
+pub const BOOT_DATA_OFFSET: u32 = BOOT_DATA_ADDR - FLASH_BASE;
+/// Flash-relative offset of the BootData mirror sector.
+pub const BOOT_DATA_MIRROR_OFFSET: u32 = BOOT_DATA_MIRROR_ADDR - FLASH_BASE;
+
+/// Pages programmed per `FlashOps::program` call by [`write_image`], to
+/// reduce XIP enter/exit overhead.
+const PROGRAM_BATCH_SIZE: u32 = FLASH_SECTOR_SIZE;
+
+/// A checked erase or program that did not take effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FlashError {
+    /// The range was refused before flash was touched.
+    OutOfRange(RangeError),
+    /// The page at this flash-relative offset still read back wrong after
+    /// one retry.
+    VerifyFailed { offset: u32 },
+    /// The flash routines were not set up (on the RP2040, `flash::init()`
+    /// had not resolved the ROM functions), so flash was not touched.
+    NotInitialized,
+    /// An erase started at this flash-relative offset, which is not on a
+    /// sector boundary. Nothing was erased.
+    Misaligned { offset: u32 },
+    /// [`erase_bank`] was given a bank other than A (0) or B (1).
+    InvalidBank(u8),
+    /// The part still reported a write in progress when the wait for it
+    /// ran out.
+    Busy,
+}
+
+impl From<RangeError> for FlashError {
+    fn from(err: RangeError) -> Self {
+        Self::OutOfRange(err)
+    }
+}
+
+/// The `Ack` a command answers when its flash write failed: `Busy`, which a
+/// host may retry, for a part still busy, `FlashError` otherwise.
+impl From<FlashError> for AckStatus {
+    fn from(err: FlashError) -> Self {
+        match err {
+            FlashError::Busy => Self::Busy,
+            _ => Self::FlashError,
+        }
+    }
+}
+
+/// Erase, program and read access to flash.
+pub trait FlashOps {
+    /// Refuse a range that overlaps boot2 or the bootloader, or runs past
+    /// the end of flash.
+    fn check_range(&self, offset: u32, len: u32) -> Result<(), RangeError>;
+
+    /// Erase the sectors of `len` bytes at `offset`, both sector aligned,
+    /// once the range passed `check_range`.
+    fn erase(&mut self, offset: u32, len: u32) -> Result<(), FlashError>;
+
+    /// Program `data`, whole pages, at page-aligned `offset` once the range
+    /// passed `check_range`, then read it back. `0xFF` bytes are not
+    /// compared: programming leaves them as they were.
+    fn program(&mut self, offset: u32, data: &[u8]) -> Result<(), FlashError>;
+
+    /// Read `buf.len()` bytes at `offset`.
+    fn read(&self, offset: u32, buf: &mut [u8]);
+
+    /// The BootData in effect: the newest valid journal record, from the
+    /// mirror if the BootData sector has none (`boot_journal::select`), or
+    /// `BootData::default_new()` if neither has one.
+    fn read_boot_data(&self) -> BootData;
+
+    /// Append `bd` to the BootData journal, then to its mirror, or the
+    /// mirror first when it holds no valid record (see
+    /// `boot_journal::mirror_first`). The second sector is left alone if the
+    /// first write fails.
+    fn write_boot_data(&mut self, bd: &BootData) -> Result<(), FlashError>;
+
+    /// Append the mirror's BootData to the BootData journal when only the
+    /// mirror holds a valid record. Returns whether it did.
+    fn restore_boot_data(&mut self) -> Result<bool, FlashError>;
+
+    /// The `algo` CRC of `len` bytes at `offset`, calling
+    /// `on_progress(bytes_done)` after each chunk.
+    fn compute_crc(
+        &self,
+        algo: CrcAlgorithm,
+        offset: u32,
+        len: u32,
+        mut on_progress: impl FnMut(u32),
+    ) -> u32 {
+        let mut crc = algo.empty();
+        let mut chunk = [0u8; 256];
+        let mut done = 0u32;
+        while done < len {
+            let n = (len - done).min(chunk.len() as u32);
+            self.read(offset + done, &mut chunk[..n as usize]);
+            crc = algo.extend(crc, &chunk[..n as usize]);
+            done += n;
+            on_progress(done);
+        }
+        crc
+    }
+
+    /// The CRC-32 (ISO-HDLC) of `len` bytes at `offset`, as BootData
+    /// records it.
+    fn compute_crc32(&self, offset: u32, len: u32) -> u32 {
+        self.compute_crc(CrcAlgorithm::IsoHdlc, offset, len, |_| {})
+    }
+}
+
+/// An erase of whole sectors done one sector per [`ErasePlan::step`], so the
+/// caller can service the host link in between: on the RP2040, interrupts
+/// stay disabled for one sector erase (about 50 ms) rather than for the
+/// whole range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErasePlan {
+    offset: u32,
+    done: u32,
+    total: u32,
+}
+
+impl ErasePlan {
+    /// Plan the erase of `len` bytes at sector-aligned `offset`, rounded up
+    /// to whole sectors. The alignment and the whole rounded range are
+    /// checked here, before any sector is erased.
+    pub fn new(flash: &impl FlashOps, offset: u32, len: u32) -> Result<Self, FlashError> {
+        if !offset.is_multiple_of(FLASH_SECTOR_SIZE) {
+            return Err(FlashError::Misaligned { offset });
+        }
+        let total = len
+            .div_ceil(FLASH_SECTOR_SIZE)
+            .checked_mul(FLASH_SECTOR_SIZE)
+            .ok_or(RangeError::PastEnd)?;
+        flash.check_range(offset, total)?;
+        Ok(Self {
+            offset,
+            done: 0,
+            total,
+        })
+    }
+
+    /// Erase the next sector, if any. Returns whether sectors remain.
+    pub fn step(&mut self, flash: &mut impl FlashOps) -> Result<bool, FlashError> {
+        if self.done < self.total {
+            flash.erase(self.offset + self.done, FLASH_SECTOR_SIZE)?;
+            self.done += FLASH_SECTOR_SIZE;
+        }
+        Ok(self.done < self.total)
+    }
+
+    /// Erase the remaining sectors, calling `on_progress(done, total)` in
+    /// bytes after each one.
+    pub fn run(
+        &mut self,
+        flash: &mut impl FlashOps,
+        mut on_progress: impl FnMut(u32, u32),
+    ) -> Result<(), FlashError> {
+        while self.done < self.total {
+            self.step(flash)?;
+            on_progress(self.done, self.total);
+        }
+        Ok(())
+    }
+}
+
+/// XIP address and size of firmware bank `bank` (0 = A, 1 = B).
+pub const fn bank_range(bank: u8) -> Option<(u32, u32)> {
+    match bank {
+        0 => Some((FW_A_ADDR, FW_BANK_SIZE)),
+        1 => Some((FW_B_ADDR, FW_BANK_SIZE)),
+        _ => None,
+    }
+}
+
+/// Erase the `len` bytes at sector-aligned XIP address `addr`, rounded up to
+/// whole sectors, calling `on_progress(done, total)` in bytes after each
+/// sector. Nothing is erased unless the whole rounded range passes
+/// `check_range`; an address below `FLASH_BASE` runs past the end.
+pub fn erase_region(
+    flash: &mut impl FlashOps,
+    addr: u32,
+    len: u32,
+    on_progress: impl FnMut(u32, u32),
+) -> Result<(), FlashError> {
+    ErasePlan::new(flash, addr.wrapping_sub(FLASH_BASE), len)?.run(flash, on_progress)
+}
+
+/// Erase firmware bank `bank` whole, `FW_BANK_SIZE` bytes, with
+/// [`erase_region`].
+pub fn erase_bank(
+    flash: &mut impl FlashOps,
+    bank: u8,
+    on_progress: impl FnMut(u32, u32),
+) -> Result<(), FlashError> {
+    let (addr, size) = bank_range(bank).ok_or(FlashError::InvalidBank(bank))?;
+    erase_region(flash, addr, size, on_progress)
+}
+
+/// Write `data` at sector-aligned `offset`, erasing the sectors first when
+/// `erase` is set; without it the range must already be erased. Pages are
+/// programmed in batches, each read back, and a trailing partial page is
+/// padded with `0xFF`. The first page that stays wrong stops the write, as
+/// does a range `check_range` refuses.
+///
+/// `on_progress(phase, done, total)` runs after every sector erase and every
+/// programmed batch.
+pub fn write_image<F: FlashOps>(
+    flash: &mut F,
+    offset: u32,
+    data: &[u8],
+    erase: bool,
+    mut on_progress: impl FnMut(ProgressPhase, u32, u32),
+) -> Result<(), FlashError> {
+    let len = data.len() as u32;
+    if erase {
+        ErasePlan::new(flash, offset, len)?.run(flash, |done, total| {
+            on_progress(ProgressPhase::Erase, done, total)
+        })?;
+    }
+
+    let full_page_bytes = len / FLASH_PAGE_SIZE * FLASH_PAGE_SIZE;
+    let mut done = 0u32;
+    while done < full_page_bytes {
+        let batch = (full_page_bytes - done).min(PROGRAM_BATCH_SIZE);
+        flash.program(offset + done, &data[done as usize..(done + batch) as usize])?;
+        done += batch;
+        on_progress(ProgressPhase::Program, done, len);
+    }
+
+    // Padded with 0xFF rather than programming whatever follows `data`.
+    if full_page_bytes < len {
+        let mut last_page = [0xFFu8; FLASH_PAGE_SIZE as usize];
+        last_page[..(len - full_page_bytes) as usize]
+            .copy_from_slice(&data[full_page_bytes as usize..]);
+        flash.program(offset + full_page_bytes, &last_page)?;
+        on_progress(ProgressPhase::Program, len, len);
+    }
+    Ok(())
+}
+
+/// Byte `index` of the self-test pattern: alternating `0x55` and `0xAA`
+/// pages, XORed with the byte's place in the page so a stuck address line
+/// shows up too.
+pub const fn self_test_pattern(index: u32) -> u8 {
+    let fill = if (index / FLASH_PAGE_SIZE) & 1 == 0 {
+        0x55
+    } else {
+        0xAA
+    };
+    fill ^ index as u8
+}
+
+/// Exercise the sector at `offset`: erase it and check it reads back erased,
+/// program [`self_test_pattern`] over it and check it reads back, then erase
+/// it again and program `saved`, the sector's contents from before, back.
+/// The sector is restored even after a failed step. Returns `SELFTEST_PASSED`
+/// or the `SELFTEST_*` code of the first step that failed.
+pub fn self_test(flash: &mut impl FlashOps, offset: u32, saved: &[u8]) -> u8 {
+    let detail = exercise_sector(flash, offset);
+    let restored = write_image(flash, offset, saved, true, |_, _, _| {});
+    match (detail, restored) {
+        (SELFTEST_PASSED, Err(_)) => SELFTEST_RESTORE_FAILED,
+        (detail, _) => detail,
+    }
+}
+
+fn exercise_sector(flash: &mut impl FlashOps, offset: u32) -> u8 {
+    if flash.erase(offset, FLASH_SECTOR_SIZE).is_err() {
+        return SELFTEST_ERASE_FAILED;
+    }
+    let mut page = [0u8; FLASH_PAGE_SIZE as usize];
+    for start in (0..FLASH_SECTOR_SIZE).step_by(page.len()) {
+        flash.read(offset + start, &mut page);
+        if page.iter().any(|&b| b != 0xFF) {
+            return SELFTEST_ERASE_FAILED;
+        }
+    }
+
+    for start in (0..FLASH_SECTOR_SIZE).step_by(page.len()) {
+        for (i, byte) in page.iter_mut().enumerate() {
+            *byte = self_test_pattern(start + i as u32);
+        }
+        match flash.program(offset + start, &page) {
+            Ok(()) => {}
+            Err(FlashError::VerifyFailed { .. }) => return SELFTEST_VERIFY_FAILED,
+            Err(_) => return SELFTEST_PROGRAM_FAILED,
+        }
+    }
+    for start in (0..FLASH_SECTOR_SIZE).step_by(page.len()) {
+        flash.read(offset + start, &mut page);
+        let wrong = page
+            .iter()
+            .enumerate()
+            .any(|(i, &b)| b != self_test_pattern(start + i as u32));
+        if wrong {
+            return SELFTEST_VERIFY_FAILED;
+        }
+    }
+    SELFTEST_PASSED
+}
+
+/// NOR flash in a `Vec`, for host tests: erasing sets every bit of a sector,
+/// programming can only clear bits, and misaligned erases and programs
+/// panic as a bug in the caller.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub struct MemFlash {
+    /// Contents, from flash offset 0.
+    pub bytes: Vec<u8>,
+    /// Ranges `check_range` accepts; `bytes` is `flash_size` long.
+    pub bounds: crate::flash_bounds::FlashBounds,
+    /// Sectors erased so far.
+    pub erases: u32,
+    /// Erases and programs still carried out before power is lost: later
+    /// ones are dropped without an error. `None` never loses power.
+    pub power_budget: Option<u32>,
+}
+
+#[cfg(feature = "std")]
+impl MemFlash {
+    /// Fully erased flash with the layout `bounds`.
+    pub fn new(bounds: crate::flash_bounds::FlashBounds) -> Self {
+        Self {
+            bytes: vec![0xFF; bounds.flash_size as usize],
+            bounds,
+            erases: 0,
+            power_budget: None,
+        }
+    }
+
+    /// `len` bytes at `offset`.
+    pub fn slice(&self, offset: u32, len: u32) -> &[u8] {
+        &self.bytes[offset as usize..(offset + len) as usize]
+    }
+
+    /// Whether power is still on for one more erase or program.
+    fn powered(&mut self) -> bool {
+        match &mut self.power_budget {
+            None => true,
+            Some(0) => false,
+            Some(left) => {
+                *left -= 1;
+                true
+            }
+        }
+    }
+
+    /// The journal sector at `offset`: `BOOT_DATA_OFFSET` or
+    /// `BOOT_DATA_MIRROR_OFFSET`.
+    pub fn boot_data_sector(&self, offset: u32) -> &crate::boot_journal::Sector {
+        self.slice(offset, FLASH_SECTOR_SIZE).try_into().unwrap()
+    }
+
+    /// Append `bd` to the journal sector at `offset`.
+    fn append_boot_data(&mut self, offset: u32, bd: &BootData) -> Result<(), FlashError> {
+        let plan = crate::boot_journal::plan_write(self.boot_data_sector(offset));
+        if plan.erase {
+            self.erase(offset, FLASH_SECTOR_SIZE)?;
+        }
+        let (page_offset, page) = plan.page(bd);
+        self.program(offset + page_offset, &page)
+    }
+
+    fn select_boot_data(&self) -> Option<(BootData, crate::boot_journal::Source)> {
+        crate::boot_journal::select(
+            self.boot_data_sector(BOOT_DATA_OFFSET),
+            self.boot_data_sector(BOOT_DATA_MIRROR_OFFSET),
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl FlashOps for MemFlash {
+    fn check_range(&self, offset: u32, len: u32) -> Result<(), RangeError> {
+        self.bounds.check(offset, len)
+    }
+
+    fn erase(&mut self, offset: u32, len: u32) -> Result<(), FlashError> {
+        assert_eq!(offset % FLASH_SECTOR_SIZE, 0, "unaligned erase");
+        assert_eq!(len % FLASH_SECTOR_SIZE, 0, "partial sector erase");
+        self.check_range(offset, len)?;
+        for sector in (offset..offset + len).step_by(FLASH_SECTOR_SIZE as usize) {
+            if self.powered() {
+                self.bytes[sector as usize..(sector + FLASH_SECTOR_SIZE) as usize].fill(0xFF);
+                self.erases += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn program(&mut self, offset: u32, data: &[u8]) -> Result<(), FlashError> {
+        assert_eq!(offset % FLASH_PAGE_SIZE, 0, "unaligned program");
+        assert_eq!(
+            data.len() as u32 % FLASH_PAGE_SIZE,
+            0,
+            "partial page program"
+        );
+        self.check_range(offset, data.len() as u32)?;
+        if !self.powered() {
+            return Ok(());
+        }
+        let start = offset as usize;
+        for (byte, new) in self.bytes[start..start + data.len()].iter_mut().zip(data) {
+            *byte &= new;
+        }
+        for (index, page) in data.chunks(FLASH_PAGE_SIZE as usize).enumerate() {
+            let page_offset = offset + index as u32 * FLASH_PAGE_SIZE;
+            let stored = self.slice(page_offset, FLASH_PAGE_SIZE);
+            if page
+                .iter()
+                .zip(stored)
+                .any(|(&want, &got)| want != 0xFF && want != got)
+            {
+                return Err(FlashError::VerifyFailed {
+                    offset: page_offset,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&self, offset: u32, buf: &mut [u8]) {
+        buf.copy_from_slice(self.slice(offset, buf.len() as u32));
+    }
+
+    fn read_boot_data(&self) -> BootData {
+        self.select_boot_data()
+            .map_or_else(BootData::default_new, |(bd, _)| bd)
+    }
+
+    fn write_boot_data(&mut self, bd: &BootData) -> Result<(), FlashError> {
+        let mut order = [BOOT_DATA_OFFSET, BOOT_DATA_MIRROR_OFFSET];
+        if crate::boot_journal::mirror_first(self.boot_data_sector(BOOT_DATA_MIRROR_OFFSET)) {
+            order.reverse();
+        }
+        self.append_boot_data(order[0], bd)?;
+        self.append_boot_data(order[1], bd)
+    }
+
+    fn restore_boot_data(&mut self) -> Result<bool, FlashError> {
+        match self.select_boot_data() {
+            Some((bd, crate::boot_journal::Source::Mirror)) => {
+                self.append_boot_data(BOOT_DATA_OFFSET, &bd)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Optional firmware image header.
+//!
+//! An image may start with a 32-byte header (metadata, and later a signature)
+//! instead of its vector table. The header declares where the vector table
+//! is; images without one keep the vector table at offset 0.
+//!
+//! The header also carries the firmware version, an optional build id and
+//! the oldest bootloader the image runs under, so the bootloader can refuse
+//! images that need a newer one. Zero means "not set" for all three, which
+//! keeps headers written before these fields existed valid.
+
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+use crate::protocol::FirmwareMetadata;
+use crate::vector_table::VectorTableError;
+
+/// `"CRSP"` in little-endian byte order.
+pub const IMAGE_HEADER_MAGIC: u32 = 0x5053_5243;
+pub const IMAGE_HEADER_VERSION: u32 = 1;
+pub const IMAGE_HEADER_SIZE: usize = 32;
+/// VTOR needs the table aligned to its size rounded up to a power of two,
+/// which is 256 bytes for the RP2040's 48-entry table.
+pub const VECTOR_TABLE_ALIGN: u32 = 256;
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageHeader {
+    pub magic: u32,                  // IMAGE_HEADER_MAGIC
+    pub header_version: u32,         // IMAGE_HEADER_VERSION
+    pub vector_table_offset: u32,    // from the start of the image, VECTOR_TABLE_ALIGN-aligned
+    pub fw_version: u32,             // packed semver, 0 = not set
+    pub min_bootloader_version: u32, // packed semver, 0 = any bootloader
+    pub build_id: u32,               // free-form (e.g. CI build number), 0 = not set
+    pub _reserved: u32,
+    pub header_crc32: u32, // CRC-32/ISO-HDLC of the preceding 28 bytes
+}
+
+const _: () = assert!(core::mem::size_of::<ImageHeader>() == IMAGE_HEADER_SIZE);
+
+impl ImageHeader {
+    /// Header without metadata.
+    pub fn new(vector_table_offset: u32) -> Self {
+        Self::with_metadata(vector_table_offset, FirmwareMetadata::default())
+    }
+
+    pub fn with_metadata(vector_table_offset: u32, metadata: FirmwareMetadata) -> Self {
+        let mut header = Self {
+            magic: IMAGE_HEADER_MAGIC,
+            header_version: IMAGE_HEADER_VERSION,
+            vector_table_offset,
+            fw_version: metadata.fw_version,
+            min_bootloader_version: metadata.min_bootloader_version,
+            build_id: metadata.build_id,
+            _reserved: 0,
+            header_crc32: 0,
+        };
+        header.header_crc32 = header.compute_crc32();
+        header
+    }
+
+    pub fn from_bytes(bytes: &[u8; IMAGE_HEADER_SIZE]) -> Self {
+        let word =
+            |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        Self {
+            magic: word(0),
+            header_version: word(4),
+            vector_table_offset: word(8),
+            fw_version: word(12),
+            min_bootloader_version: word(16),
+            build_id: word(20),
+            _reserved: word(24),
+            header_crc32: word(28),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; IMAGE_HEADER_SIZE] {
+        let words = [
+            self.magic,
+            self.header_version,
+            self.vector_table_offset,
+            self.fw_version,
+            self.min_bootloader_version,
+            self.build_id,
+            self._reserved,
+            self.header_crc32,
+        ];
+        let mut bytes = [0u8; IMAGE_HEADER_SIZE];
+        for (chunk, word) in bytes.chunks_exact_mut(4).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// CRC over every field except `header_crc32`.
+    pub fn compute_crc32(&self) -> u32 {
+        CRC32.checksum(&self.to_bytes()[..IMAGE_HEADER_SIZE - 4])
+    }
+
+    pub fn metadata(&self) -> FirmwareMetadata {
+        FirmwareMetadata {
+            fw_version: self.fw_version,
+            min_bootloader_version: self.min_bootloader_version,
+            build_id: self.build_id,
+        }
+    }
+
+    /// Whether the image runs under a bootloader of `bootloader_version`
+    /// (packed semver, which orders like the version it encodes).
+    pub fn supports_bootloader(&self, bootloader_version: u32) -> bool {
+        self.min_bootloader_version <= bootloader_version
+    }
+}
+
+/// The checked header of an image starting with `first_bytes`.
+///
+/// Returns `None` for headerless images. A header with a bad CRC or an offset
+/// that is misaligned, overlaps the header or lies outside the bank is
+/// rejected.
+pub fn parse_header(
+    first_bytes: &[u8; IMAGE_HEADER_SIZE],
+    bank_size: u32,
+) -> Result<Option<ImageHeader>, VectorTableError> {
+    let header = ImageHeader::from_bytes(first_bytes);
+    if header.magic != IMAGE_HEADER_MAGIC {
+        return Ok(None);
+    }
+    if header.header_crc32 != header.compute_crc32() {
+        return Err(VectorTableError::HeaderCrc);
+    }
+
+    let offset = header.vector_table_offset;
+    if offset < IMAGE_HEADER_SIZE as u32
+        || !offset.is_multiple_of(VECTOR_TABLE_ALIGN)
+        || offset >= bank_size
+    {
+        return Err(VectorTableError::HeaderOffset);
+    }
+
+    Ok(Some(header))
+}
+
+/// `parse_header`, also rejecting images that require a bootloader newer than
+/// `bootloader_version`.
+pub fn check_header(
+    first_bytes: &[u8; IMAGE_HEADER_SIZE],
+    bank_size: u32,
+    bootloader_version: u32,
+) -> Result<Option<ImageHeader>, VectorTableError> {
+    match parse_header(first_bytes, bank_size)? {
+        Some(header) if !header.supports_bootloader(bootloader_version) => {
+            Err(VectorTableError::BootloaderTooOld)
+        }
+        header => Ok(header),
+    }
+}
+
+/// Offset of the vector table in an image starting with `first_bytes`: 0 for
+/// headerless images, otherwise the offset from a header `parse_header`
+/// accepts.
+pub fn vector_table_offset(
+    first_bytes: &[u8; IMAGE_HEADER_SIZE],
+    bank_size: u32,
+) -> Result<u32, VectorTableError> {
+    Ok(parse_header(first_bytes, bank_size)?.map_or(0, |header| header.vector_table_offset))
+}
@@ -10,17 +10,39 @@
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
+pub mod boot_journal;
+pub mod boot_selection;
+pub mod bootloader_image;
+pub mod crc32;
+pub mod flash_bounds;
+pub mod flash_layout;
+pub mod flash_map;
+pub mod flash_ops;
+pub mod image_header;
+pub mod log_buffer;
 pub mod protocol;
+pub mod reset_reason;
 pub mod service;
+pub mod telemetry;
+pub mod update;
+pub mod vector_table;
+pub mod warm_boot;
 
 // Flash operations for firmware (requires embedded feature)
 #[cfg(feature = "embedded")]
 pub mod flash;
 
+#[cfg(feature = "embedded")]
+pub use flash::{boot_info, reboot_to_bootloader, request_bootloader};
+
 // Re-export commonly used types
-pub use protocol::{AckStatus, BootData, BootState, Command, Response};
+pub use protocol::{
+    AckStatus, BootData, BootInfo, BootState, BootTimings, Command, GoldenInfo, Response, Semver,
+};
 pub use protocol::{BOOT_DATA_ADDR, BOOT_DATA_MAGIC, FLASH_BASE, FW_A_ADDR, FW_B_ADDR};
-pub use protocol::{FLASH_PAGE_SIZE, FLASH_SECTOR_SIZE, FW_BANK_SIZE, MAX_DATA_BLOCK_SIZE};
+pub use protocol::{
+    FLASH_PAGE_SIZE, FLASH_SECTOR_SIZE, FLASH_SIZE, FW_BANK_SIZE, MAX_DATA_BLOCK_SIZE,
+};
 
 // Embedded-specific exports (only with embedded feature)
 #[cfg(feature = "embedded")]
@@ -38,3 +60,31 @@ pub fn blink(led: &mut impl OutputPin, timer: &mut impl DelayNs, count: u32, per
         timer.delay_ms(period_ms);
     }
 }
+
+/// Blink an LED like [`blink`], but stop as soon as `stop` returns true.
+///
+/// `stop` is checked before each phase and then every millisecond, so it can
+/// also service a peripheral while the LED blinks. On an early stop the LED
+/// is left off. Returns whether `stop` ended the blink.
+#[cfg(feature = "embedded")]
+pub fn blink_until(
+    led: &mut impl OutputPin,
+    timer: &mut impl DelayNs,
+    count: u32,
+    period_ms: u32,
+    mut stop: impl FnMut() -> bool,
+) -> bool {
+    for _ in 0..count {
+        for on in [true, false] {
+            led.set_state(on.into()).ok();
+            for _ in 0..period_ms {
+                if stop() {
+                    led.set_low().ok();
+                    return true;
+                }
+                timer.delay_ms(1);
+            }
+        }
+    }
+    false
+}
@@ -0,0 +1,71 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Byte FIFO for log output waiting to be sent to the host.
+//!
+//! The bootloader's `log-stream` logger appends encoded defmt frames here and
+//! the transport service drains them into `Response::Log` frames. A full
+//! buffer drops new bytes rather than old ones, so the start of a boot is
+//! kept until a host starts reading; the loss is counted. defmt frames end
+//! with a `0x00` byte, so a decoder picks up again at the frame after a gap.
+
+pub struct LogBuffer<const N: usize> {
+    buf: [u8; N],
+    /// Index of the oldest byte.
+    head: usize,
+    len: usize,
+    dropped: u32,
+}
+
+impl<const N: usize> LogBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            head: 0,
+            len: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Append `bytes`, dropping those that do not fit.
+    pub fn push(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if self.len == N {
+                self.dropped = self.dropped.saturating_add(1);
+                continue;
+            }
+            self.buf[(self.head + self.len) % N] = byte;
+            self.len += 1;
+        }
+    }
+
+    /// Move the oldest bytes into `out`; returns how many were moved.
+    pub fn drain(&mut self, out: &mut [u8]) -> usize {
+        let n = out.len().min(self.len);
+        for byte in &mut out[..n] {
+            *byte = self.buf[self.head];
+            self.head = (self.head + 1) % N;
+        }
+        self.len -= n;
+        n
+    }
+
+    /// Bytes dropped since the last call.
+    pub fn take_dropped(&mut self) -> u32 {
+        core::mem::take(&mut self.dropped)
+    }
+}
+
+impl<const N: usize> Default for LogBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
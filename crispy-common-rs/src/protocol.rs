@@ -9,16 +9,20 @@
 #[cfg(feature = "std")]
 extern crate alloc;
 
+use crate::crc32::CrcAlgorithm;
 use serde::{Deserialize, Serialize};
 
 const SEMVER_COMPONENT_MASK: u32 = 0x03FF;
 const SEMVER_MINOR_SHIFT: u32 = 10;
 const SEMVER_MAJOR_SHIFT: u32 = 20;
+const SEMVER_PACKED_MASK: u32 = (SEMVER_COMPONENT_MASK << SEMVER_MAJOR_SHIFT)
+    | (SEMVER_COMPONENT_MASK << SEMVER_MINOR_SHIFT)
+    | SEMVER_COMPONENT_MASK;
 
 /// Packs `major.minor.patch` into a compact u32.
 ///
 /// Each component must be in `[0, 1023]`.
-pub fn pack_semver(major: u32, minor: u32, patch: u32) -> Option<u32> {
+pub const fn pack_semver(major: u32, minor: u32, patch: u32) -> Option<u32> {
     if major > SEMVER_COMPONENT_MASK
         || minor > SEMVER_COMPONENT_MASK
         || patch > SEMVER_COMPONENT_MASK
@@ -37,56 +41,429 @@ pub fn unpack_semver(value: u32) -> (u32, u32, u32) {
     (major, minor, patch)
 }
 
-/// Parses a strict `X.Y.Z` semver string and packs it as `u32`.
-pub fn parse_semver(version: &str) -> Option<u32> {
-    let mut parts = version.split('.');
-    let major = parts.next()?.parse::<u32>().ok()?;
-    let minor = parts.next()?.parse::<u32>().ok()?;
-    let patch = parts.next()?.parse::<u32>().ok()?;
-    if parts.next().is_some() {
-        return None;
+/// A `major.minor.patch` version stored in the packed `u32` encoding.
+///
+/// Ordering follows semantic version precedence (major, then minor, then
+/// patch). Serializes exactly like the raw packed `u32`, so it can replace
+/// bare version fields without changing the wire format; deserializing drops
+/// the unused high bits like [`from_packed`](Self::from_packed), so equality
+/// and hashing agree with that ordering.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(from = "u32", into = "u32")]
+pub struct Semver(u32);
+
+impl From<u32> for Semver {
+    fn from(packed: u32) -> Self {
+        Self::from_packed(packed)
+    }
+}
+
+impl From<Semver> for u32 {
+    fn from(version: Semver) -> Self {
+        version.packed()
+    }
+}
+
+impl Semver {
+    /// Builds a version from its components, each in `[0, 1023]`.
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Option<Self> {
+        match pack_semver(major, minor, patch) {
+            Some(packed) => Some(Self(packed)),
+            None => None,
+        }
+    }
+
+    /// Wraps a packed value. Bits above the three 10-bit components are ignored.
+    pub const fn from_packed(packed: u32) -> Self {
+        Self(packed & SEMVER_PACKED_MASK)
+    }
+
+    /// Returns the packed `u32` representation.
+    pub const fn packed(self) -> u32 {
+        self.0
+    }
+
+    pub const fn major(self) -> u32 {
+        (self.0 >> SEMVER_MAJOR_SHIFT) & SEMVER_COMPONENT_MASK
+    }
+
+    pub const fn minor(self) -> u32 {
+        (self.0 >> SEMVER_MINOR_SHIFT) & SEMVER_COMPONENT_MASK
+    }
+
+    pub const fn patch(self) -> u32 {
+        self.0 & SEMVER_COMPONENT_MASK
+    }
+}
+
+impl PartialOrd for Semver {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Semver {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        (self.major(), self.minor(), self.patch()).cmp(&(
+            other.major(),
+            other.minor(),
+            other.patch(),
+        ))
+    }
+}
+
+impl core::fmt::Display for Semver {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}.{}.{}", self.major(), self.minor(), self.patch())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Semver {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{}.{}.{}", self.major(), self.minor(), self.patch());
+    }
+}
+
+impl core::str::FromStr for Semver {
+    type Err = SemverError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        try_parse_semver(s).map(Self)
     }
+}
 
-    pack_semver(major, minor, patch)
+/// Reasons a version string can be rejected by [`try_parse_semver`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SemverError {
+    /// The string is empty (after trimming whitespace and an optional `v` prefix).
+    Empty,
+    /// Fewer than three numeric components, or an empty component (`1..2`).
+    MissingComponent,
+    /// More than three numeric components (`1.2.3.4`).
+    TooManyComponents,
+    /// A component is larger than the packed encoding allows (1023).
+    ComponentOutOfRange,
+    /// A character that is not a digit, `.`, or the start of a suffix.
+    InvalidCharacter,
+    /// Empty or malformed pre-release / build metadata suffix.
+    InvalidSuffix,
+}
+
+impl core::fmt::Display for SemverError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let msg = match self {
+            Self::Empty => "empty version string",
+            Self::MissingComponent => "expected MAJOR.MINOR.PATCH",
+            Self::TooManyComponents => "too many version components",
+            Self::ComponentOutOfRange => "version component exceeds 1023",
+            Self::InvalidCharacter => "invalid character in version",
+            Self::InvalidSuffix => "invalid pre-release or build metadata",
+        };
+        f.write_str(msg)
+    }
+}
+
+/// Parses a `X.Y.Z` semver string and packs it as `u32`.
+///
+/// Surrounding whitespace and a leading `v` are accepted. Pre-release
+/// (`-rc.1`) and build metadata (`+build5`) suffixes are validated and then
+/// ignored, since the packed encoding only carries `major.minor.patch`.
+pub const fn try_parse_semver(version: &str) -> Result<u32, SemverError> {
+    let bytes = version.trim_ascii().as_bytes();
+    let mut i = 0;
+    if !bytes.is_empty() && (bytes[0] == b'v' || bytes[0] == b'V') {
+        i = 1;
+    }
+    if i == bytes.len() {
+        return Err(SemverError::Empty);
+    }
+
+    let mut components = [0u32; 3];
+    let mut index = 0;
+    let mut digits = 0;
+    while i < bytes.len() {
+        let byte = bytes[i];
+        match byte {
+            b'0'..=b'9' => {
+                let value = components[index] * 10 + (byte - b'0') as u32;
+                if value > SEMVER_COMPONENT_MASK {
+                    return Err(SemverError::ComponentOutOfRange);
+                }
+                components[index] = value;
+                digits += 1;
+            }
+            b'.' => {
+                if digits == 0 {
+                    return Err(SemverError::MissingComponent);
+                }
+                if index == components.len() - 1 {
+                    return Err(SemverError::TooManyComponents);
+                }
+                index += 1;
+                digits = 0;
+            }
+            b'-' | b'+' => break,
+            _ => return Err(SemverError::InvalidCharacter),
+        }
+        i += 1;
+    }
+
+    if digits == 0 || index < components.len() - 1 {
+        return Err(SemverError::MissingComponent);
+    }
+
+    if i < bytes.len() && !is_valid_semver_suffix(bytes, i) {
+        return Err(SemverError::InvalidSuffix);
+    }
+
+    match pack_semver(components[0], components[1], components[2]) {
+        Some(packed) => Ok(packed),
+        None => Err(SemverError::ComponentOutOfRange),
+    }
+}
+
+/// Packs a version string that must be valid, such as `env!("CRISPY_VERSION")`.
+///
+/// Intended for `const` items so that a malformed version fails the build
+/// instead of being reported as `0.0.0` at runtime.
+pub const fn parse_build_semver(version: &str) -> u32 {
+    match try_parse_semver(version) {
+        Ok(packed) => packed,
+        Err(_) => panic!("invalid build version: expected MAJOR.MINOR.PATCH"),
+    }
+}
+
+/// Checks a `-pre.release+build.meta` suffix starting at `start`.
+///
+/// Identifiers must be non-empty and use only `[0-9A-Za-z-]`.
+const fn is_valid_semver_suffix(bytes: &[u8], start: usize) -> bool {
+    let mut i = start;
+    let mut seen_build = false;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'-' if i == start => {}
+            b'+' if !seen_build => seen_build = true,
+            b'.' if i != start => {}
+            _ => return false,
+        }
+        i += 1;
+
+        let ident_start = i;
+        while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'-') {
+            i += 1;
+        }
+        if i == ident_start {
+            return false;
+        }
+    }
+    true
 }
 
 // --- Flash layout constants ---
 
 pub const FLASH_BASE: u32 = 0x1000_0000;
-pub const FW_A_ADDR: u32 = 0x1001_0000;
-pub const FW_B_ADDR: u32 = 0x100D_0000;
-pub const BOOT_DATA_ADDR: u32 = 0x1019_0000;
+/// boot2 plus the bootloader itself, from `FLASH_BASE` up to bank A.
+pub const BOOTLOADER_REGION_SIZE: u32 = 64 * 1024;
+/// Flash size the layout below is built for (see [`crate::flash_layout`]).
+pub const FLASH_SIZE: u32 = crate::flash_layout::FLASH_SIZE;
+pub const FW_A_ADDR: u32 = FLASH_BASE + BOOTLOADER_REGION_SIZE;
+pub const FW_B_ADDR: u32 = FW_A_ADDR + FW_BANK_SIZE;
+pub const BOOT_DATA_ADDR: u32 = FW_B_ADDR + FW_BANK_SIZE;
+/// Sector holding the bootloader's append-only flash erase counters.
+pub const WEAR_STATS_ADDR: u32 = BOOT_DATA_ADDR + FLASH_SECTOR_SIZE;
 
-pub const FW_BANK_SIZE: u32 = 768 * 1024; // 768KB per bank
+/// 768KB per bank on 2MB flash; larger layouts give the extra space to the
+/// banks.
+pub const FW_BANK_SIZE: u32 = crate::flash_layout::SELECTED.bank_size;
 
+/// Read-only recovery bank (`golden-bank` feature): the rest of the flash
+/// after the wear stats sector. Its last sector holds the `GoldenInfo` record.
+pub const FW_GOLD_ADDR: u32 = WEAR_STATS_ADDR + FLASH_SECTOR_SIZE;
+pub const FW_GOLD_SIZE: u32 = 440 * 1024;
+pub const GOLDEN_INFO_ADDR: u32 = FW_GOLD_ADDR + FW_GOLD_SIZE - FLASH_SECTOR_SIZE;
+/// Largest image the golden bank can hold: golden images are buffered whole
+/// in the 192KB RAM upload buffer (`__fw_copy_size`), so the rest of its
+/// range is given to the data partition and the config sector.
+pub const GOLDEN_MAX_IMAGE_SIZE: u32 = 192 * 1024;
+pub const GOLDEN_INFO_MAGIC: u32 = 0x601D_E2B0;
+/// Bank number of the golden bank in `BankInfo` and `BootInfo`.
+pub const GOLDEN_BANK: u8 = 2;
+
+/// Data partition for firmware assets: the golden bank's range from the end
+/// of its largest image up to the BootData mirror, present with or without the
+/// `golden-bank` feature. Uploaded like a bank (`StartUpdate` with
+/// `DATA_BANK`) but never booted; its last sector holds the `DataInfo` record.
+pub const DATA_ADDR: u32 = FW_GOLD_ADDR + GOLDEN_MAX_IMAGE_SIZE;
+pub const DATA_SIZE: u32 = BOOT_DATA_MIRROR_ADDR - DATA_ADDR;
+pub const DATA_INFO_ADDR: u32 = DATA_ADDR + DATA_SIZE - FLASH_SECTOR_SIZE;
+/// Largest upload the data partition can hold: up to its self-test scratch
+/// sector.
+pub const DATA_MAX_IMAGE_SIZE: u32 = SELFTEST_SCRATCH_ADDR - DATA_ADDR;
+pub const DATA_INFO_MAGIC: u32 = 0xDA7A_1F00;
+/// Bank number of the data partition in `StartUpdate` and `GetBankInfo`.
+pub const DATA_BANK: u8 = 3;
+
+/// Scratch sector of `FlashSelfTest`, the sector below the `DataInfo` record.
+/// No other command erases or programs it: data uploads end below it.
+pub const SELFTEST_SCRATCH_ADDR: u32 = DATA_INFO_ADDR - FLASH_SECTOR_SIZE;
+
+/// `SelfTest::detail` of a self-test that passed.
+pub const SELFTEST_PASSED: u8 = 0;
+/// The scratch sector did not erase, or did not read back erased.
+pub const SELFTEST_ERASE_FAILED: u8 = 1;
+/// Programming the test pattern failed.
+pub const SELFTEST_PROGRAM_FAILED: u8 = 2;
+/// The test pattern read back wrong.
+pub const SELFTEST_VERIFY_FAILED: u8 = 3;
+/// The test steps passed, but the sector's previous contents could not be
+/// written back.
+pub const SELFTEST_RESTORE_FAILED: u8 = 4;
+
+/// Mirror of the BootData sector, the sector below the config store: every
+/// BootData write goes to `BOOT_DATA_ADDR` and then here, and a BootData
+/// sector without a valid record is restored from it.
+pub const BOOT_DATA_MIRROR_ADDR: u32 = CONFIG_ADDR - FLASH_SECTOR_SIZE;
+
+/// User config store (`ConfigGet` / `ConfigSet`): `CONFIG_SLOTS` little-endian
+/// `u32` values at the start of the sector below `GOLDEN_INFO_ADDR`.
+pub const CONFIG_ADDR: u32 = GOLDEN_INFO_ADDR - FLASH_SECTOR_SIZE;
+pub const CONFIG_SLOTS: u8 = 16;
+/// Value of a slot that was never set.
+pub const CONFIG_UNSET: u32 = 0xFFFF_FFFF;
+
+/// Software update trigger: a RAM word outside both the firmware and the
+/// bootloader RAM regions (see `fw_rp2040.x`), so it survives a system reset
+/// and the bootloader's own startup. Its contents are undefined after
+/// power-on.
 pub const RAM_UPDATE_FLAG_ADDR: u32 = 0x2003_BFF0;
+/// Value firmware writes to `RAM_UPDATE_FLAG_ADDR` before resetting to request
+/// update mode. The bootloader clears the word on every boot.
 pub const RAM_UPDATE_MAGIC: u32 = 0x0FDA_7E00;
+/// `WarmBootMarker` (12 bytes) left by the bootloader after copying a RAM
+/// image, right after the update flag (see `warm_boot`).
+pub const WARM_BOOT_MARKER_ADDR: u32 = 0x2003_BFF4;
+/// RAM mailbox the bootloader fills with `BootInfo` before jumping to firmware.
+/// Sits between the firmware RAM region and the update flag (see `fw_rp2040.x`).
+pub const BOOT_INFO_ADDR: u32 = 0x2003_BFD0;
+pub const BOOT_INFO_MAGIC: u32 = 0xB007_1AF0;
+
+/// RP2040 `WATCHDOG.SCRATCH0` register. Unlike RAM, it keeps its value
+/// across every reset except power-on and the RUN pin.
+pub const WATCHDOG_SCRATCH0_ADDR: u32 = 0x4005_800C;
+/// Value firmware writes to `WATCHDOG.SCRATCH0` to request update mode.
+pub const WATCHDOG_UPDATE_MAGIC: u32 = 0xB007_10AD;
 
 pub const FLASH_SECTOR_SIZE: u32 = 4096;
 pub const FLASH_PAGE_SIZE: u32 = 256;
 
+/// `len` rounded up to a whole number of flash pages. The host pads an image
+/// to this length with 0xFF (erased flash) when asked to, so the size and CRC
+/// it reports cover exactly the bytes programmed.
+pub const fn page_padded_len(len: usize) -> usize {
+    len.next_multiple_of(FLASH_PAGE_SIZE as usize)
+}
+
 pub const BOOT_DATA_MAGIC: u32 = 0xB007_DA7A;
 
-// --- BootData (repr(C), 32 bytes) ---
+/// `BootData::flags` bit: the bootloader booted the other bank because the
+/// active one failed validation. Cleared when the host activates a bank.
+pub const BOOT_FLAG_FALLBACK: u8 = 1 << 0;
+/// `BootData::flags` bit: the golden bank has been provisioned. Kept by `WipeAll`.
+pub const BOOT_FLAG_GOLDEN: u8 = 1 << 1;
+/// `BootData::flags` bit: the inactive bank holds a verified bootloader image
+/// (its size and CRC in that bank's fields) waiting to be copied over the
+/// bootloader region. Cleared once the copy has been verified.
+pub const BOOT_FLAG_BOOTLOADER_STAGED: u8 = 1 << 2;
+/// `BootData::flags` bit: anti-rollback is on, images older than
+/// `BootData::min_version` are refused. Set by `EnableAntiRollback` and never
+/// cleared, not even by `WipeAll`.
+pub const BOOT_FLAG_ANTI_ROLLBACK: u8 = 1 << 3;
+/// `BootData::flags` bit: bank A is locked against writes (`SetBankLock`).
+pub const BOOT_FLAG_LOCKED_A: u8 = 1 << 4;
+/// `BootData::flags` bit: bank B is locked against writes (`SetBankLock`).
+pub const BOOT_FLAG_LOCKED_B: u8 = 1 << 5;
+
+/// `Response::BuildInfo::features`: defmt logging is compiled in.
+pub const BUILD_FEATURE_LOGGING: u32 = 1 << 0;
+/// `Response::BuildInfo::features`: boot-time CRC check disabled (`skip-boot-crc`).
+pub const BUILD_FEATURE_SKIP_BOOT_CRC: u32 = 1 << 1;
+/// `Response::BuildInfo::features`: firmware signature verification (reserved).
+pub const BUILD_FEATURE_SIGNING: u32 = 1 << 2;
+/// `Response::BuildInfo::features`: compressed uploads (reserved).
+pub const BUILD_FEATURE_COMPRESSION: u32 = 1 << 3;
+/// `Response::BuildInfo::features`: golden recovery bank (`golden-bank`).
+pub const BUILD_FEATURE_GOLDEN_BANK: u32 = 1 << 4;
+/// `Response::BuildInfo::features`: defmt output can be streamed to the host
+/// (`log-stream`).
+pub const BUILD_FEATURE_LOG_STREAM: u32 = 1 << 5;
+/// `Response::BuildInfo::features`: `FinishUpdate` writes flash on core 1
+/// (`dual-core-flash`).
+pub const BUILD_FEATURE_DUAL_CORE_FLASH: u32 = 1 << 6;
+
+// --- BootData (repr(C), 40 bytes) ---
+
+// Bank versions are packed semver (see `Semver`). Records written before this
+// convention hold a bare counter `N`; for `N < 1024` that is bit-identical to
+// `0.0.N`, so no migration is needed and such banks simply display as `0.0.N`.
+// Larger legacy values decode to arbitrary-looking versions and should be
+// re-uploaded with an explicit `MAJOR.MINOR.PATCH`.
 
 #[repr(C)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct BootData {
-    pub magic: u32,        // 0xB007DA7A
-    pub active_bank: u8,   // 0 = A, 1 = B
-    pub confirmed: u8,     // 1 = confirmed good
-    pub boot_attempts: u8, // rollback after 3
-    pub _reserved0: u8,
-    pub version_a: u32, // firmware version in bank A
-    pub version_b: u32, // firmware version in bank B
-    pub crc_a: u32,     // CRC32 of bank A firmware
-    pub crc_b: u32,     // CRC32 of bank B firmware
-    pub size_a: u32,    // size of firmware in bank A
-    pub size_b: u32,    // size of firmware in bank B
+    pub magic: u32,            // 0xB007DA7A
+    pub active_bank: u8,       // 0 = A, 1 = B
+    pub confirmed: u8,         // 1 = confirmed good
+    pub boot_attempts: u8,     // rollback after `boot_attempt_limit()`
+    pub flags: u8,             // BOOT_FLAG_* bits (0 on records predating flags)
+    pub version_a: u32,        // firmware version in bank A (packed semver)
+    pub version_b: u32,        // firmware version in bank B (packed semver)
+    pub crc_a: u32,            // CRC32 of bank A firmware
+    pub crc_b: u32,            // CRC32 of bank B firmware
+    pub size_a: u32,           // size of firmware in bank A
+    pub size_b: u32,           // size of firmware in bank B
+    pub min_version: u32,      // anti-rollback floor (packed semver), only ever raised
+    pub max_boot_attempts: u8, // rollback threshold set by `SetBootPolicy`
+    pub layout_version: u8,    // BOOT_DATA_LAYOUT_* the record was written with
+    pub _reserved: [u8; 2],
 }
 
+/// Size of the BootData record in flash.
+pub const BOOT_DATA_SIZE: usize = 40;
+
 // Compile-time size check
-const _: () = assert!(core::mem::size_of::<BootData>() == 32);
+const _: () = assert!(core::mem::size_of::<BootData>() == BOOT_DATA_SIZE);
+
+/// BootData layout of the first bootloaders: 32 bytes, up to `size_b`.
+pub const BOOT_DATA_LAYOUT_V1: u8 = 1;
+/// Adds `min_version`: 36 bytes.
+pub const BOOT_DATA_LAYOUT_V2: u8 = 2;
+/// Adds `max_boot_attempts` and `layout_version`: 40 bytes.
+pub const BOOT_DATA_LAYOUT_V3: u8 = 3;
+/// Layout this build reads into and writes.
+pub const BOOT_DATA_LAYOUT_VERSION: u8 = BOOT_DATA_LAYOUT_V3;
+
+/// Offset of `BootData::layout_version` in the record.
+const LAYOUT_VERSION_OFFSET: usize = 37;
+
+/// `layout_version` of V3 records written before the field existed, when
+/// the byte was reserved and written as zero.
+const LAYOUT_VERSION_UNSET: u8 = 0;
+
+/// `BootData::min_version` as erased flash. `from_bytes` replaces it in
+/// records that predate the field; a record holding it anyway has no floor of
+/// its own.
+const MIN_VERSION_UNSET: u32 = 0xFFFF_FFFF;
+
+/// Unconfirmed boots allowed before rolling back when BootData sets no
+/// threshold of its own.
+pub const DEFAULT_MAX_BOOT_ATTEMPTS: u8 = 3;
+
+/// `BootData::max_boot_attempts` of records that set no threshold: written
+/// before the field existed (erased padding) or never given a policy.
+pub const MAX_BOOT_ATTEMPTS_UNSET: u8 = 0xFF;
 
 impl BootData {
     pub fn default_new() -> Self {
@@ -95,18 +472,169 @@ impl BootData {
             active_bank: 0,
             confirmed: 0,
             boot_attempts: 0,
-            _reserved0: 0,
+            flags: 0,
             version_a: 0,
             version_b: 0,
             crc_a: 0,
             crc_b: 0,
             size_a: 0,
             size_b: 0,
+            min_version: 0,
+            max_boot_attempts: MAX_BOOT_ATTEMPTS_UNSET,
+            layout_version: BOOT_DATA_LAYOUT_VERSION,
+            _reserved: [0; 2],
         }
     }
 
+    /// Bytes a record of layout `version` holds; the rest of a
+    /// `BOOT_DATA_SIZE` read is erased padding. `None` for versions this build
+    /// does not know.
+    pub const fn layout_size(version: u8) -> Option<usize> {
+        match version {
+            BOOT_DATA_LAYOUT_V1 => Some(32),
+            BOOT_DATA_LAYOUT_V2 => Some(36),
+            BOOT_DATA_LAYOUT_V3 => Some(40),
+            _ => None,
+        }
+    }
+
+    /// Layout of a raw record. Records that predate `layout_version` are
+    /// told apart by their erased padding: a V1 record has `min_version` and
+    /// the version byte erased, a V2 record only the version byte. A V3
+    /// record written before the field holds zero there.
+    pub fn stored_layout(bytes: &[u8; BOOT_DATA_SIZE]) -> u8 {
+        match bytes[LAYOUT_VERSION_OFFSET] {
+            0xFF if bytes[32..36] == [0xFF; 4] => BOOT_DATA_LAYOUT_V1,
+            0xFF => BOOT_DATA_LAYOUT_V2,
+            LAYOUT_VERSION_UNSET => BOOT_DATA_LAYOUT_V3,
+            version => version,
+        }
+    }
+
+    /// Whether the record can be trusted: the magic matches, `active_bank`
+    /// is A or B, both sizes fit a bank and a bank without an image has no
+    /// CRC. An erased sector reads as all `0xFF` and fails this check.
+    ///
+    /// Callers treat an invalid record as no record at all rather than
+    /// acting on fields of a stale or half-written one.
     pub fn is_valid(&self) -> bool {
         self.magic == BOOT_DATA_MAGIC
+            && self.active_bank <= 1
+            && self.size_a <= FW_BANK_SIZE
+            && self.size_b <= FW_BANK_SIZE
+            && (self.size_a != 0 || self.crc_a == 0)
+            && (self.size_b != 0 || self.crc_b == 0)
+    }
+
+    /// Whether the active bank was chosen because the other one failed validation.
+    pub fn fell_back(&self) -> bool {
+        self.flags & BOOT_FLAG_FALLBACK != 0
+    }
+
+    /// Whether the golden bank has been provisioned.
+    pub fn golden_populated(&self) -> bool {
+        self.flags & BOOT_FLAG_GOLDEN != 0
+    }
+
+    /// Whether anti-rollback is on.
+    pub fn anti_rollback(&self) -> bool {
+        self.flags & BOOT_FLAG_ANTI_ROLLBACK != 0
+    }
+
+    /// `BOOT_FLAG_LOCKED_*` bit of `bank`; zero for banks that cannot be locked.
+    pub const fn lock_flag(bank: u8) -> u8 {
+        match bank {
+            0 => BOOT_FLAG_LOCKED_A,
+            1 => BOOT_FLAG_LOCKED_B,
+            _ => 0,
+        }
+    }
+
+    /// Whether `bank` is locked against writes.
+    pub fn bank_locked(&self, bank: u8) -> bool {
+        self.flags & Self::lock_flag(bank) != 0
+    }
+
+    /// Whether bank A or bank B is locked.
+    pub fn any_bank_locked(&self) -> bool {
+        self.flags & (BOOT_FLAG_LOCKED_A | BOOT_FLAG_LOCKED_B) != 0
+    }
+
+    /// Lock or unlock `bank`. Banks other than A and B are left alone.
+    pub fn set_bank_locked(&mut self, bank: u8, locked: bool) {
+        if locked {
+            self.flags |= Self::lock_flag(bank);
+        } else {
+            self.flags &= !Self::lock_flag(bank);
+        }
+    }
+
+    /// Oldest version an image may have to be stored or activated, or `None`
+    /// while anti-rollback is off.
+    ///
+    /// While `min_version` is erased, the newest version in either bank is
+    /// used, so losing the floor never allows a downgrade below what is
+    /// installed.
+    pub fn rollback_floor(&self) -> Option<u32> {
+        if !self.anti_rollback() {
+            return None;
+        }
+        if self.min_version == MIN_VERSION_UNSET {
+            Some(self.version_a.max(self.version_b))
+        } else {
+            Some(self.min_version)
+        }
+    }
+
+    /// Whether an image of `version` passes the anti-rollback check.
+    pub fn allows_version(&self, version: u32) -> bool {
+        self.rollback_floor().is_none_or(|floor| version >= floor)
+    }
+
+    /// Record a verified image of `size` bytes stored in bank A (0) or B (1),
+    /// as `FinishUpdate` does, raising the anti-rollback floor to `version`.
+    ///
+    /// The image in the active bank changed either way, so it must go
+    /// through a trial boot again even when not `activate`. Returns whether
+    /// a trial boot was started.
+    pub fn record_image(
+        &mut self,
+        bank: u8,
+        version: u32,
+        crc: u32,
+        size: u32,
+        activate: bool,
+    ) -> bool {
+        let trial = activate || self.active_bank == bank;
+        if trial {
+            self.active_bank = bank;
+            self.confirmed = 0;
+            self.boot_attempts = 0;
+            self.flags &= !BOOT_FLAG_FALLBACK;
+        }
+
+        if bank == 0 {
+            self.version_a = version;
+            self.crc_a = crc;
+            self.size_a = size;
+        } else {
+            self.version_b = version;
+            self.crc_b = crc;
+            self.size_b = size;
+        }
+        if let Some(floor) = self.rollback_floor() {
+            self.min_version = floor.max(version);
+        }
+        trial
+    }
+
+    /// Unconfirmed boots allowed before rolling back to the other bank:
+    /// `max_boot_attempts`, or `DEFAULT_MAX_BOOT_ATTEMPTS` while unset.
+    pub fn boot_attempt_limit(&self) -> u8 {
+        match self.max_boot_attempts {
+            0 | MAX_BOOT_ATTEMPTS_UNSET => DEFAULT_MAX_BOOT_ATTEMPTS,
+            limit => limit,
+        }
     }
 
     pub fn bank_addr(&self) -> u32 {
@@ -117,13 +645,60 @@ impl BootData {
         }
     }
 
-    /// Read BootData from a raw address via volatile reads.
+    /// BootData from its little-endian flash encoding, migrated to
+    /// `BOOT_DATA_LAYOUT_VERSION`.
+    ///
+    /// Fields an older layout lacks read as erased padding and are given
+    /// their defaults: `min_version` becomes the anti-rollback floor the
+    /// record implied (the newer bank version while anti-rollback is on, `0`
+    /// otherwise) and `max_boot_attempts` is unset. The migrated record is
+    /// what the next BootData write stores. A record from a newer layout
+    /// keeps the fields this build knows and is rewritten in this one.
+    pub fn from_bytes(bytes: &[u8; BOOT_DATA_SIZE]) -> Self {
+        let word =
+            |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let mut bd = Self {
+            magic: word(0),
+            active_bank: bytes[4],
+            confirmed: bytes[5],
+            boot_attempts: bytes[6],
+            flags: bytes[7],
+            version_a: word(8),
+            version_b: word(12),
+            crc_a: word(16),
+            crc_b: word(20),
+            size_a: word(24),
+            size_b: word(28),
+            min_version: word(32),
+            max_boot_attempts: bytes[36],
+            layout_version: BOOT_DATA_LAYOUT_VERSION,
+            _reserved: [bytes[38], bytes[39]],
+        };
+
+        let layout = Self::stored_layout(bytes);
+        if layout < BOOT_DATA_LAYOUT_V2 {
+            bd.min_version = if bd.anti_rollback() {
+                bd.version_a.max(bd.version_b)
+            } else {
+                0
+            };
+        }
+        if layout < BOOT_DATA_LAYOUT_V3 {
+            bd.max_boot_attempts = MAX_BOOT_ATTEMPTS_UNSET;
+            bd._reserved = [0; 2];
+        }
+        bd
+    }
+
+    /// Read BootData from a raw address via volatile reads, migrated as by
+    /// [`from_bytes`](Self::from_bytes).
     ///
     /// # Safety
-    /// `addr` must point to a readable, properly aligned memory region of at least 32 bytes.
+    /// `addr` must point to a readable, properly aligned memory region of at
+    /// least `BOOT_DATA_SIZE` bytes.
     pub unsafe fn read_from(addr: u32) -> Self {
-        let ptr = addr as *const Self;
-        core::ptr::read_volatile(ptr)
+        let ptr = addr as *const [u8; BOOT_DATA_SIZE];
+        Self::from_bytes(&core::ptr::read_volatile(ptr))
     }
 
     pub fn as_bytes(&self) -> &[u8] {
@@ -136,11 +711,252 @@ impl BootData {
     }
 }
 
+// --- GoldenInfo record (repr(C), 16 bytes) ---
+
+/// Metadata of the golden image, stored once at `GOLDEN_INFO_ADDR` when the
+/// golden bank is provisioned. Kept outside BootData so that `WipeAll` and
+/// BootData corruption cannot make the golden bank look empty.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GoldenInfo {
+    pub magic: u32,   // GOLDEN_INFO_MAGIC
+    pub size: u32,    // image size in bytes
+    pub crc32: u32,   // CRC32 of the image
+    pub version: u32, // packed semver
+}
+
+const _: () = assert!(core::mem::size_of::<GoldenInfo>() == 16);
+
+impl GoldenInfo {
+    pub fn new(size: u32, crc32: u32, version: u32) -> Self {
+        Self {
+            magic: GOLDEN_INFO_MAGIC,
+            size,
+            crc32,
+            version,
+        }
+    }
+
+    /// Magic matches and the size fits the golden bank. An erased sector
+    /// reads as all `0xFF` and fails this check.
+    pub fn is_valid(&self) -> bool {
+        self.magic == GOLDEN_INFO_MAGIC && self.size != 0 && self.size <= GOLDEN_MAX_IMAGE_SIZE
+    }
+
+    /// Read GoldenInfo from a raw address via a volatile read.
+    ///
+    /// # Safety
+    /// `addr` must point to a readable, properly aligned memory region of at least 16 bytes.
+    pub unsafe fn read_from(addr: u32) -> Self {
+        core::ptr::read_volatile(addr as *const Self)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const Self as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+}
+
+/// Metadata of the data partition's contents, written to `DATA_INFO_ADDR`
+/// after each data upload. The sector is erased when a data upload starts,
+/// so a partial upload never looks complete.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DataInfo {
+    pub magic: u32,   // DATA_INFO_MAGIC
+    pub size: u32,    // data size in bytes
+    pub crc32: u32,   // CRC32 of the data
+    pub version: u32, // packed semver
+}
+
+const _: () = assert!(core::mem::size_of::<DataInfo>() == 16);
+
+impl DataInfo {
+    pub fn new(size: u32, crc32: u32, version: u32) -> Self {
+        Self {
+            magic: DATA_INFO_MAGIC,
+            size,
+            crc32,
+            version,
+        }
+    }
+
+    /// Magic matches and the size fits the data partition. An erased sector
+    /// reads as all `0xFF` and fails this check.
+    pub fn is_valid(&self) -> bool {
+        self.magic == DATA_INFO_MAGIC && self.size != 0 && self.size <= DATA_MAX_IMAGE_SIZE
+    }
+
+    /// Read DataInfo from a raw address via a volatile read.
+    ///
+    /// # Safety
+    /// `addr` must point to a readable, properly aligned memory region of at least 16 bytes.
+    pub unsafe fn read_from(addr: u32) -> Self {
+        core::ptr::read_volatile(addr as *const Self)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(
+                self as *const Self as *const u8,
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+}
+
+// --- BootInfo mailbox (repr(C), 32 bytes) ---
+
+/// Boot latency milestones in microseconds of the RP2040 timer.
+///
+/// The timer starts when the bootloader initializes it, after the boot ROM,
+/// boot2 and clock setup, so those are not included.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BootTimings {
+    pub peripherals_ready: u32, // hardware and flash initialized
+    pub trigger_decided: u32,   // update trigger checked, boot path chosen
+    pub image_checked: u32,     // bank selected, vector table and CRC checked
+    pub jump: u32,              // image copied, about to jump
+}
+
+/// Boot context handed from the bootloader to firmware at `BOOT_INFO_ADDR`.
+///
+/// Reflects BootData as the bootloader saw it for this boot, so `boot_attempts`
+/// already counts the current (unconfirmed) attempt.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct BootInfo {
+    pub magic: u32,              // BOOT_INFO_MAGIC
+    pub active_bank: u8,         // 0 = A, 1 = B, 2 = golden
+    pub confirmed: u8,           // 0 = trial boot
+    pub boot_attempts: u8,       // unconfirmed boots including this one
+    pub flags: u8,               // BootData BOOT_FLAG_* bits
+    pub bootloader_version: u32, // packed semver
+    pub timings: BootTimings,    // zero from bootloaders that predate it
+    pub _reserved: u32,
+}
+
+const _: () = assert!(core::mem::size_of::<BootInfo>() == 32);
+
+impl BootInfo {
+    pub fn new(bd: &BootData, bootloader_version: u32) -> Self {
+        Self {
+            magic: BOOT_INFO_MAGIC,
+            active_bank: bd.active_bank,
+            confirmed: bd.confirmed,
+            boot_attempts: bd.boot_attempts,
+            flags: bd.flags,
+            bootloader_version,
+            timings: BootTimings::default(),
+            _reserved: 0,
+        }
+    }
+
+    /// Magic matches and the fields are in range. RAM is random after
+    /// power-on, so firmware must check this before trusting the contents.
+    pub fn is_valid(&self) -> bool {
+        self.magic == BOOT_INFO_MAGIC && self.active_bank <= GOLDEN_BANK && self.confirmed <= 1
+    }
+
+    /// Whether the bootloader fell back to the golden bank because neither
+    /// A nor B could be booted.
+    pub fn is_golden(&self) -> bool {
+        self.active_bank == GOLDEN_BANK
+    }
+
+    /// Whether this is an unconfirmed boot that may still be rolled back.
+    pub fn is_trial(&self) -> bool {
+        self.confirmed == 0
+    }
+
+    /// Whether the active bank was chosen because the other one failed validation.
+    pub fn fell_back(&self) -> bool {
+        self.flags & BOOT_FLAG_FALLBACK != 0
+    }
+
+    pub fn bootloader_version(&self) -> Semver {
+        Semver::from_packed(self.bootloader_version)
+    }
+
+    /// Boot latency milestones, if the bootloader recorded them.
+    pub fn timings(&self) -> Option<BootTimings> {
+        (self.timings.jump != 0).then_some(self.timings)
+    }
+
+    /// Read BootInfo from a raw address via a volatile read.
+    ///
+    /// # Safety
+    /// `addr` must point to a readable, properly aligned memory region of at least 32 bytes.
+    pub unsafe fn read_from(addr: u32) -> Self {
+        core::ptr::read_volatile(addr as *const Self)
+    }
+
+    /// Write BootInfo to a raw address via a volatile write.
+    ///
+    /// # Safety
+    /// `addr` must point to a writable, properly aligned memory region of at least 32 bytes.
+    pub unsafe fn write_to(&self, addr: u32) {
+        core::ptr::write_volatile(addr as *mut Self, *self);
+    }
+}
+
 // --- Command / Response protocol ---
 
 /// Maximum data block size for firmware uploads.
+///
+/// This is the hard upper bound of the `DataBlock` buffer. Devices report the
+/// size they actually accept in `Response::Status::max_data_block_size`, and
+/// hosts must not send larger blocks than that.
 pub const MAX_DATA_BLOCK_SIZE: usize = 1024;
 
+/// Largest postcard varint encoding of a `u32` (enum tags, lengths, `u32`s).
+pub const VARINT_U32_MAX_SIZE: usize = 5;
+
+/// Upper bound on the postcard encoding of any `Command`.
+///
+/// The largest is a full `DataBlock`: its variant tag, `offset` and length
+/// prefix as varints, then `MAX_DATA_BLOCK_SIZE` bytes. Every other command
+/// holds a few scalars. `protocol_tests` checks the bound against real
+/// encodings.
+pub const MAX_COMMAND_SIZE: usize = 3 * VARINT_U32_MAX_SIZE + MAX_DATA_BLOCK_SIZE;
+
+/// Most regions a `Response::FlashMap` lists, free gaps included.
+pub const MAX_FLASH_REGIONS: usize = 16;
+
+/// Most recent error codes a `Response::ErrorLog` carries.
+pub const ERROR_LOG_LEN: usize = 16;
+
+/// Most log bytes a `Response::Log` carries.
+pub const LOG_CHUNK_SIZE: usize = 64;
+
+/// Upper bound on the postcard encoding of any `Response`.
+///
+/// The largest is a full `BankData`: its variant tag, `bank`, `offset` and
+/// length prefix, then `MAX_DATA_BLOCK_SIZE` bytes. Next is a full
+/// `FlashMap` (about 260 bytes); every other response holds a few scalars
+/// (`Status` and `BankInfo` at about 40 bytes) or, for `Log`, at most
+/// `LOG_CHUNK_SIZE` bytes.
+pub const MAX_RESPONSE_SIZE: usize = 1 + 3 * VARINT_U32_MAX_SIZE + MAX_DATA_BLOCK_SIZE;
+
+/// Worst-case COBS encoding of `len` bytes, without the `0x00` delimiter:
+/// one overhead byte per started run of 254 bytes.
+pub const fn cobs_max_encoded_len(len: usize) -> usize {
+    len + len / 254 + 1
+}
+
+/// Largest COBS frame of a `Command`, without the `0x00` delimiter. A receive
+/// buffer this size holds any valid command.
+pub const MAX_COMMAND_FRAME_SIZE: usize = cobs_max_encoded_len(MAX_COMMAND_SIZE);
+
+/// Largest COBS frame of a `Response`, without the `0x00` delimiter.
+pub const MAX_RESPONSE_FRAME_SIZE: usize = cobs_max_encoded_len(MAX_RESPONSE_SIZE);
+
 #[derive(Serialize, Deserialize, Debug)]
 #[allow(clippy::large_enum_variant)] // no_std, no allocator for Box
 pub enum Command {
@@ -150,6 +966,8 @@ pub enum Command {
         size: u32,
         crc32: u32,
         version: u32,
+        /// `CrcAlgorithm` id of `crc32`; `0` is ISO-HDLC.
+        crc_algo: u8,
     },
     #[cfg(not(feature = "std"))]
     DataBlock {
@@ -169,9 +987,257 @@ pub enum Command {
     },
     /// Wipe all firmware banks and reset boot data.
     WipeAll,
+    /// Reset into the RP2040 ROM USB bootloader (BOOTSEL / UF2 mass storage).
+    EnterBootrom,
+    /// Read flash erase counters.
+    GetWearStats,
+    /// Read the stored metadata of one firmware bank.
+    GetBankInfo {
+        bank: u8,
+    },
+    /// Copy the image in bank `from` to bank `to` on the device, including
+    /// its size, CRC and version. The active bank is not changed.
+    CopyBank {
+        from: u8,
+        to: u8,
+    },
+    /// Read the bootloader build identification.
+    GetBuildInfo,
+    /// Like `FinishUpdate`, but records the image without changing
+    /// `active_bank`. An image written to the active bank restarts its trial.
+    FinishUpdateNoActivate,
+    /// Mark the active image confirmed, as firmware's `confirm_boot()` would.
+    ConfirmBoot,
+    /// Read the transport error counters.
+    GetTransportStats,
+    /// Provision the golden bank (`golden-bank` builds only). Followed by
+    /// `DataBlock`s and `FinishUpdate`, like `StartUpdate`. Rejected with
+    /// `BankInvalid` once the golden bank holds an image.
+    WriteGolden {
+        size: u32,
+        crc32: u32,
+        version: u32,
+    },
+    /// Stage a new bootloader image in the inactive bank. Followed by
+    /// `DataBlock`s and `FinishUpdate`; after its `Ack(Ok)` the device copies
+    /// the image over the bootloader region and resets.
+    StartBootloaderUpdate {
+        size: u32,
+        crc32: u32,
+    },
+    /// Turn on anti-rollback, with the active bank's version as the first
+    /// floor. There is no command to turn it off again.
+    EnableAntiRollback,
+    /// Lock bank `bank` (0 or 1) against writes, or unlock it. A locked bank
+    /// can still be booted and activated.
+    SetBankLock {
+        bank: u8,
+        locked: bool,
+    },
+    /// Read the chip temperature and, if the board wires it, VSYS.
+    GetTelemetry,
+    /// Liveness check: answered with `Pong` carrying the same `nonce`,
+    /// without touching flash.
+    Ping {
+        nonce: u32,
+    },
+    /// Read the flash layout and how much of each region is in use.
+    GetFlashMap,
+    /// Read the most recent error codes recorded by the bootloader.
+    GetErrorLog,
+    /// Recompute the CRC of bank `bank` (0 or 1) over its stored size and
+    /// compare it with `expected_crc`: `Ack(Ok)` or `Ack(CrcError)`. Changes
+    /// nothing.
+    VerifyBank {
+        bank: u8,
+        expected_crc: u32,
+    },
+    /// Roll back after `max_attempts` unconfirmed boots (1-254) instead of
+    /// `DEFAULT_MAX_BOOT_ATTEMPTS`. Stored in BootData; reported as
+    /// `Status.max_boot_attempts`.
+    SetBootPolicy {
+        max_attempts: u8,
+    },
+    /// Start (`enable`) or stop forwarding the bootloader's defmt output as
+    /// `Log` frames. `Ack(BadCommand)` from bootloaders built without the
+    /// `log-stream` feature.
+    StreamLogs {
+        enable: bool,
+    },
+    /// Read config slot `key` (below `CONFIG_SLOTS`): `ConfigValue`, or
+    /// `Ack(BadCommand)` for a key out of range.
+    ConfigGet {
+        key: u8,
+    },
+    /// Store `value` in config slot `key`; erases and rewrites the whole
+    /// config sector.
+    ConfigSet {
+        key: u8,
+        value: u32,
+    },
+    /// Discard an upload in progress (the RAM buffer, and any progress
+    /// paging it into flash) and return to `Ready`. `Ack(Ok)` in `Ready`
+    /// too, where there is nothing to discard.
+    AbortUpdate,
+    /// Recompute the flash CRCs of banks A and B over their stored sizes,
+    /// streaming `Progress` (`Verify`): `BankCompare`.
+    CompareBanks,
+    /// Read why the chip last reset, as decoded when the bootloader started.
+    GetResetReason,
+    /// Read up to `len` bytes of bank `bank` (0 or 1) from `offset`:
+    /// `BankData`. The device caps the read at `MAX_DATA_BLOCK_SIZE` and at
+    /// the bank's stored image size, so it may return fewer bytes, and none
+    /// from the end of the image on.
+    ReadBank {
+        bank: u8,
+        offset: u32,
+        len: u32,
+    },
+    /// Erase the `SELFTEST_SCRATCH_ADDR` sector, program a test pattern,
+    /// read it back, then put back what the sector held: `SelfTest`.
+    FlashSelfTest,
+}
+
+/// The commands whose encoding changed, as hosts that predate the change
+/// encode them: `StartUpdate` without `crc_algo`. Variants keep their
+/// `Command` positions, so a frame that fails to decode as a `Command` can be
+/// decoded as this instead.
+#[derive(Deserialize, Debug)]
+pub enum LegacyCommand {
+    GetStatus,
+    StartUpdate {
+        bank: u8,
+        size: u32,
+        crc32: u32,
+        version: u32,
+    },
+}
+
+impl LegacyCommand {
+    /// The current `Command` with the same meaning.
+    pub fn upgrade(self) -> Command {
+        match self {
+            Self::GetStatus => Command::GetStatus,
+            Self::StartUpdate {
+                bank,
+                size,
+                crc32,
+                version,
+            } => Command::StartUpdate {
+                bank,
+                size,
+                crc32,
+                version,
+                crc_algo: CrcAlgorithm::IsoHdlc.id(),
+            },
+        }
+    }
+}
+
+/// Firmware metadata from an image header (`image_header::ImageHeader`).
+/// Versions are packed semver; zero means the field is not set.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FirmwareMetadata {
+    pub fw_version: u32,
+    /// Oldest bootloader the image runs under.
+    pub min_bootloader_version: u32,
+    pub build_id: u32,
+}
+
+/// What a `FlashRegion` holds.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashRegionKind {
+    /// Second-stage bootloader (256 bytes).
+    Boot2,
+    /// This bootloader.
+    Bootloader,
+    BankA,
+    BankB,
+    /// The BootData sector, or its mirror.
+    BootData,
+    /// The wear counter log sector.
+    WearStats,
+    /// The golden recovery bank (`golden-bank` builds).
+    Golden,
+    /// Flash the layout does not assign.
+    Free,
+    /// The user config store sector (`CONFIG_ADDR`).
+    Config,
+    /// The data partition's image area, or its `DataInfo` sector.
+    Data,
+    /// The `FlashSelfTest` scratch sector (`SELFTEST_SCRATCH_ADDR`).
+    SelfTest,
+}
+
+/// A failure recorded by the bootloader: logged over defmt as `err=<code>`
+/// and kept in a ring buffer read with `GetErrorLog`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ErrorCode {
+    /// `DataBlock.offset` did not continue the data received so far.
+    BadOffset,
+    /// A `DataBlock` ran past the size given when the upload started.
+    SizeOverflow,
+    /// The received image did not match its CRC before being written.
+    CrcMismatchRam,
+    /// The image read back from flash did not match its CRC.
+    CrcMismatchFlash,
+    /// A received frame failed COBS or postcard decoding.
+    DecodeFailed,
+    /// A response was not fully sent because the TX side stayed full.
+    TxDropped,
+    /// A received frame did not fit the RX buffer.
+    RxOverflow,
+    /// A command decoded while sending a response replaced one not yet handled.
+    CommandDropped,
+    /// A response did not fit the TX buffer.
+    EncodeFailed,
+    /// The USB controller reported a write error.
+    UsbWriteFailed,
+    /// A command is not valid in the current update state.
+    BadState,
+    /// `FinishUpdate` arrived before all data was received.
+    IncompleteData,
+    /// An upload is empty or larger than its target.
+    BadSize,
+    /// An image failed its header checks, or is not a bootloader image.
+    ImageRejected,
+    /// An image is older than the anti-rollback floor.
+    VersionTooOld,
+    /// A write targeted a locked bank.
+    BankLocked,
+    /// `WriteGolden` while the golden bank is already provisioned.
+    GoldenProvisioned,
+    /// A programmed page read back wrong and was programmed again.
+    ProgramRetried,
+    /// A programmed page still read back wrong after its retry.
+    ProgramVerifyFailed,
+    /// An erase or program overlapped boot2 or the bootloader, or ran past
+    /// the end of flash, and was refused.
+    FlashOutOfRange,
+    /// The linker script's flash size exceeds the capacity the flash part
+    /// reports in its JEDEC ID.
+    FlashTooSmall,
+    /// The BootData sector held no valid record and was restored from its
+    /// mirror.
+    BootDataRestored,
+}
+
+/// One entry of `Response::FlashMap`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlashRegion {
+    /// XIP address of the first byte.
+    pub start: u32,
+    pub len: u32,
+    /// Bytes in use from `start` on: the image size for banks, the record
+    /// size for metadata sectors, `0` for `Free`.
+    pub used: u32,
+    pub kind: FlashRegionKind,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+#[allow(clippy::large_enum_variant)] // no_std, no allocator for Box
 pub enum Response {
     Ack(AckStatus),
     Status {
@@ -181,7 +1247,201 @@ pub enum Response {
         state: BootState,
         #[serde(default)]
         bootloader_version: Option<u32>,
+        /// Largest `DataBlock` payload the device accepts, in bytes.
+        #[serde(default)]
+        max_data_block_size: Option<u32>,
+        /// Whether the active image has confirmed a successful boot.
+        #[serde(default)]
+        confirmed: Option<bool>,
+        /// Unconfirmed boots of the active image so far.
+        #[serde(default)]
+        boot_attempts: Option<u8>,
+        /// Unconfirmed boots allowed before rolling back to the other bank.
+        #[serde(default)]
+        max_boot_attempts: Option<u8>,
+        /// The active bank was booted because the other one failed validation.
+        #[serde(default)]
+        fell_back: Option<bool>,
+        /// Anti-rollback floor (packed semver); `None` while anti-rollback is off.
+        #[serde(default)]
+        min_version: Option<u32>,
+        /// How far the flash write of a `FinishUpdate`, in percent, while
+        /// `state` is `Persisting`.
+        #[serde(default)]
+        persist_percent: Option<u8>,
+    },
+    /// Intermediate progress of a long-running command.
+    ///
+    /// Zero or more of these precede the final `Ack`; hosts should keep
+    /// reading until a non-`Progress` response arrives.
+    Progress {
+        phase: ProgressPhase,
+        percent: u8,
+    },
+    /// Number of sector-erase operations recorded per flash region.
+    WearStats {
+        bank_a_erases: u32,
+        bank_b_erases: u32,
+        bootdata_erases: u32,
+    },
+    /// Metadata recorded in BootData for one bank. `size == 0` means empty.
+    BankInfo {
+        bank: u8,
+        size: u32,
+        crc32: u32,
+        version: u32,
+        active: bool,
+        /// Metadata from the image header, for images that carry one.
+        #[serde(default)]
+        metadata: Option<FirmwareMetadata>,
+        /// Whether the bank is locked against writes; `None` for the golden bank.
+        #[serde(default)]
+        locked: Option<bool>,
+    },
+    /// Bootloader build identification.
+    BuildInfo {
+        /// Git short hash (ASCII, NUL-padded); `unknown` outside a git checkout.
+        git_hash: [u8; 8],
+        /// Build time in seconds since the Unix epoch (`SOURCE_DATE_EPOCH` if set).
+        build_epoch: u32,
+        /// `BUILD_FEATURE_*` bits compiled into this bootloader.
+        features: u32,
+    },
+    /// Transport counters since the bootloader started.
+    TransportStats {
+        /// Frames discarded because they did not fit the RX buffer.
+        rx_overflows: u32,
+        /// Responses not fully sent because the TX side stayed full.
+        tx_drops: u32,
+        /// Frames that failed COBS or postcard decoding.
+        decode_errors: u32,
+        /// Frames decoded into a command.
+        frames_received: u32,
+    },
+    /// Environmental readings taken when `GetTelemetry` is handled.
+    Telemetry {
+        /// Internal temperature sensor, in milli-degrees Celsius.
+        temp_c_milli: i32,
+        /// VSYS in millivolts; zero when the board has no VSYS sense pin.
+        vsys_mv: u32,
+    },
+    /// Answer to `Ping`, echoing its nonce.
+    Pong {
+        nonce: u32,
+    },
+    /// Flash regions in address order, with unassigned gaps as `Free`.
+    #[cfg(not(feature = "std"))]
+    FlashMap {
+        regions: heapless::Vec<FlashRegion, MAX_FLASH_REGIONS>,
+    },
+    #[cfg(feature = "std")]
+    FlashMap {
+        regions: alloc::vec::Vec<FlashRegion>,
+    },
+    /// Error codes recorded since the bootloader started, oldest first.
+    /// `total` counts all of them, including those that no longer fit.
+    #[cfg(not(feature = "std"))]
+    ErrorLog {
+        total: u32,
+        recent: heapless::Vec<ErrorCode, ERROR_LOG_LEN>,
+    },
+    #[cfg(feature = "std")]
+    ErrorLog {
+        total: u32,
+        recent: alloc::vec::Vec<ErrorCode>,
+    },
+    /// Encoded defmt frames, sent unsolicited while `StreamLogs` is on.
+    ///
+    /// The variant tag tells these apart from command responses: they can
+    /// arrive before any response, and hosts waiting for one skip them as
+    /// they skip `Progress`. `bytes` is a slice of the raw defmt stream, for
+    /// a decoder such as `defmt-print`; `dropped` counts bytes lost to a full
+    /// device buffer since the previous `Log`.
+    #[cfg(not(feature = "std"))]
+    Log {
+        dropped: u32,
+        bytes: heapless::Vec<u8, LOG_CHUNK_SIZE>,
+    },
+    #[cfg(feature = "std")]
+    Log {
+        dropped: u32,
+        bytes: alloc::vec::Vec<u8>,
     },
+    /// Answer to `ConfigGet`; `CONFIG_UNSET` for a slot never set.
+    ConfigValue {
+        key: u8,
+        value: u32,
+    },
+    /// Answer to `CompareBanks`: the CRC-32 (ISO-HDLC) of each bank's flash
+    /// over its stored size. `equal` when both banks hold firmware of the
+    /// same size and CRC.
+    BankCompare {
+        a_crc: u32,
+        b_crc: u32,
+        equal: bool,
+    },
+    /// Answer to `GetResetReason`: a `reset_reason::ResetReason` wire id.
+    /// Hosts show ids they do not know as numbers.
+    ResetReason {
+        reason: u8,
+    },
+    /// Answer to `ReadBank`: the bytes of bank `bank` from `offset` on. Empty
+    /// at or past the end of the stored image.
+    #[cfg(not(feature = "std"))]
+    BankData {
+        bank: u8,
+        offset: u32,
+        data: heapless::Vec<u8, MAX_DATA_BLOCK_SIZE>,
+    },
+    #[cfg(feature = "std")]
+    BankData {
+        bank: u8,
+        offset: u32,
+        data: alloc::vec::Vec<u8>,
+    },
+    /// Answer to `FlashSelfTest`. `detail` is `SELFTEST_PASSED`, or the
+    /// `SELFTEST_*` code of the step that failed first.
+    SelfTest {
+        passed: bool,
+        detail: u8,
+    },
+}
+
+impl Response {
+    /// Whether this can be the final response to `cmd`. `Ack` answers any
+    /// command, if only to reject it; the other responses only answer their
+    /// query, for the same bank, key or nonce. `Progress` and `Log` frames
+    /// are never final. A host uses this to skip a stale frame, such as the
+    /// late response to an earlier command.
+    pub fn answers(&self, cmd: &Command) -> bool {
+        match self {
+            Response::Ack(_) => true,
+            Response::Status { .. } => matches!(cmd, Command::GetStatus),
+            Response::Progress { .. } | Response::Log { .. } => false,
+            Response::WearStats { .. } => matches!(cmd, Command::GetWearStats),
+            Response::BankInfo { bank, .. } => {
+                matches!(cmd, Command::GetBankInfo { bank: asked } if asked == bank)
+            }
+            Response::BuildInfo { .. } => matches!(cmd, Command::GetBuildInfo),
+            Response::TransportStats { .. } => matches!(cmd, Command::GetTransportStats),
+            Response::Telemetry { .. } => matches!(cmd, Command::GetTelemetry),
+            Response::Pong { nonce } => {
+                matches!(cmd, Command::Ping { nonce: sent } if sent == nonce)
+            }
+            Response::FlashMap { .. } => matches!(cmd, Command::GetFlashMap),
+            Response::ErrorLog { .. } => matches!(cmd, Command::GetErrorLog),
+            Response::ConfigValue { key, .. } => {
+                matches!(cmd, Command::ConfigGet { key: asked } if asked == key)
+            }
+            Response::BankCompare { .. } => matches!(cmd, Command::CompareBanks),
+            Response::ResetReason { .. } => matches!(cmd, Command::GetResetReason),
+            Response::BankData { bank, offset, .. } => matches!(
+                cmd,
+                Command::ReadBank { bank: asked, offset: at, .. } if asked == bank && at == offset
+            ),
+            Response::SelfTest { .. } => matches!(cmd, Command::FlashSelfTest),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
@@ -192,6 +1452,21 @@ pub enum AckStatus {
     BadCommand,
     BadState,
     BankInvalid,
+    /// The image is older than the anti-rollback floor.
+    VersionTooOld,
+    /// The command would overwrite a locked bank.
+    BankLocked,
+    /// An upload is in progress, or the flash part stayed busy; retry once
+    /// it has finished.
+    Busy,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProgressPhase {
+    Erase,
+    Program,
+    Verify,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
@@ -199,4 +1474,7 @@ pub enum BootState {
     Idle,
     UpdateMode,
     Receiving,
+    /// Core 1 is writing an upload to flash (`dual-core-flash` builds); the
+    /// `FinishUpdate` answer follows once it is done.
+    Persisting,
 }
@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Reset reason decoding and its effect on the boot attempt counter.
+//!
+//! The bootloader reads `WATCHDOG.REASON` and `VREG_AND_CHIP_RESET.CHIP_RESET`
+//! once at startup; decoding and the boot decision are pure so they can be
+//! tested on the host.
+
+/// `WATCHDOG.REASON` bit: the watchdog timer expired.
+pub const WATCHDOG_REASON_TIMER: u32 = 1 << 0;
+/// `WATCHDOG.REASON` bit: software forced a watchdog reset.
+pub const WATCHDOG_REASON_FORCE: u32 = 1 << 1;
+
+/// `CHIP_RESET` bit: power-on reset or brown-out.
+pub const CHIP_RESET_HAD_POR: u32 = 1 << 8;
+/// `CHIP_RESET` bit: the RUN pin was pulled low.
+pub const CHIP_RESET_HAD_RUN: u32 = 1 << 16;
+/// `CHIP_RESET` bit: restart requested through the rescue debug port.
+pub const CHIP_RESET_HAD_PSM_RESTART: u32 = 1 << 20;
+
+/// Why the chip last came out of reset.
+///
+/// Sent as `Response::ResetReason.reason` by its position here, so new
+/// reasons are only appended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResetReason {
+    /// Power-on or brown-out. RAM contents are undefined.
+    PowerOn,
+    /// The RUN pin (reset button).
+    RunPin,
+    /// A debugger restart through the rescue debug port.
+    Debugger,
+    /// The watchdog timer expired: firmware hung.
+    Watchdog,
+    /// Software forced a watchdog reset.
+    WatchdogForced,
+    /// No flag set, e.g. a `SYSRESETREQ` from firmware or the bootloader.
+    Software,
+}
+
+impl ResetReason {
+    /// The reason with wire id `id`, if this build knows it.
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::PowerOn),
+            1 => Some(Self::RunPin),
+            2 => Some(Self::Debugger),
+            3 => Some(Self::Watchdog),
+            4 => Some(Self::WatchdogForced),
+            5 => Some(Self::Software),
+            _ => None,
+        }
+    }
+
+    /// Wire id, for `Response::ResetReason`.
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    /// Decode the raw `WATCHDOG.REASON` and `CHIP_RESET` register values.
+    ///
+    /// `WATCHDOG.REASON` is checked first: a watchdog reset does not clear
+    /// `CHIP_RESET`, which may still hold the flags of an earlier reset.
+    pub fn decode(watchdog_reason: u32, chip_reset: u32) -> Self {
+        if watchdog_reason & WATCHDOG_REASON_TIMER != 0 {
+            Self::Watchdog
+        } else if watchdog_reason & WATCHDOG_REASON_FORCE != 0 {
+            Self::WatchdogForced
+        } else if chip_reset & CHIP_RESET_HAD_PSM_RESTART != 0 {
+            Self::Debugger
+        } else if chip_reset & CHIP_RESET_HAD_RUN != 0 {
+            Self::RunPin
+        } else if chip_reset & CHIP_RESET_HAD_POR != 0 {
+            Self::PowerOn
+        } else {
+            Self::Software
+        }
+    }
+}
+
+/// What to do with the active bank's boot attempt counter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BootDecision {
+    /// Boot the active bank and count this boot as an unconfirmed attempt.
+    Count,
+    /// Boot the active bank without counting: the image is confirmed, or a
+    /// debugger restarted the chip.
+    Uncounted,
+    /// The unconfirmed image used up its attempts: roll back.
+    Rollback,
+}
+
+/// Decide how the boot after a `reason` reset treats the active bank.
+///
+/// An unconfirmed image is counted on every boot, so a watchdog reset before
+/// `confirm_boot()` is a failed attempt, and is rolled back once `attempts`
+/// reaches `max_attempts`. A debugger restart is never counted and never
+/// rolls back, so halting and restarting a trial image while debugging does
+/// not switch banks.
+pub fn boot_decision(
+    reason: ResetReason,
+    confirmed: bool,
+    attempts: u8,
+    max_attempts: u8,
+) -> BootDecision {
+    if confirmed || reason == ResetReason::Debugger {
+        BootDecision::Uncounted
+    } else if attempts >= max_attempts {
+        BootDecision::Rollback
+    } else {
+        BootDecision::Count
+    }
+}
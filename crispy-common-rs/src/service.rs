@@ -14,6 +14,12 @@ pub enum Event {
     RequestUpdate,
     /// Request to enter boot mode
     RequestBoot,
+    /// Update mode is up and the USB transport is ready
+    UpdateModeEntered,
+    /// USB could not be initialized; the bootloader keeps retrying
+    RecoveryEntered,
+    /// The trigger pin is watched until `until_us` (timer µs) before booting
+    TriggerWindowOpened { until_us: u64 },
 }
 
 /// Event bus for inter-service communication
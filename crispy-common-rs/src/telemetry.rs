@@ -0,0 +1,41 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Conversion of raw RP2040 ADC readings for `Response::Telemetry`.
+//!
+//! The bootloader samples the internal temperature sensor and, on boards
+//! that wire it, VSYS through a resistor divider; the conversions are pure so
+//! they can be tested on the host.
+
+/// ADC reference voltage on boards that tie ADC_AVDD to 3.3 V, in millivolts.
+pub const ADC_VREF_MV: u32 = 3300;
+
+/// Full-scale count of the 12-bit ADC.
+pub const ADC_FULL_SCALE: u32 = 4096;
+
+/// Temperature sensor output at 27 °C, in microvolts (RP2040 datasheet 4.9.5).
+const TEMP_SENSOR_UV_AT_27C: i64 = 706_000;
+
+/// Temperature sensor slope, in microvolts per °C (falling with temperature).
+const TEMP_SENSOR_UV_PER_C: i64 = 1721;
+
+/// Convert a raw ADC count to microvolts at the ADC input.
+fn adc_uv(raw: u16) -> u32 {
+    // Sub-millivolt resolution keeps the temperature steps at about 0.5 °C.
+    (u64::from(raw & 0x0FFF) * u64::from(ADC_VREF_MV) * 1000 / u64::from(ADC_FULL_SCALE)) as u32
+}
+
+/// Temperature in milli-degrees Celsius from a temperature sensor reading.
+///
+/// Uses the datasheet's typical sensor curve; individual chips are off by a
+/// few degrees, which is enough to spot a board running hot.
+pub fn temp_c_milli(raw: u16) -> i32 {
+    let uv = i64::from(adc_uv(raw));
+    (27_000 - (uv - TEMP_SENSOR_UV_AT_27C) * 1000 / TEMP_SENSOR_UV_PER_C) as i32
+}
+
+/// VSYS in millivolts from a reading taken behind a `divider`:1 resistor
+/// divider (3 on the Pico's GPIO29).
+pub fn vsys_mv(raw: u16, divider: u32) -> u32 {
+    (u64::from(adc_uv(raw)) * u64::from(divider) / 1000) as u32
+}
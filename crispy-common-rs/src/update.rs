@@ -0,0 +1,649 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! The receive side of an image upload: `StartUpdate`'s checks, `DataBlock`
+//! buffering and paging, and `FinishUpdate`'s checks, write and verify.
+//!
+//! Everything goes through [`FlashOps`] and a RAM buffer the caller lends as
+//! a slice: the bootloader runs it on the ROM flash functions and its
+//! firmware RAM region, host tests on [`MemFlash`](crate::flash_ops::MemFlash)
+//! and a `Vec`. What is specific to the bootloader (its error log, the data
+//! partition and golden bank records, writing on core 1) stays with the
+//! caller, which learns what happened from the results.
+//!
+//! [`dispatch`] runs the commands that upload to bank A, B or the data
+//! partition or that switch between the banks, with the state they share.
+//!
+//! An image larger than the buffer is paged into its bank as the buffer
+//! fills. Its first sector stays in RAM and is written last, after the first
+//! page erased it in flash, so the bank never holds a partial image that
+//! passes the vector table check.
+
+use crate::crc32::CrcAlgorithm;
+use crate::flash_ops::{self, FlashError, FlashOps};
+use crate::image_header::{self, IMAGE_HEADER_SIZE};
+use crate::protocol::{
+    AckStatus, BootData, Command, ErrorCode, ProgressPhase, BOOT_FLAG_ANTI_ROLLBACK,
+    BOOT_FLAG_FALLBACK, BOOT_FLAG_GOLDEN, DATA_ADDR, DATA_BANK, DATA_MAX_IMAGE_SIZE, FLASH_BASE,
+    FLASH_SECTOR_SIZE, FW_BANK_SIZE, GOLDEN_BANK, GOLDEN_MAX_IMAGE_SIZE,
+};
+use crate::vector_table::{ImageRegions, VectorTable, VectorTableError};
+
+/// Start of a paged image kept in RAM until `FinishUpdate`.
+pub const PAGED_HEAD_SIZE: u32 = FLASH_SECTOR_SIZE;
+
+/// Why an update command was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UpdateError {
+    /// An upload is in progress, or the command needs one and none is.
+    BadState,
+    /// `SetActiveBank` during an upload; the host can retry after it.
+    Busy,
+    /// `StartUpdate.crc_algo` names no known CRC.
+    UnknownCrcAlgorithm,
+    /// Not a bank the command accepts.
+    InvalidBank,
+    /// The command would overwrite a locked bank.
+    BankLocked,
+    /// The image is empty or larger than its target.
+    BadSize,
+    /// `SetActiveBank` to a bank BootData records no image for.
+    NoImage,
+    /// `DataBlock.offset` did not continue the data received so far.
+    BadOffset,
+    /// A `DataBlock` ran past the size given when the upload started.
+    SizeOverflow,
+    /// `FinishUpdate` arrived before all data was received.
+    Incomplete,
+    /// The received image did not match its CRC before being written.
+    CrcMismatchRam,
+    /// The image read back from flash did not match its CRC.
+    CrcMismatchFlash,
+    /// The image could never boot from its bank.
+    ImageRejected(VectorTableError),
+    /// The image is older than the anti-rollback floor.
+    VersionTooOld,
+    /// An erase, program or BootData write failed.
+    Flash(FlashError),
+}
+
+impl UpdateError {
+    /// The error log entry for this refusal, if it gets one. Flash errors
+    /// are logged by the flash layer itself.
+    pub fn error_code(self) -> Option<ErrorCode> {
+        match self {
+            Self::BadState
+            | Self::Busy
+            | Self::UnknownCrcAlgorithm
+            | Self::InvalidBank
+            | Self::NoImage
+            | Self::Flash(_) => None,
+            Self::BankLocked => Some(ErrorCode::BankLocked),
+            Self::BadSize => Some(ErrorCode::BadSize),
+            Self::BadOffset => Some(ErrorCode::BadOffset),
+            Self::SizeOverflow => Some(ErrorCode::SizeOverflow),
+            Self::Incomplete => Some(ErrorCode::IncompleteData),
+            Self::CrcMismatchRam => Some(ErrorCode::CrcMismatchRam),
+            Self::CrcMismatchFlash => Some(ErrorCode::CrcMismatchFlash),
+            Self::ImageRejected(_) => Some(ErrorCode::ImageRejected),
+            Self::VersionTooOld => Some(ErrorCode::VersionTooOld),
+        }
+    }
+
+    /// Whether the upload in progress survives the refusal: a command out
+    /// of turn, a block at the wrong offset or too long, or a `FinishUpdate`
+    /// sent too early, leave it as it was; every other error ends it.
+    pub fn keeps_upload(self) -> bool {
+        matches!(
+            self,
+            Self::BadState | Self::Busy | Self::BadOffset | Self::SizeOverflow | Self::Incomplete
+        )
+    }
+}
+
+impl From<FlashError> for UpdateError {
+    fn from(err: FlashError) -> Self {
+        Self::Flash(err)
+    }
+}
+
+impl From<UpdateError> for AckStatus {
+    fn from(err: UpdateError) -> Self {
+        match err {
+            UpdateError::BadState => Self::BadState,
+            UpdateError::Busy => Self::Busy,
+            UpdateError::InvalidBank | UpdateError::BadSize | UpdateError::NoImage => {
+                Self::BankInvalid
+            }
+            UpdateError::BankLocked => Self::BankLocked,
+            UpdateError::CrcMismatchRam | UpdateError::CrcMismatchFlash => Self::CrcError,
+            UpdateError::VersionTooOld => Self::VersionTooOld,
+            UpdateError::Flash(err) => err.into(),
+            UpdateError::UnknownCrcAlgorithm
+            | UpdateError::BadOffset
+            | UpdateError::SizeOverflow
+            | UpdateError::Incomplete
+            | UpdateError::ImageRejected(_) => Self::BadCommand,
+        }
+    }
+}
+
+/// What images are checked against before their bank is overwritten: the
+/// running bootloader's version and its firmware RAM geometry.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageLimits {
+    /// Packed semver of the running bootloader.
+    pub bootloader_version: u32,
+    /// Start of firmware RAM; RAM images are copied here.
+    pub ram_base: u32,
+    /// Bytes the boot path copies to `ram_base` for RAM images.
+    pub copy_size: u32,
+    /// Highest address the initial SP may hold (inclusive).
+    pub ram_end: u32,
+    /// Bytes a RAM image's initial SP must lie above the copied image.
+    pub min_stack_headroom: u32,
+}
+
+impl ImageLimits {
+    /// The regions an image for the bank at `bank_addr` is checked against.
+    pub fn regions(&self, bank_addr: u32, bank_size: u32) -> ImageRegions {
+        ImageRegions {
+            ram_base: self.ram_base,
+            copy_size: self.copy_size,
+            ram_end: self.ram_end,
+            bank_addr,
+            bank_size,
+            min_stack_headroom: self.min_stack_headroom,
+        }
+    }
+}
+
+/// Refuse an image that could never boot from `bank` (header checks, or a
+/// RAM image of `size` bytes that does not fit the copy window) or that the
+/// anti-rollback floor in `bd` forbids, before any of the bank is
+/// overwritten. `image` holds at least the image's first sector. The data
+/// partition is never booted, so takes any contents.
+///
+/// The rest of the vector table is left to the boot path, which also
+/// accepts banks written by other means.
+pub fn check_image(
+    bank: u8,
+    bank_addr: u32,
+    version: u32,
+    image: &[u8],
+    size: u32,
+    bd: &BootData,
+    limits: &ImageLimits,
+) -> Result<(), UpdateError> {
+    if bank == DATA_BANK {
+        return Ok(());
+    }
+    if let Some(first_bytes) = image.first_chunk::<IMAGE_HEADER_SIZE>() {
+        let bank_size = if bank == GOLDEN_BANK {
+            GOLDEN_MAX_IMAGE_SIZE
+        } else {
+            FW_BANK_SIZE
+        };
+        let header = image_header::check_header(first_bytes, bank_size, limits.bootloader_version)
+            .map_err(UpdateError::ImageRejected)?;
+
+        let offset = header.map_or(0, |header| header.vector_table_offset);
+        let regions = limits.regions(bank_addr, bank_size);
+        let mode = image
+            .get(offset as usize..)
+            .and_then(|rest| rest.first_chunk::<8>())
+            .map(VectorTable::from_bytes)
+            .and_then(|vt| vt.validate(&regions).ok());
+        if let Some(mode) = mode {
+            regions
+                .check_image_len(mode, size.saturating_sub(offset))
+                .map_err(UpdateError::ImageRejected)?;
+        }
+    }
+
+    if bank != GOLDEN_BANK && !bd.allows_version(version) {
+        return Err(UpdateError::VersionTooOld);
+    }
+    Ok(())
+}
+
+/// An upload in progress: where it goes and how much of it arrived.
+///
+/// An image larger than the RAM buffer is paged into flash as the buffer
+/// fills: `paged_out` bytes after the first sector are already programmed
+/// and `running_crc` covers the first sector and those bytes. Both stay 0
+/// while the whole image fits in RAM. `expected_crc` and `running_crc` are
+/// `crc_algo` CRCs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Upload {
+    pub bank: u8,
+    pub bank_addr: u32,
+    pub expected_size: u32,
+    pub expected_crc: u32,
+    pub crc_algo: CrcAlgorithm,
+    pub version: u32,
+    pub bytes_received: u32,
+    pub paged_out: u32,
+    pub running_crc: u32,
+}
+
+impl Upload {
+    /// An upload of `size` bytes to `bank_addr`, nothing received yet.
+    pub fn new(
+        bank: u8,
+        bank_addr: u32,
+        size: u32,
+        crc: u32,
+        crc_algo: CrcAlgorithm,
+        version: u32,
+    ) -> Self {
+        Self {
+            bank,
+            bank_addr,
+            expected_size: size,
+            expected_crc: crc,
+            crc_algo,
+            version,
+            bytes_received: 0,
+            paged_out: 0,
+            running_crc: 0,
+        }
+    }
+
+    /// `StartUpdate` to bank A, B or the data partition: a known CRC, an
+    /// unlocked bank, a size that fits it and a range the flash part has.
+    pub fn start(
+        flash: &impl FlashOps,
+        bank: u8,
+        size: u32,
+        crc32: u32,
+        version: u32,
+        crc_algo: u8,
+    ) -> Result<Self, UpdateError> {
+        let crc_algo = CrcAlgorithm::from_id(crc_algo).ok_or(UpdateError::UnknownCrcAlgorithm)?;
+
+        // The data partition is not a bank `CopyBank` or the boot path know of.
+        let (bank_addr, bank_size) = if bank == DATA_BANK {
+            (DATA_ADDR, DATA_MAX_IMAGE_SIZE)
+        } else {
+            let (addr, _) = flash_ops::bank_range(bank).ok_or(UpdateError::InvalidBank)?;
+            (addr, FW_BANK_SIZE)
+        };
+
+        if flash.read_boot_data().bank_locked(bank) {
+            return Err(UpdateError::BankLocked);
+        }
+        if size == 0 || size > bank_size {
+            return Err(UpdateError::BadSize);
+        }
+        // A bank past the end of a smaller flash part than the layout's.
+        flash
+            .check_range(bank_addr - FLASH_BASE, size)
+            .map_err(FlashError::from)?;
+
+        Ok(Self::new(bank, bank_addr, size, crc32, crc_algo, version))
+    }
+
+    /// Bytes received and still in RAM.
+    pub fn buffered(&self) -> u32 {
+        self.bytes_received - self.paged_out
+    }
+
+    /// `DataBlock`: append `data` at `offset` to the image in `ram`, paging
+    /// the buffer into flash first when `data` would overflow it. Returns
+    /// whether this block started paging, which erased the bank's first
+    /// sector.
+    ///
+    /// Only uploads started with [`Upload::start`] may outgrow `ram`, which
+    /// must hold two sectors more than the largest block.
+    pub fn data_block(
+        &mut self,
+        flash: &mut impl FlashOps,
+        ram: &mut [u8],
+        offset: u32,
+        data: &[u8],
+        limits: &ImageLimits,
+        on_progress: impl FnMut(ProgressPhase, u32, u32),
+    ) -> Result<bool, UpdateError> {
+        if offset != self.bytes_received {
+            return Err(UpdateError::BadOffset);
+        }
+        let len = u32::try_from(data.len()).map_err(|_| UpdateError::SizeOverflow)?;
+        if len > self.expected_size - self.bytes_received {
+            return Err(UpdateError::SizeOverflow);
+        }
+
+        let mut started = false;
+        if self.buffered() + len > ram.len() as u32 {
+            started = self.paged_out == 0;
+            self.page_out(flash, ram, limits, on_progress)?;
+        }
+
+        let at = self.buffered() as usize;
+        ram[at..at + data.len()].copy_from_slice(data);
+        self.bytes_received += len;
+        Ok(started)
+    }
+
+    /// Program the whole sectors buffered after the image's first sector to
+    /// the bank and move the remainder down, freeing RAM for more data.
+    ///
+    /// The first call checks the image against the bank and erases the
+    /// bank's first sector, so from then on the old image no longer
+    /// validates. The first sector itself stays in RAM until `FinishUpdate`.
+    fn page_out(
+        &mut self,
+        flash: &mut impl FlashOps,
+        ram: &mut [u8],
+        limits: &ImageLimits,
+        on_progress: impl FnMut(ProgressPhase, u32, u32),
+    ) -> Result<(), UpdateError> {
+        let head = PAGED_HEAD_SIZE as usize;
+        if self.paged_out == 0 {
+            check_image(
+                self.bank,
+                self.bank_addr,
+                self.version,
+                &ram[..head],
+                self.expected_size,
+                &flash.read_boot_data(),
+                limits,
+            )?;
+            flash_ops::erase_region(flash, self.bank_addr, FLASH_SECTOR_SIZE, |_, _| {})?;
+            self.running_crc = self.crc_algo.checksum(&ram[..head]);
+        }
+
+        let buffered = self.buffered() as usize;
+        let page_len = (buffered - head) / FLASH_SECTOR_SIZE as usize * FLASH_SECTOR_SIZE as usize;
+        let page = &ram[head..head + page_len];
+        self.running_crc = self.crc_algo.extend(self.running_crc, page);
+        let offset = self.bank_addr - FLASH_BASE + PAGED_HEAD_SIZE + self.paged_out;
+        flash_ops::write_image(flash, offset, page, true, on_progress)?;
+
+        ram.copy_within(head + page_len..buffered, head);
+        self.paged_out += page_len as u32;
+        Ok(())
+    }
+
+    /// Check that the whole image arrived and that its CRC, over the data
+    /// still in RAM and the running CRC of what was paged out, matches.
+    pub fn verify_ram(&self, ram: &[u8]) -> Result<(), UpdateError> {
+        if self.bytes_received != self.expected_size {
+            return Err(UpdateError::Incomplete);
+        }
+        let buffered = self.buffered() as usize;
+        let crc = if self.paged_out == 0 {
+            self.crc_algo.checksum(&ram[..buffered])
+        } else {
+            self.crc_algo
+                .extend(self.running_crc, &ram[PAGED_HEAD_SIZE as usize..buffered])
+        };
+        if crc != self.expected_crc {
+            return Err(UpdateError::CrcMismatchRam);
+        }
+        Ok(())
+    }
+
+    /// `FinishUpdate`'s checks before anything is written: [`verify_ram`],
+    /// then [`check_image`] for an image still whole in RAM (a paged one was
+    /// checked before its first page). Returns the image to write.
+    ///
+    /// [`verify_ram`]: Upload::verify_ram
+    pub fn finish(
+        &self,
+        flash: &impl FlashOps,
+        ram: &[u8],
+        limits: &ImageLimits,
+        activate: bool,
+    ) -> Result<PendingImage, UpdateError> {
+        self.verify_ram(ram)?;
+        if self.paged_out == 0 {
+            check_image(
+                self.bank,
+                self.bank_addr,
+                self.version,
+                &ram[..self.expected_size as usize],
+                self.expected_size,
+                &flash.read_boot_data(),
+                limits,
+            )?;
+        }
+        Ok(PendingImage {
+            bank: self.bank,
+            bank_addr: self.bank_addr,
+            size: self.expected_size,
+            crc: self.expected_crc,
+            crc_algo: self.crc_algo,
+            version: self.version,
+            activate,
+            paged_out: self.paged_out,
+        })
+    }
+}
+
+/// An upload whose RAM CRC checked out, as `FinishUpdate` records it once
+/// the image is written to flash. `paged_out` is the upload's: the bytes
+/// after the first sector already in flash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PendingImage {
+    pub bank: u8,
+    pub bank_addr: u32,
+    pub size: u32,
+    pub crc: u32,
+    pub crc_algo: CrcAlgorithm,
+    pub version: u32,
+    pub activate: bool,
+    pub paged_out: u32,
+}
+
+impl PendingImage {
+    /// Erase and program what `ram` still holds of the image: all of it, or
+    /// for a paged image the tail first and then the first sector (erased by
+    /// the first page), so the image only becomes bootable once complete.
+    pub fn write(
+        &self,
+        flash: &mut impl FlashOps,
+        ram: &[u8],
+        mut on_progress: impl FnMut(ProgressPhase, u32, u32),
+    ) -> Result<(), FlashError> {
+        let offset = self.bank_addr - FLASH_BASE;
+        let buffered = (self.size - self.paged_out) as usize;
+        if self.paged_out == 0 {
+            return flash_ops::write_image(flash, offset, &ram[..buffered], true, on_progress);
+        }
+        let head = PAGED_HEAD_SIZE as usize;
+        flash_ops::write_image(
+            flash,
+            offset + PAGED_HEAD_SIZE + self.paged_out,
+            &ram[head..buffered],
+            true,
+            &mut on_progress,
+        )?;
+        flash_ops::write_image(flash, offset, &ram[..head], false, |_, _, _| {})
+    }
+
+    /// The end of `FinishUpdate` once the image is `written`: verify it in
+    /// flash and, for bank A or B, record it in BootData (switching
+    /// `active_bank` if `activate`). Returns the image's CRC-32 (ISO-HDLC),
+    /// which the caller records for the data partition and golden bank.
+    pub fn complete(
+        &self,
+        flash: &mut impl FlashOps,
+        written: Result<(), FlashError>,
+        mut on_progress: impl FnMut(ProgressPhase, u32, u32),
+    ) -> Result<u32, UpdateError> {
+        // A page that would not program is reported as such, rather than as
+        // the CRC mismatch the verify pass would find.
+        written?;
+
+        let offset = self.bank_addr - FLASH_BASE;
+        let flash_crc = flash.compute_crc(self.crc_algo, offset, self.size, |done| {
+            on_progress(ProgressPhase::Verify, done, self.size)
+        });
+        if flash_crc != self.crc {
+            return Err(UpdateError::CrcMismatchFlash);
+        }
+
+        // BootData and the golden info record ISO-HDLC CRCs whatever the
+        // host verified the upload with: the boot path checks images
+        // against them.
+        let crc32 = match self.crc_algo {
+            CrcAlgorithm::IsoHdlc => self.crc,
+            _ => flash.compute_crc32(offset, self.size),
+        };
+
+        if flash_ops::bank_range(self.bank).is_some() {
+            let mut bd = flash.read_boot_data();
+            bd.record_image(self.bank, self.version, crc32, self.size, self.activate);
+            flash.write_boot_data(&bd)?;
+        }
+        Ok(crc32)
+    }
+}
+
+/// What an update command [`dispatch`] ran did, beyond being acknowledged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Handled {
+    /// Nothing more to do: acknowledge `Ok`.
+    Done,
+    /// `StartUpdate` began an upload.
+    Started,
+    /// A `DataBlock` was accepted; `paging_started` if it began paging the
+    /// image into its bank, erasing the bank's first sector.
+    Received { paging_started: bool },
+    /// `FinishUpdate`'s checks passed and the upload ended: the caller
+    /// writes `image` and acknowledges what [`PendingImage::complete`] says.
+    Finish(PendingImage),
+    /// `AbortUpdate` dropped an upload. What a paged upload wrote stays in
+    /// flash: its bank's first sector is erased either way.
+    Aborted(Upload),
+    /// Not an update command; left to the caller.
+    Other,
+}
+
+/// Run `cmd` if it is an update command, with `upload` the upload in
+/// progress, if any, and `ram` the buffer it is received into.
+///
+/// Commands out of turn are refused with [`UpdateError::BadState`] (or
+/// [`UpdateError::Busy`] for `SetActiveBank`) and leave everything as it
+/// was; any other refusal the upload does not survive ends it.
+pub fn dispatch(
+    flash: &mut impl FlashOps,
+    ram: &mut [u8],
+    limits: &ImageLimits,
+    upload: &mut Option<Upload>,
+    cmd: &Command,
+    on_progress: impl FnMut(ProgressPhase, u32, u32),
+) -> Result<Handled, UpdateError> {
+    let result = match (cmd, upload.as_mut()) {
+        (
+            &Command::StartUpdate {
+                bank,
+                size,
+                crc32,
+                version,
+                crc_algo,
+            },
+            None,
+        ) => Upload::start(flash, bank, size, crc32, version, crc_algo).map(|started| {
+            *upload = Some(started);
+            Handled::Started
+        }),
+        (Command::DataBlock { offset, data }, Some(receiving)) => receiving
+            .data_block(flash, ram, *offset, data, limits, on_progress)
+            .map(|paging_started| Handled::Received { paging_started }),
+        (Command::FinishUpdate | Command::FinishUpdateNoActivate, Some(receiving)) => {
+            let activate = matches!(cmd, Command::FinishUpdate);
+            receiving.finish(flash, ram, limits, activate).map(|image| {
+                *upload = None;
+                Handled::Finish(image)
+            })
+        }
+        (Command::AbortUpdate, _) => Ok(upload.take().map_or(Handled::Done, Handled::Aborted)),
+        (&Command::SetActiveBank { bank }, None) => set_active_bank(flash, bank),
+        (Command::SetActiveBank { .. }, Some(_)) => Err(UpdateError::Busy),
+        (&Command::SetBankLock { bank, locked }, None) => set_bank_lock(flash, bank, locked),
+        (Command::WipeAll, None) => wipe_all(flash),
+        (
+            Command::StartUpdate { .. }
+            | Command::DataBlock { .. }
+            | Command::FinishUpdate
+            | Command::FinishUpdateNoActivate
+            | Command::SetBankLock { .. }
+            | Command::WipeAll,
+            _,
+        ) => Err(UpdateError::BadState),
+        _ => Ok(Handled::Other),
+    };
+    if let Err(err) = result {
+        if !err.keeps_upload() {
+            *upload = None;
+        }
+    }
+    result
+}
+
+/// `SetActiveBank`: boot `bank` next, on trial, if the image BootData
+/// records for it is allowed and reads back with its CRC.
+fn set_active_bank(flash: &mut impl FlashOps, bank: u8) -> Result<Handled, UpdateError> {
+    let (addr, _) = flash_ops::bank_range(bank).ok_or(UpdateError::InvalidBank)?;
+    let mut bd = flash.read_boot_data();
+    let (size, crc, version) = if bank == 0 {
+        (bd.size_a, bd.crc_a, bd.version_a)
+    } else {
+        (bd.size_b, bd.crc_b, bd.version_b)
+    };
+    if size == 0 {
+        return Err(UpdateError::NoImage);
+    }
+    if !bd.allows_version(version) {
+        return Err(UpdateError::VersionTooOld);
+    }
+    if flash.compute_crc32(addr - FLASH_BASE, size) != crc {
+        return Err(UpdateError::CrcMismatchFlash);
+    }
+
+    bd.active_bank = bank;
+    bd.confirmed = 0;
+    bd.boot_attempts = 0;
+    bd.flags &= !BOOT_FLAG_FALLBACK;
+    flash.write_boot_data(&bd)?;
+    Ok(Handled::Done)
+}
+
+/// `SetBankLock`: protect bank A or B against writes, or lift that.
+fn set_bank_lock(
+    flash: &mut impl FlashOps,
+    bank: u8,
+    locked: bool,
+) -> Result<Handled, UpdateError> {
+    if flash_ops::bank_range(bank).is_none() {
+        return Err(UpdateError::InvalidBank);
+    }
+    let mut bd = flash.read_boot_data();
+    if bd.bank_locked(bank) != locked {
+        bd.set_bank_locked(bank, locked);
+        flash.write_boot_data(&bd)?;
+    }
+    Ok(Handled::Done)
+}
+
+/// `WipeAll`: reset BootData, unless a bank is locked.
+fn wipe_all(flash: &mut impl FlashOps) -> Result<Handled, UpdateError> {
+    let old = flash.read_boot_data();
+    if old.any_bank_locked() {
+        return Err(UpdateError::BankLocked);
+    }
+    // The golden bank is not wiped, so keep recording that it is populated.
+    // Anti-rollback survives too, or a wipe would re-open downgrades, and so
+    // does the boot policy.
+    let mut bd = BootData::default_new();
+    bd.flags = old.flags & (BOOT_FLAG_GOLDEN | BOOT_FLAG_ANTI_ROLLBACK);
+    bd.min_version = old.rollback_floor().unwrap_or(0);
+    bd.max_boot_attempts = old.max_boot_attempts;
+    flash.write_boot_data(&bd)?;
+    Ok(Handled::Done)
+}
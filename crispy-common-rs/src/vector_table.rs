@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Firmware vector table validation.
+//!
+//! The bootloader reads the first two words of a bank (initial SP and reset
+//! vector) and decides from them whether the image can be booted and how.
+//! The checks are pure so they can be tested on the host, which also runs
+//! `VectorTable::check_plausible` on a file before uploading it.
+
+/// How an image runs, detected from where its vector table points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ExecMode {
+    /// Linked for RAM: copied to the RAM base before the jump.
+    Ram,
+    /// Linked for its own bank: executed in place from flash, no copy.
+    Xip,
+}
+
+/// Why a vector table was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum VectorTableError {
+    /// Initial SP is `0xFFFFFFFF`: the bank is erased.
+    Erased,
+    /// Initial SP is `0x00000000`: the bank is zero-filled.
+    Zeroed,
+    /// Reset vector has bit 0 clear; Cortex-M0+ only executes Thumb code.
+    NotThumb,
+    /// Initial SP is not 8-byte aligned, as the AAPCS requires at entry.
+    StackMisaligned,
+    /// Reset vector is neither in the copied RAM image nor in the bank itself.
+    ResetOutOfRange,
+    /// Initial SP is outside firmware RAM, or inside the copied RAM image.
+    StackOutOfRange,
+    /// RAM image whose initial SP leaves less than `min_stack_headroom` bytes
+    /// above the copied image; usually a wrong linker script.
+    StackHeadroom,
+    /// RAM image larger than the copied region; only XIP images may be
+    /// larger than the RAM buffer.
+    ImageTooLarge,
+    /// The image header's CRC does not match its contents.
+    HeaderCrc,
+    /// The image header's vector table offset is misaligned, overlaps the
+    /// header or lies outside the bank.
+    HeaderOffset,
+    /// The image header requires a newer bootloader than the running one.
+    BootloaderTooOld,
+}
+
+/// RP2040 SRAM: the four striped banks and the two 4 KB scratch banks.
+pub const RP2040_SRAM_START: u32 = 0x2000_0000;
+pub const RP2040_SRAM_END: u32 = 0x2004_2000;
+
+/// Memory regions a vector table is checked against.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageRegions {
+    /// Start of firmware RAM; RAM images are copied here.
+    pub ram_base: u32,
+    /// Bytes copied to `ram_base` for RAM images.
+    pub copy_size: u32,
+    /// Highest address the initial SP may hold (inclusive).
+    pub ram_end: u32,
+    /// Flash address of the bank holding the image.
+    pub bank_addr: u32,
+    /// Size of that bank in bytes.
+    pub bank_size: u32,
+    /// Bytes a RAM image's initial SP must lie above the copied image.
+    pub min_stack_headroom: u32,
+}
+
+impl ImageRegions {
+    /// Check that `len` bytes from the vector table on fit where `mode` runs
+    /// them: a RAM image must fit the copied region, or its end would never
+    /// be copied. 0 (size unknown) passes.
+    pub fn check_image_len(&self, mode: ExecMode, len: u32) -> Result<(), VectorTableError> {
+        if mode == ExecMode::Ram && len > self.copy_size {
+            return Err(VectorTableError::ImageTooLarge);
+        }
+        Ok(())
+    }
+}
+
+/// First two words of a Cortex-M vector table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VectorTable {
+    pub initial_sp: u32,
+    pub reset_vector: u32,
+}
+
+impl VectorTable {
+    /// The table in the first 8 bytes of an image, little-endian.
+    pub fn from_bytes(bytes: &[u8; 8]) -> Self {
+        Self {
+            initial_sp: u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            reset_vector: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        }
+    }
+
+    /// Check what can be checked without the target bank's geometry: the
+    /// initial SP lies in RP2040 SRAM (the stack grows down, so the end
+    /// itself is allowed) and the reset vector is a Thumb address. Catches
+    /// files that are not firmware binaries at all, such as an ELF.
+    pub fn check_plausible(&self) -> Result<(), VectorTableError> {
+        match self.initial_sp {
+            0xFFFF_FFFF => return Err(VectorTableError::Erased),
+            0 => return Err(VectorTableError::Zeroed),
+            _ => {}
+        }
+        if self.initial_sp <= RP2040_SRAM_START || self.initial_sp > RP2040_SRAM_END {
+            return Err(VectorTableError::StackOutOfRange);
+        }
+        if self.reset_vector & 1 == 0 {
+            return Err(VectorTableError::NotThumb);
+        }
+        Ok(())
+    }
+
+    /// Check the table and return how the image must be executed.
+    ///
+    /// RAM images must have their reset handler inside the copied region and
+    /// their stack at least `min_stack_headroom` bytes above it. XIP images
+    /// must have their reset handler inside `bank_addr..bank_addr + bank_size`,
+    /// so an image linked for the other bank is rejected.
+    pub fn validate(&self, regions: &ImageRegions) -> Result<ExecMode, VectorTableError> {
+        match self.initial_sp {
+            0xFFFF_FFFF => return Err(VectorTableError::Erased),
+            0 => return Err(VectorTableError::Zeroed),
+            _ => {}
+        }
+        if self.reset_vector & 1 == 0 {
+            return Err(VectorTableError::NotThumb);
+        }
+        if !self.initial_sp.is_multiple_of(8) {
+            return Err(VectorTableError::StackMisaligned);
+        }
+
+        let reset = self.reset_vector & !1;
+        let copy_end = regions.ram_base.saturating_add(regions.copy_size);
+        let bank_end = regions.bank_addr.saturating_add(regions.bank_size);
+
+        let (mode, stack_floor) = if (regions.ram_base..copy_end).contains(&reset) {
+            (ExecMode::Ram, copy_end)
+        } else if (regions.bank_addr..bank_end).contains(&reset) {
+            (ExecMode::Xip, regions.ram_base)
+        } else {
+            return Err(VectorTableError::ResetOutOfRange);
+        };
+
+        // The stack grows down from the initial SP, so it must be strictly
+        // above the lowest usable address.
+        if self.initial_sp <= stack_floor || self.initial_sp > regions.ram_end {
+            return Err(VectorTableError::StackOutOfRange);
+        }
+        if mode == ExecMode::Ram && self.initial_sp - stack_floor < regions.min_stack_headroom {
+            return Err(VectorTableError::StackHeadroom);
+        }
+
+        Ok(mode)
+    }
+}
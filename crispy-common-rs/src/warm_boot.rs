@@ -0,0 +1,66 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Warm-boot marker: lets the bootloader skip copying a RAM image that a
+//! previous boot already loaded.
+//!
+//! After copying an image the bootloader records which image it was and the
+//! CRC of the copy at `WARM_BOOT_MARKER_ADDR`. On a later reset that kept RAM
+//! powered it boots the copy in place if the marker names the same image and
+//! the RAM still checks out. Erasing a firmware bank clears the marker.
+
+use crate::crc32::crc32;
+
+/// `WarmBootMarker::magic` of a marker written by the bootloader.
+pub const WARM_BOOT_MAGIC: u32 = 0x3A2B_007E;
+
+/// Record left at `WARM_BOOT_MARKER_ADDR` after a RAM image was copied.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WarmBootMarker {
+    pub magic: u32,
+    /// `image_key` of the image copied.
+    pub image_key: u32,
+    /// CRC-32 of the copy, as many bytes as were copied.
+    pub ram_crc: u32,
+}
+
+impl WarmBootMarker {
+    pub const fn new(image_key: u32, ram_crc: u32) -> Self {
+        Self {
+            magic: WARM_BOOT_MAGIC,
+            image_key,
+            ram_crc,
+        }
+    }
+
+    /// A cleared marker, which matches no image.
+    pub const fn cleared() -> Self {
+        Self {
+            magic: 0,
+            image_key: 0,
+            ram_crc: 0,
+        }
+    }
+
+    /// Whether the marker was written after copying the image `image_key`
+    /// identifies. RAM contents are undefined after power-on, so a match
+    /// still needs the RAM CRC checked.
+    pub fn matches(&self, image_key: u32) -> bool {
+        self.magic == WARM_BOOT_MAGIC && self.image_key == image_key
+    }
+}
+
+/// Identify the image in `bank` by the version, CRC and size BootData
+/// records for it.
+///
+/// A re-upload of the same bytes to the same bank yields the same key; that
+/// is harmless because the RAM copy would be identical.
+pub fn image_key(bank: u8, version: u32, crc: u32, size: u32) -> u32 {
+    let mut bytes = [0u8; 13];
+    bytes[0] = bank;
+    bytes[1..5].copy_from_slice(&version.to_le_bytes());
+    bytes[5..9].copy_from_slice(&crc.to_le_bytes());
+    bytes[9..13].copy_from_slice(&size.to_le_bytes());
+    crc32(&bytes)
+}
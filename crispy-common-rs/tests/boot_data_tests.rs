@@ -3,7 +3,15 @@
 
 //! Unit tests for BootData structure and methods.
 
-use crispy_common::protocol::{BootData, BOOT_DATA_MAGIC, FW_A_ADDR, FW_B_ADDR};
+use crispy_common::protocol::{
+    pack_semver, BootData, BootInfo, BootTimings, DataInfo, GoldenInfo, BOOT_DATA_LAYOUT_V1,
+    BOOT_DATA_LAYOUT_V2, BOOT_DATA_LAYOUT_V3, BOOT_DATA_LAYOUT_VERSION, BOOT_DATA_MAGIC,
+    BOOT_DATA_SIZE, BOOT_FLAG_ANTI_ROLLBACK, BOOT_FLAG_BOOTLOADER_STAGED, BOOT_FLAG_FALLBACK,
+    BOOT_FLAG_GOLDEN, BOOT_FLAG_LOCKED_A, BOOT_FLAG_LOCKED_B, BOOT_INFO_ADDR, BOOT_INFO_MAGIC,
+    DATA_INFO_MAGIC, DATA_MAX_IMAGE_SIZE, DEFAULT_MAX_BOOT_ATTEMPTS, FW_A_ADDR, FW_BANK_SIZE,
+    FW_B_ADDR, GOLDEN_BANK, GOLDEN_INFO_MAGIC, GOLDEN_MAX_IMAGE_SIZE, MAX_BOOT_ATTEMPTS_UNSET,
+    RAM_UPDATE_FLAG_ADDR,
+};
 
 #[test]
 fn test_boot_data_default_new() {
@@ -13,6 +21,7 @@ fn test_boot_data_default_new() {
     assert_eq!(bd.active_bank, 0);
     assert_eq!(bd.confirmed, 0);
     assert_eq!(bd.boot_attempts, 0);
+    assert_eq!(bd.flags, 0);
     assert_eq!(bd.version_a, 0);
     assert_eq!(bd.version_b, 0);
     assert_eq!(bd.crc_a, 0);
@@ -33,6 +42,127 @@ fn test_boot_data_is_valid() {
     assert!(!bd.is_valid());
 }
 
+// --- Validation of raw records ---
+
+/// Raw record with both banks populated, as the bootloader writes it.
+fn record_bytes() -> [u8; BOOT_DATA_SIZE] {
+    let mut bd = BootData::default_new();
+    bd.active_bank = 1;
+    bd.confirmed = 1;
+    bd.crc_a = 0x1234_5678;
+    bd.size_a = 4096;
+    bd.crc_b = 0x9ABC_DEF0;
+    bd.size_b = 200_000;
+    bd.as_bytes().try_into().unwrap()
+}
+
+/// Overwrite the little-endian word at `offset`.
+fn set_word(bytes: &mut [u8; BOOT_DATA_SIZE], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+#[test]
+fn test_boot_data_from_bytes_round_trip() {
+    let bytes = record_bytes();
+    let bd = BootData::from_bytes(&bytes);
+
+    assert!(bd.is_valid());
+    assert_eq!(bd.active_bank, 1);
+    assert_eq!(bd.size_b, 200_000);
+    assert_eq!(bd.as_bytes(), bytes);
+}
+
+#[test]
+fn test_erased_sector_is_invalid() {
+    let bd = BootData::from_bytes(&[0xFF; BOOT_DATA_SIZE]);
+    assert!(!bd.is_valid());
+}
+
+#[test]
+fn test_zeroed_record_is_invalid() {
+    assert!(!BootData::from_bytes(&[0; BOOT_DATA_SIZE]).is_valid());
+}
+
+#[test]
+fn test_erased_record_with_magic_is_invalid() {
+    // A record whose write stopped after the first word.
+    let mut bytes = [0xFF; BOOT_DATA_SIZE];
+    set_word(&mut bytes, 0, BOOT_DATA_MAGIC);
+    assert!(!BootData::from_bytes(&bytes).is_valid());
+}
+
+#[test]
+fn test_bank_index_above_b_is_invalid() {
+    let mut bytes = record_bytes();
+    bytes[4] = 2;
+    assert!(!BootData::from_bytes(&bytes).is_valid());
+
+    bytes[4] = 0;
+    assert!(BootData::from_bytes(&bytes).is_valid());
+}
+
+#[test]
+fn test_size_beyond_bank_is_invalid() {
+    let mut bytes = record_bytes();
+    set_word(&mut bytes, 24, FW_BANK_SIZE);
+    assert!(BootData::from_bytes(&bytes).is_valid());
+
+    set_word(&mut bytes, 24, FW_BANK_SIZE + 1);
+    assert!(!BootData::from_bytes(&bytes).is_valid());
+
+    let mut bytes = record_bytes();
+    set_word(&mut bytes, 28, u32::MAX);
+    assert!(!BootData::from_bytes(&bytes).is_valid());
+}
+
+#[test]
+fn test_crc_without_image_is_invalid() {
+    let mut bytes = record_bytes();
+    set_word(&mut bytes, 24, 0);
+    assert!(!BootData::from_bytes(&bytes).is_valid());
+
+    set_word(&mut bytes, 16, 0);
+    assert!(BootData::from_bytes(&bytes).is_valid());
+
+    let mut bytes = record_bytes();
+    set_word(&mut bytes, 28, 0);
+    assert!(!BootData::from_bytes(&bytes).is_valid());
+}
+
+#[test]
+fn test_image_with_zero_crc_is_valid() {
+    let mut bytes = record_bytes();
+    set_word(&mut bytes, 16, 0);
+    assert!(BootData::from_bytes(&bytes).is_valid());
+}
+
+#[test]
+fn test_record_predating_min_version_is_valid() {
+    let mut bytes = record_bytes();
+    set_word(&mut bytes, 32, 0xFFFF_FFFF);
+    assert!(BootData::from_bytes(&bytes).is_valid());
+}
+
+#[test]
+fn test_boot_data_fell_back_flag() {
+    let mut bd = BootData::default_new();
+    assert!(!bd.fell_back());
+
+    bd.flags |= BOOT_FLAG_FALLBACK;
+    assert!(bd.fell_back());
+
+    bd.flags &= !BOOT_FLAG_FALLBACK;
+    assert!(!bd.fell_back());
+}
+
+#[test]
+fn test_boot_data_flags_byte_offset() {
+    let mut bd = BootData::default_new();
+    bd.flags = BOOT_FLAG_FALLBACK;
+
+    assert_eq!(bd.as_bytes()[7], BOOT_FLAG_FALLBACK);
+}
+
 #[test]
 fn test_boot_data_bank_addr_bank_a() {
     let mut bd = BootData::default_new();
@@ -54,7 +184,7 @@ fn test_boot_data_as_bytes_length() {
     let bd = BootData::default_new();
     let bytes = bd.as_bytes();
 
-    assert_eq!(bytes.len(), 32);
+    assert_eq!(bytes.len(), 40);
 }
 
 #[test]
@@ -68,6 +198,444 @@ fn test_boot_data_as_bytes_magic() {
 }
 
 #[test]
-fn test_boot_data_size_is_32_bytes() {
-    assert_eq!(std::mem::size_of::<BootData>(), 32);
+fn test_boot_data_layout_sizes() {
+    assert_eq!(BootData::layout_size(BOOT_DATA_LAYOUT_V1), Some(32));
+    assert_eq!(BootData::layout_size(BOOT_DATA_LAYOUT_V2), Some(36));
+    assert_eq!(BootData::layout_size(BOOT_DATA_LAYOUT_V3), Some(40));
+    assert_eq!(BootData::layout_size(0), None);
+    assert_eq!(BootData::layout_size(BOOT_DATA_LAYOUT_VERSION + 1), None);
+}
+
+#[test]
+fn test_current_layout_fills_the_struct() {
+    assert_eq!(
+        BootData::layout_size(BOOT_DATA_LAYOUT_VERSION),
+        Some(std::mem::size_of::<BootData>())
+    );
+    assert_eq!(std::mem::size_of::<BootData>(), BOOT_DATA_SIZE);
+}
+
+// --- Layout versions ---
+
+/// `record_bytes` cut to `layout` and padded with erased flash, as a
+/// bootloader of that layout left it.
+fn legacy_bytes(layout: u8) -> [u8; BOOT_DATA_SIZE] {
+    let mut bytes = record_bytes();
+    bytes[BootData::layout_size(layout).unwrap()..].fill(0xFF);
+    bytes
+}
+
+#[test]
+fn test_new_records_carry_the_current_layout() {
+    let bd = BootData::default_new();
+    assert_eq!(bd.layout_version, BOOT_DATA_LAYOUT_VERSION);
+    assert_eq!(bd.as_bytes()[37], BOOT_DATA_LAYOUT_VERSION);
+    assert_eq!(
+        BootData::stored_layout(&record_bytes()),
+        BOOT_DATA_LAYOUT_VERSION
+    );
+}
+
+#[test]
+fn test_stored_layout_of_records_predating_the_version_byte() {
+    assert_eq!(
+        BootData::stored_layout(&legacy_bytes(BOOT_DATA_LAYOUT_V1)),
+        BOOT_DATA_LAYOUT_V1
+    );
+    assert_eq!(
+        BootData::stored_layout(&legacy_bytes(BOOT_DATA_LAYOUT_V2)),
+        BOOT_DATA_LAYOUT_V2
+    );
+    // A 40-byte record whose version byte was still reserved, written as 0.
+    let mut bytes = record_bytes();
+    bytes[37] = 0;
+    assert_eq!(BootData::stored_layout(&bytes), BOOT_DATA_LAYOUT_V3);
+}
+
+#[test]
+fn test_v1_record_migrates_field_by_field() {
+    let mut bytes = [0xFF; BOOT_DATA_SIZE];
+    set_word(&mut bytes, 0, BOOT_DATA_MAGIC);
+    bytes[4..8].copy_from_slice(&[1, 1, 2, BOOT_FLAG_FALLBACK]);
+    set_word(&mut bytes, 8, pack_semver(1, 0, 0).unwrap());
+    set_word(&mut bytes, 12, pack_semver(1, 1, 0).unwrap());
+    set_word(&mut bytes, 16, 0x1234_5678);
+    set_word(&mut bytes, 20, 0x9ABC_DEF0);
+    set_word(&mut bytes, 24, 4096);
+    set_word(&mut bytes, 28, 8192);
+
+    let bd = BootData::from_bytes(&bytes);
+    assert!(bd.is_valid());
+    assert_eq!(bd.magic, BOOT_DATA_MAGIC);
+    assert_eq!(bd.active_bank, 1);
+    assert_eq!(bd.confirmed, 1);
+    assert_eq!(bd.boot_attempts, 2);
+    assert_eq!(bd.flags, BOOT_FLAG_FALLBACK);
+    assert_eq!(bd.version_a, pack_semver(1, 0, 0).unwrap());
+    assert_eq!(bd.version_b, pack_semver(1, 1, 0).unwrap());
+    assert_eq!(bd.crc_a, 0x1234_5678);
+    assert_eq!(bd.crc_b, 0x9ABC_DEF0);
+    assert_eq!(bd.size_a, 4096);
+    assert_eq!(bd.size_b, 8192);
+    assert_eq!(bd.min_version, 0);
+    assert_eq!(bd.max_boot_attempts, MAX_BOOT_ATTEMPTS_UNSET);
+    assert_eq!(bd.layout_version, BOOT_DATA_LAYOUT_VERSION);
+    assert_eq!(bd._reserved, [0; 2]);
+    assert_eq!(bd.rollback_floor(), None);
+    assert_eq!(bd.boot_attempt_limit(), DEFAULT_MAX_BOOT_ATTEMPTS);
+
+    // The next write stores the migrated record, which reads back unchanged.
+    let rewritten: [u8; BOOT_DATA_SIZE] = bd.as_bytes().try_into().unwrap();
+    assert_eq!(
+        BootData::stored_layout(&rewritten),
+        BOOT_DATA_LAYOUT_VERSION
+    );
+    assert_eq!(BootData::from_bytes(&rewritten), bd);
+}
+
+#[test]
+fn test_v1_record_with_anti_rollback_keeps_its_floor() {
+    let mut bytes = legacy_bytes(BOOT_DATA_LAYOUT_V1);
+    bytes[7] = BOOT_FLAG_ANTI_ROLLBACK;
+    set_word(&mut bytes, 8, pack_semver(2, 0, 0).unwrap());
+    set_word(&mut bytes, 12, pack_semver(1, 9, 0).unwrap());
+
+    let bd = BootData::from_bytes(&bytes);
+    assert_eq!(bd.min_version, pack_semver(2, 0, 0).unwrap());
+    assert_eq!(bd.rollback_floor(), Some(pack_semver(2, 0, 0).unwrap()));
+}
+
+#[test]
+fn test_v2_record_keeps_min_version() {
+    let mut bytes = record_bytes();
+    set_word(&mut bytes, 32, pack_semver(1, 2, 3).unwrap());
+    bytes[36..].fill(0xFF);
+
+    let bd = BootData::from_bytes(&bytes);
+    assert_eq!(bd.min_version, pack_semver(1, 2, 3).unwrap());
+    assert_eq!(bd.max_boot_attempts, MAX_BOOT_ATTEMPTS_UNSET);
+    assert_eq!(bd.layout_version, BOOT_DATA_LAYOUT_VERSION);
+}
+
+#[test]
+fn test_unversioned_v3_record_keeps_its_policy() {
+    let mut bytes = record_bytes();
+    bytes[36] = 5;
+    bytes[37] = 0;
+
+    let bd = BootData::from_bytes(&bytes);
+    assert_eq!(bd.max_boot_attempts, 5);
+    assert_eq!(bd.layout_version, BOOT_DATA_LAYOUT_VERSION);
+}
+
+#[test]
+fn test_newer_layout_keeps_known_fields() {
+    let mut bytes = record_bytes();
+    bytes[36] = 7;
+    bytes[37] = BOOT_DATA_LAYOUT_VERSION + 1;
+
+    let bd = BootData::from_bytes(&bytes);
+    assert!(bd.is_valid());
+    assert_eq!(bd.max_boot_attempts, 7);
+    assert_eq!(bd.size_b, 200_000);
+    assert_eq!(bd.layout_version, BOOT_DATA_LAYOUT_VERSION);
+}
+
+// --- BootInfo mailbox ---
+
+#[test]
+fn test_boot_info_size() {
+    assert_eq!(std::mem::size_of::<BootInfo>(), 32);
+}
+
+#[test]
+fn test_boot_info_mailbox_below_update_flag() {
+    assert_eq!(BOOT_INFO_ADDR + 32, RAM_UPDATE_FLAG_ADDR);
+    assert!(BOOT_INFO_ADDR.is_multiple_of(8));
+}
+
+#[test]
+fn test_boot_info_from_boot_data() {
+    let mut bd = BootData::default_new();
+    bd.active_bank = 1;
+    bd.boot_attempts = 2;
+    bd.flags = BOOT_FLAG_FALLBACK;
+    let version = pack_semver(1, 4, 2).unwrap();
+
+    let info = BootInfo::new(&bd, version);
+    assert!(info.is_valid());
+    assert_eq!(info.magic, BOOT_INFO_MAGIC);
+    assert_eq!(info.active_bank, 1);
+    assert!(info.is_trial());
+    assert_eq!(info.boot_attempts, 2);
+    assert!(info.fell_back());
+    assert_eq!(info.bootloader_version().to_string(), "1.4.2");
+}
+
+#[test]
+fn test_boot_info_timings_absent_until_recorded() {
+    let mut info = BootInfo::new(&BootData::default_new(), 0);
+    assert_eq!(info.timings(), None);
+
+    info.timings = BootTimings {
+        peripherals_ready: 1_200,
+        trigger_decided: 1_350,
+        image_checked: 21_000,
+        jump: 34_500,
+    };
+    assert_eq!(info.timings(), Some(info.timings));
+}
+
+#[test]
+fn test_boot_info_timings_layout() {
+    assert_eq!(std::mem::size_of::<BootTimings>(), 16);
+    assert_eq!(std::mem::offset_of!(BootInfo, timings), 12);
+}
+
+#[test]
+fn test_boot_info_confirmed_is_not_trial() {
+    let mut bd = BootData::default_new();
+    bd.confirmed = 1;
+    assert!(!BootInfo::new(&bd, 0).is_trial());
+}
+
+#[test]
+fn test_boot_info_rejects_bad_magic_or_ranges() {
+    let bd = BootData::default_new();
+
+    let mut info = BootInfo::new(&bd, 0);
+    info.magic = 0;
+    assert!(!info.is_valid());
+
+    let mut info = BootInfo::new(&bd, 0);
+    info.active_bank = GOLDEN_BANK + 1;
+    assert!(!info.is_valid());
+
+    let mut info = BootInfo::new(&bd, 0);
+    info.confirmed = 0xFF;
+    assert!(!info.is_valid());
+}
+
+#[test]
+fn test_boot_info_golden_bank() {
+    let mut info = BootInfo::new(&BootData::default_new(), 0);
+    assert!(!info.is_golden());
+
+    info.active_bank = GOLDEN_BANK;
+    assert!(info.is_valid());
+    assert!(info.is_golden());
+}
+
+#[test]
+fn test_golden_flag_independent_of_fallback() {
+    let mut bd = BootData::default_new();
+    assert!(!bd.golden_populated());
+
+    bd.flags = BOOT_FLAG_GOLDEN;
+    assert!(bd.golden_populated());
+    assert!(!bd.fell_back());
+}
+
+#[test]
+fn test_boot_flags_distinct() {
+    assert_eq!(BOOT_FLAG_FALLBACK & BOOT_FLAG_GOLDEN, 0);
+    assert_eq!(BOOT_FLAG_FALLBACK & BOOT_FLAG_BOOTLOADER_STAGED, 0);
+    assert_eq!(BOOT_FLAG_GOLDEN & BOOT_FLAG_BOOTLOADER_STAGED, 0);
+    for flag in [
+        BOOT_FLAG_FALLBACK,
+        BOOT_FLAG_GOLDEN,
+        BOOT_FLAG_BOOTLOADER_STAGED,
+    ] {
+        assert_eq!(flag & BOOT_FLAG_ANTI_ROLLBACK, 0);
+    }
+    for flag in [
+        BOOT_FLAG_FALLBACK,
+        BOOT_FLAG_GOLDEN,
+        BOOT_FLAG_BOOTLOADER_STAGED,
+        BOOT_FLAG_ANTI_ROLLBACK,
+    ] {
+        assert_eq!(flag & (BOOT_FLAG_LOCKED_A | BOOT_FLAG_LOCKED_B), 0);
+    }
+    assert_eq!(BOOT_FLAG_LOCKED_A & BOOT_FLAG_LOCKED_B, 0);
+}
+
+// --- Bank locks ---
+
+#[test]
+fn test_bank_lock_set_and_clear() {
+    let mut bd = BootData::default_new();
+    assert!(!bd.any_bank_locked());
+
+    bd.set_bank_locked(0, true);
+    assert!(bd.bank_locked(0));
+    assert!(!bd.bank_locked(1));
+    assert!(bd.any_bank_locked());
+    assert_eq!(bd.flags, BOOT_FLAG_LOCKED_A);
+
+    bd.set_bank_locked(1, true);
+    bd.set_bank_locked(0, false);
+    assert!(!bd.bank_locked(0));
+    assert!(bd.bank_locked(1));
+    assert_eq!(bd.flags, BOOT_FLAG_LOCKED_B);
+}
+
+#[test]
+fn test_bank_lock_keeps_other_flags() {
+    let mut bd = BootData::default_new();
+    bd.flags = BOOT_FLAG_GOLDEN | BOOT_FLAG_ANTI_ROLLBACK;
+
+    bd.set_bank_locked(1, true);
+    bd.set_bank_locked(1, false);
+    assert_eq!(bd.flags, BOOT_FLAG_GOLDEN | BOOT_FLAG_ANTI_ROLLBACK);
+}
+
+#[test]
+fn test_bank_lock_ignores_other_banks() {
+    let mut bd = BootData::default_new();
+    bd.set_bank_locked(GOLDEN_BANK, true);
+
+    assert_eq!(bd.flags, 0);
+    assert!(!bd.bank_locked(GOLDEN_BANK));
+    assert_eq!(BootData::lock_flag(7), 0);
+}
+
+// --- Anti-rollback ---
+
+#[test]
+fn test_anti_rollback_off_allows_any_version() {
+    let mut bd = BootData::default_new();
+    bd.min_version = pack_semver(2, 0, 0).unwrap();
+
+    assert!(!bd.anti_rollback());
+    assert_eq!(bd.rollback_floor(), None);
+    assert!(bd.allows_version(0));
+}
+
+#[test]
+fn test_anti_rollback_refuses_older_versions() {
+    let mut bd = BootData::default_new();
+    bd.flags = BOOT_FLAG_ANTI_ROLLBACK;
+    bd.min_version = pack_semver(1, 2, 0).unwrap();
+
+    assert_eq!(bd.rollback_floor(), Some(pack_semver(1, 2, 0).unwrap()));
+    assert!(!bd.allows_version(pack_semver(1, 1, 9).unwrap()));
+    assert!(bd.allows_version(pack_semver(1, 2, 0).unwrap()));
+    assert!(bd.allows_version(pack_semver(1, 3, 0).unwrap()));
+}
+
+#[test]
+fn test_anti_rollback_erased_floor_uses_newest_bank() {
+    // A record rewritten by code predating `min_version` has it erased.
+    let mut bd = BootData::default_new();
+    bd.flags = BOOT_FLAG_ANTI_ROLLBACK;
+    bd.min_version = 0xFFFF_FFFF;
+    bd.version_a = pack_semver(1, 4, 0).unwrap();
+    bd.version_b = pack_semver(1, 5, 0).unwrap();
+
+    assert_eq!(bd.rollback_floor(), Some(bd.version_b));
+    assert!(!bd.allows_version(bd.version_a));
+}
+
+#[test]
+fn test_boot_data_min_version_after_bank_sizes() {
+    let bd = BootData::default_new();
+    let bytes = bd.as_bytes();
+    assert_eq!(&bytes[32..36], &0u32.to_le_bytes());
+}
+
+// --- Boot policy ---
+
+#[test]
+fn test_boot_policy_unset_uses_default() {
+    let mut bd = BootData::default_new();
+    assert_eq!(bd.max_boot_attempts, MAX_BOOT_ATTEMPTS_UNSET);
+    assert_eq!(bd.boot_attempt_limit(), DEFAULT_MAX_BOOT_ATTEMPTS);
+
+    bd.max_boot_attempts = 0;
+    assert_eq!(bd.boot_attempt_limit(), DEFAULT_MAX_BOOT_ATTEMPTS);
+}
+
+#[test]
+fn test_boot_policy_overrides_default() {
+    let mut bd = BootData::default_new();
+    bd.max_boot_attempts = 1;
+    assert_eq!(bd.boot_attempt_limit(), 1);
+
+    bd.max_boot_attempts = 254;
+    assert_eq!(bd.boot_attempt_limit(), 254);
+}
+
+#[test]
+fn test_record_predating_boot_policy_uses_default() {
+    // The page is padded with erased flash after a 36-byte record.
+    let mut bytes = record_bytes();
+    bytes[36..].fill(0xFF);
+    let bd = BootData::from_bytes(&bytes);
+
+    assert!(bd.is_valid());
+    assert_eq!(bd.boot_attempt_limit(), DEFAULT_MAX_BOOT_ATTEMPTS);
+}
+
+#[test]
+fn test_boot_data_max_boot_attempts_after_min_version() {
+    let mut bd = BootData::default_new();
+    bd.max_boot_attempts = 5;
+    let bytes = bd.as_bytes();
+    assert_eq!(bytes.len(), BOOT_DATA_SIZE);
+    assert_eq!(bytes[36], 5);
+    assert_eq!(BootData::from_bytes(bytes.try_into().unwrap()), bd);
+}
+
+#[test]
+fn test_golden_info_size() {
+    assert_eq!(std::mem::size_of::<GoldenInfo>(), 16);
+}
+
+#[test]
+fn test_golden_info_new_is_valid() {
+    let info = GoldenInfo::new(4096, 0xDEAD_BEEF, pack_semver(1, 0, 0).unwrap());
+    assert!(info.is_valid());
+    assert_eq!(info.magic, GOLDEN_INFO_MAGIC);
+    assert_eq!(info.as_bytes().len(), 16);
+}
+
+#[test]
+fn test_golden_info_erased_or_bad_size_invalid() {
+    let erased = GoldenInfo {
+        magic: 0xFFFF_FFFF,
+        size: 0xFFFF_FFFF,
+        crc32: 0xFFFF_FFFF,
+        version: 0xFFFF_FFFF,
+    };
+    assert!(!erased.is_valid());
+    assert!(!GoldenInfo::new(0, 0, 0).is_valid());
+    assert!(!GoldenInfo::new(GOLDEN_MAX_IMAGE_SIZE + 1, 0, 0).is_valid());
+}
+
+#[test]
+fn test_data_info_round_trip() {
+    let info = DataInfo::new(200 * 1024, 0xCAFE_F00D, pack_semver(2, 1, 0).unwrap());
+    assert!(info.is_valid());
+    assert_eq!(info.magic, DATA_INFO_MAGIC);
+    assert_eq!(info.as_bytes().len(), 16);
+}
+
+#[test]
+fn test_data_info_erased_bad_size_or_golden_magic_invalid() {
+    let erased = DataInfo {
+        magic: 0xFFFF_FFFF,
+        size: 0xFFFF_FFFF,
+        crc32: 0xFFFF_FFFF,
+        version: 0xFFFF_FFFF,
+    };
+    assert!(!erased.is_valid());
+    assert!(!DataInfo::new(0, 0, 0).is_valid());
+    assert!(!DataInfo::new(DATA_MAX_IMAGE_SIZE + 1, 0, 0).is_valid());
+
+    let golden = GoldenInfo::new(4096, 0, 0);
+    let as_data = DataInfo {
+        magic: golden.magic,
+        ..DataInfo::new(4096, 0, 0)
+    };
+    assert!(!as_data.is_valid());
 }
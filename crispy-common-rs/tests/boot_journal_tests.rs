@@ -0,0 +1,329 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Unit tests for the BootData journal, against an in-memory flash sector.
+
+use crispy_common::boot_journal::{
+    self, Sector, Source, WritePlan, RECORDS_PER_SECTOR, RECORD_SIZE,
+};
+use crispy_common::protocol::{BootData, FLASH_PAGE_SIZE, FLASH_SECTOR_SIZE};
+
+/// One flash sector: erasing sets every bit, programming can only clear bits.
+struct FlashSector {
+    bytes: Box<Sector>,
+    erases: u32,
+}
+
+impl FlashSector {
+    fn erased() -> Self {
+        Self {
+            bytes: Box::new([0xFF; FLASH_SECTOR_SIZE as usize]),
+            erases: 0,
+        }
+    }
+
+    fn erase(&mut self) {
+        self.bytes.fill(0xFF);
+        self.erases += 1;
+    }
+
+    fn program(&mut self, offset: u32, data: &[u8]) {
+        assert_eq!(offset % FLASH_PAGE_SIZE, 0, "unaligned program");
+        let start = offset as usize;
+        for (byte, new) in self.bytes[start..start + data.len()].iter_mut().zip(data) {
+            *byte &= new;
+        }
+    }
+
+    /// What `flash::write_boot_data` does.
+    fn write(&mut self, bd: &BootData) -> WritePlan {
+        let plan = boot_journal::plan_write(&self.bytes);
+        if plan.erase {
+            self.erase();
+        }
+        let (offset, page) = plan.page(bd);
+        self.program(offset, &page);
+        plan
+    }
+
+    fn read(&self) -> BootData {
+        boot_journal::read(&self.bytes)
+    }
+}
+
+fn boot_data(active_bank: u8, boot_attempts: u8) -> BootData {
+    let mut bd = BootData::default_new();
+    bd.active_bank = active_bank;
+    bd.boot_attempts = boot_attempts;
+    bd.size_a = 4096;
+    bd.crc_a = 0x1234_5678;
+    bd
+}
+
+#[test]
+fn test_records_fit_pages() {
+    assert_eq!(FLASH_PAGE_SIZE % RECORD_SIZE, 0);
+    assert_eq!(RECORDS_PER_SECTOR, 64);
+}
+
+#[test]
+fn test_record_round_trip() {
+    let bd = boot_data(1, 2);
+    let record = boot_journal::encode(&bd, 42);
+
+    assert_eq!(boot_journal::decode(&record), Some((42, bd)));
+}
+
+#[test]
+fn test_corrupt_or_erased_record_is_rejected() {
+    let mut record = boot_journal::encode(&boot_data(0, 0), 1);
+    record[5] ^= 0x01;
+
+    assert_eq!(boot_journal::decode(&record), None);
+    assert_eq!(boot_journal::decode(&[0xFF; RECORD_SIZE as usize]), None);
+}
+
+#[test]
+fn test_erased_sector_reads_invalid() {
+    let flash = FlashSector::erased();
+
+    assert!(!flash.read().is_valid());
+    assert_eq!(boot_journal::newest(&flash.bytes), None);
+    assert_eq!(boot_journal::used_bytes(&flash.bytes), 0);
+}
+
+#[test]
+fn test_writes_append_without_erasing() {
+    let mut flash = FlashSector::erased();
+
+    for attempts in 0..10 {
+        let plan = flash.write(&boot_data(0, attempts));
+        assert_eq!(plan.slot, u32::from(attempts));
+        assert_eq!(flash.read().boot_attempts, attempts);
+    }
+
+    assert_eq!(flash.erases, 0);
+    assert_eq!(boot_journal::used_bytes(&flash.bytes), 10 * RECORD_SIZE);
+    assert_eq!(boot_journal::newest(&flash.bytes).unwrap().seq, 10);
+}
+
+#[test]
+fn test_full_sector_is_erased_once_per_round() {
+    let mut flash = FlashSector::erased();
+    let writes = 3 * RECORDS_PER_SECTOR + 5;
+
+    for i in 0..writes {
+        flash.write(&boot_data((i % 2) as u8, (i % 200) as u8));
+        let bd = flash.read();
+        assert_eq!(bd.active_bank, (i % 2) as u8);
+        assert_eq!(bd.boot_attempts, (i % 200) as u8);
+    }
+
+    assert_eq!(flash.erases, 3);
+    // Sequence numbers keep counting across erases.
+    assert_eq!(boot_journal::newest(&flash.bytes).unwrap().seq, writes);
+}
+
+#[test]
+fn test_torn_write_keeps_previous_record() {
+    let mut flash = FlashSector::erased();
+    flash.write(&boot_data(0, 1));
+
+    // Power lost after the first half of the next record was programmed.
+    let plan = boot_journal::plan_write(&flash.bytes);
+    let (offset, mut page) = plan.page(&boot_data(1, 0));
+    let record_start = (plan.slot * RECORD_SIZE - offset) as usize;
+    page[record_start + 24..].fill(0xFF);
+    flash.program(offset, &page);
+
+    let bd = flash.read();
+    assert_eq!(bd.active_bank, 0);
+    assert_eq!(bd.boot_attempts, 1);
+
+    // The torn slot is skipped by the next write.
+    let next = flash.write(&boot_data(1, 0));
+    assert_eq!(next.slot, plan.slot + 1);
+    assert_eq!(flash.read().active_bank, 1);
+}
+
+#[test]
+fn test_highest_sequence_wins_over_slot_order() {
+    let mut flash = FlashSector::erased();
+    let newer = WritePlan {
+        erase: false,
+        slot: 0,
+        seq: 9,
+    };
+    let older = WritePlan {
+        erase: false,
+        slot: 1,
+        seq: 8,
+    };
+    let (offset, page) = newer.page(&boot_data(1, 0));
+    flash.program(offset, &page);
+    let (offset, page) = older.page(&boot_data(0, 0));
+    flash.program(offset, &page);
+
+    assert_eq!(flash.read().active_bank, 1);
+    assert_eq!(boot_journal::plan_write(&flash.bytes).seq, 10);
+}
+
+#[test]
+fn test_pre_journal_record_is_read_and_kept_until_first_write() {
+    // A bare 36-byte BootData padded with erased flash, as older bootloaders
+    // and firmware wrote it.
+    let mut flash = FlashSector::erased();
+    let legacy = boot_data(1, 2);
+    let mut page = [0xFF; FLASH_PAGE_SIZE as usize];
+    page[..36].copy_from_slice(&legacy.as_bytes()[..36]);
+    flash.program(0, &page);
+
+    let bd = flash.read();
+    assert!(bd.is_valid());
+    assert_eq!(bd.active_bank, 1);
+    assert_eq!(bd.boot_attempts, 2);
+
+    let plan = flash.write(&boot_data(0, 0));
+    assert_eq!(plan.slot, 1);
+    assert_eq!(plan.seq, 1);
+    assert_eq!(flash.read().active_bank, 0);
+}
+
+#[test]
+fn test_page_leaves_other_slots_erased() {
+    let plan = WritePlan {
+        erase: false,
+        slot: 6,
+        seq: 3,
+    };
+    let (offset, page) = plan.page(&boot_data(0, 0));
+    let in_page = (6 * RECORD_SIZE - offset) as usize;
+
+    assert_eq!(offset, 256);
+    assert!(page[..in_page].iter().all(|&b| b == 0xFF));
+    assert!(page[in_page + RECORD_SIZE as usize..]
+        .iter()
+        .all(|&b| b == 0xFF));
+    let record: [u8; RECORD_SIZE as usize] = page[in_page..in_page + RECORD_SIZE as usize]
+        .try_into()
+        .unwrap();
+    assert_eq!(boot_journal::decode(&record).unwrap().0, 3);
+}
+
+// --- Corruption ---
+
+/// Field name and byte range of every BootData field in a record.
+const FIELDS: [(&str, std::ops::Range<usize>); 14] = [
+    ("magic", 0..4),
+    ("active_bank", 4..5),
+    ("confirmed", 5..6),
+    ("boot_attempts", 6..7),
+    ("flags", 7..8),
+    ("version_a", 8..12),
+    ("version_b", 12..16),
+    ("crc_a", 16..20),
+    ("crc_b", 20..24),
+    ("size_a", 24..28),
+    ("size_b", 28..32),
+    ("min_version", 32..36),
+    ("max_boot_attempts", 36..37),
+    ("layout_version", 37..38),
+];
+
+#[test]
+fn test_single_bit_flip_in_any_field_is_detected() {
+    let record = boot_journal::encode(&boot_data(1, 2), 5);
+
+    for (name, range) in FIELDS {
+        for byte in range {
+            for bit in 0..8 {
+                let mut corrupt = record;
+                corrupt[byte] ^= 1 << bit;
+                assert_eq!(
+                    boot_journal::decode(&corrupt),
+                    None,
+                    "{} byte {} bit {}",
+                    name,
+                    byte,
+                    bit
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_corrupt_newest_record_falls_back_to_previous() {
+    let mut flash = FlashSector::erased();
+    flash.write(&boot_data(0, 0));
+    flash.write(&boot_data(1, 0));
+
+    // `size_a` of the newest record loses a bit.
+    flash.bytes[RECORD_SIZE as usize + 25] &= !0x10;
+
+    let bd = flash.read();
+    assert!(bd.is_valid());
+    assert_eq!(bd.active_bank, 0);
+    assert_eq!(bd.size_a, 4096);
+}
+
+#[test]
+fn test_corrupt_only_record_reads_invalid() {
+    let mut flash = FlashSector::erased();
+    flash.write(&boot_data(1, 0));
+
+    // `crc_a` loses a bit; the record would still pass `is_valid`.
+    flash.bytes[16] &= !0x08;
+
+    assert!(!flash.read().is_valid());
+}
+
+// --- Mirror ---
+
+#[test]
+fn test_select_prefers_a_valid_primary() {
+    let mut primary = FlashSector::erased();
+    let mut mirror = FlashSector::erased();
+    primary.write(&boot_data(1, 0));
+    mirror.write(&boot_data(0, 2));
+
+    assert_eq!(
+        boot_journal::select(&primary.bytes, &mirror.bytes),
+        Some((boot_data(1, 0), Source::Primary))
+    );
+}
+
+#[test]
+fn test_select_falls_back_to_the_mirror() {
+    let mut primary = FlashSector::erased();
+    let mut mirror = FlashSector::erased();
+    primary.write(&boot_data(1, 0));
+    mirror.write(&boot_data(1, 0));
+    primary.bytes[3] ^= 0x80;
+
+    assert_eq!(
+        boot_journal::select(&primary.bytes, &mirror.bytes),
+        Some((boot_data(1, 0), Source::Mirror))
+    );
+}
+
+#[test]
+fn test_select_finds_nothing_in_two_erased_sectors() {
+    let erased = FlashSector::erased();
+
+    assert_eq!(boot_journal::select(&erased.bytes, &erased.bytes), None);
+}
+
+#[test]
+fn test_mirror_without_a_valid_record_is_written_first() {
+    let mut mirror = FlashSector::erased();
+    assert!(boot_journal::mirror_first(&mirror.bytes));
+
+    // Whatever the sector held before the mirror existed is no record.
+    mirror.program(0, &[0x5A; FLASH_PAGE_SIZE as usize]);
+    assert!(boot_journal::mirror_first(&mirror.bytes));
+
+    mirror.erase();
+    mirror.write(&boot_data(0, 1));
+    assert!(!boot_journal::mirror_first(&mirror.bytes));
+}
@@ -0,0 +1,277 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Unit tests for boot bank selection.
+
+use crispy_common::boot_selection::{select_boot_action, BootAction, UpdateReason};
+use crispy_common::protocol::{BootData, BOOT_FLAG_FALLBACK};
+use crispy_common::reset_reason::ResetReason;
+
+const MAX_ATTEMPTS: u8 = 3;
+
+const ALL_REASONS: [ResetReason; 6] = [
+    ResetReason::PowerOn,
+    ResetReason::RunPin,
+    ResetReason::Debugger,
+    ResetReason::Watchdog,
+    ResetReason::WatchdogForced,
+    ResetReason::Software,
+];
+
+/// BootData with images in both banks.
+fn boot_data(active_bank: u8, confirmed: bool, boot_attempts: u8) -> BootData {
+    let mut bd = BootData::default_new();
+    bd.active_bank = active_bank;
+    bd.confirmed = u8::from(confirmed);
+    bd.boot_attempts = boot_attempts;
+    bd.size_a = 4096;
+    bd.size_b = 8192;
+    bd
+}
+
+/// Run selection with fixed bank validity; also returns the banks checked.
+fn select(bd: &BootData, reason: ResetReason, valid: [bool; 2]) -> (BootAction, Vec<u8>) {
+    let mut checked = Vec::new();
+    let action = select_boot_action(bd, reason, MAX_ATTEMPTS, |_, bank| {
+        checked.push(bank);
+        valid[usize::from(bank)]
+    });
+    (action, checked)
+}
+
+// --- No firmware ---
+
+#[test]
+fn test_empty_boot_data_stays_in_update_mode() {
+    let bd = BootData::default_new();
+    let (action, checked) = select(&bd, ResetReason::PowerOn, [true, true]);
+
+    assert_eq!(action, BootAction::UpdateMode(UpdateReason::NoFirmware));
+    assert!(checked.is_empty());
+}
+
+#[test]
+fn test_corrupted_boot_data_treated_as_empty() {
+    let mut bd = boot_data(1, true, 0);
+    bd.magic = 0xDEAD_BEEF;
+    let (action, checked) = select(&bd, ResetReason::PowerOn, [true, true]);
+
+    assert_eq!(action, BootAction::UpdateMode(UpdateReason::NoFirmware));
+    assert!(checked.is_empty());
+}
+
+#[test]
+fn test_inconsistent_boot_data_treated_as_empty() {
+    let mut bd = boot_data(1, true, 0);
+    bd.active_bank = 7;
+    let (action, checked) = select(&bd, ResetReason::PowerOn, [true, true]);
+
+    assert_eq!(action, BootAction::UpdateMode(UpdateReason::NoFirmware));
+    assert!(checked.is_empty());
+}
+
+#[test]
+fn test_erased_boot_data_treated_as_empty() {
+    let mut bd = boot_data(0, false, 0xFF);
+    bd.magic = 0xFFFF_FFFF;
+    bd.size_a = 0xFFFF_FFFF;
+    bd.size_b = 0xFFFF_FFFF;
+    let (action, _) = select(&bd, ResetReason::PowerOn, [true, true]);
+
+    assert_eq!(action, BootAction::UpdateMode(UpdateReason::NoFirmware));
+}
+
+// --- Normal boots ---
+
+#[test]
+fn test_confirmed_active_bank_boots_unchanged() {
+    let bd = boot_data(1, true, 0);
+    let (action, checked) = select(&bd, ResetReason::PowerOn, [true, true]);
+
+    assert_eq!(
+        action,
+        BootAction::Boot {
+            bank: 1,
+            boot_data: bd
+        }
+    );
+    assert_eq!(checked, [1]);
+}
+
+#[test]
+fn test_unconfirmed_boot_is_counted() {
+    let bd = boot_data(0, false, 1);
+    let (action, _) = select(&bd, ResetReason::Watchdog, [true, true]);
+
+    let BootAction::Boot { bank, boot_data } = action else {
+        panic!("expected Boot, got {:?}", action);
+    };
+    assert_eq!(bank, 0);
+    assert_eq!(boot_data.boot_attempts, 2);
+    assert_eq!(boot_data.confirmed, 0);
+}
+
+#[test]
+fn test_debugger_restart_is_not_counted() {
+    let bd = boot_data(0, false, 1);
+    let (action, _) = select(&bd, ResetReason::Debugger, [true, true]);
+
+    assert_eq!(
+        action,
+        BootAction::Boot {
+            bank: 0,
+            boot_data: bd
+        }
+    );
+}
+
+// --- Rollback ---
+
+#[test]
+fn test_exhausted_attempts_roll_back() {
+    let bd = boot_data(0, false, MAX_ATTEMPTS);
+    let (action, checked) = select(&bd, ResetReason::Watchdog, [true, true]);
+
+    let BootAction::Rollback { bank, boot_data } = action else {
+        panic!("expected Rollback, got {:?}", action);
+    };
+    assert_eq!(bank, 1);
+    assert_eq!(boot_data.active_bank, 1);
+    assert_eq!(boot_data.boot_attempts, 1);
+    assert_eq!(boot_data.confirmed, 0);
+    assert_eq!(boot_data.flags & BOOT_FLAG_FALLBACK, 0);
+    // The rollback target is checked once, the failing image not at all.
+    assert_eq!(checked, [1]);
+}
+
+#[test]
+fn test_exhausted_attempts_without_valid_target_stay_in_update_mode() {
+    let bd = boot_data(0, false, MAX_ATTEMPTS);
+    let (action, _) = select(&bd, ResetReason::PowerOn, [true, false]);
+
+    assert_eq!(
+        action,
+        BootAction::UpdateMode(UpdateReason::RollbackTargetInvalid)
+    );
+}
+
+#[test]
+fn test_debugger_restart_never_rolls_back() {
+    let bd = boot_data(1, false, MAX_ATTEMPTS + 2);
+    let (action, _) = select(&bd, ResetReason::Debugger, [true, true]);
+
+    assert_eq!(
+        action,
+        BootAction::Boot {
+            bank: 1,
+            boot_data: bd
+        }
+    );
+}
+
+// --- Fallback ---
+
+#[test]
+fn test_invalid_active_bank_falls_back() {
+    let bd = boot_data(0, true, 0);
+    let (action, checked) = select(&bd, ResetReason::PowerOn, [false, true]);
+
+    let BootAction::Fallback { bank, boot_data } = action else {
+        panic!("expected Fallback, got {:?}", action);
+    };
+    assert_eq!(bank, 1);
+    assert_eq!(boot_data.active_bank, 1);
+    assert_eq!(boot_data.boot_attempts, 1);
+    assert_eq!(boot_data.confirmed, 0);
+    assert_ne!(boot_data.flags & BOOT_FLAG_FALLBACK, 0);
+    assert_eq!(checked, [0, 1]);
+}
+
+#[test]
+fn test_both_banks_invalid_stay_in_update_mode() {
+    let bd = boot_data(1, true, 0);
+    let (action, checked) = select(&bd, ResetReason::PowerOn, [false, false]);
+
+    assert_eq!(
+        action,
+        BootAction::UpdateMode(UpdateReason::NothingBootable)
+    );
+    assert_eq!(checked, [1, 0]);
+}
+
+#[test]
+fn test_single_image_in_other_bank_falls_back() {
+    let mut bd = boot_data(0, false, 0);
+    bd.size_a = 0;
+    let (action, _) = select(&bd, ResetReason::PowerOn, [false, true]);
+
+    assert!(matches!(action, BootAction::Fallback { bank: 1, .. }));
+}
+
+// --- Every combination ---
+
+#[test]
+fn test_all_combinations_are_consistent() {
+    let validity = [[true, true], [true, false], [false, true], [false, false]];
+
+    for reason in ALL_REASONS {
+        for active in [0u8, 1] {
+            for confirmed in [false, true] {
+                for attempts in 0..=MAX_ATTEMPTS + 1 {
+                    for valid in validity {
+                        let bd = boot_data(active, confirmed, attempts);
+                        let (action, _) = select(&bd, reason, valid);
+                        let other = 1 - active;
+                        let exhausted = !confirmed
+                            && attempts >= MAX_ATTEMPTS
+                            && reason != ResetReason::Debugger;
+                        let context = format!(
+                            "{:?} active={} confirmed={} attempts={} valid={:?}: {:?}",
+                            reason, active, confirmed, attempts, valid, action
+                        );
+
+                        match action {
+                            BootAction::Boot { bank, boot_data } => {
+                                assert!(!exhausted, "{}", context);
+                                assert_eq!(bank, active, "{}", context);
+                                assert!(valid[usize::from(bank)], "{}", context);
+                                let counted = !confirmed && reason != ResetReason::Debugger;
+                                assert_eq!(
+                                    boot_data.boot_attempts,
+                                    attempts + u8::from(counted),
+                                    "{}",
+                                    context
+                                );
+                            }
+                            BootAction::Rollback { bank, boot_data } => {
+                                assert!(exhausted, "{}", context);
+                                assert_eq!(bank, other, "{}", context);
+                                assert!(valid[usize::from(other)], "{}", context);
+                                assert_eq!(boot_data.active_bank, other, "{}", context);
+                                assert_eq!(boot_data.boot_attempts, 1, "{}", context);
+                            }
+                            BootAction::Fallback { bank, boot_data } => {
+                                assert!(!exhausted, "{}", context);
+                                assert!(!valid[usize::from(active)], "{}", context);
+                                assert!(valid[usize::from(other)], "{}", context);
+                                assert_eq!(bank, other, "{}", context);
+                                assert!(boot_data.fell_back(), "{}", context);
+                            }
+                            BootAction::UpdateMode(UpdateReason::RollbackTargetInvalid) => {
+                                assert!(exhausted, "{}", context);
+                                assert!(!valid[usize::from(other)], "{}", context);
+                            }
+                            BootAction::UpdateMode(UpdateReason::NothingBootable) => {
+                                assert!(!exhausted, "{}", context);
+                                assert_eq!(valid, [false, false], "{}", context);
+                            }
+                            BootAction::UpdateMode(UpdateReason::NoFirmware) => {
+                                panic!("{}", context);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Unit tests for bootloader image validation (self-update).
+
+use crc::{Crc, CRC_32_MPEG_2};
+use crispy_common::bootloader_image::{
+    validate_bootloader_image, BootloaderImageError, BOOT2_SIZE,
+};
+use crispy_common::protocol::{BOOTLOADER_REGION_SIZE, FLASH_BASE, FW_A_ADDR};
+
+/// A minimal image: boot2 with a correct CRC, then SP and reset vector as
+/// produced by `linker_scripts/bootloader_rp2040.x` with flip-link.
+fn image(initial_sp: u32, reset_vector: u32) -> Vec<u8> {
+    let mut image = vec![0u8; 1024];
+    for (i, byte) in image[..BOOT2_SIZE - 4].iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    let crc = Crc::<u32>::new(&CRC_32_MPEG_2).checksum(&image[..BOOT2_SIZE - 4]);
+    image[BOOT2_SIZE - 4..BOOT2_SIZE].copy_from_slice(&crc.to_le_bytes());
+    image[BOOT2_SIZE..BOOT2_SIZE + 4].copy_from_slice(&initial_sp.to_le_bytes());
+    image[BOOT2_SIZE + 4..BOOT2_SIZE + 8].copy_from_slice(&reset_vector.to_le_bytes());
+    image
+}
+
+#[test]
+fn test_bootloader_region_ends_at_bank_a() {
+    assert_eq!(FLASH_BASE + BOOTLOADER_REGION_SIZE, FW_A_ADDR);
+}
+
+#[test]
+fn test_valid_image_accepted() {
+    assert_eq!(
+        validate_bootloader_image(&image(0x2003_C958, 0x1000_01C1)),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_short_image_rejected() {
+    assert_eq!(
+        validate_bootloader_image(&[0u8; BOOT2_SIZE]),
+        Err(BootloaderImageError::TooSmall)
+    );
+}
+
+#[test]
+fn test_oversized_image_rejected() {
+    let mut img = image(0x2003_C958, 0x1000_01C1);
+    img.resize(BOOTLOADER_REGION_SIZE as usize + 1, 0xFF);
+    assert_eq!(
+        validate_bootloader_image(&img),
+        Err(BootloaderImageError::TooLarge)
+    );
+}
+
+#[test]
+fn test_bad_boot2_crc_rejected() {
+    let mut img = image(0x2003_C958, 0x1000_01C1);
+    img[10] ^= 0xFF;
+    assert_eq!(
+        validate_bootloader_image(&img),
+        Err(BootloaderImageError::Boot2Crc)
+    );
+}
+
+#[test]
+fn test_firmware_image_rejected() {
+    // A RAM firmware image starts with its vector table, not boot2
+    let mut img = vec![0u8; 1024];
+    img[..4].copy_from_slice(&0x2003_BFD0u32.to_le_bytes());
+    img[4..8].copy_from_slice(&0x2000_00C1u32.to_le_bytes());
+    assert_eq!(
+        validate_bootloader_image(&img),
+        Err(BootloaderImageError::Boot2Crc)
+    );
+}
+
+#[test]
+fn test_stack_outside_sram_rejected() {
+    assert_eq!(
+        validate_bootloader_image(&image(0x1000_0000, 0x1000_01C1)),
+        Err(BootloaderImageError::StackOutOfRange)
+    );
+}
+
+#[test]
+fn test_misaligned_stack_rejected() {
+    assert_eq!(
+        validate_bootloader_image(&image(0x2003_C954, 0x1000_01C1)),
+        Err(BootloaderImageError::StackOutOfRange)
+    );
+}
+
+#[test]
+fn test_reset_in_firmware_bank_rejected() {
+    assert_eq!(
+        validate_bootloader_image(&image(0x2003_C958, FW_A_ADDR + 0x101)),
+        Err(BootloaderImageError::ResetOutOfRange)
+    );
+}
+
+#[test]
+fn test_reset_inside_boot2_rejected() {
+    assert_eq!(
+        validate_bootloader_image(&image(0x2003_C958, FLASH_BASE + 0x41)),
+        Err(BootloaderImageError::ResetOutOfRange)
+    );
+}
+
+#[test]
+fn test_reset_without_thumb_bit_rejected() {
+    assert_eq!(
+        validate_bootloader_image(&image(0x2003_C958, 0x1000_01C0)),
+        Err(BootloaderImageError::ResetOutOfRange)
+    );
+}
@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Unit tests for piecewise image CRC-32.
+
+use crispy_common::crc32::{crc32, crc32_extend, CrcAlgorithm};
+
+#[test]
+fn test_crc32_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn test_crc32_of_nothing_is_zero() {
+    assert_eq!(crc32(&[]), 0);
+    assert_eq!(crc32_extend(0, &[]), 0);
+}
+
+#[test]
+fn test_extend_from_zero_matches_whole() {
+    assert_eq!(crc32_extend(0, b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn test_extend_with_nothing_is_unchanged() {
+    let crc = crc32(b"firmware");
+    assert_eq!(crc32_extend(crc, &[]), crc);
+}
+
+#[test]
+fn test_extend_matches_whole_at_every_split() {
+    let data: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+    let whole = crc32(&data);
+    for split in [0, 1, 255, 256, 999, 1000] {
+        let (head, tail) = data.split_at(split);
+        assert_eq!(crc32_extend(crc32(head), tail), whole, "split at {}", split);
+    }
+}
+
+#[test]
+fn test_extend_in_many_pieces() {
+    let data: Vec<u8> = (0..10_000u32).map(|i| (i * 7 + i / 13) as u8).collect();
+    let crc = data.chunks(4096).fold(0, crc32_extend);
+    assert_eq!(crc, crc32(&data));
+}
+
+// --- Other algorithms ---
+
+/// Every algorithm with its catalogue check value (CRC of "123456789").
+const CHECK_VALUES: [(CrcAlgorithm, u32); 3] = [
+    (CrcAlgorithm::IsoHdlc, 0xCBF4_3926),
+    (CrcAlgorithm::Mpeg2, 0x0376_E6E7),
+    (CrcAlgorithm::Bzip2, 0xFC89_1918),
+];
+
+#[test]
+fn test_algorithm_check_values() {
+    for (algo, check) in CHECK_VALUES {
+        assert_eq!(algo.checksum(b"123456789"), check, "{:?}", algo);
+    }
+}
+
+#[test]
+fn test_algorithm_ids_round_trip() {
+    for (algo, _) in CHECK_VALUES {
+        assert_eq!(CrcAlgorithm::from_id(algo.id()), Some(algo));
+    }
+    assert_eq!(CrcAlgorithm::IsoHdlc.id(), 0);
+    assert_eq!(CrcAlgorithm::from_id(3), None);
+    assert_eq!(CrcAlgorithm::from_id(0xFF), None);
+}
+
+#[test]
+fn test_algorithm_extend_matches_whole_at_every_split() {
+    let data: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+    for (algo, _) in CHECK_VALUES {
+        let whole = algo.checksum(&data);
+        assert_eq!(algo.extend(algo.empty(), &[]), algo.checksum(&[]));
+        for split in [0, 1, 255, 256, 999, 1000] {
+            let (head, tail) = data.split_at(split);
+            assert_eq!(
+                algo.extend(algo.checksum(head), tail),
+                whole,
+                "{:?} split at {}",
+                algo,
+                split
+            );
+        }
+    }
+}
+
+#[test]
+fn test_iso_hdlc_algorithm_matches_crc32() {
+    let data = b"firmware image";
+    assert_eq!(CrcAlgorithm::IsoHdlc.checksum(data), crc32(data));
+}
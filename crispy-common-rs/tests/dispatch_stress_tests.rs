@@ -0,0 +1,430 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Randomized command sequences against the update commands, run on the
+//! in-memory flash.
+//!
+//! Every command goes through `update::dispatch`, as the bootloader's
+//! `dispatch_command` routes them, with a RAM buffer of a few sectors so
+//! that most uploads are paged into their bank. Each step is followed by the
+//! invariants a malformed sequence must never break: flash outside the banks
+//! and BootData is untouched, a bank only changes through the upload in
+//! progress (pages of the data accepted, then a `FinishUpdate` whose CRC
+//! matched it), every image BootData describes either reads back with its
+//! CRC or has its first sector erased, and the receive state stays within
+//! its image.
+
+#![cfg(feature = "std")]
+
+use crispy_common::bootloader_image::BOOT2_SIZE;
+use crispy_common::crc32::CrcAlgorithm;
+use crispy_common::flash_bounds::FlashBounds;
+use crispy_common::flash_ops::{self, FlashOps, MemFlash};
+use crispy_common::protocol::{
+    AckStatus, BootData, Command, BOOTLOADER_REGION_SIZE, FLASH_BASE, FLASH_SECTOR_SIZE,
+    FW_BANK_SIZE, GOLDEN_BANK, MAX_DATA_BLOCK_SIZE,
+};
+use crispy_common::update::{self, Handled, ImageLimits, Upload, PAGED_HEAD_SIZE};
+
+const FLASH_SIZE: u32 = 2 * 1024 * 1024;
+
+/// The layout of `linker_scripts/bootloader_rp2040.x`.
+const BOUNDS: FlashBounds = FlashBounds {
+    boot2_end: BOOT2_SIZE as u32,
+    bootloader_end: BOOTLOADER_REGION_SIZE,
+    flash_size: FLASH_SIZE,
+};
+
+/// Fills flash outside the banks and BootData sectors, which no update
+/// command may write.
+const FOREIGN: u8 = 0xA5;
+
+/// `fw_rp2040.x` geometry, as the bootloader passes it to the update code.
+const LIMITS: ImageLimits = ImageLimits {
+    bootloader_version: 0,
+    ram_base: 0x2000_0000,
+    copy_size: 0x3_0000,
+    ram_end: 0x2004_2000,
+    min_stack_headroom: 1024,
+};
+
+/// The device's RAM buffer: images larger than this are paged into flash.
+const RAM_SIZE: usize = 4 * FLASH_SECTOR_SIZE as usize;
+
+/// Largest image the generator plans to send whole, several times the RAM
+/// buffer; larger sizes are still offered to `StartUpdate` to exercise its
+/// limits.
+const MAX_PLANNED_SIZE: u32 = 10 * FLASH_SECTOR_SIZE;
+
+/// xorshift64*: deterministic, so a failing seed can be replayed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 32) as u32
+    }
+
+    fn below(&mut self, n: u32) -> u32 {
+        self.next() % n
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u32) as usize]
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+}
+
+/// The bootloader's update commands over `MemFlash`.
+struct Device {
+    flash: MemFlash,
+    ram: Vec<u8>,
+    upload: Option<Upload>,
+}
+
+impl Device {
+    fn new() -> Self {
+        let mut flash = MemFlash::new(BOUNDS);
+        flash.bytes.fill(FOREIGN);
+        for bank in [0, 1] {
+            let (addr, size) = flash_ops::bank_range(bank).unwrap();
+            fill(&mut flash, addr - FLASH_BASE, size, 0xFF);
+        }
+        for offset in [
+            flash_ops::BOOT_DATA_OFFSET,
+            flash_ops::BOOT_DATA_MIRROR_OFFSET,
+        ] {
+            fill(&mut flash, offset, FLASH_SECTOR_SIZE, 0xFF);
+        }
+        Self {
+            flash,
+            ram: vec![0; RAM_SIZE],
+            upload: None,
+        }
+    }
+
+    /// Run `cmd` and acknowledge it as `dispatch_command` does, writing a
+    /// finished image on this core.
+    fn dispatch(&mut self, cmd: &Command) -> AckStatus {
+        let handled = update::dispatch(
+            &mut self.flash,
+            &mut self.ram,
+            &LIMITS,
+            &mut self.upload,
+            cmd,
+            |_, _, _| {},
+        );
+        match handled {
+            Ok(Handled::Finish(image)) => {
+                let written = image.write(&mut self.flash, &self.ram, |_, _, _| {});
+                match image.complete(&mut self.flash, written, |_, _, _| {}) {
+                    Ok(_) => AckStatus::Ok,
+                    Err(err) => err.into(),
+                }
+            }
+            // Queries, which must not change anything.
+            Ok(_) => AckStatus::Ok,
+            Err(err) => err.into(),
+        }
+    }
+}
+
+fn fill(flash: &mut MemFlash, offset: u32, len: u32, value: u8) {
+    flash.bytes[offset as usize..(offset + len) as usize].fill(value);
+}
+
+/// Size, CRC and version BootData records for bank A or B.
+fn recorded(bd: &BootData, bank: u8) -> (u32, u32, u32) {
+    if bank == 0 {
+        (bd.size_a, bd.crc_a, bd.version_a)
+    } else {
+        (bd.size_b, bd.crc_b, bd.version_b)
+    }
+}
+
+fn bank_bytes(flash: &MemFlash, bank: u8) -> &[u8] {
+    let (addr, size) = flash_ops::bank_range(bank).unwrap();
+    flash.slice(addr - FLASH_BASE, size)
+}
+
+/// What the host side of the harness knows, independently of `Device`.
+#[derive(Default)]
+struct Host {
+    /// Image the last `StartUpdate` sent was planned for, if any.
+    pending: Vec<u8>,
+    /// Image the current upload was started for, if the generator planned one.
+    planned: Vec<u8>,
+    /// Bytes the device acknowledged in the current upload.
+    accepted: Vec<u8>,
+    finished: u32,
+    /// Uploads finished after paging part of their image into flash.
+    paged_finished: u32,
+    crc_rejected: u32,
+}
+
+fn random_command(rng: &mut Rng, host: &mut Host) -> Command {
+    match rng.below(100) {
+        0..=11 => {
+            let size = match rng.below(8) {
+                0 => rng.pick(&[0, FW_BANK_SIZE, FW_BANK_SIZE + 1, u32::MAX]),
+                1..=4 => 1 + rng.below(MAX_PLANNED_SIZE),
+                _ => 1 + rng.below(RAM_SIZE as u32),
+            };
+            host.pending = if size <= MAX_PLANNED_SIZE {
+                rng.bytes(size as usize)
+            } else {
+                Vec::new()
+            };
+            let crc_algo = rng.pick(&[0, 0, 1, 2, 3, 0xFF]);
+            let crc32 = match (rng.below(5), CrcAlgorithm::from_id(crc_algo)) {
+                (0, _) | (_, None) => rng.next(),
+                (_, Some(algo)) => algo.checksum(&host.pending),
+            };
+            Command::StartUpdate {
+                bank: rng.pick(&[0, 1, 0, 1, GOLDEN_BANK, 4, 0xFF]),
+                size,
+                crc32,
+                version: rng.below(8),
+                crc_algo,
+            }
+        }
+        12..=68 => {
+            let next = host.accepted.len();
+            let remaining = host.planned.len().saturating_sub(next);
+            match rng.below(10) {
+                // Out of order: behind, ahead, or anywhere.
+                0 => {
+                    let anywhere = rng.next();
+                    let len = 1 + rng.below(64) as usize;
+                    Command::DataBlock {
+                        offset: rng.pick(&[
+                            next as u32 + 1,
+                            next.saturating_sub(1) as u32,
+                            anywhere,
+                        ]),
+                        data: rng.bytes(len),
+                    }
+                }
+                // Runs past the announced size, when a frame can.
+                1 if remaining < MAX_DATA_BLOCK_SIZE => Command::DataBlock {
+                    offset: next as u32,
+                    data: rng.bytes(remaining + 1),
+                },
+                // The next part of the image: mostly full blocks, as the
+                // host sends them.
+                choice => {
+                    let len = match choice {
+                        1..=3 => 1 + rng.below(MAX_DATA_BLOCK_SIZE as u32) as usize,
+                        _ => MAX_DATA_BLOCK_SIZE,
+                    };
+                    Command::DataBlock {
+                        offset: next as u32,
+                        data: host
+                            .planned
+                            .get(next..next + len.min(remaining))
+                            .unwrap_or_default()
+                            .to_vec(),
+                    }
+                }
+            }
+        }
+        69..=75 => Command::FinishUpdate,
+        76..=83 => Command::FinishUpdateNoActivate,
+        84 => Command::AbortUpdate,
+        85..=89 => Command::SetActiveBank {
+            bank: rng.pick(&[0, 1, GOLDEN_BANK, 0xFF]),
+        },
+        90..=92 => Command::SetBankLock {
+            bank: rng.pick(&[0, 1, 0xFF]),
+            locked: rng.below(3) == 0,
+        },
+        93..=94 => Command::WipeAll,
+        95..=96 => Command::GetStatus,
+        97 => Command::GetBankInfo { bank: 0 },
+        98 => Command::CompareBanks,
+        _ => Command::Ping { nonce: 7 },
+    }
+}
+
+/// Run `cmd` and check every invariant against the flash and state before it.
+fn step(dev: &mut Device, host: &mut Host, cmd: &Command) {
+    let before = dev.flash.bytes.clone();
+    let prev = dev.upload;
+    let ack = dev.dispatch(cmd);
+
+    // Flash outside the banks and BootData is never written.
+    let mut writable = [
+        flash_ops::bank_range(0).unwrap(),
+        flash_ops::bank_range(1).unwrap(),
+        (FLASH_BASE + flash_ops::BOOT_DATA_OFFSET, FLASH_SECTOR_SIZE),
+        (
+            FLASH_BASE + flash_ops::BOOT_DATA_MIRROR_OFFSET,
+            FLASH_SECTOR_SIZE,
+        ),
+    ]
+    .map(|(addr, len)| {
+        (
+            (addr - FLASH_BASE) as usize,
+            (addr - FLASH_BASE + len) as usize,
+        )
+    });
+    writable.sort();
+    let mut start = 0;
+    for (end, next) in writable.into_iter().chain([(before.len(), before.len())]) {
+        assert!(
+            dev.flash.bytes[start..end] == before[start..end],
+            "{:?} wrote flash between 0x{:x} and 0x{:x}",
+            cmd,
+            start,
+            end
+        );
+        start = next;
+    }
+    if ack != AckStatus::Ok {
+        assert_eq!(dev.flash.bytes, before, "{:?} failed with {:?}", cmd, ack);
+    }
+
+    // A bank only changes through the upload to it: a block that pages out
+    // erases its first sector and programs whole sectors of what was
+    // accepted after it, and a finish whose CRC matched what was sent writes
+    // the rest.
+    let head = PAGED_HEAD_SIZE as usize;
+    for bank in [0, 1] {
+        let (addr, size) = flash_ops::bank_range(bank).unwrap();
+        let range = (addr - FLASH_BASE) as usize..(addr - FLASH_BASE + size) as usize;
+        let old = &before[range];
+        let new = bank_bytes(&dev.flash, bank);
+        if old == new {
+            continue;
+        }
+        let Some(upload) = prev else {
+            panic!("{:?} changed bank {} outside an upload", cmd, bank);
+        };
+        assert_eq!((upload.bank, ack), (bank, AckStatus::Ok));
+        match cmd {
+            Command::DataBlock { data, .. } => {
+                let now = dev.upload.unwrap();
+                let paged = head + now.paged_out as usize;
+                assert!(now.paged_out > upload.paged_out);
+                assert!(new[..head].iter().all(|&b| b == 0xFF));
+                assert_eq!(new[head..paged], host.accepted[head..paged]);
+                assert_eq!(new[paged..], old[paged..]);
+                // Only whole sectors leave RAM, the block itself stays there.
+                assert!(paged <= host.accepted.len());
+                assert_eq!(
+                    now.buffered() as usize,
+                    data.len() + host.accepted.len() - paged + head
+                );
+            }
+            Command::FinishUpdate | Command::FinishUpdateNoActivate => {
+                assert_eq!(
+                    upload.crc_algo.checksum(&host.accepted),
+                    upload.expected_crc
+                );
+                assert_eq!(&new[..host.accepted.len()], host.accepted.as_slice());
+            }
+            _ => panic!("{:?} changed bank {}", cmd, bank),
+        }
+    }
+
+    // Every image BootData describes reads back with its CRC, unless an
+    // upload to its bank erased its first sector, so it no longer boots.
+    let bd = dev.flash.read_boot_data();
+    assert!(bd.is_valid());
+    for bank in [0, 1] {
+        let (size, crc, _) = recorded(&bd, bank);
+        if size != 0 {
+            assert!(size <= FW_BANK_SIZE);
+            let image = &bank_bytes(&dev.flash, bank)[..size as usize];
+            assert!(
+                CrcAlgorithm::IsoHdlc.checksum(image) == crc
+                    || image[..head.min(image.len())].iter().all(|&b| b == 0xFF),
+                "bank {} holds a corrupt image BootData describes",
+                bank
+            );
+        }
+    }
+
+    // The host's view of the upload follows the acknowledgements.
+    match (cmd, ack) {
+        (Command::StartUpdate { .. }, AckStatus::Ok) => {
+            host.planned = core::mem::take(&mut host.pending);
+            host.accepted.clear();
+        }
+        (Command::DataBlock { data, .. }, AckStatus::Ok) => host.accepted.extend_from_slice(data),
+        (Command::FinishUpdate | Command::FinishUpdateNoActivate, AckStatus::Ok) => {
+            host.finished += 1;
+            if prev.is_some_and(|upload| upload.paged_out > 0) {
+                host.paged_finished += 1;
+            }
+        }
+        (Command::FinishUpdate | Command::FinishUpdateNoActivate, AckStatus::CrcError) => {
+            host.crc_rejected += 1
+        }
+        _ => {}
+    }
+
+    // The receive state stays within its image, and RAM holds what was
+    // accepted and not yet paged out.
+    if let Some(upload) = dev.upload {
+        assert!(upload.bank <= 1);
+        assert!((1..=FW_BANK_SIZE).contains(&upload.expected_size));
+        assert!(upload.bytes_received <= upload.expected_size);
+        assert_eq!(upload.bytes_received as usize, host.accepted.len());
+        let buffered = upload.buffered() as usize;
+        assert!(buffered <= RAM_SIZE);
+        if upload.paged_out == 0 {
+            assert_eq!(&dev.ram[..buffered], host.accepted.as_slice());
+        } else {
+            let paged = head + upload.paged_out as usize;
+            assert_eq!(dev.ram[..head], host.accepted[..head]);
+            assert_eq!(dev.ram[head..buffered], host.accepted[paged..]);
+        }
+    }
+}
+
+#[test]
+fn test_random_command_sequences_keep_the_invariants() {
+    let mut finished = 0;
+    let mut paged_finished = 0;
+    let mut crc_rejected = 0;
+    for seed in 1..=16u64 {
+        let mut rng = Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        let mut dev = Device::new();
+        let mut host = Host::default();
+        for _ in 0..600 {
+            let cmd = random_command(&mut rng, &mut host);
+            step(&mut dev, &mut host, &cmd);
+        }
+        finished += host.finished;
+        paged_finished += host.paged_finished;
+        crc_rejected += host.crc_rejected;
+    }
+    // The sequences reached both ends of the CRC gate.
+    assert!(finished > 0, "no upload completed");
+    assert!(paged_finished > 0, "no paged upload completed");
+    assert!(crc_rejected > 0, "no upload failed its CRC");
+}
+
+#[test]
+fn test_random_frames_decode_or_fail_without_panicking() {
+    let mut rng = Rng(0x5EED);
+    let mut dev = Device::new();
+    let mut host = Host::default();
+    for _ in 0..20_000 {
+        let len = rng.below(48) as usize;
+        let mut frame = rng.bytes(len);
+        // Bias towards the update commands' tags.
+        if let Some(tag) = frame.first_mut() {
+            *tag %= 32;
+        }
+        if let Ok(cmd) = postcard::from_bytes::<Command>(&frame) {
+            step(&mut dev, &mut host, &cmd);
+        }
+    }
+}
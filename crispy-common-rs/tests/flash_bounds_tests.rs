@@ -0,0 +1,223 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Unit tests for the flash erase/program bounds checks.
+
+use crispy_common::bootloader_image::BOOT2_SIZE;
+use crispy_common::flash_bounds::{
+    bootloader_protection, capacity_from_jedec, BlockProtection, FlashBounds, RangeError, SR1_BP0,
+    SR1_BP1, SR1_BP2, SR1_PROTECT_MASK, SR1_SEC, SR1_TB,
+};
+use crispy_common::protocol::{
+    BOOTLOADER_REGION_SIZE, BOOT_DATA_ADDR, FLASH_BASE, FLASH_PAGE_SIZE, FLASH_SECTOR_SIZE,
+    FW_A_ADDR,
+};
+
+const FLASH_SIZE: u32 = 2 * 1024 * 1024;
+
+/// The layout of `linker_scripts/bootloader_rp2040.x`.
+const BOUNDS: FlashBounds = FlashBounds {
+    boot2_end: BOOT2_SIZE as u32,
+    bootloader_end: BOOTLOADER_REGION_SIZE,
+    flash_size: FLASH_SIZE,
+};
+
+#[test]
+fn test_writable_flash_is_accepted() {
+    let bank_a = FW_A_ADDR - FLASH_BASE;
+
+    assert_eq!(BOUNDS.check(bank_a, FLASH_SECTOR_SIZE), Ok(()));
+    assert_eq!(
+        BOUNDS.check(BOOT_DATA_ADDR - FLASH_BASE, FLASH_PAGE_SIZE),
+        Ok(())
+    );
+    assert_eq!(BOUNDS.check(bank_a, FLASH_SIZE - bank_a), Ok(()));
+}
+
+#[test]
+fn test_boot2_is_refused() {
+    assert_eq!(BOUNDS.check(0, FLASH_SECTOR_SIZE), Err(RangeError::Boot2));
+    assert_eq!(BOUNDS.check(0, FLASH_PAGE_SIZE), Err(RangeError::Boot2));
+    assert_eq!(
+        BOUNDS.check(BOOT2_SIZE as u32 - 1, 1),
+        Err(RangeError::Boot2)
+    );
+}
+
+#[test]
+fn test_bootloader_is_refused() {
+    assert_eq!(
+        BOUNDS.check(BOOT2_SIZE as u32, FLASH_PAGE_SIZE),
+        Err(RangeError::Bootloader)
+    );
+    assert_eq!(
+        BOUNDS.check(FLASH_SECTOR_SIZE, FLASH_SECTOR_SIZE),
+        Err(RangeError::Bootloader)
+    );
+    assert_eq!(
+        BOUNDS.check(BOOTLOADER_REGION_SIZE - 1, 1),
+        Err(RangeError::Bootloader)
+    );
+}
+
+#[test]
+fn test_ranges_straddling_a_boundary_are_refused() {
+    // boot2 into the bootloader.
+    assert_eq!(
+        BOUNDS.check(BOOT2_SIZE as u32 - FLASH_PAGE_SIZE / 2, FLASH_PAGE_SIZE),
+        Err(RangeError::Boot2)
+    );
+    // The bootloader into bank A.
+    assert_eq!(
+        BOUNDS.check(
+            BOOTLOADER_REGION_SIZE - FLASH_SECTOR_SIZE,
+            2 * FLASH_SECTOR_SIZE
+        ),
+        Err(RangeError::Bootloader)
+    );
+    // The whole flash.
+    assert_eq!(BOUNDS.check(0, FLASH_SIZE), Err(RangeError::Boot2));
+    // The last sector and one byte more.
+    assert_eq!(
+        BOUNDS.check(FLASH_SIZE - FLASH_SECTOR_SIZE, FLASH_SECTOR_SIZE + 1),
+        Err(RangeError::PastEnd)
+    );
+}
+
+#[test]
+fn test_range_ending_at_bootloader_end_is_refused_starting_there_accepted() {
+    assert_eq!(
+        BOUNDS.check(BOOTLOADER_REGION_SIZE - FLASH_PAGE_SIZE, FLASH_PAGE_SIZE),
+        Err(RangeError::Bootloader)
+    );
+    assert_eq!(
+        BOUNDS.check(BOOTLOADER_REGION_SIZE, FLASH_PAGE_SIZE),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_past_end_of_flash_is_refused() {
+    assert_eq!(
+        BOUNDS.check(FLASH_SIZE, FLASH_PAGE_SIZE),
+        Err(RangeError::PastEnd)
+    );
+    assert_eq!(
+        BOUNDS.check(FLASH_SIZE - FLASH_SECTOR_SIZE, FLASH_SECTOR_SIZE),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_wrapping_range_is_refused() {
+    // An absolute address passed where an offset was expected.
+    assert_eq!(
+        BOUNDS.check(FW_A_ADDR, FLASH_SECTOR_SIZE),
+        Err(RangeError::PastEnd)
+    );
+    assert_eq!(
+        BOUNDS.check(BOOTLOADER_REGION_SIZE, u32::MAX),
+        Err(RangeError::PastEnd)
+    );
+    assert_eq!(BOUNDS.check(u32::MAX, 2), Err(RangeError::PastEnd));
+}
+
+#[test]
+fn test_capacity_from_jedec_id() {
+    // W25Q16JV, the Pico's part.
+    assert_eq!(capacity_from_jedec([0xEF, 0x40, 0x15]), Some(FLASH_SIZE));
+    // W25Q32JV and W25Q128JV.
+    assert_eq!(
+        capacity_from_jedec([0xEF, 0x40, 0x16]),
+        Some(4 * 1024 * 1024)
+    );
+    assert_eq!(
+        capacity_from_jedec([0xEF, 0x70, 0x18]),
+        Some(16 * 1024 * 1024)
+    );
+}
+
+#[test]
+fn test_implausible_jedec_capacity_is_ignored() {
+    // No part answering: the bus reads all zeros or all ones.
+    assert_eq!(capacity_from_jedec([0x00, 0x00, 0x00]), None);
+    assert_eq!(capacity_from_jedec([0xFF, 0xFF, 0xFF]), None);
+    // Beyond what XIP maps, and below one 64 KB block.
+    assert_eq!(capacity_from_jedec([0xEF, 0x40, 0x19]), None);
+    assert_eq!(capacity_from_jedec([0xEF, 0x40, 0x0F]), None);
+}
+
+#[test]
+fn test_smaller_part_than_layout_refuses_the_upper_half() {
+    let fitted = capacity_from_jedec([0xEF, 0x40, 0x15]).unwrap();
+    let bounds = FlashBounds {
+        flash_size: (2 * FLASH_SIZE).min(fitted),
+        ..BOUNDS
+    };
+
+    assert_eq!(
+        bounds.check(FLASH_SIZE, FLASH_SECTOR_SIZE),
+        Err(RangeError::PastEnd)
+    );
+    assert_eq!(
+        bounds.check(FLASH_SIZE - FLASH_SECTOR_SIZE, FLASH_SECTOR_SIZE),
+        Ok(())
+    );
+}
+
+// --- Bootloader write protection ---
+
+#[test]
+fn test_parts_up_to_4mb_protect_the_bootloader_block() {
+    for capacity in [512 * 1024, 1024 * 1024, FLASH_SIZE, 4 * 1024 * 1024] {
+        assert_eq!(
+            bootloader_protection(capacity, BOOTLOADER_REGION_SIZE),
+            Some(BlockProtection {
+                sr1_bits: SR1_TB | SR1_BP0,
+                len: 64 * 1024,
+            }),
+            "{} bytes",
+            capacity
+        );
+    }
+    // Exactly boot2 and the bootloader, and nothing of bank A.
+    assert_eq!(BOOTLOADER_REGION_SIZE, FW_A_ADDR - FLASH_BASE);
+}
+
+#[test]
+fn test_larger_parts_fall_back_to_sector_protection() {
+    // Their smallest block range (128 KB and up) would cover bank A.
+    for capacity in [8 * 1024 * 1024, 16 * 1024 * 1024] {
+        assert_eq!(
+            bootloader_protection(capacity, BOOTLOADER_REGION_SIZE),
+            Some(BlockProtection {
+                sr1_bits: SR1_SEC | SR1_TB | SR1_BP2,
+                len: 32 * 1024,
+            })
+        );
+    }
+}
+
+#[test]
+fn test_protection_never_reaches_past_the_bootloader() {
+    assert_eq!(
+        bootloader_protection(FLASH_SIZE, 20 * 1024),
+        Some(BlockProtection {
+            sr1_bits: SR1_SEC | SR1_TB | SR1_BP1 | SR1_BP0,
+            len: 16 * 1024,
+        })
+    );
+    assert_eq!(bootloader_protection(FLASH_SIZE, 4 * 1024 - 1), None);
+    for capacity in [64 * 1024, FLASH_SIZE, 16 * 1024 * 1024] {
+        for end in [4 * 1024, 8 * 1024, 48 * 1024, BOOTLOADER_REGION_SIZE] {
+            let protection = bootloader_protection(capacity, end).unwrap();
+            assert!(protection.len <= end.min(capacity));
+            assert_eq!(protection.sr1_bits & !SR1_PROTECT_MASK, 0);
+        }
+    }
+}
+
+#[test]
+fn test_unknown_capacity_is_not_protected() {
+    assert_eq!(bootloader_protection(0, BOOTLOADER_REGION_SIZE), None);
+}
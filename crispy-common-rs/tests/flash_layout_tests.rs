@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Tests for the build-time flash layouts.
+
+use crispy_common::flash_layout::{FlashLayout, SELECTED, SUPPORTED_FLASH_SIZES};
+use crispy_common::protocol::{
+    BOOTLOADER_REGION_SIZE, BOOT_DATA_ADDR, FLASH_BASE, FLASH_SECTOR_SIZE, FLASH_SIZE,
+    FW_BANK_SIZE, FW_GOLD_ADDR, FW_GOLD_SIZE,
+};
+
+const MIB: u32 = 1024 * 1024;
+
+#[test]
+fn test_default_layout_is_2mb() {
+    assert_eq!(FLASH_SIZE, 2 * MIB);
+    assert_eq!(
+        SELECTED,
+        FlashLayout {
+            flash_size: 2 * MIB,
+            bank_size: 768 * 1024,
+        }
+    );
+}
+
+#[test]
+fn test_protocol_constants_follow_selected_layout() {
+    assert_eq!(FLASH_SIZE, SELECTED.flash_size);
+    assert_eq!(FW_BANK_SIZE, SELECTED.bank_size);
+    assert_eq!(FW_GOLD_ADDR + FW_GOLD_SIZE, FLASH_BASE + FLASH_SIZE);
+}
+
+#[test]
+fn test_every_layout_fills_its_flash() {
+    for flash_size in SUPPORTED_FLASH_SIZES {
+        let layout = FlashLayout::for_flash_size(flash_size).unwrap();
+        assert_eq!(layout.flash_size, flash_size);
+        assert_eq!(layout.bank_size % FLASH_SECTOR_SIZE, 0);
+        // Bootloader, banks A and B, BootData and wear stats, golden bank.
+        let used =
+            BOOTLOADER_REGION_SIZE + 2 * layout.bank_size + 2 * FLASH_SECTOR_SIZE + FW_GOLD_SIZE;
+        assert_eq!(used, flash_size, "{} MB", flash_size / MIB);
+    }
+}
+
+#[test]
+fn test_larger_layouts_have_larger_banks() {
+    let bank_sizes: Vec<u32> = SUPPORTED_FLASH_SIZES
+        .iter()
+        .map(|&size| FlashLayout::for_flash_size(size).unwrap().bank_size)
+        .collect();
+    assert_eq!(
+        bank_sizes,
+        [768 * 1024, 1792 * 1024, 3840 * 1024, 7936 * 1024]
+    );
+}
+
+#[test]
+fn test_unsupported_flash_size_has_no_layout() {
+    assert_eq!(FlashLayout::for_flash_size(0), None);
+    assert_eq!(FlashLayout::for_flash_size(MIB), None);
+    assert_eq!(FlashLayout::for_flash_size(3 * MIB), None);
+    assert_eq!(FlashLayout::for_flash_size(32 * MIB), None);
+}
+
+#[test]
+fn test_boot_data_follows_banks() {
+    assert_eq!(
+        BOOT_DATA_ADDR,
+        FLASH_BASE + BOOTLOADER_REGION_SIZE + 2 * FW_BANK_SIZE
+    );
+}
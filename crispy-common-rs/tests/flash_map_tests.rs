@@ -0,0 +1,189 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Unit tests for flash map assembly.
+
+use crispy_common::flash_map::with_free_gaps;
+use crispy_common::protocol::{
+    FlashRegion, FlashRegionKind, BOOT_DATA_ADDR, BOOT_DATA_MIRROR_ADDR, CONFIG_ADDR, DATA_ADDR,
+    DATA_INFO_ADDR, DATA_MAX_IMAGE_SIZE, FLASH_BASE, FLASH_SECTOR_SIZE, FW_A_ADDR, FW_BANK_SIZE,
+    FW_B_ADDR, FW_GOLD_ADDR, FW_GOLD_SIZE, GOLDEN_INFO_ADDR, GOLDEN_MAX_IMAGE_SIZE,
+    MAX_FLASH_REGIONS, SELFTEST_SCRATCH_ADDR, WEAR_STATS_ADDR,
+};
+
+const FLASH_END: u32 = FLASH_BASE + 2 * 1024 * 1024;
+
+fn region(start: u32, len: u32, kind: FlashRegionKind) -> FlashRegion {
+    FlashRegion {
+        start,
+        len,
+        used: 0,
+        kind,
+    }
+}
+
+/// The regions a bootloader without the golden bank assigns.
+fn default_layout() -> Vec<FlashRegion> {
+    vec![
+        region(FLASH_BASE, 0x100, FlashRegionKind::Boot2),
+        region(
+            FLASH_BASE + 0x100,
+            FW_A_ADDR - FLASH_BASE - 0x100,
+            FlashRegionKind::Bootloader,
+        ),
+        region(FW_A_ADDR, FW_BANK_SIZE, FlashRegionKind::BankA),
+        region(FW_B_ADDR, FW_BANK_SIZE, FlashRegionKind::BankB),
+        region(BOOT_DATA_ADDR, FLASH_SECTOR_SIZE, FlashRegionKind::BootData),
+        region(
+            WEAR_STATS_ADDR,
+            FLASH_SECTOR_SIZE,
+            FlashRegionKind::WearStats,
+        ),
+    ]
+}
+
+fn assert_covers_flash(map: &[FlashRegion]) {
+    assert_eq!(map.first().unwrap().start, FLASH_BASE);
+    for pair in map.windows(2) {
+        assert_eq!(pair[0].start + pair[0].len, pair[1].start, "{:?}", pair);
+    }
+    let last = map.last().unwrap();
+    assert_eq!(last.start + last.len, FLASH_END);
+}
+
+#[test]
+fn test_default_layout_ends_with_free_space() {
+    let map = with_free_gaps(&default_layout(), FLASH_BASE, FLASH_END);
+
+    assert_eq!(map.len(), 7);
+    assert_eq!(
+        map[6],
+        region(
+            FW_GOLD_ADDR,
+            FLASH_END - FW_GOLD_ADDR,
+            FlashRegionKind::Free
+        )
+    );
+    assert_covers_flash(&map);
+}
+
+#[test]
+fn test_data_and_config_sit_in_free_space_without_golden_bank() {
+    let mut layout = default_layout();
+    layout.extend([
+        region(DATA_ADDR, DATA_MAX_IMAGE_SIZE, FlashRegionKind::Data),
+        region(
+            SELFTEST_SCRATCH_ADDR,
+            FLASH_SECTOR_SIZE,
+            FlashRegionKind::SelfTest,
+        ),
+        region(DATA_INFO_ADDR, FLASH_SECTOR_SIZE, FlashRegionKind::Data),
+        region(
+            BOOT_DATA_MIRROR_ADDR,
+            FLASH_SECTOR_SIZE,
+            FlashRegionKind::BootData,
+        ),
+        region(CONFIG_ADDR, FLASH_SECTOR_SIZE, FlashRegionKind::Config),
+    ]);
+    let map = with_free_gaps(&layout, FLASH_BASE, FLASH_END);
+
+    let kinds: Vec<_> = map[6..].iter().map(|r| r.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            FlashRegionKind::Free,
+            FlashRegionKind::Data,
+            FlashRegionKind::SelfTest,
+            FlashRegionKind::Data,
+            FlashRegionKind::BootData,
+            FlashRegionKind::Config,
+            FlashRegionKind::Free,
+        ]
+    );
+    assert_covers_flash(&map);
+}
+
+#[test]
+fn test_golden_layout_fills_flash() {
+    let mut layout = default_layout();
+    layout.extend([
+        region(FW_GOLD_ADDR, GOLDEN_MAX_IMAGE_SIZE, FlashRegionKind::Golden),
+        region(DATA_ADDR, DATA_MAX_IMAGE_SIZE, FlashRegionKind::Data),
+        region(
+            SELFTEST_SCRATCH_ADDR,
+            FLASH_SECTOR_SIZE,
+            FlashRegionKind::SelfTest,
+        ),
+        region(DATA_INFO_ADDR, FLASH_SECTOR_SIZE, FlashRegionKind::Data),
+        region(
+            BOOT_DATA_MIRROR_ADDR,
+            FLASH_SECTOR_SIZE,
+            FlashRegionKind::BootData,
+        ),
+        region(CONFIG_ADDR, FLASH_SECTOR_SIZE, FlashRegionKind::Config),
+        region(GOLDEN_INFO_ADDR, FLASH_SECTOR_SIZE, FlashRegionKind::Golden),
+    ]);
+    let map = with_free_gaps(&layout, FLASH_BASE, FLASH_END);
+
+    // The golden bank, with the data partition, the BootData mirror and the
+    // config sector in its tail, runs to the end of flash: nothing is free
+    assert_eq!(map.len(), 13);
+    assert!(map.iter().all(|r| r.kind != FlashRegionKind::Free));
+    assert_covers_flash(&map);
+    assert_eq!(
+        GOLDEN_INFO_ADDR + FLASH_SECTOR_SIZE,
+        FW_GOLD_ADDR + FW_GOLD_SIZE
+    );
+}
+
+#[test]
+fn test_gaps_between_regions_are_free() {
+    let layout = [
+        region(FLASH_BASE + 0x1000, 0x1000, FlashRegionKind::BankA),
+        region(FLASH_BASE + 0x3000, 0x1000, FlashRegionKind::BankB),
+    ];
+    let map = with_free_gaps(&layout, FLASH_BASE, FLASH_BASE + 0x4000);
+
+    let kinds: Vec<_> = map.iter().map(|r| r.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            FlashRegionKind::Free,
+            FlashRegionKind::BankA,
+            FlashRegionKind::Free,
+            FlashRegionKind::BankB,
+        ]
+    );
+    assert_eq!(map[2].start, FLASH_BASE + 0x2000);
+    assert_eq!(map[2].len, 0x1000);
+}
+
+#[test]
+fn test_used_is_kept() {
+    let mut layout = default_layout();
+    layout[2].used = 12_345;
+    let map = with_free_gaps(&layout, FLASH_BASE, FLASH_END);
+
+    assert_eq!(map[2].used, 12_345);
+}
+
+#[test]
+fn test_overlapping_region_gets_no_gap() {
+    let layout = [
+        region(FLASH_BASE, 0x2000, FlashRegionKind::BankA),
+        region(FLASH_BASE + 0x1000, 0x2000, FlashRegionKind::BankB),
+    ];
+    let map = with_free_gaps(&layout, FLASH_BASE, FLASH_BASE + 0x3000);
+
+    assert_eq!(map.len(), 2);
+}
+
+#[test]
+fn test_too_many_regions_are_truncated() {
+    let layout: Vec<_> = (0..MAX_FLASH_REGIONS as u32)
+        .map(|i| region(FLASH_BASE + i * 0x2000, 0x1000, FlashRegionKind::BankA))
+        .collect();
+    let map = with_free_gaps(&layout, FLASH_BASE, FLASH_END);
+
+    assert_eq!(map.len(), MAX_FLASH_REGIONS);
+}
@@ -0,0 +1,922 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Unit tests for the flash trait, run against the in-memory flash: the
+//! upload state machine from `StartUpdate` to `FinishUpdate`, paged or
+//! whole, and the BootData mirror.
+
+#![cfg(feature = "std")]
+
+use crispy_common::boot_journal;
+use crispy_common::bootloader_image::BOOT2_SIZE;
+use crispy_common::crc32::CrcAlgorithm;
+use crispy_common::flash_bounds::{FlashBounds, RangeError};
+use crispy_common::flash_ops::{self, ErasePlan, FlashError, FlashOps, MemFlash};
+use crispy_common::protocol::{
+    AckStatus, BootData, ProgressPhase, Semver, BOOTLOADER_REGION_SIZE, BOOT_FLAG_ANTI_ROLLBACK,
+    FLASH_BASE, FLASH_PAGE_SIZE, FLASH_SECTOR_SIZE, FW_A_ADDR, FW_BANK_SIZE, FW_B_ADDR,
+    MAX_DATA_BLOCK_SIZE, SELFTEST_ERASE_FAILED, SELFTEST_PASSED, SELFTEST_SCRATCH_ADDR,
+    SELFTEST_VERIFY_FAILED,
+};
+use crispy_common::update::{ImageLimits, UpdateError, Upload};
+use crispy_common::vector_table::VectorTableError;
+
+const FLASH_SIZE: u32 = 2 * 1024 * 1024;
+
+/// The layout of `linker_scripts/bootloader_rp2040.x`.
+const BOUNDS: FlashBounds = FlashBounds {
+    boot2_end: BOOT2_SIZE as u32,
+    bootloader_end: BOOTLOADER_REGION_SIZE,
+    flash_size: FLASH_SIZE,
+};
+
+const BANK_A: u32 = FW_A_ADDR - FLASH_BASE;
+const BANK_B: u32 = FW_B_ADDR - FLASH_BASE;
+
+fn image(len: usize, seed: u8) -> Vec<u8> {
+    (0..len)
+        .map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed))
+        .collect()
+}
+
+/// `fw_rp2040.x` geometry, as the bootloader passes it to the update code.
+const LIMITS: ImageLimits = ImageLimits {
+    bootloader_version: 0,
+    ram_base: 0x2000_0000,
+    copy_size: 0x3_0000,
+    ram_end: 0x2004_2000,
+    min_stack_headroom: 1024,
+};
+
+/// A RAM buffer the images of these tests fit in whole.
+const WHOLE_RAM: usize = 4 * FLASH_SECTOR_SIZE as usize;
+
+/// The smallest RAM buffer an upload may page through: two sectors and a
+/// block.
+const PAGING_RAM: usize = 2 * FLASH_SECTOR_SIZE as usize + MAX_DATA_BLOCK_SIZE;
+
+/// `len` bytes of a RAM image whose vector table passes the boot checks.
+fn ram_image(len: usize, seed: u8) -> Vec<u8> {
+    let mut data = image(len, seed);
+    data[..4].copy_from_slice(&0x2003_C000u32.to_le_bytes());
+    data[4..8].copy_from_slice(&0x2000_00C1u32.to_le_bytes());
+    data
+}
+
+/// `StartUpdate` and the `DataBlock`s of `data` to `bank` through `ram`,
+/// as the bootloader receives them.
+fn receive(
+    flash: &mut MemFlash,
+    ram: &mut [u8],
+    bank: u8,
+    data: &[u8],
+    version: u32,
+) -> Result<Upload, UpdateError> {
+    let crc = CrcAlgorithm::IsoHdlc.checksum(data);
+    let mut upload = Upload::start(flash, bank, data.len() as u32, crc, version, 0)?;
+    for (i, block) in data.chunks(MAX_DATA_BLOCK_SIZE).enumerate() {
+        let offset = (i * MAX_DATA_BLOCK_SIZE) as u32;
+        upload.data_block(flash, ram, offset, block, &LIMITS, |_, _, _| {})?;
+    }
+    Ok(upload)
+}
+
+/// `FinishUpdate` of a received upload: check, write, verify, record.
+fn finish(
+    flash: &mut MemFlash,
+    ram: &[u8],
+    upload: &Upload,
+    activate: bool,
+) -> Result<u32, UpdateError> {
+    let image = upload.finish(flash, ram, &LIMITS, activate)?;
+    let written = image.write(flash, ram, |_, _, _| {});
+    image.complete(flash, written, |_, _, _| {})
+}
+
+/// A whole update of `bank` to `data`, received in RAM of `ram_len` bytes.
+fn update(
+    flash: &mut MemFlash,
+    ram_len: usize,
+    bank: u8,
+    data: &[u8],
+    version: u32,
+    activate: bool,
+) -> Result<u32, UpdateError> {
+    let mut ram = vec![0u8; ram_len];
+    let upload = receive(flash, &mut ram, bank, data, version)?;
+    finish(flash, &ram, &upload, activate)
+}
+
+// --- Emulated flash ---
+
+#[test]
+fn test_new_flash_is_erased_and_has_no_boot_data() {
+    let flash = MemFlash::new(BOUNDS);
+
+    assert_eq!(flash.bytes.len(), FLASH_SIZE as usize);
+    assert!(flash.bytes.iter().all(|&b| b == 0xFF));
+    assert_eq!(flash.read_boot_data(), BootData::default_new());
+}
+
+#[test]
+fn test_program_only_clears_bits() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let mut page = [0x0Fu8; FLASH_PAGE_SIZE as usize];
+    flash.program(BANK_A, &page).unwrap();
+
+    // 0xF0 over 0x0F would need bits set again: the page reads back wrong.
+    page.fill(0xF0);
+    assert_eq!(
+        flash.program(BANK_A, &page),
+        Err(FlashError::VerifyFailed { offset: BANK_A })
+    );
+    assert!(flash.slice(BANK_A, FLASH_PAGE_SIZE).iter().all(|&b| b == 0));
+
+    flash.erase(BANK_A, FLASH_SECTOR_SIZE).unwrap();
+    flash.program(BANK_A, &page).unwrap();
+    assert!(flash
+        .slice(BANK_A, FLASH_PAGE_SIZE)
+        .iter()
+        .all(|&b| b == 0xF0));
+}
+
+#[test]
+fn test_erased_bytes_of_a_page_are_not_compared() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let mut first = [0xFFu8; FLASH_PAGE_SIZE as usize];
+    first[..4].copy_from_slice(&[1, 2, 3, 4]);
+    flash.program(BANK_A, &first).unwrap();
+
+    let mut second = [0xFFu8; FLASH_PAGE_SIZE as usize];
+    second[4..8].copy_from_slice(&[5, 6, 7, 8]);
+
+    assert_eq!(flash.program(BANK_A, &second), Ok(()));
+    assert_eq!(flash.slice(BANK_A, 8), &[1, 2, 3, 4, 5, 6, 7, 8]);
+}
+
+#[test]
+fn test_protected_ranges_are_refused_untouched() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let page = [0u8; FLASH_PAGE_SIZE as usize];
+
+    assert_eq!(
+        flash.erase(0, FLASH_SECTOR_SIZE),
+        Err(FlashError::OutOfRange(RangeError::Boot2))
+    );
+    assert_eq!(
+        flash.program(BOOTLOADER_REGION_SIZE - FLASH_PAGE_SIZE, &page),
+        Err(FlashError::OutOfRange(RangeError::Bootloader))
+    );
+    assert_eq!(
+        flash.program(FLASH_SIZE, &page),
+        Err(FlashError::OutOfRange(RangeError::PastEnd))
+    );
+    assert_eq!(flash.erases, 0);
+    assert!(flash.bytes.iter().all(|&b| b == 0xFF));
+}
+
+#[test]
+fn test_erase_plan_checks_the_whole_range_first() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let last = FLASH_SIZE - FLASH_SECTOR_SIZE;
+
+    assert_eq!(
+        ErasePlan::new(&flash, last, 2 * FLASH_SECTOR_SIZE),
+        Err(FlashError::OutOfRange(RangeError::PastEnd))
+    );
+
+    // Rounded up to whole sectors, one per step.
+    let mut plan = ErasePlan::new(&flash, BANK_A, FLASH_SECTOR_SIZE + 1).unwrap();
+    assert_eq!(plan.step(&mut flash), Ok(true));
+    assert_eq!(plan.step(&mut flash), Ok(false));
+    assert_eq!(plan.step(&mut flash), Ok(false));
+    assert_eq!(flash.erases, 2);
+}
+
+#[test]
+fn test_crc_reads_back_what_was_written() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let data = image(3000, 7);
+    flash_ops::write_image(&mut flash, BANK_B, &data, true, |_, _, _| {}).unwrap();
+
+    for algo in [CrcAlgorithm::IsoHdlc, CrcAlgorithm::Bzip2] {
+        assert_eq!(
+            flash.compute_crc(algo, BANK_B, 3000, |_| {}),
+            algo.checksum(&data)
+        );
+    }
+}
+
+// --- Region and bank erases ---
+
+/// Fill flash with a non-erased pattern, so erased bytes stand out.
+fn programmed_flash() -> MemFlash {
+    let mut flash = MemFlash::new(BOUNDS);
+    flash.bytes.fill(0x00);
+    flash
+}
+
+fn erased(flash: &MemFlash, offset: u32, len: u32) -> bool {
+    flash.bytes[offset as usize..(offset + len) as usize]
+        .iter()
+        .all(|&b| b == 0xFF)
+}
+
+#[test]
+fn test_erase_region_rounds_up_to_whole_sectors() {
+    for (len, sectors) in [
+        (1, 1),
+        (FLASH_SECTOR_SIZE - 1, 1),
+        (FLASH_SECTOR_SIZE, 1),
+        (FLASH_SECTOR_SIZE + 1, 2),
+        (3 * FLASH_SECTOR_SIZE, 3),
+    ] {
+        let mut flash = programmed_flash();
+        let mut reported = Vec::new();
+        flash_ops::erase_region(&mut flash, FW_A_ADDR, len, |done, total| {
+            reported.push((done, total))
+        })
+        .unwrap();
+
+        let total = sectors * FLASH_SECTOR_SIZE;
+        assert_eq!(flash.erases, sectors, "len {}", len);
+        assert!(erased(&flash, BANK_A, total));
+        assert_eq!(flash.bytes[(BANK_A + total) as usize], 0x00);
+        assert_eq!(
+            reported,
+            (1..=sectors)
+                .map(|n| (n * FLASH_SECTOR_SIZE, total))
+                .collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn test_erase_region_of_nothing_erases_nothing() {
+    let mut flash = programmed_flash();
+    flash_ops::erase_region(&mut flash, FW_A_ADDR, 0, |_, _| {}).unwrap();
+    assert_eq!(flash.erases, 0);
+}
+
+#[test]
+fn test_erase_region_refuses_a_misaligned_start() {
+    let mut flash = programmed_flash();
+    assert_eq!(
+        flash_ops::erase_region(&mut flash, FW_B_ADDR + FLASH_PAGE_SIZE, 1, |_, _| {}),
+        Err(FlashError::Misaligned {
+            offset: BANK_B + FLASH_PAGE_SIZE
+        })
+    );
+    assert_eq!(flash.erases, 0);
+}
+
+#[test]
+fn test_erase_region_refuses_addresses_outside_flash() {
+    let mut flash = programmed_flash();
+    for (addr, len) in [(0, FLASH_SECTOR_SIZE), (FW_A_ADDR, u32::MAX)] {
+        assert_eq!(
+            flash_ops::erase_region(&mut flash, addr, len, |_, _| {}),
+            Err(FlashError::OutOfRange(RangeError::PastEnd))
+        );
+    }
+    assert_eq!(flash.erases, 0);
+}
+
+#[test]
+fn test_erasing_the_end_of_bank_a_leaves_bank_b_alone() {
+    let mut flash = programmed_flash();
+    let last_sector = FW_A_ADDR + FW_BANK_SIZE - FLASH_SECTOR_SIZE;
+    flash_ops::erase_region(&mut flash, last_sector, FLASH_SECTOR_SIZE, |_, _| {}).unwrap();
+
+    assert!(erased(
+        &flash,
+        BANK_B - FLASH_SECTOR_SIZE,
+        FLASH_SECTOR_SIZE
+    ));
+    assert!(
+        flash.bytes[BANK_B as usize..(BANK_B + FLASH_PAGE_SIZE) as usize]
+            .iter()
+            .all(|&b| b == 0x00)
+    );
+}
+
+#[test]
+fn test_erase_bank_erases_exactly_that_bank() {
+    for bank in [0, 1] {
+        let mut flash = programmed_flash();
+        flash_ops::erase_bank(&mut flash, bank, |_, _| {}).unwrap();
+
+        let (addr, size) = flash_ops::bank_range(bank).unwrap();
+        let offset = addr - FLASH_BASE;
+        assert_eq!(size, FW_BANK_SIZE);
+        assert_eq!(flash.erases, FW_BANK_SIZE / FLASH_SECTOR_SIZE);
+        assert!(erased(&flash, offset, size));
+        assert_eq!(flash.bytes[offset as usize - 1], 0x00);
+        assert_eq!(flash.bytes[(offset + size) as usize], 0x00);
+    }
+}
+
+#[test]
+fn test_erase_bank_refuses_other_banks() {
+    let mut flash = programmed_flash();
+    for bank in [2, 3, 0xFF] {
+        assert_eq!(
+            flash_ops::erase_bank(&mut flash, bank, |_, _| {}),
+            Err(FlashError::InvalidBank(bank))
+        );
+    }
+    assert_eq!(flash.erases, 0);
+}
+
+// --- Image writes ---
+
+#[test]
+fn test_write_image_pads_the_last_page_and_leaves_the_rest_erased() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let data = image(2 * FLASH_SECTOR_SIZE as usize + 100, 1);
+    flash.bytes[BANK_A as usize..(BANK_A + 3 * FLASH_SECTOR_SIZE) as usize].fill(0x00);
+
+    flash_ops::write_image(&mut flash, BANK_A, &data, true, |_, _, _| {}).unwrap();
+
+    let end = BANK_A as usize + data.len();
+    assert_eq!(&flash.bytes[BANK_A as usize..end], data.as_slice());
+    assert!(flash.bytes[end..(BANK_A + 3 * FLASH_SECTOR_SIZE) as usize]
+        .iter()
+        .all(|&b| b == 0xFF));
+    assert_eq!(flash.erases, 3);
+}
+
+#[test]
+fn test_write_image_reports_every_sector_then_every_batch() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let data = image(FLASH_SECTOR_SIZE as usize + 300, 2);
+    let mut reports = Vec::new();
+
+    flash_ops::write_image(&mut flash, BANK_A, &data, true, |phase, done, total| {
+        reports.push((phase, done, total))
+    })
+    .unwrap();
+
+    let len = data.len() as u32;
+    let full_pages = len / FLASH_PAGE_SIZE * FLASH_PAGE_SIZE;
+    assert_eq!(
+        reports,
+        [
+            (
+                ProgressPhase::Erase,
+                FLASH_SECTOR_SIZE,
+                2 * FLASH_SECTOR_SIZE
+            ),
+            (
+                ProgressPhase::Erase,
+                2 * FLASH_SECTOR_SIZE,
+                2 * FLASH_SECTOR_SIZE
+            ),
+            (ProgressPhase::Program, FLASH_SECTOR_SIZE, len),
+            (ProgressPhase::Program, full_pages, len),
+            (ProgressPhase::Program, len, len),
+        ]
+    );
+}
+
+#[test]
+fn test_write_image_into_the_bootloader_erases_nothing() {
+    let mut flash = MemFlash::new(BOUNDS);
+    flash.bytes[..BOOTLOADER_REGION_SIZE as usize].fill(0x5A);
+
+    assert_eq!(
+        flash_ops::write_image(
+            &mut flash,
+            BOOTLOADER_REGION_SIZE - FLASH_SECTOR_SIZE,
+            &image(2 * FLASH_SECTOR_SIZE as usize, 3),
+            true,
+            |_, _, _| {},
+        ),
+        Err(FlashError::OutOfRange(RangeError::Bootloader))
+    );
+    assert_eq!(flash.erases, 0);
+    assert!(flash.bytes[..BOOTLOADER_REGION_SIZE as usize]
+        .iter()
+        .all(|&b| b == 0x5A));
+}
+
+// --- BootData mirror ---
+
+fn confirmed_boot_data() -> BootData {
+    let mut bd = BootData::default_new();
+    bd.confirmed = 1;
+    bd.size_a = 4096;
+    bd.crc_a = 0x1234_5678;
+    bd
+}
+
+#[test]
+fn test_boot_data_is_written_to_both_copies() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let bd = confirmed_boot_data();
+
+    flash.write_boot_data(&bd).unwrap();
+
+    for offset in [
+        flash_ops::BOOT_DATA_OFFSET,
+        flash_ops::BOOT_DATA_MIRROR_OFFSET,
+    ] {
+        assert_eq!(boot_journal::read(flash.boot_data_sector(offset)), bd);
+    }
+}
+
+#[test]
+fn test_erased_primary_reads_the_mirror_until_restored() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let bd = confirmed_boot_data();
+    flash.write_boot_data(&bd).unwrap();
+    flash
+        .erase(flash_ops::BOOT_DATA_OFFSET, FLASH_SECTOR_SIZE)
+        .unwrap();
+
+    assert_eq!(flash.read_boot_data(), bd);
+    assert_eq!(flash.restore_boot_data(), Ok(true));
+
+    let primary = flash.boot_data_sector(flash_ops::BOOT_DATA_OFFSET);
+    assert_eq!(boot_journal::read(primary), bd);
+    assert_eq!(flash.restore_boot_data(), Ok(false));
+}
+
+#[test]
+fn test_corrupt_primary_is_restored_after_its_last_slot() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let bd = confirmed_boot_data();
+    flash.write_boot_data(&bd).unwrap();
+    // A flipped bit in the only record: its CRC fails.
+    flash.bytes[(flash_ops::BOOT_DATA_OFFSET + 8) as usize] ^= 0x01;
+    assert!(!boot_journal::read(flash.boot_data_sector(flash_ops::BOOT_DATA_OFFSET)).is_valid());
+
+    assert_eq!(flash.restore_boot_data(), Ok(true));
+
+    let primary = flash.boot_data_sector(flash_ops::BOOT_DATA_OFFSET);
+    let newest = boot_journal::newest(primary).unwrap();
+    assert_eq!((newest.slot, newest.boot_data), (1, bd));
+    assert_eq!(flash.erases, 0);
+}
+
+#[test]
+fn test_restore_leaves_a_valid_primary_alone() {
+    let mut flash = MemFlash::new(BOUNDS);
+    flash.write_boot_data(&confirmed_boot_data()).unwrap();
+    let before = flash.bytes.clone();
+
+    assert_eq!(flash.restore_boot_data(), Ok(false));
+    assert_eq!(flash.bytes, before);
+}
+
+#[test]
+fn test_nothing_to_restore_from_an_erased_mirror() {
+    let mut flash = MemFlash::new(BOUNDS);
+
+    assert_eq!(flash.restore_boot_data(), Ok(false));
+    assert_eq!(flash.read_boot_data(), BootData::default_new());
+}
+
+#[test]
+fn test_power_loss_before_the_mirror_write_keeps_the_newer_primary() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let old = confirmed_boot_data();
+    flash.write_boot_data(&old).unwrap();
+    let mut new = old;
+    new.active_bank = 1;
+
+    // The primary record is programmed; power goes before the mirror's.
+    flash.power_budget = Some(1);
+    flash.write_boot_data(&new).unwrap();
+
+    assert_eq!(flash.read_boot_data(), new);
+    let mirror = flash.boot_data_sector(flash_ops::BOOT_DATA_MIRROR_OFFSET);
+    assert_eq!(boot_journal::read(mirror), old);
+}
+
+/// Put `count` journal records of `bd`, numbered from 1, in the erased
+/// journal sector at `offset`.
+fn fill_journal(flash: &mut MemFlash, offset: u32, bd: &BootData, count: u32) {
+    for slot in 0..count {
+        let start = (offset + slot * boot_journal::RECORD_SIZE) as usize;
+        flash.bytes[start..start + boot_journal::RECORD_SIZE as usize]
+            .copy_from_slice(&boot_journal::encode(bd, slot + 1));
+    }
+}
+
+/// Write `new` over `flash` again and again, losing power after 0, 1, 2, ...
+/// erases and programs until one write completes, and check that every
+/// restart reads the BootData from before the write or `new`, before and
+/// after the bootloader's restore from the mirror.
+fn assert_write_survives_power_loss(flash: &MemFlash, new: &BootData) {
+    let old = flash.read_boot_data();
+    for budget in 0.. {
+        let mut restarted = flash.clone();
+        restarted.power_budget = Some(budget);
+        restarted.write_boot_data(new).unwrap();
+        let completed = restarted.power_budget != Some(0);
+        restarted.power_budget = None;
+
+        let read = restarted.read_boot_data();
+        assert!(
+            read == old || read == *new,
+            "power lost after {} operations: {:?}",
+            budget,
+            read
+        );
+        restarted.restore_boot_data().unwrap();
+        assert_eq!(restarted.read_boot_data(), read);
+        if completed {
+            assert_eq!(read, *new);
+            return;
+        }
+    }
+}
+
+#[test]
+fn test_power_loss_after_any_boot_data_operation_keeps_old_or_new() {
+    let old = confirmed_boot_data();
+    let mut new = old;
+    new.active_bank = 1;
+    let full = boot_journal::RECORDS_PER_SECTOR;
+
+    // Record counts of the primary and the mirror: both with room, either or
+    // both full (erased by the write), and either without a record.
+    for (primary, mirror) in [
+        (1, 1),
+        (5, 9),
+        (full, full),
+        (full, 9),
+        (9, full),
+        (full, 0),
+        (0, full),
+        (5, 0),
+        (0, 5),
+    ] {
+        let mut flash = MemFlash::new(BOUNDS);
+        fill_journal(&mut flash, flash_ops::BOOT_DATA_OFFSET, &old, primary);
+        fill_journal(&mut flash, flash_ops::BOOT_DATA_MIRROR_OFFSET, &old, mirror);
+        assert_eq!(flash.read_boot_data(), old);
+
+        assert_write_survives_power_loss(&flash, &new);
+    }
+}
+
+#[test]
+fn test_mirror_without_a_record_is_written_before_a_full_primary_is_erased() {
+    // As after an upgrade from a bootloader without the mirror, whose sector
+    // still holds older data.
+    let old = confirmed_boot_data();
+    let mut flash = MemFlash::new(BOUNDS);
+    fill_journal(
+        &mut flash,
+        flash_ops::BOOT_DATA_OFFSET,
+        &old,
+        boot_journal::RECORDS_PER_SECTOR,
+    );
+    let mirror = flash_ops::BOOT_DATA_MIRROR_OFFSET as usize;
+    flash.bytes[mirror..mirror + 16].fill(0x00);
+    let mut new = old;
+    new.active_bank = 1;
+
+    // Power goes after the mirror's record, before the primary's erase.
+    flash.power_budget = Some(1);
+    flash.write_boot_data(&new).unwrap();
+
+    assert_eq!(flash.read_boot_data(), old);
+    let mirror = flash.boot_data_sector(flash_ops::BOOT_DATA_MIRROR_OFFSET);
+    assert_eq!(boot_journal::read(mirror), new);
+    assert_write_survives_power_loss(&flash, &new);
+}
+
+// --- StartUpdate, DataBlock, FinishUpdate ---
+
+#[test]
+fn test_update_sequence_leaves_exact_flash_and_boot_data() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let data = image(5000, 9);
+    let version = Semver::new(1, 2, 3).unwrap().packed();
+
+    update(&mut flash, WHOLE_RAM, 0, &data, version, true).unwrap();
+
+    let mut expected_flash = vec![0xFFu8; FLASH_SIZE as usize];
+    expected_flash[BANK_A as usize..BANK_A as usize + data.len()].copy_from_slice(&data);
+    for offset in [
+        flash_ops::BOOT_DATA_OFFSET,
+        flash_ops::BOOT_DATA_MIRROR_OFFSET,
+    ] {
+        let boot_data = offset as usize..(offset + FLASH_SECTOR_SIZE) as usize;
+        expected_flash[boot_data.clone()].copy_from_slice(&flash.bytes[boot_data]);
+    }
+    assert_eq!(flash.bytes, expected_flash);
+
+    let mut expected = BootData::default_new();
+    expected.version_a = version;
+    expected.crc_a = CrcAlgorithm::IsoHdlc.checksum(&data);
+    expected.size_a = 5000;
+    assert_eq!(flash.read_boot_data(), expected);
+    // The first journal record, written without erasing the sector.
+    let sector = flash.slice(flash_ops::BOOT_DATA_OFFSET, FLASH_SECTOR_SIZE);
+    let newest = crispy_common::boot_journal::newest(sector.try_into().unwrap()).unwrap();
+    assert_eq!((newest.slot, newest.boot_data), (0, expected));
+    // Two bank sectors; both BootData sectors were already erased.
+    assert_eq!(flash.erases, 2);
+}
+
+#[test]
+fn test_update_of_the_other_bank_without_activating() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let a = image(4096, 1);
+    let b = image(9000, 2);
+    update(&mut flash, WHOLE_RAM, 0, &a, 1, true).unwrap();
+    let mut bd = flash.read_boot_data();
+    bd.confirmed = 1;
+    flash.write_boot_data(&bd).unwrap();
+
+    update(&mut flash, WHOLE_RAM, 1, &b, 2, false).unwrap();
+
+    let bd = flash.read_boot_data();
+    assert_eq!((bd.active_bank, bd.confirmed), (0, 1));
+    assert_eq!((bd.version_a, bd.size_a), (1, 4096));
+    assert_eq!((bd.version_b, bd.size_b), (2, 9000));
+    assert_eq!(bd.crc_b, CrcAlgorithm::IsoHdlc.checksum(&b));
+    assert_eq!(flash.slice(BANK_A, 4096), a.as_slice());
+    assert_eq!(flash.slice(BANK_B, 9000), b.as_slice());
+}
+
+#[test]
+fn test_rewriting_the_active_bank_restarts_its_trial() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let mut bd = BootData::default_new();
+    bd.active_bank = 1;
+    bd.confirmed = 1;
+    bd.boot_attempts = 2;
+    flash.write_boot_data(&bd).unwrap();
+
+    update(&mut flash, WHOLE_RAM, 1, &image(600, 4), 3, false).unwrap();
+
+    let bd = flash.read_boot_data();
+    assert_eq!((bd.active_bank, bd.confirmed, bd.boot_attempts), (1, 0, 0));
+}
+
+#[test]
+fn test_update_raises_the_anti_rollback_floor() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let mut bd = BootData::default_new();
+    bd.flags |= BOOT_FLAG_ANTI_ROLLBACK;
+    bd.min_version = 5;
+    flash.write_boot_data(&bd).unwrap();
+
+    update(&mut flash, WHOLE_RAM, 1, &image(256, 5), 7, true).unwrap();
+
+    assert_eq!(flash.read_boot_data().min_version, 7);
+}
+
+#[test]
+fn test_power_loss_during_the_write_keeps_boot_data() {
+    let mut flash = MemFlash::new(BOUNDS);
+    update(&mut flash, WHOLE_RAM, 0, &image(4096, 1), 1, true).unwrap();
+    let before = flash.read_boot_data();
+
+    // Power goes after the bank's sectors are erased and one batch is
+    // programmed; the bootloader restarts and the verify pass never ran.
+    flash.power_budget = Some(4);
+    let result = update(&mut flash, WHOLE_RAM, 1, &image(3 * 4096, 2), 2, true);
+
+    assert_eq!(result, Err(UpdateError::CrcMismatchFlash));
+    assert_eq!(flash.read_boot_data(), before);
+    assert!(flash
+        .slice(BANK_B + FLASH_SECTOR_SIZE, 2 * FLASH_SECTOR_SIZE)
+        .iter()
+        .all(|&b| b == 0xFF));
+}
+
+#[test]
+fn test_paged_image_becomes_valid_only_with_its_first_sector() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let old = ram_image(4096, 1);
+    update(&mut flash, WHOLE_RAM, 0, &old, 1, true).unwrap();
+    let bd = flash.read_boot_data();
+    let data = ram_image(10 * FLASH_SECTOR_SIZE as usize + 300, 6);
+
+    let mut ram = vec![0u8; PAGING_RAM];
+    let upload = receive(&mut flash, &mut ram, 0, &data, 2).unwrap();
+
+    // Everything but the first sector and what is still buffered is in
+    // the bank, whose first sector paging erased.
+    assert!(upload.paged_out > 0);
+    let paged = (FLASH_SECTOR_SIZE + upload.paged_out) as usize;
+    assert_eq!(
+        flash.slice(BANK_A + FLASH_SECTOR_SIZE, upload.paged_out),
+        &data[FLASH_SECTOR_SIZE as usize..paged]
+    );
+    assert!(flash
+        .slice(BANK_A, FLASH_SECTOR_SIZE)
+        .iter()
+        .all(|&b| b == 0xFF));
+    assert_eq!(flash.read_boot_data(), bd);
+
+    let crc = finish(&mut flash, &ram, &upload, true).unwrap();
+
+    assert_eq!(crc, CrcAlgorithm::IsoHdlc.checksum(&data));
+    assert_eq!(flash.slice(BANK_A, data.len() as u32), data.as_slice());
+    let bd = flash.read_boot_data();
+    assert_eq!(
+        (bd.version_a, bd.size_a, bd.crc_a),
+        (2, data.len() as u32, crc)
+    );
+}
+
+#[test]
+fn test_power_loss_while_writing_a_paged_image_leaves_its_first_sector_erased() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let data = ram_image(6 * FLASH_SECTOR_SIZE as usize, 3);
+    let mut ram = vec![0u8; PAGING_RAM];
+    let upload = receive(&mut flash, &mut ram, 1, &data, 1).unwrap();
+    let bd = flash.read_boot_data();
+
+    // Power goes while the tail is written, before the first sector.
+    flash.power_budget = Some(1);
+    let result = finish(&mut flash, &ram, &upload, true);
+
+    assert_eq!(result, Err(UpdateError::CrcMismatchFlash));
+    assert!(flash
+        .slice(BANK_B, FLASH_SECTOR_SIZE)
+        .iter()
+        .all(|&b| b == 0xFF));
+    assert_eq!(flash.read_boot_data(), bd);
+}
+
+#[test]
+fn test_ram_image_past_the_copy_window_is_refused_before_paging() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let old = ram_image(4096, 1);
+    update(&mut flash, WHOLE_RAM, 0, &old, 1, true).unwrap();
+    let erases = flash.erases;
+
+    let limits = ImageLimits {
+        copy_size: 4 * FLASH_SECTOR_SIZE,
+        ..LIMITS
+    };
+    let data = ram_image(5 * FLASH_SECTOR_SIZE as usize, 2);
+    let crc = CrcAlgorithm::IsoHdlc.checksum(&data);
+    let mut ram = vec![0u8; PAGING_RAM];
+    let mut upload = Upload::start(&flash, 0, data.len() as u32, crc, 2, 0).unwrap();
+    let result = data
+        .chunks(MAX_DATA_BLOCK_SIZE)
+        .enumerate()
+        .try_for_each(|(i, block)| {
+            let offset = (i * MAX_DATA_BLOCK_SIZE) as u32;
+            upload
+                .data_block(&mut flash, &mut ram, offset, block, &limits, |_, _, _| {})
+                .map(|_| ())
+        });
+
+    assert_eq!(
+        result,
+        Err(UpdateError::ImageRejected(VectorTableError::ImageTooLarge))
+    );
+    assert_eq!(upload.paged_out, 0);
+    assert_eq!(flash.erases, erases);
+    assert_eq!(flash.slice(BANK_A, 4096), old.as_slice());
+}
+
+#[test]
+fn test_image_below_the_rollback_floor_is_refused_unwritten() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let mut bd = BootData::default_new();
+    bd.flags |= BOOT_FLAG_ANTI_ROLLBACK;
+    bd.min_version = 5;
+    flash.write_boot_data(&bd).unwrap();
+
+    let result = update(&mut flash, WHOLE_RAM, 1, &ram_image(2048, 4), 4, true);
+
+    assert_eq!(result, Err(UpdateError::VersionTooOld));
+    assert!(!UpdateError::VersionTooOld.keeps_upload());
+    assert_eq!(flash.erases, 0);
+    assert_eq!(flash.read_boot_data(), bd);
+}
+
+#[test]
+fn test_start_refuses_locked_banks_and_oversized_images() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let mut bd = BootData::default_new();
+    bd.set_bank_locked(0, true);
+    flash.write_boot_data(&bd).unwrap();
+
+    assert_eq!(
+        Upload::start(&flash, 0, 4096, 0, 1, 0),
+        Err(UpdateError::BankLocked)
+    );
+    assert_eq!(
+        Upload::start(&flash, 1, FW_BANK_SIZE + 1, 0, 1, 0),
+        Err(UpdateError::BadSize)
+    );
+    assert_eq!(
+        Upload::start(&flash, 2, 4096, 0, 1, 0),
+        Err(UpdateError::InvalidBank)
+    );
+    assert_eq!(
+        Upload::start(&flash, 1, 4096, 0, 1, 0xEE),
+        Err(UpdateError::UnknownCrcAlgorithm)
+    );
+}
+
+#[test]
+fn test_misplaced_blocks_and_early_finish_keep_the_upload() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let data = image(2048, 7);
+    let crc = CrcAlgorithm::IsoHdlc.checksum(&data);
+    let mut ram = vec![0u8; WHOLE_RAM];
+    let mut upload = Upload::start(&flash, 1, 2048, crc, 1, 0).unwrap();
+    let (first, second) = data.split_at(1024);
+    upload
+        .data_block(&mut flash, &mut ram, 0, first, &LIMITS, |_, _, _| {})
+        .unwrap();
+    let before = upload;
+
+    for (offset, block) in [(0, second), (1024, &data[..])] {
+        let err = upload
+            .data_block(&mut flash, &mut ram, offset, block, &LIMITS, |_, _, _| {})
+            .unwrap_err();
+        assert!(err.keeps_upload());
+    }
+    assert_eq!(
+        upload.finish(&flash, &ram, &LIMITS, true),
+        Err(UpdateError::Incomplete)
+    );
+    assert_eq!(upload, before);
+
+    upload
+        .data_block(&mut flash, &mut ram, 1024, second, &LIMITS, |_, _, _| {})
+        .unwrap();
+    assert_eq!(finish(&mut flash, &ram, &upload, true), Ok(crc));
+}
+
+#[test]
+fn test_flash_errors_map_to_ack_status() {
+    assert_eq!(AckStatus::from(FlashError::Busy), AckStatus::Busy);
+    for err in [
+        FlashError::NotInitialized,
+        FlashError::OutOfRange(RangeError::PastEnd),
+        FlashError::VerifyFailed { offset: 0x1000 },
+        FlashError::Misaligned { offset: 3 },
+        FlashError::InvalidBank(2),
+    ] {
+        assert_eq!(AckStatus::from(err), AckStatus::FlashError);
+    }
+}
+
+const SCRATCH: u32 = SELFTEST_SCRATCH_ADDR - FLASH_BASE;
+
+#[test]
+fn test_self_test_passes_and_restores_the_scratch_sector() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let saved = image(FLASH_SECTOR_SIZE as usize, 7);
+    flash_ops::write_image(&mut flash, SCRATCH, &saved, true, |_, _, _| {}).unwrap();
+    let before = flash.bytes.clone();
+
+    assert_eq!(
+        flash_ops::self_test(&mut flash, SCRATCH, &saved),
+        SELFTEST_PASSED
+    );
+    assert_eq!(flash.bytes, before);
+    // Erased for the test and again for the restore.
+    assert_eq!(flash.erases, 3);
+}
+
+#[test]
+fn test_self_test_pattern_differs_between_neighbouring_pages() {
+    let page = FLASH_PAGE_SIZE;
+    assert_eq!(flash_ops::self_test_pattern(0), 0x55);
+    assert_eq!(flash_ops::self_test_pattern(page), 0xAA);
+    assert_ne!(
+        flash_ops::self_test_pattern(1),
+        flash_ops::self_test_pattern(page + 1)
+    );
+}
+
+#[test]
+fn test_self_test_reports_an_erase_outside_the_bounds() {
+    let mut flash = MemFlash::new(BOUNDS);
+    let saved = [0xFF; FLASH_SECTOR_SIZE as usize];
+
+    assert_eq!(
+        flash_ops::self_test(&mut flash, 0, &saved),
+        SELFTEST_ERASE_FAILED
+    );
+    assert_eq!(flash.erases, 0);
+}
+
+#[test]
+fn test_self_test_reports_a_pattern_that_did_not_stick() {
+    // Power lasts for the first erase only, so the pattern is never written.
+    let mut flash = MemFlash::new(BOUNDS);
+    flash.power_budget = Some(1);
+    let saved = [0xFF; FLASH_SECTOR_SIZE as usize];
+
+    assert_eq!(
+        flash_ops::self_test(&mut flash, SCRATCH, &saved),
+        SELFTEST_VERIFY_FAILED
+    );
+}
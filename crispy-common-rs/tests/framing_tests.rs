@@ -0,0 +1,277 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Checks the encoded size bounds that the transport buffers are built on.
+
+use crispy_common::protocol::{
+    cobs_max_encoded_len, AckStatus, BootState, Command, ErrorCode, FirmwareMetadata, FlashRegion,
+    FlashRegionKind, ProgressPhase, Response, ERROR_LOG_LEN, LOG_CHUNK_SIZE,
+    MAX_COMMAND_FRAME_SIZE, MAX_COMMAND_SIZE, MAX_DATA_BLOCK_SIZE, MAX_FLASH_REGIONS,
+    MAX_RESPONSE_FRAME_SIZE, MAX_RESPONSE_SIZE,
+};
+use serde::Serialize;
+
+/// Postcard size and COBS frame size (without the delimiter) of `value`.
+fn encoded_sizes<T: Serialize>(value: &T) -> (usize, usize) {
+    let mut buf = [0u8; 4096];
+    let plain = postcard::to_slice(value, &mut buf).unwrap().len();
+    let framed = postcard::to_slice_cobs(value, &mut buf).unwrap().len() - 1;
+    (plain, framed)
+}
+
+/// `len` copies of `value` in whichever vector a field holds: the protocol's
+/// `heapless::Vec` fields are `alloc` ones with the `std` feature.
+fn filled<T: Clone, V: FromIterator<T>>(value: T, len: usize) -> V {
+    core::iter::repeat_n(value, len).collect()
+}
+
+/// Every command, with the values that encode longest.
+fn largest_commands() -> Vec<Command> {
+    vec![
+        Command::GetStatus,
+        Command::StartUpdate {
+            bank: u8::MAX,
+            size: u32::MAX,
+            crc32: u32::MAX,
+            version: u32::MAX,
+            crc_algo: u8::MAX,
+        },
+        Command::DataBlock {
+            offset: u32::MAX,
+            // No zero bytes: the worst case for COBS
+            data: filled(0xFF, MAX_DATA_BLOCK_SIZE),
+        },
+        Command::FinishUpdate,
+        Command::Reboot,
+        Command::SetActiveBank { bank: u8::MAX },
+        Command::WipeAll,
+        Command::EnterBootrom,
+        Command::GetWearStats,
+        Command::GetBankInfo { bank: u8::MAX },
+        Command::CopyBank {
+            from: u8::MAX,
+            to: u8::MAX,
+        },
+        Command::GetBuildInfo,
+        Command::FinishUpdateNoActivate,
+        Command::ConfirmBoot,
+        Command::GetTransportStats,
+        Command::WriteGolden {
+            size: u32::MAX,
+            crc32: u32::MAX,
+            version: u32::MAX,
+        },
+        Command::StartBootloaderUpdate {
+            size: u32::MAX,
+            crc32: u32::MAX,
+        },
+        Command::EnableAntiRollback,
+        Command::SetBankLock {
+            bank: u8::MAX,
+            locked: true,
+        },
+        Command::GetTelemetry,
+        Command::Ping { nonce: u32::MAX },
+        Command::GetFlashMap,
+        Command::GetErrorLog,
+        Command::VerifyBank {
+            bank: u8::MAX,
+            expected_crc: u32::MAX,
+        },
+        Command::SetBootPolicy {
+            max_attempts: u8::MAX,
+        },
+        Command::StreamLogs { enable: true },
+        Command::ConfigGet { key: u8::MAX },
+        Command::ConfigSet {
+            key: u8::MAX,
+            value: u32::MAX,
+        },
+        Command::AbortUpdate,
+        Command::CompareBanks,
+        Command::GetResetReason,
+        Command::ReadBank {
+            bank: u8::MAX,
+            offset: u32::MAX,
+            len: u32::MAX,
+        },
+        Command::FlashSelfTest,
+    ]
+}
+
+/// A `FlashMap` with every region slot used.
+fn full_flash_map() -> Response {
+    let region = FlashRegion {
+        start: u32::MAX,
+        len: u32::MAX,
+        used: u32::MAX,
+        kind: FlashRegionKind::Free,
+    };
+    Response::FlashMap {
+        regions: filled(region, MAX_FLASH_REGIONS),
+    }
+}
+
+/// A `BankData` with a full block at the largest offset.
+fn full_bank_data() -> Response {
+    Response::BankData {
+        bank: u8::MAX,
+        offset: u32::MAX,
+        data: filled(0xFF, MAX_DATA_BLOCK_SIZE),
+    }
+}
+
+/// Every response, with the values that encode longest.
+fn largest_responses() -> Vec<Response> {
+    vec![
+        Response::Ack(AckStatus::Busy),
+        Response::Status {
+            active_bank: u8::MAX,
+            version_a: u32::MAX,
+            version_b: u32::MAX,
+            state: BootState::Persisting,
+            bootloader_version: Some(u32::MAX),
+            max_data_block_size: Some(u32::MAX),
+            confirmed: Some(true),
+            boot_attempts: Some(u8::MAX),
+            max_boot_attempts: Some(u8::MAX),
+            fell_back: Some(true),
+            min_version: Some(u32::MAX),
+            persist_percent: Some(u8::MAX),
+        },
+        Response::Progress {
+            phase: ProgressPhase::Verify,
+            percent: u8::MAX,
+        },
+        Response::WearStats {
+            bank_a_erases: u32::MAX,
+            bank_b_erases: u32::MAX,
+            bootdata_erases: u32::MAX,
+        },
+        Response::BankInfo {
+            bank: u8::MAX,
+            size: u32::MAX,
+            crc32: u32::MAX,
+            version: u32::MAX,
+            active: true,
+            metadata: Some(FirmwareMetadata {
+                fw_version: u32::MAX,
+                min_bootloader_version: u32::MAX,
+                build_id: u32::MAX,
+            }),
+            locked: Some(true),
+        },
+        Response::BuildInfo {
+            git_hash: [0xFF; 8],
+            build_epoch: u32::MAX,
+            features: u32::MAX,
+        },
+        Response::TransportStats {
+            rx_overflows: u32::MAX,
+            tx_drops: u32::MAX,
+            decode_errors: u32::MAX,
+            frames_received: u32::MAX,
+        },
+        Response::Telemetry {
+            temp_c_milli: i32::MIN,
+            vsys_mv: u32::MAX,
+        },
+        Response::Pong { nonce: u32::MAX },
+        full_flash_map(),
+        Response::ErrorLog {
+            total: u32::MAX,
+            recent: filled(ErrorCode::GoldenProvisioned, ERROR_LOG_LEN),
+        },
+        Response::Log {
+            dropped: u32::MAX,
+            bytes: filled(0xFF, LOG_CHUNK_SIZE),
+        },
+        Response::ConfigValue {
+            key: u8::MAX,
+            value: u32::MAX,
+        },
+        Response::BankCompare {
+            a_crc: u32::MAX,
+            b_crc: u32::MAX,
+            equal: true,
+        },
+        Response::ResetReason { reason: u8::MAX },
+        full_bank_data(),
+        Response::SelfTest {
+            passed: true,
+            detail: u8::MAX,
+        },
+    ]
+}
+
+#[test]
+fn test_every_command_fits_max_command_size() {
+    for cmd in largest_commands() {
+        let (plain, framed) = encoded_sizes(&cmd);
+        assert!(plain <= MAX_COMMAND_SIZE, "{} bytes: {:?}", plain, cmd);
+        assert!(framed <= MAX_COMMAND_FRAME_SIZE, "{} bytes framed", framed);
+    }
+}
+
+#[test]
+fn test_full_data_block_is_the_largest_command() {
+    let commands = largest_commands();
+    let largest = commands.iter().map(|c| encoded_sizes(c).0).max().unwrap();
+    let data_block = commands
+        .iter()
+        .find(|c| matches!(c, Command::DataBlock { .. }))
+        .map(|c| encoded_sizes(c).0)
+        .unwrap();
+    assert_eq!(largest, data_block);
+    // Tag (1) + offset varint (5) + length varint (2) + data
+    assert_eq!(data_block, 8 + MAX_DATA_BLOCK_SIZE);
+}
+
+#[test]
+fn test_every_response_fits_max_response_size() {
+    for resp in largest_responses() {
+        let (plain, framed) = encoded_sizes(&resp);
+        assert!(plain <= MAX_RESPONSE_SIZE, "{} bytes: {:?}", plain, resp);
+        assert!(framed <= MAX_RESPONSE_FRAME_SIZE, "{} bytes framed", framed);
+    }
+}
+
+#[test]
+fn test_cobs_max_encoded_len() {
+    assert_eq!(cobs_max_encoded_len(0), 1);
+    assert_eq!(cobs_max_encoded_len(253), 254);
+    assert_eq!(cobs_max_encoded_len(254), 256);
+    assert_eq!(MAX_COMMAND_FRAME_SIZE, 1044);
+}
+
+#[test]
+fn test_cobs_bound_holds_for_zero_free_payloads() {
+    for len in [1usize, 253, 254, 255, 508, 1000] {
+        let data = &[0xAB; 1000][..len];
+        let (plain, framed) = encoded_sizes(&data);
+        assert!(framed <= cobs_max_encoded_len(plain), "len {}", len);
+    }
+}
+
+#[test]
+fn test_full_bank_data_is_the_largest_response() {
+    let largest = largest_responses()
+        .iter()
+        .map(|r| encoded_sizes(r).0)
+        .max()
+        .unwrap();
+    assert_eq!(largest, encoded_sizes(&full_bank_data()).0);
+    // Tag (1) + bank (1) + offset varint (5) + length varint (2) + data
+    assert_eq!(largest, 9 + MAX_DATA_BLOCK_SIZE);
+}
+
+#[test]
+fn test_full_flash_map_is_the_largest_scalar_response() {
+    let largest = largest_responses()
+        .iter()
+        .filter(|r| !matches!(r, Response::BankData { .. }))
+        .map(|r| encoded_sizes(r).0)
+        .max()
+        .unwrap();
+    assert_eq!(largest, encoded_sizes(&full_flash_map()).0);
+}
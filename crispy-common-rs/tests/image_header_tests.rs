@@ -0,0 +1,148 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Unit tests for the optional firmware image header.
+
+use crispy_common::image_header::{
+    check_header, parse_header, vector_table_offset, ImageHeader, IMAGE_HEADER_MAGIC,
+    IMAGE_HEADER_SIZE, VECTOR_TABLE_ALIGN,
+};
+use crispy_common::protocol::{pack_semver, FirmwareMetadata, FW_BANK_SIZE};
+use crispy_common::vector_table::VectorTableError;
+
+#[test]
+fn test_header_size() {
+    assert_eq!(std::mem::size_of::<ImageHeader>(), IMAGE_HEADER_SIZE);
+}
+
+#[test]
+fn test_header_magic_is_ascii_crsp() {
+    assert_eq!(&IMAGE_HEADER_MAGIC.to_le_bytes(), b"CRSP");
+}
+
+#[test]
+fn test_header_bytes_roundtrip() {
+    let header = ImageHeader::new(0x100);
+    assert_eq!(ImageHeader::from_bytes(&header.to_bytes()), header);
+}
+
+#[test]
+fn test_headerless_image_has_offset_zero() {
+    // First words of a plain RAM image: initial SP, reset vector, ...
+    let mut bytes = [0u8; IMAGE_HEADER_SIZE];
+    bytes[..4].copy_from_slice(&0x2003_BFD0u32.to_le_bytes());
+    bytes[4..8].copy_from_slice(&0x2000_00C1u32.to_le_bytes());
+    assert_eq!(vector_table_offset(&bytes, FW_BANK_SIZE), Ok(0));
+}
+
+#[test]
+fn test_erased_bank_has_offset_zero() {
+    let bytes = [0xFFu8; IMAGE_HEADER_SIZE];
+    assert_eq!(vector_table_offset(&bytes, FW_BANK_SIZE), Ok(0));
+}
+
+#[test]
+fn test_valid_header_offset() {
+    let bytes = ImageHeader::new(VECTOR_TABLE_ALIGN).to_bytes();
+    assert_eq!(
+        vector_table_offset(&bytes, FW_BANK_SIZE),
+        Ok(VECTOR_TABLE_ALIGN)
+    );
+}
+
+#[test]
+fn test_corrupted_header_rejected() {
+    let mut bytes = ImageHeader::new(VECTOR_TABLE_ALIGN).to_bytes();
+    bytes[8] ^= 0x01;
+    assert_eq!(
+        vector_table_offset(&bytes, FW_BANK_SIZE),
+        Err(VectorTableError::HeaderCrc)
+    );
+}
+
+#[test]
+fn test_misaligned_offset_rejected() {
+    let bytes = ImageHeader::new(VECTOR_TABLE_ALIGN + 4).to_bytes();
+    assert_eq!(
+        vector_table_offset(&bytes, FW_BANK_SIZE),
+        Err(VectorTableError::HeaderOffset)
+    );
+}
+
+#[test]
+fn test_offset_inside_header_rejected() {
+    let bytes = ImageHeader::new(0).to_bytes();
+    assert_eq!(
+        vector_table_offset(&bytes, FW_BANK_SIZE),
+        Err(VectorTableError::HeaderOffset)
+    );
+}
+
+#[test]
+fn test_offset_outside_bank_rejected() {
+    let bytes = ImageHeader::new(FW_BANK_SIZE).to_bytes();
+    assert_eq!(
+        vector_table_offset(&bytes, FW_BANK_SIZE),
+        Err(VectorTableError::HeaderOffset)
+    );
+}
+
+fn metadata(min_bootloader: u32) -> FirmwareMetadata {
+    FirmwareMetadata {
+        fw_version: pack_semver(2, 1, 0).unwrap(),
+        min_bootloader_version: min_bootloader,
+        build_id: 1234,
+    }
+}
+
+#[test]
+fn test_metadata_field_offsets() {
+    let bytes = ImageHeader::with_metadata(VECTOR_TABLE_ALIGN, metadata(7)).to_bytes();
+    assert_eq!(bytes[12..16], pack_semver(2, 1, 0).unwrap().to_le_bytes());
+    assert_eq!(bytes[16..20], 7u32.to_le_bytes());
+    assert_eq!(bytes[20..24], 1234u32.to_le_bytes());
+    assert_eq!(bytes[24..28], [0; 4]);
+}
+
+#[test]
+fn test_metadata_roundtrip() {
+    let bytes = ImageHeader::with_metadata(VECTOR_TABLE_ALIGN, metadata(7)).to_bytes();
+    let header = parse_header(&bytes, FW_BANK_SIZE).unwrap().unwrap();
+    assert_eq!(header.metadata(), metadata(7));
+}
+
+#[test]
+fn test_header_without_metadata_accepts_any_bootloader() {
+    let bytes = ImageHeader::new(VECTOR_TABLE_ALIGN).to_bytes();
+    let header = check_header(&bytes, FW_BANK_SIZE, 0).unwrap().unwrap();
+    assert_eq!(header.metadata(), FirmwareMetadata::default());
+}
+
+#[test]
+fn test_headerless_image_accepts_any_bootloader() {
+    let bytes = [0xFFu8; IMAGE_HEADER_SIZE];
+    assert_eq!(check_header(&bytes, FW_BANK_SIZE, 0), Ok(None));
+}
+
+#[test]
+fn test_required_bootloader_version_enforced() {
+    let required = pack_semver(0, 4, 0).unwrap();
+    let bytes = ImageHeader::with_metadata(VECTOR_TABLE_ALIGN, metadata(required)).to_bytes();
+
+    assert!(check_header(&bytes, FW_BANK_SIZE, required).is_ok());
+    assert!(check_header(&bytes, FW_BANK_SIZE, pack_semver(1, 0, 0).unwrap()).is_ok());
+    assert_eq!(
+        check_header(&bytes, FW_BANK_SIZE, pack_semver(0, 3, 9).unwrap()),
+        Err(VectorTableError::BootloaderTooOld)
+    );
+}
+
+#[test]
+fn test_corrupted_metadata_rejected() {
+    let mut bytes = ImageHeader::with_metadata(VECTOR_TABLE_ALIGN, metadata(7)).to_bytes();
+    bytes[16] ^= 0x01;
+    assert_eq!(
+        check_header(&bytes, FW_BANK_SIZE, u32::MAX),
+        Err(VectorTableError::HeaderCrc)
+    );
+}
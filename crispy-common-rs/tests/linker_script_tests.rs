@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Checks that the linker scripts agree with the protocol constants.
+//!
+//! The bootloader performs the same comparison at startup; this catches a
+//! drift before a build reaches a device.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crispy_common::protocol::{
+    BOOT_DATA_ADDR, BOOT_INFO_ADDR, DATA_ADDR, DATA_SIZE, FLASH_BASE, FW_A_ADDR, FW_BANK_SIZE,
+    FW_B_ADDR, FW_GOLD_ADDR, FW_GOLD_SIZE,
+};
+
+fn read_script(name: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../linker_scripts")
+        .join(name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+/// The script without comments and without `{ … }` blocks, leaving the
+/// top-level statements.
+fn top_level(script: &str) -> String {
+    let mut out = String::new();
+    let mut rest = script;
+    while let Some(start) = rest.find("/*") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find("*/").expect("unterminated comment");
+        rest = &rest[start + end + 2..];
+    }
+    out.push_str(rest);
+
+    let mut depth = 0usize;
+    out.chars()
+        .filter(|&c| {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => return depth == 0,
+            }
+            false
+        })
+        .collect()
+}
+
+/// Value of a number (`0x…`, decimal, optional `K`/`M` suffix) or a symbol
+/// assigned earlier.
+fn term(token: &str, symbols: &HashMap<String, u64>) -> u64 {
+    let token = token.trim();
+    if let Some(&value) = symbols.get(token) {
+        return value;
+    }
+    let (digits, scale) = match token.as_bytes().last() {
+        Some(b'K') => (&token[..token.len() - 1], 1024),
+        Some(b'M') => (&token[..token.len() - 1], 1024 * 1024),
+        _ => (token, 1),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => digits.parse(),
+    };
+    value.unwrap_or_else(|_| panic!("cannot evaluate {:?}", token)) * scale
+}
+
+/// Evaluate a sum of products, the only expressions the scripts use.
+fn eval(expr: &str, symbols: &HashMap<String, u64>) -> u64 {
+    expr.split('+')
+        .map(|product| {
+            product
+                .split('*')
+                .map(|t| term(t, symbols))
+                .product::<u64>()
+        })
+        .sum()
+}
+
+/// Top-level `__symbol = expr;` assignments of a linker script.
+fn symbols(script: &str) -> HashMap<String, u64> {
+    let mut symbols = HashMap::new();
+    for statement in top_level(script).split(';') {
+        let statement = statement.trim();
+        let Some((name, expr)) = statement.split_once('=') else {
+            continue;
+        };
+        let name = name.trim();
+        if !name.starts_with("__") || name.contains(char::is_whitespace) {
+            continue;
+        }
+        let value = eval(expr, &symbols);
+        symbols.insert(name.to_string(), value);
+    }
+    symbols
+}
+
+fn bootloader_symbols() -> HashMap<String, u64> {
+    symbols(&read_script("bootloader_rp2040.x"))
+}
+
+#[test]
+fn test_bootloader_script_matches_bank_addresses() {
+    let symbols = bootloader_symbols();
+
+    assert_eq!(symbols["__flash_base"], u64::from(FLASH_BASE));
+    assert_eq!(symbols["__fw_a_entry"], u64::from(FW_A_ADDR));
+    assert_eq!(symbols["__fw_b_entry"], u64::from(FW_B_ADDR));
+    assert_eq!(symbols["__fw_bank_size"], u64::from(FW_BANK_SIZE));
+    assert_eq!(symbols["__boot_data_addr"], u64::from(BOOT_DATA_ADDR));
+}
+
+#[test]
+fn test_bootloader_script_matches_golden_bank() {
+    let symbols = bootloader_symbols();
+
+    assert_eq!(symbols["__fw_gold_addr"], u64::from(FW_GOLD_ADDR));
+    assert_eq!(symbols["__fw_gold_size"], u64::from(FW_GOLD_SIZE));
+    assert!(
+        symbols["__fw_gold_addr"] + symbols["__fw_gold_size"]
+            <= symbols["__flash_base"] + symbols["__flash_size"]
+    );
+}
+
+#[test]
+fn test_bootloader_script_matches_data_partition() {
+    let symbols = bootloader_symbols();
+
+    assert_eq!(symbols["__data_addr"], u64::from(DATA_ADDR));
+    assert_eq!(symbols["__data_size"], u64::from(DATA_SIZE));
+    // Golden images are capped at the RAM upload buffer, so they end where
+    // the data partition starts.
+    assert_eq!(
+        symbols["__data_addr"],
+        symbols["__fw_gold_addr"] + symbols["__fw_copy_size"]
+    );
+}
+
+#[test]
+fn test_bootloader_ram_starts_above_mailbox() {
+    let symbols = bootloader_symbols();
+
+    // BootInfo mailbox and update flag fill the 48 bytes below bootloader RAM.
+    assert_eq!(symbols["__bootloader_ram"], u64::from(BOOT_INFO_ADDR) + 48);
+}
+
+#[test]
+fn test_bootloader_ram_is_scrubbable() {
+    let symbols = bootloader_symbols();
+
+    // The pre-jump scrub zeroes bootloader RAM two words at a time.
+    assert_eq!(symbols["__bootloader_ram"] % 8, 0);
+    assert_eq!(symbols["__bootloader_ram_size"] % 8, 0);
+    assert_ne!(symbols["__bootloader_ram_size"], 0);
+}
+
+#[test]
+fn test_eval_handles_suffixes_and_precedence() {
+    let mut symbols = HashMap::new();
+    symbols.insert("__a".to_string(), 0x1000);
+
+    assert_eq!(eval(" 16K ", &symbols), 16 * 1024);
+    assert_eq!(eval("0x10 + 2 * __a", &symbols), 0x10 + 0x2000);
+    assert_eq!(eval("2M", &symbols), 2 * 1024 * 1024);
+}
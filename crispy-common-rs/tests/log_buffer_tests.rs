@@ -0,0 +1,59 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Unit tests for the log byte FIFO.
+
+use crispy_common::log_buffer::LogBuffer;
+
+#[test]
+fn test_new_buffer_is_empty() {
+    let mut log = LogBuffer::<8>::new();
+    let mut out = [0u8; 4];
+
+    assert!(log.is_empty());
+    assert_eq!(log.drain(&mut out), 0);
+    assert_eq!(log.take_dropped(), 0);
+}
+
+#[test]
+fn test_drain_returns_bytes_in_order() {
+    let mut log = LogBuffer::<8>::new();
+    log.push(&[1, 2, 3]);
+    log.push(&[4, 5]);
+
+    let mut out = [0u8; 3];
+    assert_eq!(log.drain(&mut out), 3);
+    assert_eq!(out, [1, 2, 3]);
+    assert_eq!(log.len(), 2);
+
+    let mut out = [0u8; 8];
+    assert_eq!(log.drain(&mut out), 2);
+    assert_eq!(out[..2], [4, 5]);
+    assert!(log.is_empty());
+}
+
+#[test]
+fn test_full_buffer_drops_new_bytes() {
+    let mut log = LogBuffer::<4>::new();
+    log.push(&[1, 2, 3, 4, 5, 6]);
+
+    let mut out = [0u8; 8];
+    assert_eq!(log.drain(&mut out), 4);
+    assert_eq!(out[..4], [1, 2, 3, 4]);
+    assert_eq!(log.take_dropped(), 2);
+    assert_eq!(log.take_dropped(), 0);
+}
+
+#[test]
+fn test_wraps_around() {
+    let mut log = LogBuffer::<4>::new();
+    let mut out = [0u8; 3];
+    log.push(&[1, 2, 3]);
+    log.drain(&mut out);
+
+    log.push(&[4, 5, 6, 7]);
+    let mut out = [0u8; 4];
+    assert_eq!(log.drain(&mut out), 4);
+    assert_eq!(out, [4, 5, 6, 7]);
+    assert_eq!(log.take_dropped(), 0);
+}
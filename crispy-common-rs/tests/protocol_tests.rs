@@ -3,10 +3,18 @@
 
 //! Unit tests for protocol types and constants.
 
+use crispy_common::crc32::CrcAlgorithm;
 use crispy_common::protocol::{
-    pack_semver, parse_semver, unpack_semver, AckStatus, BootState, Command, Response,
-    BOOT_DATA_ADDR, FLASH_BASE, FLASH_PAGE_SIZE, FLASH_SECTOR_SIZE, FW_A_ADDR, FW_BANK_SIZE,
-    FW_B_ADDR, MAX_DATA_BLOCK_SIZE, RAM_UPDATE_FLAG_ADDR, RAM_UPDATE_MAGIC,
+    pack_semver, page_padded_len, parse_build_semver, try_parse_semver, unpack_semver, AckStatus,
+    BootState, Command, ErrorCode, FirmwareMetadata, FlashRegion, FlashRegionKind, LegacyCommand,
+    ProgressPhase, Response, Semver, SemverError, BOOT_DATA_ADDR, BOOT_DATA_MIRROR_ADDR,
+    BUILD_FEATURE_COMPRESSION, BUILD_FEATURE_DUAL_CORE_FLASH, BUILD_FEATURE_GOLDEN_BANK,
+    BUILD_FEATURE_LOGGING, BUILD_FEATURE_LOG_STREAM, BUILD_FEATURE_SIGNING,
+    BUILD_FEATURE_SKIP_BOOT_CRC, CONFIG_ADDR, CONFIG_SLOTS, CONFIG_UNSET, DATA_ADDR, DATA_BANK,
+    DATA_INFO_ADDR, DATA_MAX_IMAGE_SIZE, DATA_SIZE, FLASH_BASE, FLASH_PAGE_SIZE, FLASH_SECTOR_SIZE,
+    FW_A_ADDR, FW_BANK_SIZE, FW_B_ADDR, FW_GOLD_ADDR, FW_GOLD_SIZE, GOLDEN_BANK, GOLDEN_INFO_ADDR,
+    GOLDEN_MAX_IMAGE_SIZE, MAX_DATA_BLOCK_SIZE, RAM_UPDATE_FLAG_ADDR, RAM_UPDATE_MAGIC,
+    SELFTEST_SCRATCH_ADDR, WATCHDOG_SCRATCH0_ADDR, WATCHDOG_UPDATE_MAGIC, WEAR_STATS_ADDR,
 };
 
 // --- Flash layout constants tests ---
@@ -36,6 +44,8 @@ fn test_boot_data_address() {
 fn test_ram_update_constants() {
     assert_eq!(RAM_UPDATE_FLAG_ADDR, 0x2003_BFF0);
     assert_eq!(RAM_UPDATE_MAGIC, 0x0FDA_7E00);
+    assert_eq!(WATCHDOG_SCRATCH0_ADDR, 0x4005_800C);
+    assert_eq!(WATCHDOG_UPDATE_MAGIC, 0xB007_10AD);
 }
 
 #[test]
@@ -44,6 +54,18 @@ fn test_flash_sizes() {
     assert_eq!(FLASH_PAGE_SIZE, 256);
 }
 
+#[test]
+fn test_page_padded_len() {
+    assert_eq!(page_padded_len(0), 0);
+    assert_eq!(page_padded_len(1), 256);
+    assert_eq!(page_padded_len(256), 256);
+    assert_eq!(page_padded_len(257), 512);
+    assert_eq!(
+        page_padded_len(FW_BANK_SIZE as usize),
+        FW_BANK_SIZE as usize
+    );
+}
+
 #[test]
 fn test_max_data_block_size() {
     assert_eq!(MAX_DATA_BLOCK_SIZE, 1024);
@@ -71,6 +93,66 @@ fn test_boot_data_after_banks() {
     assert!(BOOT_DATA_ADDR >= bank_b_end);
 }
 
+#[test]
+fn test_wear_stats_sector_after_boot_data() {
+    assert_eq!(WEAR_STATS_ADDR, BOOT_DATA_ADDR + FLASH_SECTOR_SIZE);
+    assert_eq!(WEAR_STATS_ADDR % FLASH_SECTOR_SIZE, 0);
+}
+
+#[test]
+fn test_golden_bank_fills_rest_of_flash() {
+    // Matches __fw_gold_addr / __fw_gold_size in linker_scripts/bootloader_rp2040.x
+    assert_eq!(FW_GOLD_ADDR, WEAR_STATS_ADDR + FLASH_SECTOR_SIZE);
+    assert_eq!(FW_GOLD_ADDR + FW_GOLD_SIZE, FLASH_BASE + 2 * 1024 * 1024);
+    assert_eq!(FW_GOLD_SIZE % FLASH_SECTOR_SIZE, 0);
+}
+
+#[test]
+fn test_golden_info_in_last_golden_sector() {
+    assert_eq!(
+        GOLDEN_INFO_ADDR + FLASH_SECTOR_SIZE,
+        FW_GOLD_ADDR + FW_GOLD_SIZE
+    );
+    assert_eq!(GOLDEN_INFO_ADDR % FLASH_SECTOR_SIZE, 0);
+}
+
+#[test]
+fn test_config_sector_below_golden_info() {
+    assert_eq!(CONFIG_ADDR + FLASH_SECTOR_SIZE, GOLDEN_INFO_ADDR);
+    assert_eq!(CONFIG_ADDR % FLASH_SECTOR_SIZE, 0);
+    // All slots fit the one page `ConfigSet` programs.
+    assert!(u32::from(CONFIG_SLOTS) * 4 <= FLASH_PAGE_SIZE);
+}
+
+#[test]
+fn test_boot_data_mirror_below_config() {
+    assert_eq!(BOOT_DATA_MIRROR_ADDR + FLASH_SECTOR_SIZE, CONFIG_ADDR);
+    assert_eq!(BOOT_DATA_MIRROR_ADDR % FLASH_SECTOR_SIZE, 0);
+}
+
+#[test]
+fn test_data_partition_between_golden_image_and_boot_data_mirror() {
+    // Golden images are capped at the 192KB RAM upload buffer (__fw_copy_size).
+    assert_eq!(GOLDEN_MAX_IMAGE_SIZE, 192 * 1024);
+    assert_eq!(FW_GOLD_ADDR + GOLDEN_MAX_IMAGE_SIZE, DATA_ADDR);
+    assert_eq!(DATA_ADDR + DATA_SIZE, BOOT_DATA_MIRROR_ADDR);
+    assert_eq!(DATA_INFO_ADDR + FLASH_SECTOR_SIZE, BOOT_DATA_MIRROR_ADDR);
+    assert_eq!(DATA_MAX_IMAGE_SIZE, 228 * 1024);
+    assert_eq!(DATA_ADDR % FLASH_SECTOR_SIZE, 0);
+    assert!(![0, 1, GOLDEN_BANK].contains(&DATA_BANK));
+}
+
+#[test]
+fn test_selftest_scratch_between_data_images_and_data_info() {
+    assert_eq!(DATA_ADDR + DATA_MAX_IMAGE_SIZE, SELFTEST_SCRATCH_ADDR);
+    assert_eq!(SELFTEST_SCRATCH_ADDR + FLASH_SECTOR_SIZE, DATA_INFO_ADDR);
+    assert_eq!(SELFTEST_SCRATCH_ADDR % FLASH_SECTOR_SIZE, 0);
+    // Clear of the firmware banks and both BootData sectors.
+    assert!(SELFTEST_SCRATCH_ADDR >= FW_B_ADDR + FW_BANK_SIZE);
+    assert!(SELFTEST_SCRATCH_ADDR > WEAR_STATS_ADDR);
+    assert!(SELFTEST_SCRATCH_ADDR + FLASH_SECTOR_SIZE <= BOOT_DATA_MIRROR_ADDR);
+}
+
 // --- AckStatus tests ---
 
 #[test]
@@ -88,6 +170,9 @@ fn test_ack_status_debug() {
     assert_eq!(format!("{:?}", AckStatus::BadCommand), "BadCommand");
     assert_eq!(format!("{:?}", AckStatus::BadState), "BadState");
     assert_eq!(format!("{:?}", AckStatus::BankInvalid), "BankInvalid");
+    assert_eq!(format!("{:?}", AckStatus::VersionTooOld), "VersionTooOld");
+    assert_eq!(format!("{:?}", AckStatus::BankLocked), "BankLocked");
+    assert_eq!(format!("{:?}", AckStatus::Busy), "Busy");
 }
 
 // --- BootState tests ---
@@ -97,6 +182,7 @@ fn test_boot_state_equality() {
     assert_eq!(BootState::Idle, BootState::Idle);
     assert_ne!(BootState::Idle, BootState::UpdateMode);
     assert_ne!(BootState::UpdateMode, BootState::Receiving);
+    assert_ne!(BootState::Receiving, BootState::Persisting);
 }
 
 #[test]
@@ -104,6 +190,7 @@ fn test_boot_state_debug() {
     assert_eq!(format!("{:?}", BootState::Idle), "Idle");
     assert_eq!(format!("{:?}", BootState::UpdateMode), "UpdateMode");
     assert_eq!(format!("{:?}", BootState::Receiving), "Receiving");
+    assert_eq!(format!("{:?}", BootState::Persisting), "Persisting");
 }
 
 // --- Command tests ---
@@ -121,17 +208,51 @@ fn test_command_start_update_debug() {
         size: 1024,
         crc32: 0xDEADBEEF,
         version: 1,
+        crc_algo: 0,
     };
     let debug = format!("{:?}", cmd);
     assert!(debug.contains("StartUpdate"));
     assert!(debug.contains("1024"));
 }
 
+#[test]
+fn test_start_update_without_crc_algo_decodes_as_legacy() {
+    let cmd = Command::StartUpdate {
+        bank: 1,
+        size: 4096,
+        crc32: 0xDEAD_BEEF,
+        version: 7,
+        crc_algo: CrcAlgorithm::IsoHdlc.id(),
+    };
+    let mut buf = [0u8; 32];
+    let encoded = postcard::to_slice(&cmd, &mut buf).unwrap();
+    // An older host's frame: the same bytes without `crc_algo`.
+    let legacy = &encoded[..encoded.len() - 1];
+
+    assert!(postcard::from_bytes::<Command>(legacy).is_err());
+    let upgraded = postcard::from_bytes::<LegacyCommand>(legacy)
+        .unwrap()
+        .upgrade();
+    assert_eq!(format!("{:?}", upgraded), format!("{:?}", cmd));
+}
+
+#[test]
+fn test_legacy_command_keeps_command_tags() {
+    let mut buf = [0u8; 4];
+    let encoded = postcard::to_slice(&Command::GetStatus, &mut buf).unwrap();
+    assert!(matches!(
+        postcard::from_bytes::<LegacyCommand>(encoded)
+            .unwrap()
+            .upgrade(),
+        Command::GetStatus
+    ));
+}
+
 #[test]
 fn test_command_data_block_debug() {
     let cmd = Command::DataBlock {
         offset: 0,
-        data: heapless::Vec::from_slice(&[1, 2, 3, 4]).unwrap(),
+        data: [1, 2, 3, 4].into_iter().collect(),
     };
     let debug = format!("{:?}", cmd);
     assert!(debug.contains("DataBlock"));
@@ -162,6 +283,203 @@ fn test_command_wipe_all_debug() {
     assert!(format!("{:?}", cmd).contains("WipeAll"));
 }
 
+#[test]
+fn test_command_enter_bootrom_debug() {
+    let cmd = Command::EnterBootrom;
+    assert!(format!("{:?}", cmd).contains("EnterBootrom"));
+}
+
+#[test]
+fn test_command_get_wear_stats_debug() {
+    let cmd = Command::GetWearStats;
+    assert!(format!("{:?}", cmd).contains("GetWearStats"));
+}
+
+#[test]
+fn test_command_get_bank_info_debug() {
+    let cmd = Command::GetBankInfo { bank: 1 };
+    let debug = format!("{:?}", cmd);
+    assert!(debug.contains("GetBankInfo"));
+    assert!(debug.contains("bank: 1"));
+}
+
+#[test]
+fn test_command_copy_bank_debug() {
+    let cmd = Command::CopyBank { from: 0, to: 1 };
+    let debug = format!("{:?}", cmd);
+    assert!(debug.contains("CopyBank"));
+    assert!(debug.contains("from: 0"));
+    assert!(debug.contains("to: 1"));
+}
+
+#[test]
+fn test_command_finish_update_no_activate_debug() {
+    let cmd = Command::FinishUpdateNoActivate;
+    assert!(format!("{:?}", cmd).contains("FinishUpdateNoActivate"));
+}
+
+#[test]
+fn test_command_confirm_boot_debug() {
+    let cmd = Command::ConfirmBoot;
+    assert!(format!("{:?}", cmd).contains("ConfirmBoot"));
+}
+
+#[test]
+fn test_command_get_build_info_debug() {
+    let cmd = Command::GetBuildInfo;
+    assert!(format!("{:?}", cmd).contains("GetBuildInfo"));
+}
+
+#[test]
+fn test_command_get_transport_stats_debug() {
+    let cmd = Command::GetTransportStats;
+    assert!(format!("{:?}", cmd).contains("GetTransportStats"));
+}
+
+#[test]
+fn test_command_write_golden_debug() {
+    let cmd = Command::WriteGolden {
+        size: 4096,
+        crc32: 0x1234_5678,
+        version: pack_semver(1, 0, 0).unwrap(),
+    };
+    let debug = format!("{:?}", cmd);
+    assert!(debug.contains("WriteGolden"));
+    assert!(debug.contains("4096"));
+}
+
+#[test]
+fn test_command_start_bootloader_update_debug() {
+    let cmd = Command::StartBootloaderUpdate {
+        size: 56512,
+        crc32: 0xCAFE_BABE,
+    };
+    let debug = format!("{:?}", cmd);
+    assert!(debug.contains("StartBootloaderUpdate"));
+    assert!(debug.contains("56512"));
+}
+
+#[test]
+fn test_command_enable_anti_rollback_debug() {
+    assert_eq!(
+        format!("{:?}", Command::EnableAntiRollback),
+        "EnableAntiRollback"
+    );
+}
+
+#[test]
+fn test_command_set_bank_lock_debug() {
+    let cmd = Command::SetBankLock {
+        bank: 0,
+        locked: true,
+    };
+    let debug = format!("{:?}", cmd);
+    assert!(debug.contains("SetBankLock"));
+    assert!(debug.contains("locked: true"));
+}
+
+#[test]
+fn test_command_get_telemetry_debug() {
+    let cmd = Command::GetTelemetry;
+    assert!(format!("{:?}", cmd).contains("GetTelemetry"));
+}
+
+#[test]
+fn test_command_ping_debug() {
+    let cmd = Command::Ping { nonce: 0xC0FFEE };
+    let debug = format!("{:?}", cmd);
+    assert!(debug.contains("Ping"));
+    assert!(debug.contains("nonce: 12648430"));
+}
+
+#[test]
+fn test_command_get_flash_map_debug() {
+    let debug = format!("{:?}", Command::GetFlashMap);
+    assert!(debug.contains("GetFlashMap"));
+}
+
+#[test]
+fn test_command_get_error_log_debug() {
+    let debug = format!("{:?}", Command::GetErrorLog);
+    assert!(debug.contains("GetErrorLog"));
+}
+
+#[test]
+fn test_command_verify_bank_debug() {
+    let cmd = Command::VerifyBank {
+        bank: 1,
+        expected_crc: 0xDEAD_BEEF,
+    };
+    let debug = format!("{:?}", cmd);
+    assert!(debug.contains("VerifyBank"));
+    assert!(debug.contains("expected_crc: 3735928559"));
+}
+
+#[test]
+fn test_command_set_boot_policy_debug() {
+    let cmd = Command::SetBootPolicy { max_attempts: 5 };
+    let debug = format!("{:?}", cmd);
+    assert!(debug.contains("SetBootPolicy"));
+    assert!(debug.contains("max_attempts: 5"));
+}
+
+#[test]
+fn test_command_stream_logs_debug() {
+    let cmd = Command::StreamLogs { enable: true };
+    let debug = format!("{:?}", cmd);
+    assert!(debug.contains("StreamLogs"));
+    assert!(debug.contains("enable: true"));
+}
+
+#[test]
+fn test_command_config_set_debug() {
+    let cmd = Command::ConfigSet {
+        key: 3,
+        value: 0xCAFE,
+    };
+    let debug = format!("{:?}", cmd);
+    assert!(debug.contains("ConfigSet"));
+    assert!(debug.contains("key: 3"));
+    assert!(debug.contains("value: 51966"));
+}
+
+#[test]
+fn test_command_abort_update_debug() {
+    let cmd = Command::AbortUpdate;
+    assert!(format!("{:?}", cmd).contains("AbortUpdate"));
+}
+
+#[test]
+fn test_command_compare_banks_debug() {
+    let cmd = Command::CompareBanks;
+    assert!(format!("{:?}", cmd).contains("CompareBanks"));
+}
+
+#[test]
+fn test_command_get_reset_reason_debug() {
+    let cmd = Command::GetResetReason;
+    assert!(format!("{:?}", cmd).contains("GetResetReason"));
+}
+
+#[test]
+fn test_command_read_bank_debug() {
+    let cmd = Command::ReadBank {
+        bank: 1,
+        offset: 2048,
+        len: 512,
+    };
+    let debug = format!("{:?}", cmd);
+    assert!(debug.contains("ReadBank"));
+    assert!(debug.contains("offset: 2048"));
+    assert!(debug.contains("len: 512"));
+}
+
+#[test]
+fn test_command_flash_self_test_debug() {
+    let cmd = Command::FlashSelfTest;
+    assert!(format!("{:?}", cmd).contains("FlashSelfTest"));
+}
+
 // --- Response tests ---
 
 #[test]
@@ -180,10 +498,247 @@ fn test_response_status_debug() {
         version_b: 2,
         state: BootState::Idle,
         bootloader_version: Some(pack_semver(1, 2, 3).unwrap()),
+        max_data_block_size: Some(MAX_DATA_BLOCK_SIZE as u32),
+        confirmed: Some(false),
+        boot_attempts: Some(1),
+        max_boot_attempts: Some(3),
+        fell_back: Some(true),
+        min_version: None,
+        persist_percent: None,
     };
     let debug = format!("{:?}", resp);
     assert!(debug.contains("Status"));
     assert!(debug.contains("Idle"));
+    assert!(debug.contains("fell_back: Some(true)"));
+}
+
+#[test]
+fn test_response_wear_stats_debug() {
+    let resp = Response::WearStats {
+        bank_a_erases: 12,
+        bank_b_erases: 7,
+        bootdata_erases: 40,
+    };
+    let debug = format!("{:?}", resp);
+    assert!(debug.contains("WearStats"));
+    assert!(debug.contains("40"));
+}
+
+#[test]
+fn test_response_bank_info_debug() {
+    let resp = Response::BankInfo {
+        bank: 0,
+        size: 4096,
+        crc32: 0xDEADBEEF,
+        version: pack_semver(1, 2, 3).unwrap(),
+        active: true,
+        metadata: None,
+        locked: Some(false),
+    };
+    let debug = format!("{:?}", resp);
+    assert!(debug.contains("BankInfo"));
+    assert!(debug.contains("4096"));
+    assert!(debug.contains("active: true"));
+}
+
+#[test]
+fn test_response_bank_info_with_metadata_debug() {
+    let resp = Response::BankInfo {
+        bank: 1,
+        size: 4096,
+        crc32: 0xDEADBEEF,
+        version: pack_semver(1, 2, 3).unwrap(),
+        active: false,
+        metadata: Some(FirmwareMetadata {
+            fw_version: pack_semver(1, 2, 3).unwrap(),
+            min_bootloader_version: pack_semver(0, 3, 0).unwrap(),
+            build_id: 4711,
+        }),
+        locked: None,
+    };
+    let debug = format!("{:?}", resp);
+    assert!(debug.contains("min_bootloader_version"));
+    assert!(debug.contains("4711"));
+}
+
+#[test]
+fn test_response_build_info_debug() {
+    let resp = Response::BuildInfo {
+        git_hash: *b"ddae56a\0",
+        build_epoch: 1_792_000_000,
+        features: BUILD_FEATURE_LOGGING | BUILD_FEATURE_SKIP_BOOT_CRC,
+    };
+    let debug = format!("{:?}", resp);
+    assert!(debug.contains("BuildInfo"));
+    assert!(debug.contains("build_epoch: 1792000000"));
+    assert!(debug.contains("features: 3"));
+}
+
+#[test]
+fn test_response_transport_stats_debug() {
+    let resp = Response::TransportStats {
+        rx_overflows: 1,
+        tx_drops: 2,
+        decode_errors: 3,
+        frames_received: 1200,
+    };
+    let debug = format!("{:?}", resp);
+    assert!(debug.contains("TransportStats"));
+    assert!(debug.contains("decode_errors: 3"));
+    assert!(debug.contains("frames_received: 1200"));
+}
+
+#[test]
+fn test_response_telemetry_debug() {
+    let resp = Response::Telemetry {
+        temp_c_milli: -12_500,
+        vsys_mv: 0,
+    };
+    let debug = format!("{:?}", resp);
+    assert!(debug.contains("Telemetry"));
+    assert!(debug.contains("temp_c_milli: -12500"));
+    assert!(debug.contains("vsys_mv: 0"));
+}
+
+#[test]
+fn test_response_pong_debug() {
+    let resp = Response::Pong { nonce: 7 };
+    let debug = format!("{:?}", resp);
+    assert!(debug.contains("Pong"));
+    assert!(debug.contains("nonce: 7"));
+}
+
+#[test]
+fn test_response_flash_map_debug() {
+    let regions = [FlashRegion {
+        start: 0x1001_0000,
+        len: 0xC_0000,
+        used: 4096,
+        kind: FlashRegionKind::BankA,
+    }]
+    .into_iter()
+    .collect();
+    let debug = format!("{:?}", Response::FlashMap { regions });
+    assert!(debug.contains("FlashMap"));
+    assert!(debug.contains("BankA"));
+    assert!(debug.contains("used: 4096"));
+}
+
+#[test]
+fn test_response_error_log_debug() {
+    let recent = [ErrorCode::BadOffset, ErrorCode::CrcMismatchRam]
+        .into_iter()
+        .collect();
+    let debug = format!("{:?}", Response::ErrorLog { total: 5, recent });
+    assert!(debug.contains("ErrorLog"));
+    assert!(debug.contains("total: 5"));
+    assert!(debug.contains("[BadOffset, CrcMismatchRam]"));
+}
+
+#[test]
+fn test_response_log_debug() {
+    let bytes = [0x01, 0x02, 0x00].into_iter().collect();
+    let debug = format!("{:?}", Response::Log { dropped: 7, bytes });
+    assert!(debug.contains("Log"));
+    assert!(debug.contains("dropped: 7"));
+    assert!(debug.contains("[1, 2, 0]"));
+}
+
+#[test]
+fn test_response_config_value_debug() {
+    let debug = format!(
+        "{:?}",
+        Response::ConfigValue {
+            key: 1,
+            value: CONFIG_UNSET
+        }
+    );
+    assert!(debug.contains("ConfigValue"));
+    assert!(debug.contains("key: 1"));
+    assert!(debug.contains("value: 4294967295"));
+}
+
+#[test]
+fn test_response_bank_compare_debug() {
+    let debug = format!(
+        "{:?}",
+        Response::BankCompare {
+            a_crc: 0x1234,
+            b_crc: 0x1234,
+            equal: true
+        }
+    );
+    assert!(debug.contains("BankCompare"));
+    assert!(debug.contains("a_crc: 4660"));
+    assert!(debug.contains("equal: true"));
+}
+
+#[test]
+fn test_response_reset_reason_debug() {
+    let debug = format!("{:?}", Response::ResetReason { reason: 3 });
+    assert!(debug.contains("ResetReason"));
+    assert!(debug.contains("reason: 3"));
+}
+
+#[test]
+fn test_response_self_test_debug() {
+    let debug = format!(
+        "{:?}",
+        Response::SelfTest {
+            passed: false,
+            detail: 3
+        }
+    );
+    assert!(debug.contains("SelfTest"));
+    assert!(debug.contains("passed: false"));
+    assert!(debug.contains("detail: 3"));
+}
+
+#[test]
+fn test_self_test_answers_only_flash_self_test() {
+    let result = Response::SelfTest {
+        passed: true,
+        detail: 0,
+    };
+    assert!(result.answers(&Command::FlashSelfTest));
+    assert!(!result.answers(&Command::GetStatus));
+}
+
+#[test]
+fn test_error_codes_keep_their_wire_tags() {
+    // Appending is fine; reordering would misreport codes to older hosts.
+    let cases = [
+        (ErrorCode::BadOffset, 0u8),
+        (ErrorCode::DecodeFailed, 4),
+        (ErrorCode::GoldenProvisioned, 16),
+        (ErrorCode::ProgramVerifyFailed, 18),
+        (ErrorCode::FlashOutOfRange, 19),
+        (ErrorCode::FlashTooSmall, 20),
+        (ErrorCode::BootDataRestored, 21),
+    ];
+    for (code, tag) in cases {
+        let mut buf = [0u8; 4];
+        assert_eq!(postcard::to_slice(&code, &mut buf).unwrap(), [tag]);
+    }
+}
+
+#[test]
+fn test_build_feature_bits_distinct() {
+    let bits = [
+        BUILD_FEATURE_LOGGING,
+        BUILD_FEATURE_SKIP_BOOT_CRC,
+        BUILD_FEATURE_SIGNING,
+        BUILD_FEATURE_COMPRESSION,
+        BUILD_FEATURE_GOLDEN_BANK,
+        BUILD_FEATURE_LOG_STREAM,
+        BUILD_FEATURE_DUAL_CORE_FLASH,
+    ];
+    for (i, a) in bits.iter().enumerate() {
+        assert_eq!(a.count_ones(), 1);
+        for b in &bits[i + 1..] {
+            assert_eq!(a & b, 0);
+        }
+    }
 }
 
 #[test]
@@ -195,7 +750,272 @@ fn test_semver_pack_unpack_roundtrip() {
 
 #[test]
 fn test_semver_parse() {
-    let packed = parse_semver("1.2.3").unwrap();
+    let packed = try_parse_semver("1.2.3").unwrap();
     let (major, minor, patch) = unpack_semver(packed);
     assert_eq!((major, minor, patch), (1, 2, 3));
 }
+
+#[test]
+fn test_semver_parse_v_prefix() {
+    assert_eq!(
+        try_parse_semver("v1.2.3"),
+        Ok(pack_semver(1, 2, 3).unwrap())
+    );
+    assert_eq!(
+        try_parse_semver("V0.2.0"),
+        Ok(pack_semver(0, 2, 0).unwrap())
+    );
+}
+
+#[test]
+fn test_semver_parse_trims_whitespace() {
+    assert_eq!(
+        try_parse_semver(" 1.2.3\n"),
+        Ok(pack_semver(1, 2, 3).unwrap())
+    );
+    assert_eq!(
+        try_parse_semver("\t0.2.0 "),
+        Ok(pack_semver(0, 2, 0).unwrap())
+    );
+}
+
+#[test]
+fn test_semver_parse_strips_prerelease_and_build() {
+    let expected = Ok(pack_semver(1, 2, 3).unwrap());
+    assert_eq!(try_parse_semver("1.2.3-rc.1"), expected);
+    assert_eq!(try_parse_semver("1.2.3+build5"), expected);
+    assert_eq!(try_parse_semver("1.2.3-rc.1+build5"), expected);
+    assert_eq!(try_parse_semver("1.2.3-alpha-2"), expected);
+}
+
+#[test]
+fn test_semver_parse_rejects_missing_patch() {
+    assert_eq!(try_parse_semver("1.2"), Err(SemverError::MissingComponent));
+    assert_eq!(try_parse_semver("1.2."), Err(SemverError::MissingComponent));
+    assert_eq!(try_parse_semver("1..3"), Err(SemverError::MissingComponent));
+    assert_eq!(
+        try_parse_semver("1.2-rc.1"),
+        Err(SemverError::MissingComponent)
+    );
+}
+
+#[test]
+fn test_semver_parse_rejects_malformed() {
+    assert_eq!(try_parse_semver(""), Err(SemverError::Empty));
+    assert_eq!(try_parse_semver("  v "), Err(SemverError::Empty));
+    assert_eq!(
+        try_parse_semver("1.2.3.4"),
+        Err(SemverError::TooManyComponents)
+    );
+    assert_eq!(
+        try_parse_semver("1.2.3 # release"),
+        Err(SemverError::InvalidCharacter)
+    );
+    assert_eq!(
+        try_parse_semver("1.x.3"),
+        Err(SemverError::InvalidCharacter)
+    );
+    assert_eq!(try_parse_semver("1.2.3-"), Err(SemverError::InvalidSuffix));
+    assert_eq!(
+        try_parse_semver("1.2.3-rc..1"),
+        Err(SemverError::InvalidSuffix)
+    );
+    assert_eq!(
+        try_parse_semver("1.2.3+a+b"),
+        Err(SemverError::InvalidSuffix)
+    );
+}
+
+#[test]
+fn test_semver_parse_rejects_out_of_range_component() {
+    assert_eq!(
+        try_parse_semver("1023.1023.1023"),
+        Ok(pack_semver(1023, 1023, 1023).unwrap())
+    );
+    assert_eq!(
+        try_parse_semver("1024.0.0"),
+        Err(SemverError::ComponentOutOfRange)
+    );
+    assert_eq!(
+        try_parse_semver("0.0.99999999999"),
+        Err(SemverError::ComponentOutOfRange)
+    );
+}
+
+#[test]
+fn test_parse_build_semver_const() {
+    const PACKED: u32 = parse_build_semver("2.5.7");
+    assert_eq!(unpack_semver(PACKED), (2, 5, 7));
+}
+
+#[test]
+#[should_panic]
+fn test_parse_build_semver_panics_on_invalid() {
+    let version = std::hint::black_box("1.2");
+    parse_build_semver(version);
+}
+
+// --- Semver newtype tests ---
+
+/// Component values exercised by the exhaustive round-trip and ordering tests.
+const SEMVER_SAMPLES: [u32; 8] = [0, 1, 2, 9, 10, 255, 1022, 1023];
+
+fn semver_samples() -> impl Iterator<Item = (u32, u32, u32)> {
+    SEMVER_SAMPLES.iter().flat_map(|&major| {
+        SEMVER_SAMPLES.iter().flat_map(move |&minor| {
+            SEMVER_SAMPLES
+                .iter()
+                .map(move |&patch| (major, minor, patch))
+        })
+    })
+}
+
+#[test]
+fn test_semver_new_rejects_out_of_range() {
+    assert!(Semver::new(1024, 0, 0).is_none());
+    assert!(Semver::new(0, 1024, 0).is_none());
+    assert!(Semver::new(0, 0, 1024).is_none());
+}
+
+#[test]
+fn test_semver_components_roundtrip() {
+    for (major, minor, patch) in semver_samples() {
+        let version = Semver::new(major, minor, patch).unwrap();
+        assert_eq!(
+            (version.major(), version.minor(), version.patch()),
+            (major, minor, patch)
+        );
+        assert_eq!(version.packed(), pack_semver(major, minor, patch).unwrap());
+        assert_eq!(Semver::from_packed(version.packed()), version);
+        assert_eq!(unpack_semver(version.packed()), (major, minor, patch));
+    }
+}
+
+#[test]
+fn test_semver_display_parse_roundtrip() {
+    for (major, minor, patch) in semver_samples() {
+        let version = Semver::new(major, minor, patch).unwrap();
+        let text = version.to_string();
+        assert_eq!(text, format!("{major}.{minor}.{patch}"));
+        assert_eq!(text.parse::<Semver>(), Ok(version));
+    }
+}
+
+#[test]
+fn test_semver_ordering_matches_component_ordering() {
+    for a in semver_samples() {
+        for b in semver_samples() {
+            let va = Semver::new(a.0, a.1, a.2).unwrap();
+            let vb = Semver::new(b.0, b.1, b.2).unwrap();
+            assert_eq!(va.cmp(&vb), a.cmp(&b), "{va} vs {vb}");
+        }
+    }
+}
+
+#[test]
+fn test_semver_ordering_examples() {
+    let v = |s: &str| s.parse::<Semver>().unwrap();
+    assert!(v("1.0.0") > v("0.1023.1023"));
+    assert!(v("1.10.0") > v("1.9.99"));
+    assert!(v("2.0.1") > v("2.0.0"));
+    assert_eq!(v("1.2.3-rc.1"), v("1.2.3"));
+}
+
+#[test]
+fn test_semver_from_packed_ignores_high_bits() {
+    let version = Semver::new(1, 2, 3).unwrap();
+    assert_eq!(Semver::from_packed(version.packed() | 0xC000_0000), version);
+}
+
+#[test]
+fn test_semver_deserialize_ignores_high_bits() {
+    use serde::de::{value, Deserialize, IntoDeserializer};
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let version = Semver::new(1, 2, 3).unwrap();
+    let raw: value::U32Deserializer<value::Error> =
+        (version.packed() | 0xC000_0000).into_deserializer();
+    let decoded = Semver::deserialize(raw).unwrap();
+
+    // Equal, hashed alike and ordered equal, like the masked value.
+    assert_eq!(decoded, version);
+    assert_eq!(decoded.cmp(&version), core::cmp::Ordering::Equal);
+    assert_eq!(u32::from(decoded), version.packed());
+    let hash = |v: Semver| {
+        let mut hasher = DefaultHasher::new();
+        v.hash(&mut hasher);
+        hasher.finish()
+    };
+    assert_eq!(hash(decoded), hash(version));
+}
+
+#[test]
+fn test_semver_parse_error_display() {
+    let err = "1.2".parse::<Semver>().unwrap_err();
+    assert_eq!(err, SemverError::MissingComponent);
+    assert_eq!(err.to_string(), "expected MAJOR.MINOR.PATCH");
+}
+
+#[test]
+fn test_ack_answers_any_command() {
+    for cmd in [
+        Command::GetStatus,
+        Command::WipeAll,
+        Command::Ping { nonce: 1 },
+    ] {
+        assert!(Response::Ack(AckStatus::BadCommand).answers(&cmd));
+    }
+}
+
+#[test]
+fn test_query_responses_answer_only_their_query() {
+    let status = Response::Status {
+        active_bank: 0,
+        version_a: 0,
+        version_b: 0,
+        state: BootState::UpdateMode,
+        bootloader_version: None,
+        max_data_block_size: None,
+        confirmed: None,
+        boot_attempts: None,
+        max_boot_attempts: None,
+        fell_back: None,
+        min_version: None,
+        persist_percent: None,
+    };
+    assert!(status.answers(&Command::GetStatus));
+    assert!(!status.answers(&Command::FinishUpdate));
+    assert!(!status.answers(&Command::GetWearStats));
+
+    let progress = Response::Progress {
+        phase: ProgressPhase::Erase,
+        percent: 10,
+    };
+    assert!(!progress.answers(&Command::FinishUpdate));
+}
+
+#[test]
+fn test_stale_response_for_another_bank_key_or_nonce_does_not_answer() {
+    let pong = Response::Pong { nonce: 7 };
+    assert!(pong.answers(&Command::Ping { nonce: 7 }));
+    assert!(!pong.answers(&Command::Ping { nonce: 8 }));
+
+    let value = Response::ConfigValue { key: 2, value: 0 };
+    assert!(value.answers(&Command::ConfigGet { key: 2 }));
+    assert!(!value.answers(&Command::ConfigGet { key: 3 }));
+
+    let data = Response::BankData {
+        bank: 0,
+        offset: 1024,
+        data: Default::default(),
+    };
+    let read = |bank, offset| Command::ReadBank {
+        bank,
+        offset,
+        len: 1024,
+    };
+    assert!(data.answers(&read(0, 1024)));
+    assert!(!data.answers(&read(0, 0)));
+    assert!(!data.answers(&read(1, 1024)));
+}
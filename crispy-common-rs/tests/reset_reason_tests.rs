@@ -0,0 +1,169 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Unit tests for reset reason decoding and the boot decision.
+
+use crispy_common::reset_reason::{
+    boot_decision, BootDecision, ResetReason, CHIP_RESET_HAD_POR, CHIP_RESET_HAD_PSM_RESTART,
+    CHIP_RESET_HAD_RUN, WATCHDOG_REASON_FORCE, WATCHDOG_REASON_TIMER,
+};
+
+const MAX_ATTEMPTS: u8 = 3;
+
+const ALL_REASONS: [ResetReason; 6] = [
+    ResetReason::PowerOn,
+    ResetReason::RunPin,
+    ResetReason::Debugger,
+    ResetReason::Watchdog,
+    ResetReason::WatchdogForced,
+    ResetReason::Software,
+];
+
+// --- Decoding ---
+
+#[test]
+fn test_decode_single_flags() {
+    assert_eq!(
+        ResetReason::decode(0, CHIP_RESET_HAD_POR),
+        ResetReason::PowerOn
+    );
+    assert_eq!(
+        ResetReason::decode(0, CHIP_RESET_HAD_RUN),
+        ResetReason::RunPin
+    );
+    assert_eq!(
+        ResetReason::decode(0, CHIP_RESET_HAD_PSM_RESTART),
+        ResetReason::Debugger
+    );
+    assert_eq!(
+        ResetReason::decode(WATCHDOG_REASON_TIMER, 0),
+        ResetReason::Watchdog
+    );
+    assert_eq!(
+        ResetReason::decode(WATCHDOG_REASON_FORCE, 0),
+        ResetReason::WatchdogForced
+    );
+}
+
+#[test]
+fn test_decode_no_flags_is_software() {
+    assert_eq!(ResetReason::decode(0, 0), ResetReason::Software);
+}
+
+#[test]
+fn test_decode_watchdog_wins_over_stale_chip_reset() {
+    // CHIP_RESET survives a watchdog reset with the flags of the last chip reset.
+    assert_eq!(
+        ResetReason::decode(WATCHDOG_REASON_TIMER, CHIP_RESET_HAD_POR),
+        ResetReason::Watchdog
+    );
+    assert_eq!(
+        ResetReason::decode(WATCHDOG_REASON_FORCE, CHIP_RESET_HAD_RUN),
+        ResetReason::WatchdogForced
+    );
+    assert_eq!(
+        ResetReason::decode(
+            WATCHDOG_REASON_TIMER | WATCHDOG_REASON_FORCE,
+            CHIP_RESET_HAD_PSM_RESTART
+        ),
+        ResetReason::Watchdog
+    );
+}
+
+#[test]
+fn test_decode_debugger_wins_over_run_and_por() {
+    let all = CHIP_RESET_HAD_POR | CHIP_RESET_HAD_RUN | CHIP_RESET_HAD_PSM_RESTART;
+    assert_eq!(ResetReason::decode(0, all), ResetReason::Debugger);
+    assert_eq!(
+        ResetReason::decode(0, CHIP_RESET_HAD_POR | CHIP_RESET_HAD_RUN),
+        ResetReason::RunPin
+    );
+}
+
+#[test]
+fn test_decode_ignores_unrelated_bits() {
+    assert_eq!(
+        ResetReason::decode(!0b11, 0xFFEE_FEFF),
+        ResetReason::Software
+    );
+}
+
+// --- Wire ids ---
+
+#[test]
+fn test_wire_ids_round_trip() {
+    for reason in ALL_REASONS {
+        assert_eq!(ResetReason::from_id(reason.id()), Some(reason));
+    }
+    assert_eq!(ResetReason::from_id(ALL_REASONS.len() as u8), None);
+}
+
+#[test]
+fn test_wire_ids_are_stable() {
+    // Appending is fine; renumbering would misreport reasons to older hosts.
+    assert_eq!(ResetReason::PowerOn.id(), 0);
+    assert_eq!(ResetReason::Watchdog.id(), 3);
+    assert_eq!(ResetReason::Software.id(), 5);
+}
+
+// --- Boot decision ---
+
+#[test]
+fn test_confirmed_image_is_never_counted() {
+    for reason in ALL_REASONS {
+        for attempts in 0..=MAX_ATTEMPTS + 1 {
+            assert_eq!(
+                boot_decision(reason, true, attempts, MAX_ATTEMPTS),
+                BootDecision::Uncounted,
+                "{:?} with {} attempts",
+                reason,
+                attempts
+            );
+        }
+    }
+}
+
+#[test]
+fn test_debugger_restart_never_counts_or_rolls_back() {
+    for attempts in 0..=MAX_ATTEMPTS + 1 {
+        assert_eq!(
+            boot_decision(ResetReason::Debugger, false, attempts, MAX_ATTEMPTS),
+            BootDecision::Uncounted
+        );
+    }
+}
+
+#[test]
+fn test_unconfirmed_image_counts_until_exhausted() {
+    for reason in ALL_REASONS {
+        if reason == ResetReason::Debugger {
+            continue;
+        }
+        for attempts in 0..=MAX_ATTEMPTS + 1 {
+            let expected = if attempts >= MAX_ATTEMPTS {
+                BootDecision::Rollback
+            } else {
+                BootDecision::Count
+            };
+            assert_eq!(
+                boot_decision(reason, false, attempts, MAX_ATTEMPTS),
+                expected,
+                "{:?} with {} attempts",
+                reason,
+                attempts
+            );
+        }
+    }
+}
+
+#[test]
+fn test_watchdog_reset_counts_as_failed_attempt() {
+    assert_eq!(
+        boot_decision(ResetReason::Watchdog, false, 1, MAX_ATTEMPTS),
+        BootDecision::Count
+    );
+    assert_eq!(
+        boot_decision(ResetReason::Watchdog, false, MAX_ATTEMPTS, MAX_ATTEMPTS),
+        BootDecision::Rollback
+    );
+}
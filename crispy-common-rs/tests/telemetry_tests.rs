@@ -0,0 +1,54 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Unit tests for ADC telemetry conversion.
+
+use crispy_common::telemetry::{temp_c_milli, vsys_mv};
+
+// --- Temperature ---
+
+#[test]
+fn test_temp_at_datasheet_reference_point() {
+    // 0.706 V is 27 °C; 876 counts is the closest reading (705.9 mV).
+    let temp = temp_c_milli(876);
+    assert!((26_500..=27_500).contains(&temp), "{}", temp);
+}
+
+#[test]
+fn test_temp_falls_as_voltage_rises() {
+    assert!(temp_c_milli(850) > temp_c_milli(876));
+    assert!(temp_c_milli(900) < temp_c_milli(876));
+}
+
+#[test]
+fn test_temp_one_count_is_about_half_a_degree() {
+    let step = temp_c_milli(876) - temp_c_milli(877);
+    assert!((400..=550).contains(&step), "{}", step);
+}
+
+#[test]
+fn test_temp_extremes_do_not_overflow() {
+    assert!(temp_c_milli(0) > 400_000);
+    assert!(temp_c_milli(4095) < -1_000_000);
+}
+
+#[test]
+fn test_temp_ignores_bits_above_12() {
+    assert_eq!(temp_c_milli(0xF000 | 876), temp_c_milli(876));
+}
+
+// --- VSYS ---
+
+#[test]
+fn test_vsys_pico_divider() {
+    // 5 V VSYS behind the Pico's 3:1 divider reads 1.667 V, 2068 counts.
+    let mv = vsys_mv(2068, 3);
+    assert!((4990..=5010).contains(&mv), "{}", mv);
+}
+
+#[test]
+fn test_vsys_full_scale() {
+    assert_eq!(vsys_mv(0, 3), 0);
+    assert_eq!(vsys_mv(4095, 1), 3299);
+    assert_eq!(vsys_mv(4095, 3), 9897);
+}
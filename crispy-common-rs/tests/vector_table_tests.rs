@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Unit tests for firmware vector table validation.
+
+use crispy_common::protocol::{FW_A_ADDR, FW_BANK_SIZE, FW_B_ADDR};
+use crispy_common::vector_table::{
+    ExecMode, ImageRegions, VectorTable, VectorTableError, RP2040_SRAM_END, RP2040_SRAM_START,
+};
+
+/// Geometry from `linker_scripts/bootloader_rp2040.x`, bank A.
+const REGIONS: ImageRegions = ImageRegions {
+    ram_base: 0x2000_0000,
+    copy_size: 0x3_0000,
+    ram_end: 0x2004_2000,
+    bank_addr: FW_A_ADDR,
+    bank_size: FW_BANK_SIZE,
+    min_stack_headroom: 1024,
+};
+
+fn table(initial_sp: u32, reset_vector: u32) -> VectorTable {
+    VectorTable {
+        initial_sp,
+        reset_vector,
+    }
+}
+
+#[test]
+fn test_ram_image_accepted() {
+    // Layout produced by linker_scripts/fw_rp2040.x
+    let vt = table(0x2003_C000, 0x2000_00C1);
+    assert_eq!(vt.validate(&REGIONS), Ok(ExecMode::Ram));
+}
+
+#[test]
+fn test_xip_image_accepted() {
+    let vt = table(0x2004_2000, FW_A_ADDR + 0x101);
+    assert_eq!(vt.validate(&REGIONS), Ok(ExecMode::Xip));
+}
+
+#[test]
+fn test_erased_bank_rejected() {
+    let vt = table(0xFFFF_FFFF, 0xFFFF_FFFF);
+    assert_eq!(vt.validate(&REGIONS), Err(VectorTableError::Erased));
+}
+
+#[test]
+fn test_zeroed_bank_rejected() {
+    let vt = table(0, 0);
+    assert_eq!(vt.validate(&REGIONS), Err(VectorTableError::Zeroed));
+}
+
+#[test]
+fn test_reset_without_thumb_bit_rejected() {
+    let vt = table(0x2003_C000, 0x2000_00C0);
+    assert_eq!(vt.validate(&REGIONS), Err(VectorTableError::NotThumb));
+}
+
+#[test]
+fn test_misaligned_stack_rejected() {
+    let vt = table(0x2003_C002, 0x2000_00C1);
+    assert_eq!(
+        vt.validate(&REGIONS),
+        Err(VectorTableError::StackMisaligned)
+    );
+}
+
+#[test]
+fn test_reset_past_copied_region_rejected() {
+    // In RAM, but beyond what the bootloader copies
+    let vt = table(0x2003_C000, 0x2003_0001);
+    assert_eq!(
+        vt.validate(&REGIONS),
+        Err(VectorTableError::ResetOutOfRange)
+    );
+}
+
+#[test]
+fn test_xip_image_for_other_bank_rejected() {
+    let vt = table(0x2004_2000, FW_B_ADDR + 0x101);
+    assert_eq!(
+        vt.validate(&REGIONS),
+        Err(VectorTableError::ResetOutOfRange)
+    );
+}
+
+#[test]
+fn test_ram_image_stack_inside_copied_region_rejected() {
+    let vt = table(0x2002_0000, 0x2000_00C1);
+    assert_eq!(
+        vt.validate(&REGIONS),
+        Err(VectorTableError::StackOutOfRange)
+    );
+}
+
+#[test]
+fn test_stack_above_ram_end_rejected() {
+    let vt = table(0x2004_2008, 0x2000_00C1);
+    assert_eq!(
+        vt.validate(&REGIONS),
+        Err(VectorTableError::StackOutOfRange)
+    );
+}
+
+#[test]
+fn test_word_aligned_stack_not_8_byte_aligned_rejected() {
+    let vt = table(0x2003_C004, 0x2000_00C1);
+    assert_eq!(
+        vt.validate(&REGIONS),
+        Err(VectorTableError::StackMisaligned)
+    );
+}
+
+#[test]
+fn test_ram_image_stack_without_headroom_rejected() {
+    // Just above the copied image, but below the required headroom
+    let vt = table(0x2003_0200, 0x2000_00C1);
+    assert_eq!(vt.validate(&REGIONS), Err(VectorTableError::StackHeadroom));
+}
+
+#[test]
+fn test_ram_image_stack_at_minimum_headroom_accepted() {
+    let vt = table(0x2003_0400, 0x2000_00C1);
+    assert_eq!(vt.validate(&REGIONS), Ok(ExecMode::Ram));
+}
+
+#[test]
+fn test_ram_image_larger_than_copy_size_rejected() {
+    assert_eq!(
+        REGIONS.check_image_len(ExecMode::Ram, REGIONS.copy_size + 4),
+        Err(VectorTableError::ImageTooLarge)
+    );
+    assert_eq!(
+        REGIONS.check_image_len(ExecMode::Ram, REGIONS.copy_size),
+        Ok(())
+    );
+    assert_eq!(REGIONS.check_image_len(ExecMode::Ram, 0), Ok(()));
+}
+
+#[test]
+fn test_xip_image_may_exceed_copy_size() {
+    assert_eq!(REGIONS.check_image_len(ExecMode::Xip, FW_BANK_SIZE), Ok(()));
+}
+
+#[test]
+fn test_zero_headroom_allows_low_stack() {
+    let regions = ImageRegions {
+        min_stack_headroom: 0,
+        ..REGIONS
+    };
+    let vt = table(0x2003_0008, 0x2000_00C1);
+    assert_eq!(vt.validate(&regions), Ok(ExecMode::Ram));
+}
+
+#[test]
+fn test_xip_image_ignores_headroom() {
+    let regions = ImageRegions {
+        min_stack_headroom: 0x10_0000,
+        ..REGIONS
+    };
+    let vt = table(0x2004_2000, FW_A_ADDR + 0x101);
+    assert_eq!(vt.validate(&regions), Ok(ExecMode::Xip));
+}
+
+#[test]
+fn test_stack_at_ram_end_accepted() {
+    let vt = table(REGIONS.ram_end, 0x2000_00C1);
+    assert_eq!(vt.validate(&REGIONS), Ok(ExecMode::Ram));
+}
+
+// --- Classification of representative images ---
+
+#[test]
+fn test_legacy_xip_image_inside_bank_accepted() {
+    // Linked for 0x10040000, inside bank A: runs in place.
+    let vt = table(0x2004_2000, 0x1004_01F5);
+    assert_eq!(vt.validate(&REGIONS), Ok(ExecMode::Xip));
+}
+
+#[test]
+fn test_xip_image_for_bank_b_accepted_in_bank_b() {
+    let regions = ImageRegions {
+        bank_addr: FW_B_ADDR,
+        ..REGIONS
+    };
+    let vt = table(0x2004_2000, FW_B_ADDR + 0x1F5);
+    assert_eq!(vt.validate(&regions), Ok(ExecMode::Xip));
+}
+
+#[test]
+fn test_reset_at_bank_end_rejected() {
+    let vt = table(0x2004_2000, FW_A_ADDR + FW_BANK_SIZE + 1);
+    assert_eq!(
+        vt.validate(&REGIONS),
+        Err(VectorTableError::ResetOutOfRange)
+    );
+}
+
+#[test]
+fn test_reset_outside_ram_and_bank_rejected() {
+    let nonsense = [
+        0x0000_0101, // boot ROM
+        0x1000_0101, // boot2, i.e. a plain SDK image at the start of flash
+        0x1000_4001, // the bootloader itself
+        0x1500_0001, // XIP cache-maintenance alias
+        0x4000_0001, // peripherals
+        0xD000_0001, // SIO
+    ];
+    for reset in nonsense {
+        let vt = table(0x2004_2000, reset);
+        assert_eq!(
+            vt.validate(&REGIONS),
+            Err(VectorTableError::ResetOutOfRange),
+            "reset vector 0x{:08x}",
+            reset
+        );
+    }
+}
+
+// --- Host-side plausibility check ---
+
+#[test]
+fn test_from_bytes_reads_little_endian_words() {
+    let bytes = [0x00, 0xC0, 0x03, 0x20, 0xC1, 0x00, 0x00, 0x20];
+    assert_eq!(
+        VectorTable::from_bytes(&bytes),
+        table(0x2003_C000, 0x2000_00C1)
+    );
+}
+
+#[test]
+fn test_plausible_ram_and_xip_images() {
+    assert_eq!(table(0x2003_C000, 0x2000_00C1).check_plausible(), Ok(()));
+    assert_eq!(
+        table(RP2040_SRAM_END, FW_B_ADDR + 0x101).check_plausible(),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_plausible_rejects_stack_outside_sram() {
+    for sp in [
+        RP2040_SRAM_START,
+        RP2040_SRAM_END + 8,
+        0x1000_0100,
+        0x2100_0000,
+    ] {
+        assert_eq!(
+            table(sp, 0x2000_00C1).check_plausible(),
+            Err(VectorTableError::StackOutOfRange),
+            "sp 0x{:08x}",
+            sp
+        );
+    }
+}
+
+#[test]
+fn test_plausible_rejects_elf_file() {
+    // "\x7fELF" followed by ELFCLASS32, little-endian, version 1.
+    let elf = [0x7F, b'E', b'L', b'F', 0x01, 0x01, 0x01, 0x00];
+    assert_eq!(
+        VectorTable::from_bytes(&elf).check_plausible(),
+        Err(VectorTableError::StackOutOfRange)
+    );
+}
+
+#[test]
+fn test_plausible_rejects_arm_reset_vector_and_blank_images() {
+    assert_eq!(
+        table(0x2003_C000, 0x2000_00C0).check_plausible(),
+        Err(VectorTableError::NotThumb)
+    );
+    assert_eq!(
+        table(0xFFFF_FFFF, 0xFFFF_FFFF).check_plausible(),
+        Err(VectorTableError::Erased)
+    );
+    assert_eq!(table(0, 0).check_plausible(), Err(VectorTableError::Zeroed));
+}
@@ -0,0 +1,56 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Unit tests for the warm-boot marker.
+
+use crispy_common::protocol::{RAM_UPDATE_FLAG_ADDR, WARM_BOOT_MARKER_ADDR};
+use crispy_common::warm_boot::{image_key, WarmBootMarker, WARM_BOOT_MAGIC};
+
+/// First byte of bootloader RAM (`linker_scripts/bootloader_rp2040.x`).
+const BOOTLOADER_RAM_START: u32 = 0x2003_C000;
+
+#[test]
+fn test_marker_fits_between_update_flag_and_bootloader_ram() {
+    assert_eq!(core::mem::size_of::<WarmBootMarker>(), 12);
+    assert!(WARM_BOOT_MARKER_ADDR >= RAM_UPDATE_FLAG_ADDR + 4);
+    assert!(WARM_BOOT_MARKER_ADDR + 12 <= BOOTLOADER_RAM_START);
+    assert_eq!(WARM_BOOT_MARKER_ADDR % 4, 0);
+}
+
+#[test]
+fn test_marker_matches_its_image() {
+    let key = image_key(0, 0x0001_0200, 0xDEAD_BEEF, 4096);
+    let marker = WarmBootMarker::new(key, 0x1234_5678);
+
+    assert_eq!(marker.magic, WARM_BOOT_MAGIC);
+    assert!(marker.matches(key));
+    assert!(!marker.matches(key ^ 1));
+}
+
+#[test]
+fn test_cleared_marker_matches_nothing() {
+    let marker = WarmBootMarker::cleared();
+
+    assert!(!marker.matches(0));
+    assert!(!marker.matches(image_key(0, 0, 0, 0)));
+}
+
+#[test]
+fn test_marker_without_magic_matches_nothing() {
+    let key = image_key(1, 1, 2, 3);
+    let mut marker = WarmBootMarker::new(key, 0);
+    marker.magic = 0xFFFF_FFFF;
+
+    assert!(!marker.matches(key));
+}
+
+#[test]
+fn test_image_key_depends_on_every_field() {
+    let base = image_key(0, 0x0001_0000, 0xCAFE_F00D, 8192);
+
+    assert_eq!(base, image_key(0, 0x0001_0000, 0xCAFE_F00D, 8192));
+    assert_ne!(base, image_key(1, 0x0001_0000, 0xCAFE_F00D, 8192));
+    assert_ne!(base, image_key(0, 0x0001_0001, 0xCAFE_F00D, 8192));
+    assert_ne!(base, image_key(0, 0x0001_0000, 0xCAFE_F00C, 8192));
+    assert_ne!(base, image_key(0, 0x0001_0000, 0xCAFE_F00D, 8196));
+}
@@ -29,8 +29,9 @@ int main() {
         sleep_ms(100);
     }
 
-    // Confirm boot to bootloader
+    // Confirm boot to bootloader, then stop the trial-boot watchdog
     confirm_boot();
+    disarm_trial_watchdog();
 
     print_welcome();
     print_prompt();
@@ -6,7 +6,7 @@
 
 use core::fmt::Write;
 use crispy_common::flash;
-use crispy_common::protocol::BootData;
+use crispy_common::protocol::{BootData, BootInfo, Semver};
 use defmt_rtt as _;
 use embedded_hal::digital::OutputPin;
 use embedded_hal::digital::StatefulOutputPin;
@@ -104,6 +104,7 @@ fn process_command(line: &str, serial: &mut SerialPort<UsbBus>) -> bool {
             let _ = serial.write(b"Available commands:\r\n");
             let _ = serial.write(b"  help     - Show this help\r\n");
             let _ = serial.write(b"  status   - Show boot status\r\n");
+            let _ = serial.write(b"  bootinfo - Show info passed by the bootloader\r\n");
             let _ = serial.write(b"  bootload - Reboot to bootloader update mode\r\n");
             let _ = serial.write(b"  reboot   - Reboot normally\r\n");
         }
@@ -117,6 +118,16 @@ fn process_command(line: &str, serial: &mut SerialPort<UsbBus>) -> bool {
                 let _ = serial.write(b"BootData: invalid\r\n");
             }
         }
+        "bootinfo" => match crispy_common::boot_info() {
+            Some(info) => {
+                let mut buf = [0u8; 256];
+                let len = format_boot_info(&info, &mut buf);
+                let _ = serial.write(&buf[..len]);
+            }
+            None => {
+                let _ = serial.write(b"BootInfo: not provided by bootloader\r\n");
+            }
+        },
         "bootload" => {
             let _ = serial.write(b"Rebooting to bootloader...\r\n");
             return true;
@@ -144,9 +155,32 @@ fn format_status(bd: &BootData, buf: &mut [u8]) -> usize {
         if bd.active_bank == 0 { "A" } else { "B" },
         bd.confirmed,
         bd.boot_attempts,
-        bd.version_a,
-        bd.version_b
+        Semver::from_packed(bd.version_a),
+        Semver::from_packed(bd.version_b)
+    );
+
+    writer.pos
+}
+
+fn format_boot_info(info: &BootInfo, buf: &mut [u8]) -> usize {
+    let mut writer = BufWriter { buf, pos: 0 };
+    let _ = write!(
+        writer,
+        "Boot info:\r\n  Bank: {} ({})\r\n  Trial boot: {}\r\n  Attempts: {}\r\n  Fallback: {}\r\n  Bootloader: {}\r\n",
+        info.active_bank,
+        if info.active_bank == 0 { "A" } else { "B" },
+        info.is_trial(),
+        info.boot_attempts,
+        info.fell_back(),
+        info.bootloader_version()
     );
+    if let Some(t) = info.timings() {
+        let _ = write!(
+            writer,
+            "  Boot timings (us): peripherals {}, trigger {}, image checked {}, jump {}\r\n",
+            t.peripherals_ready, t.trigger_decided, t.image_checked, t.jump
+        );
+    }
 
     writer.pos
 }
@@ -155,6 +189,23 @@ fn format_status(bd: &BootData, buf: &mut [u8]) -> usize {
 fn main() -> ! {
     defmt::println!("Firmware started!");
 
+    // Read before anything else runs: the mailbox is only valid for this boot
+    match crispy_common::boot_info() {
+        Some(info) => {
+            defmt::println!(
+                "Booted from bank {} (trial: {}, attempts: {}, bootloader v{})",
+                info.active_bank,
+                info.is_trial(),
+                info.boot_attempts,
+                info.bootloader_version()
+            );
+            if let Some(t) = info.timings() {
+                defmt::println!("Bootloader handed off after {} us", t.jump);
+            }
+        }
+        None => defmt::println!("No boot info from bootloader"),
+    }
+
     // --- Inline peripheral init (need USB access) ---
     let mut pac = unsafe { hal::pac::Peripherals::steal() };
 
@@ -186,6 +237,9 @@ fn main() -> ! {
 
     let confirmed = flash::confirm_boot();
     defmt::println!("Boot confirm: {}", confirmed);
+    // The bootloader armed the watchdog for the trial boot; from here on the
+    // image is confirmed and a hang no longer counts against it.
+    flash::disarm_trial_watchdog();
 
     // Initialize USB
     let usb_bus = UsbBusAllocator::new(hal::usb::UsbBus::new(
@@ -7,31 +7,79 @@
 
 namespace crispy {
 
-// BootData structure (must match crispy-common-rs, 32 bytes)
+// BootData structure (must match crispy-common-rs, 40 bytes)
 struct __attribute__((packed)) BootData {
     uint32_t magic;
     uint8_t  active_bank;
     uint8_t  confirmed;
     uint8_t  boot_attempts;
-    uint8_t  _reserved0;
+    uint8_t  flags;           // BOOT_FLAG_* bits
     uint32_t version_a;
     uint32_t version_b;
     uint32_t crc_a;
     uint32_t crc_b;
     uint32_t size_a;
     uint32_t size_b;
+    uint32_t min_version;     // anti-rollback floor
+    uint8_t  max_boot_attempts;  // rollback threshold, 0xFF = default (3)
+    uint8_t  layout_version;  // BOOT_DATA_LAYOUT_VERSION; older records are migrated on read
+    uint8_t  _reserved[2];
 
-    bool is_valid() const { return magic == BOOT_DATA_MAGIC; }
+    // Same checks as the Rust BootData::is_valid: an erased or inconsistent
+    // record is treated as absent.
+    bool is_valid() const {
+        return magic == BOOT_DATA_MAGIC && active_bank <= 1
+            && size_a <= FW_BANK_SIZE && size_b <= FW_BANK_SIZE
+            && (size_a != 0 || crc_a == 0) && (size_b != 0 || crc_b == 0);
+    }
     const char* bank_name() const { return active_bank == 0 ? "A" : "B"; }
 };
-static_assert(sizeof(BootData) == 32, "BootData must be 32 bytes");
+static_assert(sizeof(BootData) == 40, "BootData must be 40 bytes");
+
+// Boot latency milestones in microseconds of the RP2040 timer, which the
+// bootloader starts after the boot ROM, boot2 and clock setup
+struct __attribute__((packed)) BootTimings {
+    uint32_t peripherals_ready;
+    uint32_t trigger_decided;
+    uint32_t image_checked;   // bank selected, vector table and CRC checked
+    uint32_t jump;            // 0 from bootloaders that predate timings
+};
+static_assert(sizeof(BootTimings) == 16, "BootTimings must be 16 bytes");
+
+// Boot context written by the bootloader at BOOT_INFO_ADDR (must match crispy-common-rs, 32 bytes)
+struct __attribute__((packed)) BootInfo {
+    uint32_t magic;
+    uint8_t  active_bank;     // 0 = A, 1 = B, GOLDEN_BANK = golden
+    uint8_t  confirmed;       // 0 = trial boot
+    uint8_t  boot_attempts;
+    uint8_t  flags;           // BOOT_FLAG_* bits
+    uint32_t bootloader_version;  // packed semver
+    BootTimings timings;
+    uint32_t _reserved;
+
+    bool is_valid() const {
+        return magic == BOOT_INFO_MAGIC && active_bank <= GOLDEN_BANK && confirmed <= 1;
+    }
+
+    bool is_golden() const { return active_bank == GOLDEN_BANK; }
+    bool has_timings() const { return timings.jump != 0; }
+};
+static_assert(sizeof(BootInfo) == 32, "BootInfo must be 32 bytes");
 
 // Read BootData from flash
 BootData read_boot_data();
 
-// Confirm boot to bootloader (write confirmed=1, boot_attempts=0)
+// Read the boot context left by the bootloader in RAM (check is_valid())
+BootInfo read_boot_info();
+
+// Confirm boot to bootloader (write confirmed=1, boot_attempts=0).
+// Does nothing when booted from the golden bank.
 void confirm_boot();
 
+// Stop the watchdog the bootloader arms for a trial boot. Call right after
+// confirm_boot(); does nothing when the watchdog is not running.
+void disarm_trial_watchdog();
+
 // Reboot to bootloader update mode
 [[noreturn]] void reboot_to_bootloader();
 
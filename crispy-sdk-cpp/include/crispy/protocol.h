@@ -7,18 +7,47 @@
 
 namespace crispy {
 
-// Flash layout
+// Flash layout. Define CRISPY_FLASH_SIZE_MB (2, 4, 8 or 16) to match the
+// bootloader's flash-* feature; only the bank size depends on it.
+#ifndef CRISPY_FLASH_SIZE_MB
+#define CRISPY_FLASH_SIZE_MB 2
+#endif
+static_assert(CRISPY_FLASH_SIZE_MB == 2 || CRISPY_FLASH_SIZE_MB == 4
+                  || CRISPY_FLASH_SIZE_MB == 8 || CRISPY_FLASH_SIZE_MB == 16,
+              "CRISPY_FLASH_SIZE_MB must be 2, 4, 8 or 16");
 constexpr uint32_t FLASH_BASE_ADDR      = 0x10000000;
-constexpr uint32_t FW_A_ADDR            = 0x10010000;
-constexpr uint32_t FW_B_ADDR            = 0x100D0000;
-constexpr uint32_t BOOT_DATA_ADDR       = 0x10190000;
+constexpr uint32_t FLASH_SIZE           = CRISPY_FLASH_SIZE_MB * 1024 * 1024;
+// Bootloader (64KB), BootData and wear stats sectors, golden bank (440KB)
+constexpr uint32_t FW_BANK_SIZE         = (FLASH_SIZE - 64 * 1024 - 2 * 4096 - 440 * 1024) / 2;  // 768KB on 2MB
+constexpr uint32_t FW_A_ADDR            = FLASH_BASE_ADDR + 64 * 1024;  // 0x10010000
+constexpr uint32_t FW_B_ADDR            = FW_A_ADDR + FW_BANK_SIZE;     // 0x100D0000 on 2MB
+constexpr uint32_t BOOT_DATA_ADDR       = FW_B_ADDR + FW_BANK_SIZE;     // 0x10190000 on 2MB
+constexpr uint32_t BOOT_DATA_MIRROR_ADDR = BOOT_DATA_ADDR + 2 * 4096 + 440 * 1024 - 3 * 4096;  // 0x101FD000 on 2MB; written after BOOT_DATA_ADDR, read if it has no valid record
 
-constexpr uint32_t FW_BANK_SIZE         = 768 * 1024;  // 768KB per bank
 constexpr uint32_t BOOT_DATA_MAGIC      = 0xB007DA7A;
+constexpr uint8_t  BOOT_FLAG_FALLBACK   = 1u << 0;
+constexpr uint8_t  BOOT_FLAG_GOLDEN     = 1u << 1;
+constexpr uint8_t  BOOT_FLAG_ANTI_ROLLBACK = 1u << 3;  // images below BootData::min_version are refused
+constexpr uint8_t  BOOT_FLAG_LOCKED_A   = 1u << 4;  // bank A refuses writes (SetBankLock)
+constexpr uint8_t  BOOT_FLAG_LOCKED_B   = 1u << 5;  // bank B refuses writes (SetBankLock)
+constexpr uint8_t  BOOT_DATA_LAYOUT_VERSION = 3;  // BootData::layout_version this SDK writes
+constexpr uint8_t  GOLDEN_BANK          = 2;  // BootInfo::active_bank when booted from the golden bank
+
+// BootData journal: the BootData sector holds 64-byte records (BootData,
+// u32 sequence number, CRC-32 of both) appended in order; the valid record
+// with the highest sequence number is current
+constexpr uint32_t BOOT_JOURNAL_RECORD_SIZE = 64;
 
 // RAM flags for bootloader communication
 constexpr uint32_t RAM_UPDATE_FLAG_ADDR = 0x2003BFF0;
 constexpr uint32_t RAM_UPDATE_MAGIC     = 0x0FDA7E00;
+constexpr uint32_t BOOT_INFO_ADDR       = 0x2003BFD0;
+constexpr uint32_t BOOT_INFO_MAGIC      = 0xB0071AF0;
+
+// Firmware versions are packed semver: major[29:20], minor[19:10], patch[9:0]
+constexpr uint32_t semver_major(uint32_t v) { return (v >> 20) & 0x3FF; }
+constexpr uint32_t semver_minor(uint32_t v) { return (v >> 10) & 0x3FF; }
+constexpr uint32_t semver_patch(uint32_t v) { return v & 0x3FF; }
 
 // Hardware
 constexpr uint32_t LED_PIN = 25;
@@ -5,17 +5,154 @@
 #include "pico/stdlib.h"
 #include "hardware/flash.h"
 #include "hardware/sync.h"
+#include "hardware/structs/watchdog.h"
 #include <cstring>
 #include <cstdio>
 
 namespace crispy {
 
+namespace {
+
+constexpr uint32_t JOURNAL_SLOTS = FLASH_SECTOR_SIZE / BOOT_JOURNAL_RECORD_SIZE;
+constexpr uint32_t JOURNAL_CRC_OFFSET = sizeof(BootData) + 4;
+
+// A journal record as stored; the rest of its slot stays erased
+struct __attribute__((packed)) JournalRecord {
+    BootData boot_data;
+    uint32_t seq;
+    uint32_t crc;
+};
+static_assert(sizeof(JournalRecord) == JOURNAL_CRC_OFFSET + 4, "JournalRecord layout");
+
+// CRC-32 (ISO-HDLC), as used by the bootloader
+uint32_t crc32(const uint8_t* data, size_t len) {
+    uint32_t crc = 0xFFFFFFFF;
+    for (size_t i = 0; i < len; i++) {
+        crc ^= data[i];
+        for (int bit = 0; bit < 8; bit++) {
+            crc = (crc & 1) ? (crc >> 1) ^ 0xEDB88320 : crc >> 1;
+        }
+    }
+    return ~crc;
+}
+
+const uint8_t* journal_slot(uint32_t sector, uint32_t index) {
+    return reinterpret_cast<const uint8_t*>(sector) + index * BOOT_JOURNAL_RECORD_SIZE;
+}
+
+bool slot_erased(uint32_t sector, uint32_t index) {
+    const uint8_t* slot = journal_slot(sector, index);
+    for (uint32_t i = 0; i < BOOT_JOURNAL_RECORD_SIZE; i++) {
+        if (slot[i] != 0xFF) return false;
+    }
+    return true;
+}
+
+// Newest valid record, or nullptr if the sector holds none
+const JournalRecord* newest_record(uint32_t sector) {
+    const JournalRecord* newest = nullptr;
+    for (uint32_t i = 0; i < JOURNAL_SLOTS; i++) {
+        const auto* record = reinterpret_cast<const JournalRecord*>(journal_slot(sector, i));
+        if (crc32(journal_slot(sector, i), JOURNAL_CRC_OFFSET) != record->crc) continue;
+        if (newest == nullptr || record->seq > newest->seq) newest = record;
+    }
+    return newest;
+}
+
+// Append bd to the journal at sector, erasing it only when it is full
+void append_record(uint32_t sector, const BootData& bd) {
+    const JournalRecord* newest = newest_record(sector);
+    uint32_t slot = JOURNAL_SLOTS;
+    while (slot > 0 && slot_erased(sector, slot - 1)) slot--;
+    bool erase = slot == JOURNAL_SLOTS;
+    if (erase) slot = 0;
+
+    JournalRecord record;
+    record.boot_data = bd;
+    record.seq = newest != nullptr ? newest->seq + 1 : 1;
+    record.crc = crc32(reinterpret_cast<const uint8_t*>(&record), JOURNAL_CRC_OFFSET);
+
+    // 0xFF around the record leaves the rest of the page as it is
+    uint32_t slot_offset = slot * BOOT_JOURNAL_RECORD_SIZE;
+    uint32_t page_offset = slot_offset - slot_offset % FLASH_PAGE_SIZE;
+    uint8_t page[FLASH_PAGE_SIZE];
+    memset(page, 0xFF, sizeof(page));
+    memcpy(page + (slot_offset - page_offset), &record, sizeof(record));
+
+    uint32_t offset = sector - FLASH_BASE_ADDR;
+
+    // Disable interrupts during flash operations
+    uint32_t ints = save_and_disable_interrupts();
+    if (erase) flash_range_erase(offset, FLASH_SECTOR_SIZE);
+    flash_range_program(offset + page_offset, page, sizeof(page));
+    restore_interrupts(ints);
+}
+
+// Bring a record of an older layout up to BOOT_DATA_LAYOUT_VERSION, as
+// BootData::from_bytes does: fields it lacks read as erased padding. A 40-byte
+// record written before layout_version existed holds 0 there.
+BootData migrate(BootData bd) {
+    if (bd.layout_version == 0xFF) {
+        if (bd.min_version == 0xFFFFFFFF) {
+            // 32-byte layout: the floor is the newer bank while anti-rollback is on
+            bool anti_rollback = (bd.flags & BOOT_FLAG_ANTI_ROLLBACK) != 0;
+            uint32_t newest = bd.version_a > bd.version_b ? bd.version_a : bd.version_b;
+            bd.min_version = anti_rollback ? newest : 0;
+        }
+        bd.max_boot_attempts = 0xFF;
+        memset(bd._reserved, 0, sizeof(bd._reserved));
+    }
+    bd.layout_version = BOOT_DATA_LAYOUT_VERSION;
+    return bd;
+}
+
+BootData read_sector(uint32_t sector) {
+    const JournalRecord* newest = newest_record(sector);
+    if (newest != nullptr) return migrate(newest->boot_data);
+    // A record written before the journal: a bare BootData at the start,
+    // with the sequence number and CRC of a journal record still erased
+    const auto* first = reinterpret_cast<const JournalRecord*>(journal_slot(sector, 0));
+    if (first->seq == 0xFFFFFFFF && first->crc == 0xFFFFFFFF) return migrate(first->boot_data);
+    BootData erased;
+    memset(&erased, 0xFF, sizeof(erased));
+    return erased;
+}
+
+// Append bd to the journal, then to its mirror. A mirror without a valid
+// record is written first, so erasing a full journal never destroys the only
+// valid record
+void write_boot_data(const BootData& bd) {
+    if (!read_sector(BOOT_DATA_MIRROR_ADDR).is_valid()) {
+        append_record(BOOT_DATA_MIRROR_ADDR, bd);
+        append_record(BOOT_DATA_ADDR, bd);
+        return;
+    }
+    append_record(BOOT_DATA_ADDR, bd);
+    append_record(BOOT_DATA_MIRROR_ADDR, bd);
+}
+
+} // namespace
+
 BootData read_boot_data() {
-    const auto* bd = reinterpret_cast<const BootData*>(BOOT_DATA_ADDR);
-    return *bd;
+    BootData bd = read_sector(BOOT_DATA_ADDR);
+    if (bd.is_valid()) return bd;
+    // The journal has no valid record: use the mirror's, as the bootloader does
+    BootData mirror = read_sector(BOOT_DATA_MIRROR_ADDR);
+    return mirror.is_valid() ? mirror : bd;
+}
+
+BootInfo read_boot_info() {
+    const auto* info = reinterpret_cast<const BootInfo*>(BOOT_INFO_ADDR);
+    return *info;
 }
 
 void confirm_boot() {
+    BootInfo info = read_boot_info();
+    if (info.is_valid() && info.is_golden()) {
+        printf("Booted from golden bank, skipping confirmation\r\n");
+        return;
+    }
+
     BootData bd = read_boot_data();
 
     if (!bd.is_valid()) {
@@ -32,25 +169,16 @@ void confirm_boot() {
     bd.confirmed = 1;
     bd.boot_attempts = 0;
 
-    uint32_t offset = BOOT_DATA_ADDR - FLASH_BASE_ADDR;
-
-    // Pad to FLASH_PAGE_SIZE (256 bytes)
-    uint8_t page[FLASH_PAGE_SIZE];
-    memset(page, 0xFF, sizeof(page));
-    memcpy(page, &bd, sizeof(bd));
-
-    // Disable interrupts during flash operations
-    uint32_t ints = save_and_disable_interrupts();
-
-    // Erase sector (4KB) and program page
-    flash_range_erase(offset, FLASH_SECTOR_SIZE);
-    flash_range_program(offset, page, sizeof(page));
-
-    restore_interrupts(ints);
+    write_boot_data(bd);
 
     printf("Boot confirmed successfully\r\n");
 }
 
+void disarm_trial_watchdog() {
+    // Only ENABLE: the scratch registers hold the update request
+    hw_clear_bits(&watchdog_hw->ctrl, WATCHDOG_CTRL_ENABLE_BITS);
+}
+
 // Trigger ARM system reset via AIRCR register (same as Rust's SCB::sys_reset)
 static void sys_reset() {
     constexpr uint32_t AIRCR = 0xE000ED0C;
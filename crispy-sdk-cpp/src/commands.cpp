@@ -41,8 +41,10 @@ namespace crispy
                 printf("  Bank: %d (%s)\r\n", bd.active_bank, bd.bank_name());
                 printf("  Confirmed: %d\r\n", bd.confirmed);
                 printf("  Attempts: %d\r\n", bd.boot_attempts);
-                printf("  Version A: %lu\r\n", bd.version_a);
-                printf("  Version B: %lu\r\n", bd.version_b);
+                printf("  Version A: %lu.%lu.%lu\r\n", semver_major(bd.version_a),
+                       semver_minor(bd.version_a), semver_patch(bd.version_a));
+                printf("  Version B: %lu.%lu.%lu\r\n", semver_major(bd.version_b),
+                       semver_minor(bd.version_b), semver_patch(bd.version_b));
             }
             else
             {
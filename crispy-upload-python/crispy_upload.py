@@ -49,8 +49,8 @@ def cmd_status(transport: Transport):
     else:
         print("  Bootloader:  unknown")
     print(f"  Active bank: {status.active_bank} ({status.active_bank_name})")
-    print(f"  Version A:   {status.version_a}")
-    print(f"  Version B:   {status.version_b}")
+    print(f"  Version A:   {_format_packed_semver(status.version_a)}")
+    print(f"  Version B:   {_format_packed_semver(status.version_b)}")
     print(f"  State:       {status.state}")
 
 
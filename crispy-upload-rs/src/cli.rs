@@ -4,12 +4,16 @@
 //! Command-line interface definitions.
 
 use std::path::PathBuf;
+use std::time::Duration;
 
 use anyhow::{bail, Result};
 use clap::{ArgAction, Parser, Subcommand};
+use crispy_common::protocol::{Semver, CONFIG_SLOTS, DATA_BANK, MAX_DATA_BLOCK_SIZE};
+use serialport::{DataBits, Parity, StopBits};
 
 use crate::commands;
-use crate::transport::Transport;
+use crate::error::CrispyError;
+use crate::transport::{LineSettings, Transport, DEFAULT_BAUD};
 
 /// Command-line arguments.
 #[derive(Parser)]
@@ -22,10 +26,42 @@ pub struct Cli {
     #[arg(short = 'v', long = "version", action = ArgAction::Version)]
     _version: Option<bool>,
 
-    /// Serial port (e.g., /dev/ttyACM0)
+    /// Serial port (e.g., /dev/ttyACM0, or /dev/ttyUSB0 for a UART link)
     #[arg(short, long)]
     pub port: Option<String>,
 
+    /// Baud rate of a UART link (nominal for USB CDC)
+    #[arg(long, default_value_t = DEFAULT_BAUD)]
+    pub baud: u32,
+
+    /// Data bits per character of a UART link: 5, 6, 7 or 8
+    #[arg(long, default_value = "8", value_parser = parse_data_bits)]
+    pub data_bits: DataBits,
+
+    /// Parity of a UART link: none, odd or even
+    #[arg(long, default_value = "none", value_parser = parse_parity)]
+    pub parity: Parity,
+
+    /// Stop bits of a UART link: 1 or 2
+    #[arg(long, default_value = "1", value_parser = parse_stop_bits)]
+    pub stop_bits: StopBits,
+
+    /// Set DTR once the port is open: on or off (default: leave as is)
+    #[arg(long, value_name = "on|off", value_parser = parse_line_level)]
+    pub dtr: Option<bool>,
+
+    /// Set RTS once the port is open: on or off (default: leave as is)
+    #[arg(long, value_name = "on|off", value_parser = parse_line_level)]
+    pub rts: Option<bool>,
+
+    /// Pulse DTR after opening the port, resetting boards that wire it to RUN
+    #[arg(long)]
+    pub reset_on_open: bool,
+
+    /// Response timeout in milliseconds for every command (default: per command)
+    #[arg(long, value_name = "MS")]
+    pub timeout: Option<u64>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -42,18 +78,94 @@ pub enum Commands {
         #[arg(value_name = "FILE")]
         file: PathBuf,
 
-        /// Target bank (0 = A, 1 = B)
-        #[arg(short, long, default_value = "0")]
+        /// Target bank (0 = A, 1 = B, data = the data partition)
+        #[arg(short, long, default_value = "0", value_parser = parse_upload_bank)]
         bank: u8,
 
-        /// Firmware version number
+        /// Firmware version (MAJOR.MINOR.PATCH)
         #[arg(
             short = 'V',
             long = "fw-version",
             alias = "version",
-            default_value = "1"
+            default_value = "0.0.1",
+            value_parser = parse_fw_version
         )]
-        version: u32,
+        version: Semver,
+
+        /// Upload even if the bank already holds this exact image
+        #[arg(long)]
+        force: bool,
+
+        /// Also copy the image to the other bank on the device afterwards
+        #[arg(long)]
+        both: bool,
+
+        /// What to do once the image is stored
+        #[arg(long, value_enum, default_value = "activate")]
+        after: commands::AfterUpload,
+
+        /// DataBlock payload size in bytes (1-1024, default: device maximum)
+        #[arg(long, value_name = "BYTES", value_parser = parse_chunk_size)]
+        chunk_size: Option<usize>,
+
+        /// CRC algorithm the device verifies the transfer with
+        #[arg(long, value_enum, default_value = "iso-hdlc")]
+        crc_algo: commands::CrcChoice,
+
+        /// Refuse an image whose vector table looks wrong instead of warning
+        #[arg(long)]
+        strict: bool,
+
+        /// Pad the image with 0xFF to a whole 256-byte flash page first
+        #[arg(long)]
+        pad_to_page: bool,
+
+        /// Format of FILE; gzip and uf2 are turned into raw bytes first
+        #[arg(long, value_enum, default_value = "raw")]
+        input_format: commands::InputFormat,
+
+        /// Response timeout in milliseconds for this command (default: --timeout)
+        #[arg(long, value_name = "MS")]
+        timeout: Option<u64>,
+    },
+
+    /// Provision the read-only golden recovery bank (once; needs a golden-bank bootloader)
+    WriteGolden {
+        /// Firmware binary file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Firmware version (MAJOR.MINOR.PATCH)
+        #[arg(
+            short = 'V',
+            long = "fw-version",
+            alias = "version",
+            default_value = "0.0.1",
+            value_parser = parse_fw_version
+        )]
+        version: Semver,
+    },
+
+    /// Replace the bootloader itself (DANGEROUS: power loss can require BOOTSEL recovery)
+    UpdateBootloader {
+        /// Raw bootloader binary (crispy-bootloader.bin)
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Do not ask for confirmation
+        #[arg(long)]
+        yes: bool,
+
+        /// Response timeout in milliseconds for this command (default: --timeout)
+        #[arg(long, value_name = "MS")]
+        timeout: Option<u64>,
+    },
+
+    /// Refuse firmware older than the current version from now on (cannot be undone)
+    EnableAntiRollback {
+        /// Do not ask for confirmation
+        #[arg(long)]
+        yes: bool,
     },
 
     /// Set the active bank for the next boot (without uploading new firmware)
@@ -63,12 +175,154 @@ pub enum Commands {
         bank: u8,
     },
 
+    /// Protect a bank against uploads, copies and wipes
+    Lock {
+        /// Bank to lock (0 = A, 1 = B)
+        #[arg(long)]
+        bank: u8,
+    },
+
+    /// Remove the protection added by `lock`
+    Unlock {
+        /// Bank to unlock (0 = A, 1 = B)
+        #[arg(long)]
+        bank: u8,
+    },
+
+    /// Check a bank's flash CRC against a known value (changes nothing)
+    #[command(name = "verify-crc")]
+    VerifyCrc {
+        /// Bank to check (0 = A, 1 = B)
+        #[arg(long)]
+        bank: u8,
+
+        /// Expected CRC32 (hex, e.g. 0x1A2B3C4D; see `crc <FILE>`)
+        #[arg(long, value_parser = parse_hex_u32)]
+        crc: u32,
+
+        /// Response timeout in milliseconds for this command (default: --timeout)
+        #[arg(long, value_name = "MS")]
+        timeout: Option<u64>,
+    },
+
+    /// Check whether banks A and B hold the same image (changes nothing)
+    Compare {
+        /// Response timeout in milliseconds for this command (default: --timeout)
+        #[arg(long, value_name = "MS")]
+        timeout: Option<u64>,
+    },
+
+    /// Read a bank's image back into a file, checked against its stored CRC
+    Dump {
+        /// Bank to read (0 = A, 1 = B)
+        #[arg(long)]
+        bank: u8,
+
+        /// Output file
+        #[arg(value_name = "OUTPUT")]
+        output: PathBuf,
+
+        /// Bytes asked for per ReadBank (1-1024, default: 1024)
+        #[arg(long, value_name = "BYTES", value_parser = parse_chunk_size)]
+        chunk_size: Option<usize>,
+
+        /// Response timeout in milliseconds for this command (default: --timeout)
+        #[arg(long, value_name = "MS")]
+        timeout: Option<u64>,
+    },
+
+    /// Read or write a slot of the user config store (survives updates)
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Set how many unconfirmed boots an image gets before rolling back
+    #[command(name = "boot-policy")]
+    BootPolicy {
+        /// Unconfirmed boots before rolling back to the other bank (1 = at once)
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=254))]
+        max_attempts: u8,
+    },
+
     /// Wipe all firmware banks and reset boot data
-    Wipe,
+    Wipe {
+        /// Unlock locked banks first instead of refusing
+        #[arg(long)]
+        force: bool,
+    },
 
     /// Reboot the device
     Reboot,
 
+    /// Reset into the RP2040 ROM USB bootloader (BOOTSEL / UF2 drive)
+    Bootrom,
+
+    /// Show flash erase counters per region
+    Wear,
+
+    /// Show transport error counters (framing, buffer overflows, drops)
+    TransportStats,
+
+    /// Show the most recent errors recorded by the bootloader
+    #[command(name = "error-log")]
+    ErrorLog,
+
+    /// Stream the bootloader's defmt output (raw, for `defmt-print`) to stdout
+    Logs,
+
+    /// Show the chip temperature and VSYS voltage
+    Telemetry,
+
+    /// Show why the device last reset (power-on, watchdog, software, ...)
+    #[command(name = "reset-reason")]
+    ResetReason,
+
+    /// Erase, program and read back the flash scratch sector, then restore it
+    #[command(name = "selftest")]
+    SelfTest,
+
+    /// Check the device answers and measure the round-trip time
+    Ping {
+        /// Number of pings to send
+        #[arg(short, long, default_value = "4", value_parser = clap::value_parser!(u32).range(1..))]
+        count: u32,
+    },
+
+    /// Show the flash layout and how much of each region is used
+    #[command(name = "flash-map")]
+    FlashMap,
+
+    /// Show the bootloader build (git hash, build time, compiled-in features)
+    #[command(name = "buildinfo")]
+    BuildInfo,
+
+    /// Print the CRC32 a device would compute for a file (no device needed)
+    Crc {
+        /// Firmware binary file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// CRC algorithm
+        #[arg(long, value_enum, default_value = "iso-hdlc")]
+        algo: commands::CrcChoice,
+
+        /// Pad the image with 0xFF to a whole 256-byte flash page first
+        #[arg(long)]
+        pad_to_page: bool,
+
+        /// Format of FILE, as for upload
+        #[arg(long, value_enum, default_value = "raw")]
+        input_format: commands::InputFormat,
+    },
+
+    /// Summarize a UF2 file: blocks, address range, family IDs (no device needed)
+    Inspect {
+        /// UF2 file
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+    },
+
     /// Convert a raw binary file to UF2 format
     #[command(name = "bin2uf2")]
     Bin2Uf2 {
@@ -87,10 +341,43 @@ pub enum Commands {
         /// Family ID in hex (default: 0xE48BFF56 for RP2040)
         #[arg(short, long, default_value = "0xE48BFF56", value_parser = parse_hex_u32)]
         family_id: u32,
+
+        /// Pad the image with 0xFF to a whole 256-byte flash page first
+        #[arg(long)]
+        pad_to_page: bool,
     },
 }
 
 /// Parse a hex string (with or without 0x prefix) into a u32.
+/// `config` operations.
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// Print the value of a slot
+    Get {
+        /// Slot number (0-15)
+        #[arg(value_parser = clap::value_parser!(u8).range(0..CONFIG_SLOTS as i64))]
+        key: u8,
+    },
+    /// Store a value in a slot
+    Set {
+        /// Slot number (0-15)
+        #[arg(value_parser = clap::value_parser!(u8).range(0..CONFIG_SLOTS as i64))]
+        key: u8,
+
+        /// Value (decimal, or hex with 0x)
+        #[arg(value_parser = parse_u32)]
+        value: u32,
+    },
+}
+
+/// Parse a `u32` given in decimal or as `0x`-prefixed hex.
+fn parse_u32(s: &str) -> Result<u32, String> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).map_err(|e| format!("invalid hex value: {e}")),
+        None => s.parse().map_err(|e| format!("invalid value: {e}")),
+    }
+}
+
 fn parse_hex_u32(s: &str) -> Result<u32, String> {
     let s = s
         .strip_prefix("0x")
@@ -99,6 +386,83 @@ fn parse_hex_u32(s: &str) -> Result<u32, String> {
     u32::from_str_radix(s, 16).map_err(|e| format!("invalid hex value: {e}"))
 }
 
+/// Parse a firmware version as `MAJOR.MINOR.PATCH`.
+///
+/// A bare integer is still accepted for older scripts and is mapped to the
+/// patch component (`3` becomes `0.0.3`). That is also how versions stored by
+/// tools predating the packed-semver convention display, since a raw `N` below
+/// 1024 has the same encoding as `0.0.N`.
+fn parse_fw_version(s: &str) -> Result<Semver, String> {
+    if let Ok(legacy) = s.trim().parse::<u32>() {
+        let version = Semver::new(0, 0, legacy)
+            .ok_or_else(|| format!("bare version {legacy} exceeds 1023, use MAJOR.MINOR.PATCH"))?;
+        eprintln!(
+            "warning: bare integer firmware versions are deprecated, using {version} (use MAJOR.MINOR.PATCH)"
+        );
+        return Ok(version);
+    }
+
+    s.parse::<Semver>()
+        .map_err(|e| format!("invalid firmware version '{s}': {e}"))
+}
+
+/// Parse an upload target: a bank number, or `data` for the data partition.
+fn parse_upload_bank(s: &str) -> Result<u8, String> {
+    match s.trim() {
+        "data" => Ok(DATA_BANK),
+        bank => bank
+            .parse()
+            .map_err(|_| format!("invalid bank '{s}': expected 0, 1 or data")),
+    }
+}
+
+fn parse_data_bits(s: &str) -> Result<DataBits, String> {
+    match s.trim() {
+        "5" => Ok(DataBits::Five),
+        "6" => Ok(DataBits::Six),
+        "7" => Ok(DataBits::Seven),
+        "8" => Ok(DataBits::Eight),
+        _ => Err(format!("invalid data bits '{s}': expected 5, 6, 7 or 8")),
+    }
+}
+
+fn parse_parity(s: &str) -> Result<Parity, String> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "none" | "n" => Ok(Parity::None),
+        "odd" | "o" => Ok(Parity::Odd),
+        "even" | "e" => Ok(Parity::Even),
+        _ => Err(format!("invalid parity '{s}': expected none, odd or even")),
+    }
+}
+
+fn parse_stop_bits(s: &str) -> Result<StopBits, String> {
+    match s.trim() {
+        "1" => Ok(StopBits::One),
+        "2" => Ok(StopBits::Two),
+        _ => Err(format!("invalid stop bits '{s}': expected 1 or 2")),
+    }
+}
+
+fn parse_line_level(s: &str) -> Result<bool, String> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "on" | "1" | "high" => Ok(true),
+        "off" | "0" | "low" => Ok(false),
+        _ => Err(format!("invalid level '{s}': expected on or off")),
+    }
+}
+
+fn parse_chunk_size(s: &str) -> Result<usize, String> {
+    let requested: usize = s
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid chunk size '{s}': expected a byte count"))?;
+    let clamped = requested.clamp(1, MAX_DATA_BLOCK_SIZE);
+    if clamped != requested {
+        eprintln!("warning: chunk size {requested} out of range, using {clamped} (1-{MAX_DATA_BLOCK_SIZE})");
+    }
+    Ok(clamped)
+}
+
 /// Execute the parsed CLI command.
 pub fn run(cli: Cli) -> Result<()> {
     match cli.command {
@@ -107,14 +471,34 @@ pub fn run(cli: Cli) -> Result<()> {
             output,
             base_address,
             family_id,
-        } => commands::bin2uf2(&input, &output, base_address, family_id),
+            pad_to_page,
+        } => commands::bin2uf2(&input, &output, base_address, family_id, pad_to_page),
+        Commands::Crc {
+            file,
+            algo,
+            pad_to_page,
+            input_format,
+        } => commands::crc(&file, algo, pad_to_page, input_format),
+        Commands::Inspect { file } => commands::inspect(&file),
 
         cmd => {
             let port = cli
                 .port
                 .as_deref()
-                .ok_or_else(|| anyhow::anyhow!("--port is required for this command"))?;
-            let mut transport = Transport::new(port)?;
+                .ok_or_else(|| CrispyError::Usage("--port is required for this command".into()))?;
+            let line = LineSettings {
+                baud: cli.baud,
+                data_bits: cli.data_bits,
+                parity: cli.parity,
+                stop_bits: cli.stop_bits,
+                dtr: cli.dtr,
+                rts: cli.rts,
+                reset_on_open: cli.reset_on_open,
+            };
+            let mut transport = match cli.timeout {
+                Some(ms) => Transport::with_timeout(port, &line, ms)?,
+                None => Transport::new(port, &line)?,
+            };
 
             match cmd {
                 Commands::Status => commands::status(&mut transport),
@@ -122,11 +506,91 @@ pub fn run(cli: Cli) -> Result<()> {
                     file,
                     bank,
                     version,
-                } => commands::upload(&mut transport, &file, bank, version),
+                    force,
+                    both,
+                    after,
+                    chunk_size,
+                    crc_algo,
+                    strict,
+                    pad_to_page,
+                    input_format,
+                    timeout,
+                } => commands::upload(
+                    &mut transport,
+                    &file,
+                    bank,
+                    version,
+                    force,
+                    both,
+                    after,
+                    chunk_size,
+                    crc_algo,
+                    strict,
+                    pad_to_page,
+                    input_format,
+                    timeout.map(Duration::from_millis),
+                ),
+                Commands::WriteGolden { file, version } => {
+                    commands::write_golden(&mut transport, &file, version)
+                }
+                Commands::UpdateBootloader { file, yes, timeout } => commands::update_bootloader(
+                    &mut transport,
+                    &file,
+                    yes,
+                    timeout.map(Duration::from_millis),
+                ),
+                Commands::EnableAntiRollback { yes } => {
+                    commands::enable_anti_rollback(&mut transport, yes)
+                }
                 Commands::SetBank { bank } => commands::set_bank(&mut transport, bank),
-                Commands::Wipe => commands::wipe(&mut transport),
+                Commands::Lock { bank } => commands::set_bank_lock(&mut transport, bank, true),
+                Commands::Unlock { bank } => commands::set_bank_lock(&mut transport, bank, false),
+                Commands::VerifyCrc { bank, crc, timeout } => commands::verify_crc(
+                    &mut transport,
+                    bank,
+                    crc,
+                    timeout.map(Duration::from_millis),
+                ),
+                Commands::Compare { timeout } => {
+                    commands::compare(&mut transport, timeout.map(Duration::from_millis))
+                }
+                Commands::Dump {
+                    bank,
+                    output,
+                    chunk_size,
+                    timeout,
+                } => commands::dump(
+                    &mut transport,
+                    bank,
+                    &output,
+                    chunk_size,
+                    timeout.map(Duration::from_millis),
+                ),
+                Commands::Config { action } => match action {
+                    ConfigAction::Get { key } => commands::config_get(&mut transport, key),
+                    ConfigAction::Set { key, value } => {
+                        commands::config_set(&mut transport, key, value)
+                    }
+                },
+                Commands::BootPolicy { max_attempts } => {
+                    commands::boot_policy(&mut transport, max_attempts)
+                }
+                Commands::Wipe { force } => commands::wipe(&mut transport, force),
                 Commands::Reboot => commands::reboot(&mut transport),
-                Commands::Bin2Uf2 { .. } => bail!("unreachable"),
+                Commands::Bootrom => commands::bootrom(&mut transport),
+                Commands::Wear => commands::wear(&mut transport),
+                Commands::BuildInfo => commands::buildinfo(&mut transport),
+                Commands::TransportStats => commands::transport_stats(&mut transport),
+                Commands::ErrorLog => commands::error_log(&mut transport),
+                Commands::Logs => commands::logs(&mut transport),
+                Commands::Telemetry => commands::telemetry(&mut transport),
+                Commands::ResetReason => commands::reset_reason(&mut transport),
+                Commands::SelfTest => commands::selftest(&mut transport),
+                Commands::Ping { count } => commands::ping(&mut transport, count),
+                Commands::FlashMap => commands::flash_map(&mut transport),
+                Commands::Bin2Uf2 { .. } | Commands::Crc { .. } | Commands::Inspect { .. } => {
+                    bail!("unreachable")
+                }
             }
         }
     }
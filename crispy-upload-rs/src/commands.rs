@@ -3,25 +3,47 @@
 
 //! Command implementations for bootloader operations.
 
+use std::cell::OnceCell;
 use std::fs;
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 use std::path::Path;
+use std::time::Duration;
 
 use anyhow::{bail, Context, Result};
 use crc::{Crc, CRC_32_ISO_HDLC};
 use indicatif::{ProgressBar, ProgressStyle};
 
-use crispy_common::protocol::{unpack_semver, AckStatus, Command, Response};
-use crispy_common::MAX_DATA_BLOCK_SIZE;
+use crispy_common::bootloader_image::validate_bootloader_image;
+use crispy_common::crc32::CrcAlgorithm;
+use crispy_common::image_header::{self, IMAGE_HEADER_SIZE};
+use crispy_common::protocol::page_padded_len;
+use crispy_common::protocol::{
+    AckStatus, BootState, Command, FlashRegionKind, Response, Semver, BUILD_FEATURE_COMPRESSION,
+    BUILD_FEATURE_DUAL_CORE_FLASH, BUILD_FEATURE_GOLDEN_BANK, BUILD_FEATURE_LOGGING,
+    BUILD_FEATURE_LOG_STREAM, BUILD_FEATURE_SIGNING, BUILD_FEATURE_SKIP_BOOT_CRC, CONFIG_UNSET,
+    DATA_BANK, DATA_MAX_IMAGE_SIZE, GOLDEN_MAX_IMAGE_SIZE, SELFTEST_ERASE_FAILED,
+    SELFTEST_PROGRAM_FAILED, SELFTEST_RESTORE_FAILED, SELFTEST_SCRATCH_ADDR,
+    SELFTEST_VERIFY_FAILED,
+};
+use crispy_common::reset_reason::ResetReason;
+use crispy_common::vector_table::VectorTable;
+use crispy_common::{FLASH_BASE, FLASH_SIZE, FW_BANK_SIZE, MAX_DATA_BLOCK_SIZE};
 
+use crate::device::{self, UploadOptions};
+use crate::error::CrispyError;
+use crate::inflate;
 use crate::transport::Transport;
 
 const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
 const CHUNK_SIZE: usize = MAX_DATA_BLOCK_SIZE;
 
+/// Attempts at `SetActiveBank` while the device answers `Busy`.
+const SET_BANK_ATTEMPTS: u32 = 5;
+const SET_BANK_RETRY_DELAY: Duration = Duration::from_millis(200);
+
 /// Get and display bootloader status.
 pub fn status(transport: &mut Transport) -> Result<()> {
-    let response = transport.send_recv(&Command::GetStatus)?;
+    let response = transport.send_recv(&Command::GetStatus, None)?;
 
     match response {
         Response::Status {
@@ -30,11 +52,17 @@ pub fn status(transport: &mut Transport) -> Result<()> {
             version_b,
             state,
             bootloader_version,
+            max_data_block_size,
+            confirmed,
+            boot_attempts,
+            max_boot_attempts,
+            fell_back,
+            min_version,
+            persist_percent,
         } => {
             println!("Bootloader Status:");
             if let Some(version) = bootloader_version {
-                let (major, minor, patch) = unpack_semver(version);
-                println!("  Bootloader:  {}.{}.{}", major, minor, patch);
+                println!("  Bootloader:  {}", Semver::from_packed(version));
             } else {
                 println!("  Bootloader:  unknown");
             }
@@ -43,193 +71,1868 @@ pub fn status(transport: &mut Transport) -> Result<()> {
                 active_bank,
                 if active_bank == 0 { "A" } else { "B" }
             );
-            println!("  Version A:   {}", version_a);
-            println!("  Version B:   {}", version_b);
-            println!("  State:       {:?}", state);
+            println!("  Version A:   {}", Semver::from_packed(version_a));
+            println!("  Version B:   {}", Semver::from_packed(version_b));
+            if let Some(confirmed) = confirmed {
+                println!("  Confirmed:   {}", if confirmed { "yes" } else { "no" });
+            }
+            if let (Some(attempts), Some(max)) = (boot_attempts, max_boot_attempts) {
+                if confirmed == Some(true) {
+                    println!("  Attempts:    {}/{}", attempts, max);
+                } else {
+                    println!(
+                        "  Attempts:    {}/{} ({} remaining before rollback)",
+                        attempts,
+                        max,
+                        max.saturating_sub(attempts)
+                    );
+                }
+            }
+            if fell_back == Some(true) {
+                println!("  Fallback:    yes (the other bank failed validation at boot)");
+            }
+            if let Some(floor) = min_version {
+                println!(
+                    "  Min version: {} (anti-rollback on)",
+                    Semver::from_packed(floor)
+                );
+            }
+            match persist_percent {
+                Some(percent) => println!("  State:       {:?} ({}%)", state, percent),
+                None => println!("  State:       {:?}", state),
+            }
+            if let Some(max) = max_data_block_size {
+                println!("  Max block:   {} bytes", max);
+            }
+        }
+        Response::Ack(status) => {
+            println!("Unexpected ACK response: {:?}", status);
+        }
+        response => bail!(CrispyError::unexpected(&response)),
+    }
+
+    Ok(())
+}
+
+/// Query the largest `DataBlock` payload the device accepts.
+///
+/// The result is clamped to this tool's own `CHUNK_SIZE`. Bootloaders that
+/// predate block size reporting implicitly accept `MAX_DATA_BLOCK_SIZE`.
+/// A `requested` size (`--chunk-size`) is used when the device accepts it.
+fn negotiate_chunk_size(transport: &mut Transport, requested: Option<usize>) -> Result<usize> {
+    let response = transport.send_recv(&Command::GetStatus, None)?;
+
+    let Response::Status {
+        max_data_block_size,
+        ..
+    } = response
+    else {
+        bail!(CrispyError::unexpected(&response));
+    };
+
+    let max = match max_data_block_size {
+        Some(0) => bail!(CrispyError::Protocol(
+            "Device reported a maximum block size of 0 bytes".into()
+        )),
+        Some(max) => (max as usize).min(CHUNK_SIZE),
+        None => CHUNK_SIZE,
+    };
+
+    match requested {
+        Some(size) if size > max => {
+            println!(
+                "Chunk size {} exceeds the device maximum, using {} bytes",
+                size, max
+            );
+            Ok(max)
+        }
+        Some(size) => Ok(size),
+        None => Ok(max),
+    }
+}
+
+/// Stored metadata of one bank, as reported by `GetBankInfo`.
+struct BankInfo {
+    size: u32,
+    crc32: u32,
+    version: Semver,
+    active: bool,
+}
+
+impl BankInfo {
+    fn holds(&self, size: u32, crc32: u32) -> bool {
+        self.size == size && self.crc32 == crc32
+    }
+}
+
+/// Read the stored metadata of `bank`.
+///
+/// Bootloaders without `GetBankInfo` drop the command and the read times out;
+/// that is reported as `None` so callers fall back to a full transfer.
+fn query_bank_info(transport: &mut Transport, bank: u8) -> Result<Option<BankInfo>> {
+    let Ok(response) = transport.send_recv(&Command::GetBankInfo { bank }, None) else {
+        println!("Bank info not supported by this bootloader.");
+        return Ok(None);
+    };
+
+    match response {
+        Response::BankInfo {
+            size,
+            crc32,
+            version,
+            active,
+            ..
+        } => Ok(Some(BankInfo {
+            size,
+            crc32,
+            version: Semver::from_packed(version),
+            active,
+        })),
+        Response::Ack(AckStatus::BankInvalid) => bail!(CrispyError::Usage(
+            "Invalid bank: must be 0 (A), 1 (B), or data on bootloaders with a data partition"
+                .into()
+        )),
+        Response::Ack(status) => bail!(CrispyError::rejected("GetBankInfo", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+}
+
+/// Bar style for a device operation reported in percent, with its phase.
+fn percent_style() -> Result<ProgressStyle> {
+    Ok(ProgressStyle::default_bar()
+        .template("{spinner:.green} {msg:8} [{bar:40.cyan/blue}] {pos:>3}%")?
+        .progress_chars("#>-"))
+}
+
+/// Bar style for bytes sent to the device.
+fn bytes_style() -> Result<ProgressStyle> {
+    Ok(ProgressStyle::default_bar()
+        .template(
+            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})",
+        )?
+        .progress_chars("#>-"))
+}
+
+/// Send a long-running command, showing the device's progress stream as a bar.
+/// `timeout` overrides how long to wait for the final response.
+fn send_with_progress_bar(
+    transport: &mut Transport,
+    cmd: &Command,
+    timeout: Option<Duration>,
+) -> Result<Response> {
+    let pb = ProgressBar::new(100).with_style(percent_style()?);
+    pb.set_message("Finalize");
+    let response = transport.send_recv_progress(cmd, timeout, |phase, percent| {
+        pb.set_message(format!("{:?}", phase));
+        pb.set_position(u64::from(percent));
+    });
+    pb.finish_and_clear();
+    response
+}
+
+/// Read an image file, padded with 0xFF to a whole flash page when
+/// `pad_to_page` is set. Size and CRC are then computed over the padded
+/// bytes, which is exactly what the device programs and later checks.
+fn read_image(file: &Path, pad_to_page: bool) -> Result<Vec<u8>> {
+    read_image_as(file, InputFormat::Raw, pad_to_page)
+}
+
+/// [`read_image`] for a file in `format`, turned into the raw bytes the
+/// device stores before any padding.
+fn read_image_as(file: &Path, format: InputFormat, pad_to_page: bool) -> Result<Vec<u8>> {
+    let data = fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let mut image = match format {
+        InputFormat::Raw => data,
+        InputFormat::Gzip => inflate::decompress(&data, FLASH_SIZE as usize)
+            .map_err(|err| CrispyError::Usage(format!("{}: {}", file.display(), err)))?,
+        InputFormat::Uf2 => uf2_image(file, &data)?,
+    };
+    if pad_to_page {
+        image.resize(page_padded_len(image.len()), 0xFF);
+    }
+    Ok(image)
+}
+
+/// Reject images that cannot fit `region` (`capacity` bytes, "a bank" or "the
+/// data partition") before anything is sent to the device.
+fn check_firmware_size(file: &Path, len: usize, capacity: u32, region: &str) -> Result<u32> {
+    if len == 0 {
+        bail!(CrispyError::Usage(format!(
+            "{} is empty, nothing to upload",
+            file.display()
+        )));
+    }
+    if len > capacity as usize {
+        bail!(CrispyError::Usage(format!(
+            "{}: image is {}KB but {} is {}KB ({} > {} bytes)",
+            file.display(),
+            len.div_ceil(1024),
+            region,
+            capacity / 1024,
+            len,
+            capacity
+        )));
+    }
+    Ok(len as u32)
+}
+
+/// How `upload` reads the image file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum InputFormat {
+    /// The bytes to store, as written by `objcopy -O binary`
+    Raw,
+    /// A gzip (or zlib) compressed raw image
+    Gzip,
+    /// A UF2 file; its RP2040 blocks are joined into one image
+    Uf2,
+}
+
+/// What `upload` does once the image is stored on the device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum AfterUpload {
+    /// Store the image only; the active bank is unchanged
+    None,
+    /// Make the bank active for the next boot (trial boot)
+    Activate,
+    /// Activate and reboot into the new image
+    Reboot,
+    /// Activate, mark confirmed (no trial boot) and reboot
+    Confirm,
+}
+
+/// CRC algorithm the device checks an upload with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum CrcChoice {
+    /// CRC-32/ISO-HDLC, the one BootData records
+    IsoHdlc,
+    /// CRC-32/MPEG-2, as used by the RP2040 boot2 checksum
+    Mpeg2,
+    /// CRC-32/BZIP2
+    Bzip2,
+}
+
+impl CrcChoice {
+    pub fn algorithm(self) -> CrcAlgorithm {
+        match self {
+            Self::IsoHdlc => CrcAlgorithm::IsoHdlc,
+            Self::Mpeg2 => CrcAlgorithm::Mpeg2,
+            Self::Bzip2 => CrcAlgorithm::Bzip2,
+        }
+    }
+}
+
+/// Upload firmware to the specified bank, then apply the `after` policy.
+///
+/// The transfer is skipped when the bank already holds an image with the same
+/// size and CRC, unless `force` is set. With `mirror`, the image is also
+/// copied to the other bank on the device before any reboot. `chunk_size`
+/// overrides the negotiated `DataBlock` size, `crc_algo` the CRC the device
+/// verifies the transfer with. An image whose vector table looks wrong is
+/// only warned about, unless `strict` is set. With `pad_to_page`, the image
+/// is padded with 0xFF to a whole flash page before its size and CRC are
+/// taken. `DATA_BANK` stores the file in the data partition as is; `mirror`
+/// is refused and `after` ignored. `timeout` overrides how long each command
+/// that stores or copies the image waits for its response.
+#[allow(clippy::too_many_arguments)] // one per `upload` CLI option
+pub fn upload(
+    transport: &mut Transport,
+    file: &Path,
+    bank: u8,
+    version: Semver,
+    force: bool,
+    mirror: bool,
+    after: AfterUpload,
+    chunk_size: Option<usize>,
+    crc_algo: CrcChoice,
+    strict: bool,
+    pad_to_page: bool,
+    input_format: InputFormat,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    // A host that exited mid-upload leaves the device receiving, and it
+    // refuses StartUpdate until that upload ends.
+    if query_boot_state(transport)? == Some(BootState::Receiving) {
+        print!("Aborting the upload left in progress... ");
+        std::io::stdout().flush()?;
+        device::abort_update(transport)?;
+        println!("OK");
+    }
+
+    // The data partition is never booted, so there is nothing to activate
+    // or mirror.
+    if bank == DATA_BANK {
+        if mirror {
+            bail!(CrispyError::Usage(
+                "--both copies firmware between banks A and B, not to the data partition".into()
+            ));
+        }
+        return transfer(
+            transport,
+            file,
+            bank,
+            version,
+            force,
+            AfterUpload::None,
+            chunk_size,
+            crc_algo.algorithm(),
+            strict,
+            pad_to_page,
+            input_format,
+            timeout,
+        );
+    }
+
+    let active = query_active_bank(transport)?;
+    if after == AfterUpload::None && active == Some(bank) {
+        bail!(CrispyError::Usage(format!(
+            "Bank {} is the active bank; --after none would still boot the new image. Upload to the other bank instead.",
+            bank
+        )));
+    }
+
+    transfer(
+        transport,
+        file,
+        bank,
+        version,
+        force,
+        after,
+        chunk_size,
+        crc_algo.algorithm(),
+        strict,
+        pad_to_page,
+        input_format,
+        timeout,
+    )?;
+
+    if mirror {
+        mirror_bank(
+            transport,
+            file,
+            bank,
+            force,
+            pad_to_page,
+            input_format,
+            timeout,
+        )?;
+    }
+
+    match after {
+        AfterUpload::None | AfterUpload::Activate => {
+            println!(
+                "Use 'crispy-upload --port {} reboot' to restart the device.",
+                transport.port_name()
+            );
+            Ok(())
+        }
+        AfterUpload::Reboot => reboot(transport),
+        AfterUpload::Confirm => {
+            confirm_boot(transport)?;
+            reboot(transport)
+        }
+    }
+}
+
+/// Active bank reported by `GetStatus`, if the device answers.
+fn query_active_bank(transport: &mut Transport) -> Result<Option<u8>> {
+    match transport.send_recv(&Command::GetStatus, None)? {
+        Response::Status { active_bank, .. } => Ok(Some(active_bank)),
+        _ => Ok(None),
+    }
+}
+
+/// Update state reported by `GetStatus`, if the device answers.
+fn query_boot_state(transport: &mut Transport) -> Result<Option<BootState>> {
+    match transport.send_recv(&Command::GetStatus, None)? {
+        Response::Status { state, .. } => Ok(Some(state)),
+        _ => Ok(None),
+    }
+}
+
+/// Mark the active image confirmed so it skips the trial boot.
+fn confirm_boot(transport: &mut Transport) -> Result<()> {
+    print!("Confirming active image... ");
+    std::io::stdout().flush()?;
+
+    // Bootloaders without ConfirmBoot drop the command, so this times out.
+    let response = transport
+        .send_recv(&Command::ConfirmBoot, None)
+        .context("ConfirmBoot failed (bootloader may predate this command)")?;
+
+    match response {
+        Response::Ack(AckStatus::Ok) => println!("OK"),
+        Response::Ack(AckStatus::CrcError) => bail!(CrispyError::Verify(
+            "ConfirmBoot failed: active bank CRC mismatch".into()
+        )),
+        Response::Ack(status) => bail!(CrispyError::rejected("ConfirmBoot", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+
+    Ok(())
+}
+
+/// Send the image and store it; activates the bank unless `after` is `None`.
+#[allow(clippy::too_many_arguments)] // the `upload` options it applies
+fn transfer(
+    transport: &mut Transport,
+    file: &Path,
+    bank: u8,
+    version: Semver,
+    force: bool,
+    after: AfterUpload,
+    chunk_size: Option<usize>,
+    crc_algo: CrcAlgorithm,
+    strict: bool,
+    pad_to_page: bool,
+    input_format: InputFormat,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    let firmware = read_image_as(file, input_format, pad_to_page)?;
+    let data = bank == DATA_BANK;
+    let size = if data {
+        check_firmware_size(
+            file,
+            firmware.len(),
+            DATA_MAX_IMAGE_SIZE,
+            "the data partition",
+        )?
+    } else {
+        check_firmware_size(file, firmware.len(), FW_BANK_SIZE, "a bank")?
+    };
+    let crc32 = CRC32.checksum(&firmware);
+
+    println!(
+        "Firmware: {} ({} bytes, CRC32: 0x{:08x})",
+        file.display(),
+        size,
+        crc32
+    );
+    if data {
+        println!("Target:   Data partition");
+    } else {
+        println!(
+            "Target:   Bank {} ({})",
+            bank,
+            if bank == 0 { "A" } else { "B" }
+        );
+    }
+    println!("Version:  {}", version);
+    // Data is not firmware: it has no header or vector table, and the
+    // anti-rollback floor applies to firmware versions only.
+    if !data {
+        check_image_header(transport, &firmware, version)?;
+        check_vector_table(&firmware, strict)?;
+        check_rollback_floor(transport, version)?;
+    }
+
+    if !force {
+        if let Some(info) = query_bank_info(transport, bank)? {
+            if info.holds(size, crc32) {
+                println!(
+                    "Bank {} already holds this image (version {}): size and CRC32 match.",
+                    bank, info.version
+                );
+                println!("Skipping upload. Use --force to re-flash anyway.");
+                if !data && !info.active && after != AfterUpload::None {
+                    set_bank(transport, bank)?;
+                }
+                return Ok(());
+            }
+        }
+    }
+
+    let chunk_size = negotiate_chunk_size(transport, chunk_size)?;
+    println!("Chunk:    {} bytes", chunk_size);
+    println!();
+
+    print!("Starting update... ");
+    std::io::stdout().flush()?;
+
+    let options = UploadOptions {
+        activate: after != AfterUpload::None,
+        chunk_size,
+        crc_algo,
+        timeout,
+    };
+    // The bars appear once the device accepted the upload, and once it
+    // streams progress storing it.
+    let (bytes_style, percent_style) = (bytes_style()?, percent_style()?);
+    let blocks = OnceCell::new();
+    let finish = OnceCell::new();
+    let stored = device::upload_with_options(
+        transport,
+        &firmware,
+        bank,
+        version,
+        &options,
+        |sent, total| {
+            let pb = blocks.get_or_init(|| {
+                println!("OK");
+                ProgressBar::new(total).with_style(bytes_style.clone())
+            });
+            pb.set_position(sent);
+            if sent == total {
+                pb.finish_with_message("Upload complete");
+                println!();
+            }
+        },
+        |phase, percent| {
+            let pb = finish.get_or_init(|| ProgressBar::new(100).with_style(percent_style.clone()));
+            pb.set_message(format!("{:?}", phase));
+            pb.set_position(u64::from(percent));
+        },
+    );
+    if let Some(pb) = blocks.get().filter(|pb| !pb.is_finished()) {
+        pb.abandon();
+    }
+    if let Some(pb) = finish.get() {
+        pb.finish_and_clear();
+    }
+    stored?;
+    println!("Finalizing... OK");
+
+    println!();
+    if data {
+        println!("Data partition written successfully!");
+        return Ok(());
+    }
+    println!("Firmware uploaded successfully!");
+    if after == AfterUpload::None {
+        println!(
+            "Bank {} stored but not activated; use 'set-bank {}' to boot it.",
+            bank, bank
+        );
+    }
+
+    Ok(())
+}
+
+/// Show the image header of `firmware`, if any, and refuse images the device
+/// would reject: a malformed header, or one requiring a newer bootloader than
+/// the device reports.
+fn check_image_header(transport: &mut Transport, firmware: &[u8], version: Semver) -> Result<()> {
+    let Some(first_bytes) = firmware.first_chunk::<IMAGE_HEADER_SIZE>() else {
+        return Ok(());
+    };
+    let header = image_header::parse_header(first_bytes, FW_BANK_SIZE)
+        .map_err(|reason| CrispyError::Usage(format!("Invalid image header: {:?}", reason)))?;
+    let Some(header) = header else {
+        return Ok(());
+    };
+
+    let metadata = header.metadata();
+    if metadata.fw_version != 0 {
+        let header_version = Semver::from_packed(metadata.fw_version);
+        println!(
+            "Header:   version {}, build {}",
+            header_version, metadata.build_id
+        );
+        if header_version != version {
+            println!(
+                "warning: --fw-version {} differs from the image header ({})",
+                version, header_version
+            );
+        }
+    }
+    if metadata.min_bootloader_version == 0 {
+        return Ok(());
+    }
+
+    let required = Semver::from_packed(metadata.min_bootloader_version);
+    println!("Requires: bootloader {} or newer", required);
+    match transport.send_recv(&Command::GetStatus, None)? {
+        Response::Status {
+            bootloader_version: Some(running),
+            ..
+        } if !header.supports_bootloader(running) => bail!(CrispyError::Protocol(format!(
+            "Image requires bootloader {} or newer, the device runs {}",
+            required,
+            Semver::from_packed(running)
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Warn about an image whose vector table is not plausible for an RP2040
+/// (`VectorTable::check_plausible`), or refuse it with `strict`: usually an
+/// ELF or some other file passed instead of the firmware binary. The device
+/// runs the full check at boot.
+fn check_vector_table(firmware: &[u8], strict: bool) -> Result<()> {
+    // A malformed header was already refused by `check_image_header`.
+    let offset = firmware
+        .first_chunk::<IMAGE_HEADER_SIZE>()
+        .and_then(|first_bytes| image_header::vector_table_offset(first_bytes, FW_BANK_SIZE).ok())
+        .unwrap_or(0);
+    let table = firmware
+        .get(offset as usize..)
+        .and_then(|rest| rest.first_chunk::<8>())
+        .map(VectorTable::from_bytes);
+
+    let problem = match table.map(|vt| (vt, vt.check_plausible())) {
+        Some((_, Ok(()))) => return Ok(()),
+        _ if firmware.starts_with(b"\x7fELF") => {
+            "the file is an ELF; upload a raw binary (objcopy -O binary)".to_string()
+        }
+        Some((vt, Err(reason))) => format!(
+            "the vector table at offset 0x{:x} looks wrong ({:?}): initial SP 0x{:08x}, reset vector 0x{:08x}",
+            offset, reason, vt.initial_sp, vt.reset_vector
+        ),
+        None => format!(
+            "the image is too short to hold a vector table at offset 0x{:x}",
+            offset
+        ),
+    };
+    if strict {
+        bail!(CrispyError::Usage(format!(
+            "Refusing to upload: {}",
+            problem
+        )));
+    }
+    println!("warning: {} (--strict refuses such images)", problem);
+    Ok(())
+}
+
+/// Refuse to upload `version` when the device's anti-rollback floor is higher,
+/// instead of sending the whole image for `FinishUpdate` to reject it.
+fn check_rollback_floor(transport: &mut Transport, version: Semver) -> Result<()> {
+    match transport.send_recv(&Command::GetStatus, None)? {
+        Response::Status {
+            min_version: Some(floor),
+            ..
+        } if version.packed() < floor => bail!(CrispyError::Protocol(format!(
+            "Version {} is older than the device's anti-rollback minimum {}; downgrades are refused",
+            version,
+            Semver::from_packed(floor)
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Send `firmware` as `DataBlock`s after a successful `StartUpdate`/`WriteGolden`.
+fn send_data_blocks(
+    transport: &mut Transport,
+    firmware: &[u8],
+    chunk_size: usize,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    let pb = ProgressBar::new(firmware.len() as u64).with_style(bytes_style()?);
+    let sent = device::send_data_blocks(transport, firmware, chunk_size, timeout, |sent, _| {
+        pb.set_position(sent)
+    });
+    if sent.is_err() {
+        pb.abandon();
+        return sent;
+    }
+
+    pb.finish_with_message("Upload complete");
+    println!();
+
+    Ok(())
+}
+
+/// Provision the read-only golden recovery bank.
+///
+/// Only bootloaders built with the `golden-bank` feature accept this, and
+/// only once: a populated golden bank cannot be overwritten.
+pub fn write_golden(transport: &mut Transport, file: &Path, version: Semver) -> Result<()> {
+    let firmware = fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let size = check_firmware_size(file, firmware.len(), FW_BANK_SIZE, "a bank")?;
+    if size > GOLDEN_MAX_IMAGE_SIZE {
+        bail!(CrispyError::Usage(format!(
+            "{}: firmware is {} bytes but the golden bank holds at most {} bytes",
+            file.display(),
+            size,
+            GOLDEN_MAX_IMAGE_SIZE
+        )));
+    }
+    let crc32 = CRC32.checksum(&firmware);
+
+    println!(
+        "Firmware: {} ({} bytes, CRC32: 0x{:08x})",
+        file.display(),
+        size,
+        crc32
+    );
+    println!("Target:   Golden bank (read-only once written)");
+    println!("Version:  {}", version);
+
+    let chunk_size = negotiate_chunk_size(transport, None)?;
+    println!("Chunk:    {} bytes", chunk_size);
+    println!();
+
+    print!("Starting golden write... ");
+    std::io::stdout().flush()?;
+
+    // Bootloaders without WriteGolden drop the command, so this times out.
+    let response = transport
+        .send_recv(
+            &Command::WriteGolden {
+                size,
+                crc32,
+                version: version.packed(),
+            },
+            None,
+        )
+        .context("WriteGolden failed (bootloader may predate this command)")?;
+
+    match response {
+        Response::Ack(AckStatus::Ok) => println!("OK"),
+        Response::Ack(AckStatus::BankInvalid) => bail!(CrispyError::Protocol(format!(
+            "WriteGolden rejected: the golden bank is already provisioned, the bootloader was built without golden-bank, or {} bytes is too large",
+            size
+        ))),
+        Response::Ack(status) => bail!(CrispyError::rejected("WriteGolden", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+
+    send_data_blocks(transport, &firmware, chunk_size, None)?;
+
+    let response = send_with_progress_bar(transport, &Command::FinishUpdate, None);
+
+    print!("Finalizing... ");
+    match response? {
+        Response::Ack(AckStatus::Ok) => println!("OK"),
+        Response::Ack(AckStatus::CrcError) => {
+            bail!(CrispyError::Verify("CRC verification failed!".into()))
+        }
+        Response::Ack(status) => bail!(CrispyError::rejected("FinishUpdate", status)),
+        response => bail!(CrispyError::unexpected(&response)),
+    }
+
+    println!();
+    println!("Golden bank provisioned. It boots only when banks A and B both fail.");
+
+    Ok(())
+}
+
+/// Replace the bootloader on the device with `file`.
+///
+/// The image is checked locally (boot2 CRC, vector table), staged in the
+/// inactive bank by the device, then copied over the bootloader region.
+/// Unless `yes` is set, the user must type `yes` to go ahead. `timeout`
+/// overrides how long each command of the update waits for its response.
+pub fn update_bootloader(
+    transport: &mut Transport,
+    file: &Path,
+    yes: bool,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    let image = fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    if let Err(reason) = validate_bootloader_image(&image) {
+        bail!(CrispyError::Usage(format!(
+            "{} is not a crispy-bootloader binary ({:?}). Use the raw .bin built by 'make bootloader-bin', not a UF2 or firmware image.",
+            file.display(),
+            reason
+        )));
+    }
+    let size = image.len() as u32;
+    let crc32 = CRC32.checksum(&image);
+
+    println!(
+        "Bootloader: {} ({} bytes, CRC32: 0x{:08x})",
+        file.display(),
+        size,
+        crc32
+    );
+    println!();
+    println!("!!! WARNING: this replaces the bootloader itself. !!!");
+    println!("!!! The firmware in the inactive bank is overwritten to stage the image.");
+    println!("!!! Do NOT unplug or reset the device until it has re-enumerated.");
+    println!("!!! If power is lost while the bootloader is rewritten, the device only");
+    println!("!!! starts in BOOTSEL mode and must be recovered with a UF2 or SWD.");
+    println!();
+
+    if !yes {
+        ask_to_continue()?;
+    }
+
+    let chunk_size = negotiate_chunk_size(transport, None)?;
+
+    print!("Starting bootloader update... ");
+    std::io::stdout().flush()?;
+
+    // Bootloaders without StartBootloaderUpdate drop the command, so this times out.
+    let response = transport
+        .send_recv(&Command::StartBootloaderUpdate { size, crc32 }, timeout)
+        .context("StartBootloaderUpdate failed (bootloader may predate this command)")?;
+
+    match response {
+        Response::Ack(AckStatus::Ok) => println!("OK"),
+        Response::Ack(AckStatus::BankLocked) => bail!(CrispyError::Protocol(
+            "The inactive bank, used to stage the bootloader, is locked".into()
+        )),
+        Response::Ack(status) => bail!(CrispyError::rejected("StartBootloaderUpdate", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+
+    send_data_blocks(transport, &image, chunk_size, timeout)?;
+
+    let response = send_with_progress_bar(transport, &Command::FinishUpdate, timeout);
+
+    print!("Staging... ");
+    match response? {
+        Response::Ack(AckStatus::Ok) => println!("OK"),
+        Response::Ack(AckStatus::CrcError) => {
+            bail!(CrispyError::Verify(
+                "CRC verification failed, bootloader unchanged".into()
+            ))
+        }
+        Response::Ack(AckStatus::BadCommand) => {
+            bail!(CrispyError::Protocol(
+                "Device rejected the image as a bootloader, bootloader unchanged".into()
+            ))
+        }
+        Response::Ack(status) => bail!(CrispyError::rejected("FinishUpdate", status)),
+        response => bail!(CrispyError::unexpected(&response)),
+    }
+
+    println!();
+    println!("The device is now rewriting its bootloader and will reset by itself.");
+    println!("Do not unplug it. Check the result with 'buildinfo' once it is back.");
+
+    Ok(())
+}
+
+/// Turn on anti-rollback: from now on the device refuses images older than
+/// the newest one it has stored. This cannot be undone over USB.
+pub fn enable_anti_rollback(transport: &mut Transport, yes: bool) -> Result<()> {
+    println!("Anti-rollback makes the device refuse firmware older than the version");
+    println!("it currently runs, and every newer version stored afterwards.");
+    println!("It cannot be turned off again, not even by 'wipe'.");
+    println!();
+
+    if !yes {
+        ask_to_continue()?;
+    }
+
+    // Bootloaders without EnableAntiRollback drop the command, so this times out.
+    let response = transport
+        .send_recv(&Command::EnableAntiRollback, None)
+        .context("EnableAntiRollback failed (bootloader may predate this command)")?;
+
+    match response {
+        Response::Ack(AckStatus::Ok) => println!("Anti-rollback enabled."),
+        Response::Ack(AckStatus::BadState) => {
+            bail!(CrispyError::Protocol(
+                "Cannot enable anti-rollback: upload in progress".into()
+            ))
+        }
+        Response::Ack(status) => bail!(CrispyError::rejected("EnableAntiRollback", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+
+    Ok(())
+}
+
+/// Ask the user to type `yes` before an irreversible operation.
+fn ask_to_continue() -> Result<()> {
+    print!("Type 'yes' to continue: ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    if answer.trim() != "yes" {
+        bail!("Aborted, nothing was sent to the device");
+    }
+    Ok(())
+}
+
+/// Copy the image just uploaded to `bank` into the other bank, on the device.
+///
+/// Skipped when the other bank already holds the same image, unless `force`.
+/// `pad_to_page` and `input_format` must match the upload so the size and
+/// CRC compared agree. `timeout` overrides how long to wait for the copy.
+pub fn mirror_bank(
+    transport: &mut Transport,
+    file: &Path,
+    bank: u8,
+    force: bool,
+    pad_to_page: bool,
+    input_format: InputFormat,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    let firmware = read_image_as(file, input_format, pad_to_page)?;
+    let size = check_firmware_size(file, firmware.len(), FW_BANK_SIZE, "a bank")?;
+    let crc32 = CRC32.checksum(&firmware);
+    let other = if bank == 0 { 1 } else { 0 };
+
+    println!();
+    if !force {
+        if let Some(info) = query_bank_info(transport, other)? {
+            if info.holds(size, crc32) {
+                println!(
+                    "Bank {} already holds this image (version {}), skipping copy.",
+                    other, info.version
+                );
+                return Ok(());
+            }
+        }
+    }
+
+    println!(
+        "Copying bank {} to bank {} on the device...",
+        if bank == 0 { "A" } else { "B" },
+        if other == 0 { "A" } else { "B" }
+    );
+    let response = send_with_progress_bar(
+        transport,
+        &Command::CopyBank {
+            from: bank,
+            to: other,
+        },
+        timeout,
+    );
+
+    match response? {
+        Response::Ack(AckStatus::Ok) => println!("Both banks now hold this image."),
+        Response::Ack(AckStatus::CrcError) => bail!(CrispyError::Verify(
+            "CopyBank failed: CRC verification failed".into()
+        )),
+        Response::Ack(AckStatus::BankInvalid) => {
+            bail!(CrispyError::Protocol(
+                "CopyBank failed: source bank is empty or target bank is active".into()
+            ))
+        }
+        Response::Ack(AckStatus::BankLocked) => bail!(CrispyError::Protocol(format!(
+            "CopyBank failed: bank {} is locked",
+            other
+        ))),
+        Response::Ack(status) => bail!(CrispyError::rejected("CopyBank", status)),
+        response => bail!(CrispyError::unexpected(&response)),
+    }
+
+    Ok(())
+}
+
+/// Set the active bank for the next boot.
+pub fn set_bank(transport: &mut Transport, bank: u8) -> Result<()> {
+    println!(
+        "Setting active bank to {} ({})...",
+        bank,
+        if bank == 0 { "A" } else { "B" }
+    );
+
+    let response = send_set_active_bank(transport, bank)?;
+
+    match response {
+        Response::Ack(AckStatus::Ok) => {
+            println!("Active bank set successfully.");
+            println!(
+                "Use 'crispy-upload --port {} reboot' to restart the device.",
+                transport.port_name()
+            );
+        }
+        Response::Ack(AckStatus::BankInvalid) => bail!(CrispyError::Usage(
+            "Invalid bank: must be 0 (A) or 1 (B)".into()
+        )),
+        Response::Ack(AckStatus::CrcError) => {
+            bail!(CrispyError::Verify(format!(
+                "Bank {} has no valid firmware (CRC check failed)",
+                bank
+            )))
+        }
+        Response::Ack(AckStatus::VersionTooOld) => {
+            bail!(CrispyError::Protocol(format!(
+                "Bank {} is older than the device's anti-rollback minimum",
+                bank
+            )))
+        }
+        Response::Ack(status) => bail!(CrispyError::rejected("SetActiveBank", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+
+    Ok(())
+}
+
+/// Send `SetActiveBank`, retrying after a short delay while the device is
+/// `Busy` finishing an upload. Returns the last response.
+fn send_set_active_bank(transport: &mut Transport, bank: u8) -> Result<Response> {
+    let mut attempt = 1;
+    loop {
+        let response = transport.send_recv(&Command::SetActiveBank { bank }, None)?;
+        if !matches!(response, Response::Ack(AckStatus::Busy)) || attempt == SET_BANK_ATTEMPTS {
+            return Ok(response);
+        }
+        eprintln!(
+            "Device busy, retrying in {} ms ({}/{})...",
+            SET_BANK_RETRY_DELAY.as_millis(),
+            attempt,
+            SET_BANK_ATTEMPTS - 1
+        );
+        std::thread::sleep(SET_BANK_RETRY_DELAY);
+        attempt += 1;
+    }
+}
+
+/// Lock `bank` against writes, or unlock it.
+pub fn set_bank_lock(transport: &mut Transport, bank: u8, locked: bool) -> Result<()> {
+    let action = if locked { "Locking" } else { "Unlocking" };
+    println!(
+        "{} bank {} ({})...",
+        action,
+        bank,
+        if bank == 0 { "A" } else { "B" }
+    );
+
+    // Bootloaders without SetBankLock drop the command, so this times out.
+    let response = transport
+        .send_recv(&Command::SetBankLock { bank, locked }, None)
+        .context("SetBankLock failed (bootloader may predate this command)")?;
+
+    match response {
+        Response::Ack(AckStatus::Ok) if locked => {
+            println!("Bank locked. Uploads, copies and wipes will not touch it.")
+        }
+        Response::Ack(AckStatus::Ok) => println!("Bank unlocked."),
+        Response::Ack(AckStatus::BankInvalid) => bail!(CrispyError::Usage(
+            "Invalid bank: must be 0 (A) or 1 (B)".into()
+        )),
+        Response::Ack(status) => bail!(CrispyError::rejected("SetBankLock", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+
+    Ok(())
+}
+
+/// Check that `bank`'s flash CRC equals `crc`, without changing anything.
+/// `timeout` overrides how long to wait for the device's answer.
+pub fn verify_crc(
+    transport: &mut Transport,
+    bank: u8,
+    crc: u32,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    println!(
+        "Verifying bank {} ({}) against CRC32 0x{:08x}...",
+        bank,
+        if bank == 0 { "A" } else { "B" },
+        crc
+    );
+
+    let pb = ProgressBar::new(100).with_style(percent_style()?);
+    pb.set_message("Verify");
+    let matches = device::verify_with_progress(transport, bank, crc, timeout, |done, _| {
+        pb.set_position(done)
+    });
+    pb.finish_and_clear();
+
+    if !matches? {
+        bail!(CrispyError::Verify(format!(
+            "Bank {} does not match CRC32 0x{:08x}",
+            bank, crc
+        )));
+    }
+    println!("Bank {} matches.", bank);
+    Ok(())
+}
+
+/// Check whether banks A and B hold the same image. `timeout` overrides how
+/// long to wait for the device's answer.
+pub fn compare(transport: &mut Transport, timeout: Option<Duration>) -> Result<()> {
+    println!("Comparing banks A and B...");
+
+    let pb = ProgressBar::new(100).with_style(percent_style()?);
+    pb.set_message("Verify");
+    let comparison =
+        device::compare_banks_with_progress(transport, timeout, |done, _| pb.set_position(done));
+    pb.finish_and_clear();
+    let comparison = comparison?;
+
+    println!("  Bank A CRC32: 0x{:08x}", comparison.a_crc);
+    println!("  Bank B CRC32: 0x{:08x}", comparison.b_crc);
+    if !comparison.equal {
+        bail!(CrispyError::Verify("Banks A and B differ".to_string()));
+    }
+    println!("Banks A and B match.");
+    Ok(())
+}
+
+/// Read bank `bank`'s image back into `output`, `chunk_size` bytes per
+/// `ReadBank`, and check it against the CRC BootData records for it.
+/// `timeout` overrides how long each `ReadBank` waits for its chunk.
+pub fn dump(
+    transport: &mut Transport,
+    bank: u8,
+    output: &Path,
+    chunk_size: Option<usize>,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    if bank > 1 {
+        bail!(CrispyError::Usage(format!(
+            "Invalid bank {}: must be 0 (A) or 1 (B)",
+            bank
+        )));
+    }
+    let Some(info) = query_bank_info(transport, bank)? else {
+        bail!(CrispyError::Protocol(
+            "cannot dump without the bank's size".into()
+        ));
+    };
+    if info.size == 0 {
+        bail!(CrispyError::Usage(format!(
+            "Bank {} holds no firmware",
+            bank
+        )));
+    }
+
+    println!(
+        "Reading bank {} ({}, {} bytes)...",
+        bank,
+        if bank == 0 { "A" } else { "B" },
+        info.size
+    );
+    let pb = ProgressBar::new(u64::from(info.size)).with_style(bytes_style()?);
+    let mut image = Vec::with_capacity(info.size as usize);
+    let mut reader = device::BankReader::new(
+        transport,
+        bank,
+        info.size,
+        chunk_size.unwrap_or(MAX_DATA_BLOCK_SIZE),
+        timeout,
+    );
+    let read = loop {
+        match reader.next_chunk() {
+            Ok(Some((_, chunk))) => {
+                image.extend_from_slice(chunk);
+                pb.set_position(image.len() as u64);
+            }
+            Ok(None) => break Ok(()),
+            Err(err) => break Err(err),
+        }
+    };
+    pb.finish_and_clear();
+    read?;
+
+    let crc32 = CRC32.checksum(&image);
+    if crc32 != info.crc32 {
+        bail!(CrispyError::Verify(format!(
+            "Bank {} read back with CRC32 0x{:08x}, BootData records 0x{:08x}",
+            bank, crc32, info.crc32
+        )));
+    }
+    fs::write(output, &image).with_context(|| format!("Failed to write {}", output.display()))?;
+    println!(
+        "Bank {}: {} bytes written to {} (CRC32 0x{:08x})",
+        bank,
+        image.len(),
+        output.display(),
+        crc32
+    );
+    Ok(())
+}
+
+/// Print config slot `key`.
+pub fn config_get(transport: &mut Transport, key: u8) -> Result<()> {
+    // Bootloaders without ConfigGet drop the command, so this times out.
+    let response = transport
+        .send_recv(&Command::ConfigGet { key }, None)
+        .context("ConfigGet failed (bootloader may predate this command)")?;
+
+    match response {
+        Response::ConfigValue {
+            key,
+            value: CONFIG_UNSET,
+        } => println!("Slot {}: (unset)", key),
+        Response::ConfigValue { key, value } => {
+            println!("Slot {}: {} (0x{:08x})", key, value, value)
+        }
+        Response::Ack(status) => bail!(CrispyError::rejected("ConfigGet", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+
+    Ok(())
+}
+
+/// Store `value` in config slot `key`.
+pub fn config_set(transport: &mut Transport, key: u8, value: u32) -> Result<()> {
+    print!("Setting config slot {} to {}... ", key, value);
+    std::io::stdout().flush()?;
+
+    // Bootloaders without ConfigSet drop the command, so this times out.
+    let response = transport
+        .send_recv(&Command::ConfigSet { key, value }, None)
+        .context("ConfigSet failed (bootloader may predate this command)")?;
+
+    match response {
+        Response::Ack(AckStatus::Ok) => println!("OK"),
+        Response::Ack(AckStatus::FlashError) => bail!(CrispyError::Verify(
+            "ConfigSet failed: the config sector did not program (see error-log)".into()
+        )),
+        Response::Ack(status) => bail!(CrispyError::rejected("ConfigSet", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+
+    Ok(())
+}
+
+/// Set the rollback threshold: unconfirmed boots before rolling back.
+pub fn boot_policy(transport: &mut Transport, max_attempts: u8) -> Result<()> {
+    println!(
+        "Setting rollback threshold to {} unconfirmed boot(s)...",
+        max_attempts
+    );
+
+    // Bootloaders without SetBootPolicy drop the command, so this times out.
+    let response = transport
+        .send_recv(&Command::SetBootPolicy { max_attempts }, None)
+        .context("SetBootPolicy failed (bootloader may predate this command)")?;
+
+    match response {
+        Response::Ack(AckStatus::Ok) => println!("Boot policy saved."),
+        Response::Ack(AckStatus::BadState) => bail!(CrispyError::Protocol(
+            "Cannot change the boot policy: upload in progress".into()
+        )),
+        Response::Ack(status) => bail!(CrispyError::rejected("SetBootPolicy", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+
+    Ok(())
+}
+
+/// Wipe all firmware banks and reset boot data.
+///
+/// The device refuses while a bank is locked; with `force` both banks are
+/// unlocked and the wipe is retried.
+pub fn wipe(transport: &mut Transport, force: bool) -> Result<()> {
+    println!("Resetting boot data (invalidates all firmware)...");
+
+    let mut response = transport.send_recv(&Command::WipeAll, None)?;
+    if force && matches!(response, Response::Ack(AckStatus::BankLocked)) {
+        println!("A bank is locked, unlocking both banks (--force)...");
+        for bank in 0..2 {
+            set_bank_lock(transport, bank, false)?;
         }
-        Response::Ack(status) => {
-            println!("Unexpected ACK response: {:?}", status);
+        response = transport.send_recv(&Command::WipeAll, None)?;
+    }
+
+    match response {
+        Response::Ack(AckStatus::Ok) => {
+            println!("Boot data reset. Firmware banks marked as invalid.");
+            println!("Device is now in update mode, ready for firmware upload.");
+        }
+        Response::Ack(AckStatus::BadState) => {
+            bail!(CrispyError::Protocol(
+                "Cannot wipe: device is not in idle state (upload in progress?)".into()
+            ))
         }
+        Response::Ack(AckStatus::BankLocked) => bail!(CrispyError::Protocol(
+            "Cannot wipe: a bank is locked (unlock it, or pass --force)".into()
+        )),
+        Response::Ack(status) => bail!(CrispyError::rejected("Wipe", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
     }
 
     Ok(())
 }
 
-/// Upload firmware to the specified bank.
-pub fn upload(transport: &mut Transport, file: &Path, bank: u8, version: u32) -> Result<()> {
-    // Read firmware file
-    let firmware = fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
-    let size = firmware.len() as u32;
-    let crc32 = CRC32.checksum(&firmware);
+/// Reboot the device.
+pub fn reboot(transport: &mut Transport) -> Result<()> {
+    print!("Rebooting device... ");
+    std::io::stdout().flush()?;
 
-    println!(
-        "Firmware: {} ({} bytes, CRC32: 0x{:08x})",
-        file.display(),
-        size,
-        crc32
-    );
-    println!(
-        "Target:   Bank {} ({})",
-        bank,
-        if bank == 0 { "A" } else { "B" }
-    );
-    println!("Version:  {}", version);
-    println!();
+    let response = transport.send_recv(&Command::Reboot, None)?;
+
+    match response {
+        Response::Ack(AckStatus::Ok) => println!("OK"),
+        Response::Ack(status) => bail!(CrispyError::rejected("Reboot", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+
+    Ok(())
+}
 
-    // Start update (includes erasing the target bank - can take 30+ seconds)
-    print!("Starting update (erasing bank)... ");
+/// Reset the device into the RP2040 ROM USB bootloader.
+pub fn bootrom(transport: &mut Transport) -> Result<()> {
+    print!("Resetting into ROM USB bootloader... ");
     std::io::stdout().flush()?;
 
-    let response = transport.send_recv_timeout(
-        &Command::StartUpdate {
-            bank,
-            size,
-            crc32,
-            version,
-        },
-        60_000, // 60 second timeout for bank erase
-    )?;
+    let response = transport.send_recv(&Command::EnterBootrom, None)?;
 
     match response {
         Response::Ack(AckStatus::Ok) => println!("OK"),
-        Response::Ack(status) => bail!("StartUpdate failed: {:?}", status),
-        _ => bail!("Unexpected response: {:?}", response),
-    }
-
-    // Send data blocks
-    let pb = ProgressBar::new(size as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template(
-                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})",
-            )?
-            .progress_chars("#>-"),
-    );
+        Response::Ack(status) => bail!(CrispyError::rejected("EnterBootrom", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
 
-    for (i, chunk) in firmware.chunks(CHUNK_SIZE).enumerate() {
-        let offset = (i * CHUNK_SIZE) as u32;
-        let response = transport.send_recv(&Command::DataBlock {
-            offset,
-            data: chunk.to_vec(),
-        })?;
+    println!("The device should now enumerate as an RPI-RP2 UF2 drive.");
 
-        match response {
-            Response::Ack(AckStatus::Ok) => {}
-            Response::Ack(status) => {
-                pb.abandon();
-                bail!("DataBlock failed at offset {}: {:?}", offset, status);
+    Ok(())
+}
+
+/// Show flash erase counters per region.
+pub fn wear(transport: &mut Transport) -> Result<()> {
+    let response = transport.send_recv(&Command::GetWearStats, None)?;
+
+    match response {
+        Response::WearStats {
+            bank_a_erases,
+            bank_b_erases,
+            bootdata_erases,
+        } => {
+            println!("Flash Wear (erase operations):");
+            println!("  Bank A:      {}", bank_a_erases);
+            println!("  Bank B:      {}", bank_b_erases);
+            println!("  BootData:    {}", bootdata_erases);
+        }
+        Response::Ack(status) => bail!(CrispyError::rejected("GetWearStats", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+
+    Ok(())
+}
+
+/// Show transport error counters since the bootloader started.
+pub fn transport_stats(transport: &mut Transport) -> Result<()> {
+    // Bootloaders without GetTransportStats drop the command, so this times out.
+    let response = transport
+        .send_recv(&Command::GetTransportStats, None)
+        .context("GetTransportStats failed (bootloader may predate this command)")?;
+
+    match response {
+        Response::TransportStats {
+            rx_overflows,
+            tx_drops,
+            decode_errors,
+            frames_received,
+        } => {
+            println!("USB Transport:");
+            println!("  Frames received: {}", frames_received);
+            println!("  Decode errors:   {}", decode_errors);
+            println!("  RX overflows:    {}", rx_overflows);
+            println!("  TX drops:        {}", tx_drops);
+        }
+        Response::Ack(status) => bail!(CrispyError::rejected("GetTransportStats", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+
+    Ok(())
+}
+
+/// Show the error codes the bootloader recorded since it started, oldest first.
+pub fn error_log(transport: &mut Transport) -> Result<()> {
+    // Bootloaders without GetErrorLog drop the command, so this times out.
+    let response = transport
+        .send_recv(&Command::GetErrorLog, None)
+        .context("GetErrorLog failed (bootloader may predate this command)")?;
+
+    match response {
+        Response::ErrorLog { total, recent } => {
+            if total == 0 {
+                println!("No errors recorded since the bootloader started");
+                return Ok(());
+            }
+            println!("Errors recorded: {}", total);
+            if total as usize > recent.len() {
+                println!("  (showing the last {})", recent.len());
             }
-            _ => {
-                pb.abandon();
-                bail!("Unexpected response at offset {}: {:?}", offset, response);
+            for code in recent {
+                println!("  {:?}", code);
             }
         }
-
-        pb.set_position(offset as u64 + chunk.len() as u64);
+        Response::Ack(status) => bail!(CrispyError::rejected("GetErrorLog", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
     }
 
-    pb.finish_with_message("Upload complete");
-    println!();
+    Ok(())
+}
 
-    // Finish update
-    print!("Finalizing... ");
-    std::io::stdout().flush()?;
+/// Stream the bootloader's defmt output to stdout until interrupted.
+///
+/// The output is the raw defmt stream: pipe it to a decoder with the
+/// bootloader ELF, e.g. `defmt-print -e crispy-bootloader`. Status goes to
+/// stderr so it does not mix with the stream.
+pub fn logs(transport: &mut Transport) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    if stdout.is_terminal() {
+        bail!(CrispyError::Usage(
+            "logs writes binary defmt data; pipe it to `defmt-print -e <bootloader ELF>`".into()
+        ));
+    }
 
-    let response = transport.send_recv(&Command::FinishUpdate)?;
+    // Bootloaders without StreamLogs drop the command, so this times out.
+    let response = transport
+        .send_recv(&Command::StreamLogs { enable: true }, None)
+        .context("StreamLogs failed (bootloader may predate this command)")?;
 
     match response {
-        Response::Ack(AckStatus::Ok) => println!("OK"),
-        Response::Ack(AckStatus::CrcError) => bail!("CRC verification failed!"),
-        Response::Ack(status) => bail!("FinishUpdate failed: {:?}", status),
-        _ => bail!("Unexpected response: {:?}", response),
+        Response::Ack(AckStatus::Ok) => {}
+        Response::Ack(AckStatus::BadCommand) => bail!(CrispyError::Protocol(
+            "Bootloader built without the log-stream feature".into()
+        )),
+        Response::Ack(status) => bail!(CrispyError::rejected("StreamLogs", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
     }
-
-    println!();
-    println!("Firmware uploaded successfully!");
-    println!(
-        "Use 'crispy-upload --port {} reboot' to restart the device.",
+    eprintln!(
+        "Streaming logs from {}, Ctrl-C to stop",
         transport.port_name()
     );
 
+    loop {
+        // Anything else is a quiet device or a stray response to an earlier
+        // command.
+        if let Some(Response::Log { dropped, bytes }) = transport.recv()? {
+            if dropped > 0 {
+                eprintln!("Warning: the device dropped {} log bytes", dropped);
+            }
+            stdout.write_all(&bytes)?;
+            stdout.flush()?;
+        }
+    }
+}
+
+/// Show the chip temperature and, on boards that sense it, VSYS.
+pub fn telemetry(transport: &mut Transport) -> Result<()> {
+    // Bootloaders without GetTelemetry drop the command, so this times out.
+    let response = transport
+        .send_recv(&Command::GetTelemetry, None)
+        .context("GetTelemetry failed (bootloader may predate this command)")?;
+
+    match response {
+        Response::Telemetry {
+            temp_c_milli,
+            vsys_mv,
+        } => {
+            println!("Telemetry:");
+            println!("  Temperature: {:.1} °C", f64::from(temp_c_milli) / 1000.0);
+            if vsys_mv == 0 {
+                println!("  VSYS:        not monitored");
+            } else {
+                println!("  VSYS:        {:.2} V", f64::from(vsys_mv) / 1000.0);
+            }
+        }
+        Response::Ack(status) => bail!(CrispyError::rejected("GetTelemetry", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+
     Ok(())
 }
 
-/// Set the active bank for the next boot.
-pub fn set_bank(transport: &mut Transport, bank: u8) -> Result<()> {
+/// Print why the device last reset.
+pub fn reset_reason(transport: &mut Transport) -> Result<()> {
+    // Bootloaders without GetResetReason drop the command, so this times out.
+    let response = transport
+        .send_recv(&Command::GetResetReason, None)
+        .context("GetResetReason failed (bootloader may predate this command)")?;
+
+    match response {
+        Response::ResetReason { reason } => match ResetReason::from_id(reason) {
+            Some(known) => println!("Reset reason: {}", describe_reset_reason(known)),
+            None => println!("Reset reason: unknown ({})", reason),
+        },
+        Response::Ack(status) => bail!(CrispyError::rejected("GetResetReason", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+
+    Ok(())
+}
+
+/// Run `FlashSelfTest` on the device's scratch sector and report the result.
+pub fn selftest(transport: &mut Transport) -> Result<()> {
     println!(
-        "Setting active bank to {} ({})...",
-        bank,
-        if bank == 0 { "A" } else { "B" }
+        "Running flash self-test on the scratch sector at 0x{:08x}...",
+        SELFTEST_SCRATCH_ADDR
     );
-
-    let response = transport.send_recv(&Command::SetActiveBank { bank })?;
+    // Bootloaders without FlashSelfTest drop the command, so this times out.
+    let response = transport
+        .send_recv(&Command::FlashSelfTest, None)
+        .context("FlashSelfTest failed (bootloader may predate this command)")?;
 
     match response {
-        Response::Ack(AckStatus::Ok) => {
-            println!("Active bank set successfully.");
-            println!(
-                "Use 'crispy-upload --port {} reboot' to restart the device.",
-                transport.port_name()
-            );
-        }
-        Response::Ack(AckStatus::BankInvalid) => bail!("Invalid bank: must be 0 (A) or 1 (B)"),
-        Response::Ack(AckStatus::CrcError) => {
-            bail!("Bank {} has no valid firmware (CRC check failed)", bank)
-        }
-        Response::Ack(status) => bail!("SetActiveBank failed: {:?}", status),
-        _ => bail!("Unexpected response: {:?}", response),
+        Response::SelfTest { passed: true, .. } => println!("Flash self-test passed."),
+        Response::SelfTest { detail, .. } => bail!(CrispyError::Verify(format!(
+            "Flash self-test failed: {}",
+            describe_self_test_detail(detail)
+        ))),
+        Response::Ack(status) => bail!(CrispyError::rejected("FlashSelfTest", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
     }
 
     Ok(())
 }
 
-/// Wipe all firmware banks and reset boot data.
-pub fn wipe(transport: &mut Transport) -> Result<()> {
-    println!("Resetting boot data (invalidates all firmware)...");
+fn describe_self_test_detail(detail: u8) -> String {
+    match detail {
+        SELFTEST_ERASE_FAILED => "the sector did not erase".into(),
+        SELFTEST_PROGRAM_FAILED => "programming the test pattern failed".into(),
+        SELFTEST_VERIFY_FAILED => "the test pattern read back wrong".into(),
+        SELFTEST_RESTORE_FAILED => "the sector's contents could not be restored".into(),
+        other => format!("unknown detail {}", other),
+    }
+}
 
-    let response = transport.send_recv(&Command::WipeAll)?;
+fn describe_reset_reason(reason: ResetReason) -> &'static str {
+    match reason {
+        ResetReason::PowerOn => "power-on or brown-out",
+        ResetReason::RunPin => "RUN pin (reset button)",
+        ResetReason::Debugger => "debugger restart",
+        ResetReason::Watchdog => "watchdog timeout (firmware hung)",
+        ResetReason::WatchdogForced => "forced watchdog reset",
+        ResetReason::Software => "software reset",
+    }
+}
 
-    match response {
-        Response::Ack(AckStatus::Ok) => {
-            println!("Boot data reset. Firmware banks marked as invalid.");
-            println!("Device is now in update mode, ready for firmware upload.");
-        }
-        Response::Ack(AckStatus::BadState) => {
-            bail!("Cannot wipe: device is not in idle state (upload in progress?)")
+/// Send `count` pings and print each round-trip time and a summary.
+pub fn ping(transport: &mut Transport, count: u32) -> Result<()> {
+    // Start from the clock so a late Pong from an earlier run cannot match.
+    let seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or(0);
+    let mut rtts = Vec::with_capacity(count as usize);
+
+    for seq in 0..count {
+        let nonce = seed.wrapping_add(seq);
+        let start = std::time::Instant::now();
+        // Bootloaders without Ping drop the command, so this times out.
+        let response = transport
+            .send_recv(&Command::Ping { nonce }, None)
+            .context("Ping failed (bootloader may predate this command)")?;
+        let rtt = start.elapsed();
+
+        match response {
+            Response::Pong { nonce: echoed } if echoed == nonce => {
+                println!("Pong {}: {:.2} ms", seq + 1, rtt.as_secs_f64() * 1000.0);
+                rtts.push(rtt);
+            }
+            Response::Pong { nonce: echoed } => bail!(CrispyError::Protocol(format!(
+                "Pong nonce mismatch: sent {:#010x}, got {:#010x}",
+                nonce, echoed
+            ))),
+            Response::Ack(status) => bail!(CrispyError::rejected("Ping", status)),
+            _ => bail!(CrispyError::unexpected(&response)),
         }
-        Response::Ack(status) => bail!("Wipe failed: {:?}", status),
-        _ => bail!("Unexpected response: {:?}", response),
     }
 
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    let min = rtts.iter().copied().min().unwrap_or_default();
+    let max = rtts.iter().copied().max().unwrap_or_default();
+    let avg = rtts.iter().sum::<Duration>() / count;
+    println!(
+        "{} pings: min {:.2} ms, avg {:.2} ms, max {:.2} ms",
+        count,
+        ms(min),
+        ms(avg),
+        ms(max)
+    );
+
     Ok(())
 }
 
-/// Reboot the device.
-pub fn reboot(transport: &mut Transport) -> Result<()> {
-    print!("Rebooting device... ");
-    std::io::stdout().flush()?;
+/// Show the flash layout and how much of each region is used.
+pub fn flash_map(transport: &mut Transport) -> Result<()> {
+    // Bootloaders without GetFlashMap drop the command, so this times out.
+    let response = transport
+        .send_recv(&Command::GetFlashMap, None)
+        .context("GetFlashMap failed (bootloader may predate this command)")?;
+
+    let regions = match response {
+        Response::FlashMap { regions } => regions,
+        Response::Ack(status) => bail!(CrispyError::rejected("GetFlashMap", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    };
+
+    println!(
+        "{:<11} {:>10} {:>10} {:>9} {:>9} {:>6}",
+        "Region", "Start", "End", "Size", "Used", "%"
+    );
+    for region in &regions {
+        let name = match region.kind {
+            FlashRegionKind::Boot2 => "boot2",
+            FlashRegionKind::Bootloader => "Bootloader",
+            FlashRegionKind::BankA => "Bank A",
+            FlashRegionKind::BankB => "Bank B",
+            FlashRegionKind::BootData => "BootData",
+            FlashRegionKind::WearStats => "Wear stats",
+            FlashRegionKind::Golden => "Golden",
+            FlashRegionKind::Free => "(free)",
+            FlashRegionKind::Config => "Config",
+            FlashRegionKind::Data => "Data",
+            FlashRegionKind::SelfTest => "Self-test",
+        };
+        let percent = if region.len == 0 {
+            0.0
+        } else {
+            f64::from(region.used) * 100.0 / f64::from(region.len)
+        };
+        println!(
+            "{:<11} 0x{:08x} 0x{:08x} {:>9} {:>9} {:>5.1}%",
+            name,
+            region.start,
+            region.start + region.len,
+            region.len,
+            region.used,
+            percent
+        );
+    }
+
+    let total: u64 = regions.iter().map(|r| u64::from(r.len)).sum();
+    let used: u64 = regions.iter().map(|r| u64::from(r.used)).sum();
+    let free: u64 = regions
+        .iter()
+        .filter(|r| r.kind == FlashRegionKind::Free)
+        .map(|r| u64::from(r.len))
+        .sum();
+    println!(
+        "Total {} KB: {} KB used, {} KB unassigned",
+        total / 1024,
+        used.div_ceil(1024),
+        free / 1024
+    );
+
+    Ok(())
+}
 
-    let response = transport.send_recv(&Command::Reboot)?;
+/// Show the bootloader build identification (git hash, build time, features).
+pub fn buildinfo(transport: &mut Transport) -> Result<()> {
+    // Bootloaders without GetBuildInfo drop the command, so this times out.
+    let response = transport
+        .send_recv(&Command::GetBuildInfo, None)
+        .context("GetBuildInfo failed (bootloader may predate this command)")?;
 
     match response {
-        Response::Ack(AckStatus::Ok) => println!("OK"),
-        Response::Ack(status) => bail!("Reboot failed: {:?}", status),
-        _ => bail!("Unexpected response: {:?}", response),
+        Response::BuildInfo {
+            git_hash,
+            build_epoch,
+            features,
+        } => {
+            let hash_len = git_hash.iter().position(|&b| b == 0).unwrap_or(8);
+            println!("Bootloader Build:");
+            println!(
+                "  Git hash:    {}",
+                String::from_utf8_lossy(&git_hash[..hash_len])
+            );
+            println!(
+                "  Built:       {} ({})",
+                format_utc(build_epoch),
+                build_epoch
+            );
+            println!("  Features:    {}", describe_features(features));
+        }
+        Response::Ack(status) => bail!(CrispyError::rejected("GetBuildInfo", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
     }
 
     Ok(())
 }
 
+/// Names of the `BUILD_FEATURE_*` bits set in `features`, plus any unknown bits.
+fn describe_features(features: u32) -> String {
+    const NAMES: [(u32, &str); 7] = [
+        (BUILD_FEATURE_LOGGING, "logging"),
+        (BUILD_FEATURE_SKIP_BOOT_CRC, "skip-boot-crc"),
+        (BUILD_FEATURE_SIGNING, "signing"),
+        (BUILD_FEATURE_COMPRESSION, "compression"),
+        (BUILD_FEATURE_GOLDEN_BANK, "golden-bank"),
+        (BUILD_FEATURE_LOG_STREAM, "log-stream"),
+        (BUILD_FEATURE_DUAL_CORE_FLASH, "dual-core-flash"),
+    ];
+
+    let mut names: Vec<String> = NAMES
+        .iter()
+        .filter(|(bit, _)| features & bit != 0)
+        .map(|(_, name)| name.to_string())
+        .collect();
+    let known = NAMES.iter().fold(0, |acc, (bit, _)| acc | bit);
+    if features & !known != 0 {
+        names.push(format!("unknown(0x{:08x})", features & !known));
+    }
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
+/// Format a Unix timestamp as `YYYY-MM-DD HH:MM:SS UTC`.
+fn format_utc(epoch: u32) -> String {
+    let secs = epoch as i64;
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    // Civil-from-days (Howard Hinnant), valid for the whole u32 range.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Print the CRC32 a device computes for a firmware file with `algo`.
+pub fn crc(
+    file: &Path,
+    algo: CrcChoice,
+    pad_to_page: bool,
+    input_format: InputFormat,
+) -> Result<()> {
+    let firmware = read_image_as(file, input_format, pad_to_page)?;
+
+    println!(
+        "CRC32: 0x{:08x} ({} bytes) {}",
+        algo.algorithm().checksum(&firmware),
+        firmware.len(),
+        file.display()
+    );
+
+    Ok(())
+}
+
 // UF2 constants
 const UF2_MAGIC_START0: u32 = 0x0A324655;
 const UF2_MAGIC_START1: u32 = 0x9E5D5157;
 const UF2_MAGIC_END: u32 = 0x0AB16F30;
 const UF2_FLAG_FAMILY_ID: u32 = 0x00002000;
 const UF2_PAYLOAD_SIZE: usize = 256;
+const UF2_BLOCK_SIZE: usize = 512;
+const UF2_FAMILY_RP2040: u32 = 0xE48BFF56;
+const UF2_FLAG_NOT_MAIN_FLASH: u32 = 0x00000001;
+/// Largest payload a 512-byte block can carry.
+const UF2_MAX_PAYLOAD_SIZE: usize = 476;
+
+/// The fields of one UF2 block.
+struct Uf2Block<'a> {
+    flags: u32,
+    target_addr: u32,
+    payload: &'a [u8],
+    block_no: u32,
+    num_blocks: u32,
+    family: Option<u32>,
+}
+
+/// Split a UF2 file into its blocks, checking their magic numbers.
+fn parse_uf2<'a>(file: &Path, data: &'a [u8]) -> Result<Vec<Uf2Block<'a>>> {
+    if data.is_empty() || !data.len().is_multiple_of(UF2_BLOCK_SIZE) {
+        bail!(CrispyError::Usage(format!(
+            "{} is not a UF2 file: size {} is not a multiple of {} bytes",
+            file.display(),
+            data.len(),
+            UF2_BLOCK_SIZE
+        )));
+    }
+
+    let word = |block: &[u8], offset: usize| {
+        u32::from_le_bytes(block[offset..offset + 4].try_into().unwrap())
+    };
+
+    data.chunks_exact(UF2_BLOCK_SIZE)
+        .enumerate()
+        .map(|(i, block)| {
+            if word(block, 0) != UF2_MAGIC_START0
+                || word(block, 4) != UF2_MAGIC_START1
+                || word(block, UF2_BLOCK_SIZE - 4) != UF2_MAGIC_END
+            {
+                bail!(CrispyError::Usage(format!(
+                    "Block {} (offset 0x{:x}): bad UF2 magic",
+                    i,
+                    i * UF2_BLOCK_SIZE
+                )));
+            }
+            let payload_size = word(block, 16) as usize;
+            if payload_size > UF2_MAX_PAYLOAD_SIZE {
+                bail!(CrispyError::Usage(format!(
+                    "Block {} (offset 0x{:x}): payload size {} exceeds {} bytes",
+                    i,
+                    i * UF2_BLOCK_SIZE,
+                    payload_size,
+                    UF2_MAX_PAYLOAD_SIZE
+                )));
+            }
+            let flags = word(block, 8);
+            Ok(Uf2Block {
+                flags,
+                target_addr: word(block, 12),
+                payload: &block[32..32 + payload_size],
+                block_no: word(block, 20),
+                num_blocks: word(block, 24),
+                family: (flags & UF2_FLAG_FAMILY_ID != 0).then(|| word(block, 28)),
+            })
+        })
+        .collect()
+}
+
+/// The image a UF2 file writes to flash, as raw bytes from its lowest
+/// address. Blocks for other families or marked not-main-flash are skipped;
+/// gaps between blocks read as erased flash (0xFF). Overlapping blocks are
+/// refused, as is an image larger than the flash.
+fn uf2_image(file: &Path, data: &[u8]) -> Result<Vec<u8>> {
+    let mut blocks: Vec<_> = parse_uf2(file, data)?
+        .into_iter()
+        .filter(|block| {
+            block.flags & UF2_FLAG_NOT_MAIN_FLASH == 0
+                && matches!(block.family, None | Some(UF2_FAMILY_RP2040))
+        })
+        .collect();
+    blocks.sort_by_key(|block| block.target_addr);
+
+    let Some(base) = blocks.first().map(|block| block.target_addr) else {
+        bail!(CrispyError::Usage(format!(
+            "{} has no RP2040 flash blocks",
+            file.display()
+        )));
+    };
+    let mut image = Vec::new();
+    for block in &blocks {
+        let offset = (block.target_addr - base) as usize;
+        if offset < image.len() {
+            bail!(CrispyError::Usage(format!(
+                "{}: block {} at 0x{:08x} overlaps the one before it",
+                file.display(),
+                block.block_no,
+                block.target_addr
+            )));
+        }
+        if offset + block.payload.len() > FLASH_SIZE as usize {
+            bail!(CrispyError::Usage(format!(
+                "{}: blocks span more than the {}MB flash",
+                file.display(),
+                FLASH_SIZE >> 20
+            )));
+        }
+        image.resize(offset, 0xFF);
+        image.extend_from_slice(block.payload);
+    }
+    Ok(image)
+}
 
 /// Convert a raw binary file to UF2 format.
-pub fn bin2uf2(input: &Path, output: &Path, base_address: u32, family_id: u32) -> Result<()> {
-    let data = fs::read(input).with_context(|| format!("Failed to read {}", input.display()))?;
+///
+/// The last block's payload is zero-filled past the end of the file, unless
+/// `pad_to_page` pads the image with 0xFF first, as `upload --pad-to-page`
+/// does, so both write the same bytes.
+pub fn bin2uf2(
+    input: &Path,
+    output: &Path,
+    base_address: u32,
+    family_id: u32,
+    pad_to_page: bool,
+) -> Result<()> {
+    let data = read_image(input, pad_to_page)?;
+
+    // An image placed in flash must end within the layout this tool was
+    // built for (`flash-*` features).
+    let flash_end = u64::from(FLASH_BASE) + u64::from(FLASH_SIZE);
+    let base = u64::from(base_address);
+    if (u64::from(FLASH_BASE)..flash_end).contains(&base) && base + data.len() as u64 > flash_end {
+        bail!(CrispyError::Usage(format!(
+            "{}: {} bytes at 0x{:08X} run past the end of {}MB flash (0x{:08X})",
+            input.display(),
+            data.len(),
+            base_address,
+            FLASH_SIZE >> 20,
+            flash_end
+        )));
+    }
 
     let num_blocks = data.len().div_ceil(UF2_PAYLOAD_SIZE);
     let mut out = Vec::with_capacity(num_blocks * 512);
@@ -271,3 +1974,294 @@ pub fn bin2uf2(input: &Path, output: &Path, base_address: u32, family_id: u32) -
 
     Ok(())
 }
+
+/// What `inspect` reports about a UF2 file.
+#[derive(Debug, PartialEq, Eq)]
+struct Uf2Summary {
+    blocks: usize,
+    payload_bytes: u64,
+    /// Lowest block address and end of the highest block's payload.
+    addr_range: (u32, u32),
+    /// Block count per family ID; `None` for blocks without one.
+    families: std::collections::BTreeMap<Option<u32>, usize>,
+    /// Inconsistencies between the blocks' numbering and the file.
+    warnings: Vec<String>,
+}
+
+fn summarize_uf2(blocks: &[Uf2Block<'_>]) -> Uf2Summary {
+    let mut payload_bytes = 0u64;
+    let mut addr_range: Option<(u32, u32)> = None;
+    let mut families = std::collections::BTreeMap::<Option<u32>, usize>::new();
+    let mut declared_blocks = std::collections::BTreeSet::new();
+    let mut block_numbers = std::collections::BTreeSet::new();
+
+    for block in blocks {
+        let payload_size = block.payload.len() as u32;
+        let end = block.target_addr.saturating_add(payload_size);
+        addr_range = Some(match addr_range {
+            Some((lo, hi)) => (lo.min(block.target_addr), hi.max(end)),
+            None => (block.target_addr, end),
+        });
+        payload_bytes += u64::from(payload_size);
+        *families.entry(block.family).or_default() += 1;
+        block_numbers.insert(block.block_no);
+        declared_blocks.insert(block.num_blocks);
+    }
+
+    // Consistency checks: a single file should declare one total and number
+    // its blocks 0..total without gaps.
+    let block_count = blocks.len();
+    let mut warnings = Vec::new();
+    match declared_blocks.iter().collect::<Vec<_>>().as_slice() {
+        [&total] if total as usize == block_count => {}
+        [&total] => warnings.push(format!(
+            "blocks declare a total of {}, file holds {}",
+            total, block_count
+        )),
+        totals => warnings.push(format!("inconsistent block totals: {:?}", totals)),
+    }
+    if block_numbers.len() != block_count
+        || block_numbers
+            .last()
+            .is_some_and(|&n| n as usize >= block_count)
+    {
+        warnings.push("block numbers are duplicated or not contiguous".to_string());
+    }
+
+    Uf2Summary {
+        blocks: block_count,
+        payload_bytes,
+        addr_range: addr_range.unwrap_or_default(),
+        families,
+        warnings,
+    }
+}
+
+/// Summarize a UF2 file: block count, address range and family IDs.
+pub fn inspect(file: &Path) -> Result<()> {
+    let data = fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let summary = summarize_uf2(&parse_uf2(file, &data)?);
+    let (lo, hi) = summary.addr_range;
+
+    println!("UF2: {}", file.display());
+    println!("  Blocks:      {}", summary.blocks);
+    println!("  Payload:     {} bytes", summary.payload_bytes);
+    println!("  Address:     0x{:08x} - 0x{:08x}", lo, hi);
+    println!("  Family IDs:");
+    for (family, count) in &summary.families {
+        match family {
+            Some(UF2_FAMILY_RP2040) => {
+                println!("    0x{:08x} (RP2040): {} blocks", UF2_FAMILY_RP2040, count)
+            }
+            Some(id) => println!("    0x{:08x}: {} blocks", id, count),
+            None => println!("    (none): {} blocks", count),
+        }
+    }
+    for warning in &summary.warnings {
+        println!("  Warning: {}", warning);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A file under the system temp directory, removed when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "crispy-upload-test-{}-{}",
+                std::process::id(),
+                name
+            ));
+            fs::write(&path, contents).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    /// One 512-byte UF2 block, with a family ID when `family` is set.
+    fn uf2_block(
+        addr: u32,
+        block_no: u32,
+        num_blocks: u32,
+        family: Option<u32>,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        let flags = if family.is_some() {
+            UF2_FLAG_FAMILY_ID
+        } else {
+            0
+        };
+        let mut block = Vec::with_capacity(UF2_BLOCK_SIZE);
+        for word in [
+            UF2_MAGIC_START0,
+            UF2_MAGIC_START1,
+            flags,
+            addr,
+            payload.len() as u32,
+            block_no,
+            num_blocks,
+            family.unwrap_or(0),
+        ] {
+            block.extend_from_slice(&word.to_le_bytes());
+        }
+        block.extend_from_slice(payload);
+        block.resize(UF2_BLOCK_SIZE - 4, 0);
+        block.extend_from_slice(&UF2_MAGIC_END.to_le_bytes());
+        block
+    }
+
+    const RP2040: Option<u32> = Some(UF2_FAMILY_RP2040);
+
+    #[test]
+    fn crc_matches_the_iso_hdlc_check_value() {
+        let file = TempFile::new("crc.bin", b"123456789");
+        let image = read_image_as(&file.0, InputFormat::Raw, false).unwrap();
+        assert_eq!(CrcChoice::IsoHdlc.algorithm().checksum(&image), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn bin2uf2_output_parses_back_to_the_image() {
+        let data: Vec<u8> = (0..600u32).map(|i| i as u8).collect();
+        let input = TempFile::new("roundtrip.bin", &data);
+        let output = TempFile::new("roundtrip.uf2", b"");
+        bin2uf2(&input.0, &output.0, FLASH_BASE, UF2_FAMILY_RP2040, false).unwrap();
+
+        let uf2 = fs::read(&output.0).unwrap();
+        let blocks = parse_uf2(&output.0, &uf2).unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert!(blocks.iter().all(|block| block.family == RP2040));
+        assert_eq!(blocks[2].target_addr, FLASH_BASE + 512);
+
+        // The last block is zero-filled past the end of the file.
+        let mut expected = data.clone();
+        expected.resize(3 * UF2_PAYLOAD_SIZE, 0);
+        assert_eq!(uf2_image(&output.0, &uf2).unwrap(), expected);
+    }
+
+    #[test]
+    fn summary_reports_blocks_range_and_families() {
+        let mut uf2 = uf2_block(0x1000_0100, 1, 3, RP2040, &[0; 256]);
+        uf2.extend(uf2_block(0x1000_0000, 0, 3, RP2040, &[0; 256]));
+        uf2.extend(uf2_block(0x2000_0000, 2, 3, None, &[0; 16]));
+        let summary = summarize_uf2(&parse_uf2(Path::new("x.uf2"), &uf2).unwrap());
+
+        assert_eq!(summary.blocks, 3);
+        assert_eq!(summary.payload_bytes, 528);
+        assert_eq!(summary.addr_range, (0x1000_0000, 0x2000_0010));
+        assert_eq!(
+            summary.families.into_iter().collect::<Vec<_>>(),
+            [(None, 1), (RP2040, 2)]
+        );
+        assert!(summary.warnings.is_empty(), "{:?}", summary.warnings);
+    }
+
+    #[test]
+    fn summary_warns_about_numbering() {
+        let mut uf2 = uf2_block(0x1000_0000, 0, 3, RP2040, &[0; 256]);
+        uf2.extend(uf2_block(0x1000_0100, 0, 3, RP2040, &[0; 256]));
+        let summary = summarize_uf2(&parse_uf2(Path::new("x.uf2"), &uf2).unwrap());
+        assert_eq!(
+            summary.warnings,
+            [
+                "blocks declare a total of 3, file holds 2",
+                "block numbers are duplicated or not contiguous"
+            ]
+        );
+
+        let mut uf2 = uf2_block(0x1000_0000, 0, 2, RP2040, &[0; 256]);
+        uf2.extend(uf2_block(0x1000_0100, 1, 3, RP2040, &[0; 256]));
+        let summary = summarize_uf2(&parse_uf2(Path::new("x.uf2"), &uf2).unwrap());
+        assert_eq!(summary.warnings, ["inconsistent block totals: [2, 3]"]);
+    }
+
+    #[test]
+    fn malformed_uf2_is_rejected() {
+        let file = Path::new("x.uf2");
+        let block = uf2_block(0x1000_0000, 0, 1, RP2040, &[0; 256]);
+
+        assert!(parse_uf2(file, &[]).is_err());
+        assert!(parse_uf2(file, &block[..UF2_BLOCK_SIZE - 1]).is_err());
+        for magic_at in [0, 4, UF2_BLOCK_SIZE - 4] {
+            let mut bad = block.clone();
+            bad[magic_at] ^= 1;
+            let err = parse_uf2(file, &bad).err().unwrap();
+            assert!(err.to_string().contains("bad UF2 magic"), "{}", err);
+        }
+        let mut bad = block.clone();
+        bad[16..20].copy_from_slice(&(UF2_MAX_PAYLOAD_SIZE as u32 + 1).to_le_bytes());
+        assert!(parse_uf2(file, &bad).is_err());
+    }
+
+    #[test]
+    fn uf2_image_fills_gaps_and_skips_other_families() {
+        let mut uf2 = uf2_block(0x1000_0200, 1, 3, RP2040, &[2; 4]);
+        uf2.extend(uf2_block(0x1000_0000, 0, 3, RP2040, &[1; 4]));
+        uf2.extend(uf2_block(0x1000_0004, 2, 3, Some(0x1234_5678), &[9; 4]));
+        let image = uf2_image(Path::new("x.uf2"), &uf2).unwrap();
+
+        assert_eq!(image.len(), 0x204);
+        assert_eq!(image[..4], [1; 4]);
+        assert!(image[4..0x200].iter().all(|&b| b == 0xFF));
+        assert_eq!(image[0x200..], [2; 4]);
+    }
+
+    #[test]
+    fn uf2_image_refuses_overlaps() {
+        let mut uf2 = uf2_block(0x1000_0000, 0, 2, RP2040, &[1; 256]);
+        uf2.extend(uf2_block(0x1000_00FF, 1, 2, RP2040, &[2; 256]));
+        let err = uf2_image(Path::new("x.uf2"), &uf2).unwrap_err();
+        assert!(err.to_string().contains("overlaps"), "{}", err);
+    }
+
+    #[test]
+    fn pad_to_page_rounds_up_with_erased_bytes() {
+        let file = TempFile::new("pad.bin", &[0xAB; 300]);
+        let image = read_image(&file.0, true).unwrap();
+        assert_eq!(image.len(), 512);
+        assert_eq!(image[..300], [0xAB; 300]);
+        assert!(image[300..].iter().all(|&b| b == 0xFF));
+        assert_eq!(read_image(&file.0, false).unwrap(), [0xAB; 300]);
+
+        // Already page-aligned images are unchanged.
+        let aligned = TempFile::new("aligned.bin", &[0xAB; 256]);
+        assert_eq!(read_image(&aligned.0, true).unwrap(), [0xAB; 256]);
+    }
+
+    #[test]
+    fn pad_to_page_crc_covers_the_padding() {
+        let file = TempFile::new("padcrc.bin", b"123456789");
+        let mut padded = b"123456789".to_vec();
+        padded.resize(256, 0xFF);
+
+        let image = read_image_as(&file.0, InputFormat::Raw, true).unwrap();
+        let algo = CrcChoice::IsoHdlc.algorithm();
+        assert_eq!(image, padded);
+        assert_eq!(algo.checksum(&image), algo.checksum(&padded));
+        assert_ne!(algo.checksum(&image), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn bin2uf2_pad_to_page_writes_the_uploaded_bytes() {
+        let input = TempFile::new("padded.bin", &[0x5A; 100]);
+        let output = TempFile::new("padded.uf2", b"");
+        bin2uf2(&input.0, &output.0, FLASH_BASE, UF2_FAMILY_RP2040, true).unwrap();
+
+        let uf2 = fs::read(&output.0).unwrap();
+        assert_eq!(
+            uf2_image(&output.0, &uf2).unwrap(),
+            read_image(&input.0, true).unwrap()
+        );
+    }
+}
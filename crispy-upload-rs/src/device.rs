@@ -0,0 +1,565 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Device operations for embedding `crispy-upload` in other tools.
+//!
+//! Nothing here prints: progress is reported through callbacks and outcomes
+//! are returned as values. Errors carry a [`CrispyError`] in their chain, so
+//! callers can classify them like the CLI does with `error::exit_code`. The
+//! `crispy-upload` commands are thin wrappers adding progress bars and
+//! messages.
+
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+
+use crispy_common::crc32::CrcAlgorithm;
+use crispy_common::protocol::{
+    AckStatus, Command, ProgressPhase, Response, Semver, DATA_BANK, MAX_DATA_BLOCK_SIZE,
+};
+
+use crate::error::CrispyError;
+use crate::transport::{CommandChannel, Transport};
+
+/// How [`upload_with_options`] sends and stores an image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UploadOptions {
+    /// Make the bank active for the next boot (`FinishUpdate`), or only store
+    /// the image (`FinishUpdateNoActivate`).
+    pub activate: bool,
+    /// `DataBlock` payload size, 1 to `MAX_DATA_BLOCK_SIZE` bytes and at most
+    /// what the device reports in `Status`.
+    pub chunk_size: usize,
+    /// CRC the device verifies the transfer with.
+    pub crc_algo: CrcAlgorithm,
+    /// Response timeout for each command of the upload; `None` keeps the
+    /// transport's.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for UploadOptions {
+    fn default() -> Self {
+        Self {
+            activate: true,
+            chunk_size: MAX_DATA_BLOCK_SIZE,
+            crc_algo: CrcAlgorithm::IsoHdlc,
+            timeout: None,
+        }
+    }
+}
+
+/// An image the device stored and verified.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Uploaded {
+    pub bank: u8,
+    pub size: u32,
+    /// ISO-HDLC CRC-32 of the image, as the device records it.
+    pub crc32: u32,
+}
+
+/// Upload `data` to `bank` with the default [`UploadOptions`], activating it.
+///
+/// `on_progress(sent, total)` is called in bytes once the device accepted
+/// the upload and after every `DataBlock`.
+pub fn upload_with_progress(
+    transport: &mut Transport,
+    data: &[u8],
+    bank: u8,
+    version: Semver,
+    on_progress: impl FnMut(u64, u64),
+) -> Result<Uploaded> {
+    upload_with_options(
+        transport,
+        data,
+        bank,
+        version,
+        &UploadOptions::default(),
+        on_progress,
+        |_, _| {},
+    )
+}
+
+/// Upload `data` to `bank` and store it, as `upload_with_progress` does.
+///
+/// `on_finish(phase, percent)` receives the erase, program and verify
+/// progress the device streams while storing the image. No local checks are
+/// made: the device refuses images that do not fit or fail its header,
+/// vector table or anti-rollback checks.
+pub fn upload_with_options(
+    transport: &mut Transport,
+    data: &[u8],
+    bank: u8,
+    version: Semver,
+    options: &UploadOptions,
+    mut on_progress: impl FnMut(u64, u64),
+    on_finish: impl FnMut(ProgressPhase, u8),
+) -> Result<Uploaded> {
+    let size = u32::try_from(data.len())
+        .map_err(|_| CrispyError::Usage(format!("{} bytes do not fit a bank", data.len())))?;
+    let crc_algo = options.crc_algo;
+
+    // Bootloaders that predate `crc_algo` ignore it and check ISO-HDLC, so
+    // another algorithm fails verification on them rather than passing.
+    let response = transport.send_recv(
+        &Command::StartUpdate {
+            bank,
+            size,
+            crc32: crc_algo.checksum(data),
+            version: version.packed(),
+            crc_algo: crc_algo.id(),
+        },
+        options.timeout,
+    )?;
+
+    match response {
+        Response::Ack(AckStatus::Ok) => {}
+        Response::Ack(AckStatus::BadCommand) if crc_algo != CrcAlgorithm::IsoHdlc => {
+            bail!(CrispyError::Protocol(format!(
+                "StartUpdate rejected: the device does not support CRC algorithm {:?}",
+                crc_algo
+            )))
+        }
+        Response::Ack(AckStatus::BankInvalid) if bank == DATA_BANK => {
+            bail!(CrispyError::Protocol(format!(
+                "StartUpdate rejected: no data partition on this bootloader, or {} bytes exceeds it",
+                size
+            )))
+        }
+        Response::Ack(AckStatus::BankInvalid) => bail!(CrispyError::Protocol(format!(
+            "StartUpdate rejected: invalid bank, or {} bytes exceeds the device's firmware image size",
+            size
+        ))),
+        Response::Ack(AckStatus::BankLocked) => bail!(CrispyError::Protocol(format!(
+            "Bank {} is locked, run 'crispy-upload unlock --bank {}' first",
+            bank, bank
+        ))),
+        Response::Ack(status) => bail!(CrispyError::rejected("StartUpdate", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+
+    on_progress(0, u64::from(size));
+    send_data_blocks(
+        transport,
+        data,
+        options.chunk_size,
+        options.timeout,
+        on_progress,
+    )?;
+
+    let finish = if options.activate {
+        Command::FinishUpdate
+    } else {
+        Command::FinishUpdateNoActivate
+    };
+    match transport.send_recv_progress(&finish, options.timeout, on_finish)? {
+        Response::Ack(AckStatus::Ok) => {}
+        Response::Ack(AckStatus::CrcError) => {
+            bail!(CrispyError::Verify("CRC verification failed!".into()))
+        }
+        Response::Ack(AckStatus::FlashError) => bail!(CrispyError::Verify(
+            "Flash programming failed: a page did not read back as written (see error-log)".into()
+        )),
+        Response::Ack(AckStatus::BadCommand) => bail!(CrispyError::Protocol(
+            "FinishUpdate rejected: the image header is invalid or requires a newer bootloader"
+                .into()
+        )),
+        Response::Ack(AckStatus::VersionTooOld) => bail!(CrispyError::Protocol(format!(
+            "FinishUpdate rejected: version {} is older than the device's anti-rollback minimum",
+            version
+        ))),
+        Response::Ack(status) => bail!(CrispyError::rejected("FinishUpdate", status)),
+        response => bail!(CrispyError::unexpected(&response)),
+    }
+
+    Ok(Uploaded {
+        bank,
+        size,
+        crc32: CrcAlgorithm::IsoHdlc.checksum(data),
+    })
+}
+
+/// End an upload left in progress, such as one whose host exited between
+/// `StartUpdate` and `FinishUpdate`, so the device accepts a new one. A device
+/// with no upload in progress acknowledges it too.
+pub fn abort_update(transport: &mut Transport) -> Result<()> {
+    // Bootloaders without AbortUpdate drop the command, so this times out.
+    let response = transport
+        .send_recv(&Command::AbortUpdate, None)
+        .context("AbortUpdate failed (bootloader may predate this command; reset the device)")?;
+
+    match response {
+        Response::Ack(AckStatus::Ok) => Ok(()),
+        Response::Ack(status) => bail!(CrispyError::rejected("AbortUpdate", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+}
+
+/// Send `data` as `DataBlock`s after a successful `StartUpdate`,
+/// `WriteGolden` or `StartBootloaderUpdate`, calling `on_progress(sent,
+/// total)` in bytes after each block. Each waits for its `Ack` for `timeout`
+/// if given.
+pub fn send_data_blocks(
+    transport: &mut Transport,
+    data: &[u8],
+    chunk_size: usize,
+    timeout: Option<Duration>,
+    mut on_progress: impl FnMut(u64, u64),
+) -> Result<()> {
+    let total = data.len() as u64;
+    for (i, chunk) in data.chunks(chunk_size).enumerate() {
+        let offset = (i * chunk_size) as u32;
+        let response = transport.send_recv(
+            &Command::DataBlock {
+                offset,
+                data: chunk.to_vec(),
+            },
+            timeout,
+        )?;
+
+        match response {
+            Response::Ack(AckStatus::Ok) => {}
+            Response::Ack(status) => bail!(CrispyError::Protocol(format!(
+                "DataBlock failed at offset {}: {:?}",
+                offset, status
+            ))),
+            _ => bail!(CrispyError::Protocol(format!(
+                "Unexpected response at offset {}: {:?}",
+                offset, response
+            ))),
+        }
+
+        on_progress(u64::from(offset) + chunk.len() as u64, total);
+    }
+    Ok(())
+}
+
+/// Check `bank`'s flash CRC against `expected_crc` without changing
+/// anything: `true` when it matches. `on_progress(done, total)` receives the
+/// device's verify progress as a percentage (`total` is 100); `timeout`, if
+/// given, overrides the transport's.
+pub fn verify_with_progress(
+    transport: &mut Transport,
+    bank: u8,
+    expected_crc: u32,
+    timeout: Option<Duration>,
+    mut on_progress: impl FnMut(u64, u64),
+) -> Result<bool> {
+    // Bootloaders without VerifyBank drop the command, so this times out.
+    let response = transport
+        .send_recv_progress(
+            &Command::VerifyBank { bank, expected_crc },
+            timeout,
+            |_, percent| on_progress(u64::from(percent), 100),
+        )
+        .context("VerifyBank failed (bootloader may predate this command)")?;
+
+    match response {
+        Response::Ack(AckStatus::Ok) => Ok(true),
+        Response::Ack(AckStatus::CrcError) => Ok(false),
+        Response::Ack(AckStatus::BankInvalid) => bail!(CrispyError::Usage(format!(
+            "Bank {} holds no firmware (banks are 0 (A) or 1 (B))",
+            bank
+        ))),
+        Response::Ack(status) => bail!(CrispyError::rejected("VerifyBank", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+}
+
+/// The flash CRCs of both banks, as `CompareBanks` reports them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BankComparison {
+    /// CRC-32 of bank A over its stored size.
+    pub a_crc: u32,
+    /// CRC-32 of bank B over its stored size.
+    pub b_crc: u32,
+    /// Both banks hold firmware of the same size and CRC.
+    pub equal: bool,
+}
+
+/// Compare banks A and B on the device without changing anything.
+/// `on_progress(done, total)` receives the device's progress as a percentage
+/// (`total` is 100); `timeout`, if given, overrides the transport's.
+pub fn compare_banks_with_progress(
+    transport: &mut Transport,
+    timeout: Option<Duration>,
+    mut on_progress: impl FnMut(u64, u64),
+) -> Result<BankComparison> {
+    // Bootloaders without CompareBanks drop the command, so this times out.
+    let response = transport
+        .send_recv_progress(&Command::CompareBanks, timeout, |_, percent| {
+            on_progress(u64::from(percent), 100)
+        })
+        .context("CompareBanks failed (bootloader may predate this command)")?;
+
+    match response {
+        Response::BankCompare {
+            a_crc,
+            b_crc,
+            equal,
+        } => Ok(BankComparison {
+            a_crc,
+            b_crc,
+            equal,
+        }),
+        Response::Ack(status) => bail!(CrispyError::rejected("CompareBanks", status)),
+        _ => bail!(CrispyError::unexpected(&response)),
+    }
+}
+
+/// Cursor over a bank's image read back with `ReadBank`.
+///
+/// Each [`next_chunk`](Self::next_chunk) asks for the next block and moves
+/// the cursor by the bytes the device actually returned, so a device that
+/// trims reads (near a boundary, or to a smaller block size) is still read
+/// to the end. Reading stops at the image size given to [`new`](Self::new),
+/// as `GetBankInfo` reports it.
+pub struct BankReader<'a, C: CommandChannel = Transport> {
+    transport: &'a mut C,
+    bank: u8,
+    offset: u32,
+    size: u32,
+    chunk_size: u32,
+    timeout: Option<Duration>,
+    data: Vec<u8>,
+}
+
+impl<'a, C: CommandChannel> BankReader<'a, C> {
+    /// Read bytes `0..size` of `bank` (0 or 1), asking for `chunk_size`
+    /// bytes at a time, capped at `MAX_DATA_BLOCK_SIZE`, and waiting for each
+    /// answer for `timeout` if given.
+    pub fn new(
+        transport: &'a mut C,
+        bank: u8,
+        size: u32,
+        chunk_size: usize,
+        timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            transport,
+            bank,
+            offset: 0,
+            size,
+            chunk_size: chunk_size.clamp(1, MAX_DATA_BLOCK_SIZE) as u32,
+            timeout,
+            data: Vec::new(),
+        }
+    }
+
+    /// Offset of the next byte to read.
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Image size the reader stops at.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// The next chunk and the offset it starts at, or `None` once `size`
+    /// bytes were read.
+    ///
+    /// Fails when the device refuses the read, answers for another bank or
+    /// offset, answers with more bytes than asked for, or returns none before
+    /// `size`: the cursor would not move, and reading on would loop forever.
+    pub fn next_chunk(&mut self) -> Result<Option<(u32, &[u8])>> {
+        if self.offset >= self.size {
+            return Ok(None);
+        }
+        let offset = self.offset;
+        let len = self.chunk_size.min(self.size - offset);
+
+        // Bootloaders without ReadBank drop the command, so this times out.
+        let response = self
+            .transport
+            .send_recv(
+                &Command::ReadBank {
+                    bank: self.bank,
+                    offset,
+                    len,
+                },
+                self.timeout,
+            )
+            .context("ReadBank failed (bootloader may predate this command)")?;
+
+        match response {
+            Response::BankData {
+                bank, offset: got, ..
+            } if bank != self.bank || got != offset => {
+                bail!(CrispyError::Protocol(format!(
+                    "ReadBank for bank {} offset {} answered with bank {} offset {}",
+                    self.bank, offset, bank, got
+                )))
+            }
+            Response::BankData { data, .. } if data.is_empty() => {
+                bail!(CrispyError::Protocol(format!(
+                    "ReadBank returned no data at offset {} of {}",
+                    offset, self.size
+                )))
+            }
+            Response::BankData { data, .. } if data.len() > len as usize => {
+                bail!(CrispyError::Protocol(format!(
+                    "ReadBank returned {} bytes at offset {}, {} asked for",
+                    data.len(),
+                    offset,
+                    len
+                )))
+            }
+            Response::BankData { data, .. } => self.data = data,
+            Response::Ack(AckStatus::BankInvalid) => bail!(CrispyError::Usage(format!(
+                "Bank {} cannot be read (banks are 0 (A) or 1 (B))",
+                self.bank
+            ))),
+            Response::Ack(status) => bail!(CrispyError::rejected("ReadBank", status)),
+            _ => bail!(CrispyError::unexpected(&response)),
+        }
+
+        self.offset += self.data.len() as u32;
+        Ok(Some((offset, &self.data)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    use crate::error::{exit_code, EXIT_PROTOCOL};
+
+    /// Answers commands from a script and records the reads asked for.
+    struct ScriptedDevice {
+        responses: VecDeque<Response>,
+        /// `(offset, len)` of each `ReadBank` sent.
+        reads: Vec<(u32, u32)>,
+        /// Timeout each command was sent with.
+        timeouts: Vec<Option<Duration>>,
+    }
+
+    impl ScriptedDevice {
+        fn new(responses: impl IntoIterator<Item = Response>) -> Self {
+            Self {
+                responses: responses.into_iter().collect(),
+                reads: Vec::new(),
+                timeouts: Vec::new(),
+            }
+        }
+    }
+
+    impl CommandChannel for ScriptedDevice {
+        fn send_recv(&mut self, cmd: &Command, timeout: Option<Duration>) -> Result<Response> {
+            self.timeouts.push(timeout);
+            match cmd {
+                Command::ReadBank { offset, len, .. } => self.reads.push((*offset, *len)),
+                other => panic!("unexpected command {:?}", other),
+            }
+            self.responses
+                .pop_front()
+                .ok_or_else(|| anyhow::anyhow!("no response scripted"))
+        }
+    }
+
+    fn bank_data(bank: u8, offset: u32, data: &[u8]) -> Response {
+        Response::BankData {
+            bank,
+            offset,
+            data: data.to_vec(),
+        }
+    }
+
+    /// Every chunk the reader yields, as `(offset, bytes)`.
+    fn read_all(reader: &mut BankReader<'_, ScriptedDevice>) -> Result<Vec<(u32, Vec<u8>)>> {
+        let mut chunks = Vec::new();
+        while let Some((offset, data)) = reader.next_chunk()? {
+            chunks.push((offset, data.to_vec()));
+        }
+        Ok(chunks)
+    }
+
+    #[test]
+    fn reads_up_to_size_and_stops() {
+        let mut device = ScriptedDevice::new([
+            bank_data(1, 0, &[1; 4]),
+            bank_data(1, 4, &[2; 4]),
+            bank_data(1, 8, &[3; 2]),
+        ]);
+        let mut reader = BankReader::new(&mut device, 1, 10, 4, None);
+        let chunks = read_all(&mut reader).unwrap();
+        assert_eq!(reader.offset(), 10);
+        assert!(reader.next_chunk().unwrap().is_none());
+
+        assert_eq!(chunks, [(0, vec![1; 4]), (4, vec![2; 4]), (8, vec![3; 2])]);
+        // The last read asks only for what is left.
+        assert_eq!(device.reads, [(0, 4), (4, 4), (8, 2)]);
+    }
+
+    #[test]
+    fn every_read_waits_for_the_readers_timeout() {
+        let mut device = ScriptedDevice::new([bank_data(0, 0, &[1; 4]), bank_data(0, 4, &[2; 4])]);
+        let timeout = Some(Duration::from_secs(90));
+        let mut reader = BankReader::new(&mut device, 0, 8, 4, timeout);
+        read_all(&mut reader).unwrap();
+
+        assert_eq!(device.timeouts, [timeout; 2]);
+    }
+
+    #[test]
+    fn short_read_continues_where_it_ended() {
+        let mut device = ScriptedDevice::new([
+            bank_data(0, 0, &[1; 3]),
+            bank_data(0, 3, &[2; 4]),
+            bank_data(0, 7, &[3; 1]),
+        ]);
+        let mut reader = BankReader::new(&mut device, 0, 8, 4, None);
+        let chunks = read_all(&mut reader).unwrap();
+
+        assert_eq!(chunks, [(0, vec![1; 3]), (3, vec![2; 4]), (7, vec![3; 1])]);
+        assert_eq!(device.reads, [(0, 4), (3, 4), (7, 1)]);
+    }
+
+    #[test]
+    fn empty_read_before_size_fails() {
+        let mut device = ScriptedDevice::new([bank_data(0, 0, &[1; 4]), bank_data(0, 4, &[])]);
+        let mut reader = BankReader::new(&mut device, 0, 8, 4, None);
+        assert!(reader.next_chunk().unwrap().is_some());
+
+        let err = reader.next_chunk().unwrap_err();
+        assert_eq!(exit_code(&err), EXIT_PROTOCOL);
+        assert!(err.to_string().contains("no data at offset 4"), "{}", err);
+    }
+
+    #[test]
+    fn oversized_read_fails() {
+        let mut device = ScriptedDevice::new([bank_data(0, 0, &[1; 5])]);
+        let mut reader = BankReader::new(&mut device, 0, 8, 4, None);
+
+        let err = reader.next_chunk().unwrap_err();
+        assert_eq!(exit_code(&err), EXIT_PROTOCOL);
+        assert!(
+            err.to_string().contains("5 bytes at offset 0, 4 asked for"),
+            "{}",
+            err
+        );
+        assert_eq!(reader.offset(), 0);
+    }
+
+    #[test]
+    fn data_for_another_offset_or_bank_fails() {
+        for stale in [bank_data(0, 0, &[1; 4]), bank_data(1, 4, &[1; 4])] {
+            let mut device = ScriptedDevice::new([bank_data(0, 0, &[1; 4]), stale]);
+            let mut reader = BankReader::new(&mut device, 0, 8, 4, None);
+            assert!(reader.next_chunk().unwrap().is_some());
+
+            let err = reader.next_chunk().unwrap_err();
+            assert_eq!(exit_code(&err), EXIT_PROTOCOL);
+            assert_eq!(reader.offset(), 4);
+        }
+    }
+
+    #[test]
+    fn refused_read_is_classified() {
+        let mut device = ScriptedDevice::new([Response::Ack(AckStatus::BankInvalid)]);
+        let err = BankReader::new(&mut device, 5, 8, 4, None)
+            .next_chunk()
+            .unwrap_err();
+        assert_eq!(exit_code(&err), crate::error::EXIT_USAGE);
+    }
+}
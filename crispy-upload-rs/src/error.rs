@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Failure classes and their process exit codes, so scripts can tell a
+//! missing device from a CRC failure without parsing messages.
+
+use std::fmt;
+
+use crispy_common::protocol::{AckStatus, Response};
+
+/// Exit code of failures that fit no class below.
+pub const EXIT_OTHER: u8 = 1;
+pub const EXIT_PORT: u8 = 2;
+pub const EXIT_PROTOCOL: u8 = 3;
+pub const EXIT_VERIFY: u8 = 4;
+pub const EXIT_FILE: u8 = 5;
+pub const EXIT_USAGE: u8 = 6;
+
+/// A classified failure. The message is what the user sees.
+#[derive(Debug)]
+pub enum CrispyError {
+    /// The serial port could not be opened, or failed mid-session.
+    Port(String),
+    /// The device rejected a command, answered unexpectedly or not at all.
+    Protocol(String),
+    /// A CRC or verification check failed.
+    Verify(String),
+    /// Bad arguments, or an input file that fails local checks.
+    Usage(String),
+}
+
+impl CrispyError {
+    /// The device answered `command` with a non-`Ok` status.
+    pub fn rejected(command: &str, status: AckStatus) -> Self {
+        let message = format!("{} failed: {:?}", command, status);
+        match status {
+            AckStatus::CrcError | AckStatus::FlashError => Self::Verify(message),
+            _ => Self::Protocol(message),
+        }
+    }
+
+    /// The device answered with a response of the wrong kind.
+    pub fn unexpected(response: &Response) -> Self {
+        Self::Protocol(format!("Unexpected response: {:?}", response))
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Port(_) => EXIT_PORT,
+            Self::Protocol(_) => EXIT_PROTOCOL,
+            Self::Verify(_) => EXIT_VERIFY,
+            Self::Usage(_) => EXIT_USAGE,
+        }
+    }
+}
+
+impl fmt::Display for CrispyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Port(message)
+            | Self::Protocol(message)
+            | Self::Verify(message)
+            | Self::Usage(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for CrispyError {}
+
+/// Exit code for `err`: the first classified cause in its chain wins.
+///
+/// Serial port errors count as `EXIT_PORT` and other I/O errors (reading the
+/// firmware file, writing a UF2) as `EXIT_FILE`.
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    for cause in err.chain() {
+        if let Some(err) = cause.downcast_ref::<CrispyError>() {
+            return err.exit_code();
+        }
+        if cause.is::<serialport::Error>() {
+            return EXIT_PORT;
+        }
+        if cause.is::<std::io::Error>() {
+            return EXIT_FILE;
+        }
+    }
+    EXIT_OTHER
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context};
+
+    #[test]
+    fn each_class_has_its_exit_code() {
+        let cases = [
+            (CrispyError::Port("p".into()), EXIT_PORT),
+            (CrispyError::Protocol("p".into()), EXIT_PROTOCOL),
+            (CrispyError::Verify("v".into()), EXIT_VERIFY),
+            (CrispyError::Usage("u".into()), EXIT_USAGE),
+        ];
+        for (err, code) in cases {
+            assert_eq!(err.exit_code(), code);
+            assert_eq!(exit_code(&anyhow::Error::new(err)), code);
+        }
+    }
+
+    #[test]
+    fn unclassified_errors_exit_other() {
+        assert_eq!(exit_code(&anyhow!("something else")), EXIT_OTHER);
+    }
+
+    #[test]
+    fn port_and_file_errors_are_classified() {
+        let port = serialport::Error::new(serialport::ErrorKind::NoDevice, "gone");
+        assert_eq!(exit_code(&anyhow::Error::new(port)), EXIT_PORT);
+        let file = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        assert_eq!(exit_code(&anyhow::Error::new(file)), EXIT_FILE);
+    }
+
+    #[test]
+    fn context_keeps_the_class() {
+        let err = Err::<(), _>(CrispyError::Verify("crc".into()))
+            .context("verify failed")
+            .unwrap_err();
+        assert_eq!(exit_code(&err), EXIT_VERIFY);
+    }
+
+    #[test]
+    fn rejections_split_verify_from_protocol() {
+        assert_eq!(
+            CrispyError::rejected("x", AckStatus::CrcError).exit_code(),
+            EXIT_VERIFY
+        );
+        assert_eq!(
+            CrispyError::rejected("x", AckStatus::BadState).exit_code(),
+            EXIT_PROTOCOL
+        );
+        assert_eq!(
+            CrispyError::unexpected(&Response::Ack(AckStatus::Ok)).exit_code(),
+            EXIT_PROTOCOL
+        );
+    }
+}
@@ -0,0 +1,219 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! gzip (RFC 1952) and zlib (RFC 1950) decompression for
+//! `upload --input-format gzip`, on top of `flate2`.
+
+use std::fmt;
+use std::io::{self, Read};
+
+use flate2::read::{MultiGzDecoder, ZlibDecoder};
+
+/// Why a compressed file could not be decoded.
+#[derive(Debug)]
+pub enum InflateError {
+    /// Neither a gzip nor a zlib header.
+    UnknownFormat,
+    /// The stream ends before its last block or its trailer.
+    Truncated,
+    /// Corrupt DEFLATE data, a bad wrapper field, or a trailer checksum or
+    /// length that does not match the output.
+    Corrupt(io::Error),
+    /// The output would exceed the caller's limit.
+    TooLarge { limit: usize },
+}
+
+impl fmt::Display for InflateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownFormat => write!(f, "not a gzip or zlib file"),
+            Self::Truncated => write!(f, "compressed data ends early"),
+            Self::Corrupt(err) => write!(f, "corrupt compressed data ({})", err),
+            Self::TooLarge { limit } => write!(f, "decompresses to more than {} bytes", limit),
+        }
+    }
+}
+
+impl std::error::Error for InflateError {}
+
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+/// Compression method 8, the only one zlib defines.
+const METHOD_DEFLATE: u8 = 8;
+
+/// Decompress a gzip or zlib file, told apart by their headers, into at most
+/// `limit` bytes. Concatenated gzip members are decoded one after the other,
+/// as `gunzip` does; each member's CRC32 and length, or the zlib Adler-32,
+/// are checked.
+pub fn decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, InflateError> {
+    match data {
+        [a, b, ..] if [*a, *b] == GZIP_MAGIC => read_limited(MultiGzDecoder::new(data), limit),
+        [cmf, flg, ..]
+            if cmf & 0x0F == METHOD_DEFLATE
+                && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 =>
+        {
+            read_limited(ZlibDecoder::new(data), limit)
+        }
+        _ => Err(InflateError::UnknownFormat),
+    }
+}
+
+/// Read `decoder` to the end, failing as soon as it yields more than `limit`
+/// bytes.
+fn read_limited(decoder: impl Read, limit: usize) -> Result<Vec<u8>, InflateError> {
+    let mut out = Vec::new();
+    decoder
+        .take(limit as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::UnexpectedEof => InflateError::Truncated,
+            _ => InflateError::Corrupt(err),
+        })?;
+    if out.len() > limit {
+        return Err(InflateError::TooLarge { limit });
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+    use std::io::Write;
+
+    /// Compressible but not trivially so, to get dynamic Huffman blocks.
+    fn image(len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| (i * 7 % 251) as u8 ^ (i >> 9) as u8)
+            .collect()
+    }
+
+    fn gzip(data: &[u8], level: Compression) -> Vec<u8> {
+        let mut enc = GzEncoder::new(Vec::new(), level);
+        enc.write_all(data).unwrap();
+        enc.finish().unwrap()
+    }
+
+    fn zlib(data: &[u8], level: Compression) -> Vec<u8> {
+        let mut enc = ZlibEncoder::new(Vec::new(), level);
+        enc.write_all(data).unwrap();
+        enc.finish().unwrap()
+    }
+
+    #[test]
+    fn gzip_round_trips_at_every_level() {
+        let data = image(100_000);
+        // Level 0 emits stored blocks; short input at level 1 a fixed block.
+        for level in [
+            Compression::none(),
+            Compression::fast(),
+            Compression::best(),
+        ] {
+            assert_eq!(decompress(&gzip(&data, level), data.len()).unwrap(), data);
+        }
+        assert_eq!(
+            decompress(&gzip(b"abc", Compression::fast()), 3).unwrap(),
+            b"abc"
+        );
+    }
+
+    #[test]
+    fn zlib_round_trips_at_every_level() {
+        let data = image(100_000);
+        for level in [
+            Compression::none(),
+            Compression::fast(),
+            Compression::best(),
+        ] {
+            assert_eq!(decompress(&zlib(&data, level), data.len()).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn empty_stream_decodes_to_nothing() {
+        assert!(decompress(&gzip(b"", Compression::best()), 0)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn concatenated_gzip_members_are_joined() {
+        let mut file = gzip(b"first member, ", Compression::best());
+        file.extend(gzip(b"first member, second member", Compression::best()));
+        assert_eq!(
+            decompress(&file, 1024).unwrap(),
+            b"first member, first member, second member"
+        );
+    }
+
+    #[test]
+    fn truncated_input_is_rejected() {
+        let data = image(10_000);
+        for file in [
+            gzip(&data, Compression::best()),
+            zlib(&data, Compression::best()),
+        ] {
+            for cut in [file.len() - 1, file.len() / 2, 4] {
+                assert!(
+                    decompress(&file[..cut], data.len()).is_err(),
+                    "cut at {}",
+                    cut
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bad_gzip_crc_is_rejected() {
+        let mut file = gzip(&image(1000), Compression::best());
+        let crc_at = file.len() - 8;
+        file[crc_at] ^= 1;
+        assert!(matches!(
+            decompress(&file, 1000),
+            Err(InflateError::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    fn bad_gzip_length_is_rejected() {
+        let mut file = gzip(&image(1000), Compression::best());
+        let isize_at = file.len() - 4;
+        file[isize_at] ^= 1;
+        assert!(matches!(
+            decompress(&file, 1000),
+            Err(InflateError::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    fn bad_zlib_adler_is_rejected() {
+        let mut file = zlib(&image(1000), Compression::best());
+        let last = file.len() - 1;
+        file[last] ^= 1;
+        assert!(matches!(
+            decompress(&file, 1000),
+            Err(InflateError::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    fn output_limit_is_enforced() {
+        let data = image(4096);
+        let file = gzip(&data, Compression::best());
+        assert_eq!(decompress(&file, 4096).unwrap(), data);
+        assert!(matches!(
+            decompress(&file, 4095),
+            Err(InflateError::TooLarge { limit: 4095 })
+        ));
+    }
+
+    #[test]
+    fn unknown_format_is_rejected() {
+        for file in [&b""[..], b"\x1f", b"PK\x03\x04", &image(64)] {
+            assert!(matches!(
+                decompress(file, 1024),
+                Err(InflateError::UnknownFormat)
+            ));
+        }
+    }
+}
@@ -0,0 +1,10 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2026 ADNT Sarl <info@adnt.io>
+
+//! Library side of `crispy-upload`: the serial transport, its error classes
+//! and the device operations (`device`), for tools that drive
+//! crispy-bootloader without the CLI.
+
+pub mod device;
+pub mod error;
+pub mod transport;
@@ -5,17 +5,36 @@
 //!
 //! Usage:
 //!   crispy-upload --port /dev/ttyACM0 status
-//!   crispy-upload --port /dev/ttyACM0 upload firmware.bin --bank 0 --fw-version 1
+//!   crispy-upload --port /dev/ttyACM0 upload firmware.bin --bank 0 --fw-version 1.0.0
 //!   crispy-upload --port /dev/ttyACM0 reboot
 
 mod cli;
 mod commands;
-mod transport;
+mod inflate;
+
+use crispy_upload::{device, error, transport};
+
+use std::process::ExitCode;
 
-use anyhow::Result;
 use clap::Parser;
 
-fn main() -> Result<()> {
-    let args = cli::Cli::parse();
-    cli::run(args)
+fn main() -> ExitCode {
+    let args = match cli::Cli::try_parse() {
+        Ok(args) => args,
+        // Usage errors exit with EXIT_USAGE instead of clap's 2, which is
+        // taken by port failures. --help and --version still exit 0.
+        Err(err) if err.use_stderr() => {
+            let _ = err.print();
+            return ExitCode::from(error::EXIT_USAGE);
+        }
+        Err(err) => err.exit(),
+    };
+
+    match cli::run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            ExitCode::from(error::exit_code(&err))
+        }
+    }
 }
@@ -3,41 +3,167 @@
 
 //! Serial transport layer for bootloader communication.
 
-use anyhow::{bail, Context, Result};
-use serialport::SerialPort;
+use anyhow::{bail, Result};
+use serialport::{ClearBuffer, DataBits, Parity, SerialPort, StopBits};
 use std::io::{Read, Write};
+use std::thread;
 use std::time::Duration;
 
-use crispy_common::protocol::{Command, Response};
+use crispy_common::protocol::{Command, ProgressPhase, Response, MAX_COMMAND_FRAME_SIZE};
+
+use crate::error::CrispyError;
 
 /// Default timeout for serial operations in milliseconds.
 pub const DEFAULT_TIMEOUT_MS: u64 = 5000;
 
-/// USB CDC transport for communicating with the bootloader.
+/// Default baud rate, matching the bootloader's `CRISPY_UART_BAUD` default.
+pub const DEFAULT_BAUD: u32 = 115200;
+
+/// How long `--reset-on-open` holds DTR asserted.
+const RESET_PULSE_MS: u64 = 100;
+
+/// How long `--reset-on-open` waits after the pulse for the bootloader to
+/// start listening; anything the board printed meanwhile is discarded.
+const RESET_SETTLE_MS: u64 = 500;
+
+/// Timeout for queries the bootloader answers straight from RAM.
+pub const SHORT_TIMEOUT_MS: u64 = 1000;
+
+/// Timeout for commands that erase or checksum a whole bank before answering.
+pub const LONG_TIMEOUT_MS: u64 = 30_000;
+
+/// Frames that do not answer the command sent (see `Response::answers`)
+/// skipped before one is returned anyway: a late response to an earlier
+/// command, and the bootloader's resend of it if it was cut short.
+const MAX_STALE_RESPONSES: usize = 2;
+
+/// Default response timeout for `cmd`.
+///
+/// Commands that stream `Progress` only need the timeout between frames;
+/// the long timeout covers bootloaders that predate progress reporting.
+pub fn command_timeout(cmd: &Command) -> Duration {
+    let ms = match cmd {
+        Command::Ping { .. }
+        | Command::GetStatus
+        | Command::GetWearStats
+        | Command::GetBankInfo { .. }
+        | Command::GetBuildInfo
+        | Command::GetTransportStats
+        | Command::GetTelemetry
+        | Command::GetFlashMap
+        | Command::GetErrorLog
+        | Command::ConfigGet { .. }
+        | Command::GetResetReason
+        | Command::ReadBank { .. }
+        | Command::StreamLogs { .. }
+        | Command::AbortUpdate => SHORT_TIMEOUT_MS,
+        Command::StartUpdate { .. }
+        | Command::FinishUpdate
+        | Command::FinishUpdateNoActivate
+        | Command::CopyBank { .. }
+        | Command::VerifyBank { .. }
+        | Command::CompareBanks
+        | Command::WriteGolden { .. }
+        | Command::StartBootloaderUpdate { .. }
+        | Command::WipeAll
+        | Command::FlashSelfTest => LONG_TIMEOUT_MS,
+        Command::DataBlock { .. }
+        | Command::Reboot
+        | Command::SetActiveBank { .. }
+        | Command::EnterBootrom
+        | Command::ConfirmBoot
+        | Command::EnableAntiRollback
+        | Command::SetBankLock { .. }
+        | Command::SetBootPolicy { .. }
+        | Command::ConfigSet { .. } => DEFAULT_TIMEOUT_MS,
+    };
+    Duration::from_millis(ms)
+}
+
+/// Serial line settings a `Transport` opens its port with.
+///
+/// Only a UART link uses the rate and framing; USB CDC ignores them, though
+/// some host stacks still key behavior off the nominal baud rate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineSettings {
+    pub baud: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    /// DTR level set once the port is open; `None` keeps the driver's.
+    pub dtr: Option<bool>,
+    /// RTS level set once the port is open; `None` keeps the driver's.
+    pub rts: Option<bool>,
+    /// Pulse DTR after opening, resetting boards that wire it to RUN.
+    pub reset_on_open: bool,
+}
+
+/// Serial transport for communicating with the bootloader.
+///
+/// The same framing runs over the bootloader's USB CDC port and over a plain
+/// serial device wired to its UART (`uart-transport` builds). The baud rate
+/// only matters for the latter; USB CDC ignores it.
 pub struct Transport {
     port: Box<dyn SerialPort>,
     rx_buf: Vec<u8>,
+    /// Response timeout overriding `command_timeout` for every command.
+    timeout: Option<Duration>,
 }
 
 impl Transport {
     /// Create a new transport connection to the specified serial port.
-    pub fn new(port_name: &str) -> Result<Self> {
-        Self::with_timeout(port_name, DEFAULT_TIMEOUT_MS)
+    ///
+    /// Each command waits for its response for its `command_timeout`.
+    pub fn new(port_name: &str, line: &LineSettings) -> Result<Self> {
+        Self::open(port_name, line, None)
+    }
+
+    /// Create a new transport connection that waits `timeout_ms` for every
+    /// response.
+    pub fn with_timeout(port_name: &str, line: &LineSettings, timeout_ms: u64) -> Result<Self> {
+        Self::open(port_name, line, Some(Duration::from_millis(timeout_ms)))
     }
 
-    /// Create a new transport connection with a custom timeout.
-    pub fn with_timeout(port_name: &str, timeout_ms: u64) -> Result<Self> {
-        let port = serialport::new(port_name, 115200)
-            .timeout(Duration::from_millis(timeout_ms))
+    fn open(port_name: &str, line: &LineSettings, timeout: Option<Duration>) -> Result<Self> {
+        let mut port = serialport::new(port_name, line.baud)
+            .data_bits(line.data_bits)
+            .parity(line.parity)
+            .stop_bits(line.stop_bits)
+            .timeout(timeout.unwrap_or(Duration::from_millis(DEFAULT_TIMEOUT_MS)))
             .open()
-            .with_context(|| format!("Failed to open serial port {}", port_name))?;
+            .map_err(|e| {
+                CrispyError::Port(format!("Failed to open serial port {}: {}", port_name, e))
+            })?;
+        Self::set_control_lines(port.as_mut(), line)
+            .map_err(|e| CrispyError::Port(format!("Failed to set {}: {}", port_name, e)))?;
 
         Ok(Self {
             port,
             rx_buf: Vec::with_capacity(4096),
+            timeout,
         })
     }
 
+    /// Apply the DTR/RTS levels of `line`, then its reset pulse: DTR
+    /// asserted for `RESET_PULSE_MS` and released, and whatever arrived
+    /// before the bootloader settled discarded.
+    fn set_control_lines(port: &mut dyn SerialPort, line: &LineSettings) -> serialport::Result<()> {
+        if let Some(level) = line.dtr {
+            port.write_data_terminal_ready(level)?;
+        }
+        if let Some(level) = line.rts {
+            port.write_request_to_send(level)?;
+        }
+        if line.reset_on_open {
+            port.write_data_terminal_ready(true)?;
+            thread::sleep(Duration::from_millis(RESET_PULSE_MS));
+            port.write_data_terminal_ready(false)?;
+            thread::sleep(Duration::from_millis(RESET_SETTLE_MS));
+            port.clear(ClearBuffer::Input)?;
+        }
+        Ok(())
+    }
+
     /// Get the port name.
     pub fn port_name(&self) -> String {
         self.port.name().unwrap_or_else(|| "?".to_string())
@@ -45,18 +171,20 @@ impl Transport {
 
     /// Send a command to the bootloader.
     pub fn send(&mut self, cmd: &Command) -> Result<()> {
-        let mut buf = [0u8; 2048];
+        let mut buf = [0u8; MAX_COMMAND_FRAME_SIZE + 1];
         let encoded = postcard::to_slice_cobs(cmd, &mut buf)
-            .map_err(|e| anyhow::anyhow!("Failed to serialize command: {}", e))?;
+            .map_err(|e| CrispyError::Protocol(format!("Failed to serialize command: {}", e)))?;
         self.port
             .write_all(encoded)
-            .map_err(|e| anyhow::anyhow!("Failed to write to serial port: {}", e))?;
-        self.port.flush()?;
+            .and_then(|()| self.port.flush())
+            .map_err(|e| CrispyError::Port(format!("Failed to write to serial port: {}", e)))?;
         Ok(())
     }
 
-    /// Receive a response from the bootloader.
-    pub fn receive(&mut self) -> Result<Response> {
+    /// Read bytes up to and including the next frame delimiter into `rx_buf`.
+    ///
+    /// Returns `false` if the read timed out before the frame started.
+    fn read_frame(&mut self) -> Result<bool> {
         self.rx_buf.clear();
         let mut byte = [0u8; 1];
 
@@ -71,20 +199,28 @@ impl Transport {
                 }
                 Ok(_) => continue,
                 Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
-                    bail!("Timeout waiting for response");
+                    if self.rx_buf.is_empty() {
+                        return Ok(false);
+                    }
+                    bail!(CrispyError::Protocol("Timeout waiting for response".into()));
                 }
-                Err(e) => bail!("Serial read error: {}", e),
+                Err(e) => bail!(CrispyError::Port(format!("Serial read error: {}", e))),
             }
         }
+        Ok(true)
+    }
 
+    /// Decode the frame in `rx_buf` as a response from the bootloader.
+    fn decode_frame(&mut self) -> Result<Response> {
         // Use postcard's COBS decoder for consistency with bootloader
         postcard::from_bytes_cobs(&mut self.rx_buf).map_err(|e| {
-            anyhow::anyhow!(
+            CrispyError::Protocol(format!(
                 "Failed to deserialize response: {} (raw {} bytes: {:02x?})",
                 e,
                 self.rx_buf.len(),
                 &self.rx_buf[..self.rx_buf.len().min(32)]
-            )
+            ))
+            .into()
         })
     }
 
@@ -96,29 +232,97 @@ impl Transport {
         let _ = self.port.set_timeout(old_timeout);
     }
 
-    /// Send a command and wait for the response.
-    pub fn send_recv(&mut self, cmd: &Command) -> Result<Response> {
-        self.drain_rx();
-        self.send(cmd)?;
-        self.receive()
+    /// Wait for the next frame the bootloader sends on its own, such as `Log`
+    /// while streaming; `None` if nothing arrived within the timeout.
+    pub fn recv(&mut self) -> Result<Option<Response>> {
+        if !self.read_frame()? {
+            return Ok(None);
+        }
+        self.decode_frame().map(Some)
     }
 
-    /// Send a command and wait for the response with a custom timeout.
-    pub fn send_recv_timeout(&mut self, cmd: &Command, timeout_ms: u64) -> Result<Response> {
-        // Save current timeout
-        let old_timeout = self.port.timeout();
+    /// Send a command and wait for the response, for `timeout` if given.
+    ///
+    /// Intermediate `Progress` and `Log` frames are skipped.
+    pub fn send_recv(&mut self, cmd: &Command, timeout: Option<Duration>) -> Result<Response> {
+        self.send_recv_progress(cmd, timeout, |_, _| {})
+    }
 
-        // Set new timeout
+    /// Send a command and wait for its final response, reporting `Progress` frames.
+    ///
+    /// The read timeout applies between frames, so long device operations
+    /// only need to keep streaming progress to stay alive. It is `timeout`
+    /// if given, else the one the transport was opened `with_timeout`, else
+    /// the command's `command_timeout`.
+    /// A frame that fails to decode is skipped: the bootloader resends a
+    /// response it could only partly write. The decode error is reported if
+    /// nothing valid follows. `Log` frames from a bootloader left streaming
+    /// are skipped too.
+    ///
+    /// Up to `MAX_STALE_RESPONSES` frames that cannot answer `cmd`, such as
+    /// a `Status` arriving late from an earlier `GetStatus`, are skipped as
+    /// well. Past that, or if nothing else arrives in time, the last one is
+    /// returned for the caller to report as unexpected.
+    pub fn send_recv_progress<F>(
+        &mut self,
+        cmd: &Command,
+        timeout: Option<Duration>,
+        mut on_progress: F,
+    ) -> Result<Response>
+    where
+        F: FnMut(ProgressPhase, u8),
+    {
+        let timeout = timeout
+            .or(self.timeout)
+            .unwrap_or_else(|| command_timeout(cmd));
+        self.drain_rx();
         self.port
-            .set_timeout(Duration::from_millis(timeout_ms))
-            .map_err(|e| anyhow::anyhow!("Failed to set timeout: {}", e))?;
-
-        // Send and receive
-        let result = self.send_recv(cmd);
+            .set_timeout(timeout)
+            .map_err(|e| CrispyError::Port(format!("Failed to set serial timeout: {}", e)))?;
+        self.send(cmd)?;
+        let mut dropped: Option<anyhow::Error> = None;
+        let mut skipped = 0;
+        let mut stale: Option<Response> = None;
+        loop {
+            match self.read_frame() {
+                Ok(true) => {}
+                Ok(false) => {
+                    if let Some(response) = stale {
+                        return Ok(response);
+                    }
+                    return Err(dropped.unwrap_or_else(|| {
+                        CrispyError::Protocol("Timeout waiting for response".into()).into()
+                    }));
+                }
+                Err(e) => return Err(dropped.unwrap_or(e)),
+            }
+            match self.decode_frame() {
+                Ok(Response::Progress { phase, percent }) => on_progress(phase, percent),
+                Ok(Response::Log { .. }) => {}
+                Ok(response) if response.answers(cmd) => return Ok(response),
+                Ok(response) if skipped < MAX_STALE_RESPONSES => {
+                    skipped += 1;
+                    stale = Some(response);
+                }
+                Ok(response) => return Ok(response),
+                Err(e) => dropped = Some(e.context("Dropped a truncated response frame")),
+            }
+        }
+    }
+}
 
-        // Restore old timeout
-        let _ = self.port.set_timeout(old_timeout);
+/// A link that answers one command with one final response.
+///
+/// [`Transport`] is the real one; device operations written against this
+/// trait can be tested with a scripted device.
+pub trait CommandChannel {
+    /// Send `cmd` and return the response that answers it, waiting for
+    /// `timeout` if given.
+    fn send_recv(&mut self, cmd: &Command, timeout: Option<Duration>) -> Result<Response>;
+}
 
-        result
+impl CommandChannel for Transport {
+    fn send_recv(&mut self, cmd: &Command, timeout: Option<Duration>) -> Result<Response> {
+        Transport::send_recv(self, cmd, timeout)
     }
 }
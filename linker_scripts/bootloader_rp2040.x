@@ -4,19 +4,25 @@
 *
 * RAM layout (256KB):
 *   0x20000000 - 0x20030000: Firmware code (192KB, copied by bootloader)
-*   0x20030000 - 0x2003C000: Firmware data/BSS/stack (48KB)
+*   0x20030000 - 0x2003BFD0: Firmware data/BSS/stack (48KB - 48B)
+*   0x2003BFD0 - 0x2003C000: BootInfo mailbox, update flag, warm-boot marker
 *   0x2003C000 - 0x20040000: Bootloader data/BSS/stack (16KB)
 */
 
 /* =========================== MEMORY LAYOUT CONFIG =========================== */
 /* Modify these values to change memory allocation (must be 4KB sector-aligned) */
+/* The bootloader's flash-4m/-8m/-16m features rewrite __flash_size and       */
+/* __fw_bank_size (see crispy-bootloader/build.rs and flash_layout.rs).        */
 
 __flash_base       = 0x10000000;
+__flash_size       = 2M;
 __boot2_size       = 0x100;      /* 256B - fixed by RP2040 */
 __bootloader_size  = 0x10000;    /* 64KB - adjust as needed */
 __fw_bank_size     = 0xC0000;    /* 768KB per firmware bank */
 __boot_data_size   = 0x1000;     /* 4KB for boot metadata */
 __fw_copy_size     = 0x30000;    /* 192KB copied to RAM */
+__fw_gold_size     = 0x6E000;    /* 440KB golden bank (golden-bank feature) */
+__data_size        = 0x3B000;    /* 236KB data partition, last sector DataInfo */
 
 /* Bootloader RAM (top of SRAM) */
 __bootloader_ram   = 0x2003C000;
@@ -35,6 +41,13 @@ __fw_ram_end       = 0x20042000;
 __fw_a_entry       = __flash_base + __bootloader_size;
 __fw_b_entry       = __fw_a_entry + __fw_bank_size;
 __boot_data_addr   = __fw_b_entry + __fw_bank_size;
+__fw_gold_addr     = __boot_data_addr + 2 * __boot_data_size; /* after the wear stats sector */
+__data_addr        = __fw_gold_addr + __fw_copy_size; /* golden images fit the RAM buffer */
+
+ASSERT(__data_addr + __data_size + 3 * __boot_data_size == __fw_gold_addr + __fw_gold_size, "data partition must end at the BootData mirror, config and golden info sectors");
+
+ASSERT(__fw_gold_addr + __fw_gold_size <= __flash_base + __flash_size, "golden bank exceeds __flash_size");
+ASSERT(__bootloader_ram % 8 == 0 && __bootloader_ram_size % 8 == 0, "bootloader RAM is scrubbed in 8-byte steps");
 
 MEMORY {
     BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
@@ -76,10 +89,22 @@ SECTIONS {
 } INSERT AFTER .text;
 
 /* Export symbols for bootloader code */
+PROVIDE(__flash_base = __flash_base);
+PROVIDE(__flash_size = __flash_size);
+PROVIDE(__boot2_size = __boot2_size);
+PROVIDE(__bootloader_size = __bootloader_size);
+PROVIDE(__boot_data_size = __boot_data_size);
 PROVIDE(__fw_a_entry = __fw_a_entry);
 PROVIDE(__fw_b_entry = __fw_b_entry);
+PROVIDE(__fw_bank_size = __fw_bank_size);
 PROVIDE(__boot_data_addr = __boot_data_addr);
+PROVIDE(__fw_gold_addr = __fw_gold_addr);
+PROVIDE(__fw_gold_size = __fw_gold_size);
+PROVIDE(__data_addr = __data_addr);
+PROVIDE(__data_size = __data_size);
 PROVIDE(__fw_ram_base = __fw_ram_base);
 PROVIDE(__fw_copy_size = __fw_copy_size);
 PROVIDE(__fw_ram_start = __fw_ram_start);
 PROVIDE(__fw_ram_end = __fw_ram_end);
+PROVIDE(__bootloader_ram = __bootloader_ram);
+PROVIDE(__bootloader_ram_size = __bootloader_ram_size);
@@ -9,10 +9,16 @@
 *
 * RAM layout:
 *   0x20000000 - 0x20030000: FLASH region (192KB) — code, rodata, data LMA
-*   0x20030000 - 0x2003C000: RAM region (48KB) — data VMA, BSS, stack
+*   0x20030000 - 0x2003BFD0: RAM region (48KB - 48B) — data VMA, BSS, stack
+*   0x2003BFD0 - 0x2003BFF0: BootInfo mailbox (written by the bootloader)
+*   0x2003BFF0 - 0x2003BFF4: update flag
+*   0x2003BFF4 - 0x2003C000: warm-boot marker (written by the bootloader)
+*
+* The top 48 bytes are kept out of RAM so the stack cannot overwrite the
+* mailbox before the firmware reads it.
 */
 
 MEMORY {
     FLASH : ORIGIN = 0x20000000, LENGTH = 192K
-    RAM   : ORIGIN = 0x20030000, LENGTH = 48K
+    RAM   : ORIGIN = 0x20030000, LENGTH = 48K - 48
 }
@@ -19,10 +19,12 @@ from crispy_board import (
     PID_BOOTLOADER,
     PID_FW_RUST,
     enter_update_mode_via_swd,
+    erase_bank_a_vectors,
     erase_boot_data,
     find_firmware_port,
     flash_uf2,
     project_root_from,
+    reset_device,
     run_crispy_upload,
     run_make,
 )
@@ -113,8 +115,10 @@ class TestDeployment:
         _upload(port, "upload", str(_root() / FW_RS_BIN), "--bank", "0", "--version", "1")
 
     def test_05_upload_fw_cpp_bank_b(self):
+        # Staged: bank B is written and verified but bank A stays active
         port = self._find_bootloader_port()
-        _upload(port, "upload", str(_root() / FW_CPP_BIN), "--bank", "1", "--version", "1")
+        _upload(port, "upload", str(_root() / FW_CPP_BIN), "--bank", "1", "--version", "1",
+                "--after", "none")
 
     def test_06_verify_status_after_upload(self):
         port = self._find_bootloader_port()
@@ -123,8 +127,9 @@ class TestDeployment:
 
         expected_version = (_root() / "VERSION").read_text().strip()
         assert f"bootloader:  {expected_version}" in low, f"Expected version in:\n{output}"
-        assert "version a:   1" in low, f"Expected Version A = 1 in:\n{output}"
-        assert "version b:   1" in low, f"Expected Version B = 1 in:\n{output}"
+        assert "version a:   0.0.1" in low, f"Expected Version A = 0.0.1 in:\n{output}"
+        assert "version b:   0.0.1" in low, f"Expected Version B = 0.0.1 in:\n{output}"
+        assert "active bank: 0" in low, f"Expected bank A still active in:\n{output}"
 
     def test_07_set_bank_a_and_reboot(self):
         port = self._find_bootloader_port()
@@ -173,7 +178,25 @@ class TestDeployment:
         port = _reboot_to_bootloader(TestDeployment.fw_cpp_port)
         _assert_update_mode(_upload(port, "status"))
 
-    def test_12_wipe_and_verify_update_mode(self):
+    def test_12_fallback_to_bank_b_when_bank_a_erased(self):
+        port = self._find_bootloader_port()
+        _upload(port, "set-bank", "0")
+        assert erase_bank_a_vectors(), "Failed to erase bank A via SWD"
+        assert reset_device(), "Failed to reset device"
+
+        # Bank A fails validation, so bank B (C++ firmware) must boot
+        time.sleep(3.0)
+        fw_port = find_firmware_port(pid=PID_BOOTLOADER, timeout=15.0)
+        time.sleep(1.0)
+        status_response = _serial_command(fw_port, "status")
+        assert "Bank: 1" in status_response, f"Expected 'Bank: 1', got:\n{status_response}"
+
+        port = _reboot_to_bootloader(fw_port)
+        output = _upload(port, "status").lower()
+        assert "active bank: 1" in output, f"Expected bank B active in:\n{output}"
+        assert "fallback:    yes" in output, f"Expected fallback reported in:\n{output}"
+
+    def test_13_wipe_and_verify_update_mode(self):
         port = self._find_bootloader_port()
         _upload(port, "wipe")
         _upload(port, "reboot")
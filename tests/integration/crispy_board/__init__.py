@@ -12,10 +12,12 @@ from crispy_board.constants import (  # noqa: F401
     BOOT2_ADDR,
     BOOT2_SIZE,
     BOOT_DATA_ADDR,
+    BOOT_DATA_MIRROR_ADDR,
     BOOT_DATA_SECTOR_SIZE,
     CHIP,
     DEFAULT_VID,
     EMBEDDED_TARGET,
+    FW_A_ADDR,
     PID_BOOTLOADER,
     PID_FW_RUST,
     RAM_UPDATE_FLAG_ADDR,
@@ -29,6 +31,7 @@ from crispy_board.discovery import (  # noqa: F401
 )
 from crispy_board.flash import (  # noqa: F401
     enter_update_mode_via_swd,
+    erase_bank_a_vectors,
     erase_boot_data,
     erase_flash,
     flash_elf,
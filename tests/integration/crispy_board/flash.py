@@ -10,8 +10,10 @@ from crispy_board.constants import (
     BOOT2_ADDR,
     BOOT2_SIZE,
     BOOT_DATA_ADDR,
+    BOOT_DATA_MIRROR_ADDR,
     BOOT_DATA_SECTOR_SIZE,
     CHIP,
+    FW_A_ADDR,
     RAM_UPDATE_FLAG_ADDR,
     RAM_UPDATE_MAGIC,
 )
@@ -40,11 +42,20 @@ def reset_device() -> bool:
 
 
 def erase_boot_data() -> bool:
-    result = download_binary(
-        b"\xFF" * BOOT_DATA_SECTOR_SIZE, BOOT_DATA_ADDR,
-    )
+    # Both copies: the bootloader restores BootData from an intact mirror
+    for addr in (BOOT_DATA_ADDR, BOOT_DATA_MIRROR_ADDR):
+        result = download_binary(b"\xFF" * BOOT_DATA_SECTOR_SIZE, addr)
+        if not result.success:
+            print(f"Failed to erase boot data: {result.output}")
+            return False
+    return True
+
+
+def erase_bank_a_vectors() -> bool:
+    """Erase the first sector of bank A, as left by an interrupted update."""
+    result = download_binary(b"\xFF" * BOOT_DATA_SECTOR_SIZE, FW_A_ADDR)
     if not result.success:
-        print(f"Failed to erase boot data: {result.output}")
+        print(f"Failed to erase bank A: {result.output}")
     return result.success
 
 
@@ -6,8 +6,12 @@ from crispy_protocol.protocol import AckStatus, Command
 
 
 def upload_firmware(transport, firmware_data: bytes, bank: int, version: int,
-                    chunk_size: int = 1024) -> None:
-    """Send StartUpdate + all DataBlock chunks + FinishUpdate, asserting OK on each."""
+                    chunk_size: int = 1024, activate: bool = True) -> None:
+    """Send StartUpdate + all DataBlock chunks + FinishUpdate, asserting OK on each.
+
+    With ``activate=False`` the image is finished with FinishUpdateNoActivate
+    and the active bank is left unchanged.
+    """
     size = len(firmware_data)
     checksum = crc32(firmware_data)
 
@@ -27,6 +31,6 @@ def upload_firmware(transport, firmware_data: bytes, bank: int, version: int,
         )
         offset += len(chunk)
 
-    transport.send(Command.finish_update())
+    transport.send(Command.finish_update() if activate else Command.finish_update_no_activate())
     resp = transport.receive()
     assert resp.status == AckStatus.OK, f"FinishUpdate failed: {resp.status}"